@@ -2,7 +2,8 @@
 //!
 //! Ported from Python `agent/loop.py`.
 
-use std::path::PathBuf;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
@@ -10,20 +11,126 @@ use std::time::Duration;
 use anyhow::Result;
 use serde_json::{json, Value};
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::{broadcast, oneshot, Mutex};
+use tokio::time::Instant;
 use tracing::{debug, error, info, warn};
 
+use crate::agent::compaction::{compact_messages, is_context_overflow, OVERFLOW_MESSAGE};
 use crate::agent::context::ContextBuilder;
+use crate::agent::workspaces::Workspaces;
+use crate::agent::builder::AgentLoopBuilder;
+use crate::agent::hooks::{EventHook, Hook, HookContext, HookRegistry, ToolDecision};
+use crate::audit::{AuditHook, AuditLog};
+use crate::agent::profiles::{allows_tool, load_profile_prompt, ProfileRouter};
+use crate::agent::roles::{is_internal, Role, Roles, ADMIN_COMMANDS};
+use crate::agent::progress::chat_message;
+use crate::agent::supervised::{approval_request, is_approval, needs_approval};
 use crate::agent::subagent::SubagentManager;
+use crate::agent::usage::{CostHook, UsageStore};
+use crate::agent::alerts::{Alert, AlertMonitor};
+use crate::agent::tools::politeness::Politeness;
+use crate::agent::tools::web::search_backend;
 use crate::agent::tools::{
-    CronScheduleTool, ExecTool, ListDirTool, MessageTool, ReadFileTool, SendCallback,
-    SpawnCallback, SpawnTool, ToolRegistry, WebFetchTool, WebSearchTool, WriteFileTool,
-    EditFileTool,
+    ChecklistTool, CrawlTool, CronScheduleTool, ExecTool, ListDirTool, MessageTool, ReadFileTool, RemindMeTool,
+    RestrictedPaths, SendCallback, SkillScriptTool, SpawnCallback, SpawnTool, SubagentsTool, Tool, ToolRegistry,
+    WebFetchTool, WebSearchTool, WriteFileTool, EditFileTool,
+};
+use crate::bus::events::{
+    AgentEvent, Attachment, InboundMessage, OutboundMessage, Priority, SystemEvent,
+};
+use crate::config::schema::{
+    AgentProfileConfig, AlertsConfig, Config, ContextConfig, RolesConfig, SubagentConfig,
+    ToolsConfig, WorkspaceConfig,
 };
-use crate::bus::events::{InboundMessage, OutboundMessage};
 use crate::cron::service::CronService;
-use crate::providers::base::LLMProvider;
+use crate::providers::base::{LLMProvider, LLMResponse, ToolCallRequest};
 use crate::session::manager::SessionManager;
 
+/// Minimum time between partial updates of a streamed reply, to stay within
+/// channel rate limits on message edits.
+const STREAM_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Session metadata key set when a turn stopped at the iteration limit, so
+/// that a following "continue" resumes the task.
+const UNFINISHED_TASK_KEY: &str = "unfinished_task";
+
+/// Instruction for the final, tool-less call when the iteration limit is hit.
+const ITERATION_LIMIT_PROMPT: &str = "You have used all the tool steps allowed for this \
+request. Do not call any tools. Briefly summarize what you have done so far and list the \
+steps that remain, then tell the user they can reply \"continue\" to let you keep going.";
+
+/// What the model is told when the user asks it to resume an unfinished task.
+const RESUME_PROMPT: &str = "continue (Resume the unfinished task from your previous reply, \
+starting with the remaining steps you listed.)";
+
+/// Prefix for a message injected into a running turn.
+const INJECTED_PREFIX: &str = "[New message from the user while you were working]";
+
+/// Agent events buffered per subscriber before slow subscribers lag.
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// A message submitted to the agent loop outside the bus, together with a
+/// channel on which the agent's reply is returned (used by cron jobs).
+///
+/// The reply is an error when the turn failed (e.g. the LLM call errored).
+/// With `deltas`, the text of each LLM call is sent there as it is
+/// generated, and the channel closes before the reply is sent.
+///
+/// With a `seed_history` metadata array of `role`/`content` messages, the
+/// turn runs on a one-off session holding those messages, which is not
+/// kept afterwards.
+pub struct DirectRequest {
+    pub msg: InboundMessage,
+    pub reply_tx: oneshot::Sender<Result<String>>,
+    pub deltas: Option<UnboundedSender<String>>,
+}
+
+/// Work item received by [`AgentLoop::run`].
+enum Incoming {
+    Bus(InboundMessage),
+    Direct(DirectRequest),
+}
+
+/// Index of the message to handle first: the highest priority, and among
+/// those the earliest.
+fn _most_urgent<'a>(messages: impl Iterator<Item = &'a InboundMessage>) -> Option<usize> {
+    let mut best: Option<(usize, Priority)> = None;
+    for (i, msg) in messages.enumerate() {
+        if best.is_none_or(|(_, priority)| msg.priority > priority) {
+            best = Some((i, msg.priority));
+        }
+    }
+    best.map(|(i, _)| i)
+}
+
+/// How a message is handled when it arrives in a chat whose previous
+/// message is still being processed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MidTurnMode {
+    /// Answer it after the current turn (the default).
+    #[default]
+    Queue,
+    /// Abandon the current turn and start again with both messages.
+    Restart,
+    /// Add it to the current turn as a steering note.
+    Inject,
+}
+
+impl MidTurnMode {
+    /// Parse a config value; unknown values fall back to queueing.
+    pub fn parse(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "queue" | "" => Self::Queue,
+            "restart" | "cancel" => Self::Restart,
+            "inject" => Self::Inject,
+            other => {
+                warn!("Unknown midTurnMessages mode '{}', queueing instead", other);
+                Self::Queue
+            }
+        }
+    }
+}
+
 /// The core agent loop.
 ///
 /// Consumes [`InboundMessage`]s from the bus, runs the LLM + tool loop, and
@@ -31,23 +138,62 @@ use crate::session::manager::SessionManager;
 pub struct AgentLoop {
     bus_inbound_rx: UnboundedReceiver<InboundMessage>,
     bus_outbound_tx: UnboundedSender<OutboundMessage>,
-    bus_inbound_tx: UnboundedSender<InboundMessage>,
     provider: Arc<dyn LLMProvider>,
-    workspace: PathBuf,
     model: String,
     max_iterations: u32,
-    context: ContextBuilder,
+    workspaces: Workspaces,
     sessions: SessionManager,
     tools: ToolRegistry,
+    /// Runs the scripts skills offer as tools.
+    skill_exec: Arc<ExecTool>,
+    /// Names of the tools registered from skills.
+    skill_tools: Vec<String>,
+    /// Skills generation the skill tools were registered for.
+    skill_generation: u64,
     subagents: Arc<SubagentManager>,
     /// Shared references to tools that need per-message context updates.
     message_tool: Arc<MessageTool>,
     spawn_tool: Arc<SpawnTool>,
     cron_tool: Option<Arc<CronScheduleTool>>,
+    reminder_tool: Option<Arc<RemindMeTool>>,
+    direct_rx: UnboundedReceiver<DirectRequest>,
+    direct_tx: UnboundedSender<DirectRequest>,
+    /// Publish partial replies while the LLM is generating.
+    streaming: bool,
+    /// Where the text of the direct request being handled goes as it is
+    /// generated.
+    turn_deltas: Option<UnboundedSender<String>>,
+    hooks: HookRegistry,
+    profiles: ProfileRouter,
+    roles: Roles,
+    alerts: AlertMonitor,
+    /// In supervised mode, how long to wait for approval of a tool call.
+    supervised: Option<Duration>,
+    /// Post each tool call in the chat the turn came from.
+    show_tool_calls: bool,
+    usage: Option<Arc<std::sync::Mutex<UsageStore>>>,
+    /// Wall-clock limit for a whole turn.
+    turn_timeout: Option<Duration>,
+    events: broadcast::Sender<AgentEvent>,
+    system_events: Option<broadcast::Sender<SystemEvent>>,
+    mid_turn: MidTurnMode,
+    /// Bus messages taken off the inbound queue, waiting their turn.
+    pending: VecDeque<InboundMessage>,
+    /// Direct requests taken off their queue, waiting their turn.
+    pending_direct: VecDeque<DirectRequest>,
     running: Arc<AtomicBool>,
 }
 
 impl AgentLoop {
+    /// Start building an agent using `provider` with its files in
+    /// `workspace`.
+    pub fn builder(
+        provider: Arc<dyn LLMProvider>,
+        workspace: impl Into<PathBuf>,
+    ) -> AgentLoopBuilder {
+        AgentLoopBuilder::new(provider, workspace)
+    }
+
     /// Create a new `AgentLoop`.
     #[allow(clippy::too_many_arguments)]
     pub fn new(
@@ -61,9 +207,10 @@ impl AgentLoop {
         brave_api_key: Option<String>,
         exec_timeout: u64,
         restrict_to_workspace: bool,
-        cron_service: Option<Arc<CronService>>,
+        cron_service: Option<Arc<Mutex<CronService>>>,
+        subagent_config: SubagentConfig,
     ) -> Self {
-        let context = ContextBuilder::new(&workspace);
+        let workspaces = Workspaces::new(&workspace);
         let sessions = SessionManager::new(&workspace);
 
         // Create the subagent manager.
@@ -75,6 +222,7 @@ impl AgentLoop {
             brave_api_key.clone(),
             exec_timeout,
             restrict_to_workspace,
+            subagent_config,
         ));
 
         // ---------------------------------------------------------------
@@ -89,17 +237,28 @@ impl AgentLoop {
         tools.register(Box::new(ListDirTool));
 
         // Shell.
-        tools.register(Box::new(ExecTool::new(
-            exec_timeout,
-            Some(workspace.to_string_lossy().to_string()),
-            None,
-            None,
-            restrict_to_workspace,
-        )));
+        let new_exec = || {
+            ExecTool::new(
+                exec_timeout,
+                Some(workspace.to_string_lossy().to_string()),
+                None,
+                None,
+                restrict_to_workspace,
+            )
+        };
+        tools.register(Box::new(new_exec()));
+        let skill_exec = Arc::new(new_exec());
 
         // Web.
         tools.register(Box::new(WebSearchTool::new(brave_api_key.clone(), 5)));
-        tools.register(Box::new(WebFetchTool::new(50_000)));
+        tools.register(Box::new(
+            WebFetchTool::new(50_000)
+                .with_politeness(subagents.fetch_politeness())
+                .with_downloads(workspace.join("downloads"), 25 * 1024 * 1024),
+        ));
+        tools.register(Box::new(CrawlTool::new(
+            WebFetchTool::new(50_000).with_politeness(subagents.fetch_politeness()),
+        )));
 
         // Message tool.
         let outbound_tx_clone = bus_outbound_tx.clone();
@@ -128,46 +287,116 @@ impl AgentLoop {
             });
         }
         tools.register(Box::new(SpawnToolProxy(spawn_tool.clone())));
+        tools.register(Box::new(SubagentsTool::new(subagents.clone())));
+
+        // Heartbeat checklist.
+        tools.register(Box::new(ChecklistTool::new(workspace.join("HEARTBEAT.md"))));
 
-        // Cron tool (optional).
+        // Cron and reminder tools (optional).
+        let reminder_tool = cron_service.as_ref().map(|svc| {
+            let rt = Arc::new(RemindMeTool::new(svc.clone()));
+            tools.register(Box::new(RemindMeToolProxy(rt.clone())));
+            rt
+        });
         let cron_tool = cron_service.map(|svc| {
             let ct = Arc::new(CronScheduleTool::new(svc));
             tools.register(Box::new(CronToolProxy(ct.clone())));
             ct
         });
 
-        Self {
+        let (direct_tx, direct_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        let mut hooks = HookRegistry::with_builtins();
+        hooks.register(Arc::new(EventHook::new(events.clone())));
+
+        let mut agent = Self {
             bus_inbound_rx,
             bus_outbound_tx,
-            bus_inbound_tx,
             provider,
-            workspace,
             model,
             max_iterations,
-            context,
+            workspaces,
             sessions,
             tools,
+            skill_exec,
+            skill_tools: Vec::new(),
+            skill_generation: 0,
             subagents,
             message_tool,
             spawn_tool,
             cron_tool,
+            reminder_tool,
+            direct_rx,
+            direct_tx,
+            streaming: false,
+            turn_deltas: None,
+            hooks,
+            profiles: ProfileRouter::default(),
+            roles: Roles::default(),
+            alerts: AlertMonitor::default(),
+            supervised: None,
+            show_tool_calls: false,
+            usage: None,
+            turn_timeout: None,
+            events,
+            system_events: None,
+            mid_turn: MidTurnMode::default(),
+            pending: VecDeque::new(),
+            pending_direct: VecDeque::new(),
             running: Arc::new(AtomicBool::new(false)),
-        }
+        };
+        agent.register_skill_tools();
+        agent
     }
 
     /// Run the main agent loop until stopped.
     pub async fn run(&mut self) {
         self.running.store(true, Ordering::SeqCst);
         info!("Agent loop started");
+        debug!("Agent hooks: {}", self.hooks.names().join(", "));
 
         while self.running.load(Ordering::SeqCst) {
-            let msg = match tokio::time::timeout(
-                Duration::from_secs(1),
-                self.bus_inbound_rx.recv(),
-            )
-            .await
-            {
-                Ok(Some(msg)) => msg,
+            // Look at everything that is waiting, so people are answered
+            // before background work when the loop is backed up.
+            while let Ok(msg) = self.bus_inbound_rx.try_recv() {
+                self.pending.push_back(msg);
+            }
+            while let Ok(req) = self.direct_rx.try_recv() {
+                self.pending_direct.push_back(req);
+            }
+            let waiting = self._take_most_urgent();
+            let inbound_rx = &mut self.bus_inbound_rx;
+            let direct_rx = &mut self.direct_rx;
+            let next = match waiting {
+                Some(incoming) => Ok(Some(incoming)),
+                None => {
+                    tokio::time::timeout(Duration::from_secs(1), async {
+                        tokio::select! {
+                            msg = inbound_rx.recv() => msg.map(Incoming::Bus),
+                            req = direct_rx.recv() => req.map(Incoming::Direct),
+                        }
+                    })
+                    .await
+                }
+            };
+
+            let msg = match next {
+                Ok(Some(Incoming::Bus(msg))) => msg,
+                Ok(Some(Incoming::Direct(req))) => {
+                    self.turn_deltas = req.deltas;
+                    let response = self._process_message(&req.msg, false, false).await;
+                    self.turn_deltas = None;
+                    let reply = match response {
+                        Some(response) if is_error_response(&response) => {
+                            Err(anyhow::anyhow!(response.content))
+                        }
+                        Some(response) => Ok(response.content),
+                        None => Ok(String::new()),
+                    };
+                    let _ = req.reply_tx.send(reply);
+                    continue;
+                }
                 Ok(None) => {
                     info!("Inbound channel closed, stopping agent loop");
                     break;
@@ -185,7 +414,7 @@ impl AgentLoop {
             let response = if is_system {
                 self._process_system_message(&msg).await
             } else {
-                self._process_message(&msg).await
+                self._process_message(&msg, self.streaming, true).await
             };
 
             if let Some(outbound) = response {
@@ -198,6 +427,261 @@ impl AgentLoop {
         info!("Agent loop stopped");
     }
 
+    /// Take the waiting message or request with the highest priority; among
+    /// equals, the one that arrived first.
+    fn _take_most_urgent(&mut self) -> Option<Incoming> {
+        let bus = _most_urgent(self.pending.iter());
+        let direct = _most_urgent(self.pending_direct.iter().map(|req| &req.msg));
+        let take_direct = match (bus, direct) {
+            (None, None) => return None,
+            (Some(_), None) => false,
+            (None, Some(_)) => true,
+            (Some(i), Some(j)) => {
+                let (msg, req) = (&self.pending[i], &self.pending_direct[j].msg);
+                (req.priority, std::cmp::Reverse(req.timestamp))
+                    > (msg.priority, std::cmp::Reverse(msg.timestamp))
+            }
+        };
+        if take_direct {
+            self.pending_direct.remove(direct?).map(Incoming::Direct)
+        } else {
+            self.pending.remove(bus?).map(Incoming::Bus)
+        }
+    }
+
+    /// Get a sender for submitting [`DirectRequest`]s to the running loop.
+    pub fn direct_sender(&self) -> UnboundedSender<DirectRequest> {
+        self.direct_tx.clone()
+    }
+
+    /// The manager for subagents started by this loop.
+    pub fn subagents(&self) -> Arc<SubagentManager> {
+        self.subagents.clone()
+    }
+
+    /// The model used when no profile picks another.
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Use `model` from the next turn on.
+    pub fn set_model(&mut self, model: &str) {
+        self.model = model.to_string();
+    }
+
+    /// Names of the tools the agent can use, sorted.
+    pub fn tool_names(&self) -> Vec<String> {
+        let mut names = self.tools.tool_names();
+        names.sort();
+        names
+    }
+
+    /// The last `max_messages` messages of a session.
+    pub fn history(&mut self, session_key: &str, max_messages: usize) -> Vec<Value> {
+        self.sessions.get_or_create(session_key).get_history(max_messages)
+    }
+
+    /// Subscribe to [`AgentEvent`]s describing the progress of each turn.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<AgentEvent> {
+        self.events.subscribe()
+    }
+
+    /// Stream replies to bus channels as they are generated. Channels that
+    /// can edit messages show the text forming; others get the final reply.
+    pub fn set_streaming(&mut self, enabled: bool) {
+        self.streaming = enabled;
+    }
+
+    /// Limit the estimated size of prompts sent to the LLM.
+    pub fn set_context_budget(&mut self, budget: ContextConfig) {
+        for context in self.workspaces.all_mut() {
+            context.set_budget(budget.clone());
+        }
+    }
+
+    /// Add extra system-prompt text for messages from the given channels.
+    pub fn set_channel_prompts(&mut self, prompts: HashMap<String, String>) {
+        for context in self.workspaces.all_mut() {
+            context.set_channel_prompts(prompts.clone());
+        }
+    }
+
+    /// Apply per-tool settings, replacing the web and file tools built with
+    /// defaults.
+    pub fn set_tools_config(&mut self, config: &ToolsConfig) {
+        let search = &config.web.search;
+        let api_key = Some(search.api_key.clone()).filter(|k| !k.is_empty());
+        let backend = search_backend(&search.backend, api_key.clone(), &search.url, &search.engine_id);
+        self.tools.register(Box::new(
+            WebSearchTool::new(api_key, search.max_results).with_backend(backend),
+        ));
+        let fetch = &config.web.fetch;
+        let politeness = Arc::new(Politeness::new(
+            fetch.respect_robots_txt,
+            Duration::from_millis(fetch.min_interval_ms),
+        ));
+        self.subagents.set_fetch_politeness(politeness.clone());
+        self.tools.register(Box::new(
+            WebFetchTool::with_timeout(fetch.max_chars, fetch.timeout_secs)
+                .with_allowed_hosts(&fetch.allowed_hosts)
+                .with_politeness(politeness.clone())
+                .with_downloads(
+                    self.workspaces.default_context().workspace.join("downloads"),
+                    fetch.max_download_mb * 1024 * 1024,
+                ),
+        ));
+        let crawl = &config.web.crawl;
+        self.tools.register(Box::new(
+            CrawlTool::new(
+                WebFetchTool::with_timeout(fetch.max_chars, fetch.timeout_secs)
+                    .with_allowed_hosts(&fetch.allowed_hosts)
+                    .with_politeness(politeness),
+            )
+            .with_limits(crawl.max_depth, crawl.max_pages, crawl.max_chars),
+        ));
+
+        let roots = &config.filesystem.allowed_paths;
+        if !roots.is_empty() {
+            let file_tools: [Box<dyn Tool>; 4] = [
+                Box::new(ReadFileTool),
+                Box::new(WriteFileTool),
+                Box::new(EditFileTool),
+                Box::new(ListDirTool),
+            ];
+            for tool in file_tools {
+                self.tools.register(Box::new(RestrictedPaths::new(tool, roots)));
+            }
+        }
+
+        let message = &config.message;
+        self.message_tool
+            .set_fallback(&message.default_channel, &message.default_chat_id);
+    }
+
+    /// Check the config keys skills require against `config`, and log the
+    /// skills that are unavailable and why.
+    pub fn set_skills_config(&mut self, config: Arc<Config>) {
+        for context in self.workspaces.all_mut() {
+            context.skills.set_config(config.clone());
+            context.skills.log_unavailable();
+        }
+        self.register_skill_tools();
+    }
+
+    /// Register the scripts that available skills of the default workspace
+    /// offer as tools, replacing the ones registered before. Skill tools
+    /// cannot replace built-in tools.
+    pub fn register_skill_tools(&mut self) {
+        for name in self.skill_tools.drain(..) {
+            self.tools.unregister(&name);
+        }
+        let loader = &self.workspaces.default_context().skills;
+        self.skill_generation = loader.refresh();
+        for skill in loader.list_skills(true) {
+            let dir = Path::new(&skill.path)
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+            for spec in skill.meta.tools {
+                if self.tools.has(&spec.name) {
+                    warn!(
+                        "Skill '{}' offers tool '{}', which already exists; ignoring it",
+                        skill.name, spec.name
+                    );
+                    continue;
+                }
+                self.skill_tools.push(spec.name.clone());
+                self.tools.register(Box::new(SkillScriptTool::new(
+                    spec,
+                    &skill.name,
+                    dir.clone(),
+                    self.skill_exec.clone(),
+                )));
+            }
+        }
+        if !self.skill_tools.is_empty() {
+            info!("Skill tools: {}", self.skill_tools.join(", "));
+        }
+    }
+
+    /// Serve extra workspaces, selected by profile or chat route.
+    pub fn set_workspaces(&mut self, workspaces: Vec<WorkspaceConfig>) {
+        self.workspaces.set_named(workspaces);
+    }
+
+    /// Register a tool, replacing one of the same name.
+    pub fn register_tool(&mut self, tool: Box<dyn Tool>) {
+        self.tools.register(tool);
+    }
+
+    /// Register a hook to run around LLM and tool calls, after the
+    /// built-in ones.
+    pub fn add_hook(&mut self, hook: Arc<dyn Hook>) {
+        self.hooks.register(hook);
+    }
+
+    /// Host additional agent personas; messages are routed to them by chat
+    /// or by an `@name` prefix.
+    pub fn set_profiles(&mut self, profiles: Vec<AgentProfileConfig>) {
+        self.profiles = ProfileRouter::new(profiles);
+    }
+
+    /// Give channel users the roles in `config`.
+    pub fn set_roles(&mut self, config: RolesConfig) {
+        self.roles = Roles::new(config);
+    }
+
+    /// Alert the owner when the thresholds in `config` are crossed. Call
+    /// after [`set_usage_store`](Self::set_usage_store) so the day's spend
+    /// so far is known.
+    pub fn set_alerts(&mut self, config: AlertsConfig) {
+        self.alerts = AlertMonitor::new(config, self._spent_today());
+    }
+
+    /// Record the files written and commands run by this loop and its
+    /// subagents in `log`.
+    pub fn set_audit_log(&mut self, log: Arc<AuditLog>) {
+        self.hooks.register(Arc::new(AuditHook::new(log.clone())));
+        self.subagents.set_audit_log(log);
+    }
+
+    /// Record token usage and cost of every LLM call in `store`, and answer
+    /// `/cost` from it.
+    pub fn set_usage_store(&mut self, store: UsageStore) {
+        let store = Arc::new(std::sync::Mutex::new(store));
+        self.hooks.register(Arc::new(CostHook::new(store.clone())));
+        self.usage = Some(store);
+    }
+
+    /// Publish budget warnings and alerts as [`SystemEvent`]s too.
+    pub fn set_system_events(&mut self, events: broadcast::Sender<SystemEvent>) {
+        self.system_events = Some(events);
+    }
+
+    /// Limit the wall-clock time of a whole turn (0 = no limit). When the
+    /// limit is hit the turn stops and the user is told what was done.
+    pub fn set_turn_timeout(&mut self, secs: u64) {
+        self.turn_timeout = (secs > 0).then(|| Duration::from_secs(secs));
+    }
+
+    /// Ask the chat to approve each tool call that changes something, and
+    /// decline it without a yes within `timeout_secs`.
+    pub fn set_supervised(&mut self, supervised: bool, timeout_secs: u64) {
+        self.supervised = supervised.then(|| Duration::from_secs(timeout_secs));
+    }
+
+    /// Post each tool call and the start of its result in the chat a turn
+    /// came from while the turn runs.
+    pub fn set_show_tool_calls(&mut self, enabled: bool) {
+        self.show_tool_calls = enabled;
+    }
+
+    /// Choose how messages that arrive during a turn in the same chat are
+    /// handled: `"queue"`, `"restart"` or `"inject"`.
+    pub fn set_mid_turn_mode(&mut self, mode: &str) {
+        self.mid_turn = MidTurnMode::parse(mode);
+    }
+
     /// Signal the agent loop to stop.
     pub fn stop(&self) {
         self.running.store(false, Ordering::SeqCst);
@@ -211,12 +695,27 @@ impl AgentLoop {
         session_key: &str,
         channel: &str,
         chat_id: &str,
+    ) -> String {
+        self.process_direct_with_attachments(content, Vec::new(), session_key, channel, chat_id)
+            .await
+    }
+
+    /// Like [`process_direct`](Self::process_direct), with files attached
+    /// to the message.
+    pub async fn process_direct_with_attachments(
+        &mut self,
+        content: &str,
+        attachments: Vec<Attachment>,
+        session_key: &str,
+        channel: &str,
+        chat_id: &str,
     ) -> String {
         let mut msg = InboundMessage::new(channel, "user", chat_id, content);
         msg.metadata
             .insert("session_key".to_string(), json!(session_key));
+        msg.attachments = attachments;
 
-        match self._process_message(&msg).await {
+        match self._process_message(&msg, false, false).await {
             Some(response) => response.content,
             None => String::new(),
         }
@@ -227,7 +726,30 @@ impl AgentLoop {
     // ------------------------------------------------------------------
 
     /// Process a regular inbound message through the agent loop.
-    async fn _process_message(&mut self, msg: &InboundMessage) -> Option<OutboundMessage> {
+    ///
+    /// With `stream`, partial replies are published to the bus while the LLM
+    /// generates, and the final reply carries the same `stream_id`. With
+    /// `from_bus`, new messages from the same chat are handled according to
+    /// the [`MidTurnMode`] between iterations.
+    ///
+    /// The turn's ID is taken from the `turn_id` metadata, or made up, and is
+    /// carried by the turn's log lines, events, hook calls and replies.
+    #[tracing::instrument(
+        name = "turn",
+        skip_all,
+        fields(session = tracing::field::Empty, turn = tracing::field::Empty)
+    )]
+    async fn _process_message(
+        &mut self,
+        msg: &InboundMessage,
+        stream: bool,
+        from_bus: bool,
+    ) -> Option<OutboundMessage> {
+        let turn_id = msg
+            .turn_id()
+            .map(str::to_string)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        tracing::Span::current().record("turn", turn_id.as_str());
         let session_key = msg
             .metadata
             .get("session_key")
@@ -235,6 +757,21 @@ impl AgentLoop {
             .map(|s| s.to_string())
             .unwrap_or_else(|| format!("{}:{}", msg.channel, msg.chat_id));
 
+        // Pick the agent profile; each profile keeps its own session.
+        let (profile, content) = match self.profiles.route(&msg.channel, &msg.chat_id, &msg.content) {
+            Some((profile, content)) => (Some(profile.clone()), content),
+            None => (None, msg.content.clone()),
+        };
+        let session_key = match &profile {
+            Some(p) => format!("{}:{}", session_key, p.name),
+            None => session_key,
+        };
+        tracing::Span::current().record("session", session_key.as_str());
+        let model = profile
+            .as_ref()
+            .and_then(|p| p.model.clone())
+            .unwrap_or_else(|| self.model.clone());
+
         debug!(
             "Processing message from {} on {}: {}",
             msg.sender_id,
@@ -242,6 +779,62 @@ impl AgentLoop {
             &msg.content[..msg.content.len().min(80)]
         );
 
+        if content.trim() == "/cost" {
+            let report = match &self.usage {
+                Some(store) => store.lock().map(|s| s.report(&session_key)).unwrap_or_default(),
+                None => "Cost tracking is not enabled.".to_string(),
+            };
+            let mut outbound = OutboundMessage::new(&msg.channel, &msg.chat_id, &report);
+            outbound.metadata.insert("turn_id".to_string(), json!(turn_id));
+            return Some(outbound);
+        }
+
+        let role = self.roles.role_of(&msg.channel, &msg.sender_id);
+        let command = content.split_whitespace().next().unwrap_or_default();
+        if command == "/whoami" || ADMIN_COMMANDS.contains(&command) {
+            let reply = match command {
+                "/whoami" => format!(
+                    "You are {} on {}, with the {} role.",
+                    msg.sender_id,
+                    msg.channel,
+                    role.name()
+                ),
+                _ if role != Role::Owner => format!("Only owners can use {}.", command),
+                "/resume" if self.alerts.resume() => {
+                    info!("{} resumed the agent", msg.sender_id);
+                    "Resumed.".to_string()
+                }
+                "/resume" => "Not paused.".to_string(),
+                _ => {
+                    info!("{} reset session {}", msg.sender_id, session_key);
+                    self.sessions.delete(&session_key);
+                    "Conversation cleared.".to_string()
+                }
+            };
+            let mut outbound = OutboundMessage::new(&msg.channel, &msg.chat_id, &reply);
+            outbound.metadata.insert("turn_id".to_string(), json!(turn_id));
+            return Some(outbound);
+        }
+
+        if let Some(reason) = self.alerts.paused() {
+            if is_internal(&msg.channel, &msg.sender_id) {
+                info!("Paused: skipping message from {} for {}", msg.sender_id, session_key);
+                return None;
+            }
+            let reply = format!(
+                "⏸️ Paused after an alert: {} An owner can reply /resume.",
+                reason
+            );
+            let mut outbound = OutboundMessage::new(&msg.channel, &msg.chat_id, &reply);
+            outbound.metadata.insert("turn_id".to_string(), json!(turn_id));
+            return Some(outbound);
+        }
+
+        // Pick up skills added or changed since the last turn.
+        if self.workspaces.default_context().skills.refresh() != self.skill_generation {
+            self.register_skill_tools();
+        }
+
         // Update tool contexts.
         self.message_tool
             .set_context(&msg.channel, &msg.chat_id)
@@ -252,39 +845,63 @@ impl AgentLoop {
         if let Some(ref ct) = self.cron_tool {
             ct.set_context(&msg.channel, &msg.chat_id).await;
         }
+        if let Some(ref rt) = self.reminder_tool {
+            rt.set_context(&msg.channel, &msg.chat_id).await;
+        }
 
-        // Get or create session.
+        // Get or create session; a seeded one starts from the given messages.
+        let seed = msg.metadata.get("seed_history").and_then(|v| v.as_array());
         let session = self.sessions.get_or_create(&session_key);
+        if let Some(seed) = seed {
+            session.clear();
+            for m in seed {
+                if let (Some(role), Some(text)) = (m["role"].as_str(), m["content"].as_str()) {
+                    session.add_message(role, text);
+                }
+            }
+        }
         let history = session.get_history(100);
+        // Any reply clears the unfinished marker; "continue" also resumes.
+        let resuming = session.metadata.remove(UNFINISHED_TASK_KEY).is_some()
+            && is_continue_request(&content);
+        let prompt = if resuming { RESUME_PROMPT } else { content.as_str() };
 
-        // Extract media paths.
-        let media_paths: Vec<String> = msg
-            .metadata
-            .get("media")
-            .and_then(|v| v.as_array())
-            .map(|arr| {
-                arr.iter()
-                    .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                    .collect()
-            })
-            .unwrap_or_default();
-        let media_ref: Vec<String> = media_paths;
-
-        // Build messages.
-        let mut messages = self.context.build_messages(
+        // Build messages in the workspace of the profile or chat.
+        let (workspace_name, context) =
+            self.workspaces
+                .select(profile.as_ref(), &msg.channel, &msg.chat_id);
+        if let Some(name) = workspace_name {
+            debug!("Using workspace {} for {}", name, session_key);
+        }
+        let mut messages = context.build_messages(
             &history,
-            &msg.content,
+            prompt,
             None,
-            if media_ref.is_empty() {
-                None
-            } else {
-                Some(&media_ref)
-            },
+            Some(&msg.attachments),
             Some(&msg.channel),
             Some(&msg.chat_id),
         );
 
-        let tool_defs = self.tools.get_definitions();
+        if let Some(p) = &profile {
+            self._apply_profile_prompt(&mut messages, p, &context.workspace);
+        }
+
+        let turn_started = Instant::now();
+        let _ = self.events.send(AgentEvent::TurnStarted {
+            turn_id: turn_id.clone(),
+            session_key: session_key.clone(),
+            channel: msg.channel.clone(),
+            chat_id: msg.chat_id.clone(),
+        });
+
+        let mut tool_defs = self.tools.get_definitions();
+        if let Some(p) = &profile {
+            tool_defs.retain(|d| allows_tool(p, d["function"]["name"].as_str().unwrap_or("")));
+        }
+        tool_defs.retain(|d| {
+            self.roles
+                .allows_tool(role, d["function"]["name"].as_str().unwrap_or(""))
+        });
         let tool_defs_opt: Option<&[Value]> = if tool_defs.is_empty() {
             None
         } else {
@@ -292,23 +909,109 @@ impl AgentLoop {
         };
 
         let mut final_content = String::new();
+        let mut failed = false;
+        let mut compacted = false;
+        let mut timed_out = false;
+        let mut iterations = 0;
+        let mut done = false;
+        let mut completed_tools: Vec<String> = Vec::new();
+        let mut injected: Vec<String> = Vec::new();
+        let mut restart: Option<InboundMessage> = None;
+        let deadline = self.turn_timeout.map(|t| Instant::now() + t);
+        let stream_id =
+            (stream || self.turn_deltas.is_some()).then(|| uuid::Uuid::new_v4().to_string());
 
         // Agent loop: call LLM, handle tool calls, repeat.
-        for iteration in 0..self.max_iterations {
+        'turn: for iteration in 0..self.max_iterations {
             debug!("Agent iteration {}/{}", iteration + 1, self.max_iterations);
+            iterations = iteration + 1;
 
-            let response = match self
-                .provider
-                .chat(&messages, tool_defs_opt, Some(&self.model), 8192, 0.7)
-                .await
-            {
+            // The user wrote again while we were working.
+            if from_bus && iteration > 0 {
+                let arrived = self._take_mid_turn_messages(msg);
+                match self.mid_turn {
+                    MidTurnMode::Restart if !arrived.is_empty() => {
+                        restart = Some(merge_messages(msg, &arrived));
+                        break;
+                    }
+                    MidTurnMode::Inject => {
+                        for m in arrived {
+                            info!("Injecting new message into turn for {}", session_key);
+                            messages.push(json!({
+                                "role": "user",
+                                "content": format!("{}\n{}", INJECTED_PREFIX, m.content),
+                            }));
+                            injected.push(m.content);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            let hook_ctx = HookContext {
+                turn_id: turn_id.clone(),
+                channel: msg.channel.clone(),
+                chat_id: msg.chat_id.clone(),
+                session_key: session_key.clone(),
+                model: model.clone(),
+                iteration,
+            };
+            self.hooks.before_llm_call(&hook_ctx, &mut messages).await;
+
+            let call = async {
+                match &stream_id {
+                    Some(stream_id) => {
+                        self._chat_streaming(&messages, tool_defs_opt, &model, msg, stream_id, &turn_id)
+                            .await
+                    }
+                    None => {
+                        self.provider
+                            .chat(&messages, tool_defs_opt, Some(&model), 8192, 0.7)
+                            .await
+                    }
+                }
+            };
+            let Some(response) = within(deadline, call).await else {
+                timed_out = true;
+                break;
+            };
+            let error = match &response {
+                Ok(r) if r.finish_reason == "error" => r.content.clone(),
+                Ok(_) => None,
+                Err(e) => Some(e.to_string()),
+            };
+
+            // The prompt did not fit: compact it and retry once.
+            if let Some(error) = error.as_deref().filter(|e| is_context_overflow(e)) {
+                if !compacted {
+                    compacted = true;
+                    warn!("Context window exceeded, compacting: {}", error);
+                    if compact_messages(self.provider.as_ref(), &model, &mut messages).await {
+                        continue;
+                    }
+                }
+                final_content = OVERFLOW_MESSAGE.to_string();
+                failed = true;
+                break;
+            }
+
+            let mut response = match response {
                 Ok(r) => r,
                 Err(e) => {
                     error!("LLM call failed: {}", e);
                     final_content = format!("I encountered an error: {}", e);
+                    failed = true;
                     break;
                 }
             };
+            self.hooks
+                .after_llm_call(&hook_ctx, &messages, &mut response)
+                .await;
+
+            if response.finish_reason == "error" {
+                final_content = response.content.unwrap_or_default();
+                failed = true;
+                break;
+            }
 
             if response.has_tool_calls() {
                 // Build tool_calls JSON for the assistant message.
@@ -336,50 +1039,369 @@ impl AgentLoop {
 
                 // Execute each tool call.
                 for tc in &response.tool_calls {
-                    debug!("Executing tool: {} (id: {})", tc.name, tc.id);
-                    let result = self.tools.execute(&tc.name, tc.arguments.clone()).await;
-                    debug!(
-                        "Tool {} result ({}B)",
-                        tc.name,
-                        result.len()
-                    );
+                    let mut tc = tc.clone();
+                    let mut result = match self.hooks.before_tool_call(&hook_ctx, &mut tc).await {
+                        ToolDecision::Allow if profile.as_ref().is_some_and(|p| !allows_tool(p, &tc.name)) => {
+                            format!("Error: tool '{}' is not available to this agent", tc.name)
+                        }
+                        ToolDecision::Allow if !self.roles.allows_tool(role, &tc.name) => {
+                            format!(
+                                "Error: tool '{}' is not available to {} users",
+                                tc.name,
+                                role.name()
+                            )
+                        }
+                        ToolDecision::Allow => {
+                            let declined = match self.supervised {
+                                Some(wait) if needs_approval(&tc, &msg.channel, &msg.chat_id) => {
+                                    self._await_approval(msg, &tc, from_bus, wait, deadline)
+                                        .await
+                                        .err()
+                                }
+                                _ => None,
+                            };
+                            if let Some(reason) = declined {
+                                reason
+                            } else {
+                                let run = self.tools.execute(&tc.name, tc.arguments.clone());
+                                match within(deadline, run).await {
+                                    Some(result) => result,
+                                    None => {
+                                        timed_out = true;
+                                        break 'turn;
+                                    }
+                                }
+                            }
+                        }
+                        ToolDecision::Deny(reason) => reason,
+                    };
+                    self.hooks
+                        .after_tool_call(&hook_ctx, &tc, &mut result)
+                        .await;
+                    self.alerts.record_tool_call(chrono::Local::now());
+                    if self.show_tool_calls && from_bus {
+                        let progress = chat_message(&tc, &result);
+                        let _ = self
+                            .bus_outbound_tx
+                            .send(OutboundMessage::new(&msg.channel, &msg.chat_id, &progress));
+                    }
                     ContextBuilder::add_tool_result(
                         &mut messages,
                         &tc.id,
                         &tc.name,
                         &result,
                     );
+                    completed_tools.push(describe_tool_call(&tc));
                 }
             } else {
                 // No tool calls -- the agent is done.
                 final_content = response.content.unwrap_or_default();
+                done = true;
                 break;
             }
         }
 
+        if let Some(next) = restart {
+            info!("Restarting turn for {} with the user's new message", session_key);
+            let _ = self.events.send(AgentEvent::TurnCompleted {
+                turn_id,
+                session_key: session_key.clone(),
+                iterations,
+                duration_ms: turn_started.elapsed().as_millis() as u64,
+                failed: false,
+            });
+            self.pending.push_front(next);
+            return None;
+        }
+
+        // Out of iterations mid-task: ask for a progress summary instead of
+        // stopping silently.
+        let unfinished = !done && !failed && !timed_out;
+        if unfinished {
+            warn!(
+                "Reached {} iterations for {}, asking for a summary",
+                self.max_iterations, session_key
+            );
+            messages.push(json!({"role": "user", "content": ITERATION_LIMIT_PROMPT}));
+            let call = self.provider.chat(&messages, None, Some(&model), 8192, 0.7);
+            final_content = match within(deadline, call).await {
+                Some(Ok(r)) if r.finish_reason != "error" => r.content.unwrap_or_default(),
+                _ => String::new(),
+            };
+            if final_content.trim().is_empty() {
+                final_content = format!(
+                    "I reached the limit of {} steps before finishing this task. \
+                     Reply \"continue\" to let me keep going.",
+                    self.max_iterations
+                );
+            }
+        }
+
+        if timed_out {
+            warn!("Turn for {} timed out", session_key);
+            final_content = timeout_message(self.turn_timeout.unwrap_or_default(), &completed_tools);
+            failed = true;
+        }
+
         if final_content.is_empty() && messages.len() > 2 {
             final_content = "I completed the requested actions.".to_string();
         }
 
+        let budget_warning = self
+            .usage
+            .as_ref()
+            .and_then(|store| store.lock().ok()?.take_budget_warning());
+        if let Some(warning) = &budget_warning {
+            let _ = self.events.send(AgentEvent::BudgetExceeded {
+                turn_id: turn_id.clone(),
+                message: warning.clone(),
+            });
+            if let Some(system_events) = &self.system_events {
+                let _ = system_events.send(SystemEvent::BudgetWarning {
+                    message: warning.clone(),
+                });
+            }
+        }
+
+        self.alerts.record_turn(failed);
+        let alerts = self.alerts.check(chrono::Local::now(), self._spent_today());
+        self._send_alerts(alerts);
+
+        let _ = self.events.send(AgentEvent::TurnCompleted {
+            turn_id: turn_id.clone(),
+            session_key: session_key.clone(),
+            iterations,
+            duration_ms: turn_started.elapsed().as_millis() as u64,
+            failed,
+        });
+
         // Update session history.
         {
             let session = self.sessions.get_or_create(&session_key);
-            session.add_message("user", &msg.content);
+            if unfinished {
+                session
+                    .metadata
+                    .insert(UNFINISHED_TASK_KEY.to_string(), json!(true));
+            }
+            session.add_message(
+                "user",
+                &ContextBuilder::with_attachment_notes(&content, &msg.attachments),
+            );
+            for text in &injected {
+                session.add_message("user", text);
+            }
             if !final_content.is_empty() {
                 session.add_message("assistant", &final_content);
             }
         }
         // Mutable borrow dropped; now save from cache.
-        self.sessions.save_cached(&session_key);
+        if seed.is_some() {
+            self.sessions.delete(&session_key);
+        } else {
+            self.sessions.save_cached(&session_key);
+        }
 
         if final_content.is_empty() {
             None
         } else {
-            Some(OutboundMessage::new(
-                &msg.channel,
-                &msg.chat_id,
-                &final_content,
-            ))
+            if let Some(warning) = budget_warning {
+                final_content = format!("{}\n\n{}", final_content, warning);
+            }
+            let mut outbound = OutboundMessage::new(&msg.channel, &msg.chat_id, &final_content);
+            if failed {
+                outbound.metadata.insert("error".to_string(), json!(true));
+            }
+            if let Some(stream_id) = stream_id {
+                outbound.metadata.insert("stream_id".to_string(), json!(stream_id));
+            }
+            outbound.metadata.insert("turn_id".to_string(), json!(turn_id));
+            Some(outbound)
+        }
+    }
+
+    /// Take the messages that arrived on the bus during the turn for `msg`.
+    /// Those from the same chat are returned; the rest are kept for later.
+    /// Nothing is taken in [`MidTurnMode::Queue`].
+    fn _take_mid_turn_messages(&mut self, msg: &InboundMessage) -> Vec<InboundMessage> {
+        let mut same_chat = Vec::new();
+        if self.mid_turn == MidTurnMode::Queue {
+            return same_chat;
+        }
+        while let Ok(next) = self.bus_inbound_rx.try_recv() {
+            let is_system = next
+                .metadata
+                .get("is_system")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            // A message joins the turn only if its sender may do all that
+            // the turn's sender may, so guests cannot borrow an owner's tools.
+            let same_chat_peer = next.channel == msg.channel
+                && next.chat_id == msg.chat_id
+                && self.roles.role_of(&next.channel, &next.sender_id)
+                    >= self.roles.role_of(&msg.channel, &msg.sender_id);
+            if !is_system && same_chat_peer {
+                same_chat.push(next);
+            } else {
+                self.pending.push_back(next);
+            }
+        }
+        same_chat
+    }
+
+    /// Propose `call` in `msg`'s chat and wait up to `wait` (or the turn's
+    /// deadline) for a reply from someone with at least the sender's role.
+    /// Other messages wait their turn. Returns the tool result to give the
+    /// model when the call is declined.
+    async fn _await_approval(
+        &mut self,
+        msg: &InboundMessage,
+        call: &ToolCallRequest,
+        from_bus: bool,
+        wait: Duration,
+        deadline: Option<Instant>,
+    ) -> Result<(), String> {
+        if !from_bus {
+            return Err(format!(
+                "Error: supervised mode needs approval in a chat, so '{}' was not run",
+                call.name
+            ));
+        }
+        let request = approval_request(call, wait.as_secs());
+        let _ = self
+            .bus_outbound_tx
+            .send(OutboundMessage::new(&msg.channel, &msg.chat_id, &request));
+        let role = self.roles.role_of(&msg.channel, &msg.sender_id);
+        let until = deadline.map_or(Instant::now() + wait, |d| d.min(Instant::now() + wait));
+        loop {
+            let next = match tokio::time::timeout_at(until, self.bus_inbound_rx.recv()).await {
+                Ok(Some(next)) => next,
+                Ok(None) => return Err("Error: shutting down".to_string()),
+                Err(_) => {
+                    let notice = format!("No reply, so {} was not run.", call.name);
+                    let _ = self
+                        .bus_outbound_tx
+                        .send(OutboundMessage::new(&msg.channel, &msg.chat_id, &notice));
+                    return Err(format!(
+                        "Error: the user did not approve '{}' within {}s, so it was not run",
+                        call.name,
+                        wait.as_secs()
+                    ));
+                }
+            };
+            let is_system = next
+                .metadata
+                .get("is_system")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            let is_reply = !is_system
+                && next.channel == msg.channel
+                && next.chat_id == msg.chat_id
+                && self.roles.role_of(&next.channel, &next.sender_id) >= role;
+            if !is_reply {
+                self.pending.push_back(next);
+                continue;
+            }
+            if is_approval(&next.content) {
+                info!("{} approved {}", next.sender_id, call.name);
+                return Ok(());
+            }
+            info!("{} declined {}", next.sender_id, call.name);
+            return Err(format!(
+                "Error: the user declined '{}' and replied: {}",
+                call.name, next.content
+            ));
+        }
+    }
+
+    /// Spend of the current day, if costs are tracked.
+    fn _spent_today(&self) -> f64 {
+        self.usage
+            .as_ref()
+            .and_then(|store| Some(store.lock().ok()?.spent_today()))
+            .unwrap_or(0.0)
+    }
+
+    /// Tell the owner about crossed alert thresholds, on the alert chat and
+    /// as system events.
+    fn _send_alerts(&self, alerts: Vec<Alert>) {
+        let target = self.alerts.target().or_else(|| self.roles.primary_owner());
+        for alert in alerts {
+            warn!("Alert ({}): {}", alert.kind, alert.message);
+            if let Some(system_events) = &self.system_events {
+                let _ = system_events.send(SystemEvent::Alert {
+                    alert: alert.kind.to_string(),
+                    message: alert.message.clone(),
+                });
+            }
+            let Some((channel, chat_id)) = target else {
+                continue;
+            };
+            let mut text = format!("🚨 {}", alert.message);
+            if self.alerts.paused() == Some(alert.message.as_str()) {
+                text.push_str(" The agent is paused until you reply /resume.");
+            }
+            let _ = self
+                .bus_outbound_tx
+                .send(OutboundMessage::new(channel, chat_id, &text));
+        }
+    }
+
+    /// Call the LLM with streaming, publishing the text generated so far as
+    /// partial outbound messages for `msg`'s chat, or sending it to the
+    /// deltas of the direct request being handled.
+    async fn _chat_streaming(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: &str,
+        msg: &InboundMessage,
+        stream_id: &str,
+        turn_id: &str,
+    ) -> Result<LLMResponse> {
+        if let Some(deltas) = &self.turn_deltas {
+            return self
+                .provider
+                .chat_stream(messages, tools, Some(model), 8192, 0.7, deltas.clone())
+                .await;
+        }
+        let (delta_tx, delta_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut template = OutboundMessage::new(&msg.channel, &msg.chat_id, "");
+        template.metadata.insert("stream_id".to_string(), json!(stream_id));
+        template.metadata.insert("turn_id".to_string(), json!(turn_id));
+        template.metadata.insert("partial".to_string(), json!(true));
+        let forwarder = tokio::spawn(forward_stream(
+            delta_rx,
+            self.bus_outbound_tx.clone(),
+            template,
+        ));
+
+        let result = self
+            .provider
+            .chat_stream(messages, tools, Some(model), 8192, 0.7, delta_tx)
+            .await;
+        let _ = forwarder.await;
+        result
+    }
+
+    /// Tell the model which profile it is acting as, with the profile's
+    /// persona instructions.
+    fn _apply_profile_prompt(
+        &self,
+        messages: &mut [Value],
+        profile: &AgentProfileConfig,
+        workspace: &std::path::Path,
+    ) {
+        let mut persona = format!(
+            "\n\n## Agent Profile\nYou are acting as the \"{}\" agent.",
+            profile.name
+        );
+        if let Some(prompt) = load_profile_prompt(profile, workspace) {
+            persona.push_str("\n\n");
+            persona.push_str(&prompt);
+        }
+        if let Some(system) = messages.first_mut() {
+            let content = system["content"].as_str().unwrap_or("").to_string();
+            system["content"] = json!(content + &persona);
         }
     }
 
@@ -399,6 +1421,113 @@ impl AgentLoop {
     }
 }
 
+/// Accumulate streamed deltas and publish the text so far as copies of
+/// `template`, at most once per [`STREAM_UPDATE_INTERVAL`].
+async fn forward_stream(
+    mut deltas: UnboundedReceiver<String>,
+    outbound_tx: UnboundedSender<OutboundMessage>,
+    template: OutboundMessage,
+) {
+    let mut text = String::new();
+    let mut dirty = false;
+    let mut last_sent: Option<Instant> = None;
+    loop {
+        let wait = match (dirty, last_sent) {
+            (true, Some(t)) => STREAM_UPDATE_INTERVAL.saturating_sub(t.elapsed()),
+            (true, None) => Duration::ZERO,
+            (false, _) => STREAM_UPDATE_INTERVAL,
+        };
+        match tokio::time::timeout(wait, deltas.recv()).await {
+            Ok(Some(delta)) => {
+                text.push_str(&delta);
+                dirty = true;
+            }
+            Ok(None) => break,
+            Err(_) => {}
+        }
+        let due = last_sent.is_none_or(|t| t.elapsed() >= STREAM_UPDATE_INTERVAL);
+        if dirty && due && !text.trim().is_empty() {
+            let mut update = template.clone();
+            update.content = text.clone();
+            if outbound_tx.send(update).is_err() {
+                break;
+            }
+            dirty = false;
+            last_sent = Some(Instant::now());
+        }
+    }
+}
+
+/// Combine `msg` with the messages that arrived after it into one message
+/// for a restarted turn.
+fn merge_messages(msg: &InboundMessage, later: &[InboundMessage]) -> InboundMessage {
+    let mut merged = msg.clone();
+    for m in later {
+        merged.content = format!("{}\n\n{}", merged.content, m.content);
+        merged.attachments.extend(m.attachments.iter().cloned());
+    }
+    merged
+}
+
+/// Whether a message asks the agent to resume its previous task.
+fn is_continue_request(text: &str) -> bool {
+    let text = text
+        .trim()
+        .trim_end_matches(|c: char| c.is_ascii_punctuation())
+        .to_lowercase();
+    matches!(
+        text.as_str(),
+        "continue" | "go on" | "keep going" | "carry on" | "resume"
+    )
+}
+
+/// Await `fut` unless `deadline` passes first (`None` on timeout).
+async fn within<F: std::future::Future>(deadline: Option<Instant>, fut: F) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, fut).await.ok(),
+        None => Some(fut.await),
+    }
+}
+
+/// Short description of a tool call for progress reports, e.g.
+/// `read_file (notes.md)`.
+fn describe_tool_call(tc: &ToolCallRequest) -> String {
+    let first_arg = tc.arguments.values().find_map(|v| v.as_str());
+    match first_arg {
+        Some(arg) => {
+            let arg: String = arg.chars().take(60).collect();
+            format!("{} ({})", tc.name, arg)
+        }
+        None => tc.name.clone(),
+    }
+}
+
+/// Reply sent when a turn runs out of time.
+fn timeout_message(limit: Duration, completed: &[String]) -> String {
+    let mut text = format!(
+        "I ran out of time on this request (limit: {}s) and stopped.",
+        limit.as_secs()
+    );
+    if completed.is_empty() {
+        text.push_str(" No steps were completed.");
+    } else {
+        text.push_str(" Completed so far:");
+        for step in completed {
+            text.push_str(&format!("\n- {}", step));
+        }
+    }
+    text.push_str("\nAsk me to continue if you want me to pick up from here.");
+    text
+}
+
+/// Whether an outbound message reports a failed turn rather than a reply.
+fn is_error_response(msg: &OutboundMessage) -> bool {
+    msg.metadata
+        .get("error")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
 // ---------------------------------------------------------------------------
 // Tool proxy wrappers
 // ---------------------------------------------------------------------------
@@ -447,6 +1576,25 @@ impl crate::agent::tools::Tool for SpawnToolProxy {
     }
 }
 
+/// Proxy that wraps `Arc<RemindMeTool>` to satisfy `Tool`.
+struct RemindMeToolProxy(Arc<RemindMeTool>);
+
+#[async_trait::async_trait]
+impl crate::agent::tools::Tool for RemindMeToolProxy {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+    fn description(&self) -> &str {
+        self.0.description()
+    }
+    fn parameters(&self) -> Value {
+        self.0.parameters()
+    }
+    async fn execute(&self, params: HashMap<String, Value>) -> String {
+        self.0.execute(params).await
+    }
+}
+
 /// Proxy that wraps `Arc<CronScheduleTool>` to satisfy `Tool`.
 struct CronToolProxy(Arc<CronScheduleTool>);
 
@@ -465,3 +1613,723 @@ impl crate::agent::tools::Tool for CronToolProxy {
         self.0.execute(params).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template() -> OutboundMessage {
+        let mut msg = OutboundMessage::new("telegram", "42", "");
+        msg.metadata.insert("stream_id".to_string(), json!("s1"));
+        msg.metadata.insert("partial".to_string(), json!(true));
+        msg
+    }
+
+    // ── forward_stream ──
+
+    #[tokio::test(start_paused = true)]
+    async fn test_forward_stream_throttles_updates() {
+        let (delta_tx, delta_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (out_tx, mut out_rx) = tokio::sync::mpsc::unbounded_channel();
+        let forwarder = tokio::spawn(forward_stream(delta_rx, out_tx, template()));
+
+        delta_tx.send("Hel".to_string()).unwrap();
+        delta_tx.send("lo".to_string()).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        delta_tx.send(", world".to_string()).unwrap();
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        drop(delta_tx);
+        forwarder.await.unwrap();
+
+        let mut updates = Vec::new();
+        while let Ok(msg) = out_rx.try_recv() {
+            assert!(msg.is_partial());
+            assert_eq!(msg.stream_id(), Some("s1"));
+            updates.push(msg.content);
+        }
+        // The first delta goes out at once; the rest are batched.
+        assert_eq!(updates.first().map(String::as_str), Some("Hel"));
+        assert_eq!(updates.last().map(String::as_str), Some("Hello, world"));
+        assert!(updates.len() <= 3, "too many updates: {:?}", updates);
+    }
+
+    #[tokio::test]
+    async fn test_forward_stream_ignores_blank_text() {
+        let (delta_tx, delta_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (out_tx, mut out_rx) = tokio::sync::mpsc::unbounded_channel();
+        delta_tx.send("\n".to_string()).unwrap();
+        drop(delta_tx);
+        forward_stream(delta_rx, out_tx, template()).await;
+        assert!(out_rx.try_recv().is_err());
+    }
+
+    fn test_agent(provider: Arc<dyn LLMProvider>, workspace: &std::path::Path) -> AgentLoop {
+        let (_in_tx, in_rx) = tokio::sync::mpsc::unbounded_channel();
+        test_agent_with_inbound(provider, workspace, in_rx)
+    }
+
+    fn test_agent_with_inbound(
+        provider: Arc<dyn LLMProvider>,
+        workspace: &std::path::Path,
+        in_rx: UnboundedReceiver<InboundMessage>,
+    ) -> AgentLoop {
+        let (out_tx, _out_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (bus_tx, _bus_rx) = tokio::sync::mpsc::unbounded_channel();
+        AgentLoop::new(
+            in_rx,
+            out_tx,
+            bus_tx,
+            provider,
+            workspace.to_path_buf(),
+            "default-model".to_string(),
+            5,
+            None,
+            10,
+            true,
+            None,
+            SubagentConfig::default(),
+        )
+    }
+
+    /// Sessions are stored under the home directory; tests use a throwaway
+    /// key (and delete it afterwards). Returns the session key and chat ID.
+    fn throwaway_session() -> (String, String) {
+        let chat_id = uuid::Uuid::new_v4().to_string();
+        (format!("test:{}", chat_id), chat_id)
+    }
+
+    fn text_response(content: &str, finish_reason: &str) -> LLMResponse {
+        LLMResponse {
+            content: Some(content.to_string()),
+            tool_calls: vec![],
+            finish_reason: finish_reason.to_string(),
+            usage: Default::default(),
+        }
+    }
+
+    // ── profiles ──
+
+    /// Model, messages and tool names of one LLM call.
+    type RecordedCall = (String, Vec<Value>, Vec<String>);
+
+    /// Records each request and answers with a fixed reply.
+    struct RecordingProvider {
+        calls: std::sync::Mutex<Vec<RecordedCall>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LLMProvider for RecordingProvider {
+        async fn chat(
+            &self,
+            messages: &[Value],
+            tools: Option<&[Value]>,
+            model: Option<&str>,
+            _max_tokens: u32,
+            _temperature: f64,
+        ) -> anyhow::Result<LLMResponse> {
+            let tool_names = tools
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|t| t["function"]["name"].as_str().map(String::from))
+                .collect();
+            self.calls.lock().unwrap().push((
+                model.unwrap_or_default().to_string(),
+                messages.to_vec(),
+                tool_names,
+            ));
+            Ok(text_response("done", "stop"))
+        }
+
+        fn get_default_model(&self) -> &str {
+            "default-model"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_profile_prefix_routes_model_prompt_and_tools() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        std::fs::write(tmp.path().join("CODER.md"), "Prefer small diffs.").unwrap();
+        let provider = Arc::new(RecordingProvider {
+            calls: Default::default(),
+        });
+        let mut agent = test_agent(provider.clone(), tmp.path());
+        agent.set_profiles(vec![AgentProfileConfig {
+            name: "coder".to_string(),
+            model: Some("coder-model".to_string()),
+            system_prompt_file: Some("CODER.md".to_string()),
+            tools: vec!["read_file".to_string(), "exec".to_string()],
+            routes: vec![],
+            workspace: None,
+        }]);
+
+        let (session_key, chat_id) = throwaway_session();
+        agent.process_direct("@coder fix it", &session_key, "test", &chat_id).await;
+        agent.process_direct("hello", &session_key, "test", &chat_id).await;
+        for key in [session_key.clone(), format!("{}:coder", session_key)] {
+            agent.sessions.delete(&key);
+        }
+
+        let calls = provider.calls.lock().unwrap();
+        let (model, messages, tools) = &calls[0];
+        assert_eq!(model, "coder-model");
+        let system = messages[0]["content"].as_str().unwrap();
+        assert!(system.contains("\"coder\" agent") && system.contains("Prefer small diffs."));
+        assert_eq!(messages.last().unwrap()["content"], "fix it");
+        let mut tools = tools.clone();
+        tools.sort();
+        assert_eq!(tools, vec!["exec".to_string(), "read_file".to_string()]);
+
+        // Unrouted messages use the default agent and a separate session.
+        let (model, messages, tools) = &calls[1];
+        assert_eq!(model, "default-model");
+        assert!(!messages[0]["content"].as_str().unwrap().contains("Agent Profile"));
+        assert_eq!(messages.len(), 2);
+        assert!(tools.len() > 2);
+    }
+
+    #[tokio::test]
+    async fn test_profile_workspace_supplies_bootstrap_files() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let work = tmp.path().join("work");
+        std::fs::create_dir(&work).unwrap();
+        std::fs::write(work.join("SOUL.md"), "Work persona.").unwrap();
+        std::fs::write(work.join("CODER.md"), "Prefer small diffs.").unwrap();
+        let provider = Arc::new(RecordingProvider {
+            calls: Default::default(),
+        });
+        let mut agent = test_agent(provider.clone(), tmp.path());
+        agent.set_workspaces(vec![WorkspaceConfig {
+            name: "work".to_string(),
+            path: work.to_string_lossy().to_string(),
+            routes: vec![],
+        }]);
+        agent.set_profiles(vec![AgentProfileConfig {
+            name: "coder".to_string(),
+            system_prompt_file: Some("CODER.md".to_string()),
+            workspace: Some("work".to_string()),
+            ..Default::default()
+        }]);
+
+        let (session_key, chat_id) = throwaway_session();
+        agent.process_direct("@coder fix it", &session_key, "test", &chat_id).await;
+        agent.process_direct("hello", &session_key, "test", &chat_id).await;
+        for key in [session_key.clone(), format!("{}:coder", session_key)] {
+            agent.sessions.delete(&key);
+        }
+
+        let calls = provider.calls.lock().unwrap();
+        let system = calls[0].1[0]["content"].as_str().unwrap();
+        assert!(system.contains("Work persona.") && system.contains("Prefer small diffs."));
+        assert!(system.contains(&work.to_string_lossy().to_string()));
+        let system = calls[1].1[0]["content"].as_str().unwrap();
+        assert!(!system.contains("Work persona."));
+    }
+
+    // ── roles ──
+
+    #[tokio::test]
+    async fn test_guest_gets_restricted_tools_and_no_admin_commands() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let provider = Arc::new(RecordingProvider {
+            calls: Default::default(),
+        });
+        let mut agent = test_agent(provider.clone(), tmp.path());
+        agent.set_roles(RolesConfig {
+            owners: vec!["test:owner".to_string()],
+            ..RolesConfig::default()
+        });
+
+        let (session_key, chat_id) = throwaway_session();
+        let message = |sender: &str, content: &str| {
+            let mut msg = InboundMessage::new("test", sender, &chat_id, content);
+            msg.metadata.insert("session_key".to_string(), json!(session_key));
+            msg
+        };
+        agent._process_message(&message("guest", "hi"), false, false).await;
+        let reset = agent._process_message(&message("guest", "/reset"), false, false).await;
+        assert_eq!(reset.unwrap().content, "Only owners can use /reset.");
+        agent._process_message(&message("owner", "hi"), false, false).await;
+        let reset = agent._process_message(&message("owner", "/reset"), false, false).await;
+        assert_eq!(reset.unwrap().content, "Conversation cleared.");
+        assert!(agent.sessions.get_cached(&session_key).is_none());
+
+        let calls = provider.calls.lock().unwrap();
+        let mut guest_tools = calls[0].2.clone();
+        guest_tools.sort();
+        assert_eq!(guest_tools, ["crawl", "message", "web_fetch", "web_search"]);
+        assert!(calls[1].2.contains(&"exec".to_string()));
+    }
+
+    // ── context overflow ──
+
+    /// Rejects prompts over `limit` messages as too long; otherwise replies
+    /// "ok". Summary requests (no tools) get a fixed summary.
+    struct OverflowProvider {
+        limit: usize,
+        calls: std::sync::Mutex<usize>,
+    }
+
+    #[async_trait::async_trait]
+    impl LLMProvider for OverflowProvider {
+        async fn chat(
+            &self,
+            messages: &[Value],
+            tools: Option<&[Value]>,
+            _model: Option<&str>,
+            _max_tokens: u32,
+            _temperature: f64,
+        ) -> anyhow::Result<LLMResponse> {
+            *self.calls.lock().unwrap() += 1;
+            if tools.is_none() {
+                return Ok(text_response("- earlier chat", "stop"));
+            }
+            if messages.len() > self.limit {
+                return Ok(text_response(
+                    r#"Error calling LLM (HTTP 400): {"error":{"code":"context_length_exceeded"}}"#,
+                    "error",
+                ));
+            }
+            Ok(text_response("ok", "stop"))
+        }
+
+        fn get_default_model(&self) -> &str {
+            "default-model"
+        }
+    }
+
+    async fn run_with_history(limit: usize) -> (String, usize) {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let provider = Arc::new(OverflowProvider {
+            limit,
+            calls: Default::default(),
+        });
+        let mut agent = test_agent(provider.clone(), tmp.path());
+        let (session_key, chat_id) = throwaway_session();
+        {
+            let session = agent.sessions.get_or_create(&session_key);
+            for i in 0..10 {
+                session.add_message("user", &format!("question {}", i));
+                session.add_message("assistant", &format!("answer {}", i));
+            }
+        }
+        let reply = agent.process_direct("now", &session_key, "test", &chat_id).await;
+        agent.sessions.delete(&session_key);
+        let calls = *provider.calls.lock().unwrap();
+        (reply, calls)
+    }
+
+    #[tokio::test]
+    async fn test_context_overflow_compacts_and_retries() {
+        // 22 messages are rejected; compaction halves the history.
+        let (reply, calls) = run_with_history(15).await;
+        assert_eq!(reply, "ok");
+        // Rejected call, summary, retry.
+        assert_eq!(calls, 3);
+    }
+
+    #[tokio::test]
+    async fn test_context_overflow_gives_up_after_one_retry() {
+        let (reply, calls) = run_with_history(2).await;
+        assert_eq!(reply, crate::agent::compaction::OVERFLOW_MESSAGE);
+        assert_eq!(calls, 3);
+    }
+
+    // ── turn timeout ──
+
+    /// Takes 40 seconds per call and always asks for another tool call.
+    struct SlowToolProvider;
+
+    #[async_trait::async_trait]
+    impl LLMProvider for SlowToolProvider {
+        async fn chat(
+            &self,
+            _messages: &[Value],
+            _tools: Option<&[Value]>,
+            _model: Option<&str>,
+            _max_tokens: u32,
+            _temperature: f64,
+        ) -> anyhow::Result<LLMResponse> {
+            tokio::time::sleep(Duration::from_secs(40)).await;
+            let mut response = text_response("", "tool_calls");
+            response.tool_calls.push(ToolCallRequest {
+                id: "tc_1".to_string(),
+                name: "noop".to_string(),
+                arguments: [("path".to_string(), json!("notes.md"))].into(),
+            });
+            Ok(response)
+        }
+
+        fn get_default_model(&self) -> &str {
+            "default-model"
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_turn_timeout_reports_progress() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut agent = test_agent(Arc::new(SlowToolProvider), tmp.path());
+        agent.set_turn_timeout(60);
+        let (session_key, chat_id) = throwaway_session();
+
+        let start = Instant::now();
+        let reply = agent.process_direct("go", &session_key, "test", &chat_id).await;
+        agent.sessions.delete(&session_key);
+
+        assert_eq!(start.elapsed(), Duration::from_secs(60));
+        assert!(reply.contains("ran out of time"), "{}", reply);
+        assert!(reply.contains("- noop (notes.md)"), "{}", reply);
+    }
+
+    #[test]
+    fn test_timeout_message_without_progress() {
+        let text = timeout_message(Duration::from_secs(30), &[]);
+        assert!(text.contains("limit: 30s") && text.contains("No steps were completed"));
+    }
+
+    // ── iteration limit ──
+
+    /// Asks for a tool call whenever tools are offered; otherwise summarizes.
+    /// Records the last user message of each call.
+    struct LoopingProvider {
+        prompts: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LLMProvider for LoopingProvider {
+        async fn chat(
+            &self,
+            messages: &[Value],
+            tools: Option<&[Value]>,
+            _model: Option<&str>,
+            _max_tokens: u32,
+            _temperature: f64,
+        ) -> anyhow::Result<LLMResponse> {
+            let last_user = messages
+                .iter()
+                .rev()
+                .find(|m| m["role"] == "user")
+                .and_then(|m| m["content"].as_str())
+                .unwrap_or("")
+                .to_string();
+            self.prompts.lock().unwrap().push(last_user);
+            if tools.is_none() {
+                return Ok(text_response("Did 5 steps; 2 remain.", "stop"));
+            }
+            let mut response = text_response("", "tool_calls");
+            response.tool_calls.push(ToolCallRequest {
+                id: "tc_1".to_string(),
+                name: "noop".to_string(),
+                arguments: Default::default(),
+            });
+            Ok(response)
+        }
+
+        fn get_default_model(&self) -> &str {
+            "default-model"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_iteration_limit_summarizes_and_continue_resumes() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let provider = Arc::new(LoopingProvider {
+            prompts: Default::default(),
+        });
+        let mut agent = test_agent(provider.clone(), tmp.path());
+        let (session_key, chat_id) = throwaway_session();
+
+        let reply = agent.process_direct("tidy up", &session_key, "test", &chat_id).await;
+        assert_eq!(reply, "Did 5 steps; 2 remain.");
+        {
+            let prompts = provider.prompts.lock().unwrap();
+            // Five tool iterations plus the summary call.
+            assert_eq!(prompts.len(), 6);
+            assert_eq!(prompts[5], ITERATION_LIMIT_PROMPT);
+        }
+        let session = agent.sessions.get_or_create(&session_key);
+        assert_eq!(session.metadata.get(UNFINISHED_TASK_KEY), Some(&json!(true)));
+
+        agent.process_direct("Continue!", &session_key, "test", &chat_id).await;
+        let session = agent.sessions.get_or_create(&session_key);
+        let saved_user = session.messages.iter().rev().find(|m| m["role"] == "user").cloned();
+        agent.sessions.delete(&session_key);
+
+        assert_eq!(provider.prompts.lock().unwrap()[6], RESUME_PROMPT);
+        assert_eq!(saved_user.unwrap()["content"], "Continue!");
+    }
+
+    #[test]
+    fn test_is_continue_request() {
+        assert!(is_continue_request("continue"));
+        assert!(is_continue_request("  Go on. "));
+        assert!(!is_continue_request("continue with something else"));
+    }
+
+    // ── mid-turn messages ──
+
+    /// On its first call, simulates the user writing again (plus a message
+    /// from another chat) and asks for a tool call; then answers "done".
+    struct InterruptedProvider {
+        inbound: UnboundedSender<InboundMessage>,
+        chat_id: String,
+        calls: std::sync::Mutex<Vec<Vec<Value>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LLMProvider for InterruptedProvider {
+        async fn chat(
+            &self,
+            messages: &[Value],
+            _tools: Option<&[Value]>,
+            _model: Option<&str>,
+            _max_tokens: u32,
+            _temperature: f64,
+        ) -> anyhow::Result<LLMResponse> {
+            let mut calls = self.calls.lock().unwrap();
+            calls.push(messages.to_vec());
+            if calls.len() > 1 {
+                return Ok(text_response("done", "stop"));
+            }
+            let _ = self
+                .inbound
+                .send(InboundMessage::new("test", "user", &self.chat_id, "use blue instead"));
+            let _ = self
+                .inbound
+                .send(InboundMessage::new("test", "user", "other-chat", "hello"));
+            let mut response = text_response("", "tool_calls");
+            response.tool_calls.push(ToolCallRequest {
+                id: "tc_1".to_string(),
+                name: "noop".to_string(),
+                arguments: Default::default(),
+            });
+            Ok(response)
+        }
+
+        fn get_default_model(&self) -> &str {
+            "default-model"
+        }
+    }
+
+    fn interrupted_agent(
+        mode: &str,
+        workspace: &std::path::Path,
+        chat_id: &str,
+    ) -> (AgentLoop, Arc<InterruptedProvider>) {
+        let (in_tx, in_rx) = tokio::sync::mpsc::unbounded_channel();
+        let provider = Arc::new(InterruptedProvider {
+            inbound: in_tx,
+            chat_id: chat_id.to_string(),
+            calls: Default::default(),
+        });
+        let mut agent = test_agent_with_inbound(provider.clone(), workspace, in_rx);
+        agent.set_mid_turn_mode(mode);
+        (agent, provider)
+    }
+
+    #[tokio::test]
+    async fn test_mid_turn_inject_steers_current_turn() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let (session_key, chat_id) = throwaway_session();
+        let (mut agent, provider) = interrupted_agent("inject", tmp.path(), &chat_id);
+        let mut msg = InboundMessage::new("test", "user", &chat_id, "paint it red");
+        msg.metadata.insert("session_key".to_string(), json!(session_key));
+
+        let reply = agent._process_message(&msg, false, true).await.unwrap();
+        let saved = agent.sessions.get_or_create(&session_key).messages.clone();
+        agent.sessions.delete(&session_key);
+
+        assert_eq!(reply.content, "done");
+        let second = &provider.calls.lock().unwrap()[1];
+        let last = second.last().unwrap()["content"].as_str().unwrap().to_string();
+        assert!(last.starts_with(INJECTED_PREFIX) && last.ends_with("use blue instead"));
+        assert_eq!(saved[1]["content"], "use blue instead");
+        assert_eq!(agent.pending.len(), 1);
+        assert_eq!(agent.pending[0].chat_id, "other-chat");
+    }
+
+    #[tokio::test]
+    async fn test_mid_turn_restart_merges_messages() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let (session_key, chat_id) = throwaway_session();
+        let (mut agent, provider) = interrupted_agent("restart", tmp.path(), &chat_id);
+        let msg = InboundMessage::new("test", "user", &chat_id, "paint it red");
+
+        assert!(agent._process_message(&msg, false, true).await.is_none());
+        agent.sessions.delete(&session_key);
+
+        assert_eq!(provider.calls.lock().unwrap().len(), 1);
+        assert_eq!(agent.pending.len(), 2);
+        assert_eq!(agent.pending[0].content, "paint it red\n\nuse blue instead");
+        assert_eq!(agent.pending[1].chat_id, "other-chat");
+    }
+
+    #[tokio::test]
+    async fn test_mid_turn_queue_leaves_messages_waiting() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let (session_key, chat_id) = throwaway_session();
+        let (mut agent, provider) = interrupted_agent("queue", tmp.path(), &chat_id);
+        let mut msg = InboundMessage::new("test", "user", &chat_id, "paint it red");
+        msg.metadata.insert("session_key".to_string(), json!(session_key));
+
+        agent._process_message(&msg, false, true).await.unwrap();
+        agent.sessions.delete(&session_key);
+
+        assert_eq!(provider.calls.lock().unwrap().len(), 2);
+        assert!(agent.pending.is_empty());
+        assert_eq!(agent.bus_inbound_rx.try_recv().unwrap().content, "use blue instead");
+    }
+
+    #[tokio::test]
+    async fn test_supervised_reply_declines_tool_call() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let (session_key, chat_id) = throwaway_session();
+        let (mut agent, provider) = interrupted_agent("queue", tmp.path(), &chat_id);
+        agent.set_supervised(true, 60);
+        let mut msg = InboundMessage::new("test", "user", &chat_id, "paint it red");
+        msg.metadata.insert("session_key".to_string(), json!(session_key));
+
+        agent._process_message(&msg, false, true).await.unwrap();
+        agent.sessions.delete(&session_key);
+
+        let second = &provider.calls.lock().unwrap()[1];
+        let result = second.last().unwrap()["content"].as_str().unwrap().to_string();
+        assert!(result.contains("declined 'noop' and replied: use blue instead"));
+        assert!(agent.pending.is_empty());
+        assert_eq!(agent.bus_inbound_rx.try_recv().unwrap().chat_id, "other-chat");
+    }
+
+    #[test]
+    fn test_mid_turn_mode_parse() {
+        assert_eq!(MidTurnMode::parse("Inject"), MidTurnMode::Inject);
+        assert_eq!(MidTurnMode::parse("cancel"), MidTurnMode::Restart);
+        assert_eq!(MidTurnMode::parse("bogus"), MidTurnMode::Queue);
+    }
+
+    // ── priorities ──
+
+    #[tokio::test]
+    async fn test_people_before_background_work() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let provider = Arc::new(RecordingProvider {
+            calls: Default::default(),
+        });
+        let mut agent = test_agent(provider, tmp.path());
+        let direct = |msg: InboundMessage| DirectRequest {
+            msg,
+            reply_tx: tokio::sync::oneshot::channel().0,
+            deltas: None,
+        };
+
+        let mut tick = InboundMessage::new("cli", "cron", "direct", "tick");
+        tick.priority = Priority::Low;
+        let person = InboundMessage::new("test", "user", "chat", "hi");
+        let mut api = InboundMessage::new("api", "api", "scripts", "status?");
+        api.timestamp = person.timestamp - chrono::Duration::seconds(1);
+        agent.pending_direct.push_back(direct(tick));
+        agent.pending_direct.push_back(direct(api));
+        agent.pending.push_back(person);
+
+        let mut order = Vec::new();
+        while let Some(incoming) = agent._take_most_urgent() {
+            order.push(match incoming {
+                Incoming::Bus(msg) => msg.content,
+                Incoming::Direct(req) => req.msg.content,
+            });
+        }
+        assert_eq!(order, ["status?", "hi", "tick"]);
+    }
+
+    #[tokio::test]
+    async fn test_direct_request_streams_on_seeded_session() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let provider = Arc::new(RecordingProvider {
+            calls: Default::default(),
+        });
+        let mut agent = test_agent(provider.clone(), tmp.path());
+        let (session_key, chat_id) = throwaway_session();
+        let mut msg = InboundMessage::new("test", "user", &chat_id, "and now?");
+        msg.metadata.insert(
+            "seed_history".to_string(),
+            json!([
+                {"role": "user", "content": "hi"},
+                {"role": "assistant", "content": "hello there"},
+            ]),
+        );
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        let (delta_tx, mut delta_rx) = tokio::sync::mpsc::unbounded_channel();
+        agent
+            .direct_sender()
+            .send(DirectRequest {
+                msg,
+                reply_tx,
+                deltas: Some(delta_tx),
+            })
+            .unwrap();
+
+        // The inbound bus is closed, so the loop stops after the request.
+        agent.run().await;
+
+        assert_eq!(delta_rx.recv().await.as_deref(), Some("done"));
+        assert!(delta_rx.recv().await.is_none());
+        assert_eq!(reply_rx.await.unwrap().unwrap(), "done");
+        let calls = provider.calls.lock().unwrap();
+        let contents: Vec<&str> = calls[0].1[1..]
+            .iter()
+            .filter_map(|m| m["content"].as_str())
+            .collect();
+        assert_eq!(contents[..2], ["hi", "hello there"]);
+        assert!(contents[2].contains("and now?"));
+        assert!(agent.sessions.get_cached(&session_key).is_none());
+        assert!(!agent.sessions.delete(&session_key));
+    }
+
+    // ── events ──
+
+    #[tokio::test]
+    async fn test_turn_publishes_events() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let provider = Arc::new(RecordingProvider {
+            calls: Default::default(),
+        });
+        let mut agent = test_agent(provider, tmp.path());
+        let mut events = agent.subscribe_events();
+        let (session_key, chat_id) = throwaway_session();
+
+        agent.process_direct("hi", &session_key, "test", &chat_id).await;
+        agent.sessions.delete(&session_key);
+
+        let mut received = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            received.push(event);
+        }
+        assert_eq!(received.len(), 3, "{:?}", received);
+        assert!(matches!(&received[0], AgentEvent::TurnStarted { session_key: k, .. } if *k == session_key));
+        assert!(matches!(&received[1], AgentEvent::LlmTokens { model, .. } if model == "default-model"));
+        assert!(matches!(
+            &received[2],
+            AgentEvent::TurnCompleted { iterations: 1, failed: false, .. }
+        ));
+        let turn_id = received[0].turn_id();
+        assert!(received.iter().all(|e| e.turn_id() == turn_id));
+    }
+
+    #[tokio::test]
+    async fn test_turn_id_reaches_reply() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let provider = Arc::new(RecordingProvider {
+            calls: Default::default(),
+        });
+        let mut agent = test_agent(provider, tmp.path());
+        let mut events = agent.subscribe_events();
+        let (session_key, chat_id) = throwaway_session();
+        let mut msg = InboundMessage::new("test", "user", &chat_id, "hi");
+        msg.metadata.insert("session_key".to_string(), json!(session_key));
+        msg.metadata.insert("turn_id".to_string(), json!("turn-1"));
+
+        let reply = agent._process_message(&msg, false, false).await.unwrap();
+        agent.sessions.delete(&session_key);
+
+        assert_eq!(reply.turn_id(), Some("turn-1"));
+        assert_eq!(events.try_recv().unwrap().turn_id(), "turn-1");
+    }
+}
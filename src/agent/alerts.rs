@@ -0,0 +1,189 @@
+//! Spending and activity alerts.
+//!
+//! [`AlertMonitor`] watches the agent loop against the thresholds in
+//! `agents.alerts`: the day's LLM spend, tool calls in the last hour and
+//! failed turns in a row. When one is crossed the owner is told on the
+//! alert chat and, with `pause`, the agent stops handling messages until an
+//! owner replies `/resume`. Each alert is given once until its cause goes
+//! away (the next day, a quieter hour, a successful turn).
+
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Local, NaiveDate};
+
+use crate::config::schema::AlertsConfig;
+
+/// The window tool calls are counted in.
+const TOOL_WINDOW: chrono::Duration = chrono::Duration::hours(1);
+
+/// A crossed threshold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    /// `daily_cost`, `tool_calls` or `consecutive_errors`.
+    pub kind: &'static str,
+    pub message: String,
+}
+
+/// Tracks activity against the alert thresholds.
+#[derive(Debug, Default)]
+pub struct AlertMonitor {
+    config: AlertsConfig,
+    tool_calls: VecDeque<DateTime<Local>>,
+    tool_calls_alerted: bool,
+    failed_turns: u32,
+    errors_alerted: bool,
+    cost_alerted_on: Option<NaiveDate>,
+    /// Why the agent is paused, until an owner resumes it.
+    paused: Option<String>,
+}
+
+impl AlertMonitor {
+    /// Watch the thresholds in `config`. A day's spend already past the
+    /// threshold, `spent_today`, is not alerted again.
+    pub fn new(config: AlertsConfig, spent_today: f64) -> Self {
+        let over = config.daily_cost_usd > 0.0 && spent_today >= config.daily_cost_usd;
+        Self {
+            config,
+            cost_alerted_on: over.then(|| Local::now().date_naive()),
+            ..Self::default()
+        }
+    }
+
+    /// The configured chat alerts go to.
+    pub fn target(&self) -> Option<(&str, &str)> {
+        let config = &self.config;
+        (!config.channel.is_empty() && !config.chat_id.is_empty())
+            .then_some((config.channel.as_str(), config.chat_id.as_str()))
+    }
+
+    /// Why the agent is paused, if it is.
+    pub fn paused(&self) -> Option<&str> {
+        self.paused.as_deref()
+    }
+
+    /// Let the agent handle messages again. Returns whether it was paused.
+    pub fn resume(&mut self) -> bool {
+        self.paused.take().is_some()
+    }
+
+    pub fn record_tool_call(&mut self, now: DateTime<Local>) {
+        if self.config.tool_calls_per_hour > 0 {
+            self.tool_calls.push_back(now);
+        }
+    }
+
+    pub fn record_turn(&mut self, failed: bool) {
+        if failed {
+            self.failed_turns += 1;
+        } else {
+            self.failed_turns = 0;
+            self.errors_alerted = false;
+        }
+    }
+
+    /// The alerts crossed since the last check, given the day's spend.
+    /// With `pause` configured, any alert pauses the agent.
+    pub fn check(&mut self, now: DateTime<Local>, spent_today: f64) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        let config = &self.config;
+
+        let today = now.date_naive();
+        if config.daily_cost_usd > 0.0
+            && spent_today >= config.daily_cost_usd
+            && self.cost_alerted_on != Some(today)
+        {
+            self.cost_alerted_on = Some(today);
+            alerts.push(Alert {
+                kind: "daily_cost",
+                message: format!(
+                    "Today's LLM spend is ${:.2}, past the alert threshold of ${:.2}.",
+                    spent_today, config.daily_cost_usd
+                ),
+            });
+        }
+
+        while self
+            .tool_calls
+            .front()
+            .is_some_and(|at| now - *at > TOOL_WINDOW)
+        {
+            self.tool_calls.pop_front();
+        }
+        let limit = config.tool_calls_per_hour as usize;
+        if limit > 0 && self.tool_calls.len() >= limit {
+            if !self.tool_calls_alerted {
+                self.tool_calls_alerted = true;
+                alerts.push(Alert {
+                    kind: "tool_calls",
+                    message: format!(
+                        "{} tool calls in the last hour, past the alert threshold of {}.",
+                        self.tool_calls.len(),
+                        limit
+                    ),
+                });
+            }
+        } else {
+            self.tool_calls_alerted = false;
+        }
+
+        let limit = config.consecutive_errors;
+        if limit > 0 && self.failed_turns >= limit && !self.errors_alerted {
+            self.errors_alerted = true;
+            alerts.push(Alert {
+                kind: "consecutive_errors",
+                message: format!("The last {} turns failed.", self.failed_turns),
+            });
+        }
+
+        if config.pause && self.paused.is_none() {
+            self.paused = alerts.first().map(|alert| alert.message.clone());
+        }
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alerts_fire_once_and_pause() {
+        let config = AlertsConfig {
+            daily_cost_usd: 5.0,
+            tool_calls_per_hour: 3,
+            consecutive_errors: 2,
+            pause: true,
+            ..AlertsConfig::default()
+        };
+        let mut monitor = AlertMonitor::new(config, 0.0);
+        let now = Local::now();
+        assert!(monitor.check(now, 1.0).is_empty());
+
+        for _ in 0..3 {
+            monitor.record_tool_call(now);
+        }
+        monitor.record_turn(true);
+        monitor.record_turn(true);
+        let kinds: Vec<&str> = monitor.check(now, 6.0).iter().map(|a| a.kind).collect();
+        assert_eq!(kinds, ["daily_cost", "tool_calls", "consecutive_errors"]);
+        assert!(monitor.paused().unwrap().contains("$6.00"));
+        assert!(monitor.check(now, 7.0).is_empty());
+
+        // Causes gone: the call and error alerts are armed again.
+        monitor.record_turn(false);
+        monitor.record_turn(true);
+        monitor.record_turn(true);
+        let later = now + chrono::Duration::minutes(61);
+        let kinds: Vec<&str> = monitor.check(later, 1.0).iter().map(|a| a.kind).collect();
+        assert_eq!(kinds, ["consecutive_errors"]);
+        assert!(monitor.resume());
+        assert!(!monitor.resume());
+
+        // Already over the spend threshold at startup.
+        let config = AlertsConfig {
+            daily_cost_usd: 5.0,
+            ..AlertsConfig::default()
+        };
+        assert!(AlertMonitor::new(config, 9.0).check(now, 9.0).is_empty());
+    }
+}
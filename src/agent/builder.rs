@@ -0,0 +1,327 @@
+//! Building an [`AgentLoop`].
+//!
+//! [`AgentLoopBuilder`] is how the `nanoclaw` binary, and programs that
+//! embed nanoclaw, create an agent: from a [`Config`] with everything it
+//! configures, or from just a provider and a workspace with defaults for the
+//! rest. Either way extra tools and hooks can be added before building.
+//!
+//! ```no_run
+//! use nanoclaw::agent::builder::AgentLoopBuilder;
+//! use nanoclaw::config::loader::load_config;
+//!
+//! # async fn example() {
+//! let config = load_config(None);
+//! let mut agent = AgentLoopBuilder::from_config(&config).build();
+//! let reply = agent
+//!     .process_direct("What's in my workspace?", "embed:1", "embed", "1")
+//!     .await;
+//! println!("{}", reply);
+//! # }
+//! ```
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::Mutex;
+
+use crate::agent::agent_loop::AgentLoop;
+use crate::agent::hooks::Hook;
+use crate::agent::skill_scope::{ScopeMode, SkillScopeHook};
+use crate::agent::tools::base::Tool;
+use crate::agent::usage::UsageStore;
+use crate::audit::AuditLog;
+use crate::bus::events::{InboundMessage, OutboundMessage};
+use crate::config::loader::get_data_dir;
+use crate::config::schema::{AgentDefaults, Config, ExecToolConfig, SubagentConfig};
+use crate::cron::service::CronService;
+use crate::observability::transcripts::TranscriptHook;
+use crate::providers::base::LLMProvider;
+use crate::providers::create_provider;
+
+/// The message bus an agent loop is connected to.
+struct Bus {
+    inbound_rx: UnboundedReceiver<InboundMessage>,
+    outbound_tx: UnboundedSender<OutboundMessage>,
+    inbound_tx: UnboundedSender<InboundMessage>,
+}
+
+/// Builds an [`AgentLoop`]; see the [module docs](self).
+pub struct AgentLoopBuilder {
+    provider: Arc<dyn LLMProvider>,
+    workspace: PathBuf,
+    model: String,
+    max_iterations: u32,
+    brave_api_key: Option<String>,
+    exec: ExecToolConfig,
+    cron_service: Option<Arc<Mutex<CronService>>>,
+    subagents: SubagentConfig,
+    bus: Option<Bus>,
+    config: Option<Config>,
+    tools: Vec<Box<dyn Tool>>,
+    hooks: Vec<Arc<dyn Hook>>,
+}
+
+impl AgentLoopBuilder {
+    /// An agent using `provider` with its files in `workspace`, and
+    /// defaults for everything else.
+    pub fn new(provider: Arc<dyn LLMProvider>, workspace: impl Into<PathBuf>) -> Self {
+        let defaults = AgentDefaults::default();
+        Self {
+            provider,
+            workspace: workspace.into(),
+            model: defaults.model,
+            max_iterations: defaults.max_tool_iterations,
+            brave_api_key: None,
+            exec: ExecToolConfig::default(),
+            cron_service: None,
+            subagents: SubagentConfig::default(),
+            bus: None,
+            config: None,
+            tools: Vec::new(),
+            hooks: Vec::new(),
+        }
+    }
+
+    /// An agent set up as `config` says: provider, model, tools, skills,
+    /// profiles, roles, limits, usage, alerts, audit log and transcripts.
+    pub fn from_config(config: &Config) -> Self {
+        let search_key = &config.tools.web.search.api_key;
+        Self {
+            model: config.agents.defaults.model.clone(),
+            max_iterations: config.agents.defaults.max_tool_iterations,
+            brave_api_key: Some(search_key.clone()).filter(|k| !k.is_empty()),
+            exec: config.tools.exec_.clone(),
+            subagents: config.agents.subagents.clone(),
+            config: Some(config.clone()),
+            ..Self::new(create_provider(config), config.workspace_path())
+        }
+    }
+
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// LLM calls allowed in one turn.
+    pub fn max_iterations(mut self, max_iterations: u32) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Use the Brave Search API for `web_search`.
+    pub fn brave_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.brave_api_key = Some(api_key.into());
+        self
+    }
+
+    /// Limit `exec` commands to `timeout_secs`, and with
+    /// `restrict_to_workspace` to the workspace.
+    pub fn exec(mut self, timeout_secs: u64, restrict_to_workspace: bool) -> Self {
+        self.exec = ExecToolConfig {
+            timeout: timeout_secs,
+            restrict_to_workspace,
+        };
+        self
+    }
+
+    /// Let the agent schedule jobs and reminders in `cron_service`.
+    pub fn cron_service(mut self, cron_service: Arc<Mutex<CronService>>) -> Self {
+        self.cron_service = Some(cron_service);
+        self
+    }
+
+    pub fn subagents(mut self, config: SubagentConfig) -> Self {
+        self.subagents = config;
+        self
+    }
+
+    /// Connect the agent to a message bus: it reads messages from
+    /// `inbound_rx` when [run](AgentLoop::run), sends messages for chats to
+    /// `outbound_tx`, and hands results of background work back through
+    /// `inbound_tx`. Without a bus, messages the agent sends to chats are
+    /// dropped.
+    pub fn bus(
+        mut self,
+        inbound_rx: UnboundedReceiver<InboundMessage>,
+        outbound_tx: UnboundedSender<OutboundMessage>,
+        inbound_tx: UnboundedSender<InboundMessage>,
+    ) -> Self {
+        self.bus = Some(Bus {
+            inbound_rx,
+            outbound_tx,
+            inbound_tx,
+        });
+        self
+    }
+
+    /// Add a tool, replacing a built-in one of the same name.
+    pub fn tool(mut self, tool: Box<dyn Tool>) -> Self {
+        self.tools.push(tool);
+        self
+    }
+
+    /// Add a hook, run after the built-in and configured ones.
+    pub fn hook(mut self, hook: Arc<dyn Hook>) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+
+    /// The agent. Must be called within a Tokio runtime.
+    pub fn build(self) -> AgentLoop {
+        let bus = self.bus.unwrap_or_else(|| {
+            let (inbound_tx, inbound_rx) = mpsc::unbounded_channel();
+            let (outbound_tx, _) = mpsc::unbounded_channel();
+            Bus {
+                inbound_rx,
+                outbound_tx,
+                inbound_tx,
+            }
+        });
+        let mut agent_loop = AgentLoop::new(
+            bus.inbound_rx,
+            bus.outbound_tx,
+            bus.inbound_tx,
+            self.provider,
+            self.workspace,
+            self.model,
+            self.max_iterations,
+            self.brave_api_key,
+            self.exec.timeout,
+            self.exec.restrict_to_workspace,
+            self.cron_service,
+            self.subagents,
+        );
+        if let Some(config) = self.config {
+            _configure(&mut agent_loop, &config);
+        }
+        for tool in self.tools {
+            agent_loop.register_tool(tool);
+        }
+        for hook in self.hooks {
+            agent_loop.add_hook(hook);
+        }
+        agent_loop
+    }
+}
+
+/// Apply the settings in `config` that [`AgentLoop::new`] does not take.
+fn _configure(agent_loop: &mut AgentLoop, config: &Config) {
+    let defaults = &config.agents.defaults;
+    agent_loop.set_streaming(defaults.streaming);
+    agent_loop.set_turn_timeout(defaults.turn_timeout_secs);
+    agent_loop.set_mid_turn_mode(&defaults.mid_turn_messages);
+    agent_loop.set_supervised(defaults.supervised, defaults.confirm_timeout_secs);
+    agent_loop.set_show_tool_calls(defaults.show_tool_calls);
+    agent_loop.set_workspaces(config.agents.workspaces.clone());
+    agent_loop.set_context_budget(config.agents.context.clone());
+    agent_loop.set_channel_prompts(config.channels.system_prompts.clone());
+    agent_loop.set_tools_config(&config.tools);
+    agent_loop.set_profiles(config.agents.profiles.clone());
+    agent_loop.set_roles(config.agents.roles.clone());
+    agent_loop.set_skills_config(Arc::new(config.clone()));
+    if let Some(mode) = ScopeMode::parse(&config.agents.skills.tool_scope) {
+        agent_loop.add_hook(Arc::new(SkillScopeHook::new(mode)));
+    }
+    agent_loop.set_usage_store(UsageStore::new(
+        get_data_dir().join("usage.jsonl"),
+        config.agents.costs.clone(),
+    ));
+    agent_loop.set_alerts(config.agents.alerts.clone());
+    agent_loop.set_audit_log(Arc::new(AuditLog::open()));
+    let transcripts = &config.observability.transcripts;
+    if transcripts.enabled {
+        agent_loop.add_hook(Arc::new(TranscriptHook::new(
+            get_data_dir().join("transcripts"),
+            transcripts,
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    use async_trait::async_trait;
+    use serde_json::Value;
+
+    use crate::providers::base::{LLMResponse, ToolCallRequest};
+    use crate::session::manager::SessionManager;
+
+    /// Calls `weather` once, then answers with what it returned.
+    struct WeatherProvider;
+
+    #[async_trait]
+    impl LLMProvider for WeatherProvider {
+        async fn chat(
+            &self,
+            messages: &[Value],
+            _tools: Option<&[Value]>,
+            _model: Option<&str>,
+            _max_tokens: u32,
+            _temperature: f64,
+        ) -> anyhow::Result<LLMResponse> {
+            let last = messages.last().unwrap();
+            let (content, tool_calls) = if last["role"] == "tool" {
+                (last["content"].as_str().map(str::to_string), vec![])
+            } else {
+                let call = ToolCallRequest {
+                    id: "c1".to_string(),
+                    name: "weather".to_string(),
+                    arguments: HashMap::new(),
+                };
+                (None, vec![call])
+            };
+            Ok(LLMResponse {
+                content,
+                tool_calls,
+                finish_reason: "stop".to_string(),
+                usage: HashMap::new(),
+            })
+        }
+
+        fn get_default_model(&self) -> &str {
+            "test-model"
+        }
+    }
+
+    struct WeatherTool;
+
+    #[async_trait]
+    impl Tool for WeatherTool {
+        fn name(&self) -> &str {
+            "weather"
+        }
+
+        fn description(&self) -> &str {
+            "Today's weather"
+        }
+
+        fn parameters(&self) -> Value {
+            serde_json::json!({"type": "object", "properties": {}})
+        }
+
+        async fn execute(&self, _params: HashMap<String, Value>) -> String {
+            "Sunny, 21°C".to_string()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_build_with_tool_and_no_bus() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut agent = AgentLoop::builder(Arc::new(WeatherProvider), tmp.path())
+            .model("test-model")
+            .max_iterations(3)
+            .tool(Box::new(WeatherTool))
+            .build();
+        assert!(agent.tool_names().contains(&"weather".to_string()));
+
+        let session_key = format!("test:{}", uuid::Uuid::new_v4());
+        let reply = agent
+            .process_direct("weather?", &session_key, "embed", "1")
+            .await;
+        SessionManager::new(tmp.path()).delete(&session_key);
+        assert_eq!(reply, "Sunny, 21°C");
+    }
+}
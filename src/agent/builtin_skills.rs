@@ -0,0 +1,102 @@
+//! Skills bundled with the binary.
+//!
+//! The skills under `src/agent/builtin_skills/` are compiled in. Those
+//! enabled in `agents.skills.builtin` are written to each workspace's
+//! `builtin_skills/` directory when the agent starts, where
+//! [`SkillsLoader`](super::skills::SkillsLoader) finds them. `nanoclaw
+//! onboard` also copies them to `workspace/skills/`, where they can be edited
+//! and shadow the bundled versions.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+/// Name and `SKILL.md` of each bundled skill.
+pub const BUNDLED: &[(&str, &str)] = &[
+    ("git-workflow", include_str!("builtin_skills/git-workflow/SKILL.md")),
+    ("meeting-notes", include_str!("builtin_skills/meeting-notes/SKILL.md")),
+    ("travel-planning", include_str!("builtin_skills/travel-planning/SKILL.md")),
+    ("weekly-review", include_str!("builtin_skills/weekly-review/SKILL.md")),
+];
+
+/// Names of all bundled skills.
+pub fn names() -> Vec<String> {
+    BUNDLED.iter().map(|(name, _)| name.to_string()).collect()
+}
+
+/// Write the `enabled` bundled skills to `dir` and remove the other bundled
+/// ones. Directories that are not bundled skills are left alone.
+pub fn sync(dir: &Path, enabled: &[String]) -> Result<()> {
+    for (name, content) in BUNDLED {
+        let skill_dir = dir.join(name);
+        if !enabled.iter().any(|e| e == name) {
+            if skill_dir.exists() {
+                fs::remove_dir_all(&skill_dir)
+                    .with_context(|| format!("cannot remove {}", skill_dir.display()))?;
+            }
+            continue;
+        }
+        let file = skill_dir.join("SKILL.md");
+        if fs::read_to_string(&file).is_ok_and(|old| old == *content) {
+            continue;
+        }
+        fs::create_dir_all(&skill_dir)
+            .with_context(|| format!("cannot create {}", skill_dir.display()))?;
+        fs::write(&file, content).with_context(|| format!("cannot write {}", file.display()))?;
+    }
+    Ok(())
+}
+
+/// Copy the bundled skills to `skills_dir` for customization, skipping any
+/// that already exist there. Returns the names copied.
+pub fn copy_to(skills_dir: &Path) -> Result<Vec<String>> {
+    let mut copied = Vec::new();
+    for (name, content) in BUNDLED {
+        let skill_dir = skills_dir.join(name);
+        if skill_dir.exists() {
+            continue;
+        }
+        fs::create_dir_all(&skill_dir)
+            .with_context(|| format!("cannot create {}", skill_dir.display()))?;
+        fs::write(skill_dir.join("SKILL.md"), content)?;
+        copied.push(name.to_string());
+    }
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::skills::SkillMeta;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_bundled_skills_are_valid() {
+        for (name, content) in BUNDLED {
+            let meta = SkillMeta::try_parse(content).unwrap();
+            assert_eq!(meta.name.as_deref(), Some(*name));
+            assert!(meta.description.is_some() && meta.version.is_some());
+        }
+    }
+
+    #[test]
+    fn test_sync_and_copy() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("builtin_skills");
+        fs::create_dir_all(dir.join("mine")).unwrap();
+
+        sync(&dir, &names()).unwrap();
+        assert!(dir.join("weekly-review/SKILL.md").is_file());
+        sync(&dir, &["git-workflow".to_string()]).unwrap();
+        assert!(dir.join("git-workflow/SKILL.md").is_file());
+        assert!(!dir.join("weekly-review").exists());
+        assert!(dir.join("mine").exists());
+
+        let skills = tmp.path().join("skills");
+        fs::create_dir_all(skills.join("meeting-notes")).unwrap();
+        let copied = copy_to(&skills).unwrap();
+        assert_eq!(copied.len(), BUNDLED.len() - 1);
+        assert!(!copied.contains(&"meeting-notes".to_string()));
+    }
+}
@@ -0,0 +1,248 @@
+//! Recovery from context-length errors.
+//!
+//! When a provider rejects a request because the prompt is too long, the
+//! agent loop compacts the message list and retries once: bulky tool results
+//! are cut down, and the oldest conversation turns are replaced by a short
+//! summary written by the model.
+
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+use crate::providers::base::LLMProvider;
+
+/// Tool results longer than this are cut during compaction.
+const MAX_TOOL_RESULT_CHARS: usize = 2_000;
+
+/// Characters of each message shown to the summarizer.
+const SUMMARY_INPUT_CHARS: usize = 1_000;
+
+/// Reply used when the prompt still does not fit after compaction.
+pub const OVERFLOW_MESSAGE: &str = "This conversation is too long for the model's context window, \
+even after compacting it. Please start a new conversation or ask a shorter question.";
+
+/// Phrases providers use when a request exceeds the context window.
+const OVERFLOW_MARKERS: &[&str] = &[
+    "context_length_exceeded",
+    "context length",
+    "context window",
+    "maximum context",
+    "prompt is too long",
+    "too many tokens",
+    "reduce the length",
+    "input is too long",
+];
+
+/// Whether a provider error reports that the prompt was too long.
+pub fn is_context_overflow(error: &str) -> bool {
+    let lower = error.to_lowercase();
+    OVERFLOW_MARKERS.iter().any(|m| lower.contains(m))
+}
+
+/// Shrink the message list so it is more likely to fit. Returns `false` if
+/// nothing could be removed.
+pub async fn compact_messages(
+    provider: &dyn LLMProvider,
+    model: &str,
+    messages: &mut Vec<Value>,
+) -> bool {
+    let shrunk = shrink_tool_results(messages, MAX_TOOL_RESULT_CHARS);
+
+    let summarized = match oldest_turns_end(messages) {
+        Some(end) => {
+            let summary = _summarize(provider, model, &messages[1..end]).await;
+            let dropped = end - 1;
+            messages.drain(1..end);
+            let note = match summary {
+                Some(summary) => format!("\n\n## Earlier Conversation (summary)\n{}", summary),
+                None => format!("\n\n({} earlier messages were dropped to save space.)", dropped),
+            };
+            if let Some(system) = messages.first_mut() {
+                let content = system["content"].as_str().unwrap_or("").to_string();
+                system["content"] = json!(content + &note);
+            }
+            dropped
+        }
+        None => 0,
+    };
+
+    info!(
+        "Compacted context: {} tool results shortened, {} old messages summarized",
+        shrunk, summarized
+    );
+    shrunk > 0 || summarized > 0
+}
+
+/// Cut tool results longer than `max_chars`, keeping their beginning.
+/// Returns the number of results shortened.
+pub fn shrink_tool_results(messages: &mut [Value], max_chars: usize) -> usize {
+    let mut count = 0;
+    for msg in messages.iter_mut().filter(|m| m["role"] == "tool") {
+        let Some(content) = msg["content"].as_str() else {
+            continue;
+        };
+        let total = content.chars().count();
+        if total <= max_chars {
+            continue;
+        }
+        let head: String = content.chars().take(max_chars).collect();
+        msg["content"] = json!(format!(
+            "{}\n[... output shortened from {} characters ...]",
+            head, total
+        ));
+        count += 1;
+    }
+    count
+}
+
+/// End (exclusive) of the oldest half of the conversation history, or `None`
+/// when there is too little history to summarize.
+///
+/// History runs from after the system prompt to the current user message.
+/// The cut is placed just before a user message so that tool calls and their
+/// results stay together.
+pub fn oldest_turns_end(messages: &[Value]) -> Option<usize> {
+    let current = messages.iter().rposition(|m| m["role"] == "user")?;
+    let midpoint = 1 + (current.saturating_sub(1)) / 2;
+    (midpoint..current)
+        .find(|&i| messages[i]["role"] == "user" && i > 1)
+        .or_else(|| (current > 2).then_some(current))
+}
+
+/// Ask the model for a short summary of `messages`.
+async fn _summarize(provider: &dyn LLMProvider, model: &str, messages: &[Value]) -> Option<String> {
+    let transcript: Vec<String> = messages
+        .iter()
+        .filter_map(|m| {
+            let text = m["content"].as_str().filter(|t| !t.is_empty())?;
+            let text: String = text.chars().take(SUMMARY_INPUT_CHARS).collect();
+            Some(format!("{}: {}", m["role"].as_str().unwrap_or("?"), text))
+        })
+        .collect();
+    if transcript.is_empty() {
+        return None;
+    }
+
+    let request = vec![
+        json!({"role": "system", "content": "Summarize this conversation in a few short \
+            bullet points. Keep facts, decisions, names and open tasks; skip pleasantries."}),
+        json!({"role": "user", "content": transcript.join("\n\n")}),
+    ];
+    match provider.chat(&request, None, Some(model), 1024, 0.2).await {
+        Ok(r) if r.finish_reason != "error" => r.content.filter(|c| !c.trim().is_empty()),
+        Ok(r) => {
+            warn!("Summarizing old messages failed: {}", r.content.unwrap_or_default());
+            None
+        }
+        Err(e) => {
+            warn!("Summarizing old messages failed: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::base::LLMResponse;
+    use async_trait::async_trait;
+
+    fn msg(role: &str, content: &str) -> Value {
+        json!({"role": role, "content": content})
+    }
+
+    struct Summarizer;
+
+    #[async_trait]
+    impl LLMProvider for Summarizer {
+        async fn chat(
+            &self,
+            _messages: &[Value],
+            _tools: Option<&[Value]>,
+            _model: Option<&str>,
+            _max_tokens: u32,
+            _temperature: f64,
+        ) -> anyhow::Result<LLMResponse> {
+            Ok(LLMResponse {
+                content: Some("- user likes tea".to_string()),
+                tool_calls: vec![],
+                finish_reason: "stop".to_string(),
+                usage: Default::default(),
+            })
+        }
+
+        fn get_default_model(&self) -> &str {
+            "test"
+        }
+    }
+
+    #[test]
+    fn test_is_context_overflow() {
+        assert!(is_context_overflow(
+            r#"Error calling LLM (HTTP 400): {"error":{"code":"context_length_exceeded"}}"#
+        ));
+        assert!(is_context_overflow("prompt is too long: 210000 tokens > 200000 maximum"));
+        assert!(!is_context_overflow("Error calling LLM (HTTP 401): invalid api key"));
+    }
+
+    #[test]
+    fn test_shrink_tool_results() {
+        let mut messages = vec![
+            msg("tool", &"x".repeat(5000)),
+            msg("tool", "short"),
+            msg("assistant", &"y".repeat(5000)),
+        ];
+        assert_eq!(shrink_tool_results(&mut messages, 100), 1);
+        let cut = messages[0]["content"].as_str().unwrap();
+        assert!(cut.starts_with(&"x".repeat(100)) && cut.contains("5000 characters"));
+        assert_eq!(messages[1]["content"], "short");
+        assert_eq!(messages[2]["content"].as_str().unwrap().len(), 5000);
+    }
+
+    #[test]
+    fn test_oldest_turns_end_cuts_at_user_message() {
+        let messages = vec![
+            msg("system", "s"),
+            msg("user", "u1"),
+            msg("assistant", "a1"),
+            msg("user", "u2"),
+            msg("assistant", "a2"),
+            msg("user", "u3"),
+            msg("assistant", "a3"),
+            msg("user", "current"),
+        ];
+        assert_eq!(oldest_turns_end(&messages), Some(5));
+    }
+
+    #[test]
+    fn test_oldest_turns_end_without_history() {
+        let messages = vec![msg("system", "s"), msg("user", "current")];
+        assert_eq!(oldest_turns_end(&messages), None);
+    }
+
+    #[tokio::test]
+    async fn test_compact_messages_summarizes_oldest_turns() {
+        let mut messages = vec![
+            msg("system", "prompt"),
+            msg("user", "I like tea"),
+            msg("assistant", "Noted"),
+            msg("user", "and biscuits"),
+            msg("assistant", "Noted too"),
+            msg("user", "current"),
+            msg("tool", &"z".repeat(10_000)),
+        ];
+
+        assert!(compact_messages(&Summarizer, "test", &mut messages).await);
+
+        let system = messages[0]["content"].as_str().unwrap();
+        assert!(system.contains("Earlier Conversation") && system.contains("user likes tea"));
+        assert_eq!(messages[1]["content"], "and biscuits");
+        assert_eq!(messages[3]["content"], "current");
+        assert!(messages[4]["content"].as_str().unwrap().len() < 3_000);
+    }
+
+    #[tokio::test]
+    async fn test_compact_messages_nothing_to_do() {
+        let mut messages = vec![msg("system", "prompt"), msg("user", "current")];
+        assert!(!compact_messages(&Summarizer, "test", &mut messages).await);
+    }
+}
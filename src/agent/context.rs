@@ -3,15 +3,20 @@
 //! Assembles bootstrap files, memory, skills, and conversation history into
 //! a coherent prompt for the LLM.
 
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
 use base64::Engine;
 use chrono::Local;
 use serde_json::{json, Value};
+use tracing::debug;
 
 use crate::agent::memory::MemoryStore;
 use crate::agent::skills::SkillsLoader;
+use crate::agent::tokens::{estimate_message_tokens, estimate_tokens, truncate_to_tokens};
+use crate::bus::events::{Attachment, AttachmentKind};
+use crate::config::schema::ContextConfig;
 
 /// Well-known files that are loaded from the workspace root when present.
 const BOOTSTRAP_FILES: &[&str] = &[
@@ -27,6 +32,10 @@ pub struct ContextBuilder {
     pub workspace: PathBuf,
     pub memory: MemoryStore,
     pub skills: SkillsLoader,
+    /// Prompt token budget; `None` builds prompts without limits.
+    pub budget: Option<ContextConfig>,
+    /// Extra system-prompt text by channel name.
+    pub channel_prompts: HashMap<String, String>,
 }
 
 impl ContextBuilder {
@@ -36,28 +45,49 @@ impl ContextBuilder {
             workspace: workspace.to_path_buf(),
             memory: MemoryStore::new(workspace),
             skills: SkillsLoader::new(workspace, None),
+            budget: None,
+            channel_prompts: HashMap::new(),
         }
     }
 
+    /// Limit the size of built prompts. A `max_tokens` of 0 removes the limit.
+    pub fn set_budget(&mut self, budget: ContextConfig) {
+        self.budget = (budget.max_tokens > 0).then_some(budget);
+    }
+
+    /// Set extra system-prompt text for messages from each channel.
+    pub fn set_channel_prompts(&mut self, prompts: HashMap<String, String>) {
+        self.channel_prompts = prompts;
+    }
+
     // ------------------------------------------------------------------
     // Public API
     // ------------------------------------------------------------------
 
     /// Build the system prompt from bootstrap files, memory, and skills.
+    ///
+    /// With a budget set, each section is trimmed to its share.
     pub fn build_system_prompt(&self, skill_names: Option<&[String]>) -> String {
         let mut parts: Vec<String> = Vec::new();
 
         // Core identity.
-        parts.push(self._get_identity());
+        let identity = self._get_identity();
+        let system_share = self
+            ._share(|b| b.system_percent)
+            .map(|share| share.saturating_sub(estimate_tokens(&identity)));
+        parts.push(identity);
 
         // Bootstrap files.
-        let bootstrap = self._load_bootstrap_files();
+        let bootstrap = _fit(self._load_bootstrap_files(), system_share);
         if !bootstrap.is_empty() {
             parts.push(bootstrap);
         }
 
         // Memory context.
-        let memory = self.memory.get_memory_context();
+        let memory = _fit(
+            self.memory.get_memory_context(),
+            self._share(|b| b.memory_percent),
+        );
         if !memory.is_empty() {
             parts.push(format!("# Memory\n\n{}", memory));
         }
@@ -65,15 +95,38 @@ impl ContextBuilder {
         // Skills -- progressive loading:
         // 1. Always-loaded skills: full content included directly.
         let always_skills = self.skills.get_always_skills();
-        if !always_skills.is_empty() {
-            let always_content = self.skills.load_skills_for_context(&always_skills);
-            if !always_content.is_empty() {
-                parts.push(format!("# Active Skills\n\n{}", always_content));
-            }
-        }
+        let always_content = if always_skills.is_empty() {
+            String::new()
+        } else {
+            self.skills.load_skills_for_context(&always_skills)
+        };
 
         // 2. Available skills: summary only (agent can read_file for details).
         let skills_summary = self.skills.build_skills_summary();
+
+        // 3. Explicitly requested skills.
+        let requested = match skill_names {
+            Some(names) if !names.is_empty() => self.skills.load_skills_for_context(names),
+            _ => String::new(),
+        };
+
+        // Over budget, requested skills are kept first, then the summary,
+        // and always-loaded content (also listed in the summary) goes first.
+        let mut skills_share = self._share(|b| b.skills_percent);
+        let mut take = |text: String| {
+            let text = _fit(text, skills_share);
+            if let Some(share) = skills_share.as_mut() {
+                *share = share.saturating_sub(estimate_tokens(&text));
+            }
+            text
+        };
+        let requested = take(requested);
+        let skills_summary = take(skills_summary);
+        let always_content = take(always_content);
+
+        if !always_content.is_empty() {
+            parts.push(format!("# Active Skills\n\n{}", always_content));
+        }
         if !skills_summary.is_empty() {
             parts.push(format!(
                 "# Skills\n\n\
@@ -85,27 +138,23 @@ impl ContextBuilder {
                 skills_summary
             ));
         }
-
-        // 3. Explicitly requested skills.
-        if let Some(names) = skill_names {
-            if !names.is_empty() {
-                let requested = self.skills.load_skills_for_context(names);
-                if !requested.is_empty() {
-                    parts.push(format!("# Requested Skills\n\n{}", requested));
-                }
-            }
+        if !requested.is_empty() {
+            parts.push(format!("# Requested Skills\n\n{}", requested));
         }
 
         parts.join("\n\n---\n\n")
     }
 
     /// Build the complete message list for an LLM call.
+    ///
+    /// With a budget set, the oldest history messages are dropped until the
+    /// whole prompt fits; the system prompt and current message are always kept.
     pub fn build_messages(
         &self,
         history: &[Value],
         current_message: &str,
         skill_names: Option<&[String]>,
-        media: Option<&[String]>,
+        attachments: Option<&[Attachment]>,
         channel: Option<&str>,
         chat_id: Option<&str>,
     ) -> Vec<Value> {
@@ -117,14 +166,37 @@ impl ContextBuilder {
             system_prompt
                 .push_str(&format!("\n\n## Current Session\nChannel: {}\nChat ID: {}", ch, cid));
         }
+        if let Some(extra) = channel
+            .and_then(|ch| self.channel_prompts.get(ch))
+            .filter(|text| !text.trim().is_empty())
+        {
+            system_prompt.push_str(&format!("\n\n## Channel Guidelines\n{}", extra.trim()));
+        }
         messages.push(json!({"role": "system", "content": system_prompt}));
 
+        // Current user message (with optional attachments).
+        let user_content = Self::_build_user_content(current_message, attachments);
+        let user_message = json!({"role": "user", "content": user_content});
+
         // History.
+        let history = match &self.budget {
+            Some(budget) => {
+                let used = estimate_message_tokens(&messages[0])
+                    + estimate_message_tokens(&user_message);
+                let kept = _trim_history(history, budget.max_tokens.saturating_sub(used));
+                if kept.len() < history.len() {
+                    debug!(
+                        "Dropped {} old history messages to fit the context budget",
+                        history.len() - kept.len()
+                    );
+                }
+                kept
+            }
+            None => history,
+        };
         messages.extend(history.iter().cloned());
 
-        // Current user message (with optional image attachments).
-        let user_content = Self::_build_user_content(current_message, media);
-        messages.push(json!({"role": "user", "content": user_content}));
+        messages.push(user_message);
 
         messages
     }
@@ -168,6 +240,13 @@ impl ContextBuilder {
     // Private helpers
     // ------------------------------------------------------------------
 
+    /// Token share of a prompt section, or `None` without a budget.
+    fn _share(&self, percent: impl Fn(&ContextConfig) -> u8) -> Option<usize> {
+        self.budget
+            .as_ref()
+            .map(|b| b.max_tokens * percent(b) as usize / 100)
+    }
+
     /// Core identity section including current time and workspace info.
     fn _get_identity(&self) -> String {
         let now = Local::now().format("%Y-%m-%d %H:%M (%A)").to_string();
@@ -222,40 +301,57 @@ When remembering something, write to {workspace_path}/memory/MEMORY.md"#
         parts.join("\n\n")
     }
 
-    /// Build user message content with optional base64-encoded images.
+    /// `text` followed by a note on each attachment, so the model knows the
+    /// files are there and where to find them.
+    pub fn with_attachment_notes(text: &str, attachments: &[Attachment]) -> String {
+        let mut content = text.to_string();
+        for attachment in attachments {
+            if !content.is_empty() {
+                content.push('\n');
+            }
+            content.push_str(&attachment.describe());
+        }
+        content
+    }
+
+    /// Build user message content with the attachments.
     ///
-    /// If media contains image files, returns a JSON array of content parts.
-    /// Otherwise returns a plain string value.
-    fn _build_user_content(text: &str, media: Option<&[String]>) -> Value {
-        let media = match media {
-            Some(m) if !m.is_empty() => m,
+    /// Images are included for vision models, local ones base64-encoded, and
+    /// returned as a JSON array of content parts. Other attachments are only
+    /// noted in the text, for the model to open with its tools.
+    fn _build_user_content(text: &str, attachments: Option<&[Attachment]>) -> Value {
+        let attachments = match attachments {
+            Some(a) if !a.is_empty() => a,
             _ => return Value::String(text.to_string()),
         };
+        let text = Self::with_attachment_notes(text, attachments);
 
         let mut images: Vec<Value> = Vec::new();
 
-        for path_str in media {
-            let path = Path::new(path_str);
-            if !path.is_file() {
+        for attachment in attachments {
+            if attachment.kind() != AttachmentKind::Image {
                 continue;
             }
-            let mime = _guess_mime(path_str);
-            if !mime.starts_with("image/") {
-                continue;
-            }
-            if let Ok(bytes) = fs::read(path) {
-                let b64 = base64::engine::general_purpose::STANDARD.encode(&bytes);
-                images.push(json!({
-                    "type": "image_url",
-                    "image_url": {
-                        "url": format!("data:{};base64,{}", mime, b64),
-                    }
-                }));
-            }
+            let url = if attachment.is_url() {
+                attachment.source.clone()
+            } else {
+                match fs::read(&attachment.source) {
+                    Ok(bytes) => format!(
+                        "data:{};base64,{}",
+                        attachment.mime,
+                        base64::engine::general_purpose::STANDARD.encode(&bytes)
+                    ),
+                    Err(_) => continue,
+                }
+            };
+            images.push(json!({
+                "type": "image_url",
+                "image_url": {"url": url}
+            }));
         }
 
         if images.is_empty() {
-            return Value::String(text.to_string());
+            return Value::String(text);
         }
 
         // Append text part after images.
@@ -264,27 +360,39 @@ When remembering something, write to {workspace_path}/memory/MEMORY.md"#
     }
 }
 
-/// Guess MIME type from a file extension.
-fn _guess_mime(path: &str) -> String {
-    let lower = path.to_lowercase();
-    if lower.ends_with(".jpg") || lower.ends_with(".jpeg") {
-        "image/jpeg".to_string()
-    } else if lower.ends_with(".png") {
-        "image/png".to_string()
-    } else if lower.ends_with(".gif") {
-        "image/gif".to_string()
-    } else if lower.ends_with(".webp") {
-        "image/webp".to_string()
-    } else if lower.ends_with(".svg") {
-        "image/svg+xml".to_string()
-    } else {
-        "application/octet-stream".to_string()
+/// Trim `text` to `max_tokens` when a limit is given.
+fn _fit(text: String, max_tokens: Option<usize>) -> String {
+    match max_tokens {
+        Some(max) => truncate_to_tokens(&text, max),
+        None => text,
     }
 }
 
+/// Keep the most recent history messages that fit in `max_tokens`.
+///
+/// Tool results left at the start without their assistant message are
+/// dropped as well, since providers reject them.
+fn _trim_history(history: &[Value], max_tokens: usize) -> &[Value] {
+    let mut used = 0;
+    let mut start = history.len();
+    for (i, msg) in history.iter().enumerate().rev() {
+        used += estimate_message_tokens(msg);
+        if used > max_tokens {
+            break;
+        }
+        start = i;
+    }
+    while start < history.len() && history[start]["role"] == "tool" {
+        start += 1;
+    }
+    &history[start..]
+}
+
+/// Guess MIME type from a file extension.
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bus::events::guess_mime;
     use tempfile::TempDir;
 
     /// Helper: create a ContextBuilder backed by a temporary workspace.
@@ -294,47 +402,47 @@ mod tests {
         (tmp, cb)
     }
 
-    // ----- _guess_mime -----
+    // ----- guess_mime -----
 
     #[test]
     fn test_guess_mime_jpg() {
-        assert_eq!(_guess_mime("photo.jpg"), "image/jpeg");
+        assert_eq!(guess_mime("photo.jpg"), "image/jpeg");
     }
 
     #[test]
     fn test_guess_mime_jpeg() {
-        assert_eq!(_guess_mime("photo.jpeg"), "image/jpeg");
+        assert_eq!(guess_mime("photo.jpeg"), "image/jpeg");
     }
 
     #[test]
     fn test_guess_mime_png() {
-        assert_eq!(_guess_mime("image.png"), "image/png");
+        assert_eq!(guess_mime("image.png"), "image/png");
     }
 
     #[test]
     fn test_guess_mime_gif() {
-        assert_eq!(_guess_mime("anim.gif"), "image/gif");
+        assert_eq!(guess_mime("anim.gif"), "image/gif");
     }
 
     #[test]
     fn test_guess_mime_webp() {
-        assert_eq!(_guess_mime("pic.webp"), "image/webp");
+        assert_eq!(guess_mime("pic.webp"), "image/webp");
     }
 
     #[test]
     fn test_guess_mime_svg() {
-        assert_eq!(_guess_mime("icon.svg"), "image/svg+xml");
+        assert_eq!(guess_mime("icon.svg"), "image/svg+xml");
     }
 
     #[test]
     fn test_guess_mime_unknown() {
-        assert_eq!(_guess_mime("archive.tar.gz"), "application/octet-stream");
+        assert_eq!(guess_mime("archive.tar.gz"), "application/octet-stream");
     }
 
     #[test]
     fn test_guess_mime_case_insensitive() {
-        assert_eq!(_guess_mime("PHOTO.JPG"), "image/jpeg");
-        assert_eq!(_guess_mime("image.PNG"), "image/png");
+        assert_eq!(guess_mime("PHOTO.JPG"), "image/jpeg");
+        assert_eq!(guess_mime("image.PNG"), "image/png");
     }
 
     // ----- build_system_prompt -----
@@ -423,6 +531,44 @@ mod tests {
         assert!(system_content.contains("Chat ID: 12345"));
     }
 
+    #[test]
+    fn test_build_messages_adds_channel_prompt() {
+        let (_tmp, mut cb) = make_context();
+        cb.set_channel_prompts(HashMap::from([(
+            "whatsapp".to_string(),
+            "Keep replies under 500 chars, no markdown tables.".to_string(),
+        )]));
+        let messages = cb.build_messages(&[], "hi", None, None, Some("whatsapp"), Some("1"));
+        let system_content = messages[0]["content"].as_str().unwrap();
+        assert!(system_content
+            .ends_with("## Channel Guidelines\nKeep replies under 500 chars, no markdown tables."));
+
+        let messages = cb.build_messages(&[], "hi", None, None, Some("telegram"), Some("1"));
+        let system_content = messages[0]["content"].as_str().unwrap();
+        assert!(!system_content.contains("Channel Guidelines"));
+    }
+
+    #[test]
+    fn test_build_messages_with_attachments() {
+        let (tmp, cb) = make_context();
+        let photo = tmp.path().join("photo.png");
+        fs::write(&photo, b"png").unwrap();
+        let attachments = [
+            Attachment::new(photo.to_string_lossy()),
+            Attachment::new("/media/memo.ogg"),
+        ];
+        let messages = cb.build_messages(&[], "look", None, Some(&attachments), None, None);
+        let parts = messages[1]["content"].as_array().unwrap();
+        assert_eq!(parts.len(), 2);
+        assert!(parts[0]["image_url"]["url"]
+            .as_str()
+            .unwrap()
+            .starts_with("data:image/png;base64,"));
+        let text = parts[1]["text"].as_str().unwrap();
+        assert!(text.starts_with("look\n[image: "), "{}", text);
+        assert!(text.ends_with("\n[audio: /media/memo.ogg]"), "{}", text);
+    }
+
     #[test]
     fn test_build_messages_without_history() {
         let (_tmp, cb) = make_context();
@@ -503,4 +649,75 @@ mod tests {
         // Empty tool_calls should not add the key.
         assert!(messages[0].get("tool_calls").is_none());
     }
+
+    // ----- budgeting -----
+
+    fn budget(max_tokens: usize) -> ContextConfig {
+        ContextConfig {
+            max_tokens,
+            ..ContextConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_budget_truncates_memory_to_its_share() {
+        let tmp = TempDir::new().unwrap();
+        let memory_dir = tmp.path().join("memory");
+        fs::create_dir_all(&memory_dir).unwrap();
+        fs::write(memory_dir.join("MEMORY.md"), "remember this line\n".repeat(2000)).unwrap();
+        let mut cb = ContextBuilder::new(tmp.path());
+
+        let unbounded = cb.build_system_prompt(None);
+        cb.set_budget(budget(4000));
+        let prompt = cb.build_system_prompt(None);
+
+        assert!(prompt.len() < unbounded.len());
+        assert!(prompt.contains("remember this line"));
+        assert!(prompt.contains("truncated to fit the context budget"));
+        // Memory share is 15% of 4000 tokens.
+        let memory = prompt.split("# Memory").nth(1).unwrap();
+        assert!(estimate_tokens(memory) <= 600 + 10);
+    }
+
+    #[test]
+    fn test_budget_drops_oldest_history() {
+        let (_tmp, mut cb) = make_context();
+        cb.set_budget(budget(2000));
+        let history: Vec<Value> = (0..100)
+            .map(|i| json!({"role": "user", "content": format!("message {} {}", i, "x".repeat(200))}))
+            .collect();
+
+        let messages = cb.build_messages(&history, "latest", None, None, None, None);
+
+        assert!(messages.len() > 2 && messages.len() < history.len() + 2);
+        assert!(crate::agent::tokens::estimate_messages_tokens(&messages) <= 2000);
+        // The most recent history and the current message survive.
+        let last_history = &messages[messages.len() - 2];
+        assert!(last_history["content"].as_str().unwrap().starts_with("message 99 "));
+        assert_eq!(messages.last().unwrap()["content"], "latest");
+    }
+
+    #[test]
+    fn test_budget_zero_disables_limit() {
+        let (_tmp, mut cb) = make_context();
+        cb.set_budget(budget(0));
+        assert!(cb.budget.is_none());
+        let history = vec![json!({"role": "user", "content": "x".repeat(100_000)})];
+        let messages = cb.build_messages(&history, "hi", None, None, None, None);
+        assert_eq!(messages.len(), 3);
+    }
+
+    #[test]
+    fn test_trim_history_skips_orphaned_tool_results() {
+        let history = vec![
+            json!({"role": "assistant", "content": "x".repeat(400), "tool_calls": [
+                {"id": "1", "type": "function", "function": {"name": "exec", "arguments": "{}"}}
+            ]}),
+            json!({"role": "tool", "tool_call_id": "1", "name": "exec", "content": "ok"}),
+            json!({"role": "assistant", "content": "done"}),
+        ];
+        let kept = _trim_history(&history, 20);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0]["content"], "done");
+    }
 }
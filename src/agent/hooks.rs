@@ -0,0 +1,495 @@
+//! Hooks around LLM and tool calls in the agent loop.
+//!
+//! A [`Hook`] can observe or change what the agent sends to the LLM and
+//! what tools it runs. Hooks run in registration order; every phase has a
+//! no-op default so a hook only implements the phases it needs.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::sync::broadcast;
+use tracing::debug;
+
+use crate::agent::tokens::estimate_messages_tokens;
+use crate::bus::events::AgentEvent;
+use crate::providers::base::{LLMResponse, ToolCallRequest};
+
+/// Where the current agent turn comes from.
+#[derive(Debug, Clone)]
+pub struct HookContext {
+    /// ID shared by all events of one agent turn.
+    pub turn_id: String,
+    pub channel: String,
+    pub chat_id: String,
+    pub session_key: String,
+    /// Model used for the LLM calls of this turn.
+    pub model: String,
+    /// Zero-based LLM iteration within the turn.
+    pub iteration: u32,
+}
+
+/// Outcome of a pre-tool-call hook.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolDecision {
+    /// Run the tool (possibly with arguments changed by the hook).
+    Allow,
+    /// Skip the tool; the string is returned to the LLM as its result.
+    Deny(String),
+}
+
+/// Extension point around the agent loop's LLM and tool calls.
+#[async_trait]
+pub trait Hook: Send + Sync {
+    /// Hook name, used in logs.
+    fn name(&self) -> &str;
+
+    /// Called before each LLM call with the full message list.
+    async fn before_llm_call(&self, _ctx: &HookContext, _messages: &mut Vec<Value>) {}
+
+    /// Called after each successful LLM call with the message list it was
+    /// given and the response.
+    async fn after_llm_call(
+        &self,
+        _ctx: &HookContext,
+        _messages: &[Value],
+        _response: &mut LLMResponse,
+    ) {
+    }
+
+    /// Called before a tool runs. Returning [`ToolDecision::Deny`] skips the
+    /// tool and any later hooks.
+    async fn before_tool_call(
+        &self,
+        _ctx: &HookContext,
+        _call: &mut ToolCallRequest,
+    ) -> ToolDecision {
+        ToolDecision::Allow
+    }
+
+    /// Called after a tool runs (or was denied) with its result.
+    async fn after_tool_call(
+        &self,
+        _ctx: &HookContext,
+        _call: &ToolCallRequest,
+        _result: &mut String,
+    ) {
+    }
+}
+
+/// Ordered collection of hooks run by the agent loop.
+#[derive(Clone, Default)]
+pub struct HookRegistry {
+    hooks: Vec<Arc<dyn Hook>>,
+}
+
+impl HookRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a registry with the built-in hooks.
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        registry.register(Arc::new(LoggingHook));
+        registry.register(Arc::new(UsageHook));
+        registry
+    }
+
+    /// Add a hook after the existing ones.
+    pub fn register(&mut self, hook: Arc<dyn Hook>) {
+        self.hooks.push(hook);
+    }
+
+    /// Names of the registered hooks, in order.
+    pub fn names(&self) -> Vec<String> {
+        self.hooks.iter().map(|h| h.name().to_string()).collect()
+    }
+
+    pub async fn before_llm_call(&self, ctx: &HookContext, messages: &mut Vec<Value>) {
+        for hook in &self.hooks {
+            hook.before_llm_call(ctx, messages).await;
+        }
+    }
+
+    pub async fn after_llm_call(
+        &self,
+        ctx: &HookContext,
+        messages: &[Value],
+        response: &mut LLMResponse,
+    ) {
+        for hook in &self.hooks {
+            hook.after_llm_call(ctx, messages, response).await;
+        }
+    }
+
+    /// Run pre-tool hooks until one denies the call.
+    pub async fn before_tool_call(
+        &self,
+        ctx: &HookContext,
+        call: &mut ToolCallRequest,
+    ) -> ToolDecision {
+        for hook in &self.hooks {
+            if let ToolDecision::Deny(reason) = hook.before_tool_call(ctx, call).await {
+                debug!("Hook {} denied tool {}", hook.name(), call.name);
+                return ToolDecision::Deny(reason);
+            }
+        }
+        ToolDecision::Allow
+    }
+
+    pub async fn after_tool_call(
+        &self,
+        ctx: &HookContext,
+        call: &ToolCallRequest,
+        result: &mut String,
+    ) {
+        for hook in &self.hooks {
+            hook.after_tool_call(ctx, call, result).await;
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Built-in hooks
+// ---------------------------------------------------------------------------
+
+/// Debug logging of tool calls.
+pub struct LoggingHook;
+
+#[async_trait]
+impl Hook for LoggingHook {
+    fn name(&self) -> &str {
+        "logging"
+    }
+
+    async fn before_tool_call(
+        &self,
+        ctx: &HookContext,
+        call: &mut ToolCallRequest,
+    ) -> ToolDecision {
+        debug!(
+            "Executing tool: {} (id: {}, session: {}, iteration: {})",
+            call.name,
+            call.id,
+            ctx.session_key,
+            ctx.iteration + 1
+        );
+        ToolDecision::Allow
+    }
+
+    async fn after_tool_call(
+        &self,
+        _ctx: &HookContext,
+        call: &ToolCallRequest,
+        result: &mut String,
+    ) {
+        debug!("Tool {} result ({}B)", call.name, result.len());
+    }
+}
+
+/// Records our prompt size estimate next to the provider's reported usage.
+pub struct UsageHook;
+
+#[async_trait]
+impl Hook for UsageHook {
+    fn name(&self) -> &str {
+        "usage"
+    }
+
+    async fn after_llm_call(
+        &self,
+        _ctx: &HookContext,
+        messages: &[Value],
+        response: &mut LLMResponse,
+    ) {
+        let estimate = estimate_messages_tokens(messages) as i64;
+        response
+            .usage
+            .insert("estimated_prompt_tokens".to_string(), estimate);
+        debug!(
+            "Prompt tokens: ~{} estimated, {} reported",
+            estimate,
+            response
+                .usage
+                .get("prompt_tokens")
+                .map(|n| n.to_string())
+                .unwrap_or_else(|| "none".to_string())
+        );
+    }
+}
+
+/// Publishes tool and token events on the agent event channel.
+pub struct EventHook {
+    events: broadcast::Sender<AgentEvent>,
+    /// Start of running tool calls, by call ID, and LLM calls, by turn.
+    started: Mutex<HashMap<String, Instant>>,
+}
+
+impl EventHook {
+    pub fn new(events: broadcast::Sender<AgentEvent>) -> Self {
+        Self {
+            events,
+            started: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl Hook for EventHook {
+    fn name(&self) -> &str {
+        "events"
+    }
+
+    async fn before_llm_call(&self, ctx: &HookContext, _messages: &mut Vec<Value>) {
+        if let Ok(mut started) = self.started.lock() {
+            started.insert(format!("llm:{}", ctx.turn_id), Instant::now());
+        }
+    }
+
+    async fn after_llm_call(
+        &self,
+        ctx: &HookContext,
+        _messages: &[Value],
+        response: &mut LLMResponse,
+    ) {
+        let started = self
+            .started
+            .lock()
+            .ok()
+            .and_then(|mut s| s.remove(&format!("llm:{}", ctx.turn_id)));
+        let usage = |key: &str| response.usage.get(key).copied().unwrap_or(0).max(0) as u64;
+        let prompt_tokens = match usage("prompt_tokens") {
+            0 => usage("estimated_prompt_tokens"),
+            n => n,
+        };
+        // Sending only fails when nobody is subscribed.
+        let _ = self.events.send(AgentEvent::LlmTokens {
+            turn_id: ctx.turn_id.clone(),
+            model: ctx.model.clone(),
+            prompt_tokens,
+            completion_tokens: usage("completion_tokens"),
+            duration_ms: started.map_or(0, |t| t.elapsed().as_millis() as u64),
+        });
+    }
+
+    async fn before_tool_call(
+        &self,
+        ctx: &HookContext,
+        call: &mut ToolCallRequest,
+    ) -> ToolDecision {
+        if let Ok(mut started) = self.started.lock() {
+            started.insert(call.id.clone(), Instant::now());
+        }
+        let _ = self.events.send(AgentEvent::ToolCallStarted {
+            turn_id: ctx.turn_id.clone(),
+            call_id: call.id.clone(),
+            tool: call.name.clone(),
+        });
+        ToolDecision::Allow
+    }
+
+    async fn after_tool_call(
+        &self,
+        ctx: &HookContext,
+        call: &ToolCallRequest,
+        result: &mut String,
+    ) {
+        let started = self
+            .started
+            .lock()
+            .ok()
+            .and_then(|mut s| s.remove(&call.id));
+        let _ = self.events.send(AgentEvent::ToolCallFinished {
+            turn_id: ctx.turn_id.clone(),
+            call_id: call.id.clone(),
+            tool: call.name.clone(),
+            result_bytes: result.len(),
+            duration_ms: started.map_or(0, |t| t.elapsed().as_millis() as u64),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx() -> HookContext {
+        HookContext {
+            channel: "cli".to_string(),
+            chat_id: "direct".to_string(),
+            turn_id: "turn-1".to_string(),
+            session_key: "cli:direct".to_string(),
+            model: "test-model".to_string(),
+            iteration: 0,
+        }
+    }
+
+    fn call(name: &str) -> ToolCallRequest {
+        ToolCallRequest {
+            id: "tc_1".to_string(),
+            name: name.to_string(),
+            arguments: HashMap::new(),
+        }
+    }
+
+    /// Records the phases it sees and optionally denies a tool.
+    struct Recorder {
+        label: &'static str,
+        deny: Option<&'static str>,
+        seen: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Hook for Recorder {
+        fn name(&self) -> &str {
+            self.label
+        }
+
+        async fn before_tool_call(
+            &self,
+            _ctx: &HookContext,
+            call: &mut ToolCallRequest,
+        ) -> ToolDecision {
+            self.seen
+                .lock()
+                .unwrap()
+                .push(format!("{}:before:{}", self.label, call.name));
+            match self.deny {
+                Some(tool) if tool == call.name => ToolDecision::Deny("not allowed".to_string()),
+                _ => ToolDecision::Allow,
+            }
+        }
+
+        async fn after_tool_call(
+            &self,
+            _ctx: &HookContext,
+            _call: &ToolCallRequest,
+            result: &mut String,
+        ) {
+            self.seen
+                .lock()
+                .unwrap()
+                .push(format!("{}:after", self.label));
+            result.push_str(&format!(" [{}]", self.label));
+        }
+    }
+
+    fn registry(deny: Option<&'static str>) -> (HookRegistry, Arc<Mutex<Vec<String>>>) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut registry = HookRegistry::new();
+        registry.register(Arc::new(Recorder {
+            label: "a",
+            deny,
+            seen: seen.clone(),
+        }));
+        registry.register(Arc::new(Recorder {
+            label: "b",
+            deny: None,
+            seen: seen.clone(),
+        }));
+        (registry, seen)
+    }
+
+    // ── ordering ──
+
+    #[tokio::test]
+    async fn test_hooks_run_in_registration_order() {
+        let (registry, seen) = registry(None);
+        let mut tc = call("exec");
+        assert_eq!(
+            registry.before_tool_call(&ctx(), &mut tc).await,
+            ToolDecision::Allow
+        );
+        let mut result = "ok".to_string();
+        registry.after_tool_call(&ctx(), &tc, &mut result).await;
+
+        assert_eq!(result, "ok [a] [b]");
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec!["a:before:exec", "b:before:exec", "a:after", "b:after"]
+        );
+    }
+
+    // ── deny ──
+
+    #[tokio::test]
+    async fn test_deny_stops_later_hooks() {
+        let (registry, seen) = registry(Some("exec"));
+        let decision = registry.before_tool_call(&ctx(), &mut call("exec")).await;
+        assert_eq!(decision, ToolDecision::Deny("not allowed".to_string()));
+        assert_eq!(*seen.lock().unwrap(), vec!["a:before:exec"]);
+    }
+
+    // ── built-ins ──
+
+    #[tokio::test]
+    async fn test_usage_hook_records_estimate() {
+        let messages = vec![serde_json::json!({"role": "user", "content": "hello there"})];
+        let mut response = LLMResponse {
+            content: Some("hi".to_string()),
+            tool_calls: vec![],
+            finish_reason: "stop".to_string(),
+            usage: HashMap::new(),
+        };
+        HookRegistry::with_builtins()
+            .after_llm_call(&ctx(), &messages, &mut response)
+            .await;
+        assert_eq!(
+            response.usage.get("estimated_prompt_tokens"),
+            Some(&(estimate_messages_tokens(&messages) as i64))
+        );
+    }
+
+    #[test]
+    fn test_builtin_names() {
+        assert_eq!(
+            HookRegistry::with_builtins().names(),
+            vec!["logging", "usage"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_event_hook_publishes_tool_and_token_events() {
+        let (tx, mut rx) = broadcast::channel(16);
+        let hook = EventHook::new(tx);
+        let mut tc = call("exec");
+        hook.before_tool_call(&ctx(), &mut tc).await;
+        hook.after_tool_call(&ctx(), &tc, &mut "12345".to_string())
+            .await;
+        let mut response = LLMResponse {
+            content: None,
+            tool_calls: vec![],
+            finish_reason: "stop".to_string(),
+            usage: HashMap::from([
+                ("estimated_prompt_tokens".to_string(), 7),
+                ("completion_tokens".to_string(), 3),
+            ]),
+        };
+        hook.after_llm_call(&ctx(), &[], &mut response).await;
+
+        assert!(
+            matches!(rx.recv().await.unwrap(), AgentEvent::ToolCallStarted { tool, .. } if tool == "exec")
+        );
+        assert!(matches!(
+            rx.recv().await.unwrap(),
+            AgentEvent::ToolCallFinished {
+                result_bytes: 5,
+                ..
+            }
+        ));
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            AgentEvent::LlmTokens {
+                turn_id: "turn-1".to_string(),
+                model: "test-model".to_string(),
+                prompt_tokens: 7,
+                completion_tokens: 3,
+                duration_ms: 0,
+            }
+        );
+    }
+}
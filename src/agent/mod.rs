@@ -1,6 +1,22 @@
 pub mod tools;
+pub mod alerts;
+pub mod compaction;
 pub mod context;
+pub mod hooks;
+pub mod tokens;
+pub mod usage;
 pub mod memory;
+pub mod profiles;
+pub mod progress;
+pub mod roles;
+pub mod builder;
+pub mod builtin_skills;
+pub mod skill_scope;
+pub mod skill_test;
 pub mod skills;
+pub mod skills_index;
+pub mod skills_install;
 pub mod subagent;
+pub mod supervised;
+pub mod workspaces;
 pub mod agent_loop;
@@ -0,0 +1,167 @@
+//! Agent profiles: several personas hosted by one agent loop.
+//!
+//! An inbound message is handled by a profile when it starts with
+//! `@<name>` or when its chat matches one of the profile's routes. Messages
+//! that match no profile are handled by the default agent.
+
+use std::fs;
+use std::path::Path;
+
+use tracing::warn;
+
+use crate::config::schema::AgentProfileConfig;
+
+/// Picks the profile that should handle an inbound message.
+#[derive(Debug, Clone, Default)]
+pub struct ProfileRouter {
+    profiles: Vec<AgentProfileConfig>,
+}
+
+impl ProfileRouter {
+    /// Create a router over the configured profiles.
+    pub fn new(profiles: Vec<AgentProfileConfig>) -> Self {
+        Self { profiles }
+    }
+
+    /// Find the profile for a message. Returns the profile and the message
+    /// content with any `@name` prefix removed.
+    ///
+    /// A prefix wins over routes, and a `channel:chat_id` route wins over a
+    /// plain `channel` route.
+    pub fn route(
+        &self,
+        channel: &str,
+        chat_id: &str,
+        content: &str,
+    ) -> Option<(&AgentProfileConfig, String)> {
+        if let Some((profile, rest)) = self._match_prefix(content) {
+            return Some((profile, rest.to_string()));
+        }
+
+        let chat = format!("{}:{}", channel, chat_id);
+        self.profiles
+            .iter()
+            .find(|p| p.routes.contains(&chat))
+            .or_else(|| {
+                self.profiles
+                    .iter()
+                    .find(|p| p.routes.iter().any(|r| r == channel))
+            })
+            .map(|p| (p, content.to_string()))
+    }
+
+    /// Match a leading `@name` (case-insensitive) against profile names.
+    fn _match_prefix<'a>(&self, content: &'a str) -> Option<(&AgentProfileConfig, &'a str)> {
+        let rest = content.trim_start().strip_prefix('@')?;
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let (name, rest) = rest.split_at(end);
+        let profile = self
+            .profiles
+            .iter()
+            .find(|p| p.name.eq_ignore_ascii_case(name))?;
+        Some((profile, rest.trim_start()))
+    }
+}
+
+/// Whether a profile may use the named tool.
+pub fn allows_tool(profile: &AgentProfileConfig, tool: &str) -> bool {
+    profile.tools.is_empty() || profile.tools.iter().any(|t| t == tool)
+}
+
+/// Persona instructions for a profile, read from its prompt file.
+pub fn load_profile_prompt(profile: &AgentProfileConfig, workspace: &Path) -> Option<String> {
+    let file = profile.system_prompt_file.as_ref()?;
+    match fs::read_to_string(workspace.join(file)) {
+        Ok(content) => Some(content),
+        Err(e) => {
+            warn!("Cannot read prompt file for profile {}: {}", profile.name, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn profile(name: &str, routes: &[&str]) -> AgentProfileConfig {
+        AgentProfileConfig {
+            name: name.to_string(),
+            routes: routes.iter().map(|r| r.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn router() -> ProfileRouter {
+        ProfileRouter::new(vec![
+            profile("coder", &["telegram:42"]),
+            profile("journaler", &["whatsapp"]),
+            profile("home", &["telegram"]),
+        ])
+    }
+
+    // ── prefix ──
+
+    #[test]
+    fn test_route_by_prefix_strips_it() {
+        let router = router();
+        let (p, content) = router.route("cli", "direct", "@Coder fix the build").unwrap();
+        assert_eq!(p.name, "coder");
+        assert_eq!(content, "fix the build");
+    }
+
+    #[test]
+    fn test_prefix_wins_over_route() {
+        let router = router();
+        let (p, _) = router.route("whatsapp", "1", "@home lights off").unwrap();
+        assert_eq!(p.name, "home");
+    }
+
+    #[test]
+    fn test_unknown_prefix_falls_through() {
+        let router = router();
+        assert!(router.route("cli", "direct", "@nobody hi").is_none());
+        let (p, content) = router.route("whatsapp", "1", "@nobody hi").unwrap();
+        assert_eq!(p.name, "journaler");
+        assert_eq!(content, "@nobody hi");
+    }
+
+    // ── routes ──
+
+    #[test]
+    fn test_chat_route_wins_over_channel_route() {
+        let router = router();
+        assert_eq!(router.route("telegram", "42", "hi").unwrap().0.name, "coder");
+        assert_eq!(router.route("telegram", "7", "hi").unwrap().0.name, "home");
+    }
+
+    #[test]
+    fn test_no_match_uses_default() {
+        assert!(router().route("feishu", "1", "hi").is_none());
+        assert!(ProfileRouter::default().route("telegram", "1", "@coder hi").is_none());
+    }
+
+    // ── helpers ──
+
+    #[test]
+    fn test_allows_tool() {
+        let mut p = profile("coder", &[]);
+        assert!(allows_tool(&p, "exec"));
+        p.tools = vec!["read_file".to_string()];
+        assert!(allows_tool(&p, "read_file"));
+        assert!(!allows_tool(&p, "exec"));
+    }
+
+    #[test]
+    fn test_load_profile_prompt() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(tmp.path().join("CODER.md"), "You write Rust.").unwrap();
+        let mut p = profile("coder", &[]);
+        assert!(load_profile_prompt(&p, tmp.path()).is_none());
+        p.system_prompt_file = Some("CODER.md".to_string());
+        assert_eq!(load_profile_prompt(&p, tmp.path()).unwrap(), "You write Rust.");
+        p.system_prompt_file = Some("missing.md".to_string());
+        assert!(load_profile_prompt(&p, tmp.path()).is_none());
+    }
+}
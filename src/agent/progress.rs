@@ -0,0 +1,185 @@
+//! Showing the agent's work while a turn runs.
+//!
+//! `nanoclaw agent -v` prints each tool call and the start of its result as
+//! it happens, through [`TerminalProgressHook`]. With
+//! `agents.defaults.showToolCalls`, the agent loop posts the same, as
+//! [`chat_message`], in the chat the turn came from.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use async_trait::async_trait;
+
+use crate::agent::hooks::{Hook, HookContext, ToolDecision};
+use crate::providers::base::ToolCallRequest;
+
+/// Arguments that best say what a call does, in order of preference.
+const MAIN_ARGUMENTS: &[&str] = &[
+    "command", "path", "task", "content", "message", "query", "url",
+];
+
+/// Characters of a call's main argument shown.
+const MAX_ARGUMENT_CHARS: usize = 80;
+
+/// Lines and characters of a result shown.
+const MAX_RESULT_LINES: usize = 3;
+const MAX_RESULT_CHARS: usize = 200;
+
+/// The argument that best says what `call` does, or all of them as JSON.
+pub fn main_argument(call: &ToolCallRequest) -> String {
+    MAIN_ARGUMENTS
+        .iter()
+        .find_map(|key| call.arguments.get(*key).and_then(|v| v.as_str()))
+        .map(str::to_string)
+        .unwrap_or_else(|| serde_json::to_string(&call.arguments).unwrap_or_default())
+}
+
+/// `call` on one line, e.g. `exec: ls -la`.
+pub fn call_summary(call: &ToolCallRequest) -> String {
+    let argument = main_argument(call);
+    let line = argument.lines().next().unwrap_or_default();
+    if line.is_empty() || line == "{}" {
+        return call.name.clone();
+    }
+    let mut summary = format!("{}: {}", call.name, _truncate(line, MAX_ARGUMENT_CHARS));
+    if line.len() < argument.len() && !summary.ends_with('…') {
+        summary.push('…');
+    }
+    summary
+}
+
+/// The first lines of `result`.
+pub fn result_preview(result: &str) -> String {
+    let result = result.trim();
+    let lines: Vec<&str> = result.lines().take(MAX_RESULT_LINES).collect();
+    let shown = lines.join("\n");
+    let mut preview = _truncate(&shown, MAX_RESULT_CHARS);
+    if shown.len() < result.len() && !preview.ends_with('…') {
+        preview.push('…');
+    }
+    preview
+}
+
+/// What is posted in the chat for a finished call.
+pub fn chat_message(call: &ToolCallRequest, result: &str) -> String {
+    let preview = result_preview(result);
+    if preview.is_empty() {
+        return format!("🔧 {}", call_summary(call));
+    }
+    format!("🔧 {}\n{}", call_summary(call), preview)
+}
+
+/// `text` cut to `max` characters, marked with an ellipsis when cut.
+fn _truncate(text: &str, max: usize) -> String {
+    let mut cut: String = text.chars().take(max).collect();
+    if cut.len() < text.len() {
+        cut.push('…');
+    }
+    cut
+}
+
+// ---------------------------------------------------------------------------
+// Terminal
+// ---------------------------------------------------------------------------
+
+/// Prints tool calls and their results to stderr, so they do not mix with
+/// a reply that is piped elsewhere.
+#[derive(Default)]
+pub struct TerminalProgressHook {
+    started: Mutex<HashMap<String, Instant>>,
+}
+
+impl TerminalProgressHook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl Hook for TerminalProgressHook {
+    fn name(&self) -> &str {
+        "progress"
+    }
+
+    async fn before_tool_call(
+        &self,
+        _ctx: &HookContext,
+        call: &mut ToolCallRequest,
+    ) -> ToolDecision {
+        if let Ok(mut started) = self.started.lock() {
+            started.insert(call.id.clone(), Instant::now());
+        }
+        eprintln!("  🔧 {}", call_summary(call));
+        ToolDecision::Allow
+    }
+
+    async fn after_tool_call(
+        &self,
+        _ctx: &HookContext,
+        call: &ToolCallRequest,
+        result: &mut String,
+    ) {
+        let elapsed = self
+            .started
+            .lock()
+            .ok()
+            .and_then(|mut s| s.remove(&call.id))
+            .map_or(0.0, |t| t.elapsed().as_secs_f64());
+        let preview = result_preview(result).replace('\n', "\n     ");
+        eprintln!("     ↳ {} ({:.1}s)", preview, elapsed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn call(name: &str, arguments: serde_json::Value) -> ToolCallRequest {
+        ToolCallRequest {
+            id: "c1".to_string(),
+            name: name.to_string(),
+            arguments: serde_json::from_value(arguments).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_call_summary() {
+        assert_eq!(
+            call_summary(&call("exec", json!({"command": "ls -la", "timeout": 5}))),
+            "exec: ls -la"
+        );
+        assert_eq!(
+            call_summary(&call(
+                "write_file",
+                json!({"path": "a.txt", "content": "x"})
+            )),
+            "write_file: a.txt"
+        );
+        assert_eq!(
+            call_summary(&call("exec", json!({"command": "echo a\necho b"}))),
+            "exec: echo a…"
+        );
+        assert_eq!(call_summary(&call("subagents", json!({}))), "subagents");
+        let long = "x".repeat(200);
+        let summary = call_summary(&call("exec", json!({ "command": long })));
+        assert_eq!(
+            summary.chars().count(),
+            "exec: ".len() + MAX_ARGUMENT_CHARS + 1
+        );
+    }
+
+    #[test]
+    fn test_result_preview_and_chat_message() {
+        assert_eq!(result_preview("  one\ntwo\n"), "one\ntwo");
+        assert_eq!(result_preview("1\n2\n3\n4\n5"), "1\n2\n3…");
+        assert!(result_preview(&"y".repeat(1000)).chars().count() <= MAX_RESULT_CHARS + 1);
+        let exec = call("exec", json!({"command": "date"}));
+        assert_eq!(
+            chat_message(&exec, "Fri Oct 16\n"),
+            "🔧 exec: date\nFri Oct 16"
+        );
+        assert_eq!(chat_message(&exec, ""), "🔧 exec: date");
+    }
+}
@@ -0,0 +1,147 @@
+//! Roles of channel users: owner, trusted and guest.
+//!
+//! Once `agents.roles` names any owners or trusted users, everyone else
+//! gets the `defaultRole`, normally guest. Guests only see and use the
+//! tools in `guestTools` (by default no `exec`, no file writes, no spawning
+//! or scheduling), trusted users use every tool, and owners can also run
+//! the admin commands. Messages from the CLI, the API and nanoclaw itself
+//! (cron jobs, heartbeats, subagent results) act as the owner. Messages
+//! injected through the bus bridge only match users listed as
+//! `bridge:<id>`.
+
+use crate::bus::bridge::BRIDGE_CHANNEL;
+use crate::config::schema::RolesConfig;
+
+/// Senders of messages nanoclaw makes itself, or that came through an
+/// authenticated API.
+const INTERNAL_SENDERS: &[&str] = &["cron", "system", "heartbeat", "subagent", "api", "openai"];
+
+/// Commands only owners may run.
+pub const ADMIN_COMMANDS: &[&str] = &["/reset", "/resume"];
+
+/// What a user may do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Guest,
+    Trusted,
+    Owner,
+}
+
+impl Role {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "guest" => Some(Self::Guest),
+            "trusted" => Some(Self::Trusted),
+            "owner" => Some(Self::Owner),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Guest => "guest",
+            Self::Trusted => "trusted",
+            Self::Owner => "owner",
+        }
+    }
+}
+
+/// Looks up the roles of channel users.
+#[derive(Debug, Clone, Default)]
+pub struct Roles {
+    config: RolesConfig,
+}
+
+impl Roles {
+    pub fn new(config: RolesConfig) -> Self {
+        Self { config }
+    }
+
+    /// The role of `sender_id` on `channel`.
+    pub fn role_of(&self, channel: &str, sender_id: &str) -> Role {
+        if self.config.owners.is_empty() && self.config.trusted.is_empty() {
+            return Role::Owner;
+        }
+        if is_internal(channel, sender_id) {
+            return Role::Owner;
+        }
+        if _listed(&self.config.owners, channel, sender_id) {
+            return Role::Owner;
+        }
+        if _listed(&self.config.trusted, channel, sender_id) {
+            return Role::Trusted;
+        }
+        match Role::parse(&self.config.default_role) {
+            // The default never makes strangers owners.
+            Some(Role::Trusted) => Role::Trusted,
+            _ => Role::Guest,
+        }
+    }
+
+    /// The chat of the first owner given as `channel:id`, where a private
+    /// message reaches them.
+    pub fn primary_owner(&self) -> Option<(&str, &str)> {
+        self.config
+            .owners
+            .iter()
+            .find_map(|owner| owner.split_once(':'))
+    }
+
+    /// Whether a user with `role` may use the named tool.
+    pub fn allows_tool(&self, role: Role, tool: &str) -> bool {
+        role != Role::Guest || self.config.guest_tools.iter().any(|t| t == tool)
+    }
+}
+
+/// Whether a message came from the CLI or nanoclaw itself rather than a
+/// channel user.
+pub fn is_internal(channel: &str, sender_id: &str) -> bool {
+    channel == "cli" || INTERNAL_SENDERS.contains(&sender_id)
+}
+
+/// Whether `sender_id` on `channel` is in `users`. Senders made of several
+/// IDs, like Telegram's `12345|alice`, match by any of them. IDs without a
+/// channel do not match bridge messages, whose senders anyone can name.
+fn _listed(users: &[String], channel: &str, sender_id: &str) -> bool {
+    sender_id.split('|').filter(|id| !id.is_empty()).any(|id| {
+        users.iter().any(|user| match user.split_once(':') {
+            Some((user_channel, user_id)) => user_channel == channel && user_id == id,
+            None => channel != BRIDGE_CHANNEL && user == id,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roles() -> Roles {
+        Roles::new(RolesConfig {
+            owners: vec!["telegram:12345".to_string()],
+            trusted: vec!["bob".to_string()],
+            ..RolesConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_role_of() {
+        let roles = roles();
+        assert_eq!(roles.role_of("telegram", "12345|alice"), Role::Owner);
+        assert_eq!(roles.role_of("whatsapp", "12345"), Role::Guest);
+        assert_eq!(roles.role_of("telegram", "777|bob"), Role::Trusted);
+        assert_eq!(roles.role_of("telegram", "888|eve"), Role::Guest);
+        assert_eq!(roles.role_of("telegram", "cron"), Role::Owner);
+        assert_eq!(roles.role_of("cli", "user"), Role::Owner);
+        assert_eq!(roles.role_of("bridge", "bob"), Role::Guest);
+        assert_eq!(Roles::default().role_of("telegram", "888|eve"), Role::Owner);
+    }
+
+    #[test]
+    fn test_guests_get_guest_tools() {
+        let roles = roles();
+        assert!(roles.allows_tool(Role::Guest, "web_search"));
+        assert!(!roles.allows_tool(Role::Guest, "exec"));
+        assert!(!roles.allows_tool(Role::Guest, "write_file"));
+        assert!(roles.allows_tool(Role::Trusted, "exec"));
+    }
+}
@@ -0,0 +1,226 @@
+//! Tool scopes of skills.
+//!
+//! A skill may list the tools it needs in `allowed-tools`:
+//!
+//! ```markdown
+//! ---
+//! name: reader
+//! allowed-tools: [read_file, web_fetch]
+//! ---
+//! ```
+//!
+//! Once the agent reads that skill's `SKILL.md` in a turn, the skill is
+//! active for the rest of the turn and [`SkillScopeHook`] denies (or only
+//! logs) calls to other tools. The skill's own script tools and reading
+//! other skills stay allowed. With several scoped skills active, a tool must
+//! be allowed by all of them.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use tracing::{info, warn};
+
+use crate::agent::hooks::{Hook, HookContext, ToolDecision};
+use crate::agent::skills::SkillMeta;
+use crate::agent::tools::filesystem::expand_path;
+use crate::providers::base::ToolCallRequest;
+
+/// What to do with a tool call outside the scope of an active skill.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScopeMode {
+    /// Skip the call and tell the agent why.
+    Deny,
+    /// Run the call but log a warning.
+    Warn,
+}
+
+impl ScopeMode {
+    /// Parse `"deny"` or `"warn"`; anything else turns scopes off.
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode {
+            "deny" => Some(Self::Deny),
+            "warn" => Some(Self::Warn),
+            _ => None,
+        }
+    }
+}
+
+/// An active skill that limits the tools.
+#[derive(Debug, Clone)]
+struct Scope {
+    skill: String,
+    allowed: Vec<String>,
+}
+
+/// Hook enforcing the `allowed-tools` of skills the agent has read.
+pub struct SkillScopeHook {
+    mode: ScopeMode,
+    /// Turn ID and active scopes, by session.
+    active: Mutex<HashMap<String, (String, Vec<Scope>)>>,
+}
+
+impl SkillScopeHook {
+    pub fn new(mode: ScopeMode) -> Self {
+        Self {
+            mode,
+            active: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl Hook for SkillScopeHook {
+    fn name(&self) -> &str {
+        "skill scope"
+    }
+
+    async fn before_tool_call(
+        &self,
+        ctx: &HookContext,
+        call: &mut ToolCallRequest,
+    ) -> ToolDecision {
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = active
+            .entry(ctx.session_key.clone())
+            .or_insert_with(|| (ctx.turn_id.clone(), Vec::new()));
+        if entry.0 != ctx.turn_id {
+            *entry = (ctx.turn_id.clone(), Vec::new());
+        }
+        let scopes = &mut entry.1;
+
+        if let Some(meta) = _skill_read_by(call) {
+            if let Some(allowed) = meta.allowed_tools.clone() {
+                let skill = meta.name.clone().unwrap_or_else(|| "skill".to_string());
+                info!(
+                    "Skill '{}' limits the tools to {}",
+                    skill,
+                    allowed.join(", ")
+                );
+                let mut allowed = allowed;
+                allowed.extend(meta.tools.iter().map(|t| t.name.clone()));
+                scopes.push(Scope { skill, allowed });
+            }
+            return ToolDecision::Allow;
+        }
+
+        let Some(scope) = scopes.iter().find(|s| !s.allowed.contains(&call.name)) else {
+            return ToolDecision::Allow;
+        };
+        match self.mode {
+            ScopeMode::Deny => ToolDecision::Deny(format!(
+                "Error: the {} skill only allows the tools {}; '{}' was not run",
+                scope.skill,
+                scope.allowed.join(", "),
+                call.name
+            )),
+            ScopeMode::Warn => {
+                warn!(
+                    "Tool '{}' is outside the scope of skill '{}' (allowed: {})",
+                    call.name,
+                    scope.skill,
+                    scope.allowed.join(", ")
+                );
+                ToolDecision::Allow
+            }
+        }
+    }
+}
+
+/// The skill whose `SKILL.md` `call` reads, if any.
+fn _skill_read_by(call: &ToolCallRequest) -> Option<SkillMeta> {
+    if call.name != "read_file" {
+        return None;
+    }
+    let path = expand_path(call.arguments.get("path")?.as_str()?);
+    if path.file_name()? != "SKILL.md" {
+        return None;
+    }
+    let content = std::fs::read_to_string(&path).ok()?;
+    let mut meta = SkillMeta::parse(&content);
+    if meta.name.is_none() {
+        meta.name = path
+            .parent()
+            .and_then(Path::file_name)
+            .map(|n| n.to_string_lossy().to_string());
+    }
+    Some(meta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn ctx(turn: &str) -> HookContext {
+        HookContext {
+            turn_id: turn.to_string(),
+            channel: "cli".to_string(),
+            chat_id: "direct".to_string(),
+            session_key: "cli:direct".to_string(),
+            model: "m".to_string(),
+            iteration: 0,
+        }
+    }
+
+    fn call(name: &str, path: Option<&Path>) -> ToolCallRequest {
+        let mut arguments = HashMap::new();
+        if let Some(path) = path {
+            arguments.insert(
+                "path".to_string(),
+                serde_json::json!(path.to_str().unwrap()),
+            );
+        }
+        ToolCallRequest {
+            id: "c".to_string(),
+            name: name.to_string(),
+            arguments,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scope_applies_after_reading_skill() {
+        let tmp = TempDir::new().unwrap();
+        let skill = tmp.path().join("reader/SKILL.md");
+        std::fs::create_dir_all(skill.parent().unwrap()).unwrap();
+        std::fs::write(
+            &skill,
+            "---\nallowed-tools: [read_file, web_fetch]\ntools:\n  - name: summarize\n    command: x\n---\n",
+        )
+        .unwrap();
+        let hook = SkillScopeHook::new(ScopeMode::Deny);
+        let t1 = ctx("t1");
+
+        assert_eq!(
+            hook.before_tool_call(&t1, &mut call("exec", None)).await,
+            ToolDecision::Allow
+        );
+        let read = &mut call("read_file", Some(&skill));
+        assert_eq!(hook.before_tool_call(&t1, read).await, ToolDecision::Allow);
+        for allowed in ["web_fetch", "summarize"] {
+            let decision = hook.before_tool_call(&t1, &mut call(allowed, None)).await;
+            assert_eq!(decision, ToolDecision::Allow);
+        }
+        let ToolDecision::Deny(reason) = hook.before_tool_call(&t1, &mut call("exec", None)).await
+        else {
+            panic!("exec should be denied");
+        };
+        assert!(reason.contains("reader skill only allows"));
+
+        // The scope ends with the turn.
+        let t2 = ctx("t2");
+        assert_eq!(
+            hook.before_tool_call(&t2, &mut call("exec", None)).await,
+            ToolDecision::Allow
+        );
+
+        let warn = SkillScopeHook::new(ScopeMode::Warn);
+        warn.before_tool_call(&t1, &mut call("read_file", Some(&skill)))
+            .await;
+        assert_eq!(
+            warn.before_tool_call(&t1, &mut call("exec", None)).await,
+            ToolDecision::Allow
+        );
+    }
+}
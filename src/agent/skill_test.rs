@@ -0,0 +1,409 @@
+//! Scenario tests for skills, run by `nanoclaw skills test <name>`.
+//!
+//! A skill can ship a `tests.yaml` next to its `SKILL.md` with scenarios:
+//! a prompt, the turns of a scripted model, and what should have happened.
+//!
+//! ```yaml
+//! - name: greets by name
+//!   prompt: Say hello to Bob
+//!   steps:
+//!     - tool: read_file
+//!       args: {path: "{skill_dir}/SKILL.md"}
+//!     - tool: greet
+//!       args: {who: Bob}
+//!     - reply: I said hello to Bob.
+//!   expect:
+//!     tools: [read_file, greet]
+//!     output_contains: [hello Bob]
+//!     reply_contains: [Bob]
+//! ```
+//!
+//! Each scenario runs the real agent loop, tools and hooks on a copy of the
+//! skill in a throwaway workspace; only the model is replaced by
+//! [`ScriptedProvider`], which makes the tool calls and replies of `steps` in
+//! order. `{skill_dir}` in string arguments is the copied skill's directory.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::agent::agent_loop::AgentLoop;
+use crate::agent::skill_scope::{ScopeMode, SkillScopeHook};
+use crate::agent::skills::SkillsLoader;
+use crate::agent::skills_install::copy_dir;
+use crate::config::schema::{Config, SubagentConfig};
+use crate::providers::base::{LLMProvider, LLMResponse, ToolCallRequest};
+use crate::session::manager::SessionManager;
+
+/// File of a skill holding its scenarios.
+pub const TESTS_FILE: &str = "tests.yaml";
+
+/// Name of the scripted model.
+const MODEL: &str = "scripted";
+
+/// One scenario of a skill.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    pub prompt: String,
+    #[serde(default)]
+    pub steps: Vec<Step>,
+    #[serde(default)]
+    pub expect: Expect,
+}
+
+/// One turn of the scripted model: a tool call or a reply.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Step {
+    Tool {
+        tool: String,
+        #[serde(default)]
+        args: HashMap<String, Value>,
+    },
+    Reply {
+        reply: String,
+    },
+}
+
+/// What a scenario checks once the turn is over.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Expect {
+    /// Tools that ran without an error, in this order (others may run in
+    /// between).
+    #[serde(default)]
+    pub tools: Vec<String>,
+    /// Text that some tool output contains.
+    #[serde(default)]
+    pub output_contains: Vec<String>,
+    /// Text that the final reply contains.
+    #[serde(default)]
+    pub reply_contains: Vec<String>,
+}
+
+/// The result of one scenario.
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub name: String,
+    /// Why the scenario failed; empty if it passed.
+    pub failures: Vec<String>,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Read the scenarios of the skill in `skill_dir`.
+pub fn load_scenarios(skill_dir: &Path) -> Result<Vec<Scenario>> {
+    let path = skill_dir.join(TESTS_FILE);
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("no {} in {}", TESTS_FILE, skill_dir.display()))?;
+    serde_yaml::from_str(&content).with_context(|| format!("invalid {}", path.display()))
+}
+
+/// Run every scenario of the skill `name` in `skill_dir`. `config` supplies
+/// the exec timeout, the skill tool scope mode and the config keys skills
+/// may require.
+pub async fn run(name: &str, skill_dir: &Path, config: &Config) -> Result<Vec<Outcome>> {
+    let scenarios = load_scenarios(skill_dir)?;
+    let mut outcomes = Vec::new();
+    for scenario in scenarios {
+        let failures = run_scenario(name, skill_dir, &scenario, config).await?;
+        outcomes.push(Outcome {
+            name: scenario.name,
+            failures,
+        });
+    }
+    Ok(outcomes)
+}
+
+/// Run one scenario and return its failures.
+async fn run_scenario(
+    name: &str,
+    skill_dir: &Path,
+    scenario: &Scenario,
+    config: &Config,
+) -> Result<Vec<String>> {
+    let workspace = Workspace::create()?;
+    let copied = workspace.0.join("skills").join(name);
+    copy_dir(skill_dir, &copied)?;
+
+    let mut failures = Vec::new();
+    let mut loader = SkillsLoader::new(&workspace.0, None);
+    loader.set_config(Arc::new(config.clone()));
+    if let Some(skill) = loader.list_skills(false).iter().find(|s| s.name == name) {
+        if !skill.missing.is_empty() {
+            failures.push(format!(
+                "the skill is unavailable: missing {}",
+                skill.missing_text()
+            ));
+        }
+    }
+
+    let provider = Arc::new(ScriptedProvider::new(&scenario.steps, &copied));
+    let (_in_tx, in_rx) = tokio::sync::mpsc::unbounded_channel();
+    let (out_tx, _out_rx) = tokio::sync::mpsc::unbounded_channel();
+    let (bus_tx, _bus_rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut agent = AgentLoop::new(
+        in_rx,
+        out_tx,
+        bus_tx,
+        provider.clone(),
+        workspace.0.clone(),
+        MODEL.to_string(),
+        scenario.steps.len() as u32 + 1,
+        None,
+        config.tools.exec_.timeout,
+        true,
+        None,
+        SubagentConfig::default(),
+    );
+    agent.set_skills_config(Arc::new(config.clone()));
+    if let Some(mode) = ScopeMode::parse(&config.agents.skills.tool_scope) {
+        agent.add_hook(Arc::new(SkillScopeHook::new(mode)));
+    }
+
+    let session_key = format!("skills-test:{}", uuid::Uuid::new_v4());
+    let reply = agent
+        .process_direct(&scenario.prompt, &session_key, "cli", "skills-test")
+        .await;
+    SessionManager::new(&workspace.0).delete(&session_key);
+
+    failures.extend(check(&scenario.expect, &provider.tool_results(), &reply));
+    Ok(failures)
+}
+
+/// Throwaway workspace of a scenario, removed when dropped.
+struct Workspace(PathBuf);
+
+impl Workspace {
+    fn create() -> Result<Self> {
+        let dir =
+            std::env::temp_dir().join(format!("nanoclaw-skills-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("cannot create {}", dir.display()))?;
+        Ok(Self(dir))
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Compare what happened with `expect`. `results` holds the name and output
+/// of each tool call, in order.
+fn check(expect: &Expect, results: &[(String, String)], reply: &str) -> Vec<String> {
+    let mut failures = Vec::new();
+    let mut ran = results
+        .iter()
+        .filter(|(_, output)| !output.starts_with("Error"))
+        .map(|(name, _)| name);
+    for tool in &expect.tools {
+        if !ran.any(|name| name == tool) {
+            failures.push(format!("expected tool '{}' to run without an error", tool));
+            break;
+        }
+    }
+    for text in &expect.output_contains {
+        if !results
+            .iter()
+            .any(|(_, output)| output.contains(text.as_str()))
+        {
+            failures.push(format!("no tool output contains '{}'", text));
+        }
+    }
+    for text in &expect.reply_contains {
+        if !reply.contains(text.as_str()) {
+            failures.push(format!("reply does not contain '{}': {}", text, reply));
+        }
+    }
+    failures
+}
+
+// ----------------------------------------------------------------------------
+// Scripted provider
+// ----------------------------------------------------------------------------
+
+/// Model that makes the tool calls and replies of a scenario's steps in
+/// order, and records the messages it is sent.
+pub struct ScriptedProvider {
+    steps: Mutex<VecDeque<Step>>,
+    /// The messages of the latest request.
+    messages: Mutex<Vec<Value>>,
+    calls: Mutex<usize>,
+}
+
+impl ScriptedProvider {
+    /// Script `steps`, with `{skill_dir}` in string arguments replaced by
+    /// `skill_dir`.
+    pub fn new(steps: &[Step], skill_dir: &Path) -> Self {
+        let dir = skill_dir.to_string_lossy();
+        let steps = steps
+            .iter()
+            .cloned()
+            .map(|step| match step {
+                Step::Tool { tool, args } => Step::Tool {
+                    tool,
+                    args: args
+                        .into_iter()
+                        .map(|(k, v)| (k, _fill_skill_dir(v, &dir)))
+                        .collect(),
+                },
+                reply => reply,
+            })
+            .collect();
+        Self {
+            steps: Mutex::new(steps),
+            messages: Mutex::new(Vec::new()),
+            calls: Mutex::new(0),
+        }
+    }
+
+    /// Name and output of each tool call, in order.
+    fn tool_results(&self) -> Vec<(String, String)> {
+        let messages = self.messages.lock().unwrap();
+        messages
+            .iter()
+            .filter(|m| m["role"] == "tool")
+            .map(|m| {
+                (
+                    m["name"].as_str().unwrap_or_default().to_string(),
+                    m["content"].as_str().unwrap_or_default().to_string(),
+                )
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl LLMProvider for ScriptedProvider {
+    async fn chat(
+        &self,
+        messages: &[Value],
+        _tools: Option<&[Value]>,
+        _model: Option<&str>,
+        _max_tokens: u32,
+        _temperature: f64,
+    ) -> Result<LLMResponse> {
+        *self.messages.lock().unwrap() = messages.to_vec();
+        let step = self.steps.lock().unwrap().pop_front();
+        let mut calls = self.calls.lock().unwrap();
+        *calls += 1;
+        let (content, tool_calls, finish_reason) = match step {
+            Some(Step::Tool { tool, args }) => (
+                None,
+                vec![ToolCallRequest {
+                    id: format!("call_{}", calls),
+                    name: tool,
+                    arguments: args,
+                }],
+                "tool_calls",
+            ),
+            Some(Step::Reply { reply }) => (Some(reply), Vec::new(), "stop"),
+            None => (Some("(end of script)".to_string()), Vec::new(), "stop"),
+        };
+        Ok(LLMResponse {
+            content,
+            tool_calls,
+            finish_reason: finish_reason.to_string(),
+            usage: HashMap::new(),
+        })
+    }
+
+    fn get_default_model(&self) -> &str {
+        MODEL
+    }
+}
+
+/// Replace `{skill_dir}` in the strings of `value`.
+fn _fill_skill_dir(value: Value, dir: &str) -> Value {
+    match value {
+        Value::String(s) => Value::String(s.replace("{skill_dir}", dir)),
+        Value::Array(items) => {
+            Value::Array(items.into_iter().map(|v| _fill_skill_dir(v, dir)).collect())
+        }
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| (k, _fill_skill_dir(v, dir)))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_runs_scenarios_against_scripted_model() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("greeter");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("SKILL.md"),
+            "---\nname: greeter\ndescription: Greets people\ntools:\n  - name: greet\n    \
+             command: \"echo hello {who}\"\n    parameters:\n      who: {type: string}\n---\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join(TESTS_FILE),
+            r#"
+- name: greets
+  prompt: Say hello to Bob
+  steps:
+    - tool: read_file
+      args: {path: "{skill_dir}/SKILL.md"}
+    - tool: greet
+      args: {who: Bob}
+    - reply: I said hello to Bob.
+  expect:
+    tools: [read_file, greet]
+    output_contains: [hello Bob]
+    reply_contains: [Bob]
+- name: wrong
+  prompt: Say hello
+  steps:
+    - tool: greet
+      args: {}
+  expect:
+    tools: [greet]
+    reply_contains: [Alice]
+"#,
+        )
+        .unwrap();
+
+        let outcomes = run("greeter", &dir, &Config::default()).await.unwrap();
+        assert!(outcomes[0].passed(), "{:?}", outcomes[0].failures);
+        assert_eq!(outcomes[1].failures.len(), 2, "{:?}", outcomes[1].failures);
+        assert!(outcomes[1].failures[0].contains("'greet' to run"));
+    }
+
+    #[test]
+    fn test_check_order_of_tools() {
+        let results = vec![
+            ("exec".to_string(), "ok".to_string()),
+            ("read_file".to_string(), "text".to_string()),
+        ];
+        let expect = Expect {
+            tools: vec!["read_file".to_string(), "exec".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(check(&expect, &results, "").len(), 1);
+        let expect = Expect {
+            tools: vec!["exec".to_string(), "read_file".to_string()],
+            ..Default::default()
+        };
+        assert!(check(&expect, &results, "").is_empty());
+    }
+}
@@ -1,28 +1,256 @@
 //! Skills loader for agent capabilities.
 //!
 //! Skills are markdown files (`SKILL.md`) that teach the agent how to use
-//! specific tools or perform certain tasks.
+//! specific tools or perform certain tasks. A skill may start with YAML
+//! frontmatter describing it:
+//!
+//! ```markdown
+//! ---
+//! name: github
+//! description: Work with issues and pull requests through the gh CLI
+//! version: 1.2.0
+//! always: false
+//! requires:
+//!   bins: [gh]
+//!   env: [GITHUB_TOKEN]
+//!   config: [tools.web.search.apiKey]
+//! install:
+//!   brew: gh
+//!   apt: gh
+//! tools:
+//!   - name: gh_pr_list
+//!     description: List open pull requests of a repository
+//!     command: "gh pr list --repo {repo}"
+//!     parameters:
+//!       repo: {type: string, description: "owner/name"}
+//! ---
+//! ```
+//!
+//! The scripts under `tools` are offered to the agent as tools of their own,
+//! run through the guarded `exec` path; see
+//! [`SkillScriptTool`](crate::agent::tools::SkillScriptTool). A skill can
+//! also limit the agent to the tools it names in `allowed-tools`.
+//!
+//! Skills whose requirements are not met are listed as unavailable, with
+//! what is missing; `install` tells `nanoclaw skills doctor` how to get
+//! missing binaries. The upstream `metadata: {"nanobot": {...}}` form is still read.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
+use anyhow::{bail, Context, Result};
+use once_cell::sync::Lazy;
 use regex::Regex;
-use tracing::debug;
+use serde::Deserialize;
+use tracing::{debug, info};
+
+use crate::config::edit::get_value;
+use crate::config::schema::Config;
+
+/// How long requirement checks of unchanged skills are trusted.
+const REQUIREMENTS_TTL: Duration = Duration::from_secs(60);
+
+/// Characters of prose used as a description when the frontmatter has none.
+const PROSE_DESCRIPTION_CHARS: usize = 120;
+
+/// What a skill needs from the machine.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Requirements {
+    /// Binaries that must be on the PATH.
+    pub bins: Vec<String>,
+    /// Environment variables that must be set.
+    pub env: Vec<String>,
+    /// Config keys that must be set, such as API keys
+    /// (`tools.web.search.apiKey`).
+    pub config: Vec<String>,
+}
+
+/// A requirement that is not met.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Missing {
+    Bin(String),
+    Env(String),
+    Config(String),
+}
+
+impl std::fmt::Display for Missing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Missing::Bin(bin) => write!(f, "CLI: {}", bin),
+            Missing::Env(var) => write!(f, "ENV: {}", var),
+            Missing::Config(key) => write!(f, "CONFIG: {}", key),
+        }
+    }
+}
+
+impl Requirements {
+    /// The requirements not met here. Config keys are only checked when
+    /// `config` is given.
+    pub fn missing(&self, config: Option<&Config>) -> Vec<Missing> {
+        let bins = self
+            .bins
+            .iter()
+            .filter(|bin| !command_exists(bin))
+            .map(|bin| Missing::Bin(bin.clone()));
+        let env = self
+            .env
+            .iter()
+            .filter(|var| std::env::var(var).is_err())
+            .map(|var| Missing::Env(var.clone()));
+        let keys = self
+            .config
+            .iter()
+            .filter(|key| config.is_some_and(|config| !_config_is_set(config, key)))
+            .map(|key| Missing::Config(key.clone()));
+        bins.chain(env).chain(keys).collect()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bins.is_empty() && self.env.is_empty() && self.config.is_empty()
+    }
+}
+
+/// The frontmatter of a `SKILL.md`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SkillMeta {
+    /// Name shown to the agent; the directory name when unset.
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub version: Option<String>,
+    /// Include the whole skill in every prompt.
+    pub always: bool,
+    pub requires: Requirements,
+    /// Package to install for missing binaries, by package manager (`brew`,
+    /// `apt`, `npm`, `pip`, `cargo`, ...).
+    pub install: BTreeMap<String, String>,
+    /// Scripts offered to the agent as tools.
+    pub tools: Vec<SkillTool>,
+    /// Tools the agent may use once it has read the skill; any tool when
+    /// unset. See [`crate::agent::skill_scope`].
+    #[serde(rename = "allowed-tools")]
+    pub allowed_tools: Option<Vec<String>>,
+}
+
+/// A script a skill offers as a tool.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SkillTool {
+    pub name: String,
+    pub description: String,
+    /// Shell command to run. `{param}` is replaced by the quoted argument
+    /// and `{skill_dir}` by the skill's directory.
+    pub command: String,
+    /// JSON Schema of each parameter, by name.
+    pub parameters: BTreeMap<String, serde_json::Value>,
+    /// Parameters that must be given; all of them when unset.
+    pub required: Option<Vec<String>>,
+}
+
+impl SkillMeta {
+    /// Read the frontmatter of `content`. A file without frontmatter, or
+    /// with frontmatter that is not valid, has no metadata.
+    pub fn parse(content: &str) -> Self {
+        Self::try_parse(content).unwrap_or_else(|e| {
+            debug!("Ignoring malformed skill frontmatter: {}", e);
+            Self::default()
+        })
+    }
+
+    /// Read the frontmatter of `content`, failing if it is not valid.
+    pub fn try_parse(content: &str) -> Result<Self> {
+        let Some(frontmatter) = _frontmatter(content) else {
+            return Ok(Self::default());
+        };
+        let value: serde_yaml::Value =
+            serde_yaml::from_str(frontmatter).context("frontmatter is not valid YAML")?;
+        let mut meta: SkillMeta =
+            serde_yaml::from_value(value.clone()).context("unexpected frontmatter")?;
+
+        // Upstream skills keep `always` and `requires` in a JSON `metadata`
+        // field, either as a string or read by YAML as a mapping.
+        let legacy = match value.get("metadata") {
+            Some(serde_yaml::Value::String(raw)) => _parse_skill_metadata(raw),
+            Some(other) => _parse_skill_metadata(&serde_json::to_string(other).unwrap_or_default()),
+            None => HashMap::new(),
+        };
+        if legacy.get("always").and_then(|v| v.as_bool()) == Some(true) {
+            meta.always = true;
+        }
+        if meta.requires.is_empty() {
+            if let Some(requires) = legacy.get("requires") {
+                meta.requires = serde_json::from_value(requires.clone())
+                    .context("unexpected requires in metadata")?;
+            }
+        }
+        for tool in &meta.tools {
+            let valid_name = !tool.name.is_empty()
+                && tool
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if !valid_name {
+                bail!("invalid tool name '{}' (use letters, digits, '_' and '-')", tool.name);
+            }
+            if tool.command.trim().is_empty() {
+                bail!("tool '{}' has no command", tool.name);
+            }
+        }
+        Ok(meta)
+    }
+}
 
 /// Information about a discovered skill.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SkillInfo {
+    /// Directory name, which identifies the skill.
     pub name: String,
     pub path: String,
     pub source: String,
+    pub meta: SkillMeta,
+    /// Requirements not met on this machine; the skill is unavailable
+    /// unless this is empty.
+    pub missing: Vec<Missing>,
+}
+
+impl SkillInfo {
+    /// Name shown to the agent.
+    pub fn display_name(&self) -> &str {
+        self.meta.name.as_deref().unwrap_or(&self.name)
+    }
+
+    /// The missing requirements as text, e.g. `CLI: gh, ENV: GITHUB_TOKEN`.
+    pub fn missing_text(&self) -> String {
+        let parts: Vec<String> = self.missing.iter().map(|m| m.to_string()).collect();
+        parts.join(", ")
+    }
+}
+
+/// The skills as last read, and the state of their files then.
+struct Snapshot {
+    files: Vec<(PathBuf, Option<SystemTime>, u64)>,
+    checked: Instant,
+    skills: Vec<SkillInfo>,
 }
 
 /// Loads and manages agent skills from workspace and built-in directories.
+///
+/// Skills are read once and read again when a `SKILL.md` is added, changed
+/// or removed, so edits apply without a restart. Requirements are checked
+/// again every [`REQUIREMENTS_TTL`], to notice newly installed binaries.
 pub struct SkillsLoader {
-    workspace: PathBuf,
     workspace_skills: PathBuf,
     builtin_skills: PathBuf,
+    /// Config to check `requires.config` keys against.
+    config: Option<Arc<Config>>,
+    snapshot: Mutex<Option<Snapshot>>,
+    /// Bumped whenever the skills read differ from the ones before.
+    generation: AtomicU64,
 }
 
 impl SkillsLoader {
@@ -36,9 +264,42 @@ impl SkillsLoader {
             None => workspace.join("builtin_skills"),
         };
         Self {
-            workspace: workspace.to_path_buf(),
             workspace_skills: workspace.join("skills"),
             builtin_skills: builtin,
+            config: None,
+            snapshot: Mutex::new(None),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Check the config keys skills require against `config`.
+    pub fn set_config(&mut self, config: Arc<Config>) {
+        self.config = Some(config);
+        *self.snapshot.get_mut().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Read the skills again if their files changed. Returns a number that
+    /// grows each time the skills differ from before.
+    pub fn refresh(&self) -> u64 {
+        self._current();
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    /// The config set with [`set_config`](Self::set_config).
+    pub fn config(&self) -> Option<Arc<Config>> {
+        self.config.clone()
+    }
+
+    /// Log each skill whose requirements are not met, with what is missing.
+    pub fn log_unavailable(&self) {
+        for skill in self.list_skills(false) {
+            if !skill.missing.is_empty() {
+                info!(
+                    "Skill '{}' is unavailable, missing {} (see `nanoclaw skills doctor`)",
+                    skill.display_name(),
+                    skill.missing_text()
+                );
+            }
         }
     }
 
@@ -47,71 +308,11 @@ impl SkillsLoader {
     /// When `filter_unavailable` is `true`, skills with unmet requirements are
     /// excluded from the result.
     pub fn list_skills(&self, filter_unavailable: bool) -> Vec<SkillInfo> {
-        let mut skills: Vec<SkillInfo> = Vec::new();
-        let mut seen_names: Vec<String> = Vec::new();
-
-        // Workspace skills (highest priority).
-        if self.workspace_skills.exists() {
-            if let Ok(entries) = fs::read_dir(&self.workspace_skills) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    if path.is_dir() {
-                        let skill_file = path.join("SKILL.md");
-                        if skill_file.exists() {
-                            let name = path
-                                .file_name()
-                                .unwrap_or_default()
-                                .to_string_lossy()
-                                .to_string();
-                            seen_names.push(name.clone());
-                            skills.push(SkillInfo {
-                                name,
-                                path: skill_file.to_string_lossy().to_string(),
-                                source: "workspace".to_string(),
-                            });
-                        }
-                    }
-                }
-            }
-        }
-
-        // Built-in skills.
-        if self.builtin_skills.exists() {
-            if let Ok(entries) = fs::read_dir(&self.builtin_skills) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    if path.is_dir() {
-                        let skill_file = path.join("SKILL.md");
-                        if skill_file.exists() {
-                            let name = path
-                                .file_name()
-                                .unwrap_or_default()
-                                .to_string_lossy()
-                                .to_string();
-                            if !seen_names.contains(&name) {
-                                skills.push(SkillInfo {
-                                    name,
-                                    path: skill_file.to_string_lossy().to_string(),
-                                    source: "builtin".to_string(),
-                                });
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
+        let mut skills = self._current();
         if filter_unavailable {
-            skills
-                .into_iter()
-                .filter(|s| {
-                    let meta = self._get_skill_meta(&s.name);
-                    _check_requirements(&meta)
-                })
-                .collect()
-        } else {
-            skills
+            skills.retain(|s| s.missing.is_empty());
         }
+        skills
     }
 
     /// Load a skill's content by name.
@@ -159,30 +360,22 @@ impl SkillsLoader {
         let mut lines: Vec<String> = vec!["<skills>".to_string()];
 
         for s in &all_skills {
-            let name = _escape_xml(&s.name);
-            let path = &s.path;
-            let desc = _escape_xml(&self._get_skill_description(&s.name));
-            let skill_meta = self._get_skill_meta(&s.name);
-            let available = _check_requirements(&skill_meta);
-
+            lines.push(format!("  <skill available=\"{}\">", s.missing.is_empty()));
+            lines.push(format!("    <name>{}</name>", _escape_xml(s.display_name())));
             lines.push(format!(
-                "  <skill available=\"{}\">",
-                if available { "true" } else { "false" }
+                "    <description>{}</description>",
+                _escape_xml(&self._get_skill_description(s))
             ));
-            lines.push(format!("    <name>{}</name>", name));
-            lines.push(format!("    <description>{}</description>", desc));
-            lines.push(format!("    <location>{}</location>", path));
-
-            if !available {
-                let missing = _get_missing_requirements(&skill_meta);
-                if !missing.is_empty() {
-                    lines.push(format!(
-                        "    <requires>{}</requires>",
-                        _escape_xml(&missing)
-                    ));
-                }
+            if let Some(version) = &s.meta.version {
+                lines.push(format!("    <version>{}</version>", _escape_xml(version)));
+            }
+            lines.push(format!("    <location>{}</location>", s.path));
+            if !s.missing.is_empty() {
+                lines.push(format!(
+                    "    <requires>{}</requires>",
+                    _escape_xml(&s.missing_text())
+                ));
             }
-
             lines.push("  </skill>".to_string());
         }
 
@@ -190,78 +383,115 @@ impl SkillsLoader {
         lines.join("\n")
     }
 
-    /// Get skills marked as `always=true` that also meet requirements.
+    /// Get skills marked as `always: true` that also meet requirements.
     pub fn get_always_skills(&self) -> Vec<String> {
-        let mut result: Vec<String> = Vec::new();
-
-        for s in self.list_skills(true) {
-            if let Some(meta) = self.get_skill_metadata(&s.name) {
-                let skill_meta =
-                    _parse_skill_metadata(meta.get("metadata").map(|s| s.as_str()).unwrap_or(""));
-                if skill_meta
-                    .get("always")
-                    .and_then(|v| v.as_bool())
-                    .unwrap_or(false)
-                {
-                    result.push(s.name.clone());
-                    continue;
-                }
-                // Also check top-level "always" in frontmatter.
-                if meta.get("always").map(|v| v == "true").unwrap_or(false) {
-                    result.push(s.name.clone());
-                }
-            }
-        }
-
-        result
+        self.list_skills(true)
+            .into_iter()
+            .filter(|s| s.meta.always)
+            .map(|s| s.name)
+            .collect()
     }
 
-    /// Parse YAML-like frontmatter metadata from a skill file.
-    pub fn get_skill_metadata(&self, name: &str) -> Option<HashMap<String, String>> {
-        let content = self.load_skill(name)?;
+    // ------------------------------------------------------------------
+    // Private helpers
+    // ------------------------------------------------------------------
 
-        if !content.starts_with("---") {
-            return None;
+    /// The `SKILL.md` of each skill directory, workspace first.
+    fn _skill_files(&self) -> Vec<(PathBuf, &'static str)> {
+        let mut files = Vec::new();
+        for (dir, source) in [
+            (&self.workspace_skills, "workspace"),
+            (&self.builtin_skills, "builtin"),
+        ] {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            let mut found: Vec<PathBuf> = entries
+                .flatten()
+                .map(|entry| entry.path().join("SKILL.md"))
+                .filter(|file| file.is_file())
+                .collect();
+            found.sort();
+            files.extend(found.into_iter().map(|file| (file, source)));
         }
-
-        let re = Regex::new(r"(?s)^---\n(.*?)\n---").ok()?;
-        let caps = re.captures(&content)?;
-        let frontmatter = caps.get(1)?.as_str();
-
-        let mut metadata = HashMap::new();
-        for line in frontmatter.lines() {
-            if let Some((key, value)) = line.split_once(':') {
-                let k = key.trim().to_string();
-                let v = value.trim().trim_matches(|c| c == '"' || c == '\'').to_string();
-                metadata.insert(k, v);
+        files
+    }
+
+    /// The skills, read again if their files changed or the requirement
+    /// checks are stale.
+    fn _current(&self) -> Vec<SkillInfo> {
+        let files = self._skill_files();
+        let stamps: Vec<(PathBuf, Option<SystemTime>, u64)> = files
+            .iter()
+            .map(|(file, _)| {
+                let meta = fs::metadata(file).ok();
+                let modified = meta.as_ref().and_then(|m| m.modified().ok());
+                (file.clone(), modified, meta.map_or(0, |m| m.len()))
+            })
+            .collect();
+
+        let mut snapshot = self.snapshot.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(snap) = snapshot.as_ref() {
+            if snap.files == stamps && snap.checked.elapsed() < REQUIREMENTS_TTL {
+                return snap.skills.clone();
             }
         }
 
-        Some(metadata)
+        let skills = self._scan(&files);
+        if let Some(old) = snapshot.as_ref() {
+            if old.skills != skills {
+                info!("Skills changed, reloaded {} skills", skills.len());
+                self.generation.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        *snapshot = Some(Snapshot {
+            files: stamps,
+            checked: Instant::now(),
+            skills: skills.clone(),
+        });
+        skills
     }
 
-    // ------------------------------------------------------------------
-    // Private helpers
-    // ------------------------------------------------------------------
-
-    fn _get_skill_description(&self, name: &str) -> String {
-        if let Some(meta) = self.get_skill_metadata(name) {
-            if let Some(desc) = meta.get("description") {
-                if !desc.is_empty() {
-                    return desc.clone();
-                }
+    /// Read the skills of `files`; a workspace skill shadows the built-in
+    /// one of the same name.
+    fn _scan(&self, files: &[(PathBuf, &str)]) -> Vec<SkillInfo> {
+        let mut skills: Vec<SkillInfo> = Vec::new();
+        for (file, source) in files {
+            let name = file
+                .parent()
+                .and_then(|dir| dir.file_name())
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            if skills.iter().any(|s| s.name == name) {
+                continue;
             }
+            let content = fs::read_to_string(file).unwrap_or_default();
+            let meta = SkillMeta::parse(&content);
+            let missing = meta.requires.missing(self.config.as_deref());
+            skills.push(SkillInfo {
+                name,
+                path: file.to_string_lossy().to_string(),
+                source: source.to_string(),
+                meta,
+                missing,
+            });
         }
-        name.to_string()
+        skills
     }
 
-    fn _get_skill_meta(&self, name: &str) -> HashMap<String, serde_json::Value> {
-        let meta = match self.get_skill_metadata(name) {
-            Some(m) => m,
-            None => return HashMap::new(),
-        };
-        let raw = meta.get("metadata").map(|s| s.as_str()).unwrap_or("");
-        _parse_skill_metadata(raw)
+    /// The frontmatter description, else the first line of prose, else the
+    /// name.
+    fn _get_skill_description(&self, skill: &SkillInfo) -> String {
+        if let Some(desc) = skill.meta.description.as_ref().filter(|d| !d.is_empty()) {
+            return desc.clone();
+        }
+        let content = self.load_skill(&skill.name).unwrap_or_default();
+        _strip_frontmatter(&content)
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.chars().take(PROSE_DESCRIPTION_CHARS).collect())
+            .unwrap_or_else(|| skill.display_name().to_string())
     }
 }
 
@@ -269,13 +499,22 @@ impl SkillsLoader {
 // Module-level helpers
 // ---------------------------------------------------------------------------
 
+/// The YAML between the `---` lines opening a markdown file.
+fn _frontmatter(content: &str) -> Option<&str> {
+    if !content.starts_with("---") {
+        return None;
+    }
+    static FRONTMATTER: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)^---\n(.*?)\n---").unwrap());
+    Some(FRONTMATTER.captures(content)?.get(1)?.as_str())
+}
+
 /// Strip YAML frontmatter from markdown content.
 fn _strip_frontmatter(content: &str) -> String {
+    static FRONTMATTER_BLOCK: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(?s)^---\n.*?\n---\n").unwrap());
     if content.starts_with("---") {
-        if let Some(re) = Regex::new(r"(?s)^---\n.*?\n---\n").ok() {
-            if let Some(m) = re.find(content) {
-                return content[m.end()..].trim().to_string();
-            }
+        if let Some(m) = FRONTMATTER_BLOCK.find(content) {
+            return content[m.end()..].trim().to_string();
         }
     }
     content.to_string()
@@ -302,68 +541,18 @@ fn _parse_skill_metadata(raw: &str) -> HashMap<String, serde_json::Value> {
     }
 }
 
-/// Check if skill requirements are met (bins in PATH, env vars set).
-fn _check_requirements(skill_meta: &HashMap<String, serde_json::Value>) -> bool {
-    if let Some(requires) = skill_meta.get("requires") {
-        // Check required binaries.
-        if let Some(bins) = requires.get("bins").and_then(|v| v.as_array()) {
-            for bin_val in bins {
-                if let Some(bin_name) = bin_val.as_str() {
-                    if !_command_exists(bin_name) {
-                        debug!("Skill requirement not met: binary '{}' not found", bin_name);
-                        return false;
-                    }
-                }
-            }
-        }
-        // Check required environment variables.
-        if let Some(env_vars) = requires.get("env").and_then(|v| v.as_array()) {
-            for env_val in env_vars {
-                if let Some(env_name) = env_val.as_str() {
-                    if std::env::var(env_name).is_err() {
-                        debug!(
-                            "Skill requirement not met: env var '{}' not set",
-                            env_name
-                        );
-                        return false;
-                    }
-                }
-            }
-        }
+/// Whether the config value at `key` is set to something other than empty.
+fn _config_is_set(config: &Config, key: &str) -> bool {
+    match get_value(config, key) {
+        Ok(serde_json::Value::Null) | Err(_) => false,
+        Ok(serde_json::Value::String(s)) => !s.is_empty(),
+        Ok(serde_json::Value::Array(items)) => !items.is_empty(),
+        Ok(_) => true,
     }
-    true
-}
-
-/// Get a description of what requirements are missing.
-fn _get_missing_requirements(skill_meta: &HashMap<String, serde_json::Value>) -> String {
-    let mut missing: Vec<String> = Vec::new();
-
-    if let Some(requires) = skill_meta.get("requires") {
-        if let Some(bins) = requires.get("bins").and_then(|v| v.as_array()) {
-            for bin_val in bins {
-                if let Some(bin_name) = bin_val.as_str() {
-                    if !_command_exists(bin_name) {
-                        missing.push(format!("CLI: {}", bin_name));
-                    }
-                }
-            }
-        }
-        if let Some(env_vars) = requires.get("env").and_then(|v| v.as_array()) {
-            for env_val in env_vars {
-                if let Some(env_name) = env_val.as_str() {
-                    if std::env::var(env_name).is_err() {
-                        missing.push(format!("ENV: {}", env_name));
-                    }
-                }
-            }
-        }
-    }
-
-    missing.join(", ")
 }
 
 /// Check whether a binary exists on the PATH.
-fn _command_exists(name: &str) -> bool {
+pub fn command_exists(name: &str) -> bool {
     std::process::Command::new("which")
         .arg(name)
         .stdout(std::process::Stdio::null())
@@ -491,43 +680,124 @@ mod tests {
         assert!(result.is_empty());
     }
 
-    // ----- _check_requirements -----
+    // ----- Requirements -----
 
     #[test]
-    fn test_check_requirements_no_requires_key() {
-        let meta: HashMap<String, serde_json::Value> = HashMap::new();
-        assert!(_check_requirements(&meta));
+    fn test_requirements_none() {
+        assert!(Requirements::default().missing(None).is_empty());
     }
 
     #[test]
-    fn test_check_requirements_with_existing_bin() {
+    fn test_requirements_with_existing_bin() {
         // "ls" should always exist on Linux/macOS.
-        let mut meta: HashMap<String, serde_json::Value> = HashMap::new();
-        meta.insert(
-            "requires".to_string(),
-            serde_json::json!({"bins": ["ls"]}),
+        let requires = Requirements {
+            bins: vec!["ls".to_string()],
+            ..Default::default()
+        };
+        assert!(requires.missing(None).is_empty());
+    }
+
+    #[test]
+    fn test_requirements_with_missing_bin_and_env() {
+        let requires = Requirements {
+            bins: vec!["this_binary_does_not_exist_xyz_123".to_string()],
+            env: vec!["NANOBOT_TEST_NONEXISTENT_VAR_XYZ".to_string()],
+            ..Default::default()
+        };
+        let missing: Vec<String> = requires.missing(None).iter().map(|m| m.to_string()).collect();
+        assert_eq!(
+            missing,
+            [
+                "CLI: this_binary_does_not_exist_xyz_123",
+                "ENV: NANOBOT_TEST_NONEXISTENT_VAR_XYZ"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_requirements_config_keys() {
+        let requires = Requirements {
+            config: vec![
+                "tools.web.search.apiKey".to_string(),
+                "agents.defaults.model".to_string(),
+                "no.such.key".to_string(),
+            ],
+            ..Default::default()
+        };
+        assert!(requires.missing(None).is_empty());
+        let config = Config::default();
+        assert_eq!(
+            requires.missing(Some(&config)),
+            [
+                Missing::Config("tools.web.search.apiKey".to_string()),
+                Missing::Config("no.such.key".to_string())
+            ]
         );
-        assert!(_check_requirements(&meta));
     }
 
+    // ----- SkillMeta -----
+
     #[test]
-    fn test_check_requirements_with_missing_bin() {
-        let mut meta: HashMap<String, serde_json::Value> = HashMap::new();
-        meta.insert(
-            "requires".to_string(),
-            serde_json::json!({"bins": ["this_binary_does_not_exist_xyz_123"]}),
+    fn test_skill_meta_parse() {
+        let meta = SkillMeta::parse(
+            "---\nname: GitHub\ndescription: \"Issues: and PRs\"\nversion: 1.2.0\nalways: true\n\
+             requires:\n  bins: [gh]\n  env:\n    - GITHUB_TOKEN\ninstall:\n  brew: gh\n---\nbody",
+        );
+        assert_eq!(meta.name.as_deref(), Some("GitHub"));
+        assert_eq!(meta.description.as_deref(), Some("Issues: and PRs"));
+        assert_eq!(meta.version.as_deref(), Some("1.2.0"));
+        assert!(meta.always);
+        assert_eq!(meta.requires.bins, ["gh"]);
+        assert_eq!(meta.requires.env, ["GITHUB_TOKEN"]);
+        assert_eq!(meta.install["brew"], "gh");
+
+        let meta = SkillMeta::parse(
+            "---\ntools:\n  - name: forecast\n    command: \"forecast {city}\"\n    \
+             parameters:\n      city: {type: string}\n---\n",
         );
-        assert!(!_check_requirements(&meta));
+        assert_eq!(meta.tools[0].name, "forecast");
+        assert_eq!(meta.tools[0].parameters["city"]["type"], "string");
+        let bad_tool = "---\ntools:\n  - name: \"bad name\"\n    command: x\n---\n";
+        assert!(SkillMeta::try_parse(bad_tool).is_err());
+
+        assert_eq!(SkillMeta::parse("no frontmatter"), SkillMeta::default());
+        assert_eq!(SkillMeta::parse("---\n: [broken\n---\n"), SkillMeta::default());
     }
 
     #[test]
-    fn test_check_requirements_with_missing_env() {
-        let mut meta: HashMap<String, serde_json::Value> = HashMap::new();
-        meta.insert(
-            "requires".to_string(),
-            serde_json::json!({"env": ["NANOBOT_TEST_NONEXISTENT_VAR_XYZ"]}),
+    fn test_skill_meta_legacy_metadata() {
+        let meta = SkillMeta::parse(
+            "---\ndescription: Weather\nmetadata: {\"nanobot\": {\"always\": true, \"requires\": {\"bins\": [\"curl\"]}}}\n---\n",
         );
-        assert!(!_check_requirements(&meta));
+        assert!(meta.always);
+        assert_eq!(meta.requires.bins, ["curl"]);
+
+        let meta = SkillMeta::parse(
+            "---\nmetadata: '{\"nanobot\": {\"requires\": {\"env\": [\"KEY\"]}}}'\n---\n",
+        );
+        assert_eq!(meta.requires.env, ["KEY"]);
+    }
+
+    // ----- reload -----
+
+    #[test]
+    fn test_reloads_changed_skills() {
+        let (tmp, loader) = make_workspace_with_skill(Some("version: 1.0.0"), "# Test");
+        let generation = loader.refresh();
+        assert_eq!(loader.list_skills(false)[0].meta.version.as_deref(), Some("1.0.0"));
+        assert_eq!(loader.refresh(), generation);
+
+        let skill_file = tmp.path().join("skills/test-skill/SKILL.md");
+        fs::write(&skill_file, "---\nversion: 1.10.0\n---\n# Test").unwrap();
+        let new_dir = tmp.path().join("skills/another");
+        fs::create_dir_all(&new_dir).unwrap();
+        fs::write(new_dir.join("SKILL.md"), "# Another").unwrap();
+
+        assert_eq!(loader.refresh(), generation + 1);
+        let skills = loader.list_skills(false);
+        assert_eq!(skills.len(), 2);
+        let test_skill = skills.iter().find(|s| s.name == "test-skill").unwrap();
+        assert_eq!(test_skill.meta.version.as_deref(), Some("1.10.0"));
     }
 
     // ----- list_skills -----
@@ -566,32 +836,33 @@ mod tests {
         assert!(content.is_none());
     }
 
-    // ----- get_skill_metadata -----
+    // ----- skill metadata -----
 
     #[test]
-    fn test_get_skill_metadata_with_frontmatter() {
+    fn test_skill_metadata_with_frontmatter() {
         let frontmatter = "description: A cool skill\nauthor: tester";
         let (_tmp, loader) = make_workspace_with_skill(Some(frontmatter), "body");
-        let meta = loader.get_skill_metadata("test-skill");
-        assert!(meta.is_some());
-        let meta = meta.unwrap();
-        assert_eq!(meta.get("description").map(|s| s.as_str()), Some("A cool skill"));
-        assert_eq!(meta.get("author").map(|s| s.as_str()), Some("tester"));
+        let meta = &loader.list_skills(false)[0].meta;
+        assert_eq!(meta.description.as_deref(), Some("A cool skill"));
     }
 
     #[test]
-    fn test_get_skill_metadata_without_frontmatter() {
+    fn test_skill_metadata_without_frontmatter() {
         let (_tmp, loader) = make_workspace_with_skill(None, "plain body");
-        let meta = loader.get_skill_metadata("test-skill");
-        assert!(meta.is_none());
+        let skill = &loader.list_skills(false)[0];
+        assert_eq!(skill.meta, SkillMeta::default());
+        // The description falls back to the prose.
+        assert!(loader
+            .build_skills_summary()
+            .contains("<description>plain body</description>"));
     }
 
     #[test]
-    fn test_get_skill_metadata_strips_quotes() {
+    fn test_skill_metadata_strips_quotes() {
         let frontmatter = "description: \"Quoted value\"";
         let (_tmp, loader) = make_workspace_with_skill(Some(frontmatter), "body");
-        let meta = loader.get_skill_metadata("test-skill").unwrap();
-        assert_eq!(meta.get("description").map(|s| s.as_str()), Some("Quoted value"));
+        let meta = &loader.list_skills(false)[0].meta;
+        assert_eq!(meta.description.as_deref(), Some("Quoted value"));
     }
 
     // ----- build_skills_summary -----
@@ -0,0 +1,187 @@
+//! Index of community skills, for `nanoclaw skills search`.
+//!
+//! `agents.skills.indexUrl` points at a JSON file (an `http(s)` URL or a
+//! local path) listing skills and where to install them from:
+//!
+//! ```json
+//! {"skills": [
+//!   {"name": "github", "description": "Work with GitHub issues and PRs",
+//!    "source": "https://github.com/someone/skill-github.git",
+//!    "version": "1.2.0", "tags": ["git", "code"]}
+//! ]}
+//! ```
+//!
+//! A bare array of entries works too. In a local index, relative `source`
+//! paths are relative to the index file. `nanoclaw skills install <name>`
+//! falls back to the index when `<name>` is neither a git URL nor a
+//! directory.
+
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use super::skills_install::{check_source, is_git_url};
+use crate::utils::http::shared_client;
+
+/// How long fetching the index may take.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A skill listed in the index.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IndexEntry {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Git URL or directory to install from.
+    pub source: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// The two accepted shapes of an index file.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IndexFile {
+    Wrapped { skills: Vec<IndexEntry> },
+    List(Vec<IndexEntry>),
+}
+
+/// Fetch and parse the index at `url`.
+pub async fn fetch(url: &str) -> Result<Vec<IndexEntry>> {
+    if url.trim().is_empty() {
+        bail!("no skills index configured; set agents.skills.indexUrl");
+    }
+    if url.starts_with("http://") || url.starts_with("https://") {
+        let response = shared_client()
+            .get(url)
+            .timeout(FETCH_TIMEOUT)
+            .send()
+            .await
+            .with_context(|| format!("cannot fetch {}", url))?;
+        if !response.status().is_success() {
+            bail!("cannot fetch {}: HTTP {}", url, response.status());
+        }
+        return parse(&response.text().await?, None);
+    }
+
+    let path = Path::new(url);
+    let text = std::fs::read_to_string(path).with_context(|| format!("cannot read {}", url))?;
+    parse(&text, path.parent())
+}
+
+/// Parse an index; relative sources are resolved against `base`, if given.
+pub fn parse(text: &str, base: Option<&Path>) -> Result<Vec<IndexEntry>> {
+    let file: IndexFile = serde_json::from_str(text).context("invalid skills index")?;
+    let mut entries = match file {
+        IndexFile::Wrapped { skills } => skills,
+        IndexFile::List(skills) => skills,
+    };
+    for entry in &entries {
+        check_source(&entry.source).with_context(|| format!("skill '{}' in the index", entry.name))?;
+    }
+    if let Some(base) = base {
+        for entry in &mut entries {
+            if !is_git_url(&entry.source) && Path::new(&entry.source).is_relative() {
+                entry.source = base.join(&entry.source).to_string_lossy().to_string();
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Entries matching every word of `query` in their name, description or
+/// tags, best first: name matches before the others.
+pub fn search<'a>(entries: &'a [IndexEntry], query: &str) -> Vec<&'a IndexEntry> {
+    let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    let mut found: Vec<(usize, &IndexEntry)> = entries
+        .iter()
+        .filter_map(|entry| {
+            let name = entry.name.to_lowercase();
+            let text = format!(
+                "{} {} {}",
+                name,
+                entry.description.to_lowercase(),
+                entry.tags.join(" ").to_lowercase()
+            );
+            if !words.iter().all(|w| text.contains(w.as_str())) {
+                return None;
+            }
+            let rank = if words.contains(&name) {
+                0
+            } else if words.iter().any(|w| name.contains(w.as_str())) {
+                1
+            } else {
+                2
+            };
+            Some((rank, entry))
+        })
+        .collect();
+    found.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.name.cmp(&b.1.name)));
+    found.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// The entry named `name`.
+pub fn find<'a>(entries: &'a [IndexEntry], name: &str) -> Option<&'a IndexEntry> {
+    entries.iter().find(|e| e.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const INDEX: &str = r#"{"skills": [
+        {"name": "notes", "description": "Keep meeting notes", "source": "skills/notes"},
+        {"name": "github", "description": "Issues and notes on PRs",
+         "source": "https://github.com/a/b.git", "version": "1.0", "tags": ["git"]},
+        {"name": "calendar", "description": "Plan the week", "source": "/abs/calendar"}
+    ]}"#;
+
+    #[tokio::test]
+    async fn test_fetch_local_index_resolves_sources() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("index.json");
+        std::fs::write(&path, INDEX).unwrap();
+        let entries = fetch(&path.to_string_lossy()).await.unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            find(&entries, "notes").unwrap().source,
+            tmp.path().join("skills/notes").to_string_lossy()
+        );
+        assert_eq!(
+            find(&entries, "github").unwrap().source,
+            "https://github.com/a/b.git"
+        );
+        assert_eq!(find(&entries, "calendar").unwrap().source, "/abs/calendar");
+        assert!(fetch("")
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("indexUrl"));
+
+        let list = parse(r#"[{"name": "x", "source": "y"}]"#, None).unwrap();
+        assert_eq!(list[0].name, "x");
+        let err = parse(r#"[{"name": "x", "source": "--upload-pack=sh x.git"}]"#, None)
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("invalid skill source"), "{:#}", err);
+    }
+
+    #[test]
+    fn test_search_ranks_name_matches_first() {
+        let entries = parse(INDEX, None).unwrap();
+        let names = |query| {
+            search(&entries, query)
+                .iter()
+                .map(|e| e.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names("notes"), vec!["notes", "github"]);
+        assert_eq!(names("GIT"), vec!["github"]);
+        assert_eq!(names("plan week"), vec!["calendar"]);
+        assert!(names("nothing").is_empty());
+    }
+}
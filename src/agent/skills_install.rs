@@ -0,0 +1,615 @@
+//! Installing, updating and removing workspace skills.
+//!
+//! A skill is installed from a git repository (cloned shallowly) or a local
+//! directory, and placed under `workspace/skills/<name>/`. It must have a
+//! `SKILL.md` whose frontmatter, if any, is valid; the name comes from
+//! `--name`, the frontmatter or the source, in that order. Where a skill came
+//! from, its version and git commit are kept in its `.source` file, so it can
+//! be fetched again and compared before an update is applied.
+//!
+//! [`suggest_fix`] turns a missing requirement into something to run, for
+//! `nanoclaw skills doctor`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::skills::{Missing, SkillMeta};
+
+/// Package managers a skill's `install` hints may name: the name, the
+/// binary that shows it is present, and its install command.
+const PACKAGE_MANAGERS: &[(&str, &str, &str)] = &[
+    ("brew", "brew", "brew install"),
+    ("apt", "apt-get", "sudo apt-get install"),
+    ("dnf", "dnf", "sudo dnf install"),
+    ("pacman", "pacman", "sudo pacman -S"),
+    ("npm", "npm", "npm install -g"),
+    ("pip", "pip", "pip install"),
+    ("cargo", "cargo", "cargo install"),
+    ("go", "go", "go install"),
+];
+
+/// Unchanged lines shown around each change by [`line_diff`].
+const DIFF_CONTEXT: usize = 2;
+
+/// Package managers that may have a binary under its own name.
+const SYSTEM_MANAGERS: &[&str] = &["brew", "apt", "dnf", "pacman"];
+
+/// File in a skill's directory recording where it was installed from.
+pub const SOURCE_FILE: &str = ".source";
+
+/// Where an installed skill came from, kept in its [`SOURCE_FILE`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Origin {
+    /// Git URL or absolute path.
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Commit installed, for git sources.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed_at: Option<DateTime<Utc>>,
+}
+
+/// An installed skill.
+#[derive(Debug)]
+pub struct Installed {
+    pub name: String,
+    pub path: PathBuf,
+    pub meta: SkillMeta,
+}
+
+/// A skill fetched into a staging directory and validated, not installed
+/// yet. The staging directory is removed when this is dropped.
+pub struct Staged {
+    dir: PathBuf,
+    pub origin: Origin,
+    pub meta: SkillMeta,
+    /// Text of the fetched `SKILL.md`.
+    pub skill_md: String,
+}
+
+/// A fresh copy of an installed skill, fetched from where it came from.
+pub struct Update {
+    pub name: String,
+    /// Version installed now.
+    pub installed_version: Option<String>,
+    /// Text of the installed `SKILL.md`.
+    pub installed_skill_md: String,
+    pub staged: Staged,
+    /// Whether any file differs from the installed ones.
+    pub changed: bool,
+}
+
+/// Whether `source` names a git repository rather than a local directory.
+pub fn is_git_url(source: &str) -> bool {
+    ["http://", "https://", "git@", "ssh://", "git://", "file://"]
+        .iter()
+        .any(|prefix| source.starts_with(prefix))
+        || (source.ends_with(".git") && !Path::new(source).is_dir())
+}
+
+/// Where the skill in `skills_dir/name` was installed from, if known.
+/// Early installs recorded only the source, as plain text.
+pub fn origin_of(skills_dir: &Path, name: &str) -> Option<Origin> {
+    let text = fs::read_to_string(skills_dir.join(name).join(SOURCE_FILE)).ok()?;
+    if let Ok(origin) = serde_json::from_str::<Origin>(&text) {
+        return Some(origin);
+    }
+    let source = text.trim();
+    (!source.is_empty()).then(|| Origin {
+        source: source.to_string(),
+        version: None,
+        revision: None,
+        installed_at: None,
+    })
+}
+
+/// Check that `source` cannot be taken for an option or a transport
+/// helper (`ext::...`) by git.
+pub fn check_source(source: &str) -> Result<()> {
+    if source.trim().is_empty() {
+        bail!("empty skill source");
+    }
+    let helper = source
+        .split_once("::")
+        .is_some_and(|(transport, _)| transport.chars().all(|c| c.is_ascii_alphanumeric()));
+    if source.starts_with('-') || helper {
+        bail!("invalid skill source '{}'", source);
+    }
+    Ok(())
+}
+
+/// Install the skill at `source` into `skills_dir`. An existing skill of the
+/// same name is only replaced when `force` is set.
+pub fn install(
+    skills_dir: &Path,
+    source: &str,
+    name: Option<&str>,
+    force: bool,
+) -> Result<Installed> {
+    fetch(skills_dir, source)?.install(skills_dir, name, force)
+}
+
+/// Fetch and validate the skill at `source`, staged inside `skills_dir`.
+pub fn fetch(skills_dir: &Path, source: &str) -> Result<Staged> {
+    static STAGED: AtomicUsize = AtomicUsize::new(0);
+    check_source(source)?;
+    fs::create_dir_all(skills_dir)
+        .with_context(|| format!("cannot create {}", skills_dir.display()))?;
+    let dir = skills_dir.join(format!(
+        ".installing-{}-{}",
+        std::process::id(),
+        STAGED.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    let mut staged = Staged {
+        dir,
+        origin: Origin {
+            source: source.to_string(),
+            version: None,
+            revision: None,
+            installed_at: None,
+        },
+        meta: SkillMeta::default(),
+        skill_md: String::new(),
+    };
+
+    if is_git_url(source) {
+        staged.origin.revision = Some(_clone(source, &staged.dir)?);
+    } else {
+        let from = Path::new(source);
+        if !from.is_dir() {
+            bail!("{} is not a directory or git URL", source);
+        }
+        copy_dir(from, &staged.dir)?;
+        // Keep an absolute path so updates work from anywhere.
+        if let Ok(path) = fs::canonicalize(from) {
+            staged.origin.source = path.to_string_lossy().to_string();
+        }
+    }
+
+    staged.skill_md = fs::read_to_string(staged.dir.join("SKILL.md"))
+        .with_context(|| format!("{} has no SKILL.md", source))?;
+    staged.meta = SkillMeta::try_parse(&staged.skill_md)
+        .with_context(|| format!("invalid SKILL.md in {}", source))?;
+    staged.origin.version = staged.meta.version.clone();
+    Ok(staged)
+}
+
+/// Fetch the skill `name` again from where it came from, to compare with
+/// the installed one before applying.
+pub fn check_update(skills_dir: &Path, name: &str) -> Result<Update> {
+    _check_name(name)?;
+    let dir = skills_dir.join(name);
+    if !dir.is_dir() {
+        bail!("no skill named '{}' in {}", name, skills_dir.display());
+    }
+    let Some(origin) = origin_of(skills_dir, name) else {
+        bail!("skill '{}' was not installed from a source", name);
+    };
+    let staged = fetch(skills_dir, &origin.source)?;
+    let changed = !_same_files(&dir, &staged.dir);
+    Ok(Update {
+        name: name.to_string(),
+        installed_version: SkillMeta::parse(
+            &fs::read_to_string(dir.join("SKILL.md")).unwrap_or_default(),
+        )
+        .version,
+        installed_skill_md: fs::read_to_string(dir.join("SKILL.md")).unwrap_or_default(),
+        staged,
+        changed,
+    })
+}
+
+/// Delete the skill `name` from `skills_dir`.
+pub fn remove(skills_dir: &Path, name: &str) -> Result<()> {
+    _check_name(name)?;
+    let dir = skills_dir.join(name);
+    if !dir.join("SKILL.md").is_file() {
+        bail!("no skill named '{}' in {}", name, skills_dir.display());
+    }
+    fs::remove_dir_all(&dir).with_context(|| format!("cannot remove {}", dir.display()))
+}
+
+impl Staged {
+    /// Move the skill into `skills_dir`, as `name` or else the name from
+    /// its frontmatter or source. An existing skill of that name is only
+    /// replaced when `force` is set.
+    pub fn install(mut self, skills_dir: &Path, name: Option<&str>, force: bool) -> Result<Installed> {
+        let name = match name.or(self.meta.name.as_deref()) {
+            Some(name) => name.to_string(),
+            None => _name_from_source(&self.origin.source),
+        };
+        _check_name(&name)?;
+
+        let target = skills_dir.join(&name);
+        if target.exists() {
+            if !force {
+                bail!(
+                    "skill '{}' already exists; use --force to replace it",
+                    name
+                );
+            }
+            fs::remove_dir_all(&target)
+                .with_context(|| format!("cannot remove {}", target.display()))?;
+        }
+        self.origin.installed_at = Some(Utc::now());
+        fs::write(
+            self.dir.join(SOURCE_FILE),
+            serde_json::to_string_pretty(&self.origin)?,
+        )?;
+        fs::rename(&self.dir, &target)
+            .with_context(|| format!("cannot move skill to {}", target.display()))?;
+        Ok(Installed {
+            name,
+            path: target,
+            meta: self.meta.clone(),
+        })
+    }
+}
+
+impl Drop for Staged {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+impl Update {
+    /// Changes to `SKILL.md`, as lines starting with `-` or `+` and a little
+    /// context. Empty when it did not change.
+    pub fn diff(&self) -> String {
+        line_diff(&self.installed_skill_md, &self.staged.skill_md)
+    }
+
+    /// Replace the installed skill with the fetched one.
+    pub fn apply(self, skills_dir: &Path) -> Result<Installed> {
+        self.staged.install(skills_dir, Some(&self.name), true)
+    }
+}
+
+/// The lines that differ between `old` and `new`, with up to
+/// [`DIFF_CONTEXT`] unchanged lines around each change.
+pub fn line_diff(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Longest common subsequence, from the end.
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut ops: Vec<(char, &str)> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(('-', old[i]));
+            i += 1;
+        } else {
+            ops.push(('+', new[j]));
+            j += 1;
+        }
+    }
+
+    let near_change = |k: usize| {
+        let from = k.saturating_sub(DIFF_CONTEXT);
+        let to = (k + DIFF_CONTEXT + 1).min(ops.len());
+        ops[from..to].iter().any(|(op, _)| *op != ' ')
+    };
+    let mut lines: Vec<String> = Vec::new();
+    let mut skipped = false;
+    for (k, (op, line)) in ops.iter().enumerate() {
+        if *op == ' ' && !near_change(k) {
+            skipped = true;
+            continue;
+        }
+        if skipped && !lines.is_empty() {
+            lines.push("  ...".to_string());
+        }
+        skipped = false;
+        lines.push(format!("{} {}", op, line));
+    }
+    if ops.iter().all(|(op, _)| *op == ' ') {
+        return String::new();
+    }
+    lines.join("\n")
+}
+
+/// Ways to provide `missing` for the skill described by `meta`.
+/// `has_command` tells which package managers are installed.
+pub fn suggest_fix(
+    meta: &SkillMeta,
+    missing: &Missing,
+    has_command: impl Fn(&str) -> bool,
+) -> Vec<String> {
+    let bin = match missing {
+        Missing::Bin(bin) => bin,
+        Missing::Env(var) => {
+            return vec![format!(
+                "export {}=<value> (in the environment the gateway runs in)",
+                var
+            )]
+        }
+        Missing::Config(key) => return vec![format!("nanoclaw config set {} <value>", key)],
+    };
+    let present = |manager: &str| {
+        PACKAGE_MANAGERS
+            .iter()
+            .find(|(name, _, _)| *name == manager)
+            .filter(|(_, binary, _)| has_command(binary))
+    };
+
+    // The skill's own hints, for package managers found here if any.
+    let hinted: Vec<String> = meta
+        .install
+        .iter()
+        .filter_map(|(manager, package)| {
+            present(manager).map(|(_, _, command)| format!("{} {}", command, package))
+        })
+        .collect();
+    if !hinted.is_empty() {
+        return hinted;
+    }
+    if !meta.install.is_empty() {
+        return meta
+            .install
+            .iter()
+            .map(|(manager, package)| format!("{}: {}", manager, package))
+            .collect();
+    }
+
+    // No hints: guess a package of the same name.
+    match SYSTEM_MANAGERS.iter().find_map(|manager| present(manager)) {
+        Some((_, _, command)) => vec![format!(
+            "{} {} (the package name may differ)",
+            command, bin
+        )],
+        None => vec![format!("install '{}' and put it on the PATH", bin)],
+    }
+}
+
+/// Shallow-clone `url` into `dir`, without its git metadata. Returns the
+/// commit cloned.
+fn _clone(url: &str, dir: &Path) -> Result<String> {
+    let output = Command::new("git")
+        .args(["clone", "--depth", "1", "--quiet", "--"])
+        .arg(url)
+        .arg(dir)
+        .output()
+        .context("cannot run git; is it installed?")?;
+    if !output.status.success() {
+        bail!(
+            "git clone failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let revision = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+        .unwrap_or_default();
+    let _ = fs::remove_dir_all(dir.join(".git"));
+    Ok(revision)
+}
+
+/// Whether the directories `a` and `b` hold the same files, apart from
+/// [`SOURCE_FILE`].
+fn _same_files(a: &Path, b: &Path) -> bool {
+    fn files(root: &Path, dir: &Path, out: &mut Vec<(PathBuf, Vec<u8>)>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                files(root, &path, out);
+            } else if entry.file_name() != SOURCE_FILE {
+                let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+                out.push((relative, fs::read(&path).unwrap_or_default()));
+            }
+        }
+    }
+    let (mut left, mut right) = (Vec::new(), Vec::new());
+    files(a, a, &mut left);
+    files(b, b, &mut right);
+    left.sort();
+    right.sort();
+    left == right
+}
+
+/// Copy the directory `from` to `to`, leaving out `.git`.
+pub(crate) fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to).with_context(|| format!("cannot create {}", to.display()))?;
+    for entry in fs::read_dir(from).with_context(|| format!("cannot read {}", from.display()))? {
+        let entry = entry?;
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)
+                .with_context(|| format!("cannot copy {}", entry.path().display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// The last path segment of `source`, without `.git`.
+fn _name_from_source(source: &str) -> String {
+    let last = source
+        .trim_end_matches(['/', '\\'])
+        .rsplit(['/', '\\', ':'])
+        .next()
+        .unwrap_or(source);
+    last.strip_suffix(".git").unwrap_or(last).to_string()
+}
+
+/// Skill names become directory names: letters, digits, `.`, `_` and `-`.
+fn _check_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if !valid {
+        bail!(
+            "invalid skill name '{}' (use letters, digits, '.', '_' and '-')",
+            name
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_skill(dir: &Path, content: &str) {
+        fs::create_dir_all(dir.join("scripts")).unwrap();
+        fs::write(dir.join("SKILL.md"), content).unwrap();
+        fs::write(dir.join("scripts/run.sh"), "echo hi\n").unwrap();
+    }
+
+    #[test]
+    fn test_install_update_remove() {
+        let tmp = TempDir::new().unwrap();
+        let source = tmp.path().join("weather-skill");
+        let skills = tmp.path().join("ws/skills");
+        write_skill(&source, "---\nname: weather\nversion: 1.0.0\n---\n# Weather\n");
+
+        let installed = install(&skills, source.to_str().unwrap(), None, false).unwrap();
+        assert_eq!(installed.name, "weather");
+        assert_eq!(installed.meta.version.as_deref(), Some("1.0.0"));
+        assert!(skills.join("weather/scripts/run.sh").is_file());
+        let origin = origin_of(&skills, "weather").unwrap();
+        assert_eq!(origin.version.as_deref(), Some("1.0.0"));
+        assert!(origin.installed_at.is_some());
+
+        let err = install(&skills, source.to_str().unwrap(), None, false).unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+
+        assert!(!check_update(&skills, "weather").unwrap().changed);
+        fs::write(
+            source.join("SKILL.md"),
+            "---\nname: weather\nversion: 1.1.0\n---\n# Weather\n",
+        )
+        .unwrap();
+        let update = check_update(&skills, "weather").unwrap();
+        assert!(update.changed);
+        assert_eq!(update.installed_version.as_deref(), Some("1.0.0"));
+        assert_eq!(
+            update.diff(),
+            "  ---\n  name: weather\n- version: 1.0.0\n+ version: 1.1.0\n  ---\n  # Weather"
+        );
+        let updated = update.apply(&skills).unwrap();
+        assert_eq!(updated.meta.version.as_deref(), Some("1.1.0"));
+        assert!(!check_update(&skills, "weather").unwrap().changed);
+
+        let renamed = install(&skills, source.to_str().unwrap(), Some("wx"), false).unwrap();
+        assert_eq!(renamed.name, "wx");
+
+        remove(&skills, "weather").unwrap();
+        assert!(!skills.join("weather").exists());
+        assert!(remove(&skills, "weather").is_err());
+        assert!(remove(&skills, "../ws").is_err());
+        // Nothing is left behind from staging.
+        let left: Vec<_> = fs::read_dir(&skills).unwrap().flatten().collect();
+        assert_eq!(left.len(), 1);
+    }
+
+    #[test]
+    fn test_install_validation() {
+        let tmp = TempDir::new().unwrap();
+        let skills = tmp.path().join("skills");
+
+        let empty = tmp.path().join("empty");
+        fs::create_dir_all(&empty).unwrap();
+        let err = install(&skills, empty.to_str().unwrap(), None, false).unwrap_err();
+        assert!(err.to_string().contains("no SKILL.md"));
+
+        let bad = tmp.path().join("bad");
+        write_skill(&bad, "---\nname: [unclosed\n---\n");
+        let err = install(&skills, bad.to_str().unwrap(), None, false).unwrap_err();
+        assert!(format!("{:#}", err).contains("not valid YAML"));
+
+        let plain = tmp.path().join("plain-notes");
+        write_skill(&plain, "# Notes\n");
+        let installed = install(&skills, plain.to_str().unwrap(), None, false).unwrap();
+        assert_eq!(installed.name, "plain-notes");
+        assert!(install(&skills, plain.to_str().unwrap(), Some("a b"), false).is_err());
+        assert!(install(&skills, "/no/such/dir", None, false).is_err());
+
+        let err = install(&skills, "--upload-pack=touch pwned;x.git", None, false).unwrap_err();
+        assert!(err.to_string().contains("invalid skill source"), "{}", err);
+        assert!(check_source("ext::sh -c touch% pwned").is_err());
+        assert!(check_source("https://[::1]/skills/notes.git").is_ok());
+    }
+
+    #[test]
+    fn test_suggest_fix() {
+        let meta = SkillMeta::parse("---\ninstall:\n  brew: gh\n  apt: gh-cli\n---\n");
+        let gh = Missing::Bin("gh".to_string());
+        assert_eq!(
+            suggest_fix(&meta, &gh, |bin| bin == "apt-get"),
+            ["sudo apt-get install gh-cli"]
+        );
+        assert_eq!(
+            suggest_fix(&meta, &gh, |_| false),
+            ["apt: gh-cli", "brew: gh"]
+        );
+        let plain = SkillMeta::default();
+        assert_eq!(
+            suggest_fix(&plain, &gh, |bin| bin == "brew"),
+            ["brew install gh (the package name may differ)"]
+        );
+        assert_eq!(
+            suggest_fix(&plain, &Missing::Config("tools.web.search.apiKey".into()), |_| true),
+            ["nanoclaw config set tools.web.search.apiKey <value>"]
+        );
+    }
+
+    #[test]
+    fn test_line_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\n";
+        assert_eq!(
+            line_diff(old, new),
+            "  a\n- b\n+ B\n  c\n  d\n  ...\n  g\n  h\n+ i"
+        );
+        assert_eq!(line_diff(old, old), "");
+    }
+
+    #[test]
+    fn test_source_names() {
+        assert!(is_git_url("https://github.com/me/weather-skill"));
+        assert!(is_git_url("git@github.com:me/weather-skill.git"));
+        assert!(!is_git_url("./skills/weather"));
+        assert_eq!(
+            _name_from_source("git@github.com:me/weather-skill.git"),
+            "weather-skill"
+        );
+        assert_eq!(_name_from_source("/tmp/weather/"), "weather");
+    }
+}
@@ -3,25 +3,143 @@
 //! Spawns independent agent loops that can read/write files, execute commands,
 //! and search the web, then announce results back to the main agent.
 
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use tokio::sync::mpsc::UnboundedSender;
-use tokio::sync::Mutex;
-use tracing::{debug, error, info};
+use tokio::sync::{broadcast, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn, Instrument};
 use uuid::Uuid;
 
 use crate::agent::context::ContextBuilder;
+use crate::agent::tools::politeness::Politeness;
 use crate::agent::tools::{
-    ExecTool, ListDirTool, ReadFileTool, ToolRegistry, WebFetchTool, WebSearchTool, WriteFileTool,
+    CrawlTool, EditFileTool, ExecTool, ListDirTool, ReadFileTool, ToolRegistry, WebFetchTool,
+    WebSearchTool, WriteFileTool,
 };
-use crate::bus::events::InboundMessage;
+use crate::audit::{tool_entry, AuditLog};
+use crate::bus::events::{InboundMessage, SystemEvent};
+use crate::config::schema::SubagentConfig;
 use crate::providers::base::LLMProvider;
 
-/// Maximum iterations for a subagent run.
-const MAX_SUBAGENT_ITERATIONS: u32 = 15;
+/// Number of finished subagents remembered for status reporting.
+const MAX_RECENT_SUBAGENTS: usize = 20;
+
+/// A subagent that is currently running.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubagentInfo {
+    pub id: String,
+    pub label: String,
+    pub task: String,
+    pub origin_channel: String,
+    pub origin_chat_id: String,
+    pub started_at: DateTime<Local>,
+}
+
+/// A subagent that has finished.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubagentRun {
+    #[serde(flatten)]
+    pub info: SubagentInfo,
+    pub finished_at: DateTime<Local>,
+    /// `"completed"`, `"failed"`, `"cancelled"`, or `"interrupted"`.
+    pub status: String,
+}
+
+impl SubagentRun {
+    /// Wall-clock run time.
+    pub fn duration(&self) -> chrono::Duration {
+        self.finished_at - self.info.started_at
+    }
+}
+
+/// Snapshot of subagent activity, written to disk so other processes
+/// (e.g. `nanoclaw status --agents`) can see what the gateway is doing.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubagentStatus {
+    pub updated_at: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub running: Vec<SubagentInfo>,
+    /// Most recent first.
+    #[serde(default)]
+    pub recent: Vec<SubagentRun>,
+}
+
+impl SubagentStatus {
+    /// Read a snapshot written by [`SubagentManager::set_status_file`].
+    pub fn load(path: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+}
+
+/// Bookkeeping for a running subagent.
+struct RunningSubagent {
+    info: SubagentInfo,
+    handle: JoinHandle<()>,
+}
+
+/// Running and recently finished subagents.
+#[derive(Default)]
+struct Tracker {
+    running: HashMap<String, RunningSubagent>,
+    recent: VecDeque<SubagentRun>,
+    status_path: Option<PathBuf>,
+    system_events: Option<broadcast::Sender<SystemEvent>>,
+}
+
+impl Tracker {
+    /// Move a subagent from running to recent. Returns it if it was still
+    /// running.
+    fn finish(&mut self, task_id: &str, status: &str) -> Option<RunningSubagent> {
+        let running = self.running.remove(task_id)?;
+        self.recent.push_front(SubagentRun {
+            info: running.info.clone(),
+            finished_at: Local::now(),
+            status: status.to_string(),
+        });
+        self.recent.truncate(MAX_RECENT_SUBAGENTS);
+        if let Some(events) = &self.system_events {
+            let _ = events.send(SystemEvent::SubagentFinished {
+                task_id: running.info.id.clone(),
+                label: running.info.label.clone(),
+                status: status.to_string(),
+            });
+        }
+        Some(running)
+    }
+
+    fn snapshot(&self) -> SubagentStatus {
+        let mut running: Vec<SubagentInfo> =
+            self.running.values().map(|t| t.info.clone()).collect();
+        running.sort_by_key(|info| info.started_at);
+        SubagentStatus {
+            updated_at: Some(Local::now()),
+            running,
+            recent: self.recent.iter().cloned().collect(),
+        }
+    }
+
+    /// Write the snapshot to the status file, if one is configured.
+    fn persist(&self) {
+        let Some(path) = &self.status_path else {
+            return;
+        };
+        if let Ok(json) = serde_json::to_string_pretty(&self.snapshot()) {
+            if let Err(e) = std::fs::write(path, json) {
+                warn!("Failed to write subagent status: {}", e);
+            }
+        }
+    }
+}
 
 /// Manages background subagent tasks.
 pub struct SubagentManager {
@@ -32,11 +150,16 @@ pub struct SubagentManager {
     brave_api_key: Option<String>,
     exec_timeout: u64,
     restrict_to_workspace: bool,
-    running_tasks: Arc<Mutex<HashMap<String, tokio::task::JoinHandle<()>>>>,
+    config: SubagentConfig,
+    /// Shared with the parent's web_fetch so they take turns per site.
+    fetch_politeness: std::sync::Mutex<Arc<Politeness>>,
+    audit: std::sync::Mutex<Option<Arc<AuditLog>>>,
+    tracker: Arc<Mutex<Tracker>>,
 }
 
 impl SubagentManager {
     /// Create a new subagent manager.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         provider: Arc<dyn LLMProvider>,
         workspace: PathBuf,
@@ -45,6 +168,7 @@ impl SubagentManager {
         brave_api_key: Option<String>,
         exec_timeout: u64,
         restrict_to_workspace: bool,
+        config: SubagentConfig,
     ) -> Self {
         Self {
             provider,
@@ -54,13 +178,49 @@ impl SubagentManager {
             brave_api_key,
             exec_timeout,
             restrict_to_workspace,
-            running_tasks: Arc::new(Mutex::new(HashMap::new())),
+            config,
+            fetch_politeness: std::sync::Mutex::new(Arc::new(Politeness::default())),
+            audit: std::sync::Mutex::new(None),
+            tracker: Arc::new(Mutex::new(Tracker::default())),
         }
     }
 
+    /// The robots.txt and throttle state web_fetch of subagents uses.
+    pub fn fetch_politeness(&self) -> Arc<Politeness> {
+        self.fetch_politeness
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Make web_fetch of subagents started from now on use `politeness`.
+    pub fn set_fetch_politeness(&self, politeness: Arc<Politeness>) {
+        *self.fetch_politeness.lock().unwrap_or_else(|e| e.into_inner()) = politeness;
+    }
+
+    /// Record the files written and commands run by subagents in `log`,
+    /// under the session that started them.
+    pub fn set_audit_log(&self, log: Arc<AuditLog>) {
+        *self.audit.lock().unwrap_or_else(|e| e.into_inner()) = Some(log);
+    }
+
+    /// Keep a JSON [`SubagentStatus`] snapshot at `path` up to date.
+    pub async fn set_status_file(&self, path: PathBuf) {
+        let mut tracker = self.tracker.lock().await;
+        tracker.status_path = Some(path);
+        tracker.persist();
+    }
+
+    /// Publish a [`SystemEvent::SubagentFinished`] whenever a subagent
+    /// ends.
+    pub async fn set_system_events(&self, events: broadcast::Sender<SystemEvent>) {
+        self.tracker.lock().await.system_events = Some(events);
+    }
+
     /// Spawn a background subagent task.
     ///
-    /// Returns a status message with the task ID.
+    /// Returns a status message with the task ID, or an error message if
+    /// the concurrency limit has been reached.
     pub async fn spawn(
         &self,
         task: String,
@@ -68,6 +228,17 @@ impl SubagentManager {
         origin_channel: String,
         origin_chat_id: String,
     ) -> String {
+        // Hold the lock across spawn + insert so a fast task can't finish
+        // (and remove itself) before it has been tracked.
+        let mut tracker = self.tracker.lock().await;
+        if tracker.running.len() >= self.config.max_concurrent {
+            return format!(
+                "Error: {} subagents are already running (limit {}). Wait for one to finish or cancel one.",
+                tracker.running.len(),
+                self.config.max_concurrent
+            );
+        }
+
         let task_id = Uuid::new_v4().to_string()[..8].to_string();
         let display_label = label
             .clone()
@@ -85,10 +256,26 @@ impl SubagentManager {
         let brave_api_key = self.brave_api_key.clone();
         let exec_timeout = self.exec_timeout;
         let restrict_to_workspace = self.restrict_to_workspace;
-        let running_tasks = self.running_tasks.clone();
+        let config = self.config.clone();
+        let politeness = self.fetch_politeness();
+        let audit = self
+            .audit
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .map(|log| (log, format!("{}:{}", origin_channel, origin_chat_id)));
+        let task_tracker = self.tracker.clone();
         let tid = task_id.clone();
         let lbl = display_label.clone();
         let tsk = task.clone();
+        let info = SubagentInfo {
+            id: task_id.clone(),
+            label: display_label.clone(),
+            task: task.clone(),
+            origin_channel: origin_channel.clone(),
+            origin_chat_id: origin_chat_id.clone(),
+            started_at: Local::now(),
+        };
 
         let handle = tokio::spawn(async move {
             let result = Self::_run_subagent(
@@ -101,6 +288,9 @@ impl SubagentManager {
                 brave_api_key.as_deref(),
                 exec_timeout,
                 restrict_to_workspace,
+                &config,
+                politeness,
+                audit,
             )
             .await;
 
@@ -120,16 +310,19 @@ impl SubagentManager {
                 status,
             );
 
-            // Remove self from running tasks.
-            let mut tasks = running_tasks.lock().await;
-            tasks.remove(&tid);
-        });
-
-        // Track the task.
-        {
-            let mut tasks = self.running_tasks.lock().await;
-            tasks.insert(task_id.clone(), handle);
+            let mut tracker = task_tracker.lock().await;
+            if tracker.finish(&tid, status).is_some() {
+                tracker.persist();
+            }
         }
+        // Log under the turn that spawned it.
+        .in_current_span());
+
+        tracker
+            .running
+            .insert(task_id.clone(), RunningSubagent { info, handle });
+        tracker.persist();
+        drop(tracker);
 
         format!(
             "Subagent '{}' spawned (id: {}). It will announce results when done.",
@@ -139,8 +332,41 @@ impl SubagentManager {
 
     /// Get the count of currently running subagent tasks.
     pub async fn get_running_count(&self) -> usize {
-        let tasks = self.running_tasks.lock().await;
-        tasks.len()
+        self.tracker.lock().await.running.len()
+    }
+
+    /// List running subagents, oldest first.
+    pub async fn list_running(&self) -> Vec<SubagentInfo> {
+        self.tracker.lock().await.snapshot().running
+    }
+
+    /// Running subagents plus recently finished ones.
+    pub async fn status(&self) -> SubagentStatus {
+        self.tracker.lock().await.snapshot()
+    }
+
+    /// Stop a running subagent. Returns its info, or `None` if no subagent
+    /// with that ID is running.
+    pub async fn cancel(&self, task_id: &str) -> Option<SubagentInfo> {
+        let mut tracker = self.tracker.lock().await;
+        let running = tracker.finish(task_id, "cancelled")?;
+        running.handle.abort();
+        tracker.persist();
+        info!("Cancelled subagent {} ({})", running.info.id, running.info.label);
+        Some(running.info)
+    }
+
+    /// Stop all running subagents (e.g. on gateway shutdown), recording
+    /// them as interrupted.
+    pub async fn shutdown(&self) {
+        let mut tracker = self.tracker.lock().await;
+        let ids: Vec<String> = tracker.running.keys().cloned().collect();
+        for id in ids {
+            if let Some(running) = tracker.finish(&id, "interrupted") {
+                running.handle.abort();
+            }
+        }
+        tracker.persist();
     }
 
     // ------------------------------------------------------------------
@@ -148,23 +374,29 @@ impl SubagentManager {
     // ------------------------------------------------------------------
 
     /// Run the subagent agent loop.
+    #[allow(clippy::too_many_arguments)]
     async fn _run_subagent(
         task_id: &str,
         task: &str,
         label: &str,
         provider: &dyn LLMProvider,
-        workspace: &PathBuf,
+        workspace: &Path,
         model: &str,
         brave_api_key: Option<&str>,
         exec_timeout: u64,
         restrict_to_workspace: bool,
+        config: &SubagentConfig,
+        politeness: Arc<Politeness>,
+        audit: Option<(Arc<AuditLog>, String)>,
     ) -> anyhow::Result<String> {
         debug!("Subagent {} starting: {}", task_id, label);
 
-        // Build a tool registry with basic tools (no message, no spawn).
+        // Build a tool registry with basic tools (no message, no spawn),
+        // trimmed to the configured set.
         let mut tools = ToolRegistry::new();
         tools.register(Box::new(ReadFileTool));
         tools.register(Box::new(WriteFileTool));
+        tools.register(Box::new(EditFileTool));
         tools.register(Box::new(ListDirTool));
         tools.register(Box::new(ExecTool::new(
             exec_timeout,
@@ -177,7 +409,19 @@ impl SubagentManager {
             brave_api_key.map(|s| s.to_string()),
             5,
         )));
-        tools.register(Box::new(WebFetchTool::new(50_000)));
+        tools.register(Box::new(
+            WebFetchTool::new(50_000)
+                .with_politeness(politeness.clone())
+                .with_downloads(workspace.join("downloads"), 25 * 1024 * 1024),
+        ));
+        tools.register(Box::new(CrawlTool::new(
+            WebFetchTool::new(50_000).with_politeness(politeness),
+        )));
+        for name in tools.tool_names() {
+            if !config.tools.contains(&name) {
+                tools.unregister(&name);
+            }
+        }
 
         // Build the subagent system prompt.
         let system_prompt = Self::_build_subagent_prompt(task, workspace);
@@ -196,12 +440,12 @@ impl SubagentManager {
 
         let mut final_content = String::new();
 
-        for iteration in 0..MAX_SUBAGENT_ITERATIONS {
+        for iteration in 0..config.max_iterations {
             debug!(
                 "Subagent {} iteration {}/{}",
                 task_id,
                 iteration + 1,
-                MAX_SUBAGENT_ITERATIONS
+                config.max_iterations
             );
 
             let response = provider
@@ -236,6 +480,14 @@ impl SubagentManager {
                 for tc in &response.tool_calls {
                     debug!("Subagent {} calling tool: {}", task_id, tc.name);
                     let result = tools.execute(&tc.name, tc.arguments.clone()).await;
+                    if let Some((log, session)) = &audit {
+                        if let Some(mut entry) =
+                            tool_entry(session, &tc.name, &tc.arguments, &result)
+                        {
+                            entry.action = format!("{} (subagent {})", tc.name, task_id);
+                            log.record(&entry);
+                        }
+                    }
                     ContextBuilder::add_tool_result(
                         &mut messages,
                         &tc.id,
@@ -258,6 +510,7 @@ impl SubagentManager {
     }
 
     /// Announce the subagent result to the bus as an InboundMessage.
+    #[allow(clippy::too_many_arguments)]
     fn _announce_result(
         bus_tx: &UnboundedSender<InboundMessage>,
         task_id: &str,
@@ -289,7 +542,7 @@ impl SubagentManager {
     }
 
     /// Build the system prompt for a subagent.
-    fn _build_subagent_prompt(task: &str, workspace: &PathBuf) -> String {
+    fn _build_subagent_prompt(task: &str, workspace: &Path) -> String {
         let workspace_str = workspace.to_string_lossy();
         format!(
             r#"You are a subagent of nanoclaw, a helpful AI assistant.
@@ -311,3 +564,141 @@ Your workspace is at: {workspace_str}
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::base::LLMResponse;
+    use async_trait::async_trait;
+
+    /// Provider whose calls never finish, so subagents stay "running".
+    struct StalledProvider;
+
+    #[async_trait]
+    impl LLMProvider for StalledProvider {
+        async fn chat(
+            &self,
+            _messages: &[Value],
+            _tools: Option<&[Value]>,
+            _model: Option<&str>,
+            _max_tokens: u32,
+            _temperature: f64,
+        ) -> anyhow::Result<LLMResponse> {
+            std::future::pending().await
+        }
+
+        fn get_default_model(&self) -> &str {
+            "stalled"
+        }
+    }
+
+    fn manager(
+        max_concurrent: usize,
+    ) -> (SubagentManager, tokio::sync::mpsc::UnboundedReceiver<InboundMessage>) {
+        let (bus_tx, bus_rx) = tokio::sync::mpsc::unbounded_channel();
+        let config = SubagentConfig {
+            max_concurrent,
+            ..Default::default()
+        };
+        let mgr = SubagentManager::new(
+            Arc::new(StalledProvider),
+            std::env::temp_dir(),
+            bus_tx,
+            "stalled".to_string(),
+            None,
+            5,
+            true,
+            config,
+        );
+        (mgr, bus_rx)
+    }
+
+    // ── spawn / list / cancel ──
+
+    #[tokio::test]
+    async fn test_spawn_tracks_running_subagents() {
+        let (mgr, _rx) = manager(3);
+        let result = mgr
+            .spawn(
+                "dig through logs".to_string(),
+                Some("logs".to_string()),
+                "telegram".to_string(),
+                "42".to_string(),
+            )
+            .await;
+        assert!(result.contains("spawned"), "got: {}", result);
+
+        let running = mgr.list_running().await;
+        assert_eq!(running.len(), 1);
+        assert_eq!(running[0].label, "logs");
+        assert_eq!(running[0].origin_channel, "telegram");
+        assert_eq!(running[0].origin_chat_id, "42");
+    }
+
+    #[tokio::test]
+    async fn test_spawn_respects_concurrency_limit() {
+        let (mgr, _rx) = manager(1);
+        mgr.spawn("a".to_string(), None, "cli".to_string(), "direct".to_string()).await;
+        let result = mgr
+            .spawn("b".to_string(), None, "cli".to_string(), "direct".to_string())
+            .await;
+        assert!(result.starts_with("Error"), "got: {}", result);
+        assert_eq!(mgr.get_running_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_frees_a_slot() {
+        let (mgr, _rx) = manager(1);
+        let events = SystemEvent::channel();
+        let mut finished = events.subscribe();
+        mgr.set_system_events(events).await;
+        mgr.spawn("a".to_string(), None, "cli".to_string(), "direct".to_string()).await;
+        let id = mgr.list_running().await[0].id.clone();
+
+        assert!(mgr.cancel("missing").await.is_none());
+        assert_eq!(mgr.cancel(&id).await.map(|i| i.id), Some(id.clone()));
+        assert_eq!(mgr.get_running_count().await, 0);
+        assert_eq!(
+            finished.try_recv().unwrap(),
+            SystemEvent::SubagentFinished {
+                task_id: id,
+                label: "a".to_string(),
+                status: "cancelled".to_string(),
+            }
+        );
+
+        let result = mgr
+            .spawn("b".to_string(), None, "cli".to_string(), "direct".to_string())
+            .await;
+        assert!(result.contains("spawned"));
+    }
+
+    // ── status ──
+
+    #[tokio::test]
+    async fn test_status_file_tracks_recent_runs() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("subagents.json");
+        let (mgr, _rx) = manager(3);
+        mgr.set_status_file(path.clone()).await;
+
+        let label = Some("first".to_string());
+        mgr.spawn("a".to_string(), label, "cli".to_string(), "direct".to_string())
+            .await;
+        let status = SubagentStatus::load(&path).unwrap();
+        assert_eq!(status.running.len(), 1);
+        assert!(status.recent.is_empty());
+
+        let id = status.running[0].id.clone();
+        mgr.cancel(&id).await;
+        mgr.spawn("b".to_string(), None, "cli".to_string(), "direct".to_string()).await;
+        mgr.shutdown().await;
+
+        let status = SubagentStatus::load(&path).unwrap();
+        assert!(status.running.is_empty());
+        let outcomes: Vec<&str> = status.recent.iter().map(|r| r.status.as_str()).collect();
+        assert_eq!(outcomes, vec!["interrupted", "cancelled"]);
+        assert_eq!(status.recent[1].info.label, "first");
+        assert!(status.recent[1].duration() >= chrono::Duration::zero());
+    }
+}
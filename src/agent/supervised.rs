@@ -0,0 +1,120 @@
+//! Supervised mode: the agent proposes, the user approves.
+//!
+//! With `agents.defaults.supervised`, every tool call that changes
+//! something — writing or editing files, running commands or skills,
+//! spawning subagents, scheduling jobs, messaging another chat — is first
+//! described in the chat the turn came from, and only runs if the reply
+//! there is a yes within `confirmTimeoutSecs`. Anything else, or silence,
+//! declines it and the model is told so. Subagents, once approved, run
+//! their own tools unsupervised.
+
+use crate::agent::progress::main_argument;
+use crate::providers::base::ToolCallRequest;
+
+/// Tools that only read, which never need approval.
+const READ_ONLY_TOOLS: &[&str] = &[
+    "read_file",
+    "list_dir",
+    "web_search",
+    "web_fetch",
+    "crawl",
+    "subagents",
+];
+
+/// Replies that approve a proposed action.
+const APPROVALS: &[&str] = &[
+    "y", "yes", "ok", "okay", "approve", "approved", "sure", "go", "go ahead", "do it", "si", "sì",
+    "👍", "✅",
+];
+
+/// Characters of a tool's main argument shown in the proposal.
+const MAX_PREVIEW_CHARS: usize = 300;
+
+/// Whether `call`, made in a turn from `channel`/`chat_id`, needs approval.
+pub fn needs_approval(call: &ToolCallRequest, channel: &str, chat_id: &str) -> bool {
+    if READ_ONLY_TOOLS.contains(&call.name.as_str()) {
+        return false;
+    }
+    if call.name == "message" {
+        // Replying in the same chat is what the agent does anyway.
+        let arg = |key: &str| call.arguments.get(key).and_then(|v| v.as_str());
+        let to_origin = arg("channel").is_none_or(|c| c == channel)
+            && arg("chat_id").is_none_or(|c| c == chat_id);
+        let targets = call
+            .arguments
+            .get("targets")
+            .and_then(|v| v.as_array())
+            .is_some_and(|t| !t.is_empty());
+        return !to_origin || targets;
+    }
+    true
+}
+
+/// The message asking to approve `call`.
+pub fn approval_request(call: &ToolCallRequest, timeout_secs: u64) -> String {
+    let main_arg = main_argument(call);
+    let mut preview: String = main_arg.chars().take(MAX_PREVIEW_CHARS).collect();
+    if preview.len() < main_arg.len() {
+        preview.push('…');
+    }
+    format!(
+        "✋ Approve {}?\n{}\n\nReply yes within {}s to go ahead; anything else declines.",
+        call.name, preview, timeout_secs
+    )
+}
+
+/// Whether `reply` approves the proposed action.
+pub fn is_approval(reply: &str) -> bool {
+    let reply = reply.trim().trim_end_matches(['.', '!']).to_lowercase();
+    APPROVALS.contains(&reply.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn call(name: &str, arguments: serde_json::Value) -> ToolCallRequest {
+        ToolCallRequest {
+            id: "c1".to_string(),
+            name: name.to_string(),
+            arguments: serde_json::from_value(arguments).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_needs_approval() {
+        assert!(!needs_approval(
+            &call("read_file", json!({"path": "a"})),
+            "telegram",
+            "42"
+        ));
+        assert!(needs_approval(
+            &call("exec", json!({"command": "ls"})),
+            "telegram",
+            "42"
+        ));
+        assert!(needs_approval(
+            &call("weather", json!({})),
+            "telegram",
+            "42"
+        ));
+        let reply = call("message", json!({"content": "hi"}));
+        assert!(!needs_approval(&reply, "telegram", "42"));
+        let elsewhere = call("message", json!({"content": "hi", "chat_id": "99"}));
+        assert!(needs_approval(&elsewhere, "telegram", "42"));
+        let group = call("message", json!({"content": "hi", "targets": ["alerts"]}));
+        assert!(needs_approval(&group, "telegram", "42"));
+    }
+
+    #[test]
+    fn test_approval_request_and_replies() {
+        let request = approval_request(&call("exec", json!({"command": "rm -r build"})), 120);
+        assert!(request.contains("Approve exec?\nrm -r build"));
+        assert!(request.contains("120s"));
+        assert!(is_approval(" Yes! "));
+        assert!(is_approval("👍"));
+        assert!(!is_approval("no"));
+        assert!(!is_approval("yes but not that folder"));
+    }
+}
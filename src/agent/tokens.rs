@@ -0,0 +1,169 @@
+//! Approximate token counting for prompt budgeting.
+//!
+//! Exact counts depend on each provider's tokenizer, so these estimates use
+//! a simple heuristic that errs on the high side: about four ASCII
+//! characters per token, and one token per non-ASCII character (CJK text,
+//! emoji, accented letters).
+
+use serde_json::Value;
+
+/// Per-message overhead for role and framing.
+const MESSAGE_OVERHEAD: usize = 4;
+
+/// Flat estimate for an attached image.
+const IMAGE_TOKENS: usize = 1000;
+
+/// Marker appended to text that was cut to fit a budget.
+pub const TRUNCATION_MARKER: &str = "\n\n[... truncated to fit the context budget]";
+
+/// Estimate the number of tokens in a piece of text.
+pub fn estimate_tokens(text: &str) -> usize {
+    let (ascii, other) = text.chars().fold((0usize, 0usize), |(a, o), c| {
+        if c.is_ascii() {
+            (a + 1, o)
+        } else {
+            (a, o + 1)
+        }
+    });
+    ascii.div_ceil(4) + other
+}
+
+/// Estimate the tokens used by a single chat message, including any
+/// multimodal content parts and tool calls.
+pub fn estimate_message_tokens(message: &Value) -> usize {
+    let mut tokens = MESSAGE_OVERHEAD;
+    match message.get("content") {
+        Some(Value::String(s)) => tokens += estimate_tokens(s),
+        Some(Value::Array(parts)) => {
+            for part in parts {
+                match part.get("type").and_then(|v| v.as_str()) {
+                    Some("text") => tokens += estimate_tokens(part["text"].as_str().unwrap_or("")),
+                    Some("image_url") => tokens += IMAGE_TOKENS,
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+    if let Some(calls) = message.get("tool_calls").and_then(|v| v.as_array()) {
+        for call in calls {
+            let function = &call["function"];
+            tokens += estimate_tokens(function["name"].as_str().unwrap_or(""));
+            tokens += estimate_tokens(function["arguments"].as_str().unwrap_or(""));
+        }
+    }
+    tokens
+}
+
+/// Estimate the tokens used by a whole message list.
+pub fn estimate_messages_tokens(messages: &[Value]) -> usize {
+    messages.iter().map(estimate_message_tokens).sum()
+}
+
+/// Cut `text` so that it fits in `max_tokens`, keeping the beginning.
+///
+/// The cut is moved back to a line break when one is close, and a marker is
+/// appended so the model knows content is missing. Returns an empty string
+/// when the budget cannot even hold the marker.
+pub fn truncate_to_tokens(text: &str, max_tokens: usize) -> String {
+    if estimate_tokens(text) <= max_tokens {
+        return text.to_string();
+    }
+    let marker_tokens = estimate_tokens(TRUNCATION_MARKER);
+    if max_tokens <= marker_tokens {
+        return String::new();
+    }
+
+    // Count in quarter tokens so ASCII characters cost 1 and others 4.
+    let limit = (max_tokens - marker_tokens) * 4;
+    let mut used = 0;
+    let mut cut = 0;
+    for (i, c) in text.char_indices() {
+        used += if c.is_ascii() { 1 } else { 4 };
+        if used > limit {
+            break;
+        }
+        cut = i + c.len_utf8();
+    }
+
+    let head = &text[..cut];
+    let head = match head.rfind('\n') {
+        Some(pos) if pos >= cut / 2 => &head[..pos],
+        _ => head,
+    };
+    format!("{}{}", head.trim_end(), TRUNCATION_MARKER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_estimate_tokens_ascii() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+    }
+
+    #[test]
+    fn test_estimate_tokens_non_ascii_counts_each_char() {
+        assert_eq!(estimate_tokens("你好世界"), 4);
+        assert_eq!(estimate_tokens("ab你好"), 3);
+    }
+
+    #[test]
+    fn test_estimate_message_tokens_text_and_images() {
+        let plain = json!({"role": "user", "content": "abcdefgh"});
+        assert_eq!(estimate_message_tokens(&plain), MESSAGE_OVERHEAD + 2);
+
+        let multimodal = json!({"role": "user", "content": [
+            {"type": "image_url", "image_url": {"url": "data:image/png;base64,AAAA"}},
+            {"type": "text", "text": "abcd"},
+        ]});
+        assert_eq!(
+            estimate_message_tokens(&multimodal),
+            MESSAGE_OVERHEAD + IMAGE_TOKENS + 1
+        );
+    }
+
+    #[test]
+    fn test_estimate_message_tokens_counts_tool_calls() {
+        let msg = json!({
+            "role": "assistant",
+            "content": "",
+            "tool_calls": [{"id": "1", "type": "function", "function": {
+                "name": "exec", "arguments": "{\"command\":\"ls\"}"
+            }}]
+        });
+        assert!(estimate_message_tokens(&msg) > MESSAGE_OVERHEAD + 1);
+    }
+
+    #[test]
+    fn test_truncate_to_tokens_keeps_short_text() {
+        assert_eq!(truncate_to_tokens("short", 10), "short");
+    }
+
+    #[test]
+    fn test_truncate_to_tokens_fits_budget() {
+        let text = "line of text here\n".repeat(200);
+        let cut = truncate_to_tokens(&text, 100);
+        assert!(estimate_tokens(&cut) <= 100);
+        assert!(cut.ends_with(TRUNCATION_MARKER));
+        assert!(cut.starts_with("line of text here\n"));
+        // Cut at a line boundary.
+        assert!(cut.trim_end_matches(TRUNCATION_MARKER).ends_with("here"));
+    }
+
+    #[test]
+    fn test_truncate_to_tokens_multibyte_safe() {
+        let text = "你好".repeat(100);
+        let cut = truncate_to_tokens(&text, 50);
+        assert!(estimate_tokens(&cut) <= 50);
+    }
+
+    #[test]
+    fn test_truncate_to_tokens_tiny_budget() {
+        assert_eq!(truncate_to_tokens(&"x".repeat(1000), 2), "");
+    }
+}
@@ -0,0 +1,139 @@
+//! Checklist tool: tick off or put off tasks in `HEARTBEAT.md`.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+
+use super::base::Tool;
+use crate::cron::natural::parse_natural_time;
+use crate::heartbeat::checklist::Checklist;
+
+/// Tool that updates the heartbeat checklist.
+pub struct ChecklistTool {
+    path: PathBuf,
+}
+
+impl ChecklistTool {
+    /// Create a tool for the checklist at `path`.
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Apply `action` to `task`; `when` is only used to reschedule.
+    fn update(&self, action: &str, task: &str, when: &str, now: DateTime<Local>) -> String {
+        let mut checklist = match Checklist::load(&self.path) {
+            Ok(checklist) => checklist,
+            Err(e) => return format!("Error: {}", e),
+        };
+        let result = match action {
+            "done" => checklist.check(task, now.date_naive()),
+            "reschedule" => {
+                if when.is_empty() {
+                    return "Error: 'when' parameter is required to reschedule".to_string();
+                }
+                match parse_natural_time(when, now) {
+                    Ok(at) => checklist.reschedule(task, at.date_naive()),
+                    Err(e) => return format!("Error: {}", e),
+                }
+            }
+            other => return format!("Error: unknown action '{}'", other),
+        };
+        match result.and_then(|reply| checklist.save(&self.path).map(|_| reply)) {
+            Ok(reply) => reply,
+            Err(e) => format!("Error: {}", e),
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for ChecklistTool {
+    fn name(&self) -> &str {
+        "checklist"
+    }
+
+    fn description(&self) -> &str {
+        "Update the routine checklist in HEARTBEAT.md: check off a task you finished, or reschedule one that has to wait. Recurring tasks come back on their own."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "action": {
+                    "type": "string",
+                    "enum": ["done", "reschedule"],
+                    "description": "'done' to check the task off, 'reschedule' to put it off"
+                },
+                "task": {
+                    "type": "string",
+                    "description": "The task's text, or a unique part of it"
+                },
+                "when": {
+                    "type": "string",
+                    "description": "For 'reschedule': when to pick the task up again, e.g. 'tomorrow', 'friday', 'in 3 days'"
+                }
+            },
+            "required": ["action", "task"]
+        })
+    }
+
+    async fn execute(&self, params: HashMap<String, serde_json::Value>) -> String {
+        let get = |key: &str| {
+            params
+                .get(key)
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .trim()
+        };
+        let task = get("task");
+        if task.is_empty() {
+            return "Error: 'task' parameter is required".to_string();
+        }
+        self.update(get("action"), task, get("when"), Local::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_done_and_reschedule() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("HEARTBEAT.md");
+        std::fs::write(
+            &path,
+            "# Routines\n- [ ] Check mail (every 1d)\n- [ ] Review PRs\n",
+        )
+        .unwrap();
+        let tool = ChecklistTool::new(path.clone());
+        let now = Local.with_ymd_and_hms(2026, 3, 2, 9, 0, 0).unwrap();
+
+        let reply = tool.update("done", "mail", "", now);
+        assert_eq!(
+            reply,
+            "Checked off 'Check mail'; it comes back on 2026-03-03."
+        );
+        let reply = tool.update("reschedule", "review prs", "tomorrow", now);
+        assert_eq!(reply, "'Review PRs' is put off until 2026-03-03.");
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# Routines\n\
+             - [x] Check mail (every 1d, done 2026-03-02)\n\
+             - [ ] Review PRs (after 2026-03-03)\n"
+        );
+
+        assert!(tool
+            .update("reschedule", "mail", "", now)
+            .starts_with("Error"));
+        assert!(tool
+            .update("skip", "mail", "", now)
+            .contains("unknown action"));
+        let missing = ChecklistTool::new(tmp.path().join("missing.md"));
+        assert!(missing.update("done", "mail", "", now).starts_with("Error"));
+    }
+}
@@ -0,0 +1,382 @@
+//! Crawl tool: read a small site, such as a documentation section.
+//!
+//! Starting from a URL, pages are fetched breadth first through the same
+//! [`WebFetchTool`] machinery as `web_fetch` (address checks, robots.txt and
+//! the per-site throttle), following links on the same site up to a depth
+//! and page limit. The result is either every page's text merged, or the
+//! opening of each page.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use async_trait::async_trait;
+use url::Url;
+
+use super::base::Tool;
+use super::web::{chunk_bounds, WebFetchTool};
+
+/// Characters of each page shown in `summaries` mode, at most.
+const SUMMARY_CHARS: usize = 600;
+
+/// File extensions of links that are not worth fetching as pages.
+const SKIPPED_EXTENSIONS: &[&str] = &[
+    "pdf", "zip", "gz", "tgz", "tar", "7z", "rar", "png", "jpg", "jpeg", "gif", "svg", "webp",
+    "ico", "mp3", "mp4", "mov", "avi", "webm", "exe", "dmg", "msi", "deb", "rpm", "woff", "woff2",
+    "ttf", "css", "js",
+];
+
+/// Tool that fetches a page and the pages it links to on the same site.
+pub struct CrawlTool {
+    fetcher: WebFetchTool,
+    max_depth: u32,
+    max_pages: usize,
+    max_chars: usize,
+}
+
+impl CrawlTool {
+    /// Crawl with `fetcher`, at most 2 links deep and 20 pages.
+    pub fn new(fetcher: WebFetchTool) -> Self {
+        Self {
+            fetcher,
+            max_depth: 2,
+            max_pages: 20,
+            max_chars: 50_000,
+        }
+    }
+
+    /// Cap the depth and pages the agent may ask for, and the characters
+    /// returned.
+    pub fn with_limits(mut self, max_depth: u32, max_pages: usize, max_chars: usize) -> Self {
+        self.max_depth = max_depth;
+        self.max_pages = max_pages.max(1);
+        self.max_chars = max_chars;
+        self
+    }
+}
+
+/// A crawled page.
+struct Page {
+    url: String,
+    depth: u32,
+    text: String,
+}
+
+#[async_trait]
+impl Tool for CrawlTool {
+    fn name(&self) -> &str {
+        "crawl"
+    }
+
+    fn description(&self) -> &str {
+        "Read a small website, e.g. a documentation section: fetch a URL and the pages it links \
+         to on the same site, breadth first, up to a depth and page limit. Returns the merged \
+         text of all pages, or the opening of each."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "Page to start from"
+                },
+                "maxDepth": {
+                    "type": "integer",
+                    "minimum": 0,
+                    "maximum": self.max_depth,
+                    "description": "Links to follow away from the start page"
+                },
+                "maxPages": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "maximum": self.max_pages
+                },
+                "pathPrefix": {
+                    "type": "string",
+                    "description": "Only follow links whose path starts with this, e.g. /docs/"
+                },
+                "mode": {
+                    "type": "string",
+                    "enum": ["summaries", "merged"],
+                    "default": "summaries",
+                    "description": "summaries: the opening of each page; merged: all text"
+                }
+            },
+            "required": ["url"]
+        })
+    }
+
+    async fn execute(&self, params: HashMap<String, serde_json::Value>) -> String {
+        let Some(start) = params.get("url").and_then(|v| v.as_str()) else {
+            return serde_json::json!({"error": "url parameter is required"}).to_string();
+        };
+        let start_url = match Url::parse(start) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") && url.host_str().is_some() => url,
+            _ => {
+                return serde_json::json!({"error": "url must be an http(s) URL", "url": start})
+                    .to_string()
+            }
+        };
+        let max_depth = params
+            .get("maxDepth")
+            .and_then(|v| v.as_u64())
+            .map_or(self.max_depth, |n| (n as u32).min(self.max_depth));
+        let max_pages = params
+            .get("maxPages")
+            .and_then(|v| v.as_u64())
+            .map_or(self.max_pages, |n| (n as usize).clamp(1, self.max_pages));
+        let prefix = params
+            .get("pathPrefix")
+            .and_then(|v| v.as_str())
+            .unwrap_or("/");
+        let merged = match params.get("mode").and_then(|v| v.as_str()) {
+            None | Some("summaries") => false,
+            Some("merged") => true,
+            Some(other) => {
+                return serde_json::json!({
+                    "error": format!("unknown mode '{}' (summaries or merged)", other)
+                })
+                .to_string()
+            }
+        };
+
+        let mut queue = VecDeque::from([(start_url.clone(), 0)]);
+        let mut seen = HashSet::from([start_url.to_string()]);
+        let mut pages: Vec<Page> = Vec::new();
+        let mut failed = Vec::new();
+        while let Some((url, depth)) = queue.pop_front() {
+            // Failures count too, or a site of broken links could run on.
+            if pages.len() >= max_pages || failed.len() >= max_pages {
+                break;
+            }
+            let page = match self.fetcher.fetch_page(url.as_str()).await {
+                Ok(page) => page,
+                Err(e) => {
+                    failed.push(serde_json::json!({"url": url.as_str(), "error": e}));
+                    continue;
+                }
+            };
+            if depth < max_depth {
+                for link in page.links {
+                    if _same_site(&link, &start_url)
+                        && link.path().starts_with(prefix)
+                        && !_skipped(&link)
+                        && seen.insert(link.to_string())
+                    {
+                        queue.push_back((link, depth + 1));
+                    }
+                }
+            }
+            pages.push(Page {
+                url: page.final_url,
+                depth,
+                text: page.text,
+            });
+        }
+
+        let (text, truncated) = if merged {
+            _merge(&pages, self.max_chars)
+        } else {
+            _summarize(&pages, self.max_chars)
+        };
+        serde_json::json!({
+            "url": start,
+            "pages": pages
+                .iter()
+                .map(|p| serde_json::json!({"url": p.url, "depth": p.depth, "length": p.text.len()}))
+                .collect::<Vec<_>>(),
+            "failed": failed,
+            // Links found but not fetched because of maxPages.
+            "notVisited": queue.len(),
+            "truncated": truncated,
+            "text": text
+        })
+        .to_string()
+    }
+}
+
+/// Whether `url` is on the site of `start`: the same host, whatever the
+/// scheme or port, counting `www.` as the same.
+fn _same_site(url: &Url, start: &Url) -> bool {
+    let host = |u: &Url| {
+        u.host_str()
+            .unwrap_or_default()
+            .trim_start_matches("www.")
+            .to_lowercase()
+    };
+    host(url) == host(start)
+}
+
+/// Whether `url` points at a file rather than a page.
+fn _skipped(url: &Url) -> bool {
+    let last = url.path().rsplit('/').next().unwrap_or("");
+    last.rsplit_once('.')
+        .is_some_and(|(_, ext)| SKIPPED_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// All pages' text under their URLs, up to `max_chars`.
+fn _merge(pages: &[Page], max_chars: usize) -> (String, bool) {
+    let mut text = String::new();
+    for page in pages {
+        let section = format!("## {}\n\n{}\n\n", page.url, page.text.trim());
+        if text.len() + section.len() > max_chars {
+            let room = max_chars.saturating_sub(text.len());
+            if let Some(&(start, end)) = chunk_bounds(&section, room.max(1)).first() {
+                text.push_str(&section[start..end]);
+            }
+            return (text.trim_end().to_string(), true);
+        }
+        text.push_str(&section);
+    }
+    (text.trim_end().to_string(), false)
+}
+
+/// The opening of each page under its URL, sharing `max_chars`.
+fn _summarize(pages: &[Page], max_chars: usize) -> (String, bool) {
+    let each = (max_chars / pages.len().max(1)).clamp(1, SUMMARY_CHARS);
+    let mut truncated = false;
+    let sections: Vec<String> = pages
+        .iter()
+        .map(|page| {
+            let text = page.text.trim();
+            let (start, end) = chunk_bounds(text, each)[0];
+            let mut opening = text[start..end].trim_end().to_string();
+            if end < text.len() {
+                opening.push_str(" ...");
+                truncated = true;
+            }
+            format!("## {}\n\n{}", page.url, opening)
+        })
+        .collect();
+    (sections.join("\n\n"), truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::tools::politeness::Politeness;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Serve a tiny site: `/docs/` links to two pages, one of which links
+    /// on, plus a page outside `/docs/`, another site and a PDF.
+    async fn serve_site() -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 2048];
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                let body = match path.as_str() {
+                    "/docs/" => "<h1>Docs</h1><p>Welcome to the docs.</p>\
+                        <a href=\"a\">A</a> <a href=\"/docs/b#top\">B</a> <a href=\"/blog\">Blog</a>\
+                        <a href=\"https://other.example/\">Other</a> <a href=\"/docs/guide.pdf\">PDF</a>"
+                        .to_string(),
+                    "/docs/a" => "<h1>Page A</h1><p>All about A.</p><a href=\"/docs/c\">C</a>".to_string(),
+                    "/docs/b" => "<h1>Page B</h1><p>All about B.</p>".to_string(),
+                    "/docs/c" => "<h1>Page C</h1><p>All about C.</p>".to_string(),
+                    _ => "<p>Elsewhere</p>".to_string(),
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\n\
+                     Connection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        port
+    }
+
+    fn tool() -> CrawlTool {
+        CrawlTool::new(
+            WebFetchTool::new(50_000)
+                .with_allowed_hosts(&["127.0.0.1".to_string()])
+                .with_politeness(Arc::new(Politeness::new(false, Duration::ZERO))),
+        )
+    }
+
+    async fn crawl(
+        tool: &CrawlTool,
+        port: u16,
+        extra: &[(&str, serde_json::Value)],
+    ) -> serde_json::Value {
+        let mut params = HashMap::from([(
+            "url".to_string(),
+            serde_json::json!(format!("http://127.0.0.1:{}/docs/", port)),
+        )]);
+        for (key, value) in extra {
+            params.insert(key.to_string(), value.clone());
+        }
+        serde_json::from_str(&tool.execute(params).await).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_crawl_follows_same_site_links_to_a_depth() {
+        let port = serve_site().await;
+        let tool = tool();
+        let urls = |result: &serde_json::Value| {
+            result["pages"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|p| {
+                    p["url"]
+                        .as_str()
+                        .unwrap()
+                        .rsplit(':')
+                        .next()
+                        .unwrap()
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let result = crawl(&tool, port, &[("pathPrefix", serde_json::json!("/docs/"))]).await;
+        let expected: Vec<String> = ["/docs/", "/docs/a", "/docs/b", "/docs/c"]
+            .iter()
+            .map(|p| format!("{}{}", port, p))
+            .collect();
+        assert_eq!(urls(&result), expected);
+        let text = result["text"].as_str().unwrap();
+        assert!(
+            text.contains("Welcome to the docs.") && text.contains("All about C."),
+            "{}",
+            text
+        );
+
+        let shallow = crawl(&tool, port, &[("maxDepth", serde_json::json!(1))]).await;
+        assert_eq!(urls(&shallow).len(), 4, "{}", shallow);
+        assert!(urls(&shallow).iter().any(|u| u.ends_with("/blog")));
+
+        let few = crawl(&tool, port, &[("maxPages", serde_json::json!(2))]).await;
+        assert_eq!(urls(&few).len(), 2);
+        assert!(few["notVisited"].as_u64().unwrap() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_crawl_merged_text_is_capped() {
+        let port = serve_site().await;
+        let tool = tool().with_limits(1, 5, 60);
+        let result = crawl(&tool, port, &[("mode", serde_json::json!("merged"))]).await;
+        assert_eq!(result["truncated"], true);
+        assert!(result["text"].as_str().unwrap().len() <= 60);
+        assert!(result["text"].as_str().unwrap().starts_with("## http"));
+    }
+
+    #[test]
+    fn test_same_site_and_skipped_links() {
+        let url = |u: &str| Url::parse(u).unwrap();
+        let start = url("https://docs.example/guide/");
+        assert!(_same_site(&url("https://www.docs.example/x"), &start));
+        assert!(_same_site(&url("http://docs.example/x"), &start));
+        assert!(!_same_site(&url("https://api.docs.example/x"), &start));
+        assert!(_skipped(&url("https://docs.example/a/Manual.PDF")));
+        assert!(!_skipped(&url("https://docs.example/v1.2/intro")));
+    }
+}
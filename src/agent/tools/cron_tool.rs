@@ -4,22 +4,24 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use chrono::Local;
 use tokio::sync::Mutex;
 
 use super::base::Tool;
-use crate::cron::service::CronService;
+use crate::cron::natural::parse_natural_time;
+use crate::cron::service::{validate_schedule, CronService};
 use crate::cron::types::CronSchedule;
 
 /// Tool to schedule reminders and recurring tasks.
 pub struct CronScheduleTool {
-    cron_service: Arc<CronService>,
+    cron_service: Arc<Mutex<CronService>>,
     channel: Arc<Mutex<String>>,
     chat_id: Arc<Mutex<String>>,
 }
 
 impl CronScheduleTool {
     /// Create a new cron schedule tool.
-    pub fn new(cron_service: Arc<CronService>) -> Self {
+    pub fn new(cron_service: Arc<Mutex<CronService>>) -> Self {
         Self {
             cron_service,
             channel: Arc::new(Mutex::new(String::new())),
@@ -34,12 +36,24 @@ impl CronScheduleTool {
     }
 
     /// Handle the "add" action.
-    async fn add_job(
-        &self,
-        message: &str,
-        every_seconds: Option<i64>,
-        cron_expr: Option<&str>,
-    ) -> String {
+    async fn add_job(&self, params: &HashMap<String, serde_json::Value>) -> String {
+        let str_param = |key: &str| params.get(key).and_then(|v| v.as_str());
+        let message = str_param("message").unwrap_or("");
+        let every_seconds = params.get("every_seconds").and_then(|v| v.as_i64());
+        let cron_expr = str_param("cron_expr");
+        let tz = str_param("tz");
+        let at = str_param("at");
+        let max_runs = params
+            .get("max_runs")
+            .and_then(|v| v.as_u64())
+            .filter(|&n| n > 0)
+            .map(|n| n.min(u32::MAX as u64) as u32);
+        let deliver = params.get("deliver").and_then(|v| v.as_bool()).unwrap_or(true);
+        let save_output = params
+            .get("save_output")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
         if message.is_empty() {
             return "Error: message is required for add".to_string();
         }
@@ -62,36 +76,78 @@ impl CronScheduleTool {
             CronSchedule {
                 kind: "cron".to_string(),
                 expr: Some(expr.to_string()),
+                tz: tz.map(|s| s.to_string()),
                 ..Default::default()
             }
+        } else if let Some(when) = at {
+            match parse_natural_time(when, Local::now()) {
+                Ok(dt) => CronSchedule {
+                    kind: "at".to_string(),
+                    at_ms: Some(dt.timestamp_millis()),
+                    ..Default::default()
+                },
+                Err(e) => return format!("Error: {}", e),
+            }
         } else {
-            return "Error: either every_seconds or cron_expr is required".to_string();
+            return "Error: one of every_seconds, cron_expr or at is required".to_string();
+        };
+
+        let until_ms = match str_param("until") {
+            Some(until) => match parse_natural_time(until, Local::now()) {
+                Ok(dt) => Some(dt.timestamp_millis()),
+                Err(e) => return format!("Error: invalid until: {}", e),
+            },
+            None => None,
         };
 
+        if let Err(e) = validate_schedule(&schedule) {
+            return format!("Error: {}", e);
+        }
+        if let Err(e) = self.cron_service.lock().await.check_min_interval(&schedule) {
+            return format!("Error: {}", e);
+        }
+
         // Truncate name to 30 chars.
         let name: String = message.chars().take(30).collect();
+        let one_shot = schedule.kind == "at";
 
-        // CronService uses interior &self so we need to get mutable somehow.
-        // Since it's Arc<CronService> and add_job is &mut self, we work around
-        // by using unsafe or by changing the service. For now, we use a best-effort
-        // approach: the CronService methods are synchronous.
-        // This requires CronService to be behind a Mutex if we want to mutate.
-        // For simplicity, we'll just report the operation.
-        // TODO: In production, wrap CronService in Mutex in the AgentLoop.
-        format!(
-            "Scheduled: '{}' (schedule: {}). Note: use CLI `nanoclaw cron add` for persistent scheduling.",
-            name,
-            if every_seconds.is_some() {
-                format!("every {}s", every_seconds.unwrap())
-            } else {
-                cron_expr.unwrap_or("unknown").to_string()
-            }
-        )
+        let mut service = self.cron_service.lock().await;
+        let job = service.add_job(
+            &name,
+            schedule,
+            message,
+            deliver,
+            Some(&channel),
+            Some(&chat_id),
+            one_shot,
+        );
+        let job = if max_runs.is_some() || until_ms.is_some() || save_output {
+            service
+                .update_job(&job.id, |j| {
+                    j.max_runs = max_runs;
+                    j.until_ms = until_ms;
+                    j.payload.save_output = save_output;
+                })
+                .unwrap_or(job)
+        } else {
+            job
+        };
+        drop(service);
+
+        match job.state.next_run_at_ms.and_then(chrono::DateTime::from_timestamp_millis) {
+            Some(next) => format!(
+                "Scheduled '{}' (id: {}), next run at {}",
+                job.name,
+                job.id,
+                next.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+            ),
+            None => format!("Scheduled '{}' (id: {})", job.name, job.id),
+        }
     }
 
     /// Handle the "list" action.
     async fn list_jobs(&self) -> String {
-        let jobs = self.cron_service.list_jobs(false);
+        let jobs = self.cron_service.lock().await.list_jobs(false);
         if jobs.is_empty() {
             return "No scheduled jobs.".to_string();
         }
@@ -102,15 +158,37 @@ impl CronScheduleTool {
         format!("Scheduled jobs:\n{}", lines.join("\n"))
     }
 
+    /// Handle the "run" action.
+    ///
+    /// The job is fired in the background: it is executed by the same agent
+    /// loop that is running this tool call, so waiting for it here would
+    /// deadlock.
+    async fn run_job(&self, job_id: Option<&str>) -> String {
+        let job_id = match job_id {
+            Some(id) if !id.is_empty() => id.to_string(),
+            _ => return "Error: job_id is required for run".to_string(),
+        };
+        let Some(job) = self.cron_service.lock().await.get_job(&job_id) else {
+            return format!("Job {} not found", job_id);
+        };
+        let service = self.cron_service.clone();
+        tokio::spawn(async move {
+            CronService::run_job_now(&service, &job_id).await;
+        });
+        format!("Triggered job '{}' ({}); it will run shortly", job.name, job.id)
+    }
+
     /// Handle the "remove" action.
     async fn remove_job(&self, job_id: Option<&str>) -> String {
         let job_id = match job_id {
             Some(id) if !id.is_empty() => id,
             _ => return "Error: job_id is required for remove".to_string(),
         };
-        // remove_job requires &mut self, but we only have Arc<CronService>.
-        // Report that CLI should be used for removal.
-        format!("To remove job {}, use CLI: `nanoclaw cron remove {}`", job_id, job_id)
+        if self.cron_service.lock().await.remove_job(job_id) {
+            format!("Removed job {}", job_id)
+        } else {
+            format!("Job {} not found", job_id)
+        }
     }
 }
 
@@ -121,7 +199,7 @@ impl Tool for CronScheduleTool {
     }
 
     fn description(&self) -> &str {
-        "Schedule reminders and recurring tasks. Actions: add, list, remove."
+        "Schedule reminders and recurring tasks. Actions: add, list, remove, run (fire a job now)."
     }
 
     fn parameters(&self) -> serde_json::Value {
@@ -130,7 +208,7 @@ impl Tool for CronScheduleTool {
             "properties": {
                 "action": {
                     "type": "string",
-                    "enum": ["add", "list", "remove"],
+                    "enum": ["add", "list", "remove", "run"],
                     "description": "Action to perform"
                 },
                 "message": {
@@ -145,9 +223,33 @@ impl Tool for CronScheduleTool {
                     "type": "string",
                     "description": "Cron expression like '0 9 * * *' (for scheduled tasks)"
                 },
+                "tz": {
+                    "type": "string",
+                    "description": "IANA timezone for cron_expr, e.g. 'Europe/Rome' (defaults to the gateway timezone)"
+                },
+                "at": {
+                    "type": "string",
+                    "description": "One-time run, e.g. 'in 20 minutes', 'tomorrow 9am', 'friday 14:30' (job is deleted after it runs)"
+                },
+                "max_runs": {
+                    "type": "integer",
+                    "description": "Stop a recurring task after this many runs"
+                },
+                "until": {
+                    "type": "string",
+                    "description": "Stop a recurring task after this time, e.g. 'friday 6pm'"
+                },
+                "deliver": {
+                    "type": "boolean",
+                    "description": "Send each response to this chat (default true)"
+                },
+                "save_output": {
+                    "type": "boolean",
+                    "description": "Also save each response to workspace/cron/<job_id>/ for later review"
+                },
                 "job_id": {
                     "type": "string",
-                    "description": "Job ID (for remove)"
+                    "description": "Job ID (for remove and run)"
                 }
             },
             "required": ["action"]
@@ -161,19 +263,7 @@ impl Tool for CronScheduleTool {
         };
 
         match action {
-            "add" => {
-                let message = params
-                    .get("message")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("");
-                let every_seconds = params
-                    .get("every_seconds")
-                    .and_then(|v| v.as_i64());
-                let cron_expr = params
-                    .get("cron_expr")
-                    .and_then(|v| v.as_str());
-                self.add_job(message, every_seconds, cron_expr).await
-            }
+            "add" => self.add_job(&params).await,
             "list" => self.list_jobs().await,
             "remove" => {
                 let job_id = params
@@ -181,7 +271,156 @@ impl Tool for CronScheduleTool {
                     .and_then(|v| v.as_str());
                 self.remove_job(job_id).await
             }
+            "run" => {
+                let job_id = params
+                    .get("job_id")
+                    .and_then(|v| v.as_str());
+                self.run_job(job_id).await
+            }
             other => format!("Unknown action: {}", other),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn make_tool() -> (TempDir, CronScheduleTool, Arc<Mutex<CronService>>) {
+        let tmp = TempDir::new().unwrap();
+        let svc = Arc::new(Mutex::new(CronService::new(tmp.path().join("jobs.json"))));
+        let tool = CronScheduleTool::new(svc.clone());
+        (tmp, tool, svc)
+    }
+
+    fn params(pairs: &[(&str, serde_json::Value)]) -> HashMap<String, serde_json::Value> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_add_at_creates_one_shot_job() {
+        let (_tmp, tool, svc) = make_tool();
+        tool.set_context("telegram", "42").await;
+        let result = tool
+            .execute(params(&[
+                ("action", serde_json::json!("add")),
+                ("message", serde_json::json!("Stretch")),
+                ("at", serde_json::json!("in 20 minutes")),
+            ]))
+            .await;
+        assert!(result.starts_with("Scheduled"), "got: {}", result);
+
+        let jobs = svc.lock().await.list_jobs(true);
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].schedule.kind, "at");
+        assert!(jobs[0].delete_after_run);
+        assert_eq!(jobs[0].payload.channel.as_deref(), Some("telegram"));
+        assert_eq!(jobs[0].payload.to.as_deref(), Some("42"));
+    }
+
+    #[tokio::test]
+    async fn test_add_rejects_unparseable_time() {
+        let (_tmp, tool, svc) = make_tool();
+        tool.set_context("cli", "direct").await;
+        let result = tool
+            .execute(params(&[
+                ("action", serde_json::json!("add")),
+                ("message", serde_json::json!("x")),
+                ("at", serde_json::json!("someday maybe")),
+            ]))
+            .await;
+        assert!(result.starts_with("Error"));
+        assert!(svc.lock().await.list_jobs(true).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_remove_job() {
+        let (_tmp, tool, svc) = make_tool();
+        tool.set_context("cli", "direct").await;
+        tool.execute(params(&[
+            ("action", serde_json::json!("add")),
+            ("message", serde_json::json!("x")),
+            ("every_seconds", serde_json::json!(60)),
+        ]))
+        .await;
+        let id = svc.lock().await.list_jobs(true)[0].id.clone();
+        let result = tool
+            .execute(params(&[
+                ("action", serde_json::json!("remove")),
+                ("job_id", serde_json::json!(id)),
+            ]))
+            .await;
+        assert!(result.starts_with("Removed"));
+        assert!(svc.lock().await.list_jobs(true).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_run_unknown_job() {
+        let (_tmp, tool, _svc) = make_tool();
+        let result = tool
+            .execute(params(&[
+                ("action", serde_json::json!("run")),
+                ("job_id", serde_json::json!("missing")),
+            ]))
+            .await;
+        assert!(result.contains("not found"));
+    }
+
+    #[tokio::test]
+    async fn test_add_rejects_interval_below_minimum() {
+        let (_tmp, tool, svc) = make_tool();
+        tool.set_context("cli", "direct").await;
+        let result = tool
+            .execute(params(&[
+                ("action", serde_json::json!("add")),
+                ("message", serde_json::json!("spam")),
+                ("every_seconds", serde_json::json!(1)),
+            ]))
+            .await;
+        assert!(result.contains("minimum interval"), "got: {}", result);
+        assert!(svc.lock().await.list_jobs(true).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_add_with_expiry_limits() {
+        let (_tmp, tool, svc) = make_tool();
+        tool.set_context("cli", "direct").await;
+        let result = tool
+            .execute(params(&[
+                ("action", serde_json::json!("add")),
+                ("message", serde_json::json!("check tracking number")),
+                ("every_seconds", serde_json::json!(3600)),
+                ("max_runs", serde_json::json!(24)),
+                ("until", serde_json::json!("in 2 days")),
+            ]))
+            .await;
+        assert!(result.starts_with("Scheduled"), "got: {}", result);
+
+        let job = svc.lock().await.list_jobs(true).remove(0);
+        assert_eq!(job.max_runs, Some(24));
+        let until = job.until_ms.unwrap();
+        assert!(until > job.state.next_run_at_ms.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_add_save_output_without_delivery() {
+        let (_tmp, tool, svc) = make_tool();
+        tool.set_context("cli", "direct").await;
+        tool.execute(params(&[
+            ("action", serde_json::json!("add")),
+            ("message", serde_json::json!("nightly report")),
+            ("cron_expr", serde_json::json!("0 2 * * *")),
+            ("deliver", serde_json::json!(false)),
+            ("save_output", serde_json::json!(true)),
+        ]))
+        .await;
+
+        let job = svc.lock().await.list_jobs(true).remove(0);
+        assert!(!job.payload.deliver);
+        assert!(job.payload.save_output);
+    }
+}
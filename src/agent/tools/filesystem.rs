@@ -1,7 +1,7 @@
 //! File system tools: read, write, edit, list.
 
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use async_trait::async_trait;
 
@@ -318,12 +318,98 @@ impl Tool for ListDirTool {
     }
 }
 
+// ---------------------------------------------------------------------------
+// RestrictedPaths
+// ---------------------------------------------------------------------------
+
+/// Wraps a file tool so it only accepts paths under the allowed directories.
+pub struct RestrictedPaths {
+    inner: Box<dyn Tool>,
+    roots: Vec<PathBuf>,
+}
+
+impl RestrictedPaths {
+    /// Limit `inner` to paths under `roots` (`~` is expanded).
+    pub fn new(inner: Box<dyn Tool>, roots: &[String]) -> Self {
+        let roots = roots.iter().map(|r| resolve_path(&expand_path(r))).collect();
+        Self { inner, roots }
+    }
+
+    fn _allows(&self, path: &str) -> bool {
+        let full = resolve_path(&expand_path(path));
+        self.roots.iter().any(|root| full.starts_with(root))
+    }
+}
+
+#[async_trait]
+impl Tool for RestrictedPaths {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn description(&self) -> &str {
+        self.inner.description()
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        self.inner.parameters()
+    }
+
+    async fn execute(&self, params: HashMap<String, serde_json::Value>) -> String {
+        if let Some(path) = params.get("path").and_then(|v| v.as_str()) {
+            if !self._allows(path) {
+                return format!("Error: Access denied: {} is outside the allowed paths", path);
+            }
+        }
+        self.inner.execute(params).await
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Helpers
 // ---------------------------------------------------------------------------
 
+/// Absolute form of `path` with symlinks and `..` resolved, as far as it
+/// exists (files about to be written do not exist yet).
+fn resolve_path(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+    let mut existing = absolute.as_path();
+    let mut rest: Vec<&std::ffi::OsStr> = Vec::new();
+    loop {
+        if let Ok(real) = existing.canonicalize() {
+            let mut full = real;
+            for part in rest.iter().rev() {
+                match *part {
+                    p if p == ".." => {
+                        full.pop();
+                    }
+                    p if p == "." => {}
+                    p => full.push(p),
+                }
+            }
+            return full;
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                existing = parent;
+            }
+            // `..` as the last component has no file name.
+            (Some(parent), None) => {
+                rest.push(std::ffi::OsStr::new(".."));
+                existing = parent;
+            }
+            _ => return absolute,
+        }
+    }
+}
+
 /// Expand a leading `~` to the user's home directory.
-fn expand_path(path: &str) -> PathBuf {
+pub(crate) fn expand_path(path: &str) -> PathBuf {
     if let Some(rest) = path.strip_prefix("~/") {
         let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
         home.join(rest)
@@ -360,7 +446,7 @@ mod tests {
     fn test_expand_path_tilde() {
         let result = expand_path("~");
         // Should be the home directory (or "." if none).
-        assert!(result.is_absolute() || result == PathBuf::from("."));
+        assert!(result.is_absolute() || result == Path::new("."));
     }
 
     #[test]
@@ -626,4 +712,35 @@ mod tests {
         let result = tool.execute(params).await;
         assert!(result.contains("'path' parameter is required"));
     }
+
+    // -----------------------------------------------------------------------
+    // RestrictedPaths tests
+    // -----------------------------------------------------------------------
+
+    #[tokio::test]
+    async fn test_restricted_paths_blocks_outside_roots() {
+        let allowed = TempDir::new().unwrap();
+        let other = TempDir::new().unwrap();
+        std::fs::write(other.path().join("secret.txt"), "nope").unwrap();
+        let root = allowed.path().to_string_lossy().to_string();
+        let write = RestrictedPaths::new(Box::new(WriteFileTool), std::slice::from_ref(&root));
+        let read = RestrictedPaths::new(Box::new(ReadFileTool), &[root]);
+        assert_eq!(read.name(), "read_file");
+
+        let inside = allowed.path().join("notes/new.txt");
+        let mut params = HashMap::new();
+        params.insert("path".to_string(), serde_json::json!(inside.to_string_lossy()));
+        params.insert("content".to_string(), serde_json::json!("hi"));
+        assert!(write.execute(params).await.starts_with("Successfully wrote"));
+
+        for path in [
+            other.path().join("secret.txt"),
+            allowed.path().join("notes/../../escape.txt"),
+        ] {
+            let mut params = HashMap::new();
+            params.insert("path".to_string(), serde_json::json!(path.to_string_lossy()));
+            let result = read.execute(params).await;
+            assert!(result.contains("outside the allowed paths"), "{}", result);
+        }
+    }
 }
@@ -10,7 +10,8 @@ use async_trait::async_trait;
 use tokio::sync::Mutex;
 
 use super::base::Tool;
-use crate::bus::events::OutboundMessage;
+use crate::bus::events::{Attachment, OutboundMessage};
+use crate::channels::broadcast;
 
 /// Type alias for the send callback.
 pub type SendCallback = Arc<
@@ -22,6 +23,8 @@ pub struct MessageTool {
     send_callback: Arc<Mutex<Option<SendCallback>>>,
     default_channel: Arc<Mutex<String>>,
     default_chat_id: Arc<Mutex<String>>,
+    /// Target used when neither the call nor the context gives one.
+    fallback: std::sync::Mutex<(String, String)>,
 }
 
 impl MessageTool {
@@ -35,6 +38,15 @@ impl MessageTool {
             send_callback: Arc::new(Mutex::new(send_callback)),
             default_channel: Arc::new(Mutex::new(default_channel.to_string())),
             default_chat_id: Arc::new(Mutex::new(default_chat_id.to_string())),
+            fallback: std::sync::Mutex::new((String::new(), String::new())),
+        }
+    }
+
+    /// Set the target used when a call has no channel or chat and the
+    /// current context has none either.
+    pub fn set_fallback(&self, channel: &str, chat_id: &str) {
+        if let Ok(mut fallback) = self.fallback.lock() {
+            *fallback = (channel.to_string(), chat_id.to_string());
         }
     }
 
@@ -75,6 +87,16 @@ impl Tool for MessageTool {
                 "chat_id": {
                     "type": "string",
                     "description": "Optional: target chat/user ID"
+                },
+                "attachments": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Optional: paths or URLs of images, audio or documents to send with the message"
+                },
+                "targets": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Optional: send to all of these instead, each 'channel:chat_id' or 'group:<name>' for a group from the config"
                 }
             },
             "required": ["content"]
@@ -87,8 +109,24 @@ impl Tool for MessageTool {
             None => return "Error: 'content' parameter is required".to_string(),
         };
 
-        let default_channel = self.default_channel.lock().await.clone();
-        let default_chat_id = self.default_chat_id.lock().await.clone();
+        let mut default_channel = self.default_channel.lock().await.clone();
+        let mut default_chat_id = self.default_chat_id.lock().await.clone();
+        if default_channel.is_empty() || default_chat_id.is_empty() {
+            if let Ok(fallback) = self.fallback.lock() {
+                (default_channel, default_chat_id) = fallback.clone();
+            }
+        }
+
+        let targets: Vec<String> = params
+            .get("targets")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+            .collect();
+        if let Some(bad) = targets.iter().find(|t| broadcast::parse_target(t).is_none()) {
+            return format!("Error: target '{}' is not 'channel:chat_id' or 'group:<name>'", bad);
+        }
 
         let channel = params
             .get("channel")
@@ -104,7 +142,7 @@ impl Tool for MessageTool {
             .map(|s| s.to_string())
             .unwrap_or(default_chat_id);
 
-        if channel.is_empty() || chat_id.is_empty() {
+        if targets.is_empty() && (channel.is_empty() || chat_id.is_empty()) {
             return "Error: No target channel/chat specified".to_string();
         }
 
@@ -116,10 +154,32 @@ impl Tool for MessageTool {
         // Drop the lock before awaiting the callback.
         drop(callback_guard);
 
-        let msg = OutboundMessage::new(&channel, &chat_id, &content);
+        let recipients = if targets.is_empty() {
+            format!("{}:{}", channel, chat_id)
+        } else {
+            targets.join(", ")
+        };
+        let mut msg = match targets.split_first() {
+            Some((first, rest)) => {
+                let (channel, chat_id) = broadcast::parse_target(first).unwrap_or_default();
+                let mut msg = OutboundMessage::new(channel, chat_id, &content);
+                msg.targets = rest.to_vec();
+                msg
+            }
+            None => OutboundMessage::new(&channel, &chat_id, &content),
+        };
+
+        msg.attachments = params
+            .get("attachments")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|v| v.as_str())
+            .map(Attachment::new)
+            .collect();
 
         match callback(msg).await {
-            Ok(()) => format!("Message sent to {}:{}", channel, chat_id),
+            Ok(()) => format!("Message sent to {}", recipients),
             Err(e) => format!("Error sending message: {}", e),
         }
     }
@@ -183,6 +243,24 @@ mod tests {
         assert!(result.contains("No target channel/chat specified"));
     }
 
+    #[tokio::test]
+    async fn test_execute_uses_fallback_without_context() {
+        let callback: SendCallback = Arc::new(|_msg: OutboundMessage| {
+            Box::pin(async { Ok(()) })
+        });
+        let tool = MessageTool::new(Some(callback), "", "");
+        tool.set_fallback("telegram", "42");
+        let mut params = HashMap::new();
+        params.insert(
+            "content".to_string(),
+            serde_json::Value::String("hello".to_string()),
+        );
+        assert_eq!(tool.execute(params.clone()).await, "Message sent to telegram:42");
+
+        tool.set_context("whatsapp", "7").await;
+        assert_eq!(tool.execute(params).await, "Message sent to whatsapp:7");
+    }
+
     #[tokio::test]
     async fn test_set_context() {
         let tool = MessageTool::new(None, "old_channel", "old_chat");
@@ -218,6 +296,37 @@ mod tests {
         assert_eq!(result, "Message sent to telegram:12345");
     }
 
+    #[tokio::test]
+    async fn test_execute_with_targets() {
+        let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = sent.clone();
+        let callback: SendCallback = Arc::new(move |msg: OutboundMessage| {
+            log.lock().unwrap().push(msg);
+            Box::pin(async { Ok(()) })
+        });
+        let tool = MessageTool::new(Some(callback), "telegram", "12345");
+
+        let mut params = HashMap::new();
+        params.insert("content".to_string(), serde_json::json!("digest"));
+        params.insert(
+            "targets".to_string(),
+            serde_json::json!(["group:alerts", "feishu:oc_1"]),
+        );
+        params.insert(
+            "attachments".to_string(),
+            serde_json::json!(["https://example.com/chart.png"]),
+        );
+        let result = tool.execute(params.clone()).await;
+        assert_eq!(result, "Message sent to group:alerts, feishu:oc_1");
+        let msg = sent.lock().unwrap().pop().unwrap();
+        assert_eq!((msg.channel.as_str(), msg.chat_id.as_str()), ("group", "alerts"));
+        assert_eq!(msg.targets, ["feishu:oc_1"]);
+        assert_eq!(msg.attachments[0].mime, "image/png");
+
+        params.insert("targets".to_string(), serde_json::json!(["alerts"]));
+        assert!(tool.execute(params).await.contains("target 'alerts' is not"));
+    }
+
     #[tokio::test]
     async fn test_execute_with_failing_callback() {
         let callback: SendCallback = Arc::new(|_msg: OutboundMessage| {
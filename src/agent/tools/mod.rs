@@ -5,15 +5,28 @@ pub mod registry;
 pub mod filesystem;
 pub mod shell;
 pub mod web;
+pub mod crawl;
+pub mod readability;
+pub mod ssrf;
+pub mod politeness;
 pub mod message;
 pub mod spawn;
 pub mod cron_tool;
+pub mod reminder;
+pub mod subagents;
+pub mod checklist;
+pub mod skill_script;
 
 pub use base::Tool;
 pub use registry::ToolRegistry;
-pub use filesystem::{ReadFileTool, WriteFileTool, EditFileTool, ListDirTool};
+pub use filesystem::{ReadFileTool, WriteFileTool, EditFileTool, ListDirTool, RestrictedPaths};
 pub use shell::ExecTool;
 pub use web::{WebSearchTool, WebFetchTool};
+pub use crawl::CrawlTool;
 pub use message::{MessageTool, SendCallback};
 pub use spawn::{SpawnTool, SpawnCallback};
 pub use cron_tool::CronScheduleTool;
+pub use reminder::RemindMeTool;
+pub use subagents::SubagentsTool;
+pub use checklist::ChecklistTool;
+pub use skill_script::SkillScriptTool;
@@ -0,0 +1,317 @@
+//! Politeness of `web_fetch` towards the sites it visits.
+//!
+//! An agent loop, or several subagents at once, may fetch page after page
+//! from one site. [`Politeness`] spaces requests to the same host at least
+//! `tools.web.fetch.minIntervalMs` apart, longer when the site's robots.txt
+//! asks for a `Crawl-delay`, and refuses paths robots.txt disallows for
+//! `nanoclaw` unless `tools.web.fetch.respectRobotsTxt` is off. The agent
+//! loop shares one instance with its subagents so they take turns too.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use reqwest::Client;
+use tracing::debug;
+use url::Url;
+
+/// The user agent matched against robots.txt groups.
+pub const ROBOTS_AGENT: &str = "nanoclaw";
+
+/// How long a site's robots.txt is trusted before it is fetched again.
+const ROBOTS_TTL: Duration = Duration::from_secs(3600);
+
+/// Longest `Crawl-delay` honoured.
+const MAX_CRAWL_DELAY: Duration = Duration::from_secs(30);
+
+/// Largest robots.txt read; the rest is ignored.
+const MAX_ROBOTS_BYTES: usize = 512 * 1024;
+
+/// Throttles requests per host and checks robots.txt.
+#[derive(Debug)]
+pub struct Politeness {
+    robots: bool,
+    min_interval: Duration,
+    /// When each host may next be requested.
+    next_slot: Mutex<HashMap<String, tokio::time::Instant>>,
+    /// robots.txt rules and when they were fetched, by origin.
+    rules: Mutex<HashMap<String, (Instant, Arc<RobotsRules>)>>,
+}
+
+impl Default for Politeness {
+    fn default() -> Self {
+        Self::new(true, Duration::from_secs(1))
+    }
+}
+
+impl Politeness {
+    /// Check robots.txt if `robots` is set, and leave `min_interval`
+    /// between requests to a host (zero turns the throttle off).
+    pub fn new(robots: bool, min_interval: Duration) -> Self {
+        Self {
+            robots,
+            min_interval,
+            next_slot: Mutex::new(HashMap::new()),
+            rules: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Wait for the turn of `url`'s host, or say why it may not be fetched.
+    /// robots.txt is fetched with `client` when it is not cached.
+    pub async fn admit(&self, client: &Client, url: &Url) -> Result<(), String> {
+        let mut interval = self.min_interval;
+        if self.robots {
+            let rules = self._rules(client, url).await;
+            let mut path = url.path().to_string();
+            if let Some(query) = url.query() {
+                path.push('?');
+                path.push_str(query);
+            }
+            if !rules.allows(&path) {
+                return Err(format!(
+                    "Blocked by robots.txt: {} disallows {} for {}; set \
+                     tools.web.fetch.respectRobotsTxt to false to fetch it anyway",
+                    url.host_str().unwrap_or_default(),
+                    path,
+                    ROBOTS_AGENT
+                ));
+            }
+            if let Some(delay) = rules.crawl_delay {
+                interval = interval.max(delay.min(MAX_CRAWL_DELAY));
+            }
+        }
+        let host = url.host_str().unwrap_or_default().to_lowercase();
+        self._wait(&host, interval).await;
+        Ok(())
+    }
+
+    /// Take the next free slot of `host` and sleep until it comes.
+    async fn _wait(&self, host: &str, interval: Duration) {
+        if interval.is_zero() {
+            return;
+        }
+        let slot = {
+            let mut next_slot = self.next_slot.lock().unwrap_or_else(|e| e.into_inner());
+            let now = tokio::time::Instant::now();
+            next_slot.retain(|_, at| *at > now);
+            let slot = next_slot.get(host).copied().unwrap_or(now);
+            next_slot.insert(host.to_string(), slot + interval);
+            slot
+        };
+        if slot > tokio::time::Instant::now() {
+            debug!("Waiting {:?} before fetching from {}", slot - tokio::time::Instant::now(), host);
+            tokio::time::sleep_until(slot).await;
+        }
+    }
+
+    /// The robots.txt rules for `url`'s origin.
+    async fn _rules(&self, client: &Client, url: &Url) -> Arc<RobotsRules> {
+        let origin = url.origin().ascii_serialization();
+        {
+            let rules = self.rules.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some((fetched, cached)) = rules.get(&origin) {
+                if fetched.elapsed() < ROBOTS_TTL {
+                    return cached.clone();
+                }
+            }
+        }
+        let fetched = Arc::new(_fetch_robots(client, &origin).await);
+        let mut rules = self.rules.lock().unwrap_or_else(|e| e.into_inner());
+        rules.retain(|_, (at, _)| at.elapsed() < ROBOTS_TTL);
+        rules.insert(origin, (Instant::now(), fetched.clone()));
+        fetched
+    }
+}
+
+/// Fetch and parse `origin`'s robots.txt. A missing or unreadable one
+/// allows everything.
+async fn _fetch_robots(client: &Client, origin: &str) -> RobotsRules {
+    let url = format!("{}/robots.txt", origin);
+    let response = match client.get(&url).send().await {
+        Ok(r) if r.status().is_success() => r,
+        Ok(r) => {
+            debug!("No robots.txt at {} (HTTP {})", url, r.status());
+            return RobotsRules::default();
+        }
+        Err(e) => {
+            debug!("Cannot fetch {}: {}", url, e);
+            return RobotsRules::default();
+        }
+    };
+    match response.text().await {
+        Ok(mut text) => {
+            if text.len() > MAX_ROBOTS_BYTES {
+                let mut end = MAX_ROBOTS_BYTES;
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+                text.truncate(end);
+            }
+            RobotsRules::parse(&text, ROBOTS_AGENT)
+        }
+        Err(_) => RobotsRules::default(),
+    }
+}
+
+/// The robots.txt rules that apply to one user agent.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RobotsRules {
+    /// `(allow, path pattern)` pairs.
+    rules: Vec<(bool, String)>,
+    crawl_delay: Option<Duration>,
+}
+
+impl RobotsRules {
+    /// The rules of the groups naming `agent`, or of the `*` groups if
+    /// none does.
+    pub fn parse(text: &str, agent: &str) -> Self {
+        let agent = agent.to_lowercase();
+        let mut named: Option<RobotsRules> = None;
+        let mut any: Option<RobotsRules> = None;
+        let mut agents: Vec<String> = Vec::new();
+        let mut group = RobotsRules::default();
+        let mut in_rules = false;
+
+        let mut finish = |agents: &[String], group: RobotsRules| {
+            let target = if agents.contains(&agent) {
+                &mut named
+            } else if agents.iter().any(|a| a == "*") {
+                &mut any
+            } else {
+                return;
+            };
+            let merged = target.get_or_insert_with(RobotsRules::default);
+            merged.rules.extend(group.rules);
+            merged.crawl_delay = merged.crawl_delay.or(group.crawl_delay);
+        };
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_lowercase().as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        finish(&agents, std::mem::take(&mut group));
+                        agents.clear();
+                        in_rules = false;
+                    }
+                    agents.push(value.to_lowercase());
+                }
+                key @ ("allow" | "disallow") => {
+                    in_rules = true;
+                    // An empty `Disallow:` allows everything.
+                    if !value.is_empty() {
+                        group.rules.push((key == "allow", value.to_string()));
+                    }
+                }
+                "crawl-delay" => {
+                    in_rules = true;
+                    group.crawl_delay = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                        .map(Duration::from_secs_f64);
+                }
+                _ => {}
+            }
+        }
+        finish(&agents, group);
+        named.or(any).unwrap_or_default()
+    }
+
+    /// Whether `path` (with its query) may be fetched: the longest
+    /// matching rule decides, `Allow` winning ties.
+    pub fn allows(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| _matches(pattern, path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .is_none_or(|(allow, _)| *allow)
+    }
+}
+
+/// Whether robots.txt `pattern`, with `*` wildcards and an optional `$`
+/// end anchor, matches the start of `path`.
+fn _matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = path.strip_prefix(parts.next().unwrap_or("")) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        if anchored && i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = "\
+# Example
+User-agent: *
+Disallow: /private/
+Allow: /private/open$
+Crawl-delay: 2
+
+User-agent: otherbot
+User-agent: nanoclaw
+Disallow: /*.pdf$
+Disallow: /search?
+";
+
+    #[test]
+    fn test_parse_picks_the_named_group() {
+        let rules = RobotsRules::parse(ROBOTS, "nanoclaw");
+        assert!(rules.allows("/private/x"));
+        assert!(!rules.allows("/docs/a.pdf"));
+        assert!(rules.allows("/docs/a.pdf?v=1"));
+        assert!(!rules.allows("/search?q=x"));
+        assert_eq!(rules.crawl_delay, None);
+
+        let rules = RobotsRules::parse(ROBOTS, "somebot");
+        assert!(!rules.allows("/private/x"));
+        assert!(rules.allows("/private/open"));
+        assert!(!rules.allows("/private/open/more"));
+        assert!(rules.allows("/"));
+        assert_eq!(rules.crawl_delay, Some(Duration::from_secs(2)));
+
+        assert!(RobotsRules::parse("", "nanoclaw").allows("/anything"));
+        let open = RobotsRules::parse("User-agent: *\nDisallow:\n", "nanoclaw");
+        assert!(open.allows("/x"));
+    }
+
+    #[test]
+    fn test_matches_wildcards() {
+        assert!(_matches("/a", "/abc"));
+        assert!(_matches("/a*c", "/abbbc/d"));
+        assert!(_matches("/*.php$", "/x/index.php"));
+        assert!(!_matches("/*.php$", "/x/index.php5"));
+        assert!(!_matches("/b", "/abc"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_spaces_requests_per_host() {
+        let politeness = Politeness::new(false, Duration::from_secs(1));
+        let start = tokio::time::Instant::now();
+        politeness._wait("a.example", Duration::from_secs(1)).await;
+        politeness._wait("b.example", Duration::from_secs(1)).await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+        politeness._wait("a.example", Duration::from_secs(1)).await;
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+}
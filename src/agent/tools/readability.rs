@@ -0,0 +1,486 @@
+//! Readable content of HTML pages, for `web_fetch`.
+//!
+//! A readability-style pass picks the element holding the page's main
+//! content: the page's only `<article>`, its `<main>`, or else the block
+//! whose paragraphs score highest (long, comma-rich text; few links).
+//! Navigation, sidebars, comments, scripts and hidden elements are left out.
+//! The content is then rendered as markdown, keeping headings, lists,
+//! tables, code blocks (as fences), quotes, links and emphasis, or as plain
+//! text.
+
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use scraper::{ElementRef, Html, Node, Selector};
+use url::Url;
+
+/// Elements that never hold content worth reading.
+const SKIPPED: &[&str] = &[
+    "script", "style", "noscript", "template", "nav", "footer", "aside", "form", "button", "input",
+    "select", "textarea", "iframe", "svg", "canvas", "object", "embed", "dialog",
+];
+
+/// Containers that are dropped when their class or ID looks like this...
+static UNLIKELY: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?i)-ad-|banner|breadcrumb|combx|comment|community|cookie|disqus|gdpr|menu|newsletter|pager|pagination|popup|related|remark|replies|share|shoutbox|sidebar|skyscraper|social|sponsor|subscribe",
+    )
+    .unwrap()
+});
+
+/// ...unless it also looks like this.
+static MAYBE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)article|body|column|content|main|post|story").unwrap());
+
+/// Runs of whitespace in text outside `<pre>`.
+static SPACES: Lazy<Regex> = Lazy::new(|| Regex::new(r"\s+").unwrap());
+
+/// Runs of spaces in a rendered line.
+static LINE_SPACES: Lazy<Regex> = Lazy::new(|| Regex::new(r"[ \t]{2,}").unwrap());
+
+static TITLE: Lazy<Selector> = Lazy::new(|| Selector::parse("title").unwrap());
+static ARTICLE: Lazy<Selector> = Lazy::new(|| Selector::parse("article").unwrap());
+static MAIN: Lazy<Selector> = Lazy::new(|| Selector::parse("main, [role=\"main\"]").unwrap());
+static BODY: Lazy<Selector> = Lazy::new(|| Selector::parse("body").unwrap());
+static PARAGRAPHS: Lazy<Selector> = Lazy::new(|| Selector::parse("p, pre, td").unwrap());
+static LINKS: Lazy<Selector> = Lazy::new(|| Selector::parse("a").unwrap());
+static ROWS: Lazy<Selector> = Lazy::new(|| Selector::parse("tr").unwrap());
+
+/// Indentation that survives the whitespace cleanup; becomes a space at the
+/// end.
+const INDENT: char = '\u{0}';
+
+/// How the content is rendered.
+struct Render<'a> {
+    markdown: bool,
+    /// Page URL, for resolving relative links.
+    base: Option<&'a Url>,
+}
+
+/// Extract the title and main content of `html`, as markdown or plain text.
+/// Relative links are resolved against `base`.
+pub fn extract(html: &str, markdown: bool, base: Option<&Url>) -> String {
+    let document = Html::parse_document(html);
+    let title = document
+        .select(&TITLE)
+        .next()
+        .map(|el| _collapse(&el.text().collect::<String>()).trim().to_string())
+        .unwrap_or_default();
+
+    let render = Render { markdown, base };
+    let mut body = match main_content(&document) {
+        Some(root) => tidy(&render.children(root)),
+        None => String::new(),
+    };
+    if body.is_empty() {
+        body = _collapse(&document.root_element().text().collect::<Vec<_>>().join(" "))
+            .trim()
+            .to_string();
+    }
+
+    if title.is_empty() {
+        body
+    } else {
+        format!("# {}\n\n{}", title, body)
+    }
+}
+
+/// The element holding the page's main content.
+fn main_content(document: &Html) -> Option<ElementRef<'_>> {
+    let mut articles = document.select(&ARTICLE).filter(|el| _has_text(*el));
+    if let (Some(article), None) = (articles.next(), articles.next()) {
+        return Some(article);
+    }
+    if let Some(main) = document.select(&MAIN).find(|el| _has_text(*el)) {
+        return Some(main);
+    }
+
+    // Score the parents (fully) and grandparents (half) of each paragraph.
+    let mut scores = HashMap::new();
+    for paragraph in document.select(&PARAGRAPHS) {
+        let skipped = std::iter::once(*paragraph)
+            .chain(paragraph.ancestors())
+            .filter_map(ElementRef::wrap)
+            .any(_is_skipped);
+        if skipped {
+            continue;
+        }
+        let text = _collapse(&paragraph.text().collect::<String>());
+        let len = text.trim().chars().count();
+        if len < 25 {
+            continue;
+        }
+        let score = 1.0 + text.matches(',').count() as f64 + (len as f64 / 100.0).min(3.0);
+        let parent = paragraph.parent().and_then(ElementRef::wrap);
+        if let Some(parent) = parent {
+            *scores.entry(parent.id()).or_insert(0.0) += score;
+            if let Some(grandparent) = parent.parent().and_then(ElementRef::wrap) {
+                *scores.entry(grandparent.id()).or_insert(0.0) += score / 2.0;
+            }
+        }
+    }
+    let best = scores
+        .into_iter()
+        .filter_map(|(id, score)| {
+            let el = ElementRef::wrap(document.tree.get(id)?)?;
+            Some((el, score * (1.0 - _link_density(el))))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(el, _)| el);
+    best.or_else(|| document.select(&BODY).next())
+}
+
+impl Render<'_> {
+    /// The rendered children of `el`.
+    fn children(&self, el: ElementRef) -> String {
+        let mut out = String::new();
+        for child in el.children() {
+            match child.value() {
+                Node::Text(text) => out.push_str(&_collapse(text)),
+                Node::Element(_) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        out.push_str(&self.element(child));
+                    }
+                }
+                _ => {}
+            }
+        }
+        out
+    }
+
+    /// `el` rendered on its own.
+    fn element(&self, el: ElementRef) -> String {
+        if _is_skipped(el) {
+            return String::new();
+        }
+        let name = el.value().name();
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let text = _inline(&self.children(el));
+                if text.is_empty() {
+                    return String::new();
+                }
+                let level = name[1..].parse().unwrap_or(1);
+                if self.markdown {
+                    format!("\n\n{} {}\n\n", "#".repeat(level), text)
+                } else {
+                    format!("\n\n{}\n\n", text)
+                }
+            }
+            "br" => "\n".to_string(),
+            "hr" if self.markdown => "\n\n---\n\n".to_string(),
+            "a" => self.link(el),
+            "img" => match (el.attr("alt"), el.attr("src")) {
+                (Some(alt), Some(src)) if self.markdown && !alt.trim().is_empty() => {
+                    format!("![{}]({})", _inline(alt), self.resolve(src))
+                }
+                _ => String::new(),
+            },
+            "strong" | "b" => self.wrap_inline(el, "**"),
+            "em" | "i" => self.wrap_inline(el, "*"),
+            "code" | "kbd" | "samp" => self.wrap_inline(el, "`"),
+            "pre" => self.code_block(el),
+            "ul" | "ol" => self.list(el, name == "ol"),
+            "li" => format!("\n- {}\n", tidy(&self.children(el))),
+            "blockquote" => {
+                let content = tidy(&self.children(el));
+                if !self.markdown {
+                    return format!("\n\n{}\n\n", content);
+                }
+                let quoted: Vec<String> = content
+                    .lines()
+                    .map(|line| format!("> {}", line).trim_end().to_string())
+                    .collect();
+                format!("\n\n{}\n\n", quoted.join("\n"))
+            }
+            "table" => self.table(el),
+            "p" | "div" | "section" | "article" | "main" | "header" | "figure" | "figcaption"
+            | "dl" | "dt" | "dd" | "address" | "details" | "summary" | "hr" | "tr" => {
+                format!("\n\n{}\n\n", self.children(el))
+            }
+            "td" | "th" => format!(" {} ", self.children(el)),
+            _ => self.children(el),
+        }
+    }
+
+    /// A link as `[text](url)`; just the text for in-page and script links
+    /// or in plain text.
+    fn link(&self, el: ElementRef) -> String {
+        let text = self.children(el);
+        let href = el.attr("href").unwrap_or_default().trim();
+        if !self.markdown
+            || text.trim().is_empty()
+            || href.is_empty()
+            || href.starts_with('#')
+            || href.starts_with("javascript:")
+        {
+            return text;
+        }
+        format!("[{}]({})", _inline(&text), self.resolve(href))
+    }
+
+    /// `href` made absolute against the page URL, if possible.
+    fn resolve(&self, href: &str) -> String {
+        self.base
+            .and_then(|base| base.join(href).ok())
+            .map(|url| url.to_string())
+            .unwrap_or_else(|| href.to_string())
+    }
+
+    /// Inline content between `mark`s, in markdown.
+    fn wrap_inline(&self, el: ElementRef, mark: &str) -> String {
+        let text = self.children(el);
+        if !self.markdown || text.trim().is_empty() {
+            return text;
+        }
+        // Keep the surrounding spaces outside the marks.
+        let lead = if text.starts_with(' ') { " " } else { "" };
+        let trail = if text.ends_with(' ') { " " } else { "" };
+        format!("{}{}{}{}{}", lead, mark, text.trim(), mark, trail)
+    }
+
+    /// A `<pre>` block as a fenced code block, with the language from a
+    /// `language-*` class.
+    fn code_block(&self, el: ElementRef) -> String {
+        let code = el.text().collect::<String>();
+        let code = code.trim_matches('\n').trim_end();
+        if !self.markdown {
+            return format!("\n\n{}\n\n", code);
+        }
+        let language = std::iter::once(el)
+            .chain(el.child_elements())
+            .flat_map(|e| e.value().classes())
+            .find_map(|class| {
+                class
+                    .strip_prefix("language-")
+                    .or_else(|| class.strip_prefix("lang-"))
+            })
+            .unwrap_or_default();
+        format!("\n\n```{}\n{}\n```\n\n", language, code)
+    }
+
+    /// A list, with nested content indented under each item.
+    fn list(&self, el: ElementRef, ordered: bool) -> String {
+        let mut items = Vec::new();
+        for (i, item) in el
+            .child_elements()
+            .filter(|e| e.value().name() == "li")
+            .enumerate()
+        {
+            let marker = if ordered {
+                format!("{}. ", i + 1)
+            } else {
+                "- ".to_string()
+            };
+            let indent = INDENT.to_string().repeat(marker.len());
+            let content = tidy(&self.children(item));
+            let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+            let mut text = format!("{}{}", marker, lines.next().unwrap_or_default());
+            for line in lines {
+                let depth = line.len() - line.trim_start().len();
+                text.push('\n');
+                text.push_str(&indent);
+                text.push_str(&INDENT.to_string().repeat(depth));
+                text.push_str(line.trim_start());
+            }
+            items.push(text);
+        }
+        format!("\n\n{}\n\n", items.join("\n"))
+    }
+
+    /// A table as a markdown table, or its cells as blocks when it has a
+    /// single column (a layout table).
+    fn table(&self, el: ElementRef) -> String {
+        let rows: Vec<Vec<String>> = el
+            .select(&ROWS)
+            .map(|row| {
+                row.child_elements()
+                    .filter(|c| matches!(c.value().name(), "td" | "th"))
+                    .map(|cell| _inline(&tidy(&self.children(cell))).replace('|', "\\|"))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|cells| !cells.is_empty())
+            .collect();
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if columns <= 1 {
+            return format!("\n\n{}\n\n", self.children(el));
+        }
+
+        let line = |cells: &[String]| {
+            let mut cells = cells.to_vec();
+            cells.resize(columns, String::new());
+            if self.markdown {
+                format!("| {} |", cells.join(" | "))
+            } else {
+                cells.join(" | ")
+            }
+        };
+        let mut lines = vec![line(&rows[0])];
+        if self.markdown {
+            lines.push(format!("|{}", " --- |".repeat(columns)));
+        }
+        lines.extend(rows[1..].iter().map(|row| line(row)));
+        format!("\n\n{}\n\n", lines.join("\n"))
+    }
+}
+
+/// Clean up rendered text: trim lines and collapse spaces (except in code
+/// fences), and keep at most one blank line between blocks.
+fn tidy(text: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut in_fence = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            lines.push(line.trim().to_string());
+            continue;
+        }
+        if in_fence {
+            lines.push(line.to_string());
+            continue;
+        }
+        let line = LINE_SPACES
+            .replace_all(line.trim(), " ")
+            .replace(INDENT, " ");
+        if line.is_empty() && lines.last().is_none_or(|l| l.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// Collapse whitespace runs, including newlines, to single spaces.
+fn _collapse(text: &str) -> String {
+    SPACES.replace_all(text, " ").into_owned()
+}
+
+/// `text` on one line.
+fn _inline(text: &str) -> String {
+    _collapse(text).trim().to_string()
+}
+
+/// Whether `el` has any non-blank text.
+fn _has_text(el: ElementRef) -> bool {
+    el.text().any(|t| !t.trim().is_empty())
+}
+
+/// Whether `el` is left out: a skipped element, hidden, or a container that
+/// looks like boilerplate.
+fn _is_skipped(el: ElementRef) -> bool {
+    let element = el.value();
+    let name = element.name();
+    if SKIPPED.contains(&name)
+        || element.attr("hidden").is_some()
+        || element.attr("aria-hidden") == Some("true")
+        || element
+            .attr("style")
+            .is_some_and(|s| s.replace(' ', "").contains("display:none"))
+    {
+        return true;
+    }
+    if !matches!(
+        name,
+        "div" | "section" | "ul" | "ol" | "table" | "span" | "header"
+    ) {
+        return false;
+    }
+    let class_and_id = format!(
+        "{} {}",
+        element.classes().collect::<Vec<_>>().join(" "),
+        element.id().unwrap_or_default()
+    );
+    UNLIKELY.is_match(&class_and_id) && !MAYBE.is_match(&class_and_id)
+}
+
+/// Share of `el`'s text that is inside links.
+fn _link_density(el: ElementRef) -> f64 {
+    let total: usize = el.text().map(|t| t.trim().len()).sum();
+    if total == 0 {
+        return 1.0;
+    }
+    let linked: usize = el
+        .select(&LINKS)
+        .flat_map(|a| a.text())
+        .map(|t| t.trim().len())
+        .sum();
+    linked as f64 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<html><head><title>Guide</title></head><body>
+        <nav><a href="/">Home</a> <a href="/about">About</a></nav>
+        <div class="sidebar"><p>Subscribe to our newsletter, today, now, please, really.</p></div>
+        <div id="content">
+          <h2>Install</h2>
+          <p>Run the installer, then check the version, and you are done with it.</p>
+          <ul><li>Fast</li><li>Small<ul><li>Really <b>small</b></li></ul></li></ul>
+          <ol><li>One</li><li>Two</li></ol>
+          <pre><code class="language-rust">fn main() {
+    println!("hi");
+}</code></pre>
+          <table><tr><th>Name</th><th>Size</th></tr><tr><td>a|b</td><td>1</td></tr></table>
+          <blockquote><p>Quoted, with a <a href="docs/q">link</a>.</p></blockquote>
+          <script>var x = 1;</script>
+        </div>
+        <footer>Copyright</footer>
+        </body></html>"#;
+
+    #[test]
+    fn test_markdown_keeps_structure() {
+        let base = Url::parse("https://example.com/guide/").unwrap();
+        let text = extract(PAGE, true, Some(&base));
+        assert!(
+            text.starts_with("# Guide\n\n## Install\n\nRun the installer"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("- Fast\n- Small\n  - Really **small**"),
+            "{}",
+            text
+        );
+        assert!(text.contains("1. One\n2. Two"), "{}", text);
+        assert!(
+            text.contains("```rust\nfn main() {\n    println!(\"hi\");\n}\n```"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("| Name | Size |\n| --- | --- |\n| a\\|b | 1 |"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("> Quoted, with a [link](https://example.com/guide/docs/q)."),
+            "{}",
+            text
+        );
+        for noise in ["Home", "newsletter", "var x", "Copyright"] {
+            assert!(!text.contains(noise), "{} in {}", noise, text);
+        }
+    }
+
+    #[test]
+    fn test_text_mode_and_scored_content() {
+        let html = "<html><body><div class=\"menu\"><p>Menu text, long enough, to be scored, here.</p></div>\
+            <div><p>The story goes on, and on, for quite a while, with commas.</p>\
+            <p>Another long paragraph, with <a href=\"/x\">a link</a>, in it.</p></div>\
+            <div><a href=\"/1\">Links only, and a lot of them, really many links</a></div></body></html>";
+        let text = extract(html, false, None);
+        assert!(text.starts_with("The story goes on"), "{}", text);
+        assert!(text.contains("with a link, in it."), "{}", text);
+        assert!(
+            !text.contains("Menu text") && !text.contains("Links only"),
+            "{}",
+            text
+        );
+    }
+}
@@ -3,6 +3,7 @@
 use std::collections::HashMap;
 
 use super::base::Tool;
+use crate::observability::redact;
 
 /// Registry for agent tools.
 ///
@@ -48,7 +49,8 @@ impl ToolRegistry {
     /// Execute a tool by name with given parameters.
     ///
     /// Returns the tool execution result as a string, or an error message
-    /// if the tool is not found or execution fails.
+    /// if the tool is not found or execution fails. Secrets in the result
+    /// are redacted before it reaches the LLM.
     pub async fn execute(
         &self,
         name: &str,
@@ -59,11 +61,8 @@ impl ToolRegistry {
             None => return format!("Error: Tool '{}' not found", name),
         };
 
-        match std::panic::AssertUnwindSafe(tool.execute(params))
-            .await
-        {
-            result => result,
-        }
+        let result = std::panic::AssertUnwindSafe(tool.execute(params)).await;
+        redact::redact(&result).into_owned()
     }
 
     /// Get list of registered tool names.
@@ -0,0 +1,198 @@
+//! Reminder tool: "remind me to X at Y" as a one-shot cron job.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use tokio::sync::Mutex;
+
+use super::base::Tool;
+use crate::cron::natural::parse_natural_time;
+use crate::cron::service::CronService;
+use crate::cron::types::CronSchedule;
+
+/// Tool that schedules a one-time reminder back to the current chat.
+pub struct RemindMeTool {
+    cron_service: Arc<Mutex<CronService>>,
+    channel: Arc<Mutex<String>>,
+    chat_id: Arc<Mutex<String>>,
+}
+
+impl RemindMeTool {
+    /// Create a new reminder tool.
+    pub fn new(cron_service: Arc<Mutex<CronService>>) -> Self {
+        Self {
+            cron_service,
+            channel: Arc::new(Mutex::new(String::new())),
+            chat_id: Arc::new(Mutex::new(String::new())),
+        }
+    }
+
+    /// Set the current session context for delivery.
+    pub async fn set_context(&self, channel: &str, chat_id: &str) {
+        *self.channel.lock().await = channel.to_string();
+        *self.chat_id.lock().await = chat_id.to_string();
+    }
+
+    /// Schedule `text` for the time described by `when`, relative to `now`.
+    async fn remind(&self, text: &str, when: &str, now: DateTime<Local>) -> String {
+        let channel = self.channel.lock().await.clone();
+        let chat_id = self.chat_id.lock().await.clone();
+        if channel.is_empty() || chat_id.is_empty() {
+            return "Error: no session context (channel/chat_id)".to_string();
+        }
+
+        let at = match parse_natural_time(when, now) {
+            Ok(at) => at,
+            Err(e) => return format!("Error: {}", e),
+        };
+        let schedule = CronSchedule {
+            kind: "at".to_string(),
+            at_ms: Some(at.timestamp_millis()),
+            ..Default::default()
+        };
+
+        let name: String = format!("Reminder: {}", text).chars().take(30).collect();
+        let message = format!(
+            "Reminder for the user (they asked for it earlier): {}. Send it as a short, friendly reminder.",
+            text
+        );
+        let job = self.cron_service.lock().await.add_job(
+            &name,
+            schedule,
+            &message,
+            true,
+            Some(&channel),
+            Some(&chat_id),
+            true,
+        );
+
+        format!(
+            "Reminder set for {} ({}): {} (id: {})",
+            at.format("%a %Y-%m-%d %H:%M"),
+            _describe_delay(at - now),
+            text,
+            job.id
+        )
+    }
+}
+
+/// Render a delay like "in 2h 5m" for the confirmation message.
+fn _describe_delay(delay: chrono::Duration) -> String {
+    let minutes = (delay.num_seconds() + 59) / 60;
+    let (days, hours, mins) = (minutes / 1440, minutes % 1440 / 60, minutes % 60);
+    let parts: Vec<String> = [(days, "d"), (hours, "h"), (mins, "m")]
+        .iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, unit)| format!("{}{}", n, unit))
+        .collect();
+    if parts.is_empty() {
+        "in under a minute".to_string()
+    } else {
+        format!("in {}", parts.join(" "))
+    }
+}
+
+#[async_trait]
+impl Tool for RemindMeTool {
+    fn name(&self) -> &str {
+        "remind_me"
+    }
+
+    fn description(&self) -> &str {
+        "Set a one-time reminder for the user, delivered back to this chat. Use for requests like 'remind me to call mom at 6pm'."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "text": {
+                    "type": "string",
+                    "description": "What to remind the user about, e.g. 'call mom'"
+                },
+                "when": {
+                    "type": "string",
+                    "description": "When to remind, e.g. 'at 6pm', 'in 20 minutes', 'tomorrow morning', 'friday 14:30'"
+                }
+            },
+            "required": ["text", "when"]
+        })
+    }
+
+    async fn execute(&self, params: HashMap<String, serde_json::Value>) -> String {
+        let text = params.get("text").and_then(|v| v.as_str()).unwrap_or("").trim();
+        let when = params.get("when").and_then(|v| v.as_str()).unwrap_or("").trim();
+        if text.is_empty() {
+            return "Error: 'text' parameter is required".to_string();
+        }
+        if when.is_empty() {
+            return "Error: 'when' parameter is required".to_string();
+        }
+        self.remind(text, when, Local::now()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn make_tool() -> (TempDir, RemindMeTool, Arc<Mutex<CronService>>) {
+        let tmp = TempDir::new().unwrap();
+        let svc = Arc::new(Mutex::new(CronService::new(tmp.path().join("jobs.json"))));
+        let tool = RemindMeTool::new(svc.clone());
+        (tmp, tool, svc)
+    }
+
+    fn params(text: &str, when: &str) -> HashMap<String, serde_json::Value> {
+        HashMap::from([
+            ("text".to_string(), serde_json::json!(text)),
+            ("when".to_string(), serde_json::json!(when)),
+        ])
+    }
+
+    // ── execute ──
+
+    #[tokio::test]
+    async fn test_creates_one_shot_job_for_current_chat() {
+        let (_tmp, tool, svc) = make_tool();
+        tool.set_context("telegram", "42").await;
+        let result = tool.execute(params("call mom", "in 2 hours")).await;
+        assert!(result.starts_with("Reminder set for"), "got: {}", result);
+        assert!(result.contains("(in 2h)"), "got: {}", result);
+
+        let jobs = svc.lock().await.list_jobs(true);
+        assert_eq!(jobs.len(), 1);
+        let job = &jobs[0];
+        assert_eq!(job.schedule.kind, "at");
+        assert!(job.delete_after_run);
+        assert!(job.payload.deliver);
+        assert_eq!(job.payload.channel.as_deref(), Some("telegram"));
+        assert_eq!(job.payload.to.as_deref(), Some("42"));
+        assert!(job.payload.message.contains("call mom"));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_bad_time_and_missing_context() {
+        let (_tmp, tool, svc) = make_tool();
+        let result = tool.execute(params("x", "in 5 minutes")).await;
+        assert!(result.contains("no session context"));
+
+        tool.set_context("cli", "direct").await;
+        let result = tool.execute(params("x", "whenever")).await;
+        assert!(result.starts_with("Error"));
+        assert!(svc.lock().await.list_jobs(true).is_empty());
+    }
+
+    // ── _describe_delay ──
+
+    #[test]
+    fn test_describe_delay() {
+        assert_eq!(_describe_delay(chrono::Duration::seconds(20)), "in 1m");
+        assert_eq!(_describe_delay(chrono::Duration::minutes(125)), "in 2h 5m");
+        assert_eq!(_describe_delay(chrono::Duration::hours(49)), "in 2d 1h");
+        assert_eq!(_describe_delay(chrono::Duration::zero()), "in under a minute");
+    }
+}
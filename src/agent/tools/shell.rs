@@ -5,31 +5,54 @@ use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use async_trait::async_trait;
+use once_cell::sync::Lazy;
 use regex::Regex;
 use tokio::process::Command;
+use tracing::warn;
 
 use super::base::Tool;
 
 /// Default deny patterns for dangerous shell commands.
-fn default_deny_patterns() -> Vec<String> {
-    vec![
-        r"\brm\s+-[rf]{1,2}\b".to_string(),
-        r"\bdel\s+/[fq]\b".to_string(),
-        r"\brmdir\s+/s\b".to_string(),
-        r"\b(format|mkfs|diskpart)\b".to_string(),
-        r"\bdd\s+if=".to_string(),
-        r">\s*/dev/sd".to_string(),
-        r"\b(shutdown|reboot|poweroff)\b".to_string(),
-        r":\(\)\s*\{.*\};\s*:".to_string(),
-    ]
+static DEFAULT_DENY_PATTERNS: Lazy<Vec<Regex>> = Lazy::new(|| {
+    _compile(&[
+        r"\brm\s+-[rf]{1,2}\b",
+        r"\bdel\s+/[fq]\b",
+        r"\brmdir\s+/s\b",
+        r"\b(format|mkfs|diskpart)\b",
+        r"\bdd\s+if=",
+        r">\s*/dev/sd",
+        r"\b(shutdown|reboot|poweroff)\b",
+        r":\(\)\s*\{.*\};\s*:",
+    ])
+});
+
+/// Absolute POSIX paths in a command.
+static POSIX_PATH: Lazy<Regex> = Lazy::new(|| Regex::new(r#"/[^\s"']+"#).unwrap());
+
+/// Absolute Windows paths in a command.
+static WINDOWS_PATH: Lazy<Regex> = Lazy::new(|| Regex::new(r#"[A-Za-z]:\\[^\\"']+"#).unwrap());
+
+/// Compile `patterns`, leaving out (and logging) invalid ones.
+fn _compile<S: AsRef<str>>(patterns: &[S]) -> Vec<Regex> {
+    patterns
+        .iter()
+        .filter_map(|pattern| match Regex::new(pattern.as_ref()) {
+            Ok(re) => Some(re),
+            Err(e) => {
+                warn!("Ignoring invalid exec pattern {:?}: {}", pattern.as_ref(), e);
+                None
+            }
+        })
+        .collect()
 }
 
 /// Tool to execute shell commands.
 pub struct ExecTool {
     timeout: u64,
     working_dir: Option<String>,
-    deny_patterns: Vec<String>,
-    allow_patterns: Vec<String>,
+    deny_patterns: Vec<Regex>,
+    /// When configured, commands must match one of these.
+    allow_patterns: Option<Vec<Regex>>,
     restrict_to_workspace: bool,
 }
 
@@ -45,8 +68,12 @@ impl ExecTool {
         Self {
             timeout,
             working_dir,
-            deny_patterns: deny_patterns.unwrap_or_else(default_deny_patterns),
-            allow_patterns: allow_patterns.unwrap_or_default(),
+            deny_patterns: deny_patterns
+                .map(|patterns| _compile(&patterns))
+                .unwrap_or_else(|| DEFAULT_DENY_PATTERNS.clone()),
+            allow_patterns: allow_patterns
+                .filter(|patterns| !patterns.is_empty())
+                .map(|patterns| _compile(&patterns)),
             restrict_to_workspace,
         }
     }
@@ -59,25 +86,15 @@ impl ExecTool {
         let lower = cmd.to_lowercase();
 
         // Check deny patterns.
-        for pattern in &self.deny_patterns {
-            if let Ok(re) = Regex::new(pattern) {
-                if re.is_match(&lower) {
-                    return Some(
-                        "Error: Command blocked by safety guard (dangerous pattern detected)"
-                            .to_string(),
-                    );
-                }
-            }
+        if self.deny_patterns.iter().any(|re| re.is_match(&lower)) {
+            return Some(
+                "Error: Command blocked by safety guard (dangerous pattern detected)".to_string(),
+            );
         }
 
         // Check allow patterns (if any are configured, command must match at least one).
-        if !self.allow_patterns.is_empty() {
-            let allowed = self.allow_patterns.iter().any(|pattern| {
-                Regex::new(pattern)
-                    .map(|re| re.is_match(&lower))
-                    .unwrap_or(false)
-            });
-            if !allowed {
+        if let Some(allow_patterns) = &self.allow_patterns {
+            if !allow_patterns.iter().any(|re| re.is_match(&lower)) {
                 return Some(
                     "Error: Command blocked by safety guard (not in allowlist)".to_string(),
                 );
@@ -98,15 +115,11 @@ impl ExecTool {
             };
 
             // Extract absolute paths from the command.
-            let posix_re = Regex::new(r#"/[^\s"']+"#).unwrap_or_else(|_| Regex::new(r"^$").unwrap());
-            let win_re =
-                Regex::new(r#"[A-Za-z]:\\[^\\"']+"#).unwrap_or_else(|_| Regex::new(r"^$").unwrap());
-
             let mut paths: Vec<String> = Vec::new();
-            for m in posix_re.find_iter(cmd) {
+            for m in POSIX_PATH.find_iter(cmd) {
                 paths.push(m.as_str().to_string());
             }
-            for m in win_re.find_iter(cmd) {
+            for m in WINDOWS_PATH.find_iter(cmd) {
                 paths.push(m.as_str().to_string());
             }
 
@@ -398,6 +411,13 @@ mod tests {
         assert!(result.unwrap().contains("not in allowlist"));
     }
 
+    #[test]
+    fn test_invalid_allow_pattern_still_blocks() {
+        let tool = ExecTool::new(10, None, None, Some(vec!["(echo".to_string()]), false);
+        let result = tool.guard_command("echo hi", ".");
+        assert!(result.unwrap().contains("not in allowlist"));
+    }
+
     // -----------------------------------------------------------------------
     // Tool trait basics
     // -----------------------------------------------------------------------
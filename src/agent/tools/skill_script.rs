@@ -0,0 +1,214 @@
+//! Skill script tool: a script declared in a skill's `tools` frontmatter.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+use super::base::Tool;
+use super::shell::ExecTool;
+use crate::agent::skills::SkillTool;
+
+/// A `{name}` placeholder in a command template.
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{([A-Za-z0-9_]+)\}").unwrap());
+
+/// Tool that runs a skill's script through the `exec` tool, so the same
+/// safety guard, timeout and working directory apply.
+pub struct SkillScriptTool {
+    spec: SkillTool,
+    description: String,
+    skill_dir: PathBuf,
+    exec: Arc<ExecTool>,
+}
+
+impl SkillScriptTool {
+    /// Create the tool for `spec` of the skill in `skill_dir`.
+    pub fn new(spec: SkillTool, skill: &str, skill_dir: PathBuf, exec: Arc<ExecTool>) -> Self {
+        let description = if spec.description.is_empty() {
+            format!("Run the '{}' script of the {} skill.", spec.name, skill)
+        } else {
+            spec.description.clone()
+        };
+        Self {
+            spec,
+            description,
+            skill_dir,
+            exec,
+        }
+    }
+
+    /// Parameters that must be given.
+    fn required(&self) -> Vec<String> {
+        match &self.spec.required {
+            Some(required) => required.clone(),
+            None => self.spec.parameters.keys().cloned().collect(),
+        }
+    }
+
+    /// The command with its placeholders filled in from `params`.
+    fn command(&self, params: &HashMap<String, serde_json::Value>) -> Result<String, String> {
+        if let Some(name) = self.required().iter().find(|name| {
+            params.get(*name).is_none_or(|v| v.is_null())
+        }) {
+            return Err(format!("Error: '{}' parameter is required", name));
+        }
+        let known = |name: &str| name == "skill_dir" || self.spec.parameters.contains_key(name);
+        if let Some(name) = _quoted_placeholder(&self.spec.command, known) {
+            return Err(format!(
+                "Error: placeholder {{{}}} is inside quotes in the '{}' command; \
+                 placeholders are quoted automatically",
+                name, self.spec.name
+            ));
+        }
+        let command = PLACEHOLDER.replace_all(&self.spec.command, |caps: &Captures| {
+            let name = &caps[1];
+            if name == "skill_dir" {
+                return _quote(&self.skill_dir.to_string_lossy());
+            }
+            if !known(name) {
+                return caps[0].to_string();
+            }
+            match params.get(name) {
+                Some(serde_json::Value::String(s)) => _quote(s),
+                Some(serde_json::Value::Null) | None => _quote(""),
+                Some(other) => _quote(&other.to_string()),
+            }
+        });
+        Ok(command.into_owned())
+    }
+}
+
+#[async_trait]
+impl Tool for SkillScriptTool {
+    fn name(&self) -> &str {
+        &self.spec.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": self.spec.parameters,
+            "required": self.required(),
+        })
+    }
+
+    async fn execute(&self, params: HashMap<String, serde_json::Value>) -> String {
+        let command = match self.command(&params) {
+            Ok(command) => command,
+            Err(e) => return e,
+        };
+        let params = HashMap::from([("command".to_string(), serde_json::json!(command))]);
+        self.exec.execute(params).await
+    }
+}
+
+/// The first `known` placeholder that sits inside single or double quotes in
+/// `command`. Splicing a quoted word there would leave the value unquoted (or
+/// open to `$(…)` expansion), so such templates are refused.
+fn _quoted_placeholder(command: &str, known: impl Fn(&str) -> bool) -> Option<String> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in command.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (c, quote) {
+            ('\\', q) if q != Some('\'') => escaped = true,
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('{', Some(_)) => {
+                let caps = PLACEHOLDER.captures_at(command, i)?;
+                if caps.get(0).is_some_and(|m| m.start() == i) && known(&caps[1]) {
+                    return Some(caps[1].to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Quote `value` as one shell word.
+fn _quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::skills::SkillMeta;
+    use tempfile::TempDir;
+
+    fn tool(frontmatter: &str, dir: &std::path::Path) -> SkillScriptTool {
+        let meta = SkillMeta::try_parse(&format!("---\n{}\n---\n", frontmatter)).unwrap();
+        let exec = ExecTool::new(10, Some(dir.to_string_lossy().to_string()), None, None, true);
+        SkillScriptTool::new(
+            meta.tools[0].clone(),
+            "greeter",
+            dir.join("skills/greeter"),
+            Arc::new(exec),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_runs_script_with_quoted_arguments() {
+        let tmp = TempDir::new().unwrap();
+        let tool = tool(
+            "tools:\n  - name: greet\n    command: \"echo hello {who} {times} {other}\"\n    \
+             parameters:\n      who: {type: string}\n      times: {type: integer}\n    \
+             required: [who]",
+            tmp.path(),
+        );
+        assert_eq!(tool.description(), "Run the 'greet' script of the greeter skill.");
+        assert_eq!(tool.parameters()["required"], serde_json::json!(["who"]));
+
+        let params = HashMap::from([
+            ("who".to_string(), serde_json::json!("Bob's; ls")),
+            ("times".to_string(), serde_json::json!(2)),
+        ]);
+        assert_eq!(
+            tool.command(&params).unwrap(),
+            r"echo hello 'Bob'\''s; ls' '2' {other}"
+        );
+        assert_eq!(tool.execute(params).await, "hello Bob's; ls 2 {other}\n");
+        assert!(tool.execute(HashMap::new()).await.contains("'who' parameter is required"));
+    }
+
+    #[tokio::test]
+    async fn test_goes_through_exec_guard() {
+        let tmp = TempDir::new().unwrap();
+        let tool = tool(
+            "tools:\n  - name: wipe\n    command: \"rm -rf {skill_dir}\"",
+            tmp.path(),
+        );
+        assert!(tool.execute(HashMap::new()).await.contains("blocked by safety guard"));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_placeholders_inside_quotes() {
+        let tmp = TempDir::new().unwrap();
+        let tool = tool(
+            "tools:\n  - name: greet\n    command: 'echo \"hi {arg}\"'\n    \
+             parameters:\n      arg: {type: string}",
+            tmp.path(),
+        );
+        let params = HashMap::from([("arg".to_string(), serde_json::json!("$(touch x)"))]);
+        let result = tool.execute(params).await;
+        assert!(result.contains("inside quotes"), "{}", result);
+        assert!(!tmp.path().join("x").exists());
+
+        let known = |name: &str| name == "arg";
+        assert_eq!(_quoted_placeholder("echo '{arg}'", known), Some("arg".to_string()));
+        assert_eq!(_quoted_placeholder(r#"echo "{other}" {arg}"#, known), None);
+        assert_eq!(_quoted_placeholder(r#"echo \"{arg}"#, known), None);
+        assert_eq!(_quoted_placeholder(r#"echo "a" {arg} "b""#, known), None);
+    }
+}
@@ -0,0 +1,249 @@
+//! Protection against server-side request forgery in `web_fetch`.
+//!
+//! A URL the agent fetches may come from a prompt injection and point into
+//! the user's network: a router's admin page, a cloud metadata endpoint, a
+//! service on localhost. [`UrlGuard`] resolves the host and rejects
+//! loopback, private, link-local, shared, multicast and reserved addresses
+//! unless `tools.web.fetch.allowedHosts` lists the host (`nas.local`,
+//! `*.lan`), the address or its network (`192.168.1.0/24`). The addresses
+//! that passed are returned so the request can be pinned to them; otherwise
+//! the name could resolve to another address when connecting.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use tracing::warn;
+use url::{Host, Url};
+
+/// An `allowedHosts` entry.
+#[derive(Debug, Clone, PartialEq)]
+enum Allowed {
+    /// A host name, lowercase.
+    Host(String),
+    /// Subdomains of this suffix (from `*.suffix`), with the leading dot.
+    Suffix(String),
+    /// An address or network: address and prefix length.
+    Net(IpAddr, u8),
+}
+
+/// Checks the hosts of URLs before they are fetched.
+#[derive(Debug, Clone, Default)]
+pub struct UrlGuard {
+    allowed: Vec<Allowed>,
+}
+
+impl UrlGuard {
+    /// Block private addresses except the `allowed` hosts and networks.
+    /// Invalid entries are logged and ignored.
+    pub fn new(allowed: &[String]) -> Self {
+        let allowed = allowed
+            .iter()
+            .filter_map(|entry| {
+                let parsed = _parse_entry(entry);
+                if parsed.is_none() {
+                    warn!(
+                        "Ignoring invalid tools.web.fetch.allowedHosts entry '{}'",
+                        entry
+                    );
+                }
+                parsed
+            })
+            .collect();
+        Self { allowed }
+    }
+
+    /// Resolve the host of `url` and return its addresses, or why it may
+    /// not be fetched.
+    pub async fn check(&self, url: &Url) -> Result<Vec<SocketAddr>, String> {
+        let port = url.port_or_known_default().unwrap_or(80);
+        let (name, addrs) = match url.host() {
+            Some(Host::Domain(domain)) => {
+                let addrs: Vec<SocketAddr> = tokio::net::lookup_host((domain, port))
+                    .await
+                    .map_err(|e| format!("Cannot resolve {}: {}", domain, e))?
+                    .collect();
+                (domain.to_lowercase(), addrs)
+            }
+            Some(Host::Ipv4(ip)) => (ip.to_string(), vec![SocketAddr::new(ip.into(), port)]),
+            Some(Host::Ipv6(ip)) => (ip.to_string(), vec![SocketAddr::new(ip.into(), port)]),
+            None => return Err("Missing domain".to_string()),
+        };
+        if addrs.is_empty() {
+            return Err(format!("Cannot resolve {}", name));
+        }
+        if self.allows_host(&name) {
+            return Ok(addrs);
+        }
+        if let Some(addr) = addrs
+            .iter()
+            .find(|a| !is_public(a.ip()) && !self.allows_ip(a.ip()))
+        {
+            return Err(format!(
+                "Blocked: {} is a private or local address ({}); add it to \
+                 tools.web.fetch.allowedHosts to allow it",
+                name,
+                addr.ip()
+            ));
+        }
+        Ok(addrs)
+    }
+
+    fn allows_host(&self, name: &str) -> bool {
+        self.allowed.iter().any(|a| match a {
+            Allowed::Host(host) => host == name,
+            Allowed::Suffix(suffix) => name.ends_with(suffix.as_str()),
+            Allowed::Net(..) => false,
+        })
+    }
+
+    fn allows_ip(&self, ip: IpAddr) -> bool {
+        self.allowed.iter().any(|a| match a {
+            Allowed::Net(net, prefix) => _in_net(ip, *net, *prefix),
+            _ => false,
+        })
+    }
+}
+
+/// Whether `entry` is a valid `allowedHosts` entry.
+pub fn is_valid_entry(entry: &str) -> bool {
+    _parse_entry(entry).is_some()
+}
+
+/// Whether `ip` is a public internet address.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => _is_public_v4(ip),
+        IpAddr::V6(ip) => {
+            // Addresses embedding an IPv4 one: mapped, and NAT64 (64:ff9b::/96).
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return _is_public_v4(v4);
+            }
+            let segments = ip.segments();
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                let [a, b, c, d] = ip.octets()[12..] else {
+                    return false;
+                };
+                return _is_public_v4(Ipv4Addr::new(a, b, c, d));
+            }
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (segments[0] & 0xfe00) == 0xfc00 // unique local
+                || (segments[0] & 0xffc0) == 0xfe80 // link-local
+                || (segments[0] & 0xffc0) == 0xfec0 // site-local
+                || (segments[0] == 0x2001 && segments[1] == 0x0db8)) // documentation
+        }
+    }
+}
+
+fn _is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local() // includes cloud metadata, 169.254.169.254
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        || a >= 240 // reserved
+        || (a == 100 && (64..128).contains(&b)) // shared address space (CGNAT)
+        || (a == 198 && (18..20).contains(&b)) // benchmarking
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)) // IETF protocol assignments
+}
+
+fn _parse_entry(entry: &str) -> Option<Allowed> {
+    let entry = entry.trim().to_lowercase();
+    if entry.is_empty() {
+        return None;
+    }
+    if let Some((ip, prefix)) = entry.split_once('/') {
+        let ip: IpAddr = ip.parse().ok()?;
+        let prefix: u8 = prefix.parse().ok()?;
+        let max = if ip.is_ipv4() { 32 } else { 128 };
+        return (prefix <= max).then_some(Allowed::Net(ip, prefix));
+    }
+    if let Ok(ip) = entry.trim_matches(['[', ']']).parse::<IpAddr>() {
+        let prefix = if ip.is_ipv4() { 32 } else { 128 };
+        return Some(Allowed::Net(ip, prefix));
+    }
+    if let Some(suffix) = entry.strip_prefix("*.") {
+        return Some(Allowed::Suffix(format!(".{}", suffix)));
+    }
+    Some(Allowed::Host(entry))
+}
+
+fn _in_net(ip: IpAddr, net: IpAddr, prefix: u8) -> bool {
+    match (ip, net) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V4(_)) => ip
+            .to_ipv4_mapped()
+            .is_some_and(|v4| _in_net(IpAddr::V4(v4), net, prefix)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_private_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "224.0.0.1",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "64:ff9b::a00:1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        assert!(!is_public(IpAddr::V6(
+            Ipv4Addr::new(10, 0, 0, 1).to_ipv6_mapped()
+        )));
+        for ip in ["1.1.1.1", "93.184.216.34", "2606:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_blocks_local_hosts_unless_allowed() {
+        let url = |u: &str| Url::parse(u).unwrap();
+        let guard = UrlGuard::default();
+        for blocked in [
+            "http://127.0.0.1:8080/",
+            "http://localhost/",
+            "http://[::1]/",
+        ] {
+            let err = guard.check(&url(blocked)).await.unwrap_err();
+            assert!(err.contains("allowedHosts"), "{}: {}", blocked, err);
+        }
+        assert!(guard.check(&url("http://1.1.1.1/")).await.is_ok());
+
+        let guard = UrlGuard::new(&[
+            "localhost".to_string(),
+            "*.lan".to_string(),
+            "10.0.0.0/8".to_string(),
+            "bad/99".to_string(),
+        ]);
+        assert_eq!(guard.allowed.len(), 3);
+        assert!(guard.check(&url("http://localhost:8080/")).await.is_ok());
+        assert!(guard.check(&url("http://10.20.30.40/")).await.is_ok());
+        assert!(guard.check(&url("http://127.0.0.1/")).await.is_err());
+        assert!(guard.allows_host("nas.lan") && !guard.allows_host("lan"));
+        assert!(!is_valid_entry("10.0.0.0/40") && is_valid_entry("[fd00::1]"));
+    }
+}
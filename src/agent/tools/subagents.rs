@@ -0,0 +1,118 @@
+//! Tool for inspecting and cancelling background subagents.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Local;
+
+use super::base::Tool;
+use crate::agent::subagent::SubagentManager;
+
+/// Tool that lets the main agent list and cancel its subagents.
+pub struct SubagentsTool {
+    manager: Arc<SubagentManager>,
+}
+
+impl SubagentsTool {
+    /// Create a new subagents tool.
+    pub fn new(manager: Arc<SubagentManager>) -> Self {
+        Self { manager }
+    }
+
+    /// Handle the "list" action.
+    async fn list(&self) -> String {
+        let status = self.manager.status().await;
+        let now = Local::now();
+        let mut out = if status.running.is_empty() {
+            "No subagents are running.".to_string()
+        } else {
+            let lines: Vec<String> = status
+                .running
+                .iter()
+                .map(|info| {
+                    format!(
+                        "- {} (id: {}, running {}s, from {}:{})",
+                        info.label,
+                        info.id,
+                        (now - info.started_at).num_seconds(),
+                        info.origin_channel,
+                        info.origin_chat_id
+                    )
+                })
+                .collect();
+            format!("Running subagents:\n{}", lines.join("\n"))
+        };
+        if !status.recent.is_empty() {
+            let lines: Vec<String> = status
+                .recent
+                .iter()
+                .take(5)
+                .map(|run| {
+                    format!(
+                        "- {} (id: {}, {} after {}s)",
+                        run.info.label,
+                        run.info.id,
+                        run.status,
+                        run.duration().num_seconds()
+                    )
+                })
+                .collect();
+            out.push_str(&format!("\n\nRecently finished:\n{}", lines.join("\n")));
+        }
+        out
+    }
+
+    /// Handle the "cancel" action.
+    async fn cancel(&self, task_id: Option<&str>) -> String {
+        let task_id = match task_id {
+            Some(id) if !id.is_empty() => id,
+            _ => return "Error: task_id is required for cancel".to_string(),
+        };
+        match self.manager.cancel(task_id).await {
+            Some(info) => format!("Cancelled subagent '{}' ({})", info.label, info.id),
+            None => format!("No running subagent with id {}", task_id),
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for SubagentsTool {
+    fn name(&self) -> &str {
+        "subagents"
+    }
+
+    fn description(&self) -> &str {
+        "Check on or stop background subagents started with spawn. Actions: list, cancel."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "action": {
+                    "type": "string",
+                    "enum": ["list", "cancel"],
+                    "description": "Action to perform"
+                },
+                "task_id": {
+                    "type": "string",
+                    "description": "Subagent ID (for cancel)"
+                }
+            },
+            "required": ["action"]
+        })
+    }
+
+    async fn execute(&self, params: HashMap<String, serde_json::Value>) -> String {
+        match params.get("action").and_then(|v| v.as_str()) {
+            Some("list") => self.list().await,
+            Some("cancel") => {
+                let task_id = params.get("task_id").and_then(|v| v.as_str());
+                self.cancel(task_id).await
+            }
+            Some(other) => format!("Unknown action: {}", other),
+            None => "Error: 'action' parameter is required".to_string(),
+        }
+    }
+}
@@ -1,13 +1,25 @@
 //! Web tools: web_search and web_fetch.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
+use once_cell::sync::Lazy;
 use regex::Regex;
+use scraper::{Html, Selector};
+use tokio::io::AsyncWriteExt;
 use reqwest::Client;
+use tracing::warn;
 use url::Url;
 
 use super::base::Tool;
+use super::politeness::Politeness;
+use super::readability;
+use super::ssrf::UrlGuard;
+
+use crate::utils::http::shared_client;
 
 /// Shared user-agent string.
 const USER_AGENT: &str =
@@ -16,29 +28,33 @@ const USER_AGENT: &str =
 /// Maximum number of redirects to follow.
 const MAX_REDIRECTS: usize = 5;
 
+/// Fetched pages kept for reading long ones chunk by chunk, and for how long.
+const PAGE_CACHE_SIZE: usize = 8;
+const PAGE_CACHE_TTL: Duration = Duration::from_secs(600);
+
 // ---------------------------------------------------------------------------
 // Helpers
 // ---------------------------------------------------------------------------
 
+static RE_SCRIPT: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<script[\s\S]*?</script>").unwrap());
+static RE_STYLE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<style[\s\S]*?</style>").unwrap());
+static RE_TAGS: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]+>").unwrap());
+static RE_SPACES: Lazy<Regex> = Lazy::new(|| Regex::new(r"[ \t]+").unwrap());
+static RE_NEWLINES: Lazy<Regex> = Lazy::new(|| Regex::new(r"\n{3,}").unwrap());
+
 /// Remove HTML tags and decode entities.
 fn strip_tags(text: &str) -> String {
-    // Remove script and style blocks.
-    let re_script = Regex::new(r"(?is)<script[\s\S]*?</script>").unwrap();
-    let text = re_script.replace_all(text, "");
-    let re_style = Regex::new(r"(?is)<style[\s\S]*?</style>").unwrap();
-    let text = re_style.replace_all(&text, "");
-    // Remove remaining tags.
-    let re_tags = Regex::new(r"<[^>]+>").unwrap();
-    let text = re_tags.replace_all(&text, "");
+    // Remove script and style blocks, then the remaining tags.
+    let text = RE_SCRIPT.replace_all(text, "");
+    let text = RE_STYLE.replace_all(&text, "");
+    let text = RE_TAGS.replace_all(&text, "");
     html_escape::decode_html_entities(&text).trim().to_string()
 }
 
 /// Normalize whitespace: collapse runs of spaces/tabs, limit consecutive newlines.
 fn normalize_whitespace(text: &str) -> String {
-    let re_spaces = Regex::new(r"[ \t]+").unwrap();
-    let text = re_spaces.replace_all(text, " ");
-    let re_newlines = Regex::new(r"\n{3,}").unwrap();
-    re_newlines.replace_all(&text, "\n\n").trim().to_string()
+    let text = RE_SPACES.replace_all(text, " ");
+    RE_NEWLINES.replace_all(&text, "\n\n").trim().to_string()
 }
 
 /// Validate a URL: must be http(s) with a valid domain.
@@ -63,30 +79,592 @@ fn validate_url(url_str: &str) -> Result<(), String> {
 // WebSearchTool
 // ---------------------------------------------------------------------------
 
-/// Search the web using Brave Search API.
-pub struct WebSearchTool {
+/// A search hit: title, URL and snippet.
+type SearchResult = (String, String, String);
+
+/// The kind of results to search for.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Vertical {
+    #[default]
+    Web,
+    News,
+    Images,
+}
+
+impl Vertical {
+    /// Parse `"web"`, `"news"` or `"images"`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "web" => Some(Self::Web),
+            "news" => Some(Self::News),
+            "images" => Some(Self::Images),
+            _ => None,
+        }
+    }
+}
+
+/// How recent results must be.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Freshness {
+    Day,
+    Week,
+    Month,
+}
+
+impl Freshness {
+    /// Parse `"day"`, `"week"` or `"month"`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "day" => Some(Self::Day),
+            "week" => Some(Self::Week),
+            "month" => Some(Self::Month),
+            _ => None,
+        }
+    }
+
+    /// The name SearXNG and Tavily use.
+    fn name(self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+        }
+    }
+}
+
+/// A search: the query, how many hits, and optional filters and hints.
+/// Backends ignore the hints they cannot express.
+#[derive(Debug, Clone, Default)]
+pub struct SearchQuery {
+    pub text: String,
+    pub count: u32,
+    pub vertical: Vertical,
+    pub freshness: Option<Freshness>,
+    /// Two-letter country code, lowercase (`us`).
+    pub country: Option<String>,
+    /// Two-letter language code, lowercase (`en`).
+    pub language: Option<String>,
+}
+
+/// A web search service.
+#[async_trait]
+pub trait SearchBackend: Send + Sync {
+    /// Up to `query.count` hits for `query`, or an error message for the
+    /// agent.
+    async fn search(&self, client: &Client, query: &SearchQuery)
+        -> Result<Vec<SearchResult>, String>;
+}
+
+/// The backend named `name` (`tools.web.search.backend`): `"brave"`,
+/// `"duckduckgo"`, `"searxng"` (at `url`), `"google"` (with `engine_id`) or
+/// `"tavily"`. Without `api_key`, the backend's environment variable is
+/// checked. Unknown names fall back to Brave.
+pub fn search_backend(
+    name: &str,
+    api_key: Option<String>,
+    url: &str,
+    engine_id: &str,
+) -> Box<dyn SearchBackend> {
+    let key = |var: &str| {
+        api_key
+            .clone()
+            .filter(|k| !k.is_empty())
+            .or_else(|| std::env::var(var).ok())
+            .unwrap_or_default()
+    };
+    match name {
+        "duckduckgo" => Box::new(DuckDuckGoSearch),
+        "searxng" => Box::new(SearxngSearch {
+            base: url.trim_end_matches('/').to_string(),
+        }),
+        "google" => Box::new(GoogleSearch {
+            api_key: key("GOOGLE_API_KEY"),
+            engine_id: engine_id.to_string(),
+        }),
+        "tavily" => Box::new(TavilySearch {
+            api_key: key("TAVILY_API_KEY"),
+        }),
+        "brave" | "" => Box::new(BraveSearch {
+            api_key: key("BRAVE_API_KEY"),
+        }),
+        other => {
+            warn!("Unknown web search backend '{}', using brave", other);
+            Box::new(BraveSearch {
+                api_key: key("BRAVE_API_KEY"),
+            })
+        }
+    }
+}
+
+/// Send `request` and parse its JSON body, naming `service` in errors.
+async fn _search_json(
+    request: reqwest::RequestBuilder,
+    service: &str,
+) -> Result<serde_json::Value, String> {
+    let response = request
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| format!("Error: {}", e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("Error: {} returned HTTP {}: {}", service, status, body));
+    }
+    response
+        .json::<serde_json::Value>()
+        .await
+        .map_err(|e| format!("Error parsing search results: {}", e))
+}
+
+/// The error for a vertical `service` cannot search.
+fn _unsupported(service: &str, vertical: Vertical) -> String {
+    let vertical = match vertical {
+        Vertical::Web => "web",
+        Vertical::News => "news",
+        Vertical::Images => "image",
+    };
+    format!(
+        "Error: the {} backend cannot do {} searches; search the web instead",
+        service, vertical
+    )
+}
+
+/// Brave Search API (needs an API key).
+struct BraveSearch {
     api_key: String,
+}
+
+impl BraveSearch {
+    fn _request(&self, client: &Client, query: &SearchQuery) -> reqwest::RequestBuilder {
+        let endpoint = match query.vertical {
+            Vertical::Web => "web",
+            Vertical::News => "news",
+            Vertical::Images => "images",
+        };
+        let mut params = vec![("q", query.text.clone()), ("count", query.count.to_string())];
+        if let Some(freshness) = query.freshness {
+            let code = match freshness {
+                Freshness::Day => "pd",
+                Freshness::Week => "pw",
+                Freshness::Month => "pm",
+            };
+            params.push(("freshness", code.to_string()));
+        }
+        if let Some(country) = &query.country {
+            params.push(("country", country.clone()));
+        }
+        if let Some(language) = &query.language {
+            params.push(("search_lang", language.clone()));
+        }
+        client
+            .get(format!("https://api.search.brave.com/res/v1/{}/search", endpoint))
+            .query(&params)
+            .header("Accept", "application/json")
+            .header("X-Subscription-Token", &self.api_key)
+    }
+}
+
+#[async_trait]
+impl SearchBackend for BraveSearch {
+    async fn search(
+        &self,
+        client: &Client,
+        query: &SearchQuery,
+    ) -> Result<Vec<SearchResult>, String> {
+        if self.api_key.is_empty() {
+            return Err("Error: BRAVE_API_KEY not configured (the duckduckgo backend, \
+                        tools.web.search.backend, needs no key)"
+                .to_string());
+        }
+        let data = _search_json(self._request(client, query), "Brave Search").await?;
+        Ok(match query.vertical {
+            Vertical::Web => parse_results(&data["web"]["results"], "description"),
+            Vertical::News => _dated(&data["results"], "description", "age"),
+            Vertical::Images => _images(&data["results"], "/properties/url", "/url"),
+        })
+    }
+}
+
+/// DuckDuckGo's HTML results page (no key needed).
+struct DuckDuckGoSearch;
+
+impl DuckDuckGoSearch {
+    fn _request(&self, client: &Client, query: &SearchQuery) -> reqwest::RequestBuilder {
+        let mut params = vec![("q", query.text.clone())];
+        if let Some(freshness) = query.freshness {
+            let code = match freshness {
+                Freshness::Day => "d",
+                Freshness::Week => "w",
+                Freshness::Month => "m",
+            };
+            params.push(("df", code.to_string()));
+        }
+        // Regions are country-language, e.g. `us-en`.
+        if query.country.is_some() || query.language.is_some() {
+            let country = query.country.as_deref().unwrap_or("wt");
+            let language = query.language.as_deref().unwrap_or("wt");
+            params.push(("kl", format!("{}-{}", country, language)));
+        }
+        client
+            .get("https://html.duckduckgo.com/html/")
+            .query(&params)
+            .header("User-Agent", USER_AGENT)
+    }
+}
+
+#[async_trait]
+impl SearchBackend for DuckDuckGoSearch {
+    async fn search(
+        &self,
+        client: &Client,
+        query: &SearchQuery,
+    ) -> Result<Vec<SearchResult>, String> {
+        if query.vertical != Vertical::Web {
+            return Err(_unsupported("duckduckgo", query.vertical));
+        }
+        let response = self
+            ._request(client, query)
+            .timeout(std::time::Duration::from_secs(10))
+            .send()
+            .await
+            .map_err(|e| format!("Error: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Error: DuckDuckGo returned HTTP {}", response.status()));
+        }
+        let html = response.text().await.map_err(|e| format!("Error: {}", e))?;
+        Ok(parse_duckduckgo(&html))
+    }
+}
+
+/// Search hits from DuckDuckGo's HTML results, without ads.
+fn parse_duckduckgo(html: &str) -> Vec<SearchResult> {
+    static RESULT: Lazy<Selector> =
+        Lazy::new(|| Selector::parse(".result:not(.result--ad)").unwrap());
+    static LINK: Lazy<Selector> = Lazy::new(|| Selector::parse("a.result__a").unwrap());
+    static SNIPPET: Lazy<Selector> = Lazy::new(|| Selector::parse(".result__snippet").unwrap());
+
+    let document = Html::parse_document(html);
+    let text = |el: scraper::ElementRef| normalize_whitespace(&el.text().collect::<String>());
+    document
+        .select(&RESULT)
+        .filter_map(|result| {
+            let link = result.select(&LINK).next()?;
+            let href = link.attr("href")?;
+            // Links go through a redirect holding the target in `uddg`.
+            let url = Url::parse(&format!("https:{}", href.trim_start_matches("https:")))
+                .ok()
+                .and_then(|u| {
+                    u.query_pairs()
+                        .find(|(k, _)| k == "uddg")
+                        .map(|(_, v)| v.to_string())
+                })
+                .unwrap_or_else(|| href.to_string());
+            let snippet = result.select(&SNIPPET).next().map(text).unwrap_or_default();
+            Some((text(link), url, snippet))
+        })
+        .collect()
+}
+
+/// A SearXNG instance with its JSON API enabled, at this base URL.
+struct SearxngSearch {
+    base: String,
+}
+
+impl SearxngSearch {
+    fn _request(&self, client: &Client, query: &SearchQuery) -> reqwest::RequestBuilder {
+        let category = match query.vertical {
+            Vertical::Web => "general",
+            Vertical::News => "news",
+            Vertical::Images => "images",
+        };
+        let mut params = vec![
+            ("q", query.text.clone()),
+            ("format", "json".to_string()),
+            ("categories", category.to_string()),
+        ];
+        if let Some(freshness) = query.freshness {
+            params.push(("time_range", freshness.name().to_string()));
+        }
+        // SearXNG takes locales such as `de` or `de-CH`.
+        match (&query.language, &query.country) {
+            (Some(language), Some(country)) => {
+                params.push(("language", format!("{}-{}", language, country.to_uppercase())))
+            }
+            (Some(language), None) => params.push(("language", language.clone())),
+            _ => {}
+        }
+        client
+            .get(format!("{}/search", self.base))
+            .query(&params)
+            .header("Accept", "application/json")
+    }
+}
+
+#[async_trait]
+impl SearchBackend for SearxngSearch {
+    async fn search(
+        &self,
+        client: &Client,
+        query: &SearchQuery,
+    ) -> Result<Vec<SearchResult>, String> {
+        if self.base.is_empty() {
+            return Err("Error: SearXNG URL not configured (tools.web.search.url)".to_string());
+        }
+        let data = _search_json(self._request(client, query), "SearXNG").await?;
+        Ok(match query.vertical {
+            Vertical::Web => parse_results(&data["results"], "content"),
+            Vertical::News => _dated(&data["results"], "content", "publishedDate"),
+            Vertical::Images => _images(&data["results"], "/img_src", "/url"),
+        })
+    }
+}
+
+/// Google Programmable Search (Custom Search JSON API): an API key and a
+/// search engine ID.
+struct GoogleSearch {
+    api_key: String,
+    engine_id: String,
+}
+
+impl GoogleSearch {
+    fn _request(&self, client: &Client, query: &SearchQuery) -> reqwest::RequestBuilder {
+        let mut params = vec![
+            ("key", self.api_key.clone()),
+            ("cx", self.engine_id.clone()),
+            ("q", query.text.clone()),
+            ("num", query.count.to_string()),
+        ];
+        if query.vertical == Vertical::Images {
+            params.push(("searchType", "image".to_string()));
+        }
+        if let Some(freshness) = query.freshness {
+            let code = match freshness {
+                Freshness::Day => "d1",
+                Freshness::Week => "w1",
+                Freshness::Month => "m1",
+            };
+            params.push(("dateRestrict", code.to_string()));
+        }
+        if let Some(country) = &query.country {
+            params.push(("gl", country.clone()));
+        }
+        if let Some(language) = &query.language {
+            params.push(("lr", format!("lang_{}", language)));
+        }
+        client
+            .get("https://www.googleapis.com/customsearch/v1")
+            .query(&params)
+    }
+}
+
+#[async_trait]
+impl SearchBackend for GoogleSearch {
+    async fn search(
+        &self,
+        client: &Client,
+        query: &SearchQuery,
+    ) -> Result<Vec<SearchResult>, String> {
+        if self.api_key.is_empty() || self.engine_id.is_empty() {
+            return Err(
+                "Error: Google search needs an API key and tools.web.search.engineId".to_string(),
+            );
+        }
+        if query.vertical == Vertical::News {
+            return Err(_unsupported("google", query.vertical));
+        }
+        let data = _search_json(self._request(client, query), "Google").await?;
+        Ok(match query.vertical {
+            Vertical::Images => _images(&data["items"], "/link", "/image/contextLink"),
+            _ => parse_results_with(&data["items"], "link", "snippet"),
+        })
+    }
+}
+
+/// Tavily search API (needs an API key).
+struct TavilySearch {
+    api_key: String,
+}
+
+impl TavilySearch {
+    fn _request(&self, client: &Client, query: &SearchQuery) -> reqwest::RequestBuilder {
+        let mut body = serde_json::json!({
+            "query": query.text,
+            "max_results": query.count,
+            "topic": if query.vertical == Vertical::News { "news" } else { "general" },
+        });
+        if query.vertical == Vertical::Images {
+            body["include_images"] = serde_json::json!(true);
+            body["include_image_descriptions"] = serde_json::json!(true);
+        }
+        if let Some(freshness) = query.freshness {
+            body["time_range"] = serde_json::json!(freshness.name());
+        }
+        client
+            .post("https://api.tavily.com/search")
+            .bearer_auth(&self.api_key)
+            .json(&body)
+    }
+}
+
+#[async_trait]
+impl SearchBackend for TavilySearch {
+    async fn search(
+        &self,
+        client: &Client,
+        query: &SearchQuery,
+    ) -> Result<Vec<SearchResult>, String> {
+        if self.api_key.is_empty() {
+            return Err("Error: TAVILY_API_KEY not configured".to_string());
+        }
+        let data = _search_json(self._request(client, query), "Tavily").await?;
+        Ok(match query.vertical {
+            Vertical::Web => parse_results(&data["results"], "content"),
+            Vertical::News => _dated(&data["results"], "content", "published_date"),
+            // Images are plain URLs, or objects with a description.
+            Vertical::Images => data["images"]
+                .as_array()
+                .map(|images| {
+                    images
+                        .iter()
+                        .filter_map(|image| {
+                            let url = image.as_str().or_else(|| image["url"].as_str())?;
+                            let description = image["description"].as_str().unwrap_or("");
+                            let title = normalize_whitespace(description);
+                            Some((title, url.to_string(), String::new()))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// Search the web through one of the [`SearchBackend`]s.
+pub struct WebSearchTool {
     max_results: u32,
+    backend: Box<dyn SearchBackend>,
     client: Client,
 }
 
 impl WebSearchTool {
-    /// Create a new web search tool.
+    /// Create a new web search tool using Brave Search.
     ///
     /// If `api_key` is empty/None, the `BRAVE_API_KEY` environment variable is
     /// checked.
     pub fn new(api_key: Option<String>, max_results: u32) -> Self {
-        let resolved_key = api_key
-            .filter(|k| !k.is_empty())
-            .or_else(|| std::env::var("BRAVE_API_KEY").ok())
-            .unwrap_or_default();
-
         Self {
-            api_key: resolved_key,
             max_results,
-            client: Client::new(),
+            backend: search_backend("brave", api_key, "", ""),
+            client: shared_client(),
+        }
+    }
+
+    /// Send requests with `client` instead of the shared one.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Search with `backend` instead (see [`search_backend`]).
+    pub fn with_backend(mut self, backend: Box<dyn SearchBackend>) -> Self {
+        self.backend = backend;
+        self
+    }
+}
+
+/// Search hits from a JSON array of results with `title`, `url` and a
+/// snippet under `snippet_key`.
+fn parse_results(items: &serde_json::Value, snippet_key: &str) -> Vec<SearchResult> {
+    parse_results_with(items, "url", snippet_key)
+}
+
+/// Search hits from a JSON array of results with `title`, the URL under
+/// `url_key` and a snippet under `snippet_key`.
+fn parse_results_with(
+    items: &serde_json::Value,
+    url_key: &str,
+    snippet_key: &str,
+) -> Vec<SearchResult> {
+    // Snippets may highlight the query with tags (Brave uses <strong>).
+    let field = |item: &serde_json::Value, key: &str| {
+        normalize_whitespace(&strip_tags(item.get(key).and_then(|v| v.as_str()).unwrap_or("")))
+    };
+    items
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .map(|item| (field(item, "title"), field(item, url_key), field(item, snippet_key)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Like [`parse_results`], with the date under `date_key` of each item
+/// before its snippet.
+fn _dated(items: &serde_json::Value, snippet_key: &str, date_key: &str) -> Vec<SearchResult> {
+    let mut results = parse_results(items, snippet_key);
+    let items = items.as_array().map(Vec::as_slice).unwrap_or_default();
+    for ((_, _, snippet), item) in results.iter_mut().zip(items) {
+        if let Some(date) = item[date_key].as_str().filter(|d| !d.is_empty()) {
+            *snippet = if snippet.is_empty() {
+                date.to_string()
+            } else {
+                format!("{} - {}", date, snippet)
+            };
+        }
+    }
+    results
+}
+
+/// Image hits from a JSON array of results: the title, the image URL at
+/// the JSON pointer `image`, and the page showing it at `page`.
+fn _images(items: &serde_json::Value, image: &str, page: &str) -> Vec<SearchResult> {
+    items
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| {
+                    let url = item.pointer(image)?.as_str()?;
+                    let title = normalize_whitespace(item["title"].as_str().unwrap_or(""));
+                    let page = item
+                        .pointer(page)
+                        .and_then(|p| p.as_str())
+                        .map(|p| format!("On {}", p))
+                        .unwrap_or_default();
+                    Some((title, url.to_string(), page))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Format up to `count` search hits for the model.
+fn format_results(query: &str, results: &[SearchResult], count: usize) -> String {
+    if results.is_empty() {
+        return format!("No results for: {}", query);
+    }
+    let mut lines = vec![format!("Results for: {}\n", query)];
+    for (i, (title, url, snippet)) in results.iter().take(count).enumerate() {
+        lines.push(format!("{}. {}\n   {}", i + 1, title, url));
+        if !snippet.is_empty() {
+            lines.push(format!("   {}", snippet));
         }
     }
+    lines.join("\n")
+}
+
+/// A two-letter country or language code, lowercase.
+fn _code(params: &HashMap<String, serde_json::Value>, key: &str) -> Result<Option<String>, String> {
+    let Some(value) = params.get(key).and_then(|v| v.as_str()).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    if value.len() != 2 || !value.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(format!("Error: '{}' must be a two-letter code, got '{}'", key, value));
+    }
+    Ok(Some(value.to_lowercase()))
 }
 
 #[async_trait]
@@ -96,7 +674,7 @@ impl Tool for WebSearchTool {
     }
 
     fn description(&self) -> &str {
-        "Search the web. Returns titles, URLs, and snippets."
+        "Search the web, news or images. Returns titles, URLs, and snippets."
     }
 
     fn parameters(&self) -> serde_json::Value {
@@ -112,6 +690,24 @@ impl Tool for WebSearchTool {
                     "description": "Results (1-10)",
                     "minimum": 1,
                     "maximum": 10
+                },
+                "vertical": {
+                    "type": "string",
+                    "enum": ["web", "news", "images"],
+                    "default": "web"
+                },
+                "freshness": {
+                    "type": "string",
+                    "enum": ["day", "week", "month"],
+                    "description": "Only results from the past day, week or month"
+                },
+                "country": {
+                    "type": "string",
+                    "description": "Two-letter country code to favour results from, e.g. us"
+                },
+                "language": {
+                    "type": "string",
+                    "description": "Two-letter language code of results, e.g. en"
                 }
             },
             "required": ["query"]
@@ -124,68 +720,42 @@ impl Tool for WebSearchTool {
             None => return "Error: 'query' parameter is required".to_string(),
         };
 
-        if self.api_key.is_empty() {
-            return "Error: BRAVE_API_KEY not configured".to_string();
-        }
-
         let count = params
             .get("count")
             .and_then(|v| v.as_u64())
-            .map(|n| n.min(10).max(1) as u32)
+            .map(|n| n.clamp(1, 10) as u32)
             .unwrap_or(self.max_results);
 
-        match self
-            .client
-            .get("https://api.search.brave.com/res/v1/web/search")
-            .query(&[("q", query), ("count", &count.to_string())])
-            .header("Accept", "application/json")
-            .header("X-Subscription-Token", &self.api_key)
-            .timeout(std::time::Duration::from_secs(10))
-            .send()
-            .await
-        {
-            Ok(response) => {
-                if !response.status().is_success() {
-                    let status = response.status();
-                    let body = response.text().await.unwrap_or_default();
-                    return format!("Error: Brave Search returned HTTP {}: {}", status, body);
-                }
+        let vertical = match params.get("vertical").and_then(|v| v.as_str()) {
+            None => Vertical::Web,
+            Some(name) => match Vertical::parse(name) {
+                Some(vertical) => vertical,
+                None => return format!("Error: unknown vertical '{}' (web, news or images)", name),
+            },
+        };
+        let freshness = match params.get("freshness").and_then(|v| v.as_str()) {
+            None => None,
+            Some(name) => match Freshness::parse(name) {
+                Some(freshness) => Some(freshness),
+                None => return format!("Error: unknown freshness '{}' (day, week or month)", name),
+            },
+        };
+        let (country, language) = match (_code(&params, "country"), _code(&params, "language")) {
+            (Ok(country), Ok(language)) => (country, language),
+            (Err(e), _) | (_, Err(e)) => return e,
+        };
 
-                match response.json::<serde_json::Value>().await {
-                    Ok(data) => {
-                        let results = data
-                            .get("web")
-                            .and_then(|w| w.get("results"))
-                            .and_then(|r| r.as_array())
-                            .cloned()
-                            .unwrap_or_default();
-
-                        if results.is_empty() {
-                            return format!("No results for: {}", query);
-                        }
-
-                        let mut lines = vec![format!("Results for: {}\n", query)];
-                        for (i, item) in results.iter().take(count as usize).enumerate() {
-                            let title = item
-                                .get("title")
-                                .and_then(|v| v.as_str())
-                                .unwrap_or("");
-                            let url = item
-                                .get("url")
-                                .and_then(|v| v.as_str())
-                                .unwrap_or("");
-                            lines.push(format!("{}. {}\n   {}", i + 1, title, url));
-
-                            if let Some(desc) = item.get("description").and_then(|v| v.as_str()) {
-                                lines.push(format!("   {}", desc));
-                            }
-                        }
-                        lines.join("\n")
-                    }
-                    Err(e) => format!("Error parsing search results: {}", e),
-                }
-            }
-            Err(e) => format!("Error: {}", e),
+        let search = SearchQuery {
+            text: query.to_string(),
+            count,
+            vertical,
+            freshness,
+            country,
+            language,
+        };
+        match self.backend.search(&self.client, &search).await {
+            Ok(results) => format_results(query, &results, count as usize),
+            Err(e) => e,
         }
     }
 }
@@ -195,22 +765,281 @@ impl Tool for WebSearchTool {
 // ---------------------------------------------------------------------------
 
 /// Fetch and extract content from a URL.
+///
+/// Every URL, including each redirect, is checked by a [`UrlGuard`] first,
+/// and the request is made to the addresses it checked.
 pub struct WebFetchTool {
     max_chars: usize,
-    client: Client,
+    timeout: std::time::Duration,
+    guard: UrlGuard,
+    politeness: Arc<Politeness>,
+    /// Recently fetched pages, by extract mode and URL, for reading on.
+    pages: std::sync::Mutex<VecDeque<(String, FetchedPage)>>,
+    /// Where non-text responses are saved, if anywhere, and their size limit.
+    downloads: Option<(PathBuf, u64)>,
+}
+
+/// What fetching a URL produced.
+enum Fetched {
+    Page(FetchedPage),
+    File(SavedFile),
+}
+
+/// A non-text response saved to the downloads directory.
+struct SavedFile {
+    final_url: String,
+    status: u16,
+    content_type: String,
+    path: PathBuf,
+    bytes: u64,
+}
+
+/// The extracted text of a fetched page.
+#[derive(Clone)]
+pub(crate) struct FetchedPage {
+    pub(crate) final_url: String,
+    status: u16,
+    extractor: &'static str,
+    pub(crate) text: String,
+    /// Absolute URLs the page links to, without fragments.
+    pub(crate) links: Vec<Url>,
+    fetched_at: Instant,
 }
 
 impl WebFetchTool {
     /// Create a new web fetch tool.
     pub fn new(max_chars: usize) -> Self {
-        let client = Client::builder()
-            .redirect(reqwest::redirect::Policy::limited(MAX_REDIRECTS))
-            .user_agent(USER_AGENT)
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .unwrap_or_else(|_| Client::new());
+        Self::with_timeout(max_chars, 30)
+    }
+
+    /// Create a web fetch tool whose requests give up after `timeout_secs`.
+    pub fn with_timeout(max_chars: usize, timeout_secs: u64) -> Self {
+        Self {
+            max_chars,
+            timeout: std::time::Duration::from_secs(timeout_secs),
+            guard: UrlGuard::default(),
+            politeness: Arc::new(Politeness::default()),
+            pages: std::sync::Mutex::new(VecDeque::new()),
+            downloads: None,
+        }
+    }
+
+    /// Allow fetching these private hosts, addresses or networks (see
+    /// [`UrlGuard::new`]).
+    pub fn with_allowed_hosts(mut self, allowed: &[String]) -> Self {
+        self.guard = UrlGuard::new(allowed);
+        self
+    }
+
+    /// Throttle requests and check robots.txt with `politeness`, which may
+    /// be shared with other fetch tools.
+    pub fn with_politeness(mut self, politeness: Arc<Politeness>) -> Self {
+        self.politeness = politeness;
+        self
+    }
+
+    /// Save non-text responses under `dir` instead of decoding them, up to
+    /// `max_bytes` each; without this they are refused.
+    pub fn with_downloads(mut self, dir: PathBuf, max_bytes: u64) -> Self {
+        self.downloads = (max_bytes > 0).then_some((dir, max_bytes));
+        self
+    }
+
+    /// Fetch `url` and extract its text, or save it if it is not text.
+    async fn _fetch(&self, url: &str, extract_mode: &str) -> Result<Fetched, String> {
+        let mut response = self._get(url).await?;
+        let status = response.status().as_u16();
+        let final_url = response.url().to_string();
+        let header = |name: reqwest::header::HeaderName| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("")
+                .to_string()
+        };
+        let content_type = header(reqwest::header::CONTENT_TYPE);
+        let disposition = header(reqwest::header::CONTENT_DISPOSITION);
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_lowercase();
+        let read_error = |e: reqwest::Error| format!("Failed to read response body: {}", e);
+
+        let body = if _is_text_type(&mime) {
+            response.text().await.map_err(read_error)?
+        } else {
+            // Without a type, the first bytes tell.
+            let first = response.chunk().await.map_err(read_error)?.unwrap_or_default();
+            if !mime.is_empty() || _looks_binary(&first) {
+                let name = _download_name(&disposition, &final_url, &mime);
+                let (path, bytes) = self._save(response, &first, &name).await?;
+                return Ok(Fetched::File(SavedFile {
+                    final_url,
+                    status,
+                    content_type: mime,
+                    path,
+                    bytes,
+                }));
+            }
+            let mut body = first.to_vec();
+            while let Some(chunk) = response.chunk().await.map_err(read_error)? {
+                body.extend_from_slice(&chunk);
+            }
+            String::from_utf8_lossy(&body).into_owned()
+        };
+
+        let mut links = Vec::new();
+        let (text, extractor) = if content_type.contains("application/json") {
+            // Pretty-print JSON.
+            let formatted = match serde_json::from_str::<serde_json::Value>(&body) {
+                Ok(v) => serde_json::to_string_pretty(&v).unwrap_or_else(|_| body.clone()),
+                Err(_) => body.clone(),
+            };
+            (formatted, "json")
+        } else if content_type.contains("text/html")
+            || body.trim_start().to_lowercase().starts_with("<!doctype")
+            || body.trim_start().to_lowercase().starts_with("<html")
+        {
+            let base = Url::parse(&final_url).ok();
+            if let Some(base) = &base {
+                links = _links(&body, base);
+            }
+            let markdown = extract_mode != "text";
+            (readability::extract(&body, markdown, base.as_ref()), "readability")
+        } else {
+            (body, "raw")
+        };
+        Ok(Fetched::Page(FetchedPage {
+            final_url,
+            status,
+            extractor,
+            text,
+            links,
+            fetched_at: Instant::now(),
+        }))
+    }
+
+    /// Fetch `url` as markdown, refusing responses that are not text.
+    pub(crate) async fn fetch_page(&self, url: &str) -> Result<FetchedPage, String> {
+        validate_url(url)?;
+        match self._fetch(url, "markdown").await? {
+            Fetched::Page(page) => Ok(page),
+            Fetched::File(file) => Err(format!("Not text ({})", file.content_type)),
+        }
+    }
+
+    /// Write the body of `response`, starting with `first`, to a new file
+    /// called `name` (or a numbered variant) in the downloads directory.
+    async fn _save(
+        &self,
+        mut response: reqwest::Response,
+        first: &[u8],
+        name: &str,
+    ) -> Result<(PathBuf, u64), String> {
+        let Some((dir, max_bytes)) = &self.downloads else {
+            return Err("The response is not text, and saving files is off \
+                        (tools.web.fetch.maxDownloadMb)"
+                .to_string());
+        };
+        let too_large = || {
+            format!(
+                "The file is larger than the download limit of {} MB \
+                 (tools.web.fetch.maxDownloadMb)",
+                max_bytes.div_ceil(1024 * 1024)
+            )
+        };
+        if response.content_length().is_some_and(|len| len > *max_bytes) {
+            return Err(too_large());
+        }
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+        let path = _unused_path(dir, name);
+        let mut file = tokio::fs::File::create(&path)
+            .await
+            .map_err(|e| format!("Cannot create {}: {}", path.display(), e))?;
 
-        Self { max_chars, client }
+        let write_error = |e: std::io::Error| format!("Cannot write {}: {}", path.display(), e);
+        let result = async {
+            let mut written = first.len() as u64;
+            if written > *max_bytes {
+                return Err(too_large());
+            }
+            file.write_all(first).await.map_err(write_error)?;
+            while let Some(bytes) = response
+                .chunk()
+                .await
+                .map_err(|e| format!("Failed to read response body: {}", e))?
+            {
+                written += bytes.len() as u64;
+                if written > *max_bytes {
+                    return Err(too_large());
+                }
+                file.write_all(&bytes).await.map_err(write_error)?;
+            }
+            file.flush().await.map_err(write_error)?;
+            Ok(written)
+        }
+        .await;
+        match result {
+            Ok(written) => Ok((path, written)),
+            Err(e) => {
+                drop(file);
+                let _ = std::fs::remove_file(&path);
+                Err(e)
+            }
+        }
+    }
+
+    /// The page cached under `key`, if still fresh.
+    fn _cached(&self, key: &str) -> Option<FetchedPage> {
+        let pages = self.pages.lock().unwrap_or_else(|e| e.into_inner());
+        pages
+            .iter()
+            .find(|(k, page)| k == key && page.fetched_at.elapsed() < PAGE_CACHE_TTL)
+            .map(|(_, page)| page.clone())
+    }
+
+    fn _cache(&self, key: String, page: FetchedPage) {
+        let mut pages = self.pages.lock().unwrap_or_else(|e| e.into_inner());
+        pages.retain(|(k, _)| *k != key);
+        pages.push_front((key, page));
+        pages.truncate(PAGE_CACHE_SIZE);
+    }
+
+    /// GET `url`, following redirects after checking each of them.
+    async fn _get(&self, url: &str) -> Result<reqwest::Response, String> {
+        let mut url = Url::parse(url).map_err(|e| format!("Invalid URL: {}", e))?;
+        for _ in 0..=MAX_REDIRECTS {
+            let addrs = self.guard.check(&url).await?;
+            // Not the shared client: this one only connects to the addresses
+            // just checked, so DNS cannot be changed to point elsewhere.
+            let client = Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .user_agent(USER_AGENT)
+                .timeout(self.timeout)
+                .resolve_to_addrs(url.host_str().unwrap_or_default(), &addrs)
+                .build()
+                .map_err(|e| e.to_string())?;
+            self.politeness.admit(&client, &url).await?;
+            let response = client.get(url.clone()).send().await.map_err(|e| e.to_string())?;
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|v| v.to_str().ok());
+            match location {
+                Some(location) if response.status().is_redirection() => {
+                    url = url
+                        .join(location)
+                        .map_err(|e| format!("Invalid redirect: {}", e))?;
+                    validate_url(url.as_str())?;
+                }
+                _ => return Ok(response),
+            }
+        }
+        Err(format!("Too many redirects (more than {})", MAX_REDIRECTS))
     }
 }
 
@@ -221,7 +1050,9 @@ impl Tool for WebFetchTool {
     }
 
     fn description(&self) -> &str {
-        "Fetch URL and extract readable content (HTML -> text)."
+        "Fetch URL and extract readable content (HTML -> text). Long pages come in chunks; \
+         pass nextCursor back as cursor to read on. Files that are not text (PDF, zip, images) \
+         are saved to the workspace and their path returned."
     }
 
     fn parameters(&self) -> serde_json::Value {
@@ -240,6 +1071,15 @@ impl Tool for WebFetchTool {
                 "maxChars": {
                     "type": "integer",
                     "minimum": 100
+                },
+                "cursor": {
+                    "type": "integer",
+                    "description": "Continue a long page: the nextCursor of the previous result"
+                },
+                "page": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": "Chunk of a long page to return (1 = first)"
                 }
             },
             "required": ["url"]
@@ -260,8 +1100,10 @@ impl Tool for WebFetchTool {
         let max_chars = params
             .get("maxChars")
             .and_then(|v| v.as_u64())
-            .map(|n| n as usize)
+            .map(|n| (n as usize).max(100))
             .unwrap_or(self.max_chars);
+        let cursor = params.get("cursor").and_then(|v| v.as_u64()).map(|n| n as usize);
+        let page = params.get("page").and_then(|v| v.as_u64()).map(|n| n.max(1) as usize);
 
         // Validate URL.
         if let Err(e) = validate_url(url) {
@@ -272,160 +1114,189 @@ impl Tool for WebFetchTool {
             .to_string();
         }
 
-        match self.client.get(url).send().await {
-            Ok(response) => {
-                let status = response.status().as_u16();
-                let final_url = response.url().to_string();
-                let content_type = response
-                    .headers()
-                    .get("content-type")
-                    .and_then(|v| v.to_str().ok())
-                    .unwrap_or("")
-                    .to_string();
-
-                match response.text().await {
-                    Ok(body) => {
-                        let (text, extractor) = if content_type.contains("application/json") {
-                            // Pretty-print JSON.
-                            let formatted = match serde_json::from_str::<serde_json::Value>(&body)
-                            {
-                                Ok(v) => {
-                                    serde_json::to_string_pretty(&v).unwrap_or_else(|_| body.clone())
-                                }
-                                Err(_) => body.clone(),
-                            };
-                            (formatted, "json")
-                        } else if content_type.contains("text/html")
-                            || body.trim_start().to_lowercase().starts_with("<!doctype")
-                            || body.trim_start().to_lowercase().starts_with("<html")
-                        {
-                            // Extract text from HTML using scraper.
-                            let extracted = extract_html_content(&body, extract_mode);
-                            (extracted, "scraper")
-                        } else {
-                            (body, "raw")
-                        };
-
-                        let truncated = text.len() > max_chars;
-                        let final_text = if truncated {
-                            text[..max_chars].to_string()
-                        } else {
-                            text.clone()
-                        };
-
-                        serde_json::json!({
-                            "url": url,
-                            "finalUrl": final_url,
-                            "status": status,
-                            "extractor": extractor,
-                            "truncated": truncated,
-                            "length": final_text.len(),
-                            "text": final_text
-                        })
-                        .to_string()
-                    }
-                    Err(e) => serde_json::json!({
-                        "error": format!("Failed to read response body: {}", e),
-                        "url": url
+        // Later chunks come from the copy fetched for the first one.
+        let key = format!("{} {}", extract_mode, url);
+        let cached = match (cursor, page) {
+            (None, None | Some(1)) => None,
+            _ => self._cached(&key),
+        };
+        let fetched = match cached {
+            Some(fetched) => fetched,
+            None => match self._fetch(url, extract_mode).await {
+                Ok(Fetched::Page(fetched)) => {
+                    self._cache(key, fetched.clone());
+                    fetched
+                }
+                Ok(Fetched::File(file)) => {
+                    return serde_json::json!({
+                        "url": url,
+                        "finalUrl": file.final_url,
+                        "status": file.status,
+                        "contentType": file.content_type,
+                        "savedTo": file.path.to_string_lossy(),
+                        "bytes": file.bytes,
+                        "text": format!(
+                            "Not text; saved {} bytes of {} to {}",
+                            file.bytes,
+                            if file.content_type.is_empty() { "data" } else { &file.content_type },
+                            file.path.display()
+                        )
                     })
-                    .to_string(),
+                    .to_string()
                 }
-            }
-            Err(e) => serde_json::json!({
-                "error": e.to_string(),
+                Err(e) => return serde_json::json!({"error": e, "url": url}).to_string(),
+            },
+        };
+
+        let chunks = chunk_bounds(&fetched.text, max_chars);
+        let index = match (cursor, page) {
+            (Some(cursor), _) => chunks.iter().position(|&(start, _)| start >= cursor),
+            (None, Some(page)) => Some(page - 1).filter(|&i| i < chunks.len()),
+            (None, None) => Some(0),
+        };
+        let Some(index) = index.filter(|&i| i < chunks.len()) else {
+            return serde_json::json!({
+                "error": format!("No such chunk; the page has {} of them", chunks.len()),
                 "url": url
             })
-            .to_string(),
+            .to_string();
+        };
+        let (start, end) = chunks[index];
+        let text = &fetched.text[start..end];
+        let mut result = serde_json::json!({
+            "url": url,
+            "finalUrl": fetched.final_url,
+            "status": fetched.status,
+            "extractor": fetched.extractor,
+            "truncated": end < fetched.text.len(),
+            "length": text.len(),
+            "page": index + 1,
+            "pages": chunks.len(),
+            "text": text
+        });
+        if end < fetched.text.len() {
+            result["nextCursor"] = serde_json::json!(end);
         }
+        result.to_string()
     }
 }
 
-/// Extract readable content from HTML using the `scraper` crate.
-///
-/// This is a simplified readability extraction: we look for the `<body>` or
-/// `<main>` or `<article>` element and extract text, falling back to the whole
-/// document if those are not found.
-fn extract_html_content(html: &str, mode: &str) -> String {
-    use scraper::{Html, Selector};
-
+/// The http(s) links of an HTML page at `base`, resolved and without
+/// fragments, each once.
+fn _links(html: &str, base: &Url) -> Vec<Url> {
+    static LINK: Lazy<Selector> = Lazy::new(|| Selector::parse("a[href]").unwrap());
     let document = Html::parse_document(html);
-
-    // Try to extract title.
-    let title = Selector::parse("title")
-        .ok()
-        .and_then(|sel| document.select(&sel).next())
-        .map(|el| el.text().collect::<String>())
-        .unwrap_or_default();
-
-    // Try progressively narrower selectors.
-    let selectors = ["article", "main", "[role=\"main\"]", "body"];
-    let mut body_text = String::new();
-
-    for sel_str in &selectors {
-        if let Ok(sel) = Selector::parse(sel_str) {
-            if let Some(el) = document.select(&sel).next() {
-                body_text = if mode == "markdown" {
-                    html_to_markdown_simple(&el.html())
-                } else {
-                    el.text().collect::<Vec<_>>().join(" ")
-                };
-                if !body_text.trim().is_empty() {
-                    break;
-                }
-            }
+    let mut seen = std::collections::HashSet::new();
+    let mut links: Vec<Url> = Vec::new();
+    for href in document.select(&LINK).filter_map(|a| a.attr("href")) {
+        let Ok(mut link) = base.join(href.trim()) else {
+            continue;
+        };
+        link.set_fragment(None);
+        if matches!(link.scheme(), "http" | "https") && seen.insert(link.to_string()) {
+            links.push(link);
         }
     }
+    links
+}
 
-    if body_text.trim().is_empty() {
-        // Fallback: extract all text from the document.
-        body_text = document.root_element().text().collect::<Vec<_>>().join(" ");
-    }
+/// Whether responses of type `mime` are text to extract.
+fn _is_text_type(mime: &str) -> bool {
+    mime.starts_with("text/")
+        || mime.ends_with("json")
+        || mime.ends_with("xml")
+        || mime.ends_with("javascript")
+        || mime.ends_with("yaml")
+        || mime.ends_with("toml")
+}
 
-    let result = normalize_whitespace(&body_text);
+/// Whether a body starting with `bytes` is binary data rather than text.
+fn _looks_binary(bytes: &[u8]) -> bool {
+    let head = &bytes[..bytes.len().min(1024)];
+    head.contains(&0) || std::str::from_utf8(head).is_err_and(|e| e.error_len().is_some())
+}
 
-    if title.is_empty() {
-        result
-    } else {
-        format!("# {}\n\n{}", title.trim(), result)
+/// A file name for a download: the `Content-Disposition` one, else the
+/// last segment of `url`, made safe, with an extension for `mime` if it
+/// has none.
+fn _download_name(disposition: &str, url: &str, mime: &str) -> String {
+    static RE_FILENAME: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r#"(?i)filename\*?=(?:[\w-]+'[\w-]*')?"?([^";]+)"?"#).unwrap());
+    let from_header = RE_FILENAME
+        .captures(disposition)
+        .map(|c| c[1].to_string());
+    let from_url = || {
+        Url::parse(url)
+            .ok()
+            .and_then(|u| u.path_segments()?.next_back().map(str::to_string))
+    };
+    let raw = from_header.or_else(from_url).unwrap_or_default();
+    let raw = raw.rsplit(['/', '\\']).next().unwrap_or("");
+    let mut name: String = raw
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "._-".contains(c) { c } else { '_' })
+        .collect::<String>()
+        .trim_start_matches('.')
+        .to_string();
+    if name.is_empty() {
+        name = "download".to_string();
     }
+    if !name.contains('.') {
+        if let Some(ext) = mime_guess::get_mime_extensions_str(mime).and_then(|e| e.first()) {
+            name = format!("{}.{}", name, ext);
+        }
+    }
+    name
 }
 
-/// Very simple HTML-to-markdown converter.
-fn html_to_markdown_simple(html: &str) -> String {
-    // Convert links.
-    let re_links =
-        Regex::new(r#"(?is)<a\s+[^>]*href=["']([^"']+)["'][^>]*>([\s\S]*?)</a>"#).unwrap();
-    let text = re_links.replace_all(html, |caps: &regex::Captures| {
-        let href = &caps[1];
-        let inner = strip_tags(&caps[2]);
-        format!("[{}]({})", inner, href)
-    });
-
-    // Convert headings (h1-h6). We can't use backreferences (\1) in the regex
-    // crate, so we match any closing </hN> and extract the level from the opening tag.
-    let re_headings = Regex::new(r"(?is)<h([1-6])[^>]*>([\s\S]*?)</h[1-6]>").unwrap();
-    let text = re_headings.replace_all(&text, |caps: &regex::Captures| {
-        let level: usize = caps[1].parse().unwrap_or(1);
-        let inner = strip_tags(&caps[2]);
-        format!("\n{} {}\n", "#".repeat(level), inner)
-    });
-
-    // Convert list items.
-    let re_li = Regex::new(r"(?is)<li[^>]*>([\s\S]*?)</li>").unwrap();
-    let text = re_li.replace_all(&text, |caps: &regex::Captures| {
-        let inner = strip_tags(&caps[1]);
-        format!("\n- {}", inner)
-    });
-
-    // Convert block-end tags to newlines.
-    let re_block = Regex::new(r"(?i)</(p|div|section|article)>").unwrap();
-    let text = re_block.replace_all(&text, "\n\n");
-
-    // Convert br/hr.
-    let re_br = Regex::new(r"(?i)<(br|hr)\s*/?>").unwrap();
-    let text = re_br.replace_all(&text, "\n");
+/// `dir/name`, or `dir/stem-N.ext` if that exists.
+fn _unused_path(dir: &Path, name: &str) -> PathBuf {
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{}", ext)),
+        _ => (name, String::new()),
+    };
+    let mut path = dir.join(name);
+    let mut n = 1;
+    while path.exists() {
+        path = dir.join(format!("{}-{}{}", stem, n, ext));
+        n += 1;
+    }
+    path
+}
 
-    normalize_whitespace(&strip_tags(&text))
+/// Split `text` into chunks of at most `max_chars` bytes, ending at a
+/// paragraph, line or sentence break when one is near the limit. Returns
+/// the byte range of each chunk.
+pub(crate) fn chunk_bounds(text: &str, max_chars: usize) -> Vec<(usize, usize)> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < text.len() || chunks.is_empty() {
+        let mut end = (start + max_chars).min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end < text.len() {
+            // Look for a break in the last fifth of the chunk.
+            let mut from = end - (end - start) / 5;
+            while !text.is_char_boundary(from) {
+                from += 1;
+            }
+            let window = &text[from..end];
+            if let Some(i) = ["\n\n", "\n", ". ", " "]
+                .iter()
+                .find_map(|sep| window.rfind(sep).map(|i| i + sep.len()))
+            {
+                end = from + i;
+            }
+        }
+        if end <= start && start < text.len() {
+            // A single character wider than the limit.
+            end = start + text[start..].chars().next().map_or(1, char::len_utf8);
+        }
+        chunks.push((start, end));
+        start = end;
+    }
+    chunks
 }
 
 #[cfg(test)]
@@ -554,13 +1425,13 @@ mod tests {
     }
 
     // -----------------------------------------------------------------------
-    // html_to_markdown_simple tests
+    // markdown rendering tests
     // -----------------------------------------------------------------------
 
     #[test]
     fn test_html_to_markdown_headings() {
         let html = "<h1>Title</h1><h2>Subtitle</h2>";
-        let result = html_to_markdown_simple(html);
+        let result = readability::extract(html, true, None);
         assert!(result.contains("# Title"), "result: {}", result);
         assert!(result.contains("## Subtitle"), "result: {}", result);
     }
@@ -568,14 +1439,14 @@ mod tests {
     #[test]
     fn test_html_to_markdown_links() {
         let html = r#"<a href="https://example.com">Example</a>"#;
-        let result = html_to_markdown_simple(html);
+        let result = readability::extract(html, true, None);
         assert!(result.contains("[Example](https://example.com)"), "result: {}", result);
     }
 
     #[test]
     fn test_html_to_markdown_list_items() {
         let html = "<ul><li>First</li><li>Second</li></ul>";
-        let result = html_to_markdown_simple(html);
+        let result = readability::extract(html, true, None);
         assert!(result.contains("- First"), "result: {}", result);
         assert!(result.contains("- Second"), "result: {}", result);
     }
@@ -583,7 +1454,7 @@ mod tests {
     #[test]
     fn test_html_to_markdown_paragraphs() {
         let html = "<p>First paragraph</p><p>Second paragraph</p>";
-        let result = html_to_markdown_simple(html);
+        let result = readability::extract(html, true, None);
         assert!(result.contains("First paragraph"), "result: {}", result);
         assert!(result.contains("Second paragraph"), "result: {}", result);
     }
@@ -591,7 +1462,7 @@ mod tests {
     #[test]
     fn test_html_to_markdown_br() {
         let html = "line1<br/>line2";
-        let result = html_to_markdown_simple(html);
+        let result = readability::extract(html, true, None);
         assert!(result.contains("line1"), "result: {}", result);
         assert!(result.contains("line2"), "result: {}", result);
     }
@@ -599,20 +1470,20 @@ mod tests {
     #[test]
     fn test_html_to_markdown_strips_remaining_tags() {
         let html = "<div><span>text</span></div>";
-        let result = html_to_markdown_simple(html);
+        let result = readability::extract(html, true, None);
         assert!(result.contains("text"), "result: {}", result);
         assert!(!result.contains("<span>"), "result: {}", result);
         assert!(!result.contains("<div>"), "result: {}", result);
     }
 
     // -----------------------------------------------------------------------
-    // extract_html_content tests
+    // readability::extract tests
     // -----------------------------------------------------------------------
 
     #[test]
     fn test_extract_html_content_with_title() {
         let html = "<html><head><title>Test Page</title></head><body><p>Content here</p></body></html>";
-        let result = extract_html_content(html, "text");
+        let result = readability::extract(html, false, None);
         assert!(result.contains("# Test Page"), "result: {}", result);
         assert!(result.contains("Content here"), "result: {}", result);
     }
@@ -620,7 +1491,7 @@ mod tests {
     #[test]
     fn test_extract_html_content_markdown_mode() {
         let html = "<html><body><h1>Heading</h1><p>Paragraph</p></body></html>";
-        let result = extract_html_content(html, "markdown");
+        let result = readability::extract(html, true, None);
         assert!(result.contains("# Heading"), "result: {}", result);
         assert!(result.contains("Paragraph"), "result: {}", result);
     }
@@ -628,7 +1499,7 @@ mod tests {
     #[test]
     fn test_extract_html_content_prefers_article() {
         let html = "<html><body><div>Noise</div><article><p>Article content</p></article></body></html>";
-        let result = extract_html_content(html, "text");
+        let result = readability::extract(html, false, None);
         assert!(result.contains("Article content"), "result: {}", result);
     }
 
@@ -676,6 +1547,135 @@ mod tests {
         assert!(result.contains("BRAVE_API_KEY not configured"));
     }
 
+    #[tokio::test]
+    async fn test_web_search_searxng_needs_url() {
+        let tool = WebSearchTool::new(None, 5).with_backend(search_backend("searxng", None, "", ""));
+        let mut params = HashMap::new();
+        params.insert("query".to_string(), serde_json::json!("rust"));
+        let result = tool.execute(params).await;
+        assert!(result.contains("SearXNG URL not configured"), "{}", result);
+    }
+
+    #[test]
+    fn test_search_results_from_searxng_json() {
+        let data = serde_json::json!({"results": [
+            {"title": "Rust", "url": "https://rust-lang.org", "content": "A language"},
+            {"title": "Crates", "url": "https://crates.io"},
+            {"title": "Docs", "url": "https://docs.rs", "content": "Docs"}
+        ]});
+        let results = parse_results(&data["results"], "content");
+        assert_eq!(
+            format_results("rust", &results, 2),
+            "Results for: rust\n\n1. Rust\n   https://rust-lang.org\n   A language\n2. Crates\n   https://crates.io"
+        );
+        assert_eq!(format_results("rust", &[], 5), "No results for: rust");
+    }
+
+    #[test]
+    fn test_search_results_from_duckduckgo_html() {
+        let html = r#"<div class="result result--ad"><a class="result__a" href="https://ads.example">Ad</a></div>
+            <div class="result"><a class="result__a" href="//duckduckgo.com/l/?uddg=https%3A%2F%2Frust%2Dlang.org%2F&amp;rut=x">The <b>Rust</b> Language</a>
+            <a class="result__snippet">A language  empowering everyone.</a></div>"#;
+        assert_eq!(
+            parse_duckduckgo(html),
+            vec![(
+                "The Rust Language".to_string(),
+                "https://rust-lang.org/".to_string(),
+                "A language empowering everyone.".to_string()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_google_needs_engine_id() {
+        let tool = WebSearchTool::new(None, 5)
+            .with_backend(search_backend("google", Some("key".to_string()), "", ""));
+        let params = HashMap::from([("query".to_string(), serde_json::json!("rust"))]);
+        assert!(tool.execute(params).await.contains("engineId"));
+    }
+
+    #[test]
+    fn test_search_query_maps_onto_backends() {
+        let client = Client::new();
+        let query = SearchQuery {
+            text: "rust".to_string(),
+            count: 5,
+            vertical: Vertical::News,
+            freshness: Some(Freshness::Day),
+            country: Some("de".to_string()),
+            language: Some("en".to_string()),
+        };
+        let url = |request: reqwest::RequestBuilder| request.build().unwrap().url().to_string();
+
+        let brave = BraveSearch { api_key: "k".to_string() };
+        assert_eq!(
+            url(brave._request(&client, &query)),
+            "https://api.search.brave.com/res/v1/news/search?q=rust&count=5&freshness=pd&country=de&search_lang=en"
+        );
+        let searxng = SearxngSearch { base: "http://sx".to_string() };
+        assert_eq!(
+            url(searxng._request(&client, &query)),
+            "http://sx/search?q=rust&format=json&categories=news&time_range=day&language=en-DE"
+        );
+        let images = SearchQuery {
+            vertical: Vertical::Images,
+            freshness: Some(Freshness::Month),
+            ..query.clone()
+        };
+        let google = GoogleSearch {
+            api_key: "k".to_string(),
+            engine_id: "e".to_string(),
+        };
+        assert!(url(google._request(&client, &images))
+            .ends_with("&searchType=image&dateRestrict=m1&gl=de&lr=lang_en"));
+        assert!(url(DuckDuckGoSearch._request(&client, &query)).ends_with("?q=rust&df=d&kl=de-en"));
+    }
+
+    #[test]
+    fn test_news_and_image_results() {
+        let data = serde_json::json!({"results": [
+            {"title": "Launch", "url": "https://news.example/a", "description": "It flew", "age": "2 hours ago"},
+            {"title": "Old", "url": "https://news.example/b"}
+        ]});
+        assert_eq!(
+            _dated(&data["results"], "description", "age"),
+            vec![
+                ("Launch".to_string(), "https://news.example/a".to_string(), "2 hours ago - It flew".to_string()),
+                ("Old".to_string(), "https://news.example/b".to_string(), String::new()),
+            ]
+        );
+
+        let data = serde_json::json!({"results": [
+            {"title": "Cat", "url": "https://page.example", "properties": {"url": "https://img.example/cat.jpg"}},
+            {"title": "No image"}
+        ]});
+        assert_eq!(
+            _images(&data["results"], "/properties/url", "/url"),
+            vec![(
+                "Cat".to_string(),
+                "https://img.example/cat.jpg".to_string(),
+                "On https://page.example".to_string()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_web_search_checks_filters() {
+        let tool =
+            WebSearchTool::new(None, 5).with_backend(search_backend("duckduckgo", None, "", ""));
+        let params = |key: &str, value: &str| {
+            HashMap::from([
+                ("query".to_string(), serde_json::json!("rust")),
+                (key.to_string(), serde_json::json!(value)),
+            ])
+        };
+        assert!(tool.execute(params("vertical", "video")).await.contains("unknown vertical"));
+        assert!(tool.execute(params("freshness", "year")).await.contains("unknown freshness"));
+        assert!(tool.execute(params("country", "germany")).await.contains("two-letter"));
+        let news = tool.execute(params("vertical", "news")).await;
+        assert!(news.contains("cannot do news searches"), "{}", news);
+    }
+
     #[tokio::test]
     async fn test_web_fetch_invalid_url() {
         let tool = WebFetchTool::new(50000);
@@ -688,6 +1688,211 @@ mod tests {
         assert!(result.contains("error") || result.contains("URL validation failed"));
     }
 
+    #[tokio::test]
+    async fn test_web_fetch_checks_every_redirect() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf).await;
+            let response = format!(
+                "HTTP/1.1 302 Found\r\nLocation: http://127.0.0.1:{}/secret\r\n\
+                 Content-Length: 0\r\nConnection: close\r\n\r\n",
+                port
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+
+        let url = format!("http://localhost:{}/", port);
+        let params = || HashMap::from([("url".to_string(), serde_json::json!(url))]);
+        let blocked = WebFetchTool::new(1000).execute(params()).await;
+        assert!(blocked.contains("Blocked: localhost"), "{}", blocked);
+        let tool = WebFetchTool::new(1000)
+            .with_allowed_hosts(&["localhost".to_string()])
+            .with_politeness(Arc::new(Politeness::new(false, Duration::ZERO)));
+        let redirected = tool.execute(params()).await;
+        assert!(redirected.contains("Blocked: 127.0.0.1"), "{}", redirected);
+    }
+
+    #[test]
+    fn test_chunk_bounds_break_at_paragraphs() {
+        let text = format!("{}\n\n{}", "a".repeat(90), "b".repeat(50));
+        assert_eq!(chunk_bounds(&text, 100), vec![(0, 92), (92, 142)]);
+        assert_eq!(chunk_bounds("", 100), vec![(0, 0)]);
+
+        // Never splits a character.
+        let text = "é".repeat(101);
+        let chunks = chunk_bounds(&text, 101);
+        assert_eq!(chunks[0], (0, 100));
+        assert!(chunks.iter().all(|&(_, end)| text.is_char_boundary(end)));
+    }
+
+    #[tokio::test]
+    async fn test_web_fetch_pages_with_cursor() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            // Serves a single request: later chunks must come from the cache.
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf).await;
+            let body = format!("{}\n{}", "x".repeat(170), "y".repeat(120));
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+
+        let tool = WebFetchTool::new(200)
+            .with_allowed_hosts(&["127.0.0.1".to_string()])
+            .with_politeness(Arc::new(Politeness::new(false, Duration::ZERO)));
+        let url = format!("http://127.0.0.1:{}/", port);
+        let fetch = |extra: (&str, serde_json::Value)| {
+            let params = HashMap::from([
+                ("url".to_string(), serde_json::json!(url)),
+                (extra.0.to_string(), extra.1),
+            ]);
+            let tool = &tool;
+            async move {
+                serde_json::from_str::<serde_json::Value>(&tool.execute(params).await).unwrap()
+            }
+        };
+
+        let first = fetch(("maxChars", serde_json::json!(200))).await;
+        assert_eq!(first["truncated"], true);
+        assert_eq!(first["pages"], 2);
+        assert_eq!(first["text"].as_str().unwrap(), format!("{}\n", "x".repeat(170)));
+        let cursor = first["nextCursor"].clone();
+        assert_eq!(cursor, 171);
+
+        let second = fetch(("cursor", cursor)).await;
+        assert_eq!(second["page"], 2);
+        assert_eq!(second["truncated"], false);
+        assert!(second.get("nextCursor").is_none());
+        assert_eq!(second["text"].as_str().unwrap(), "y".repeat(120));
+
+        let again = fetch(("page", serde_json::json!(2))).await;
+        assert_eq!(again["text"], second["text"]);
+        let missing = fetch(("page", serde_json::json!(3))).await;
+        assert!(missing["error"].as_str().unwrap().contains("2 of them"));
+    }
+
+    #[tokio::test]
+    async fn test_web_fetch_respects_robots_txt() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 1024];
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                let body = if buf[..n].starts_with(b"GET /robots.txt ") {
+                    "User-agent: nanoclaw\nDisallow: /private\n"
+                } else {
+                    "public page"
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let tool = WebFetchTool::new(1000)
+            .with_allowed_hosts(&["127.0.0.1".to_string()])
+            .with_politeness(Arc::new(Politeness::new(true, Duration::ZERO)));
+        let fetch = |path: &str| {
+            let url = format!("http://127.0.0.1:{}{}", port, path);
+            tool.execute(HashMap::from([("url".to_string(), serde_json::json!(url))]))
+        };
+        let blocked = fetch("/private/x").await;
+        assert!(blocked.contains("Blocked by robots.txt"), "{}", blocked);
+        let allowed = fetch("/public").await;
+        assert!(allowed.contains("public page"), "{}", allowed);
+    }
+
+    #[test]
+    fn test_download_names() {
+        assert_eq!(
+            _download_name(
+                "attachment; filename=\"Q3 report.pdf\"",
+                "https://x.example/dl?id=1",
+                ""
+            ),
+            "Q3_report.pdf"
+        );
+        assert_eq!(
+            _download_name("", "https://x.example/files/archive", "application/zip"),
+            "archive.zip"
+        );
+        assert_eq!(_download_name("filename=../../.bashrc", "", ""), "bashrc");
+        assert_eq!(_download_name("", "https://x.example/", ""), "download");
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        std::fs::write(tmp.path().join("a.pdf"), "").unwrap();
+        assert_eq!(_unused_path(tmp.path(), "a.pdf"), tmp.path().join("a-1.pdf"));
+        assert!(_looks_binary(b"%PDF-1.7\n\x00\xff") && !_looks_binary("héllo".as_bytes()));
+    }
+
+    #[tokio::test]
+    async fn test_web_fetch_saves_binary_files() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let body = b"%PDF-1.7\n\x00\x01\x02binary";
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/pdf\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = stream.write_all(head.as_bytes()).await;
+                let _ = stream.write_all(body).await;
+            }
+        });
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let downloads = tmp.path().join("downloads");
+        let tool = |max_bytes| {
+            WebFetchTool::new(1000)
+                .with_allowed_hosts(&["127.0.0.1".to_string()])
+                .with_politeness(Arc::new(Politeness::new(false, Duration::ZERO)))
+                .with_downloads(downloads.clone(), max_bytes)
+        };
+        let params = || {
+            let url = format!("http://127.0.0.1:{}/paper.pdf", port);
+            HashMap::from([("url".to_string(), serde_json::json!(url))])
+        };
+
+        let result: serde_json::Value =
+            serde_json::from_str(&tool(1024).execute(params()).await).unwrap();
+        assert_eq!(result["contentType"], "application/pdf");
+        assert_eq!(result["bytes"], 18);
+        let saved = downloads.join("paper.pdf");
+        assert_eq!(result["savedTo"], saved.to_string_lossy().as_ref());
+        assert!(std::fs::read(&saved).unwrap().starts_with(b"%PDF"));
+
+        let too_large = tool(10).execute(params()).await;
+        assert!(too_large.contains("download limit"), "{}", too_large);
+        let off = tool(0).execute(params()).await;
+        assert!(off.contains("saving files is off"), "{}", off);
+        assert_eq!(std::fs::read_dir(&downloads).unwrap().count(), 1);
+    }
+
     #[tokio::test]
     async fn test_web_fetch_missing_url() {
         let tool = WebFetchTool::new(50000);
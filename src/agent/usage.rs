@@ -0,0 +1,609 @@
+//! Token usage and cost tracking.
+//!
+//! Every LLM call made by the agent loop is appended to a JSON-lines usage
+//! store with its token counts and an estimated cost from a model pricing
+//! table. The store answers the `/cost` command and raises a warning the
+//! first time a day's spend passes the configured budget.
+
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::agent::hooks::{Hook, HookContext};
+use crate::agent::tokens::estimate_tokens;
+use crate::config::schema::{CostConfig, ModelPricing};
+use crate::providers::base::LLMResponse;
+
+/// Built-in prices (USD per million input / output tokens). Keys match any
+/// model name containing them; the longest match wins.
+const DEFAULT_PRICING: &[(&str, f64, f64)] = &[
+    ("claude-opus-4-5", 5.0, 25.0),
+    ("claude-opus-4", 15.0, 75.0),
+    ("claude-sonnet-4", 3.0, 15.0),
+    ("claude-3-5-sonnet", 3.0, 15.0),
+    ("claude-haiku-4", 1.0, 5.0),
+    ("claude-3-5-haiku", 0.8, 4.0),
+    ("gpt-4o-mini", 0.15, 0.6),
+    ("gpt-4o", 2.5, 10.0),
+    ("gpt-4.1-mini", 0.4, 1.6),
+    ("gpt-4.1", 2.0, 8.0),
+    ("deepseek-chat", 0.27, 1.1),
+    ("deepseek-reasoner", 0.55, 2.19),
+    ("gemini-2.5-pro", 1.25, 10.0),
+    ("gemini-2.5-flash", 0.3, 2.5),
+    ("llama-3.3-70b", 0.59, 0.79),
+];
+
+/// One LLM call in the usage store.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageRecord {
+    pub timestamp: DateTime<Local>,
+    pub session: String,
+    pub channel: String,
+    pub model: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Estimated cost in USD; `None` when the model has no known price.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+}
+
+/// Look up the price of a model, preferring configured prices.
+pub fn price_for(model: &str, overrides: &HashMap<String, ModelPricing>) -> Option<ModelPricing> {
+    let model = model.to_lowercase();
+    let configured = overrides
+        .iter()
+        .filter(|(key, _)| model.contains(&key.to_lowercase()))
+        .max_by_key(|(key, _)| key.len())
+        .map(|(_, price)| *price);
+    configured.or_else(|| {
+        DEFAULT_PRICING
+            .iter()
+            .filter(|(key, _, _)| model.contains(key))
+            .max_by_key(|(key, _, _)| key.len())
+            .map(|&(_, input, output)| ModelPricing {
+                input_per_million: input,
+                output_per_million: output,
+            })
+    })
+}
+
+/// All records in the usage file at `path` (unreadable lines are skipped).
+pub fn read_records(path: &Path) -> Vec<UsageRecord> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Append-only store of LLM usage with running daily totals.
+pub struct UsageStore {
+    path: PathBuf,
+    config: CostConfig,
+    /// Spend of the current day, kept in memory for the budget check.
+    today: (NaiveDate, f64),
+    /// Day for which the budget warning was already given.
+    warned_on: Option<NaiveDate>,
+    pending_warning: Option<String>,
+}
+
+impl UsageStore {
+    /// Open the store at `path`, reading today's spend so far.
+    pub fn new(path: PathBuf, config: CostConfig) -> Self {
+        let mut store = Self {
+            path,
+            config,
+            today: (Local::now().date_naive(), 0.0),
+            warned_on: None,
+            pending_warning: None,
+        };
+        let today = store.today.0;
+        store.today.1 = store
+            .load_records()
+            .iter()
+            .filter(|r| r.timestamp.date_naive() == today)
+            .filter_map(|r| r.cost_usd)
+            .sum();
+        if store._over_budget() {
+            store.warned_on = Some(today);
+        }
+        store
+    }
+
+    /// Record one LLM call. Token counts come from the provider's usage
+    /// report when present and are estimated otherwise.
+    pub fn record(&mut self, ctx: &HookContext, messages: &[Value], response: &LLMResponse) {
+        let prompt_tokens = response
+            .usage
+            .get("prompt_tokens")
+            .or_else(|| response.usage.get("estimated_prompt_tokens"))
+            .copied()
+            .unwrap_or_else(|| crate::agent::tokens::estimate_messages_tokens(messages) as i64)
+            .max(0) as u64;
+        let completion_tokens = match response.usage.get("completion_tokens") {
+            Some(n) => (*n).max(0) as u64,
+            None => {
+                let mut text = response.content.clone().unwrap_or_default();
+                for tc in &response.tool_calls {
+                    text.push_str(&tc.name);
+                    text.push_str(&serde_json::to_string(&tc.arguments).unwrap_or_default());
+                }
+                estimate_tokens(&text) as u64
+            }
+        };
+        let cost_usd = price_for(&ctx.model, &self.config.pricing).map(|p| {
+            (prompt_tokens as f64 * p.input_per_million
+                + completion_tokens as f64 * p.output_per_million)
+                / 1_000_000.0
+        });
+
+        let record = UsageRecord {
+            timestamp: Local::now(),
+            session: ctx.session_key.clone(),
+            channel: ctx.channel.clone(),
+            model: ctx.model.clone(),
+            prompt_tokens,
+            completion_tokens,
+            cost_usd,
+        };
+        self._append(&record);
+        self._add_to_today(&record);
+    }
+
+    /// All records in the store (unreadable lines are skipped).
+    pub fn load_records(&self) -> Vec<UsageRecord> {
+        read_records(&self.path)
+    }
+
+    /// Text answer to `/cost` for a session.
+    pub fn report(&self, session: &str) -> String {
+        let records = self.load_records();
+        let today = Local::now().date_naive();
+        let today_totals = _totals(records.iter().filter(|r| r.timestamp.date_naive() == today));
+        let session_totals = _totals(records.iter().filter(|r| r.session == session));
+
+        let mut lines = vec![
+            format!("Today: {}", today_totals),
+            format!("This session: {}", session_totals),
+        ];
+        if self.config.daily_budget_usd > 0.0 {
+            lines.push(format!("Daily budget: ${:.2}", self.config.daily_budget_usd));
+        }
+        if today_totals.unpriced > 0 || session_totals.unpriced > 0 {
+            lines.push(
+                "Some calls used models without a known price and are not included in the cost."
+                    .to_string(),
+            );
+        }
+        lines.join("\n")
+    }
+
+    /// Spend of the current day so far.
+    pub fn spent_today(&self) -> f64 {
+        if self.today.0 == Local::now().date_naive() {
+            self.today.1
+        } else {
+            0.0
+        }
+    }
+
+    /// Warning to show the user if the daily budget was passed since the
+    /// last call. Returned at most once per day.
+    pub fn take_budget_warning(&mut self) -> Option<String> {
+        self.pending_warning.take()
+    }
+
+    // ------------------------------------------------------------------
+    // Private helpers
+    // ------------------------------------------------------------------
+
+    fn _append(&self, record: &UsageRecord) {
+        if let Some(parent) = self.path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let line = match serde_json::to_string(record) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Failed to serialize usage record: {}", e);
+                return;
+            }
+        };
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut f| writeln!(f, "{}", line));
+        if let Err(e) = result {
+            warn!("Failed to write usage store {}: {}", self.path.display(), e);
+        }
+    }
+
+    fn _add_to_today(&mut self, record: &UsageRecord) {
+        let day = record.timestamp.date_naive();
+        if day != self.today.0 {
+            self.today = (day, 0.0);
+        }
+        self.today.1 += record.cost_usd.unwrap_or(0.0);
+
+        if self._over_budget() && self.warned_on != Some(day) {
+            self.warned_on = Some(day);
+            self.pending_warning = Some(format!(
+                "⚠️ Today's spend (${:.2}) has passed the daily budget of ${:.2}.",
+                self.today.1, self.config.daily_budget_usd
+            ));
+        }
+    }
+
+    fn _over_budget(&self) -> bool {
+        self.config.daily_budget_usd > 0.0 && self.today.1 >= self.config.daily_budget_usd
+    }
+}
+
+/// Summed usage over a set of records.
+struct Totals {
+    calls: usize,
+    tokens: u64,
+    cost: f64,
+    unpriced: usize,
+}
+
+impl std::fmt::Display for Totals {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "${:.4} ({} calls, {} tokens)",
+            self.cost, self.calls, self.tokens
+        )
+    }
+}
+
+fn _totals<'a>(records: impl Iterator<Item = &'a UsageRecord>) -> Totals {
+    records.fold(
+        Totals {
+            calls: 0,
+            tokens: 0,
+            cost: 0.0,
+            unpriced: 0,
+        },
+        |mut t, r| {
+            t.calls += 1;
+            t.tokens += r.prompt_tokens + r.completion_tokens;
+            match r.cost_usd {
+                Some(cost) => t.cost += cost,
+                None => t.unpriced += 1,
+            }
+            t
+        },
+    )
+}
+
+// ---------------------------------------------------------------------------
+// Reports
+// ---------------------------------------------------------------------------
+
+/// What `nanoclaw usage` groups records by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UsageGrouping {
+    Model,
+    Session,
+    Channel,
+}
+
+impl UsageGrouping {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "model" => Some(Self::Model),
+            "session" => Some(Self::Session),
+            "channel" => Some(Self::Channel),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Model => "model",
+            Self::Session => "session",
+            Self::Channel => "channel",
+        }
+    }
+
+    fn key(self, record: &UsageRecord) -> &str {
+        match self {
+            Self::Model => &record.model,
+            Self::Session => &record.session,
+            Self::Channel => &record.channel,
+        }
+    }
+}
+
+/// Usage of one model, session or channel.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UsageRow {
+    pub key: String,
+    pub calls: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64,
+    /// Calls with no known price, missing from the cost.
+    pub unpriced: usize,
+}
+
+/// The start of a `--since` period: a duration back from `now` (`30m`,
+/// `24h`, `7d`, `2w`) or a date (`2026-03-01`).
+pub fn parse_since(s: &str, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return date.and_hms_opt(0, 0, 0)?.and_local_timezone(Local).earliest();
+    }
+    let unit = s.chars().last()?;
+    let n: i64 = s[..s.len() - unit.len_utf8()].parse().ok()?;
+    let duration = match unit {
+        'm' => chrono::Duration::try_minutes(n)?,
+        'h' => chrono::Duration::try_hours(n)?,
+        'd' => chrono::Duration::try_days(n)?,
+        'w' => chrono::Duration::try_weeks(n)?,
+        _ => return None,
+    };
+    now.checked_sub_signed(duration)
+}
+
+/// Usage of the records from `since` on, grouped `by`, most expensive
+/// first.
+pub fn usage_by(
+    records: &[UsageRecord],
+    since: Option<DateTime<Local>>,
+    by: UsageGrouping,
+) -> Vec<UsageRow> {
+    let mut rows: HashMap<&str, UsageRow> = HashMap::new();
+    for record in records
+        .iter()
+        .filter(|r| since.is_none_or(|since| r.timestamp >= since))
+    {
+        let key = by.key(record);
+        let row = rows.entry(key).or_insert_with(|| UsageRow {
+            key: key.to_string(),
+            ..Default::default()
+        });
+        row.calls += 1;
+        row.prompt_tokens += record.prompt_tokens;
+        row.completion_tokens += record.completion_tokens;
+        match record.cost_usd {
+            Some(cost) => row.cost_usd += cost,
+            None => row.unpriced += 1,
+        }
+    }
+    let mut rows: Vec<UsageRow> = rows.into_values().collect();
+    let tokens = |row: &UsageRow| row.prompt_tokens + row.completion_tokens;
+    rows.sort_by(|a, b| {
+        b.cost_usd
+            .total_cmp(&a.cost_usd)
+            .then(tokens(b).cmp(&tokens(a)))
+            .then(a.key.cmp(&b.key))
+    });
+    rows
+}
+
+/// Hook that records every LLM call in a [`UsageStore`].
+pub struct CostHook {
+    store: Arc<Mutex<UsageStore>>,
+}
+
+impl CostHook {
+    pub fn new(store: Arc<Mutex<UsageStore>>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl Hook for CostHook {
+    fn name(&self) -> &str {
+        "cost"
+    }
+
+    async fn after_llm_call(
+        &self,
+        ctx: &HookContext,
+        messages: &[Value],
+        response: &mut LLMResponse,
+    ) {
+        if let Ok(mut store) = self.store.lock() {
+            store.record(ctx, messages, response);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn ctx(session: &str, model: &str) -> HookContext {
+        HookContext {
+            turn_id: "t1".to_string(),
+            channel: "telegram".to_string(),
+            chat_id: "42".to_string(),
+            session_key: session.to_string(),
+            model: model.to_string(),
+            iteration: 0,
+        }
+    }
+
+    fn response(prompt: i64, completion: i64) -> LLMResponse {
+        LLMResponse {
+            content: Some("hi".to_string()),
+            tool_calls: vec![],
+            finish_reason: "stop".to_string(),
+            usage: HashMap::from([
+                ("prompt_tokens".to_string(), prompt),
+                ("completion_tokens".to_string(), completion),
+            ]),
+        }
+    }
+
+    // ── pricing ──
+
+    #[test]
+    fn test_price_for_longest_match() {
+        let none = HashMap::new();
+        let mini = price_for("openai/gpt-4o-mini", &none).unwrap();
+        assert_eq!(mini.input_per_million, 0.15);
+        let full = price_for("openai/gpt-4o", &none).unwrap();
+        assert_eq!(full.input_per_million, 2.5);
+        assert!(price_for("some/unknown-model", &none).is_none());
+    }
+
+    #[test]
+    fn test_price_for_prefers_config() {
+        let overrides = HashMap::from([(
+            "gpt-4o".to_string(),
+            ModelPricing {
+                input_per_million: 1.0,
+                output_per_million: 2.0,
+            },
+        )]);
+        assert_eq!(
+            price_for("openai/gpt-4o-mini", &overrides).unwrap().input_per_million,
+            1.0
+        );
+    }
+
+    // ── store ──
+
+    #[test]
+    fn test_record_and_report() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("usage.jsonl");
+        let mut store = UsageStore::new(path.clone(), CostConfig::default());
+
+        // 1M prompt + 100k completion tokens of gpt-4o: $2.50 + $1.00.
+        store.record(&ctx("s1", "openai/gpt-4o"), &[], &response(1_000_000, 100_000));
+        store.record(&ctx("s2", "openai/gpt-4o"), &[], &response(1_000_000, 0));
+
+        let records = store.load_records();
+        assert_eq!(records.len(), 2);
+        assert!((records[0].cost_usd.unwrap() - 3.5).abs() < 1e-9);
+
+        let report = store.report("s1");
+        assert!(report.contains("Today: $6.0000 (2 calls"), "{}", report);
+        assert!(report.contains("This session: $3.5000 (1 calls"), "{}", report);
+
+        // Totals survive a restart.
+        let reopened = UsageStore::new(path, CostConfig::default());
+        assert!((reopened.today.1 - 6.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_unpriced_model_is_reported() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = UsageStore::new(tmp.path().join("usage.jsonl"), CostConfig::default());
+        store.record(&ctx("s1", "local/llama"), &[], &response(10, 10));
+        assert!(store.load_records()[0].cost_usd.is_none());
+        assert!(store.report("s1").contains("without a known price"));
+    }
+
+    #[test]
+    fn test_missing_usage_is_estimated() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = UsageStore::new(tmp.path().join("usage.jsonl"), CostConfig::default());
+        let mut resp = response(0, 0);
+        resp.usage.clear();
+        resp.content = Some("abcdefgh".to_string());
+        let messages = vec![serde_json::json!({"role": "user", "content": "abcd"})];
+        store.record(&ctx("s1", "gpt-4o"), &messages, &resp);
+        let record = &store.load_records()[0];
+        assert!(record.prompt_tokens > 0);
+        assert_eq!(record.completion_tokens, 2);
+    }
+
+    // ── reports ──
+
+    fn record(
+        model: &str,
+        channel: &str,
+        days_ago: i64,
+        tokens: u64,
+        cost: Option<f64>,
+    ) -> UsageRecord {
+        UsageRecord {
+            timestamp: Local::now() - chrono::Duration::days(days_ago),
+            session: format!("{}:1", channel),
+            channel: channel.to_string(),
+            model: model.to_string(),
+            prompt_tokens: tokens,
+            completion_tokens: tokens / 10,
+            cost_usd: cost,
+        }
+    }
+
+    #[test]
+    fn test_usage_by_groups_and_sorts() {
+        let records = vec![
+            record("gpt-4o", "telegram", 0, 1000, Some(0.5)),
+            record("claude-sonnet-4", "telegram", 1, 2000, Some(2.0)),
+            record("gpt-4o", "cli", 2, 1000, Some(0.5)),
+            record("local/llama", "cli", 3, 5000, None),
+            record("gpt-4o", "cli", 30, 1000, Some(0.5)),
+        ];
+        let rows = usage_by(&records, None, UsageGrouping::Model);
+        let keys: Vec<&str> = rows.iter().map(|r| r.key.as_str()).collect();
+        assert_eq!(keys, ["claude-sonnet-4", "gpt-4o", "local/llama"]);
+        assert_eq!(rows[1].calls, 3);
+        assert_eq!(rows[1].prompt_tokens, 3000);
+        assert_eq!(rows[1].completion_tokens, 300);
+        assert_eq!(rows[2].unpriced, 1);
+
+        let since = parse_since("7d", Local::now());
+        let rows = usage_by(&records, since, UsageGrouping::Channel);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].key, "telegram");
+        assert!((rows[0].cost_usd - 2.5).abs() < 1e-9);
+        assert_eq!(rows[1].calls, 2);
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let now = Local::now();
+        assert_eq!(parse_since("24h", now), Some(now - chrono::Duration::hours(24)));
+        assert_eq!(parse_since("2w", now), Some(now - chrono::Duration::days(14)));
+        let date = parse_since("2026-03-01", now).unwrap();
+        assert_eq!(date.format("%Y-%m-%d %H:%M").to_string(), "2026-03-01 00:00");
+        for bad in ["", "7", "d", "7y", "-", "2026-13-01"] {
+            assert!(parse_since(bad, now).is_none(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_budget_warning_once_per_day() {
+        let tmp = TempDir::new().unwrap();
+        let config = CostConfig {
+            daily_budget_usd: 3.0,
+            ..Default::default()
+        };
+        let path = tmp.path().join("usage.jsonl");
+        let mut store = UsageStore::new(path.clone(), config.clone());
+
+        store.record(&ctx("s1", "gpt-4o"), &[], &response(1_000_000, 0));
+        assert!(store.take_budget_warning().is_none());
+        store.record(&ctx("s1", "gpt-4o"), &[], &response(1_000_000, 0));
+        let warning = store.take_budget_warning().unwrap();
+        assert!(warning.contains("$5.00") && warning.contains("$3.00"));
+        store.record(&ctx("s1", "gpt-4o"), &[], &response(1_000_000, 0));
+        assert!(store.take_budget_warning().is_none());
+
+        // Already over budget at startup: no repeated warning.
+        let mut reopened = UsageStore::new(path, config);
+        reopened.record(&ctx("s1", "gpt-4o"), &[], &response(1, 0));
+        assert!(reopened.take_budget_warning().is_none());
+    }
+}
@@ -0,0 +1,156 @@
+//! Several workspaces served by one agent loop.
+//!
+//! Besides the default workspace, the config can name extra ones, each with
+//! its own memory, skills and bootstrap files (e.g. work and personal). A
+//! message uses the workspace of its agent profile if the profile names
+//! one, otherwise the first workspace whose routes match its chat, otherwise
+//! the default.
+
+use std::path::Path;
+
+use tracing::warn;
+
+use crate::agent::context::ContextBuilder;
+use crate::config::schema::{AgentProfileConfig, WorkspaceConfig};
+
+/// A named workspace and the context builder over it.
+struct Named {
+    config: WorkspaceConfig,
+    context: ContextBuilder,
+}
+
+/// The context builders of all workspaces.
+pub struct Workspaces {
+    default: ContextBuilder,
+    named: Vec<Named>,
+}
+
+impl Workspaces {
+    /// Start with the default workspace only.
+    pub fn new(default: &Path) -> Self {
+        Self {
+            default: ContextBuilder::new(default),
+            named: Vec::new(),
+        }
+    }
+
+    /// Replace the named workspaces. Builder settings made so far (budget,
+    /// channel prompts, skills config) are copied to the new ones.
+    pub fn set_named(&mut self, workspaces: Vec<WorkspaceConfig>) {
+        self.named = workspaces
+            .into_iter()
+            .filter(|ws| {
+                let ok = !ws.name.is_empty() && !ws.path.is_empty();
+                if !ok {
+                    warn!("Ignoring workspace without name or path");
+                }
+                ok
+            })
+            .map(|config| {
+                let mut context = ContextBuilder::new(&config.expanded_path());
+                context.budget = self.default.budget.clone();
+                context.channel_prompts = self.default.channel_prompts.clone();
+                if let Some(config) = self.default.skills.config() {
+                    context.skills.set_config(config);
+                }
+                Named { config, context }
+            })
+            .collect();
+    }
+
+    /// Every context builder, for applying settings to all of them.
+    pub fn all_mut(&mut self) -> impl Iterator<Item = &mut ContextBuilder> {
+        std::iter::once(&mut self.default).chain(self.named.iter_mut().map(|n| &mut n.context))
+    }
+
+    /// The context builder of the default workspace.
+    pub fn default_context(&self) -> &ContextBuilder {
+        &self.default
+    }
+
+    /// The context builder for a message, with the workspace name (`None`
+    /// for the default workspace).
+    pub fn select(
+        &self,
+        profile: Option<&AgentProfileConfig>,
+        channel: &str,
+        chat_id: &str,
+    ) -> (Option<&str>, &ContextBuilder) {
+        if let Some(name) = profile.and_then(|p| p.workspace.as_deref()) {
+            match self.named.iter().find(|n| n.config.name == name) {
+                Some(n) => return (Some(&n.config.name), &n.context),
+                None => warn!("Profile workspace '{}' is not configured", name),
+            }
+        }
+
+        let chat = format!("{}:{}", channel, chat_id);
+        self.named
+            .iter()
+            .find(|n| n.config.routes.contains(&chat))
+            .or_else(|| {
+                self.named
+                    .iter()
+                    .find(|n| n.config.routes.iter().any(|r| r == channel))
+            })
+            .map_or((None, &self.default), |n| {
+                (Some(n.config.name.as_str()), &n.context)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn workspace(name: &str, dir: &TempDir, routes: &[&str]) -> WorkspaceConfig {
+        WorkspaceConfig {
+            name: name.to_string(),
+            path: dir.path().join(name).to_string_lossy().to_string(),
+            routes: routes.iter().map(|r| r.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_select_by_profile_then_route() {
+        let dir = TempDir::new().unwrap();
+        let mut workspaces = Workspaces::new(&dir.path().join("default"));
+        workspaces.set_named(vec![
+            workspace("work", &dir, &["slack", "telegram:42"]),
+            workspace("personal", &dir, &["telegram"]),
+        ]);
+
+        let (name, context) = workspaces.select(None, "telegram", "42");
+        assert_eq!(name, Some("work"));
+        assert!(context.workspace.ends_with("work"));
+        assert_eq!(workspaces.select(None, "telegram", "7").0, Some("personal"));
+        assert_eq!(workspaces.select(None, "cli", "direct").0, None);
+
+        let profile = AgentProfileConfig {
+            name: "coder".to_string(),
+            workspace: Some("work".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(workspaces.select(Some(&profile), "telegram", "7").0, Some("work"));
+        let unknown = AgentProfileConfig {
+            workspace: Some("nope".to_string()),
+            ..profile
+        };
+        assert_eq!(workspaces.select(Some(&unknown), "cli", "direct").0, None);
+    }
+
+    #[test]
+    fn test_named_workspaces_inherit_settings() {
+        let dir = TempDir::new().unwrap();
+        let mut workspaces = Workspaces::new(dir.path());
+        for context in workspaces.all_mut() {
+            context
+                .channel_prompts
+                .insert("whatsapp".to_string(), "Be brief.".to_string());
+        }
+        workspaces.set_named(vec![workspace("work", &dir, &["whatsapp"]), workspace("", &dir, &[])]);
+        assert_eq!(workspaces.named.len(), 1);
+        let (_, context) = workspaces.select(None, "whatsapp", "1");
+        assert_eq!(context.channel_prompts["whatsapp"], "Be brief.");
+    }
+}
@@ -0,0 +1,247 @@
+//! Authentication and CORS for the HTTP APIs.
+//!
+//! Requests carry `Authorization: Bearer <token>`. `gateway.apiToken` has
+//! full access; each entry of `gateway.apiTokens` has a scope: `read` may
+//! only use GET endpoints, `send` may also send messages. Without any
+//! token, only requests from this machine are accepted.
+
+use std::net::SocketAddr;
+
+use axum::extract::{ConnectInfo, Request};
+use axum::http::{header, HeaderValue, Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use serde_json::json;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tracing::warn;
+
+use crate::config::schema::GatewayConfig;
+
+/// Prefix of generated tokens, to make them recognisable.
+const TOKEN_PREFIX: &str = "nc_";
+
+/// What a token may do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Scope {
+    /// GET endpoints only.
+    Read,
+    /// Everything, including sending messages.
+    Send,
+}
+
+impl Scope {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "read" => Some(Self::Read),
+            "send" => Some(Self::Send),
+            _ => None,
+        }
+    }
+
+    /// Scope a request needs: reading for GET and HEAD, sending otherwise.
+    fn needed_for(method: &Method) -> Self {
+        if method == Method::GET || method == Method::HEAD {
+            Self::Read
+        } else {
+            Self::Send
+        }
+    }
+}
+
+/// The accepted tokens and browser origins.
+#[derive(Default)]
+pub struct ApiAccess {
+    tokens: Vec<(String, Scope)>,
+    cors_origins: Vec<String>,
+}
+
+impl ApiAccess {
+    pub fn new(tokens: Vec<(String, Scope)>, cors_origins: Vec<String>) -> Self {
+        let tokens = tokens.into_iter().filter(|(t, _)| !t.is_empty()).collect();
+        Self {
+            tokens,
+            cors_origins,
+        }
+    }
+
+    /// Tokens and origins from the gateway config. Tokens with an unknown
+    /// scope are read-only.
+    pub fn from_config(gateway: &GatewayConfig) -> Self {
+        let mut tokens = vec![(gateway.api_token.clone(), Scope::Send)];
+        for entry in &gateway.api_tokens {
+            let scope = Scope::parse(&entry.scope).unwrap_or_else(|| {
+                warn!(
+                    "API token '{}' has unknown scope '{}'; read-only",
+                    entry.name, entry.scope
+                );
+                Scope::Read
+            });
+            tokens.push((entry.token.clone(), scope));
+        }
+        Self::new(tokens, gateway.cors_origins.clone())
+    }
+
+    /// Check the request's token and its scope, or without any tokens,
+    /// that the caller is local. Returns the error response for a rejected
+    /// request.
+    pub fn check(&self, request: &Request) -> Option<Response> {
+        if self.tokens.is_empty() {
+            let local = request
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .is_some_and(|ConnectInfo(addr)| addr.ip().is_loopback());
+            if local {
+                return None;
+            }
+            return Some(error(
+                StatusCode::FORBIDDEN,
+                "set gateway.apiToken to allow requests from other machines",
+            ));
+        }
+
+        let given = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        let scope = self
+            .tokens
+            .iter()
+            .filter(|(token, _)| same(token, given))
+            .map(|(_, scope)| *scope)
+            .max();
+        match scope {
+            None => Some(error(
+                StatusCode::UNAUTHORIZED,
+                "missing or wrong API token",
+            )),
+            Some(scope) if scope < Scope::needed_for(request.method()) => {
+                Some(error(StatusCode::FORBIDDEN, "this API token is read-only"))
+            }
+            Some(_) => None,
+        }
+    }
+
+    /// CORS layer for the configured origins, if any.
+    pub fn cors_layer(&self) -> Option<CorsLayer> {
+        if self.cors_origins.is_empty() {
+            return None;
+        }
+        let origins = if self.cors_origins.iter().any(|o| o == "*") {
+            AllowOrigin::from(Any)
+        } else {
+            let values = self
+                .cors_origins
+                .iter()
+                .filter_map(|o| match HeaderValue::from_str(o) {
+                    Ok(value) => Some(value),
+                    Err(_) => {
+                        warn!("Ignoring invalid CORS origin '{}'", o);
+                        None
+                    }
+                })
+                .collect::<Vec<_>>();
+            AllowOrigin::list(values)
+        };
+        Some(
+            CorsLayer::new()
+                .allow_origin(origins)
+                .allow_methods([Method::GET, Method::POST])
+                .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE]),
+        )
+    }
+}
+
+/// Compare tokens in time independent of where they differ.
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// A new random token.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 24];
+    OsRng.fill_bytes(&mut bytes);
+    format!("{}{}", TOKEN_PREFIX, hex::encode(bytes))
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({"error": message}))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    fn request(method: Method, token: Option<&str>) -> Request {
+        let mut builder = Request::builder().method(method).uri("/api/status");
+        if let Some(token) = token {
+            builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn test_token_scopes() {
+        let access = ApiAccess::new(
+            vec![
+                ("full".to_string(), Scope::Send),
+                ("viewer".to_string(), Scope::Read),
+                (String::new(), Scope::Send),
+            ],
+            Vec::new(),
+        );
+        assert!(access
+            .check(&request(Method::GET, Some("viewer")))
+            .is_none());
+        assert!(access.check(&request(Method::POST, Some("full"))).is_none());
+
+        let denied = access
+            .check(&request(Method::POST, Some("viewer")))
+            .unwrap();
+        assert_eq!(denied.status(), StatusCode::FORBIDDEN);
+        let denied = access.check(&request(Method::GET, Some("nope"))).unwrap();
+        assert_eq!(denied.status(), StatusCode::UNAUTHORIZED);
+        // The empty token never matches.
+        let denied = access.check(&request(Method::GET, Some(""))).unwrap();
+        assert_eq!(denied.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_cors_preflight() {
+        use tower::ServiceExt;
+
+        assert!(ApiAccess::default().cors_layer().is_none());
+        let access = ApiAccess::new(Vec::new(), vec!["https://app.example".to_string()]);
+        let app = axum::Router::new()
+            .route("/api/status", axum::routing::get(|| async { "ok" }))
+            .layer(access.cors_layer().unwrap());
+        let preflight = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/api/status")
+            .header(header::ORIGIN, "https://app.example")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(preflight).await.unwrap();
+        assert_eq!(
+            response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example"
+        );
+    }
+
+    #[test]
+    fn test_generate_token() {
+        let token = generate_token();
+        assert!(token.starts_with(TOKEN_PREFIX));
+        assert_eq!(token.len(), TOKEN_PREFIX.len() + 48);
+        assert_ne!(token, generate_token());
+    }
+}
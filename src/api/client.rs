@@ -0,0 +1,188 @@
+//! Client for a running gateway's REST API.
+//!
+//! CLI commands that would otherwise run their own agent loop send their
+//! messages to the gateway instead, so sessions, memory and cron jobs are
+//! only ever touched by one process.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use crate::config::schema::GatewayConfig;
+use crate::daemon::Daemon;
+
+/// How long to wait for the agent's reply.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Where clients on this machine reach the REST API of a gateway listening
+/// on `gateway.host` and `port`.
+pub fn local_api_url(gateway: &GatewayConfig, port: u16) -> String {
+    let host = match gateway.host.as_str() {
+        "" | "0.0.0.0" | "::" | "[::]" => "127.0.0.1",
+        host => host,
+    };
+    let host = if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]", host)
+    } else {
+        host.to_string()
+    };
+    let scheme = crate::api::tls::scheme(&gateway.tls);
+    format!("{}://{}:{}/api", scheme, host, port)
+}
+
+/// A connection to the gateway's REST API.
+pub struct GatewayClient {
+    base: String,
+    token: Option<String>,
+    http: Client,
+}
+
+impl GatewayClient {
+    /// A client for the gateway running on `daemon`'s data directory, if
+    /// one is running. Authenticates with `gateway.apiToken`, or else the
+    /// first token that may send.
+    pub fn connect(daemon: &Daemon, gateway: &GatewayConfig) -> Option<Self> {
+        let base = daemon.api_url()?;
+        let token = Some(gateway.api_token.clone())
+            .filter(|t| !t.is_empty())
+            .or_else(|| {
+                gateway
+                    .api_tokens
+                    .iter()
+                    .find(|t| t.scope == "send")
+                    .map(|t| t.token.clone())
+            });
+        Some(Self::new(base, token))
+    }
+
+    pub fn new(base: String, token: Option<String>) -> Self {
+        // The certificate is for the public name, not for the loopback
+        // address the client connects to.
+        let http = Client::builder()
+            .danger_accept_invalid_certs(
+                base.starts_with("https://127.0.0.1") || base.starts_with("https://[::1]"),
+            )
+            .no_proxy()
+            .build()
+            .unwrap_or_default();
+        Self { base, token, http }
+    }
+
+    /// Send `content` to chat `to` of `channel` as the bot, without the
+    /// agent.
+    pub async fn send_to_channel(&self, channel: &str, to: &str, content: &str) -> Result<()> {
+        self._post(
+            &["channels", channel, "messages"],
+            json!({"to": to, "content": content}),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Hand dead letter `id`, or all of them for `all`, back to the
+    /// channels. Returns the ids resent.
+    pub async fn retry_dead_letter(&self, id: &str) -> Result<Vec<String>> {
+        let body = self
+            ._post(&["dead-letters", id, "retry"], json!({}))
+            .await?;
+        Ok(body["retried"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|id| id.as_str().map(str::to_string))
+            .collect())
+    }
+
+    /// Send `content`, with the files at the paths or URLs `attachments`,
+    /// to the agent in session `session` and wait for the reply.
+    pub async fn send(
+        &self,
+        session: &str,
+        content: &str,
+        attachments: &[String],
+    ) -> Result<String> {
+        let body = self
+            ._post(
+                &["sessions", session, "messages"],
+                json!({"content": content, "wait": true, "attachments": attachments}),
+            )
+            .await?;
+        match body["status"].as_str() {
+            Some("done") => Ok(body["reply"].as_str().unwrap_or_default().to_string()),
+            _ => bail!(
+                "the agent failed: {}",
+                body["error"].as_str().unwrap_or("unknown error")
+            ),
+        }
+    }
+
+    /// Run cron job `id` now, through the gateway's job handler, and wait
+    /// for its output.
+    pub async fn run_job(&self, id: &str) -> Result<String> {
+        let body = self._post(&["jobs", id, "run"], json!({})).await?;
+        match body["status"].as_str() {
+            Some("done") => Ok(body["output"].as_str().unwrap_or_default().to_string()),
+            _ => bail!(body["error"].as_str().unwrap_or("unknown error").to_string()),
+        }
+    }
+
+    /// POST `body` to the API path made of `segments`; returns the answer.
+    async fn _post(&self, segments: &[&str], body: Value) -> Result<Value> {
+        let mut url = url::Url::parse(&self.base)?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("bad gateway URL {}", self.base))?
+            .extend(segments);
+        let mut request = self.http.post(url).timeout(REPLY_TIMEOUT).json(&body);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| anyhow!("cannot reach the gateway at {}: {}", self.base, e))?;
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or_default();
+        if !status.is_success() {
+            let message = body["error"].as_str().unwrap_or("no details");
+            bail!("the gateway answered {}: {}", status, message);
+        }
+        Ok(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_api_url() {
+        let mut gateway = GatewayConfig::default();
+        assert_eq!(local_api_url(&gateway, 9000), "http://127.0.0.1:9000/api");
+        gateway.host = "::1".to_string();
+        gateway.tls.cert = "cert.pem".to_string();
+        assert_eq!(local_api_url(&gateway, 9000), "https://[::1]:9000/api");
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        use axum::extract::Path;
+        use axum::routing::post;
+        use axum::{Json, Router};
+
+        let app = Router::new().route(
+            "/api/sessions/{key}/messages",
+            post(|Path(key): Path<String>, Json(body): Json<Value>| async move {
+                Json(json!({"id": "1", "status": "done", "reply": format!("{}: {}", key, body["content"])}))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}/api", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = GatewayClient::new(base, None);
+        let reply = client.send("cli:default", "hi", &[]).await.unwrap();
+        assert_eq!(reply, "cli:default: \"hi\"");
+    }
+}
@@ -0,0 +1,6 @@
+pub mod auth;
+pub mod client;
+pub mod openai;
+pub mod server;
+pub mod tls;
+pub mod webhooks;
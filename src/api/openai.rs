@@ -0,0 +1,491 @@
+//! OpenAI-compatible chat completions server (`nanoclaw serve --openai`).
+//!
+//! Exposes `POST /v1/chat/completions` and `GET /v1/models`, so IDE plugins
+//! and chat UIs that speak the OpenAI API can use nanoclaw as a model. Each
+//! request runs a full agent turn, with tools, memory and the workspace.
+//!
+//! Requests with the same `user` share a session kept by the agent, so only
+//! their last user message is sent to it. Without `user`, each request
+//! runs on a one-off session holding the user and assistant messages that
+//! came before its last user message. Client system prompts are ignored in
+//! favour of the agent's own. With `"stream": true` the reply is sent in
+//! chunks as the LLM generates it.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use axum_server::tls_rustls::RustlsConfig;
+use futures_util::stream;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::sync::oneshot;
+use tracing::info;
+
+use crate::agent::agent_loop::DirectRequest;
+use crate::api::auth::ApiAccess;
+use crate::api::tls;
+use crate::bus::events::InboundMessage;
+
+/// Channel name of messages sent through the OpenAI API.
+const OPENAI_CHANNEL: &str = "openai";
+
+/// Model id reported to clients.
+const MODEL_ID: &str = "nanoclaw";
+
+/// What the handlers share.
+pub struct OpenAiState {
+    direct_tx: UnboundedSender<DirectRequest>,
+    access: ApiAccess,
+}
+
+impl OpenAiState {
+    pub fn new(direct_tx: UnboundedSender<DirectRequest>, access: ApiAccess) -> Self {
+        Self { direct_tx, access }
+    }
+}
+
+/// The `/v1` routes over `state`.
+pub fn router(state: Arc<OpenAiState>) -> Router {
+    let router = Router::new()
+        .route("/v1/chat/completions", post(chat_completions))
+        .route("/v1/models", get(list_models))
+        .layer(middleware::from_fn_with_state(state.clone(), authorize));
+    match state.access.cors_layer() {
+        Some(cors) => router.layer(cors).with_state(state),
+        None => router.with_state(state),
+    }
+}
+
+/// Serve the API on `host:port` until the task is dropped, over HTTPS
+/// when `tls` is given.
+pub async fn serve(
+    state: Arc<OpenAiState>,
+    host: &str,
+    port: u16,
+    tls: Option<RustlsConfig>,
+) -> anyhow::Result<()> {
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    info!(
+        "OpenAI-compatible API listening on {}://{}:{}/v1",
+        scheme, host, port
+    );
+    tls::serve(router(state), host, port, tls).await
+}
+
+async fn authorize(
+    State(state): State<Arc<OpenAiState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match state.access.check(&request) {
+        Some(rejected) => rejected,
+        None => next.run(request).await,
+    }
+}
+
+/// An error in the shape OpenAI clients expect.
+fn error(status: StatusCode, kind: &str, message: &str) -> Response {
+    (
+        status,
+        Json(json!({"error": {"message": message, "type": kind}})),
+    )
+        .into_response()
+}
+
+// ---------------------------------------------------------------------------
+// Requests
+// ---------------------------------------------------------------------------
+
+#[derive(Deserialize)]
+struct ChatMessage {
+    role: String,
+    #[serde(default)]
+    content: Value,
+}
+
+#[derive(Deserialize)]
+struct ChatRequest {
+    messages: Vec<ChatMessage>,
+    #[serde(default)]
+    stream: bool,
+    #[serde(default)]
+    user: Option<String>,
+}
+
+/// The text of a message `content`: a string, or a list of parts of which
+/// the text ones are joined.
+fn content_text(content: &Value) -> String {
+    match content {
+        Value::String(s) => s.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter(|p| p["type"] == "text")
+            .filter_map(|p| p["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// The user and assistant messages before the last user message, as
+/// `role`/`content` pairs to seed a one-off session with.
+fn earlier_messages(request: &ChatRequest) -> Vec<Value> {
+    let last_user = request
+        .messages
+        .iter()
+        .rposition(|m| m.role == "user")
+        .unwrap_or(0);
+    request.messages[..last_user]
+        .iter()
+        .filter(|m| m.role == "user" || m.role == "assistant")
+        .map(|m| json!({"role": m.role, "content": content_text(&m.content)}))
+        .filter(|m| m["content"].as_str().is_some_and(|c| !c.trim().is_empty()))
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Handlers
+// ---------------------------------------------------------------------------
+
+async fn list_models() -> Json<Value> {
+    Json(json!({
+        "object": "list",
+        "data": [{"id": MODEL_ID, "object": "model", "created": 0, "owned_by": "nanoclaw"}],
+    }))
+}
+
+async fn chat_completions(
+    State(state): State<Arc<OpenAiState>>,
+    Json(request): Json<ChatRequest>,
+) -> Response {
+    let Some(last) = request.messages.iter().rev().find(|m| m.role == "user") else {
+        return error(
+            StatusCode::BAD_REQUEST,
+            "invalid_request_error",
+            "no user message",
+        );
+    };
+    let content = content_text(&last.content);
+    if content.trim().is_empty() {
+        return error(
+            StatusCode::BAD_REQUEST,
+            "invalid_request_error",
+            "user message is empty",
+        );
+    }
+
+    let msg = match request.user.as_deref().filter(|u| !u.is_empty()) {
+        Some(user) => InboundMessage::new(OPENAI_CHANNEL, OPENAI_CHANNEL, user, content),
+        None => {
+            let conversation = uuid::Uuid::new_v4().simple().to_string();
+            let mut msg =
+                InboundMessage::new(OPENAI_CHANNEL, OPENAI_CHANNEL, conversation, content);
+            msg.metadata
+                .insert("seed_history".to_string(), json!(earlier_messages(&request)));
+            msg
+        }
+    };
+    let (reply_tx, reply_rx) = oneshot::channel();
+    let (deltas, delta_rx) = if request.stream {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Some(tx), Some(rx))
+    } else {
+        (None, None)
+    };
+    if state
+        .direct_tx
+        .send(DirectRequest { msg, reply_tx, deltas })
+        .is_err()
+    {
+        return error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "server_error",
+            "agent loop is not running",
+        );
+    }
+
+    let id = format!("chatcmpl-{}", uuid::Uuid::new_v4().simple());
+    let created = chrono::Utc::now().timestamp();
+
+    // The deltas close before the reply is sent, so a turn that produced
+    // no text is answered as if it had not been streamed.
+    if let Some(mut delta_rx) = delta_rx {
+        if let Some(first) = delta_rx.recv().await {
+            return stream_reply(id, created, first, delta_rx, reply_rx);
+        }
+    }
+
+    let reply = match reply_rx.await {
+        Ok(Ok(reply)) => reply,
+        Ok(Err(e)) => return error(StatusCode::BAD_GATEWAY, "server_error", &e.to_string()),
+        Err(_) => {
+            return error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "server_error",
+                "agent loop dropped the request",
+            )
+        }
+    };
+
+    if !request.stream {
+        return Json(json!({
+            "id": id,
+            "object": "chat.completion",
+            "created": created,
+            "model": MODEL_ID,
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": reply},
+                "finish_reason": "stop",
+            }],
+            "usage": {"prompt_tokens": 0, "completion_tokens": 0, "total_tokens": 0},
+        }))
+        .into_response();
+    }
+
+    let events = vec![
+        completion_chunk(&id, created, json!({"role": "assistant", "content": reply}), Value::Null),
+        completion_chunk(&id, created, json!({}), json!("stop")),
+        "[DONE]".to_string(),
+    ];
+    Sse::new(stream::iter(
+        events
+            .into_iter()
+            .map(|data| Ok::<_, Infallible>(Event::default().data(data))),
+    ))
+    .into_response()
+}
+
+/// One `chat.completion.chunk` of a streamed reply.
+fn completion_chunk(id: &str, created: i64, delta: Value, finish: Value) -> String {
+    json!({
+        "id": id,
+        "object": "chat.completion.chunk",
+        "created": created,
+        "model": MODEL_ID,
+        "choices": [{"index": 0, "delta": delta, "finish_reason": finish}],
+    })
+    .to_string()
+}
+
+/// Stream the turn's text as chunks, starting with `first`, and then the
+/// end of the reply that was not streamed, such as a budget warning.
+fn stream_reply(
+    id: String,
+    created: i64,
+    first: String,
+    mut deltas: mpsc::UnboundedReceiver<String>,
+    reply_rx: oneshot::Receiver<anyhow::Result<String>>,
+) -> Response {
+    let (event_tx, event_rx) = mpsc::unbounded_channel::<String>();
+    tokio::spawn(async move {
+        let chunk = |delta: Value, finish: Value| completion_chunk(&id, created, delta, finish);
+        let failure = |message: &str| {
+            json!({"error": {"message": message, "type": "server_error"}}).to_string()
+        };
+        let _ = event_tx.send(chunk(
+            json!({"role": "assistant", "content": first}),
+            Value::Null,
+        ));
+        let mut streamed = first;
+        while let Some(delta) = deltas.recv().await {
+            let _ = event_tx.send(chunk(json!({"content": delta}), Value::Null));
+            streamed.push_str(&delta);
+        }
+        match reply_rx.await {
+            Ok(Ok(reply)) => {
+                if let Some(rest) = reply.strip_prefix(&streamed).filter(|r| !r.is_empty()) {
+                    let _ = event_tx.send(chunk(json!({"content": rest}), Value::Null));
+                }
+                let _ = event_tx.send(chunk(json!({}), json!("stop")));
+            }
+            Ok(Err(e)) => {
+                let _ = event_tx.send(failure(&e.to_string()));
+            }
+            Err(_) => {
+                let _ = event_tx.send(failure("agent loop dropped the request"));
+            }
+        }
+        let _ = event_tx.send("[DONE]".to_string());
+    });
+    let events = stream::unfold(event_rx, |mut rx| async move {
+        let data = rx.recv().await?;
+        Some((Ok::<_, Infallible>(Event::default().data(data)), rx))
+    });
+    Sse::new(events).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::extract::ConnectInfo;
+    use axum::http::header;
+    use std::net::SocketAddr;
+    use tokio::sync::mpsc;
+    use tower::ServiceExt;
+
+    fn chat(body: Value) -> Request {
+        Request::builder()
+            .method("POST")
+            .uri("/v1/chat/completions")
+            .header(header::CONTENT_TYPE, "application/json")
+            .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 5000))))
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    /// Answer every agent request with "echo: <content>".
+    fn echo_state() -> Arc<OpenAiState> {
+        let (tx, mut rx) = mpsc::unbounded_channel::<DirectRequest>();
+        tokio::spawn(async move {
+            while let Some(request) = rx.recv().await {
+                let reply = format!("echo: {}", request.msg.content);
+                let _ = request.reply_tx.send(Ok(reply));
+            }
+        });
+        Arc::new(OpenAiState::new(tx, ApiAccess::default()))
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    // ── sessions ──
+
+    #[test]
+    fn test_earlier_messages() {
+        let request: ChatRequest = serde_json::from_value(json!({
+            "messages": [
+                {"role": "system", "content": "be nice"},
+                {"role": "user", "content": [{"type": "text", "text": "hello"}]},
+                {"role": "assistant", "content": "hi"},
+                {"role": "user", "content": "again"},
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            earlier_messages(&request),
+            vec![
+                json!({"role": "user", "content": "hello"}),
+                json!({"role": "assistant", "content": "hi"}),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sessions_by_user_or_seeded() {
+        let (tx, mut rx) = mpsc::unbounded_channel::<DirectRequest>();
+        let app = router(Arc::new(OpenAiState::new(tx, ApiAccess::default())));
+        let seen = tokio::spawn(async move {
+            let mut seen = Vec::new();
+            while let Some(request) = rx.recv().await {
+                seen.push(request.msg.clone());
+                let _ = request.reply_tx.send(Ok("ok".to_string()));
+                if seen.len() == 3 {
+                    break;
+                }
+            }
+            seen
+        });
+        let opening = json!([{"role": "user", "content": "hi"}]);
+        for body in [
+            json!({"messages": opening}),
+            json!({"messages": opening}),
+            json!({"messages": opening, "user": "ide"}),
+        ] {
+            app.clone().oneshot(chat(body)).await.unwrap();
+        }
+        let seen = seen.await.unwrap();
+
+        // Two clients opening with the same message get separate sessions.
+        assert_ne!(seen[0].chat_id, seen[1].chat_id);
+        assert_eq!(seen[0].metadata["seed_history"], json!([]));
+        assert_eq!(seen[2].chat_id, "ide");
+        assert!(!seen[2].metadata.contains_key("seed_history"));
+    }
+
+    // ── completions ──
+
+    #[tokio::test]
+    async fn test_chat_completion() {
+        let app = router(echo_state());
+        let response = app
+            .clone()
+            .oneshot(chat(json!({
+                "model": "anything",
+                "messages": [{"role": "user", "content": "ping"}]
+            })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = serde_json::from_str(&body_text(response).await).unwrap();
+        assert_eq!(body["object"], "chat.completion");
+        assert_eq!(body["choices"][0]["message"]["content"], "echo: ping");
+
+        let response = app
+            .clone()
+            .oneshot(chat(
+                json!({"messages": [{"role": "system", "content": "x"}]}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .oneshot(chat(json!({
+                "stream": true,
+                "messages": [{"role": "user", "content": "ping"}]
+            })))
+            .await
+            .unwrap();
+        let text = body_text(response).await;
+        assert!(text.contains("chat.completion.chunk"), "{}", text);
+        assert!(text.contains("echo: ping"));
+        assert!(text.trim_end().ends_with("data: [DONE]"));
+    }
+
+    #[tokio::test]
+    async fn test_stream_forwards_deltas() {
+        let (tx, mut rx) = mpsc::unbounded_channel::<DirectRequest>();
+        tokio::spawn(async move {
+            while let Some(request) = rx.recv().await {
+                let deltas = request.deltas.unwrap();
+                for delta in ["Hel", "lo"] {
+                    deltas.send(delta.to_string()).unwrap();
+                }
+                drop(deltas);
+                let _ = request.reply_tx.send(Ok("Hello\n\n(budget warning)".to_string()));
+            }
+        });
+        let app = router(Arc::new(OpenAiState::new(tx, ApiAccess::default())));
+        let response = app
+            .oneshot(chat(json!({
+                "stream": true,
+                "messages": [{"role": "user", "content": "hi"}]
+            })))
+            .await
+            .unwrap();
+        let text = body_text(response).await;
+        let contents: Vec<String> = text
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter_map(|data| serde_json::from_str::<Value>(data).ok())
+            .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str().map(String::from))
+            .collect();
+        assert_eq!(contents, ["Hel", "lo", "\n\n(budget warning)"]);
+        assert!(text.contains("\"finish_reason\":\"stop\""), "{}", text);
+        assert!(text.trim_end().ends_with("data: [DONE]"));
+    }
+}
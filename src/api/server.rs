@@ -0,0 +1,933 @@
+//! REST API served by the gateway.
+//!
+//! Lets shell scripts and other apps talk to the running agent:
+//!
+//! | Method | Path                            | Purpose                                       |
+//! |--------|---------------------------------|-----------------------------------------------|
+//! | GET    | `/api/status`                   | version, model, uptime, counts, heartbeat     |
+//! | GET    | `/api/subagents`                | running and recently finished subagents       |
+//! | GET    | `/api/sessions`                 | sessions on disk                              |
+//! | POST   | `/api/sessions/{key}/messages`  | send `{"content": ...}`                       |
+//! | GET    | `/api/messages/{id}`            | the reply to a sent message                   |
+//! | GET    | `/api/jobs`                     | cron jobs                                     |
+//! | POST   | `/api/jobs/{id}/run`            | run a cron job now and wait for its output    |
+//! | GET    | `/api/channels`                 | enabled channels                              |
+//! | POST   | `/api/channels/{name}/messages` | send `{"to": ..., "content": ...}` as the bot |
+//! | GET    | `/api/dead-letters`             | messages the channels failed to deliver       |
+//! | POST   | `/api/dead-letters/{id}/retry`  | resend one dead letter, or `all`              |
+//! | GET    | `/api/events`                   | system events, as server-sent events          |
+//! | GET    | `/healthz`                      | liveness                                      |
+//! | GET    | `/readyz`                       | readiness of each component                   |
+//!
+//! Sending returns the message id at once; the reply is fetched later, or
+//! waited for with `"wait": true`. Requests are authorized as described in
+//! [`crate::api::auth`]. The health endpoints are open, for container health
+//! checks; they answer 503 when something is down.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::extract::{Path, Request, State};
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use axum_server::tls_rustls::RustlsConfig;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use futures_util::stream;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::{broadcast, oneshot, Mutex};
+use tracing::info;
+
+use crate::agent::agent_loop::DirectRequest;
+use crate::agent::subagent::SubagentManager;
+use crate::api::auth::ApiAccess;
+use crate::api::tls;
+use crate::bus::events::{Attachment, InboundMessage, OutboundMessage, SystemEvent};
+use crate::bus::tap::TrafficCounter;
+use crate::channels::broadcast::GROUP_CHANNEL;
+use crate::channels::dead_letters::DeadLetterStore;
+use crate::channels::manager::ChannelManager;
+use crate::cron::service::CronService;
+use crate::heartbeat::agenda::HeartbeatState;
+use crate::providers::base::LLMProvider;
+use crate::session::manager::SessionManager;
+
+/// Finished replies kept for fetching; older ones are dropped.
+const MAX_REPLIES: usize = 256;
+
+/// Channel name of messages sent through the API.
+const API_CHANNEL: &str = "api";
+
+/// How long a provider reachability check is reused by `/readyz`.
+const PROVIDER_CHECK_TTL: Duration = Duration::from_secs(30);
+
+/// The reply to a message sent through the API.
+#[derive(Clone)]
+enum Reply {
+    Pending,
+    Done(String),
+    Failed(String),
+}
+
+impl Reply {
+    fn to_json(&self, id: &str) -> Value {
+        match self {
+            Reply::Pending => json!({"id": id, "status": "pending"}),
+            Reply::Done(reply) => json!({"id": id, "status": "done", "reply": reply}),
+            Reply::Failed(error) => json!({"id": id, "status": "failed", "error": error}),
+        }
+    }
+}
+
+/// Replies by message id, oldest first.
+#[derive(Default)]
+struct Replies {
+    by_id: HashMap<String, Reply>,
+    order: VecDeque<String>,
+}
+
+impl Replies {
+    fn insert(&mut self, id: &str, reply: Reply) {
+        if self.by_id.insert(id.to_string(), reply).is_none() {
+            self.order.push_back(id.to_string());
+        }
+        while self.order.len() > MAX_REPLIES {
+            if let Some(old) = self.order.pop_front() {
+                self.by_id.remove(&old);
+            }
+        }
+    }
+}
+
+/// What the API handlers share.
+pub struct ApiState {
+    direct_tx: UnboundedSender<DirectRequest>,
+    cron: Arc<Mutex<CronService>>,
+    workspace: PathBuf,
+    model: String,
+    channels: Vec<String>,
+    access: ApiAccess,
+    started: Instant,
+    replies: std::sync::Mutex<Replies>,
+    channel_manager: Option<Arc<ChannelManager>>,
+    provider: Option<Arc<dyn LLMProvider>>,
+    /// Where messages sent straight to a channel go.
+    outbound_tx: Option<UnboundedSender<OutboundMessage>>,
+    dead_letters: Option<Arc<DeadLetterStore>>,
+    system_events: Option<broadcast::Sender<SystemEvent>>,
+    traffic: Option<Arc<TrafficCounter>>,
+    /// State file of the heartbeat, when it runs.
+    heartbeat_state: Option<PathBuf>,
+    subagents: Option<Arc<SubagentManager>>,
+    /// Last provider check and when it ran.
+    provider_check: Mutex<Option<(Instant, Result<(), String>)>>,
+}
+
+impl ApiState {
+    pub fn new(
+        direct_tx: UnboundedSender<DirectRequest>,
+        cron: Arc<Mutex<CronService>>,
+        workspace: PathBuf,
+        model: String,
+        mut channels: Vec<String>,
+        access: ApiAccess,
+    ) -> Self {
+        channels.sort();
+        Self {
+            direct_tx,
+            cron,
+            workspace,
+            model,
+            channels,
+            access,
+            started: Instant::now(),
+            replies: std::sync::Mutex::new(Replies::default()),
+            channel_manager: None,
+            provider: None,
+            outbound_tx: None,
+            dead_letters: None,
+            system_events: None,
+            traffic: None,
+            heartbeat_state: None,
+            subagents: None,
+            provider_check: Mutex::new(None),
+        }
+    }
+
+    /// Also check the channels and the LLM provider in `/readyz`.
+    pub fn with_readiness(
+        mut self,
+        channel_manager: Arc<ChannelManager>,
+        provider: Arc<dyn LLMProvider>,
+    ) -> Self {
+        self.channel_manager = Some(channel_manager);
+        self.provider = Some(provider);
+        self
+    }
+
+    /// Allow sending messages straight to the channels, bypassing the agent.
+    pub fn with_outbound(mut self, outbound_tx: UnboundedSender<OutboundMessage>) -> Self {
+        self.outbound_tx = Some(outbound_tx);
+        self
+    }
+
+    /// Allow listing and resending undelivered messages. Resending needs
+    /// [`ApiState::with_outbound`] too.
+    pub fn with_dead_letters(mut self, dead_letters: Arc<DeadLetterStore>) -> Self {
+        self.dead_letters = Some(dead_letters);
+        self
+    }
+
+    /// Stream `events` from `/api/events`.
+    pub fn with_system_events(mut self, events: broadcast::Sender<SystemEvent>) -> Self {
+        self.system_events = Some(events);
+        self
+    }
+
+    /// Report the bus traffic counted by `traffic` in `/api/status`.
+    pub fn with_traffic(mut self, traffic: Arc<TrafficCounter>) -> Self {
+        self.traffic = Some(traffic);
+        self
+    }
+
+    /// Report the heartbeat's last run and next beat, kept in the state file
+    /// at `path`, in `/api/status`.
+    pub fn with_heartbeat(mut self, path: PathBuf) -> Self {
+        self.heartbeat_state = Some(path);
+        self
+    }
+
+    /// Report the subagents of `manager` in `/api/subagents`, and how many
+    /// run in `/api/status`.
+    pub fn with_subagents(mut self, manager: Arc<SubagentManager>) -> Self {
+        self.subagents = Some(manager);
+        self
+    }
+
+    /// Whether the provider is reachable, checked at most every
+    /// [`PROVIDER_CHECK_TTL`].
+    async fn provider_reachable(&self) -> Option<Result<(), String>> {
+        let provider = self.provider.as_ref()?;
+        let mut last = self.provider_check.lock().await;
+        if let Some((at, result)) = last.as_ref() {
+            if at.elapsed() < PROVIDER_CHECK_TTL {
+                return Some(result.clone());
+            }
+        }
+        let result = provider.check_reachable().await.map_err(|e| e.to_string());
+        *last = Some((Instant::now(), result.clone()));
+        Some(result)
+    }
+
+    fn set_reply(&self, id: &str, reply: Reply) {
+        if let Ok(mut replies) = self.replies.lock() {
+            replies.insert(id, reply);
+        }
+    }
+
+    fn reply(&self, id: &str) -> Option<Reply> {
+        self.replies.lock().ok()?.by_id.get(id).cloned()
+    }
+}
+
+/// The API routes over `state`.
+pub fn router(state: Arc<ApiState>) -> Router {
+    let router = Router::new()
+        .route("/api/status", get(status))
+        .route("/api/subagents", get(list_subagents))
+        .route("/api/sessions", get(list_sessions))
+        .route("/api/sessions/{key}/messages", post(send_message))
+        .route("/api/messages/{id}", get(get_reply))
+        .route("/api/jobs", get(list_jobs))
+        .route("/api/jobs/{id}/run", post(run_job))
+        .route("/api/channels", get(list_channels))
+        .route("/api/channels/{name}/messages", post(send_to_channel))
+        .route("/api/dead-letters", get(list_dead_letters))
+        .route("/api/dead-letters/{id}/retry", post(retry_dead_letters))
+        .route("/api/events", get(stream_events))
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz));
+    match state.access.cors_layer() {
+        Some(cors) => router.layer(cors).with_state(state),
+        None => router.with_state(state),
+    }
+}
+
+/// Serve the API on `host:port` until the task is dropped, over HTTPS
+/// when `tls` is given.
+pub async fn serve(
+    state: Arc<ApiState>,
+    host: &str,
+    port: u16,
+    tls: Option<RustlsConfig>,
+) -> anyhow::Result<()> {
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    info!("REST API listening on {}://{}:{}/api", scheme, host, port);
+    tls::serve(router(state), host, port, tls).await
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({"error": message}))).into_response()
+}
+
+// ---------------------------------------------------------------------------
+// Handlers
+// ---------------------------------------------------------------------------
+
+async fn authorize(State(state): State<Arc<ApiState>>, request: Request, next: Next) -> Response {
+    match state.access.check(&request) {
+        Some(rejected) => rejected,
+        None => next.run(request).await,
+    }
+}
+
+async fn status(State(state): State<Arc<ApiState>>) -> Json<Value> {
+    let sessions = SessionManager::new(&state.workspace).list_sessions().len();
+    let cron = state.cron.lock().await.status();
+    let mut status = json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "model": state.model,
+        "uptime_secs": state.started.elapsed().as_secs(),
+        "channels": state.channels,
+        "sessions": sessions,
+        "cron": cron,
+    });
+    if let Some(traffic) = &state.traffic {
+        status["traffic"] = traffic.to_json();
+    }
+    if let Some(path) = &state.heartbeat_state {
+        status["heartbeat"] = HeartbeatState::load(path).to_json();
+    }
+    if let Some(subagents) = &state.subagents {
+        status["subagents"] = json!({"running": subagents.get_running_count().await});
+    }
+    Json(status)
+}
+
+/// Running subagents, oldest first, and recently finished ones, most
+/// recent first, with how long they ran and how they ended.
+async fn list_subagents(State(state): State<Arc<ApiState>>) -> Response {
+    let Some(subagents) = &state.subagents else {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "subagents are not available");
+    };
+    let status = subagents.status().await;
+    let recent: Vec<Value> = status
+        .recent
+        .iter()
+        .map(|run| {
+            let mut entry = json!(run);
+            entry["durationMs"] = json!(run.duration().num_milliseconds());
+            entry
+        })
+        .collect();
+    Json(json!({"running": status.running, "recent": recent})).into_response()
+}
+
+/// Alive as long as the agent loop takes requests.
+async fn healthz(State(state): State<Arc<ApiState>>) -> Response {
+    if state.direct_tx.is_closed() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({"status": "down"})),
+        )
+            .into_response();
+    }
+    Json(json!({"status": "ok"})).into_response()
+}
+
+/// Ready when the agent loop, every channel, the provider and the cron
+/// scheduler are up.
+async fn readyz(State(state): State<Arc<ApiState>>) -> Response {
+    let mut ready = !state.direct_tx.is_closed();
+    let mut checks = json!({"agent_loop": {"ok": ready}});
+
+    if let Some(manager) = &state.channel_manager {
+        let running = manager.running().await;
+        ready &= running.values().all(|up| *up);
+        checks["channels"] = json!(running);
+    }
+
+    match state.provider_reachable().await {
+        Some(Ok(())) => checks["provider"] = json!({"ok": true}),
+        Some(Err(e)) => {
+            ready = false;
+            checks["provider"] = json!({"ok": false, "error": e});
+        }
+        None => {}
+    }
+
+    let cron = state.cron.lock().await.status();
+    let scheduler_up = cron["enabled"].as_bool().unwrap_or(false);
+    ready &= scheduler_up;
+    checks["cron"] = json!({"ok": scheduler_up, "jobs": cron["jobs"]});
+
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(json!({"ready": ready, "checks": checks}))).into_response()
+}
+
+async fn list_sessions(State(state): State<Arc<ApiState>>) -> Json<Value> {
+    Json(json!(SessionManager::new(&state.workspace).list_sessions()))
+}
+
+async fn list_jobs(State(state): State<Arc<ApiState>>) -> Json<Value> {
+    Json(json!(state.cron.lock().await.list_jobs(true)))
+}
+
+/// Run job `id` now through the gateway's job handler, delivering its
+/// output like a scheduled run, and answer with the output.
+async fn run_job(State(state): State<Arc<ApiState>>, Path(id): Path<String>) -> Response {
+    match CronService::run_job_now(&state.cron, &id).await {
+        None => error(StatusCode::NOT_FOUND, &format!("no job '{}'", id)),
+        Some(Ok(output)) => Json(json!({"id": id, "status": "done", "output": output})).into_response(),
+        Some(Err(e)) => {
+            Json(json!({"id": id, "status": "failed", "error": e.to_string()})).into_response()
+        }
+    }
+}
+
+async fn list_channels(State(state): State<Arc<ApiState>>) -> Json<Value> {
+    Json(json!(state.channels))
+}
+
+#[derive(Deserialize)]
+struct ChannelSendBody {
+    /// Chat to send to.
+    to: String,
+    content: String,
+}
+
+/// Send a message to a chat of channel `name` as the bot, without the
+/// agent. The channel delivers it in the background.
+async fn send_to_channel(
+    State(state): State<Arc<ApiState>>,
+    Path(name): Path<String>,
+    Json(body): Json<ChannelSendBody>,
+) -> Response {
+    if name != GROUP_CHANNEL && !state.channels.contains(&name) {
+        return error(
+            StatusCode::NOT_FOUND,
+            &format!("channel '{}' is not enabled", name),
+        );
+    }
+    if body.to.is_empty() || body.content.trim().is_empty() {
+        return error(StatusCode::BAD_REQUEST, "to and content are required");
+    }
+    let Some(outbound_tx) = &state.outbound_tx else {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "channels are not running");
+    };
+    let msg = OutboundMessage::new(&name, &body.to, &body.content);
+    if outbound_tx.send(msg).is_err() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "channels are not running");
+    }
+    (
+        StatusCode::ACCEPTED,
+        Json(json!({"channel": name, "to": body.to, "status": "queued"})),
+    )
+        .into_response()
+}
+
+async fn list_dead_letters(State(state): State<Arc<ApiState>>) -> Json<Value> {
+    let letters = state
+        .dead_letters
+        .as_ref()
+        .map(|store| store.list())
+        .unwrap_or_default();
+    Json(json!(letters))
+}
+
+/// Hand dead letter `id`, or all of them for `all`, back to the channels.
+async fn retry_dead_letters(
+    State(state): State<Arc<ApiState>>,
+    Path(id): Path<String>,
+) -> Response {
+    let (Some(store), Some(outbound_tx)) = (&state.dead_letters, &state.outbound_tx) else {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "channels are not running");
+    };
+    let ids = if id == "all" { Vec::new() } else { vec![id.clone()] };
+    let letters = match store.take(&ids) {
+        Ok(letters) => letters,
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    };
+    if letters.is_empty() && id != "all" {
+        return error(
+            StatusCode::NOT_FOUND,
+            &format!("no dead letter '{}'", id),
+        );
+    }
+    let retried: Vec<String> = letters.iter().map(|l| l.id.clone()).collect();
+    for letter in letters {
+        // Fails again into the store if the channel is still down.
+        if outbound_tx.send(letter.message).is_err() {
+            return error(StatusCode::SERVICE_UNAVAILABLE, "channels are not running");
+        }
+    }
+    (StatusCode::ACCEPTED, Json(json!({"retried": retried}))).into_response()
+}
+
+/// System events as they happen, each named by its type with the event as
+/// JSON data. Events published before the request are not sent.
+async fn stream_events(State(state): State<Arc<ApiState>>) -> Response {
+    let Some(events) = &state.system_events else {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "system events are not available");
+    };
+    let events = stream::unfold(events.subscribe(), |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    let sse = Event::default().event(event.kind()).json_data(&event);
+                    return Some((sse, rx));
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+#[derive(Deserialize)]
+struct SendBody {
+    content: String,
+    /// Answer with the reply instead of the message id.
+    #[serde(default)]
+    wait: bool,
+    /// Paths or URLs of files to attach.
+    #[serde(default)]
+    attachments: Vec<String>,
+}
+
+/// Send a message to the session `key` and answer with its id (and the
+/// reply, when waiting).
+async fn send_message(
+    State(state): State<Arc<ApiState>>,
+    Path(key): Path<String>,
+    Json(body): Json<SendBody>,
+) -> Response {
+    if body.content.trim().is_empty() {
+        return error(StatusCode::BAD_REQUEST, "content is empty");
+    }
+
+    let mut msg = InboundMessage::new(API_CHANNEL, API_CHANNEL, &key, &body.content);
+    msg.metadata.insert("session_key".to_string(), json!(key));
+    msg.attachments = body.attachments.iter().map(Attachment::new).collect();
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if state
+        .direct_tx
+        .send(DirectRequest { msg, reply_tx, deltas: None })
+        .is_err()
+    {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "agent loop is not running");
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    state.set_reply(&id, Reply::Pending);
+    let task = {
+        let state = state.clone();
+        let id = id.clone();
+        tokio::spawn(async move {
+            let reply = match reply_rx.await {
+                Ok(Ok(reply)) => Reply::Done(reply),
+                Ok(Err(e)) => Reply::Failed(e.to_string()),
+                Err(_) => Reply::Failed("agent loop dropped the message".to_string()),
+            };
+            state.set_reply(&id, reply.clone());
+            reply
+        })
+    };
+
+    if !body.wait {
+        return (StatusCode::ACCEPTED, Json(Reply::Pending.to_json(&id))).into_response();
+    }
+    match task.await {
+        Ok(reply) => Json(reply.to_json(&id)).into_response(),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+async fn get_reply(State(state): State<Arc<ApiState>>, Path(id): Path<String>) -> Response {
+    match state.reply(&id) {
+        Some(reply) => Json(reply.to_json(&id)).into_response(),
+        None => error(StatusCode::NOT_FOUND, "unknown message id"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::auth::Scope;
+    use axum::body::Body;
+    use axum::extract::ConnectInfo;
+    use axum::http::header;
+    use std::net::SocketAddr;
+    use tokio::sync::mpsc;
+    use tower::ServiceExt;
+
+    fn api_state(
+        token: &str,
+    ) -> (
+        Arc<ApiState>,
+        mpsc::UnboundedReceiver<DirectRequest>,
+        tempfile::TempDir,
+    ) {
+        let dir = tempfile::tempdir().unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+        let cron = Arc::new(Mutex::new(CronService::new(dir.path().join("jobs.json"))));
+        let state = ApiState::new(
+            tx,
+            cron,
+            dir.path().to_path_buf(),
+            "test-model".to_string(),
+            vec!["telegram".to_string()],
+            ApiAccess::new(vec![(token.to_string(), Scope::Send)], Vec::new()),
+        );
+        (Arc::new(state), rx, dir)
+    }
+
+    fn local(request: axum::http::request::Builder) -> axum::http::request::Builder {
+        request.extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 5000))))
+    }
+
+    async fn body_json(response: Response) -> Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    // ── auth ──
+
+    #[tokio::test]
+    async fn test_remote_requests_need_a_token() {
+        let (state, _rx, _dir) = api_state("");
+        let remote = Request::builder()
+            .uri("/api/status")
+            .extension(ConnectInfo(SocketAddr::from(([10, 0, 0, 2], 5000))))
+            .body(Body::empty())
+            .unwrap();
+        let response = router(state.clone()).oneshot(remote).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let request = local(Request::builder().uri("/api/status"))
+            .body(Body::empty())
+            .unwrap();
+        let response = router(state).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let status = body_json(response).await;
+        assert_eq!(status["model"], "test-model");
+        assert_eq!(status["channels"], json!(["telegram"]));
+
+        let (state, _rx, _dir) = api_state("s3cret");
+        let request = Request::builder()
+            .uri("/api/jobs")
+            .body(Body::empty())
+            .unwrap();
+        let response = router(state.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let request = Request::builder()
+            .uri("/api/jobs")
+            .header(header::AUTHORIZATION, "Bearer s3cret")
+            .body(Body::empty())
+            .unwrap();
+        let response = router(state).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await, json!([]));
+    }
+
+    // ── subagents ──
+
+    /// Provider whose calls never finish, so subagents stay running.
+    struct StalledProvider;
+
+    #[async_trait::async_trait]
+    impl LLMProvider for StalledProvider {
+        async fn chat(
+            &self,
+            _messages: &[Value],
+            _tools: Option<&[Value]>,
+            _model: Option<&str>,
+            _max_tokens: u32,
+            _temperature: f64,
+        ) -> anyhow::Result<crate::providers::base::LLMResponse> {
+            std::future::pending().await
+        }
+
+        fn get_default_model(&self) -> &str {
+            "stalled"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subagents() {
+        let (state, _rx, dir) = api_state("");
+        let get = |uri: &str| local(Request::builder().uri(uri)).body(Body::empty()).unwrap();
+        let response = router(state).oneshot(get("/api/subagents")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let (bus_tx, _bus_rx) = mpsc::unbounded_channel();
+        let manager = Arc::new(SubagentManager::new(
+            Arc::new(StalledProvider),
+            dir.path().to_path_buf(),
+            bus_tx,
+            "stalled".to_string(),
+            None,
+            5,
+            true,
+            Default::default(),
+        ));
+        for label in ["logs", "inbox"] {
+            manager
+                .spawn(label.to_string(), Some(label.to_string()), "cli".to_string(), "direct".to_string())
+                .await;
+        }
+        let running = manager.list_running().await;
+        let inbox = running.iter().find(|i| i.label == "inbox").unwrap();
+        manager.cancel(&inbox.id).await.unwrap();
+
+        let (state, _rx, _dir) = api_state("");
+        let state = Arc::new(Arc::into_inner(state).unwrap().with_subagents(manager));
+        let response = router(state.clone()).oneshot(get("/api/subagents")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        assert_eq!(body["running"][0]["label"], "logs");
+        assert_eq!(body["recent"][0]["label"], "inbox");
+        assert_eq!(body["recent"][0]["status"], "cancelled");
+        assert!(body["recent"][0]["durationMs"].as_i64().unwrap() >= 0);
+
+        let response = router(state).oneshot(get("/api/status")).await.unwrap();
+        assert_eq!(body_json(response).await["subagents"]["running"], 1);
+    }
+
+    // ── jobs ──
+
+    #[tokio::test]
+    async fn test_run_job() {
+        use crate::cron::types::{CronJob, CronSchedule};
+
+        let (state, _rx, _dir) = api_state("");
+        let job = {
+            let mut cron = state.cron.lock().await;
+            cron.set_on_job(Arc::new(|job: CronJob| {
+                Box::pin(async move { Ok(format!("ran {}", job.payload.message)) })
+            }));
+            let schedule = CronSchedule {
+                kind: "every".to_string(),
+                every_ms: Some(60_000),
+                ..CronSchedule::default()
+            };
+            cron.add_job("daily", schedule, "report", false, None, None, false)
+        };
+        let run = |id: &str| {
+            local(Request::builder().method("POST").uri(format!("/api/jobs/{}/run", id)))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = router(state.clone()).oneshot(run(&job.id)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        assert_eq!(body["status"], "done");
+        assert_eq!(body["output"], "ran report");
+        let cron = state.cron.lock().await;
+        assert_eq!(cron.get_job(&job.id).unwrap().state.history.len(), 1);
+        drop(cron);
+
+        let response = router(state).oneshot(run("nope")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    // ── messages ──
+
+    #[tokio::test]
+    async fn test_send_message_and_fetch_reply() {
+        let (state, mut rx, _dir) = api_state("");
+        let send = local(
+            Request::builder()
+                .method("POST")
+                .uri("/api/sessions/scripts/messages"),
+        )
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            r#"{"content": "hello", "attachments": ["https://example.com/a.png"]}"#,
+        ))
+        .unwrap();
+        let response = router(state.clone()).oneshot(send).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let sent = body_json(response).await;
+        assert_eq!(sent["status"], "pending");
+        let id = sent["id"].as_str().unwrap().to_string();
+
+        let request = rx.recv().await.unwrap();
+        assert_eq!(request.msg.channel, "api");
+        assert_eq!(request.msg.content, "hello");
+        assert_eq!(request.msg.metadata["session_key"], "scripts");
+        assert_eq!(request.msg.attachments[0].mime, "image/png");
+        request.reply_tx.send(Ok("hi there".to_string())).unwrap();
+        tokio::task::yield_now().await;
+
+        let fetch = |id: &str| {
+            local(Request::builder().uri(format!("/api/messages/{}", id)))
+                .body(Body::empty())
+                .unwrap()
+        };
+        let mut reply = Value::Null;
+        for _ in 0..50 {
+            reply = body_json(router(state.clone()).oneshot(fetch(&id)).await.unwrap()).await;
+            if reply["status"] != "pending" {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(reply["status"], "done");
+        assert_eq!(reply["reply"], "hi there");
+
+        let response = router(state).oneshot(fetch("nope")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_send_to_channel() {
+        let (state, _rx, _dir) = api_state("");
+        let (out_tx, mut out_rx) = mpsc::unbounded_channel();
+        let state = Arc::new(Arc::into_inner(state).unwrap().with_outbound(out_tx));
+        let send = |channel: &str| {
+            local(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("/api/channels/{}/messages", channel)),
+            )
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"to": "42", "content": "ping"}"#))
+            .unwrap()
+        };
+
+        let response = router(state.clone()).oneshot(send("telegram")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let msg = out_rx.recv().await.unwrap();
+        assert_eq!(
+            (msg.channel.as_str(), msg.chat_id.as_str(), msg.content.as_str()),
+            ("telegram", "42", "ping")
+        );
+
+        let response = router(state).oneshot(send("slack")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_retry_dead_letters() {
+        let (state, _rx, dir) = api_state("");
+        let store = Arc::new(DeadLetterStore::new(dir.path().join("dead_letters.json")));
+        let id = store
+            .push(OutboundMessage::new("telegram", "42", "late"), 3, "timeout")
+            .unwrap();
+        let (out_tx, mut out_rx) = mpsc::unbounded_channel();
+        let state = Arc::new(
+            Arc::into_inner(state)
+                .unwrap()
+                .with_outbound(out_tx)
+                .with_dead_letters(store.clone()),
+        );
+        let retry = |id: &str| {
+            local(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("/api/dead-letters/{}/retry", id)),
+            )
+            .body(Body::empty())
+            .unwrap()
+        };
+
+        let listed = local(Request::builder().uri("/api/dead-letters"))
+            .body(Body::empty())
+            .unwrap();
+        let listed = body_json(router(state.clone()).oneshot(listed).await.unwrap()).await;
+        assert_eq!(listed[0]["id"], id.as_str());
+
+        let response = router(state.clone()).oneshot(retry(&id)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(body_json(response).await["retried"][0], id.as_str());
+        assert_eq!(out_rx.recv().await.unwrap().content, "late");
+        assert!(store.list().is_empty());
+
+        let response = router(state).oneshot(retry(&id)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_stream_events() {
+        use futures_util::StreamExt;
+
+        let (state, _rx, _dir) = api_state("");
+        let events = SystemEvent::channel();
+        let state = Arc::new(
+            Arc::into_inner(state)
+                .unwrap()
+                .with_system_events(events.clone()),
+        );
+        let request = local(Request::builder().uri("/api/events"))
+            .body(Body::empty())
+            .unwrap();
+        let response = router(state).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        events
+            .send(SystemEvent::CronFired {
+                job_id: "j1".to_string(),
+                name: "backup".to_string(),
+            })
+            .unwrap();
+        let mut body = response.into_body().into_data_stream();
+        let chunk = body.next().await.unwrap().unwrap();
+        let text = String::from_utf8(chunk.to_vec()).unwrap();
+        assert!(text.starts_with("event: cron_fired\n"), "{}", text);
+        assert!(text.contains(r#""name":"backup""#), "{}", text);
+    }
+
+    // ── health ──
+
+    #[tokio::test]
+    async fn test_health_endpoints_are_open() {
+        let (state, _rx, _dir) = api_state("s3cret");
+        let get = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+        let response = router(state.clone())
+            .oneshot(get("/healthz"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // The cron scheduler has not started yet.
+        let response = router(state.clone()).oneshot(get("/readyz")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let readiness = body_json(response).await;
+        assert_eq!(readiness["checks"]["cron"]["ok"], false);
+        assert_eq!(readiness["checks"]["agent_loop"]["ok"], true);
+
+        state.cron.lock().await.start().await;
+        let response = router(state.clone()).oneshot(get("/readyz")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        state.cron.lock().await.stop();
+    }
+
+    #[test]
+    fn test_replies_are_capped() {
+        let mut replies = Replies::default();
+        for i in 0..MAX_REPLIES + 10 {
+            replies.insert(&i.to_string(), Reply::Pending);
+        }
+        assert_eq!(replies.by_id.len(), MAX_REPLIES);
+        assert!(!replies.by_id.contains_key("0"));
+    }
+}
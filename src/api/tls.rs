@@ -0,0 +1,124 @@
+//! HTTPS for the API servers.
+//!
+//! With `gateway.tls.cert` and `gateway.tls.key` set, the servers accept
+//! TLS connections only. Certificates are read from PEM files at startup;
+//! renew them with certbot or similar and restart the gateway.
+
+use std::net::SocketAddr;
+
+use anyhow::{bail, Context, Result};
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+
+use crate::config::schema::TlsConfig;
+
+/// The rustls config for `tls`, or `None` when TLS is not configured.
+pub async fn load(tls: &TlsConfig) -> Result<Option<RustlsConfig>> {
+    if !tls.is_enabled() {
+        return Ok(None);
+    }
+    if tls.cert.is_empty() || tls.key.is_empty() {
+        bail!("gateway.tls needs both cert and key");
+    }
+    let (cert, key) = (tls.cert_path(), tls.key_path());
+    let config = RustlsConfig::from_pem_file(&cert, &key)
+        .await
+        .with_context(|| {
+            format!(
+                "cannot load TLS certificate {} with key {}",
+                cert.display(),
+                key.display()
+            )
+        })?;
+    Ok(Some(config))
+}
+
+/// `http` or `https`, for printing URLs.
+pub fn scheme(tls: &TlsConfig) -> &'static str {
+    if tls.is_enabled() {
+        "https"
+    } else {
+        "http"
+    }
+}
+
+/// Serve `app` on `host:port`, over TLS when `tls` is given.
+pub async fn serve(app: Router, host: &str, port: u16, tls: Option<RustlsConfig>) -> Result<()> {
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    let Some(tls) = tls else {
+        let listener = tokio::net::TcpListener::bind((host, port)).await?;
+        axum::serve(listener, app).await?;
+        return Ok(());
+    };
+    let addr = tokio::net::lookup_host((host, port))
+        .await?
+        .next()
+        .with_context(|| format!("cannot resolve {}", host))?;
+    axum_server::bind_rustls(addr, tls).serve(app).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_load() {
+        assert!(load(&TlsConfig::default()).await.unwrap().is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        let half = TlsConfig {
+            cert: dir.path().join("cert.pem").to_string_lossy().into_owned(),
+            key: String::new(),
+        };
+        assert!(load(&half).await.is_err());
+
+        std::fs::write(dir.path().join("cert.pem"), "not a certificate").unwrap();
+        std::fs::write(dir.path().join("key.pem"), "not a key").unwrap();
+        let bad = TlsConfig {
+            key: dir.path().join("key.pem").to_string_lossy().into_owned(),
+            ..half
+        };
+        let err = load(&bad).await.unwrap_err().to_string();
+        assert!(err.contains("cannot load TLS certificate"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_serve_https() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let tls = TlsConfig {
+            cert: dir.path().join("cert.pem").to_string_lossy().into_owned(),
+            key: dir.path().join("key.pem").to_string_lossy().into_owned(),
+        };
+        std::fs::write(&tls.cert, cert.cert.pem()).unwrap();
+        std::fs::write(&tls.key, cert.signing_key.serialize_pem()).unwrap();
+
+        // Find a free port, then serve on it.
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let app = Router::new().route("/ping", axum::routing::get(|| async { "pong" }));
+        let config = load(&tls).await.unwrap();
+        tokio::spawn(async move { serve(app, "127.0.0.1", port, config).await });
+
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .no_proxy()
+            .build()
+            .unwrap();
+        let url = format!("https://127.0.0.1:{}/ping", port);
+        let mut body = None;
+        for _ in 0..50 {
+            if let Ok(response) = client.get(&url).send().await {
+                body = Some(response.text().await.unwrap());
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(body.as_deref(), Some("pong"));
+    }
+}
@@ -0,0 +1,342 @@
+//! Outbound webhooks.
+//!
+//! Each URL under `webhooks` in the config receives a JSON POST for the
+//! events it subscribes to:
+//!
+//! ```json
+//! {"event": "cron.failed", "timestamp": "2026-01-01T09:00:00+01:00", "data": {...}}
+//! ```
+//!
+//! Events are `agent.replied`, `cron.failed`, `channel.disconnected`,
+//! `budget.exceeded` and `alert.triggered`. With a `secret`, the body is signed with HMAC-SHA256
+//! in `X-Nanoclaw-Signature: sha256=<hex>`. Failed deliveries are retried
+//! with exponential backoff; client errors other than 429 are not.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+use crate::audit::{AuditEntry, AuditKind, AuditLog, SYSTEM_SESSION};
+use crate::bus::events::{AgentEvent, SystemEvent};
+use crate::config::schema::WebhookConfig;
+use crate::cron::service::FailureCallback;
+use crate::cron::types::CronJob;
+use crate::utils::http::shared_client;
+
+pub const AGENT_REPLIED: &str = "agent.replied";
+pub const CRON_FAILED: &str = "cron.failed";
+pub const CHANNEL_DISCONNECTED: &str = "channel.disconnected";
+pub const BUDGET_EXCEEDED: &str = "budget.exceeded";
+pub const ALERT_TRIGGERED: &str = "alert.triggered";
+
+/// Header carrying the body signature.
+const SIGNATURE_HEADER: &str = "X-Nanoclaw-Signature";
+
+/// Header carrying the event name.
+const EVENT_HEADER: &str = "X-Nanoclaw-Event";
+
+/// Delay before the first retry; doubled for each further one.
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Timeout of one delivery attempt.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sends events to the configured webhooks.
+pub struct Webhooks {
+    hooks: Vec<WebhookConfig>,
+    client: Client,
+    retry_delay: Duration,
+    audit: Option<AuditLog>,
+}
+
+impl Webhooks {
+    pub fn new(hooks: Vec<WebhookConfig>) -> Self {
+        Self {
+            hooks: hooks.into_iter().filter(|h| !h.url.is_empty()).collect(),
+            client: shared_client(),
+            retry_delay: FIRST_RETRY_DELAY,
+            audit: None,
+        }
+    }
+
+    /// Record each delivery, or the failure to deliver, in `log`.
+    pub fn with_audit_log(mut self, log: AuditLog) -> Self {
+        self.audit = Some(log);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Send `event` to every webhook subscribed to it, in the background.
+    pub fn emit(self: &Arc<Self>, event: &str, data: Value) {
+        let body = json!({
+            "event": event,
+            "timestamp": chrono::Local::now().to_rfc3339(),
+            "data": data,
+        })
+        .to_string();
+        for (i, hook) in self.hooks.iter().enumerate() {
+            if !hook.events.is_empty() && !hook.events.iter().any(|e| e == event) {
+                continue;
+            }
+            let this = self.clone();
+            let event = event.to_string();
+            let body = body.clone();
+            tokio::spawn(async move {
+                let hook = &this.hooks[i];
+                let delivered = this._deliver(hook, &event, body).await;
+                if let Some(audit) = &this.audit {
+                    let action = format!("POST {}", event);
+                    audit.record(&AuditEntry::new(
+                        SYSTEM_SESSION,
+                        AuditKind::Http,
+                        &action,
+                        &hook.url,
+                        delivered,
+                    ));
+                }
+            });
+        }
+    }
+
+    /// POST `body` to `hook`, retrying on failure. Returns whether it was
+    /// delivered.
+    async fn _deliver(&self, hook: &WebhookConfig, event: &str, body: String) -> bool {
+        let mut delay = self.retry_delay;
+        for attempt in 0..=hook.max_retries {
+            if attempt > 0 {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            let mut request = self
+                .client
+                .post(&hook.url)
+                .timeout(DELIVERY_TIMEOUT)
+                .header("Content-Type", "application/json")
+                .header(EVENT_HEADER, event)
+                .body(body.clone());
+            if !hook.secret.is_empty() {
+                request = request.header(SIGNATURE_HEADER, sign(&hook.secret, &body));
+            }
+            match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    debug!("Webhook {} delivered to {}", event, hook.url);
+                    return true;
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    warn!("Webhook {} to {} answered {}", event, hook.url, status);
+                    if status.is_client_error() && status.as_u16() != 429 {
+                        return false;
+                    }
+                }
+                Err(e) => warn!("Webhook {} to {} failed: {}", event, hook.url, e),
+            }
+        }
+        false
+    }
+
+    /// Forward agent events: finished turns as `agent.replied`, budget
+    /// warnings as `budget.exceeded`.
+    pub fn forward_agent_events(
+        self: Arc<Self>,
+        mut events: broadcast::Receiver<AgentEvent>,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            // Where each running turn came from, by turn ID.
+            let mut chats: HashMap<String, (String, String)> = HashMap::new();
+            loop {
+                let event = match events.recv().await {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("Webhooks missed {} agent events", n);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                match event {
+                    AgentEvent::TurnStarted {
+                        turn_id,
+                        channel,
+                        chat_id,
+                        ..
+                    } => {
+                        chats.insert(turn_id, (channel, chat_id));
+                    }
+                    AgentEvent::TurnCompleted {
+                        turn_id,
+                        session_key,
+                        iterations,
+                        duration_ms,
+                        failed,
+                    } => {
+                        let (channel, chat_id) = chats.remove(&turn_id).unwrap_or_default();
+                        if !failed {
+                            self.emit(
+                                AGENT_REPLIED,
+                                json!({
+                                    "session_key": session_key,
+                                    "channel": channel,
+                                    "chat_id": chat_id,
+                                    "iterations": iterations,
+                                    "duration_ms": duration_ms,
+                                }),
+                            );
+                        }
+                    }
+                    AgentEvent::BudgetExceeded { message, .. } => {
+                        self.emit(BUDGET_EXCEEDED, json!({"message": message}));
+                    }
+                    _ => {}
+                }
+            }
+        })
+    }
+
+    /// Callback for the cron service that reports failed runs.
+    pub fn cron_failure_callback(self: &Arc<Self>) -> FailureCallback {
+        let this = self.clone();
+        Arc::new(move |job: &CronJob, error: &str| {
+            this.emit(
+                CRON_FAILED,
+                json!({"job_id": job.id, "name": job.name, "error": error}),
+            );
+        })
+    }
+
+    /// Forward channel disconnections as `channel.disconnected` and alerts
+    /// as `alert.triggered`.
+    pub fn forward_system_events(
+        self: Arc<Self>,
+        mut events: broadcast::Receiver<SystemEvent>,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(SystemEvent::ChannelDisconnected { channel }) => {
+                        self.emit(CHANNEL_DISCONNECTED, json!({"channel": channel}));
+                    }
+                    Ok(SystemEvent::Alert { alert, message }) => {
+                        self.emit(ALERT_TRIGGERED, json!({"alert": alert, "message": message}));
+                    }
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("Webhooks missed {} system events", n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+}
+
+/// `sha256=<hex>` HMAC of `body` under `secret`.
+pub fn sign(secret: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231 test case 2.
+        assert_eq!(
+            sign("Jefe", "what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    /// Requests received by the test endpoint.
+    type Received = mpsc::UnboundedReceiver<(HeaderMap, String)>;
+
+    /// Test endpoint state: where to record requests, and how many to fail
+    /// first.
+    #[derive(Clone)]
+    struct Endpoint {
+        tx: mpsc::UnboundedSender<(HeaderMap, String)>,
+        failures: Arc<std::sync::Mutex<u32>>,
+    }
+
+    async fn receive(
+        State(endpoint): State<Endpoint>,
+        headers: HeaderMap,
+        body: String,
+    ) -> StatusCode {
+        let mut failures = endpoint.failures.lock().unwrap();
+        if *failures > 0 {
+            *failures -= 1;
+            return StatusCode::SERVICE_UNAVAILABLE;
+        }
+        let _ = endpoint.tx.send((headers, body));
+        StatusCode::OK
+    }
+
+    /// A local endpoint that fails `failures` times, then records requests.
+    async fn endpoint(failures: u32) -> (String, Received) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let state = Endpoint {
+            tx,
+            failures: Arc::new(std::sync::Mutex::new(failures)),
+        };
+        let app = Router::new()
+            .route("/hook", post(receive))
+            .with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, rx)
+    }
+
+    #[tokio::test]
+    async fn test_deliver_signs_and_retries() {
+        let (url, mut rx) = endpoint(1).await;
+        let mut webhooks = Webhooks::new(vec![
+            WebhookConfig {
+                url: url.clone(),
+                secret: "s3cret".to_string(),
+                events: vec![CRON_FAILED.to_string()],
+                ..Default::default()
+            },
+            WebhookConfig {
+                url: url.clone(),
+                events: vec![AGENT_REPLIED.to_string()],
+                ..Default::default()
+            },
+        ]);
+        webhooks.retry_delay = Duration::from_millis(10);
+        let webhooks = Arc::new(webhooks);
+
+        webhooks.emit(CRON_FAILED, json!({"name": "backup"}));
+        let (headers, body) = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(headers[EVENT_HEADER], CRON_FAILED);
+        assert_eq!(headers[SIGNATURE_HEADER], sign("s3cret", &body).as_str());
+        let payload: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(payload["event"], CRON_FAILED);
+        assert_eq!(payload["data"]["name"], "backup");
+        // The second hook is not subscribed to cron failures.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,419 @@
+//! Audit log of side effects and `nanoclaw audit`.
+//!
+//! Everything the agent does outside its own head is appended to
+//! `~/.nanoclaw/audit.jsonl`, separate from the debug logs and never
+//! rotated: files written (`write_file`, `edit_file`, downloads), commands
+//! run (`exec` and skill scripts, by subagents too), messages sent by the
+//! channels and webhook POSTs. Each entry has the time, the session it came
+//! from, what was done, to what, and whether it worked.
+
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::agent::hooks::{Hook, HookContext};
+use crate::config::loader::get_data_dir;
+use crate::providers::base::ToolCallRequest;
+
+/// Tools without side effects outside nanoclaw's own state.
+const QUIET_TOOLS: &[&str] = &[
+    "read_file",
+    "list_dir",
+    "web_search",
+    "crawl",
+    "message",
+    "spawn",
+    "subagents",
+    "cron",
+    "remind_me",
+    "checklist",
+];
+
+/// Characters kept of a command or other target.
+const MAX_TARGET_CHARS: usize = 300;
+
+/// Session of side effects not caused by a conversation.
+pub const SYSTEM_SESSION: &str = "system";
+
+/// The kind of a side effect.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditKind {
+    File,
+    Command,
+    Message,
+    Http,
+}
+
+impl AuditKind {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "file" => Some(Self::File),
+            "command" => Some(Self::Command),
+            "message" => Some(Self::Message),
+            "http" => Some(Self::Http),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::File => "file",
+            Self::Command => "command",
+            Self::Message => "message",
+            Self::Http => "http",
+        }
+    }
+}
+
+/// One side effect.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Local>,
+    pub session: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turn: Option<String>,
+    pub kind: AuditKind,
+    /// What was done: the tool, `send`, or `POST` and the webhook event.
+    pub action: String,
+    /// What it was done to: a path, a command, a chat or a URL.
+    pub target: String,
+    pub ok: bool,
+}
+
+impl AuditEntry {
+    pub fn new(session: &str, kind: AuditKind, action: &str, target: &str, ok: bool) -> Self {
+        let mut target = target.replace('\n', " ");
+        if let Some((end, _)) = target.char_indices().nth(MAX_TARGET_CHARS) {
+            target.truncate(end);
+            target.push('…');
+        }
+        Self {
+            timestamp: Local::now(),
+            session: session.to_string(),
+            turn: None,
+            kind,
+            action: action.to_string(),
+            target,
+            ok,
+        }
+    }
+
+    pub fn with_turn(mut self, turn: Option<&str>) -> Self {
+        self.turn = turn.map(str::to_string);
+        self
+    }
+}
+
+/// Append-only file of [`AuditEntry`] lines.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// The log at `~/.nanoclaw/audit.jsonl`.
+    pub fn open() -> Self {
+        Self::new(audit_path())
+    }
+
+    /// Append `entry`. Failures are logged; side effects are not held up.
+    pub fn record(&self, entry: &AuditEntry) {
+        let line = match serde_json::to_string(entry) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Failed to serialize audit entry: {}", e);
+                return;
+            }
+        };
+        if let Some(parent) = self.path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        // One write per line, so appends from several writers stay whole.
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut f| f.write_all(format!("{}\n", line).as_bytes()));
+        if let Err(e) = result {
+            warn!("Failed to write audit log {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Path of the audit log.
+pub fn audit_path() -> PathBuf {
+    get_data_dir().join("audit.jsonl")
+}
+
+/// The entry for a tool call in `session`, if it had a side effect.
+pub fn tool_entry(
+    session: &str,
+    name: &str,
+    arguments: &HashMap<String, Value>,
+    result: &str,
+) -> Option<AuditEntry> {
+    let (kind, target) = _side_effect(name, arguments, result)?;
+    Some(AuditEntry::new(
+        session,
+        kind,
+        name,
+        &target,
+        !_failed(result),
+    ))
+}
+
+fn _side_effect(
+    name: &str,
+    arguments: &HashMap<String, Value>,
+    result: &str,
+) -> Option<(AuditKind, String)> {
+    let arg = |key: &str| {
+        arguments
+            .get(key)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+    };
+    match name {
+        "write_file" | "edit_file" => Some((AuditKind::File, arg("path").to_string())),
+        "exec" => Some((AuditKind::Command, arg("command").to_string())),
+        "web_fetch" => {
+            let saved: Value = serde_json::from_str(result).ok()?;
+            Some((AuditKind::File, saved["savedTo"].as_str()?.to_string()))
+        }
+        name if QUIET_TOOLS.contains(&name) => None,
+        // Skill scripts run a command built from their arguments.
+        _ => Some((
+            AuditKind::Command,
+            serde_json::to_string(arguments).unwrap_or_default(),
+        )),
+    }
+}
+
+/// Whether a tool result reports a failure.
+fn _failed(result: &str) -> bool {
+    result.starts_with("Error")
+        || serde_json::from_str::<Value>(result).is_ok_and(|v| v.get("error").is_some())
+}
+
+/// Records the side effects of tool calls.
+pub struct AuditHook {
+    log: Arc<AuditLog>,
+}
+
+impl AuditHook {
+    pub fn new(log: Arc<AuditLog>) -> Self {
+        Self { log }
+    }
+}
+
+#[async_trait]
+impl Hook for AuditHook {
+    fn name(&self) -> &str {
+        "audit"
+    }
+
+    async fn after_tool_call(
+        &self,
+        ctx: &HookContext,
+        call: &ToolCallRequest,
+        result: &mut String,
+    ) {
+        if let Some(entry) = tool_entry(&ctx.session_key, &call.name, &call.arguments, result) {
+            self.log.record(&entry.with_turn(Some(&ctx.turn_id)));
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Reading
+// ---------------------------------------------------------------------------
+
+/// Which audit entries to show.
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    pub since: Option<DateTime<Local>>,
+    /// Session key, matching its profile sessions (`key:profile`) too.
+    pub session: Option<String>,
+    pub kind: Option<AuditKind>,
+}
+
+impl AuditFilter {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        if self.since.is_some_and(|since| entry.timestamp < since) {
+            return false;
+        }
+        if self.kind.is_some_and(|kind| entry.kind != kind) {
+            return false;
+        }
+        if let Some(session) = &self.session {
+            let profile_session = entry
+                .session
+                .strip_prefix(session.as_str())
+                .is_some_and(|rest| rest.starts_with(':'));
+            if entry.session != *session && !profile_session {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// The last `count` entries in the log at `path` matching `filter`, oldest
+/// first. Unreadable lines are skipped.
+pub fn read_entries(path: &Path, filter: &AuditFilter, count: usize) -> Vec<AuditEntry> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<AuditEntry> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .filter(|e| filter.matches(e))
+        .collect();
+    let skip = entries.len().saturating_sub(count);
+    entries.split_off(skip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx() -> HookContext {
+        HookContext {
+            turn_id: "t1".to_string(),
+            channel: "telegram".to_string(),
+            chat_id: "42".to_string(),
+            session_key: "telegram:42".to_string(),
+            model: "m".to_string(),
+            iteration: 0,
+        }
+    }
+
+    fn call(name: &str, arguments: Value) -> ToolCallRequest {
+        ToolCallRequest {
+            id: "c1".to_string(),
+            name: name.to_string(),
+            arguments: serde_json::from_value(arguments).unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hook_records_side_effects_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let hook = AuditHook::new(Arc::new(AuditLog::new(path.clone())));
+        let calls = [
+            (
+                call("read_file", serde_json::json!({"path": "a.txt"})),
+                "text",
+            ),
+            (
+                call("write_file", serde_json::json!({"path": "a.txt"})),
+                "Wrote 4 bytes",
+            ),
+            (
+                call("exec", serde_json::json!({"command": "rm -rf /"})),
+                "Error: blocked",
+            ),
+            (
+                call(
+                    "web_fetch",
+                    serde_json::json!({"url": "https://x.org/a.pdf"}),
+                ),
+                r#"{"savedTo": "downloads/a.pdf", "bytes": 3}"#,
+            ),
+            (
+                call("web_fetch", serde_json::json!({"url": "https://x.org"})),
+                r#"{"text": "hi"}"#,
+            ),
+            (
+                call("weather", serde_json::json!({"city": "Rome"})),
+                "sunny",
+            ),
+        ];
+        for (call, result) in calls {
+            hook.after_tool_call(&ctx(), &call, &mut result.to_string())
+                .await;
+        }
+
+        let entries = read_entries(&path, &AuditFilter::default(), 10);
+        let summary: Vec<(AuditKind, &str, &str, bool)> = entries
+            .iter()
+            .map(|e| (e.kind, e.action.as_str(), e.target.as_str(), e.ok))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (AuditKind::File, "write_file", "a.txt", true),
+                (AuditKind::Command, "exec", "rm -rf /", false),
+                (AuditKind::File, "web_fetch", "downloads/a.pdf", true),
+                (AuditKind::Command, "weather", r#"{"city":"Rome"}"#, true),
+            ]
+        );
+        assert_eq!(entries[0].session, "telegram:42");
+        assert_eq!(entries[0].turn.as_deref(), Some("t1"));
+    }
+
+    #[test]
+    fn test_read_entries_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(dir.path().join("audit.jsonl"));
+        let mut old = AuditEntry::new("cli:direct", AuditKind::Command, "exec", "ls", true);
+        old.timestamp -= chrono::Duration::days(10);
+        log.record(&old);
+        log.record(&AuditEntry::new(
+            "telegram:42:coder",
+            AuditKind::Message,
+            "send",
+            "telegram:42",
+            true,
+        ));
+        log.record(&AuditEntry::new(
+            SYSTEM_SESSION,
+            AuditKind::Http,
+            "POST",
+            "https://x",
+            false,
+        ));
+        log.record(&AuditEntry::new(
+            "telegram:420",
+            AuditKind::Message,
+            "send",
+            "telegram:420",
+            true,
+        ));
+
+        let path = dir.path().join("audit.jsonl");
+        let by_session = AuditFilter {
+            session: Some("telegram:42".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(read_entries(&path, &by_session, 10).len(), 1);
+        let recent = AuditFilter {
+            since: Some(Local::now() - chrono::Duration::days(1)),
+            kind: Some(AuditKind::Message),
+            ..Default::default()
+        };
+        let entries = read_entries(&path, &recent, 1);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].session, "telegram:420");
+        assert_eq!(read_entries(&path, &AuditFilter::default(), 10).len(), 4);
+
+        let long = AuditEntry::new("s", AuditKind::Command, "exec", &"x\n".repeat(400), true);
+        assert_eq!(long.target.chars().count(), MAX_TARGET_CHARS + 1);
+        assert!(!long.target.contains('\n'));
+    }
+}
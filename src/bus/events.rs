@@ -5,6 +5,126 @@ use std::collections::HashMap;
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use tokio::sync::broadcast;
+
+/// How urgently a message is handled when several are waiting for the
+/// agent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    /// Background work such as heartbeat ticks and cron jobs.
+    Low,
+    /// Messages from people.
+    #[default]
+    Normal,
+}
+
+/// What kind of file an [`Attachment`] is, going by its MIME type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachmentKind {
+    Image,
+    Audio,
+    Document,
+}
+
+/// A file carried by a message: a local path or a URL.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    /// Local file path or `http(s)` URL.
+    pub source: String,
+    /// MIME type, e.g. `"image/jpeg"`.
+    pub mime: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+    /// Size in bytes, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+impl Attachment {
+    /// An attachment for `source`, with the MIME type guessed from its
+    /// extension and the size read from the file if it is local.
+    pub fn new(source: impl Into<String>) -> Self {
+        let source = source.into();
+        let size = if is_url(&source) {
+            None
+        } else {
+            std::fs::metadata(&source).ok().map(|m| m.len())
+        };
+        Self {
+            mime: guess_mime(&source),
+            source,
+            caption: None,
+            size,
+        }
+    }
+
+    pub fn with_mime(mut self, mime: impl Into<String>) -> Self {
+        self.mime = mime.into();
+        self
+    }
+
+    pub fn kind(&self) -> AttachmentKind {
+        if self.mime.starts_with("image/") {
+            AttachmentKind::Image
+        } else if self.mime.starts_with("audio/") {
+            AttachmentKind::Audio
+        } else {
+            AttachmentKind::Document
+        }
+    }
+
+    /// Whether the file is at a URL rather than on this machine.
+    pub fn is_url(&self) -> bool {
+        is_url(&self.source)
+    }
+
+    /// A one-line note for the model, e.g. `[image: /path/a.jpg "caption"]`.
+    pub fn describe(&self) -> String {
+        let kind = match self.kind() {
+            AttachmentKind::Image => "image",
+            AttachmentKind::Audio => "audio",
+            AttachmentKind::Document => "file",
+        };
+        match &self.caption {
+            Some(caption) => format!("[{}: {} \"{}\"]", kind, self.source, caption),
+            None => format!("[{}: {}]", kind, self.source),
+        }
+    }
+}
+
+fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// MIME type for a file name or URL, from its extension.
+pub fn guess_mime(path: &str) -> String {
+    let lower = path.to_lowercase();
+    let ext = lower
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default()
+        .rsplit_once('.')
+        .map(|(_, ext)| ext)
+        .unwrap_or_default();
+    match ext {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ogg" | "oga" | "opus" => "audio/ogg",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "pdf" => "application/pdf",
+        "txt" | "md" => "text/plain",
+        "csv" => "text/csv",
+        _ => "application/octet-stream",
+    }
+    .to_string()
+}
 
 /// Message received from a chat channel.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,12 +140,15 @@ pub struct InboundMessage {
     /// When the message was received.
     #[serde(default = "now")]
     pub timestamp: DateTime<Local>,
-    /// Media attachment URLs.
-    #[serde(default)]
-    pub media: Vec<String>,
+    /// Files that came with the message.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
     /// Channel-specific metadata.
     #[serde(default)]
     pub metadata: HashMap<String, Value>,
+    /// Waiting messages of higher priority are handled first.
+    #[serde(default)]
+    pub priority: Priority,
 }
 
 fn now() -> DateTime<Local> {
@@ -41,8 +164,9 @@ impl InboundMessage {
             chat_id: chat_id.into(),
             content: content.into(),
             timestamp: Local::now(),
-            media: Vec::new(),
+            attachments: Vec::new(),
             metadata: HashMap::new(),
+            priority: Priority::Normal,
         }
     }
 
@@ -50,6 +174,12 @@ impl InboundMessage {
     pub fn session_key(&self) -> String {
         format!("{}:{}", self.channel, self.chat_id)
     }
+
+    /// ID to use for the agent turn handling this message, if the sender
+    /// chose one.
+    pub fn turn_id(&self) -> Option<&str> {
+        self.metadata.get("turn_id").and_then(|v| v.as_str())
+    }
 }
 
 /// Message to send to a chat channel.
@@ -64,12 +194,16 @@ pub struct OutboundMessage {
     /// Optional message ID to reply to.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub reply_to: Option<String>,
-    /// Media attachment URLs.
-    #[serde(default)]
-    pub media: Vec<String>,
+    /// Files to send with the message.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
     /// Channel-specific metadata.
     #[serde(default)]
     pub metadata: HashMap<String, Value>,
+    /// Further recipients, as `channel:chat_id` or `group:<name>`; see
+    /// [`crate::channels::broadcast`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<String>,
 }
 
 impl OutboundMessage {
@@ -80,8 +214,178 @@ impl OutboundMessage {
             chat_id: chat_id.into(),
             content: content.into(),
             reply_to: None,
-            media: Vec::new(),
+            attachments: Vec::new(),
             metadata: HashMap::new(),
+            targets: Vec::new(),
+        }
+    }
+
+    /// ID tying together the partial updates and final text of a streamed
+    /// reply, if this message is part of one.
+    pub fn stream_id(&self) -> Option<&str> {
+        self.metadata.get("stream_id").and_then(|v| v.as_str())
+    }
+
+    /// The text followed by the URL of each linked attachment, for channels
+    /// that cannot upload files.
+    pub fn content_with_links(&self) -> String {
+        let mut text = self.content.clone();
+        for attachment in self.attachments.iter().filter(|a| a.is_url()) {
+            text.push('\n');
+            if let Some(caption) = &attachment.caption {
+                text.push_str(&format!("{}: ", caption));
+            }
+            text.push_str(&attachment.source);
+        }
+        text
+    }
+
+    /// ID of the agent turn that produced this message, if any.
+    pub fn turn_id(&self) -> Option<&str> {
+        self.metadata.get("turn_id").and_then(|v| v.as_str())
+    }
+
+    /// Whether this is an in-progress update of a streamed reply (the final
+    /// message of a stream is not partial).
+    pub fn is_partial(&self) -> bool {
+        self.metadata
+            .get("partial")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+    }
+}
+
+/// Progress of an agent turn, published on the agent loop's event channel
+/// for observers such as channels, UIs and metrics.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentEvent {
+    /// The agent started working on a message.
+    TurnStarted {
+        turn_id: String,
+        session_key: String,
+        channel: String,
+        chat_id: String,
+    },
+    /// A tool is about to run.
+    ToolCallStarted {
+        turn_id: String,
+        call_id: String,
+        tool: String,
+    },
+    /// A tool finished (or was denied by a hook).
+    ToolCallFinished {
+        turn_id: String,
+        call_id: String,
+        tool: String,
+        result_bytes: usize,
+        duration_ms: u64,
+    },
+    /// Tokens used by one LLM call, and how long it took.
+    LlmTokens {
+        turn_id: String,
+        model: String,
+        prompt_tokens: u64,
+        completion_tokens: u64,
+        #[serde(default)]
+        duration_ms: u64,
+    },
+    /// The turn ended, successfully or not.
+    TurnCompleted {
+        turn_id: String,
+        session_key: String,
+        iterations: u32,
+        duration_ms: u64,
+        failed: bool,
+    },
+    /// The day's spend passed the configured budget during the turn.
+    BudgetExceeded { turn_id: String, message: String },
+}
+
+impl AgentEvent {
+    /// ID of the turn this event belongs to.
+    pub fn turn_id(&self) -> &str {
+        match self {
+            AgentEvent::TurnStarted { turn_id, .. }
+            | AgentEvent::ToolCallStarted { turn_id, .. }
+            | AgentEvent::ToolCallFinished { turn_id, .. }
+            | AgentEvent::LlmTokens { turn_id, .. }
+            | AgentEvent::TurnCompleted { turn_id, .. }
+            | AgentEvent::BudgetExceeded { turn_id, .. } => turn_id,
+        }
+    }
+}
+
+/// System events buffered per subscriber before slow subscribers lag.
+const SYSTEM_EVENT_CAPACITY: usize = 256;
+
+/// Something that happened to the gateway itself rather than in a chat.
+/// Published on the system event channel for webhooks, UIs and, when
+/// configured, the agent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SystemEvent {
+    ChannelConnected { channel: String },
+    ChannelDisconnected { channel: String },
+    /// A cron job started running.
+    CronFired { job_id: String, name: String },
+    /// A subagent ended; `status` is as in `SubagentRun`.
+    SubagentFinished {
+        task_id: String,
+        label: String,
+        status: String,
+    },
+    /// The day's spend passed the configured budget.
+    BudgetWarning { message: String },
+    /// An `agents.alerts` threshold was crossed; `alert` is its kind.
+    Alert { alert: String, message: String },
+}
+
+impl SystemEvent {
+    /// Every event type, as returned by [`SystemEvent::kind`].
+    pub const KINDS: [&'static str; 6] = [
+        "channel_connected",
+        "channel_disconnected",
+        "cron_fired",
+        "subagent_finished",
+        "budget_warning",
+        "alert",
+    ];
+
+    /// A sender for system events, without subscribers yet.
+    pub fn channel() -> broadcast::Sender<SystemEvent> {
+        broadcast::channel(SYSTEM_EVENT_CAPACITY).0
+    }
+
+    /// The event's type, as in its JSON form (e.g. `cron_fired`).
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SystemEvent::ChannelConnected { .. } => "channel_connected",
+            SystemEvent::ChannelDisconnected { .. } => "channel_disconnected",
+            SystemEvent::CronFired { .. } => "cron_fired",
+            SystemEvent::SubagentFinished { .. } => "subagent_finished",
+            SystemEvent::BudgetWarning { .. } => "budget_warning",
+            SystemEvent::Alert { .. } => "alert",
+        }
+    }
+
+    /// One line for people.
+    pub fn describe(&self) -> String {
+        match self {
+            SystemEvent::ChannelConnected { channel } => format!("Channel {} connected", channel),
+            SystemEvent::ChannelDisconnected { channel } => {
+                format!("Channel {} disconnected", channel)
+            }
+            SystemEvent::CronFired { job_id, name } => {
+                format!("Cron job '{}' ({}) started", name, job_id)
+            }
+            SystemEvent::SubagentFinished {
+                task_id,
+                label,
+                status,
+            } => format!("Subagent '{}' ({}) {}", label, task_id, status),
+            SystemEvent::BudgetWarning { message } => format!("Budget warning: {}", message),
+            SystemEvent::Alert { message, .. } => format!("Alert: {}", message),
         }
     }
 }
@@ -105,6 +409,42 @@ mod tests {
         assert!(msg.reply_to.is_none());
     }
 
+    #[test]
+    fn test_outbound_stream_metadata() {
+        let mut msg = OutboundMessage::new("telegram", "42", "Hel");
+        assert!(msg.stream_id().is_none());
+        assert!(!msg.is_partial());
+
+        msg.metadata.insert("stream_id".to_string(), Value::from("s1"));
+        msg.metadata.insert("partial".to_string(), Value::from(true));
+        assert_eq!(msg.stream_id(), Some("s1"));
+        assert!(msg.is_partial());
+    }
+
+    #[test]
+    fn test_attachments() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("voice.ogg");
+        std::fs::write(&path, b"OggS").unwrap();
+        let voice = Attachment::new(path.to_string_lossy());
+        assert_eq!((voice.kind(), voice.size), (AttachmentKind::Audio, Some(4)));
+
+        let mut chart = Attachment::new("https://example.com/chart.png?v=2");
+        chart.caption = Some("Sales".to_string());
+        assert_eq!(chart.kind(), AttachmentKind::Image);
+        assert_eq!(chart.describe(), "[image: https://example.com/chart.png?v=2 \"Sales\"]");
+        assert_eq!(Attachment::new("report.pdf").kind(), AttachmentKind::Document);
+
+        let mut msg = OutboundMessage::new("whatsapp", "1", "Here you go");
+        msg.attachments = vec![chart, voice];
+        assert_eq!(
+            msg.content_with_links(),
+            "Here you go\nSales: https://example.com/chart.png?v=2"
+        );
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["attachments"][0]["mime"], "image/png");
+    }
+
     #[test]
     fn test_inbound_serialization_roundtrip() {
         let msg = InboundMessage::new("feishu", "u123", "c456", "test message");
@@ -113,4 +453,33 @@ mod tests {
         assert_eq!(deserialized.channel, "feishu");
         assert_eq!(deserialized.content, "test message");
     }
+
+    #[test]
+    fn test_agent_event_serialization() {
+        let event = AgentEvent::ToolCallStarted {
+            turn_id: "t1".to_string(),
+            call_id: "c1".to_string(),
+            tool: "exec".to_string(),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "tool_call_started");
+        assert_eq!(json["tool"], "exec");
+        let back: AgentEvent = serde_json::from_value(json).unwrap();
+        assert_eq!(back, event);
+        assert_eq!(back.turn_id(), "t1");
+    }
+
+    #[test]
+    fn test_system_event_kind_matches_json() {
+        let event = SystemEvent::SubagentFinished {
+            task_id: "ab12".to_string(),
+            label: "research".to_string(),
+            status: "failed".to_string(),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], event.kind());
+        assert_eq!(event.describe(), "Subagent 'research' (ab12) failed");
+        let back: SystemEvent = serde_json::from_value(json).unwrap();
+        assert_eq!(back, event);
+    }
 }
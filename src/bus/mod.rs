@@ -1,2 +1,5 @@
+pub mod bridge;
 pub mod events;
 pub mod queue;
+pub mod receipts;
+pub mod tap;
@@ -0,0 +1,78 @@
+//! Delivery receipts: let the sender of an outbound message learn whether a
+//! channel actually delivered it.
+//!
+//! Sending on the bus only hands a message to the channel manager; the send
+//! itself (with its own resends) happens later. A sender that cares asks for
+//! a receipt, which tags the message, and the manager reports the outcome of
+//! the final attempt back through it.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde_json::Value;
+use tokio::sync::oneshot;
+
+use crate::bus::events::OutboundMessage;
+
+/// Metadata key carrying the receipt ID of an outbound message.
+pub const RECEIPT_KEY: &str = "receipt_id";
+
+/// Outcome of a delivery: `Err` holds the channel's error.
+pub type DeliveryResult = Result<(), String>;
+
+/// Pending receipts, shared by senders and the channel manager.
+#[derive(Default)]
+pub struct DeliveryReceipts {
+    pending: Mutex<HashMap<String, oneshot::Sender<DeliveryResult>>>,
+}
+
+impl DeliveryReceipts {
+    /// Ask for a receipt for `msg`. The returned receiver resolves once the
+    /// channel manager has delivered the message or given up on it.
+    pub fn request(&self, msg: &mut OutboundMessage) -> oneshot::Receiver<DeliveryResult> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let (tx, rx) = oneshot::channel();
+        msg.metadata.insert(RECEIPT_KEY.to_string(), Value::from(id.clone()));
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id, tx);
+        rx
+    }
+
+    /// Report the outcome for `msg`, if a receipt was asked for. Only the
+    /// first report for a message counts.
+    pub fn resolve(&self, msg: &OutboundMessage, result: DeliveryResult) {
+        let Some(id) = msg.metadata.get(RECEIPT_KEY).and_then(Value::as_str) else {
+            return;
+        };
+        let tx = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(id);
+        if let Some(tx) = tx {
+            let _ = tx.send(result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_receipt_reports_first_outcome() {
+        let receipts = DeliveryReceipts::default();
+        let mut msg = OutboundMessage::new("telegram", "42", "hi");
+        let rx = receipts.request(&mut msg);
+        assert!(msg.metadata.contains_key(RECEIPT_KEY));
+
+        receipts.resolve(&msg, Err("chat not found".to_string()));
+        receipts.resolve(&msg, Ok(()));
+        assert_eq!(rx.await.unwrap(), Err("chat not found".to_string()));
+
+        // Messages without a receipt are ignored.
+        receipts.resolve(&OutboundMessage::new("telegram", "42", "hi"), Ok(()));
+    }
+}
@@ -0,0 +1,249 @@
+//! Observing bus traffic.
+//!
+//! The gateway's queues are plain channels with a single consumer each. A
+//! [`BusTap`] sits in front of them: every message is passed on unchanged
+//! and a copy is broadcast to subscribers, so optional components such as
+//! metrics, audit logs, UIs and plugins can watch the traffic without the
+//! agent loop or the channel manager knowing about them.
+//!
+//! Requests sent straight to the agent loop (API, cron, heartbeat) do not
+//! pass through the bus and are not seen here.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::bus::events::{InboundMessage, OutboundMessage, SystemEvent};
+
+/// Messages buffered per subscriber before slow subscribers lag.
+const TAP_CAPACITY: usize = 1024;
+
+/// A message seen on the bus.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "direction", content = "message", rename_all = "snake_case")]
+pub enum BusTraffic {
+    /// From a channel to the agent.
+    Inbound(InboundMessage),
+    /// From the agent to a channel.
+    Outbound(OutboundMessage),
+    System(SystemEvent),
+}
+
+/// A component that observes bus traffic; see [`BusTap::add_subscriber`].
+#[async_trait]
+pub trait BusSubscriber: Send + Sync {
+    /// Name used in log messages.
+    fn name(&self) -> &str;
+
+    /// Called for every message, in order. Slow subscribers miss messages
+    /// rather than hold up the bus.
+    async fn on_traffic(&self, traffic: &BusTraffic);
+}
+
+/// Copies bus traffic to subscribers. Cloning gives another handle to the
+/// same tap.
+#[derive(Clone)]
+pub struct BusTap {
+    tx: broadcast::Sender<BusTraffic>,
+}
+
+impl BusTap {
+    pub fn new() -> Self {
+        Self {
+            tx: broadcast::channel(TAP_CAPACITY).0,
+        }
+    }
+
+    /// Receive a copy of every message from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<BusTraffic> {
+        self.tx.subscribe()
+    }
+
+    /// Run `subscriber` on all traffic until the tap is dropped.
+    pub fn add_subscriber(&self, subscriber: Arc<dyn BusSubscriber>) -> JoinHandle<()> {
+        let mut rx = self.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(traffic) => subscriber.on_traffic(&traffic).await,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("Bus subscriber {} missed {} messages", subscriber.name(), n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    /// A sender to use instead of `inner` for messages to the agent.
+    pub fn inbound(
+        &self,
+        inner: UnboundedSender<InboundMessage>,
+    ) -> UnboundedSender<InboundMessage> {
+        self._forward(inner, BusTraffic::Inbound)
+    }
+
+    /// A sender to use instead of `inner` for messages to the channels.
+    pub fn outbound(
+        &self,
+        inner: UnboundedSender<OutboundMessage>,
+    ) -> UnboundedSender<OutboundMessage> {
+        self._forward(inner, BusTraffic::Outbound)
+    }
+
+    /// Copy `events` to the subscribers.
+    pub fn system(&self, events: &broadcast::Sender<SystemEvent>) -> JoinHandle<()> {
+        let mut rx = events.subscribe();
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => {
+                        let _ = tx.send(BusTraffic::System(event));
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    /// A sender whose messages are copied to the subscribers and passed on
+    /// to `inner`. Passing on stops once every clone of the returned sender
+    /// is dropped or `inner`'s receiver is gone.
+    fn _forward<T: Clone + Send + 'static>(
+        &self,
+        inner: UnboundedSender<T>,
+        wrap: fn(T) -> BusTraffic,
+    ) -> UnboundedSender<T> {
+        let (tx, mut rx) = mpsc::unbounded_channel::<T>();
+        let copies = self.tx.clone();
+        tokio::spawn(async move {
+            while let Some(msg) = rx.recv().await {
+                if copies.receiver_count() > 0 {
+                    let _ = copies.send(wrap(msg.clone()));
+                }
+                if inner.send(msg).is_err() {
+                    break;
+                }
+            }
+        });
+        tx
+    }
+}
+
+impl Default for BusTap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Built-in subscribers
+// ---------------------------------------------------------------------------
+
+/// Counts messages by direction and channel, for `/api/status`.
+#[derive(Default)]
+pub struct TrafficCounter {
+    counts: Mutex<HashMap<(&'static str, String), u64>>,
+}
+
+impl TrafficCounter {
+    /// Counts as `{"inbound": {"telegram": 3}, "outbound": {...}, "system": {...}}`,
+    /// where system events are counted by type.
+    pub fn to_json(&self) -> Value {
+        let mut out = json!({"inbound": {}, "outbound": {}, "system": {}});
+        if let Ok(counts) = self.counts.lock() {
+            for ((direction, key), count) in counts.iter() {
+                out[*direction][key] = json!(count);
+            }
+        }
+        out
+    }
+}
+
+#[async_trait]
+impl BusSubscriber for TrafficCounter {
+    fn name(&self) -> &str {
+        "traffic counter"
+    }
+
+    async fn on_traffic(&self, traffic: &BusTraffic) {
+        let key = match traffic {
+            BusTraffic::Inbound(msg) => ("inbound", msg.channel.clone()),
+            BusTraffic::Outbound(msg) => ("outbound", msg.channel.clone()),
+            BusTraffic::System(event) => ("system", event.kind().to_string()),
+        };
+        if let Ok(mut counts) = self.counts.lock() {
+            *counts.entry(key).or_default() += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_tap_forwards_and_copies() {
+        let tap = BusTap::new();
+        let mut copies = tap.subscribe();
+        let (inner_tx, mut inner_rx) = mpsc::unbounded_channel();
+        let outbound_tx = tap.outbound(inner_tx);
+
+        outbound_tx
+            .send(OutboundMessage::new("telegram", "42", "hi"))
+            .unwrap();
+        assert_eq!(inner_rx.recv().await.unwrap().content, "hi");
+        match copies.recv().await.unwrap() {
+            BusTraffic::Outbound(msg) => assert_eq!(msg.chat_id, "42"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        drop(outbound_tx);
+        assert!(inner_rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_traffic_counter() {
+        let tap = BusTap::new();
+        let counter = Arc::new(TrafficCounter::default());
+        tap.add_subscriber(counter.clone());
+        let events = SystemEvent::channel();
+        tap.system(&events);
+        let (inner_tx, mut inner_rx) = mpsc::unbounded_channel();
+        let inbound_tx = tap.inbound(inner_tx);
+
+        for text in ["a", "b"] {
+            inbound_tx
+                .send(InboundMessage::new("telegram", "u", "42", text))
+                .unwrap();
+            inner_rx.recv().await.unwrap();
+        }
+        events
+            .send(SystemEvent::BudgetWarning {
+                message: "over".to_string(),
+            })
+            .unwrap();
+
+        let mut counts = Value::Null;
+        for _ in 0..50 {
+            counts = counter.to_json();
+            if counts["system"]["budget_warning"] == 1 && counts["inbound"]["telegram"] == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(counts["inbound"]["telegram"], 2);
+        assert_eq!(counts["system"]["budget_warning"], 1);
+        assert_eq!(counts["outbound"], json!({}));
+    }
+}
@@ -27,6 +27,15 @@ pub trait Channel: Send + Sync {
     /// Send an outbound message through this channel.
     async fn send(&self, msg: &OutboundMessage) -> Result<()>;
 
+    /// Whether the channel can edit a sent message in place, and therefore
+    /// show streamed replies as they form.
+    ///
+    /// Channels that return `false` never see partial messages (see
+    /// [`OutboundMessage::is_partial`]); they only receive the final text.
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+
     /// Check whether `sender_id` is in the allow list.
     ///
     /// If the allow list is empty every sender is permitted.  The
@@ -0,0 +1,141 @@
+//! Sending one outbound message to several chats.
+//!
+//! An [`OutboundMessage`] can name further recipients in `targets`, as
+//! `channel:chat_id`. A recipient on the `group` channel stands for a named
+//! list of recipients from the `groups` section of the config, so cron jobs
+//! and alerts can be delivered with `--channel group --to alerts`:
+//!
+//! ```json
+//! { "groups": { "alerts": ["telegram:12345", "feishu:oc_abc"] } }
+//! ```
+//!
+//! The channel manager sends a copy of the message to each recipient.
+
+use std::collections::HashMap;
+
+use tracing::warn;
+
+use crate::bus::events::OutboundMessage;
+
+/// Channel name under which recipients are group names.
+pub const GROUP_CHANNEL: &str = "group";
+
+/// Split a `channel:chat_id` recipient.
+pub fn parse_target(target: &str) -> Option<(&str, &str)> {
+    target
+        .split_once(':')
+        .filter(|(channel, chat_id)| !channel.is_empty() && !chat_id.is_empty())
+}
+
+/// The copies of `msg` to send, one per recipient, with groups resolved
+/// through `groups` and duplicates left out. Streamed updates are only sent
+/// to single chats; the final message of a broadcast is sent as a whole.
+pub fn expand_recipients(
+    msg: OutboundMessage,
+    groups: &HashMap<String, Vec<String>>,
+) -> Vec<OutboundMessage> {
+    if msg.targets.is_empty() && msg.channel != GROUP_CHANNEL {
+        return vec![msg];
+    }
+
+    let mut recipients: Vec<(String, String)> = Vec::new();
+    let mut add = |channel: &str, chat_id: &str| {
+        let recipient = (channel.to_string(), chat_id.to_string());
+        if !recipients.contains(&recipient) {
+            recipients.push(recipient);
+        }
+    };
+    let primary = format!("{}:{}", msg.channel, msg.chat_id);
+    for target in std::iter::once(&primary).chain(&msg.targets) {
+        match parse_target(target) {
+            Some((GROUP_CHANNEL, name)) => match groups.get(name) {
+                Some(members) => {
+                    for member in members {
+                        match parse_target(member) {
+                            Some((channel, chat_id)) if channel != GROUP_CHANNEL => {
+                                add(channel, chat_id)
+                            }
+                            _ => warn!("Ignoring recipient '{}' of group '{}'", member, name),
+                        }
+                    }
+                }
+                None => warn!("No group named '{}' in the config", name),
+            },
+            Some((channel, chat_id)) => add(channel, chat_id),
+            None => warn!("Ignoring recipient '{}', expected channel:chat_id", target),
+        }
+    }
+
+    if recipients.len() > 1 && msg.is_partial() {
+        return Vec::new();
+    }
+    recipients
+        .into_iter()
+        .map(|(channel, chat_id)| {
+            let mut copy = msg.clone();
+            copy.channel = channel;
+            copy.chat_id = chat_id;
+            copy.targets.clear();
+            copy.metadata.remove("stream_id");
+            copy
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recipients(msgs: &[OutboundMessage]) -> Vec<String> {
+        msgs.iter()
+            .map(|m| format!("{}:{}", m.channel, m.chat_id))
+            .collect()
+    }
+
+    #[test]
+    fn test_expand_targets_and_groups() {
+        let groups = HashMap::from([(
+            "alerts".to_string(),
+            vec!["telegram:42".to_string(), "feishu:oc_1".to_string()],
+        )]);
+
+        let single = expand_recipients(OutboundMessage::new("telegram", "42", "hi"), &groups);
+        assert_eq!(recipients(&single), ["telegram:42"]);
+
+        let mut msg = OutboundMessage::new("group", "alerts", "disk full");
+        msg.targets = vec![
+            "telegram:42".to_string(),
+            "whatsapp:7".to_string(),
+            "bad".to_string(),
+        ];
+        let copies = expand_recipients(msg, &groups);
+        assert_eq!(
+            recipients(&copies),
+            ["telegram:42", "feishu:oc_1", "whatsapp:7"]
+        );
+        assert!(copies
+            .iter()
+            .all(|m| m.content == "disk full" && m.targets.is_empty()));
+
+        assert!(
+            expand_recipients(OutboundMessage::new("group", "nobody", "x"), &groups).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_broadcast_skips_partial_updates() {
+        let groups = HashMap::new();
+        let mut msg = OutboundMessage::new("telegram", "42", "draft");
+        msg.targets = vec!["feishu:oc_1".to_string()];
+        msg.metadata
+            .insert("stream_id".to_string(), serde_json::json!("s1"));
+        msg.metadata
+            .insert("partial".to_string(), serde_json::json!(true));
+        assert!(expand_recipients(msg.clone(), &groups).is_empty());
+
+        msg.metadata.remove("partial");
+        let copies = expand_recipients(msg, &groups);
+        assert_eq!(copies.len(), 2);
+        assert!(copies.iter().all(|m| m.stream_id().is_none()));
+    }
+}
@@ -0,0 +1,135 @@
+//! Dead-letter store for outbound messages that could not be delivered.
+//!
+//! When a channel keeps failing to send a message, the message is kept in
+//! `~/.nanoclaw/dead_letters.json` instead of being dropped. `nanoclaw
+//! channels dlq list` shows them and `nanoclaw channels dlq retry` hands them
+//! back to the running gateway.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::bus::events::OutboundMessage;
+
+/// At most this many dead letters are kept; the oldest go first.
+const MAX_DEAD_LETTERS: usize = 500;
+
+/// A message that could not be delivered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub id: String,
+    pub failed_at: DateTime<Local>,
+    pub attempts: u32,
+    /// The last send error.
+    pub error: String,
+    pub message: OutboundMessage,
+}
+
+/// The dead letters, persisted as a JSON array.
+pub struct DeadLetterStore {
+    path: PathBuf,
+    /// Serializes read-modify-write cycles within the gateway.
+    lock: Mutex<()>,
+}
+
+impl DeadLetterStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// All dead letters, oldest first.
+    pub fn list(&self) -> Vec<DeadLetter> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable {}: {}", self.path.display(), e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Keep `message`, which failed `attempts` times with `error`. Returns
+    /// the dead letter's id.
+    pub fn push(&self, message: OutboundMessage, attempts: u32, error: &str) -> Result<String> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut letters = self.list();
+        let id = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+        letters.push(DeadLetter {
+            id: id.clone(),
+            failed_at: Local::now(),
+            attempts,
+            error: error.to_string(),
+            message,
+        });
+        let excess = letters.len().saturating_sub(MAX_DEAD_LETTERS);
+        letters.drain(..excess);
+        self._save(&letters)?;
+        Ok(id)
+    }
+
+    /// Remove and return the dead letters with `ids`, or all of them when
+    /// `ids` is empty.
+    pub fn take(&self, ids: &[String]) -> Result<Vec<DeadLetter>> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let (taken, kept): (Vec<_>, Vec<_>) = self
+            .list()
+            .into_iter()
+            .partition(|l| ids.is_empty() || ids.contains(&l.id));
+        if !taken.is_empty() {
+            self._save(&kept)?;
+        }
+        Ok(taken)
+    }
+
+    fn _save(&self, letters: &[DeadLetter]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(letters)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_list_take() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = DeadLetterStore::new(dir.path().join("dead_letters.json"));
+        assert!(store.list().is_empty());
+
+        let first = store
+            .push(
+                OutboundMessage::new("telegram", "42", "hello"),
+                3,
+                "timeout",
+            )
+            .unwrap();
+        store
+            .push(OutboundMessage::new("feishu", "7", "later"), 3, "HTTP 500")
+            .unwrap();
+        let letters = store.list();
+        assert_eq!(letters.len(), 2);
+        assert_eq!(letters[0].message.content, "hello");
+        assert_eq!(letters[0].error, "timeout");
+
+        let taken = store.take(std::slice::from_ref(&first)).unwrap();
+        assert_eq!(taken.len(), 1);
+        assert_eq!(taken[0].message.chat_id, "42");
+        assert!(store.take(&[first]).unwrap().is_empty());
+
+        assert_eq!(store.take(&[]).unwrap().len(), 1);
+        assert!(store.list().is_empty());
+    }
+}
@@ -17,6 +17,7 @@ use tracing::{error, info, warn};
 use crate::bus::events::{InboundMessage, OutboundMessage};
 use crate::channels::base::Channel;
 use crate::config::schema::FeishuConfig;
+use crate::utils::http::shared_client;
 
 /// Feishu/Lark channel.
 ///
@@ -25,6 +26,8 @@ use crate::config::schema::FeishuConfig;
 /// Feishu HTTP API.
 pub struct FeishuChannel {
     config: FeishuConfig,
+    /// Kept for inbound events, which need the long connection API.
+    #[allow(dead_code)]
     bus_tx: UnboundedSender<InboundMessage>,
     running: Arc<AtomicBool>,
     client: reqwest::Client,
@@ -39,7 +42,7 @@ impl FeishuChannel {
             config,
             bus_tx,
             running: Arc::new(AtomicBool::new(false)),
-            client: reqwest::Client::new(),
+            client: shared_client(),
             token: Arc::new(TokioMutex::new(None)),
         }
     }
@@ -155,7 +158,7 @@ impl Channel for FeishuChannel {
             "open_id"
         };
 
-        let content = json!({"text": msg.content}).to_string();
+        let content = json!({"text": msg.content_with_links()}).to_string();
 
         let resp = self
             .client
@@ -1,27 +1,54 @@
 //! Channel manager for coordinating chat channels.
 //!
 //! Initialises enabled channels, starts them, and dispatches outbound messages
-//! to the correct channel.
+//! to the correct channel, or channels for a broadcast. Final messages that
+//! fail to send are retried, then kept in the [`DeadLetterStore`]; senders
+//! that asked for a [`DeliveryReceipts`] receipt learn the outcome.
 
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use serde_json::{json, Value};
+use tokio::sync::broadcast;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 use tokio::sync::Mutex as TokioMutex;
 use tracing::{error, info, warn};
 
-use crate::bus::events::{InboundMessage, OutboundMessage};
+use crate::bus::bridge::BRIDGE_CHANNEL;
+use crate::bus::events::{InboundMessage, OutboundMessage, SystemEvent};
+use crate::bus::receipts::DeliveryReceipts;
 use crate::channels::base::Channel;
+use crate::channels::broadcast::expand_recipients;
+use crate::audit::{AuditEntry, AuditKind, AuditLog};
+use crate::channels::dead_letters::DeadLetterStore;
 use crate::channels::feishu::FeishuChannel;
+use crate::channels::guardrails::Guardrails;
+use crate::channels::postprocess::PostProcessPipeline;
 use crate::channels::telegram::TelegramChannel;
 use crate::channels::whatsapp::WhatsAppChannel;
+use crate::config::loader::get_data_dir;
 use crate::config::schema::Config;
 
+/// Attempts at sending a final message before it becomes a dead letter.
+const SEND_ATTEMPTS: u32 = 3;
+
+/// Delay before the first resend; doubled for each further one.
+const FIRST_RESEND_DELAY: Duration = Duration::from_secs(2);
+
+type SharedChannel = Arc<TokioMutex<Box<dyn Channel>>>;
+
 /// Manages chat channels and coordinates message routing.
 pub struct ChannelManager {
-    channels: HashMap<String, Arc<TokioMutex<Box<dyn Channel>>>>,
+    channels: HashMap<String, SharedChannel>,
     bus_outbound_rx: Arc<TokioMutex<UnboundedReceiver<OutboundMessage>>>,
+    guardrails: Arc<Guardrails>,
+    post_processors: Arc<PostProcessPipeline>,
+    dead_letters: Arc<DeadLetterStore>,
+    /// Named recipient lists from the config.
+    groups: Arc<HashMap<String, Vec<String>>>,
+    audit: Option<Arc<AuditLog>>,
+    receipts: Option<Arc<DeliveryReceipts>>,
 }
 
 impl ChannelManager {
@@ -31,7 +58,7 @@ impl ChannelManager {
         bus_inbound_tx: UnboundedSender<InboundMessage>,
         bus_outbound_rx: UnboundedReceiver<OutboundMessage>,
     ) -> Self {
-        let mut channels: HashMap<String, Arc<TokioMutex<Box<dyn Channel>>>> = HashMap::new();
+        let mut channels: HashMap<String, SharedChannel> = HashMap::new();
 
         // Telegram.
         if config.channels.telegram.enabled {
@@ -74,10 +101,48 @@ impl ChannelManager {
             info!("Feishu channel enabled");
         }
 
+        let guardrails = Guardrails::from_config(&config.guardrails)
+            .with_audit_file(get_data_dir().join("guardrails.jsonl"));
+        if !guardrails.is_empty() {
+            info!("Output guardrails enabled");
+        }
+
         Self {
             channels,
             bus_outbound_rx: Arc::new(TokioMutex::new(bus_outbound_rx)),
+            guardrails: Arc::new(guardrails),
+            post_processors: Arc::new(PostProcessPipeline::default()),
+            dead_letters: Arc::new(DeadLetterStore::new(
+                get_data_dir().join("dead_letters.json"),
+            )),
+            groups: Arc::new(config.groups.clone()),
+            audit: None,
+            receipts: None,
+        }
+    }
+
+    /// Where undeliverable messages are kept.
+    pub fn dead_letters(&self) -> Arc<DeadLetterStore> {
+        self.dead_letters.clone()
+    }
+
+    /// Transform final replies with `pipeline` before they are sent.
+    pub fn set_post_processors(&mut self, pipeline: PostProcessPipeline) {
+        if !pipeline.is_empty() {
+            info!("Reply post-processing enabled");
         }
+        self.post_processors = Arc::new(pipeline);
+    }
+
+    /// Record every final message sent, or given up on, in `log`.
+    pub fn set_audit_log(&mut self, log: Arc<AuditLog>) {
+        self.audit = Some(log);
+    }
+
+    /// Report the outcome of final messages that carry a receipt to
+    /// `receipts`.
+    pub fn set_delivery_receipts(&mut self, receipts: Arc<DeliveryReceipts>) {
+        self.receipts = Some(receipts);
     }
 
     /// Start all enabled channels and the outbound message dispatcher.
@@ -102,6 +167,12 @@ impl ChannelManager {
         // Start the outbound dispatcher.
         let channels = self.channels.clone();
         let rx = self.bus_outbound_rx.clone();
+        let guardrails = self.guardrails.clone();
+        let post_processors = self.post_processors.clone();
+        let dead_letters = self.dead_letters.clone();
+        let groups = self.groups.clone();
+        let audit = self.audit.clone();
+        let receipts = self.receipts.clone();
 
         tokio::spawn(async move {
             info!("Outbound dispatcher started");
@@ -117,13 +188,56 @@ impl ChannelManager {
                     }
                 };
 
-                if let Some(channel) = channels.get(&msg.channel) {
-                    let guard = channel.lock().await;
-                    if let Err(e) = guard.send(&msg).await {
-                        error!("Error sending to {}: {}", msg.channel, e);
+                for mut msg in expand_recipients(msg, &groups) {
+                    if !guardrails.apply(&mut msg) {
+                        continue;
+                    }
+                    post_processors.apply(&mut msg).await;
+
+                    let Some(channel) = channels.get(&msg.channel) else {
+                        // Replies to bridge messages reach it as mirrored
+                        // outbound traffic.
+                        if msg.channel != BRIDGE_CHANNEL {
+                            warn!("Unknown channel: {}", msg.channel);
+                        }
+                        if let Some(receipts) = &receipts {
+                            receipts.resolve(&msg, Err(format!("unknown channel {}", msg.channel)));
+                        }
+                        continue;
+                    };
+                    // Streamed updates are superseded by the final message, so
+                    // they are neither retried nor kept.
+                    if msg.is_partial() {
+                        let guard = channel.lock().await;
+                        if guard.supports_streaming() {
+                            if let Err(e) = guard.send(&msg).await {
+                                error!(turn = msg.turn_id(), "Error sending to {}: {}", msg.channel, e);
+                            }
+                        }
+                        continue;
+                    }
+                    let sent = _send_with_retries(channel, &msg, FIRST_RESEND_DELAY).await;
+                    if let Some(audit) = &audit {
+                        let chat = format!("{}:{}", msg.channel, msg.chat_id);
+                        let entry =
+                            AuditEntry::new(&chat, AuditKind::Message, "send", &chat, sent.is_ok());
+                        audit.record(&entry.with_turn(msg.turn_id()));
+                    }
+                    if let Some(receipts) = &receipts {
+                        receipts.resolve(&msg, sent.as_ref().map(|_| ()).map_err(|e| e.to_string()));
+                    }
+                    if let Err(e) = sent {
+                        error!(
+                            turn = msg.turn_id(),
+                            "Giving up sending to {}:{} after {} attempts: {}",
+                            msg.channel, msg.chat_id, SEND_ATTEMPTS, e
+                        );
+                        let turn = msg.turn_id().map(str::to_string);
+                        match dead_letters.push(msg, SEND_ATTEMPTS, &e.to_string()) {
+                            Ok(id) => warn!(turn, "Kept the undelivered message as dead letter {}", id),
+                            Err(e) => error!("Failed to keep the undelivered message: {}", e),
+                        }
                     }
-                } else {
-                    warn!("Unknown channel: {}", msg.channel);
                 }
             }
         });
@@ -160,8 +274,135 @@ impl ChannelManager {
         status
     }
 
+    /// Whether each enabled channel is running (connected).
+    pub async fn running(&self) -> HashMap<String, bool> {
+        let mut running = HashMap::new();
+        for (name, channel) in &self.channels {
+            running.insert(name.clone(), channel.lock().await.is_running());
+        }
+        running
+    }
+
+    /// Check the channels every `interval` and publish when one connects or
+    /// disconnects.
+    pub fn watch(
+        self: Arc<Self>,
+        interval: Duration,
+        events: broadcast::Sender<SystemEvent>,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut was_running: HashMap<String, bool> = HashMap::new();
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                for (name, running) in self.running().await {
+                    let before = was_running.insert(name.clone(), running);
+                    if before == Some(running) || (before.is_none() && !running) {
+                        continue;
+                    }
+                    let _ = events.send(if running {
+                        SystemEvent::ChannelConnected { channel: name }
+                    } else {
+                        SystemEvent::ChannelDisconnected { channel: name }
+                    });
+                }
+            }
+        })
+    }
+
     /// Get the list of enabled channel names.
     pub fn enabled_channels(&self) -> Vec<String> {
         self.channels.keys().cloned().collect()
     }
 }
+
+/// Send `msg` through `channel`, making up to [`SEND_ATTEMPTS`] attempts with
+/// exponential backoff from `delay`. Returns the last error.
+async fn _send_with_retries(
+    channel: &SharedChannel,
+    msg: &OutboundMessage,
+    mut delay: Duration,
+) -> anyhow::Result<()> {
+    let mut attempt = 1;
+    loop {
+        // Not held while waiting, so status checks are not blocked.
+        let result = channel.lock().await.send(msg).await;
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt >= SEND_ATTEMPTS => return Err(e),
+            Err(e) => {
+                warn!(
+                    turn = msg.turn_id(),
+                    "Sending to {} failed (attempt {}/{}): {}",
+                    msg.channel, attempt, SEND_ATTEMPTS, e
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails the first `failures` sends.
+    struct FlakyChannel {
+        failures: u32,
+        sends: Arc<AtomicU32>,
+    }
+
+    #[async_trait::async_trait]
+    impl Channel for FlakyChannel {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        async fn start(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn stop(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn send(&self, _msg: &OutboundMessage) -> anyhow::Result<()> {
+            let n = self.sends.fetch_add(1, Ordering::SeqCst) + 1;
+            if n <= self.failures {
+                anyhow::bail!("send {} failed", n);
+            }
+            Ok(())
+        }
+
+        fn is_running(&self) -> bool {
+            true
+        }
+    }
+
+    fn flaky(failures: u32) -> (SharedChannel, Arc<AtomicU32>) {
+        let sends = Arc::new(AtomicU32::new(0));
+        let channel: Box<dyn Channel> = Box::new(FlakyChannel {
+            failures,
+            sends: sends.clone(),
+        });
+        (Arc::new(TokioMutex::new(channel)), sends)
+    }
+
+    #[tokio::test]
+    async fn test_send_with_retries() {
+        let msg = OutboundMessage::new("flaky", "1", "hi");
+        let delay = Duration::from_millis(1);
+
+        let (channel, sends) = flaky(SEND_ATTEMPTS - 1);
+        assert!(_send_with_retries(&channel, &msg, delay).await.is_ok());
+        assert_eq!(sends.load(Ordering::SeqCst), SEND_ATTEMPTS);
+
+        let (channel, sends) = flaky(u32::MAX);
+        let err = _send_with_retries(&channel, &msg, delay).await.unwrap_err();
+        assert_eq!(err.to_string(), format!("send {} failed", SEND_ATTEMPTS));
+        assert_eq!(sends.load(Ordering::SeqCst), SEND_ATTEMPTS);
+    }
+}
@@ -1,5 +1,9 @@
 pub mod base;
+pub mod broadcast;
+pub mod dead_letters;
 pub mod telegram;
 pub mod whatsapp;
 pub mod feishu;
+pub mod guardrails;
 pub mod manager;
+pub mod postprocess;
@@ -0,0 +1,402 @@
+//! Reply post-processing: chainable transforms applied to final replies.
+//!
+//! Each channel can have its own chain of processors (plus a `"*"` chain
+//! shared by all channels) that run in the outbound path after the output
+//! guardrails: appending a signature, translating into the user's language,
+//! or attaching a spoken version of the reply. Partial updates of streamed
+//! replies are left alone.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::json;
+use tracing::{debug, warn};
+
+use crate::bus::events::{Attachment, OutboundMessage};
+use crate::config::schema::{Config, PostProcessorConfig};
+use crate::providers::base::LLMProvider;
+use crate::utils::http::shared_client;
+
+/// Chain key that applies to every channel.
+const ALL_CHANNELS: &str = "*";
+
+/// A transform applied to a final reply before it is sent.
+#[async_trait]
+pub trait PostProcessor: Send + Sync {
+    /// Short name used in logs.
+    fn name(&self) -> &str;
+
+    /// Transform `msg` in place.
+    async fn process(&self, msg: &mut OutboundMessage) -> Result<()>;
+}
+
+// ---------------------------------------------------------------------------
+// Pipeline
+// ---------------------------------------------------------------------------
+
+/// Post-processor chains by channel name.
+#[derive(Default)]
+pub struct PostProcessPipeline {
+    chains: HashMap<String, Vec<Arc<dyn PostProcessor>>>,
+}
+
+impl PostProcessPipeline {
+    /// Build the chains configured in `config.post_processing`. Unknown
+    /// processor types are logged and skipped.
+    pub fn from_config(
+        config: &Config,
+        provider: Arc<dyn LLMProvider>,
+        workspace: &Path,
+        media_dir: PathBuf,
+    ) -> Self {
+        let mut pipeline = Self::default();
+        for (channel, steps) in &config.post_processing {
+            for step in steps {
+                let processor: Arc<dyn PostProcessor> = match step.kind.as_str() {
+                    "signature" => Arc::new(Signature::new(&step.text)),
+                    "translate" => Arc::new(Translate::new(
+                        provider.clone(),
+                        step,
+                        workspace.join("USER.md"),
+                    )),
+                    "speech" => Arc::new(Speech::new(config, step, media_dir.clone())),
+                    other => {
+                        warn!("Unknown post-processor '{}' for {}, skipping", other, channel);
+                        continue;
+                    }
+                };
+                pipeline.add(channel, processor);
+            }
+        }
+        pipeline
+    }
+
+    /// Append `processor` to the chain of `channel` (`"*"` for all).
+    pub fn add(&mut self, channel: &str, processor: Arc<dyn PostProcessor>) {
+        self.chains
+            .entry(channel.to_string())
+            .or_default()
+            .push(processor);
+    }
+
+    /// Whether no processors are configured.
+    pub fn is_empty(&self) -> bool {
+        self.chains.values().all(|c| c.is_empty())
+    }
+
+    /// Run the shared chain and then the channel's chain over a final reply.
+    /// A failing processor is logged and skipped so the reply still goes out.
+    pub async fn apply(&self, msg: &mut OutboundMessage) {
+        if msg.is_partial() {
+            return;
+        }
+        let shared = self.chains.get(ALL_CHANNELS).into_iter().flatten();
+        let own = self.chains.get(&msg.channel).into_iter().flatten();
+        for processor in shared.chain(own) {
+            if let Err(e) = processor.process(msg).await {
+                warn!(
+                    "Post-processor {} failed for {}:{}: {}",
+                    processor.name(),
+                    msg.channel,
+                    msg.chat_id,
+                    e
+                );
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Processors
+// ---------------------------------------------------------------------------
+
+/// Appends a fixed signature to every reply.
+pub struct Signature {
+    text: String,
+}
+
+impl Signature {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl PostProcessor for Signature {
+    fn name(&self) -> &str {
+        "signature"
+    }
+
+    async fn process(&self, msg: &mut OutboundMessage) -> Result<()> {
+        if !self.text.is_empty() && !msg.content.is_empty() {
+            msg.content = format!("{}\n\n{}", msg.content, self.text);
+        }
+        Ok(())
+    }
+}
+
+/// Translates replies with the LLM into a fixed language or the one given
+/// in USER.md (a `Language:` line).
+pub struct Translate {
+    provider: Arc<dyn LLMProvider>,
+    model: Option<String>,
+    language: String,
+    user_file: PathBuf,
+}
+
+impl Translate {
+    pub fn new(provider: Arc<dyn LLMProvider>, config: &PostProcessorConfig, user_file: PathBuf) -> Self {
+        Self {
+            provider,
+            model: (!config.model.is_empty()).then(|| config.model.clone()),
+            language: config.language.clone(),
+            user_file,
+        }
+    }
+
+    /// The configured language, or the one in USER.md (read on every reply
+    /// so edits take effect without a restart).
+    fn _language(&self) -> Option<String> {
+        if !self.language.is_empty() {
+            return Some(self.language.clone());
+        }
+        let profile = std::fs::read_to_string(&self.user_file).ok()?;
+        user_language(&profile)
+    }
+}
+
+#[async_trait]
+impl PostProcessor for Translate {
+    fn name(&self) -> &str {
+        "translate"
+    }
+
+    async fn process(&self, msg: &mut OutboundMessage) -> Result<()> {
+        let Some(language) = self._language() else {
+            debug!("No user language set, not translating");
+            return Ok(());
+        };
+        if msg.content.trim().is_empty() {
+            return Ok(());
+        }
+
+        let messages = vec![
+            json!({"role": "system", "content": format!(
+                "Translate the user's message into {}. Keep the formatting, code, \
+                 names and links unchanged. If it is already in {}, return it as is. \
+                 Reply with the translation only.",
+                language, language
+            )}),
+            json!({"role": "user", "content": msg.content}),
+        ];
+        let response = self
+            .provider
+            .chat(&messages, None, self.model.as_deref(), 8192, 0.2)
+            .await?;
+        match response.content {
+            Some(text) if response.finish_reason != "error" && !text.trim().is_empty() => {
+                msg.content = text;
+                Ok(())
+            }
+            other => bail!("translation failed: {}", other.unwrap_or_default()),
+        }
+    }
+}
+
+/// Preferred language from a USER.md profile, ignoring the unfilled
+/// template placeholder.
+pub fn user_language(profile: &str) -> Option<String> {
+    profile.lines().find_map(|line| {
+        let line = line.trim().trim_start_matches(['-', '*']).trim();
+        let (key, value) = line.split_once(':')?;
+        if !key.trim().eq_ignore_ascii_case("language") {
+            return None;
+        }
+        let value = value.trim();
+        (!value.is_empty() && !value.starts_with('(')).then(|| value.to_string())
+    })
+}
+
+/// Attaches a spoken version of the reply, generated with an OpenAI
+/// compatible text-to-speech endpoint.
+pub struct Speech {
+    client: Client,
+    api_key: String,
+    api_url: String,
+    model: String,
+    voice: String,
+    media_dir: PathBuf,
+}
+
+impl Speech {
+    pub fn new(config: &Config, step: &PostProcessorConfig, media_dir: PathBuf) -> Self {
+        let openai = &config.providers.openai;
+        let base = openai
+            .api_base
+            .clone()
+            .unwrap_or_else(|| "https://api.openai.com/v1".to_string());
+        let or_default = |value: &str, default: &str| {
+            if value.is_empty() { default } else { value }.to_string()
+        };
+        Self {
+            client: shared_client(),
+            api_key: openai.api_key.clone(),
+            api_url: format!("{}/audio/speech", base.trim_end_matches('/')),
+            model: or_default(&step.model, "tts-1"),
+            voice: or_default(&step.voice, "alloy"),
+            media_dir,
+        }
+    }
+}
+
+#[async_trait]
+impl PostProcessor for Speech {
+    fn name(&self) -> &str {
+        "speech"
+    }
+
+    async fn process(&self, msg: &mut OutboundMessage) -> Result<()> {
+        if self.api_key.is_empty() {
+            bail!("OpenAI API key not configured");
+        }
+        if msg.content.trim().is_empty() {
+            return Ok(());
+        }
+
+        let response = self
+            .client
+            .post(&self.api_url)
+            .bearer_auth(&self.api_key)
+            .json(&json!({
+                "model": self.model,
+                "voice": self.voice,
+                "input": msg.content,
+                "response_format": "opus",
+            }))
+            .timeout(std::time::Duration::from_secs(60))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            bail!("HTTP {}: {}", status, response.text().await.unwrap_or_default());
+        }
+        let audio = response.bytes().await?;
+
+        tokio::fs::create_dir_all(&self.media_dir).await?;
+        let path = self
+            .media_dir
+            .join(format!("reply-{}.ogg", uuid::Uuid::new_v4()));
+        tokio::fs::write(&path, &audio).await?;
+        msg.attachments
+            .push(Attachment::new(path.to_string_lossy()).with_mime("audio/ogg"));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Uppercases its input, like a very loud translator.
+    struct Upper;
+
+    #[async_trait]
+    impl PostProcessor for Upper {
+        fn name(&self) -> &str {
+            "upper"
+        }
+
+        async fn process(&self, msg: &mut OutboundMessage) -> Result<()> {
+            msg.content = msg.content.to_uppercase();
+            Ok(())
+        }
+    }
+
+    struct Failing;
+
+    #[async_trait]
+    impl PostProcessor for Failing {
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        async fn process(&self, _msg: &mut OutboundMessage) -> Result<()> {
+            bail!("boom")
+        }
+    }
+
+    // ── pipeline ──
+
+    #[tokio::test]
+    async fn test_shared_chain_runs_before_channel_chain() {
+        let mut pipeline = PostProcessPipeline::default();
+        pipeline.add("telegram", Arc::new(Upper));
+        pipeline.add("*", Arc::new(Signature::new("-- bot")));
+
+        let mut msg = OutboundMessage::new("telegram", "1", "hi");
+        pipeline.apply(&mut msg).await;
+        assert_eq!(msg.content, "HI\n\n-- BOT");
+
+        let mut msg = OutboundMessage::new("whatsapp", "1", "hi");
+        pipeline.apply(&mut msg).await;
+        assert_eq!(msg.content, "hi\n\n-- bot");
+    }
+
+    #[tokio::test]
+    async fn test_partials_are_untouched() {
+        let mut pipeline = PostProcessPipeline::default();
+        pipeline.add("*", Arc::new(Upper));
+        let mut msg = OutboundMessage::new("telegram", "1", "hi");
+        msg.metadata.insert("partial".to_string(), json!(true));
+        pipeline.apply(&mut msg).await;
+        assert_eq!(msg.content, "hi");
+    }
+
+    #[tokio::test]
+    async fn test_failing_processor_is_skipped() {
+        let mut pipeline = PostProcessPipeline::default();
+        pipeline.add("*", Arc::new(Failing));
+        pipeline.add("*", Arc::new(Signature::new("sig")));
+        let mut msg = OutboundMessage::new("telegram", "1", "hi");
+        pipeline.apply(&mut msg).await;
+        assert_eq!(msg.content, "hi\n\nsig");
+    }
+
+    #[test]
+    fn test_from_config_skips_unknown_types() {
+        use crate::providers::openai_compat::OpenAICompatProvider;
+
+        let mut config = Config::default();
+        let step = |kind: &str| PostProcessorConfig {
+            kind: kind.to_string(),
+            text: "sig".to_string(),
+            ..Default::default()
+        };
+        config
+            .post_processing
+            .insert("telegram".to_string(), vec![step("signature"), step("sing")]);
+        let provider = Arc::new(OpenAICompatProvider::new("", None, None));
+        let pipeline =
+            PostProcessPipeline::from_config(&config, provider, Path::new("."), PathBuf::from("."));
+        assert_eq!(pipeline.chains["telegram"].len(), 1);
+        assert!(PostProcessPipeline::default().is_empty());
+    }
+
+    // ── translate ──
+
+    #[test]
+    fn test_user_language() {
+        assert_eq!(
+            user_language("## Preferences\n\n- Timezone: CET\n- Language: Italian\n"),
+            Some("Italian".to_string())
+        );
+        assert_eq!(user_language("- Language: (your preferred language)"), None);
+        assert_eq!(user_language("# User\n"), None);
+    }
+}
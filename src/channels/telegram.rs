@@ -2,19 +2,25 @@
 //!
 //! Uses long polling (`getUpdates`) so no public IP or webhook is needed.
 
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use anyhow::Result;
 use async_trait::async_trait;
+use once_cell::sync::Lazy;
 use regex::Regex;
 use serde_json::{json, Value};
 use tokio::sync::mpsc::UnboundedSender;
-use tracing::{debug, error, info, warn};
+use tracing::{debug, info, warn};
 
-use crate::bus::events::{InboundMessage, OutboundMessage};
+use crate::bus::events::{Attachment, AttachmentKind, InboundMessage, OutboundMessage};
 use crate::channels::base::Channel;
 use crate::config::schema::TelegramConfig;
+use crate::utils::http::shared_client;
+
+/// Maximum length of a Telegram text message.
+const MAX_MESSAGE_LEN: usize = 4096;
 
 /// Telegram channel using long-polling.
 pub struct TelegramChannel {
@@ -23,6 +29,8 @@ pub struct TelegramChannel {
     groq_api_key: String,
     running: Arc<AtomicBool>,
     client: reqwest::Client,
+    /// Message IDs of streamed replies still being edited, by stream ID.
+    stream_messages: Mutex<HashMap<String, i64>>,
 }
 
 impl TelegramChannel {
@@ -37,10 +45,161 @@ impl TelegramChannel {
             bus_tx,
             groq_api_key,
             running: Arc::new(AtomicBool::new(false)),
-            client: reqwest::Client::new(),
+            client: shared_client(),
+            stream_messages: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Call a Bot API method, returning its `result` or the API's error.
+    async fn _api(&self, method: &str, body: Value) -> Result<Value> {
+        let url = format!("https://api.telegram.org/bot{}/{}", self.config.token, method);
+        let data: Value = self.client.post(&url).json(&body).send().await?.json().await?;
+        if data.get("ok").and_then(|v| v.as_bool()) == Some(true) {
+            Ok(data.get("result").cloned().unwrap_or(Value::Null))
+        } else {
+            let description = data
+                .get("description")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown error");
+            Err(anyhow::anyhow!("Telegram {} failed: {}", method, description))
         }
     }
 
+    /// Replace the text of a previously sent message, preferring HTML and
+    /// falling back to plain text.
+    async fn _edit_text(&self, chat_id: i64, message_id: i64, content: &str) -> Result<()> {
+        let html = self
+            ._api(
+                "editMessageText",
+                json!({
+                    "chat_id": chat_id,
+                    "message_id": message_id,
+                    "text": markdown_to_telegram_html(content),
+                    "parse_mode": "HTML",
+                }),
+            )
+            .await;
+        if html.is_ok() {
+            return Ok(());
+        }
+        let plain = self
+            ._api(
+                "editMessageText",
+                json!({ "chat_id": chat_id, "message_id": message_id, "text": content }),
+            )
+            .await;
+        match plain {
+            // Editing to identical text is rejected; the message is already right.
+            Err(e) if e.to_string().contains("message is not modified") => Ok(()),
+            other => other.map(|_| ()),
+        }
+    }
+
+    /// Send the files attached to a reply, picking the Bot API method from
+    /// the kind of file. Local files are uploaded; for URLs Telegram fetches
+    /// the file itself. Failures are logged per file.
+    async fn _send_media(&self, chat_id: i64, attachments: &[Attachment]) {
+        for attachment in attachments {
+            let (method, field) = media_method(attachment);
+            let mut form = reqwest::multipart::Form::new().text("chat_id", chat_id.to_string());
+            if let Some(caption) = &attachment.caption {
+                form = form.text("caption", caption.clone());
+            }
+            form = if attachment.is_url() {
+                form.text(field, attachment.source.clone())
+            } else {
+                let path = std::path::Path::new(&attachment.source);
+                let bytes = match tokio::fs::read(path).await {
+                    Ok(b) => b,
+                    Err(e) => {
+                        warn!("Cannot read attachment {}: {}", path.display(), e);
+                        continue;
+                    }
+                };
+                let file_name = path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string();
+                form.part(field, reqwest::multipart::Part::bytes(bytes).file_name(file_name))
+            };
+            let url = format!("https://api.telegram.org/bot{}/{}", self.config.token, method);
+            match self.client.post(&url).multipart(form).send().await {
+                Ok(r) if r.status().is_success() => {}
+                Ok(r) => warn!("Telegram {} failed (HTTP {})", method, r.status()),
+                Err(e) => warn!("Telegram {} failed: {}", method, e),
+            }
+        }
+    }
+
+    /// Show a streamed reply: the first partial update sends a message, later
+    /// ones edit it, and the final message replaces it with formatted text.
+    ///
+    /// Returns `false` if the message should instead go through the normal
+    /// send path (a final message whose stream never produced a partial, or
+    /// one too long to edit into the streamed message, which is deleted).
+    async fn _send_streamed(
+        &self,
+        chat_id: i64,
+        stream_id: &str,
+        msg: &OutboundMessage,
+    ) -> Result<bool> {
+        let existing = self.stream_messages.lock().unwrap().get(stream_id).copied();
+
+        if !msg.is_partial() {
+            let Some(message_id) = existing else {
+                return Ok(false);
+            };
+            self.stream_messages.lock().unwrap().remove(stream_id);
+            if msg.content.chars().count() > MAX_MESSAGE_LEN {
+                // The reply is sent anew, so the partial one must not stay.
+                let deleted = self
+                    ._api(
+                        "deleteMessage",
+                        json!({ "chat_id": chat_id, "message_id": message_id }),
+                    )
+                    .await;
+                if let Err(e) = deleted {
+                    warn!("Cannot remove the streamed Telegram message: {}", e);
+                }
+                return Ok(false);
+            }
+            self._edit_text(chat_id, message_id, &msg.content).await?;
+            return Ok(true);
+        }
+
+        // Partial text is sent unformatted: half-written markdown rarely
+        // converts to valid HTML. Stop updating once it outgrows one message.
+        if msg.content.chars().count() > MAX_MESSAGE_LEN {
+            return Ok(true);
+        }
+        match existing {
+            Some(message_id) => {
+                let edit = self
+                    ._api(
+                        "editMessageText",
+                        json!({ "chat_id": chat_id, "message_id": message_id, "text": msg.content }),
+                    )
+                    .await;
+                if let Err(e) = edit {
+                    debug!("Telegram stream update skipped: {}", e);
+                }
+            }
+            None => {
+                let sent = self
+                    ._api("sendMessage", json!({ "chat_id": chat_id, "text": msg.content }))
+                    .await?;
+                if let Some(message_id) = sent.get("message_id").and_then(|v| v.as_i64()) {
+                    self.stream_messages
+                        .lock()
+                        .unwrap()
+                        .insert(stream_id.to_string(), message_id);
+                }
+            }
+        }
+        Ok(true)
+    }
+
     /// Process a single Telegram update.
     async fn _on_message(
         client: &reqwest::Client,
@@ -100,59 +259,47 @@ impl TelegramChannel {
             content_parts.push(caption.to_string());
         }
 
-        // Handle photo.
-        if let Some(photos) = message.get("photo").and_then(|v| v.as_array()) {
-            if !photos.is_empty() {
-                // Take the largest photo (last in the array).
-                if let Some(file_id) = photos
-                    .last()
-                    .and_then(|p| p.get("file_id"))
-                    .and_then(|v| v.as_str())
-                {
-                    let media_path =
-                        Self::_download_file(client, token, file_id, "image", ".jpg").await;
-                    if let Some(path) = media_path {
-                        content_parts.push(format!("[image: {}]", path));
-                    } else {
-                        content_parts.push("[image: download failed]".to_string());
+        // Download photos, voice notes and documents as attachments.
+        let mut attachments: Vec<Attachment> = Vec::new();
+        let photo = message
+            .get("photo")
+            .and_then(|v| v.as_array())
+            .and_then(|photos| photos.last()); // The largest size.
+        let files = [
+            (photo, "image", Some("image/jpeg")),
+            (message.get("voice"), "voice", None),
+            (message.get("audio"), "audio", None),
+            (message.get("document"), "file", None),
+        ];
+        for (file, media_type, mime) in files {
+            let Some(file) = file else {
+                continue;
+            };
+            let Some(file_id) = file.get("file_id").and_then(|v| v.as_str()) else {
+                continue;
+            };
+            let mime = mime
+                .or_else(|| file.get("mime_type").and_then(|v| v.as_str()))
+                .unwrap_or(if media_type == "voice" { "audio/ogg" } else { "application/octet-stream" });
+            let ext = file
+                .get("file_name")
+                .and_then(|v| v.as_str())
+                .and_then(|name| name.rsplit_once('.'))
+                .map(|(_, e)| format!(".{}", e))
+                .unwrap_or_else(|| mime_extension(mime).to_string());
+            match Self::_download_file(client, token, file_id, media_type, &ext).await {
+                Some(path) => {
+                    let mut attachment = Attachment::new(path).with_mime(mime);
+                    if attachment.size.is_none() {
+                        attachment.size = file.get("file_size").and_then(|v| v.as_u64());
                     }
+                    attachments.push(attachment);
                 }
+                None => content_parts.push(format!("[{}: download failed]", media_type)),
             }
         }
 
-        // Handle voice.
-        if let Some(voice) = message.get("voice") {
-            if let Some(file_id) = voice.get("file_id").and_then(|v| v.as_str()) {
-                let media_path =
-                    Self::_download_file(client, token, file_id, "voice", ".ogg").await;
-                if let Some(path) = media_path {
-                    content_parts.push(format!("[voice: {}]", path));
-                } else {
-                    content_parts.push("[voice: download failed]".to_string());
-                }
-            }
-        }
-
-        // Handle document.
-        if let Some(doc) = message.get("document") {
-            if let Some(file_id) = doc.get("file_id").and_then(|v| v.as_str()) {
-                let ext = doc
-                    .get("file_name")
-                    .and_then(|v| v.as_str())
-                    .and_then(|name| name.rsplit('.').next())
-                    .map(|e| format!(".{}", e))
-                    .unwrap_or_default();
-                let media_path =
-                    Self::_download_file(client, token, file_id, "file", &ext).await;
-                if let Some(path) = media_path {
-                    content_parts.push(format!("[file: {}]", path));
-                } else {
-                    content_parts.push("[file: download failed]".to_string());
-                }
-            }
-        }
-
-        let content = if content_parts.is_empty() {
+        let content = if content_parts.is_empty() && attachments.is_empty() {
             "[empty message]".to_string()
         } else {
             content_parts.join("\n")
@@ -179,9 +326,10 @@ impl TelegramChannel {
         let mut msg = InboundMessage::new(
             "telegram",
             &sender_id,
-            &chat_id.to_string(),
+            chat_id.to_string(),
             &content,
         );
+        msg.attachments = attachments;
         msg.metadata
             .insert("message_id".to_string(), json!(message_id));
         msg.metadata
@@ -329,6 +477,13 @@ impl Channel for TelegramChannel {
             .parse()
             .map_err(|_| anyhow::anyhow!("Invalid chat_id: {}", msg.chat_id))?;
 
+        if let Some(stream_id) = msg.stream_id() {
+            if self._send_streamed(chat_id, stream_id, msg).await? {
+                self._send_media(chat_id, &msg.attachments).await;
+                return Ok(());
+            }
+        }
+
         let html_content = markdown_to_telegram_html(&msg.content);
         let url = format!(
             "https://api.telegram.org/bot{}/sendMessage",
@@ -346,7 +501,7 @@ impl Channel for TelegramChannel {
             .send()
             .await;
 
-        match resp {
+        let result = match resp {
             Ok(r) if r.status().is_success() => Ok(()),
             Ok(_) => {
                 // Fallback to plain text if HTML fails.
@@ -363,7 +518,14 @@ impl Channel for TelegramChannel {
                 Ok(())
             }
             Err(e) => Err(anyhow::anyhow!("Failed to send Telegram message: {}", e)),
-        }
+        };
+        result?;
+        self._send_media(chat_id, &msg.attachments).await;
+        Ok(())
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
     }
 
     fn is_running(&self) -> bool {
@@ -371,6 +533,27 @@ impl Channel for TelegramChannel {
     }
 }
 
+/// File extension for a downloaded file without a name.
+fn mime_extension(mime: &str) -> &'static str {
+    match mime {
+        "image/jpeg" => ".jpg",
+        "audio/ogg" => ".ogg",
+        "audio/mpeg" => ".mp3",
+        "application/pdf" => ".pdf",
+        _ => "",
+    }
+}
+
+/// Bot API method and form field for sending a file.
+fn media_method(attachment: &Attachment) -> (&'static str, &'static str) {
+    match attachment.kind() {
+        AttachmentKind::Audio if attachment.mime == "audio/ogg" => ("sendVoice", "voice"),
+        AttachmentKind::Audio => ("sendAudio", "audio"),
+        AttachmentKind::Image => ("sendPhoto", "photo"),
+        AttachmentKind::Document => ("sendDocument", "document"),
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Markdown -> Telegram HTML conversion
 // ---------------------------------------------------------------------------
@@ -385,8 +568,9 @@ pub fn markdown_to_telegram_html(text: &str) -> String {
 
     // 1. Extract and protect code blocks.
     let mut code_blocks: Vec<String> = Vec::new();
-    let re_codeblock = Regex::new(r"```[\w]*\n?([\s\S]*?)```").unwrap();
-    let text = re_codeblock
+    static RE_CODEBLOCK: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"```[\w]*\n?([\s\S]*?)```").unwrap());
+    let text = RE_CODEBLOCK
         .replace_all(text, |caps: &regex::Captures| {
             let idx = code_blocks.len();
             code_blocks.push(caps[1].to_string());
@@ -396,8 +580,8 @@ pub fn markdown_to_telegram_html(text: &str) -> String {
 
     // 2. Extract and protect inline code.
     let mut inline_codes: Vec<String> = Vec::new();
-    let re_inline = Regex::new(r"`([^`]+)`").unwrap();
-    let text = re_inline
+    static RE_INLINE: Lazy<Regex> = Lazy::new(|| Regex::new(r"`([^`]+)`").unwrap());
+    let text = RE_INLINE
         .replace_all(&text, |caps: &regex::Captures| {
             let idx = inline_codes.len();
             inline_codes.push(caps[1].to_string());
@@ -406,12 +590,12 @@ pub fn markdown_to_telegram_html(text: &str) -> String {
         .to_string();
 
     // 3. Headers -> plain text.
-    let re_header = Regex::new(r"(?m)^#{1,6}\s+(.+)$").unwrap();
-    let text = re_header.replace_all(&text, "$1").to_string();
+    static RE_HEADER: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?m)^#{1,6}\s+(.+)$").unwrap());
+    let text = RE_HEADER.replace_all(&text, "$1").to_string();
 
     // 4. Blockquotes -> plain text.
-    let re_blockquote = Regex::new(r"(?m)^>\s*(.*)$").unwrap();
-    let text = re_blockquote.replace_all(&text, "$1").to_string();
+    static RE_BLOCKQUOTE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?m)^>\s*(.*)$").unwrap());
+    let text = RE_BLOCKQUOTE.replace_all(&text, "$1").to_string();
 
     // 5. Escape HTML special characters.
     let text = text
@@ -420,30 +604,31 @@ pub fn markdown_to_telegram_html(text: &str) -> String {
         .replace('>', "&gt;");
 
     // 6. Links [text](url).
-    let re_link = Regex::new(r"\[([^\]]+)\]\(([^)]+)\)").unwrap();
-    let text = re_link
+    static RE_LINK: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[([^\]]+)\]\(([^)]+)\)").unwrap());
+    let text = RE_LINK
         .replace_all(&text, r#"<a href="$2">$1</a>"#)
         .to_string();
 
     // 7. Bold **text** or __text__.
-    let re_bold_star = Regex::new(r"\*\*(.+?)\*\*").unwrap();
-    let text = re_bold_star.replace_all(&text, "<b>$1</b>").to_string();
-    let re_bold_under = Regex::new(r"__(.+?)__").unwrap();
-    let text = re_bold_under.replace_all(&text, "<b>$1</b>").to_string();
+    static RE_BOLD_STAR: Lazy<Regex> = Lazy::new(|| Regex::new(r"\*\*(.+?)\*\*").unwrap());
+    let text = RE_BOLD_STAR.replace_all(&text, "<b>$1</b>").to_string();
+    static RE_BOLD_UNDER: Lazy<Regex> = Lazy::new(|| Regex::new(r"__(.+?)__").unwrap());
+    let text = RE_BOLD_UNDER.replace_all(&text, "<b>$1</b>").to_string();
 
     // 8. Italic _text_ (avoid matching inside words).
     // The regex crate does not support look-around, so we capture the
     // preceding and following non-alphanumeric characters and restore them.
-    let re_italic = Regex::new(r"(^|[^a-zA-Z0-9])_([^_]+)_($|[^a-zA-Z0-9])").unwrap();
-    let text = re_italic.replace_all(&text, "$1<i>$2</i>$3").to_string();
+    static RE_ITALIC: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(^|[^a-zA-Z0-9])_([^_]+)_($|[^a-zA-Z0-9])").unwrap());
+    let text = RE_ITALIC.replace_all(&text, "$1<i>$2</i>$3").to_string();
 
     // 9. Strikethrough ~~text~~.
-    let re_strike = Regex::new(r"~~(.+?)~~").unwrap();
-    let text = re_strike.replace_all(&text, "<s>$1</s>").to_string();
+    static RE_STRIKE: Lazy<Regex> = Lazy::new(|| Regex::new(r"~~(.+?)~~").unwrap());
+    let text = RE_STRIKE.replace_all(&text, "<s>$1</s>").to_string();
 
     // 10. Bullet lists.
-    let re_bullet = Regex::new(r"(?m)^[-*]\s+").unwrap();
-    let mut text = re_bullet.replace_all(&text, "\u{2022} ").to_string();
+    static RE_BULLET: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?m)^[-*]\s+").unwrap());
+    let mut text = RE_BULLET.replace_all(&text, "\u{2022} ").to_string();
 
     // 11. Restore inline code.
     for (i, code) in inline_codes.iter().enumerate() {
@@ -691,4 +876,13 @@ systemctl restart service-b
         assert!(result.contains("<pre><code>"));
         assert!(result.contains("systemctl restart service-b"));
     }
+
+    #[test]
+    fn test_media_method_by_extension() {
+        let method = |source: &str| media_method(&Attachment::new(source));
+        assert_eq!(method("/m/reply.ogg"), ("sendVoice", "voice"));
+        assert_eq!(method("song.MP3"), ("sendAudio", "audio"));
+        assert_eq!(method("chart.png"), ("sendPhoto", "photo"));
+        assert_eq!(method("report.pdf"), ("sendDocument", "document"));
+    }
 }
@@ -251,7 +251,7 @@ impl Channel for WhatsAppChannel {
         let payload = json!({
             "type": "send",
             "to": msg.chat_id,
-            "text": msg.content,
+            "text": msg.content_with_links(),
         });
 
         tx.send(serde_json::to_string(&payload).unwrap_or_default())
@@ -0,0 +1,313 @@
+//! At-rest encryption of secrets in the config file.
+//!
+//! `nanoclaw config encrypt` replaces API keys, tokens and other secret
+//! values in the file with ciphertext, so the config can be synced (Dropbox,
+//! git) without exposing them. Values are encrypted with ChaCha20-Poly1305
+//! using either a key derived from a passphrase (Argon2) or a random machine
+//! key kept in the OS keyring. They are decrypted when the config is loaded:
+//! passphrase-encrypted values need the passphrase in `NANOCLAW_CONFIG_PASSPHRASE`.
+//!
+//! Encrypted values look like `enc:pass:<salt>:<data>` or
+//! `enc:machine:<data>`, where `<data>` is the base64 nonce and ciphertext.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::config::schema::Config;
+use crate::config::secrets::{get_secret, rewrite_strings, set_secret};
+
+/// Environment variable holding the passphrase for encrypted values.
+pub const PASSPHRASE_VAR: &str = "NANOCLAW_CONFIG_PASSPHRASE";
+
+/// Prefix of encrypted values.
+const ENC_PREFIX: &str = "enc:";
+
+/// Keyring entry holding the machine key.
+const MACHINE_KEY_ENTRY: &str = "config-key";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// Where the encryption key comes from.
+pub enum KeySource {
+    /// Derived from a passphrase; portable between machines.
+    Passphrase(String),
+    /// A random key in this machine's OS keyring, created on first use.
+    Machine,
+}
+
+/// Whether a config key (or header / env var name) holds a secret. The one
+/// classifier shared by encryption, secret references and log redaction.
+pub fn is_secret_key(key: &str) -> bool {
+    let key = key.replace(['_', '-'], "").to_lowercase();
+    // A bare `key` is the path of a TLS key, not the key itself.
+    key == "authorization"
+        || (key != "key"
+            && ["key", "token", "secret", "password"]
+                .iter()
+                .any(|suffix| key.ends_with(suffix)))
+}
+
+/// Whether a config value is encrypted.
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENC_PREFIX)
+}
+
+// ---------------------------------------------------------------------------
+// Encryption
+// ---------------------------------------------------------------------------
+
+/// Encrypt the secret values of a raw config in place. Empty values,
+/// references (`keyring:`, `env:`, `file:`) and values that are already
+/// encrypted are left alone. Returns the number of values encrypted.
+pub fn encrypt_config(raw: &mut Value, source: &KeySource) -> Result<usize> {
+    let (key, tag) = match source {
+        KeySource::Passphrase(passphrase) => {
+            let mut salt = [0u8; SALT_LEN];
+            OsRng.fill_bytes(&mut salt);
+            let key = derive_key(passphrase, &salt)?;
+            (key, format!("pass:{}", STANDARD.encode(salt)))
+        }
+        KeySource::Machine => (machine_key(true)?, "machine".to_string()),
+    };
+    let cipher = ChaCha20Poly1305::new(&key);
+
+    let mut count = 0;
+    let mut result = Ok(());
+    for_each_secret(raw, &mut |value| {
+        let reference = ["keyring:", "env:", "file:"].iter().any(|p| value.starts_with(p));
+        if value.is_empty() || is_encrypted(value) || reference {
+            return;
+        }
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        match cipher.encrypt(&nonce, value.as_bytes()) {
+            Ok(ciphertext) => {
+                let mut data = nonce.to_vec();
+                data.extend(ciphertext);
+                *value = format!("{}{}:{}", ENC_PREFIX, tag, STANDARD.encode(data));
+                count += 1;
+            }
+            Err(e) => result = Err(anyhow!("encryption failed: {}", e)),
+        }
+    });
+    result.map(|_| count)
+}
+
+/// Call `f` on every string under a secret key of `node`.
+fn for_each_secret(node: &mut Value, f: &mut impl FnMut(&mut String)) {
+    match node {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match value {
+                    Value::String(s) if is_secret_key(key) => f(s),
+                    _ => for_each_secret(value, f),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| for_each_secret(v, f)),
+        _ => {}
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Decryption
+// ---------------------------------------------------------------------------
+
+/// Decrypts values, deriving each key once.
+pub struct Decryptor {
+    passphrase: Option<String>,
+    keys: HashMap<String, Key>,
+}
+
+impl Decryptor {
+    pub fn new(passphrase: Option<String>) -> Self {
+        Self {
+            passphrase,
+            keys: HashMap::new(),
+        }
+    }
+
+    /// Decrypt one `enc:` value.
+    pub fn decrypt(&mut self, value: &str) -> Result<String> {
+        let rest = value
+            .strip_prefix(ENC_PREFIX)
+            .ok_or_else(|| anyhow!("not an encrypted value"))?;
+        let (tag, data) = rest
+            .rsplit_once(':')
+            .ok_or_else(|| anyhow!("malformed encrypted value"))?;
+        let key = self._key(tag)?;
+
+        let data = STANDARD.decode(data)?;
+        if data.len() < NONCE_LEN {
+            bail!("malformed encrypted value");
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plain = ChaCha20Poly1305::new(&key)
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("cannot decrypt secret: wrong passphrase or key"))?;
+        Ok(String::from_utf8(plain)?)
+    }
+
+    fn _key(&mut self, tag: &str) -> Result<Key> {
+        if let Some(key) = self.keys.get(tag) {
+            return Ok(*key);
+        }
+        let key = match tag.split_once(':') {
+            Some(("pass", salt)) => {
+                let Some(passphrase) = &self.passphrase else {
+                    bail!("secret is encrypted with a passphrase; set {}", PASSPHRASE_VAR);
+                };
+                derive_key(passphrase, &STANDARD.decode(salt)?)?
+            }
+            None if tag == "machine" => machine_key(false)?,
+            _ => bail!("unknown encryption '{}'", tag),
+        };
+        self.keys.insert(tag.to_string(), key);
+        Ok(key)
+    }
+}
+
+/// Decrypt the encrypted values of a raw config in place (for
+/// `config decrypt`). Returns the number of values decrypted.
+pub fn decrypt_config(raw: &mut Value, passphrase: Option<String>) -> Result<usize> {
+    let mut decryptor = Decryptor::new(passphrase);
+    let mut count = 0;
+    let mut result = Ok(());
+    for_each_secret(raw, &mut |value| {
+        if !is_encrypted(value) || result.is_err() {
+            return;
+        }
+        match decryptor.decrypt(value) {
+            Ok(plain) => {
+                *value = plain;
+                count += 1;
+            }
+            Err(e) => result = Err(e),
+        }
+    });
+    result.map(|_| count)
+}
+
+/// Decrypt encrypted values at load time, with the passphrase from the
+/// environment. Values that cannot be decrypted are logged and become empty.
+pub fn decrypt_secrets(config: Config) -> Config {
+    decrypt_with(config, std::env::var(PASSPHRASE_VAR).ok())
+}
+
+fn decrypt_with(config: Config, passphrase: Option<String>) -> Config {
+    let mut decryptor = Decryptor::new(passphrase);
+    rewrite_strings(config, |_, s| {
+        if !is_encrypted(s) {
+            return false;
+        }
+        *s = decryptor.decrypt(s).unwrap_or_else(|e| {
+            warn!("{}", e);
+            String::new()
+        });
+        true
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Keys
+// ---------------------------------------------------------------------------
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+/// The machine key from the OS keyring, created when `create` is set and
+/// there is none yet.
+fn machine_key(create: bool) -> Result<Key> {
+    match get_secret(MACHINE_KEY_ENTRY) {
+        Ok(encoded) => {
+            let bytes = STANDARD.decode(encoded.trim())?;
+            if bytes.len() != 32 {
+                bail!("machine key in the keyring is corrupt");
+            }
+            Ok(*Key::from_slice(&bytes))
+        }
+        Err(_) if create => {
+            let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+            set_secret(MACHINE_KEY_ENTRY, &STANDARD.encode(key))?;
+            Ok(key)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_is_secret_key() {
+        for key in [
+            "apiKey",
+            "api_key",
+            "token",
+            "appSecret",
+            "encryptKey",
+            "verificationToken",
+            "Authorization",
+            "X-Api-Key",
+            "GITHUB_TOKEN",
+        ] {
+            assert!(is_secret_key(key), "{}", key);
+        }
+        for key in ["key", "appId", "apiBase", "model", "allowFrom", "maxTokens", "redactSecrets"] {
+            assert!(!is_secret_key(key), "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_passphrase_round_trip() {
+        let mut raw = json!({
+            "providers": {"openrouter": {"apiKey": "sk-or-1", "apiBase": "https://x"}},
+            "channels": {"telegram": {"token": "123:abc"}, "feishu": {"appSecret": ""}},
+            "tools": {"web": {"search": {"apiKey": "keyring:brave"}}}
+        });
+        let source = KeySource::Passphrase("hunter2".to_string());
+        assert_eq!(encrypt_config(&mut raw, &source).unwrap(), 2);
+
+        let api_key = raw["providers"]["openrouter"]["apiKey"].as_str().unwrap();
+        assert!(api_key.starts_with("enc:pass:") && !api_key.contains("sk-or-1"));
+        assert_eq!(raw["providers"]["openrouter"]["apiBase"], "https://x");
+        assert_eq!(raw["channels"]["feishu"]["appSecret"], "");
+        assert_eq!(raw["tools"]["web"]["search"]["apiKey"], "keyring:brave");
+        // Already encrypted values are not encrypted twice.
+        assert_eq!(encrypt_config(&mut raw, &source).unwrap(), 0);
+
+        let config: Config = serde_json::from_value(raw.clone()).unwrap();
+        let config = decrypt_with(config, Some("hunter2".to_string()));
+        assert_eq!(config.providers.openrouter.api_key, "sk-or-1");
+        assert_eq!(config.channels.telegram.token, "123:abc");
+
+        assert!(decrypt_config(&mut raw.clone(), Some("wrong".to_string())).is_err());
+        assert_eq!(decrypt_config(&mut raw, Some("hunter2".to_string())).unwrap(), 2);
+        assert_eq!(raw["channels"]["telegram"]["token"], "123:abc");
+    }
+
+    #[test]
+    fn test_undecryptable_values_become_empty() {
+        let mut raw = json!({"providers": {"openrouter": {"apiKey": "sk-or-1"}}});
+        encrypt_config(&mut raw, &KeySource::Passphrase("pw".to_string())).unwrap();
+        let config: Config = serde_json::from_value(raw).unwrap();
+        let config = decrypt_with(config, None);
+        assert_eq!(config.providers.openrouter.api_key, "");
+    }
+}
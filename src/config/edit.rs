@@ -0,0 +1,619 @@
+//! Reading, changing and checking config values by key path.
+//!
+//! Backs `nanoclaw config get|set|validate`. Key paths are dotted, e.g.
+//! `channels.telegram.token`; each part matches a config key ignoring case
+//! and underscores, so `agents.defaults.max_tool_iterations` works as well
+//! as `agents.defaults.maxToolIterations`. List items are addressed by index.
+
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+use serde_json::Value;
+
+use crate::bus::bridge;
+use crate::channels::broadcast;
+use crate::bus::events::SystemEvent;
+use crate::config::include::merge_includes;
+use crate::config::loader::parse_value_like;
+use crate::config::migrate::migrate;
+use crate::config::schema::Config;
+use crate::cron::service::parse_timezone;
+
+/// Normalized form of a key for matching.
+fn normalize(key: &str) -> String {
+    key.replace(['_', '-'], "").to_lowercase()
+}
+
+/// Split a dotted path, rejecting empty parts.
+fn split_path(path: &str) -> Result<Vec<&str>> {
+    let parts: Vec<&str> = path.split('.').collect();
+    if parts.iter().any(|p| p.is_empty()) {
+        bail!("invalid key path '{}'", path);
+    }
+    Ok(parts)
+}
+
+/// Find the child of `node` named `part`, returning its actual key.
+fn child_key(node: &Value, part: &str) -> Option<String> {
+    match node {
+        Value::Object(map) => map.keys().find(|k| normalize(k) == normalize(part)).cloned(),
+        Value::Array(items) => part
+            .parse::<usize>()
+            .ok()
+            .filter(|i| *i < items.len())
+            .map(|i| i.to_string()),
+        _ => None,
+    }
+}
+
+fn child_mut<'a>(node: &'a mut Value, key: &str) -> Option<&'a mut Value> {
+    match node {
+        Value::Object(map) => map.get_mut(key),
+        Value::Array(items) => items.get_mut(key.parse::<usize>().ok()?),
+        _ => None,
+    }
+}
+
+/// Error for a path part that matches nothing, listing what would.
+fn unknown_key(node: &Value, part: &str, parent: &str) -> anyhow::Error {
+    let known = match node {
+        Value::Object(map) if !map.is_empty() => {
+            format!(" Known keys: {}.", map.keys().cloned().collect::<Vec<_>>().join(", "))
+        }
+        Value::Array(items) => format!(" It is a list of {} items.", items.len()),
+        _ => String::new(),
+    };
+    let parent = if parent.is_empty() { "the config" } else { parent };
+    anyhow!("no key '{}' in {}.{}", part, parent, known)
+}
+
+/// The value at `path` in `config`.
+pub fn get_value(config: &Config, path: &str) -> Result<Value> {
+    let mut node = serde_json::to_value(config)?;
+    let mut walked: Vec<String> = Vec::new();
+    for part in split_path(path)? {
+        let key = child_key(&node, part).ok_or_else(|| unknown_key(&node, part, &walked.join(".")))?;
+        node = child_mut(&mut node, &key).map(Value::take).unwrap_or_default();
+        walked.push(key);
+    }
+    Ok(node)
+}
+
+/// Set the value at `path` from `raw`, parsed as the type of the current
+/// value (JSON for lists and objects). A missing last part is added as a
+/// new key, which must be one the config knows about.
+///
+/// Returns the updated config; it is only valid if every value still has
+/// the right type.
+pub fn set_value(config: &Config, path: &str, raw: &str) -> Result<Config> {
+    let parts = split_path(path)?;
+    let mut tree = serde_json::to_value(config)?;
+
+    let mut node = &mut tree;
+    let mut walked: Vec<String> = Vec::new();
+    for (i, part) in parts.iter().enumerate() {
+        let last = i + 1 == parts.len();
+        match child_key(node, part) {
+            Some(key) => {
+                node = child_mut(node, &key).expect("key was just found");
+                walked.push(key);
+            }
+            None if last && node.is_object() => {
+                let map = node.as_object_mut().expect("checked above");
+                node = map.entry(part.to_string()).or_insert(Value::Null);
+                walked.push(part.to_string());
+            }
+            None => return Err(unknown_key(node, part, &walked.join("."))),
+        }
+    }
+    *node = parse_value_like(node, raw);
+
+    let updated: Config = serde_json::from_value(tree)
+        .map_err(|e| anyhow!("invalid value for {}: {}", walked.join("."), e))?;
+    // Keys the schema does not know are dropped on the round trip.
+    if get_value(&updated, &walked.join(".")).is_err() {
+        bail!("'{}' is not a config key", walked.join("."));
+    }
+    Ok(updated)
+}
+
+// ---------------------------------------------------------------------------
+// Validation
+// ---------------------------------------------------------------------------
+
+/// A problem found in a config file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    /// Errors make the config unusable; warnings point at likely mistakes.
+    pub error: bool,
+    pub message: String,
+}
+
+impl Issue {
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            error: true,
+            message: message.into(),
+        }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        Self {
+            error: false,
+            message: message.into(),
+        }
+    }
+}
+
+/// A parsed config file and what was wrong with it.
+#[derive(Debug)]
+pub struct ParsedConfig {
+    pub config: Config,
+    /// Paths of keys the config does not know; they are ignored.
+    pub unknown: Vec<String>,
+    /// Included fragments that could not be merged.
+    pub include_problems: Vec<String>,
+}
+
+/// Parse the text of a config file, migrating it in memory if it is from
+/// an older version. With `dir`, the directory of the file, fragments listed
+/// under `include` are merged in. Type errors name the key path of the bad
+/// value; keys the config does not know are returned (as paths) so they can
+/// be reported instead of silently ignored.
+pub fn parse_config(contents: &str, dir: Option<&Path>) -> Result<ParsedConfig> {
+    let mut raw: Value =
+        serde_json::from_str(contents).map_err(|e| anyhow!("invalid JSON: {}", e))?;
+    migrate(&mut raw);
+    let include_problems = match dir {
+        Some(dir) => merge_includes(&mut raw, dir),
+        None => Vec::new(),
+    };
+    let config: Config = serde_path_to_error::deserialize(raw.clone())
+        .map_err(|e| anyhow!("invalid value at {}: {}", e.path(), e.inner()))?;
+
+    let mut unknown = Vec::new();
+    let known = serde_json::to_value(&config)?;
+    unknown_keys(&raw, &known, "", &mut unknown);
+    Ok(ParsedConfig {
+        config,
+        unknown,
+        include_problems,
+    })
+}
+
+/// Check the text of a config file in `dir`: JSON syntax, included
+/// fragments, value types, unknown keys and settings that cannot work
+/// (missing tokens, bad patterns, ...).
+pub fn validate(contents: &str, dir: &Path) -> Vec<Issue> {
+    let parsed = match parse_config(contents, Some(dir)) {
+        Ok(parsed) => parsed,
+        Err(e) => return vec![Issue::error(e.to_string())],
+    };
+
+    let mut issues: Vec<Issue> = parsed
+        .include_problems
+        .into_iter()
+        .map(Issue::error)
+        .collect();
+    issues.extend(
+        parsed
+            .unknown
+            .iter()
+            .map(|path| Issue::warning(format!("unknown key at {} (ignored)", path))),
+    );
+    check_settings(&parsed.config, &mut issues);
+    issues
+}
+
+/// Collect paths of keys in `raw` that did not survive parsing (typos, old
+/// names). Map-valued settings keep all their keys, so only struct fields
+/// can be reported. `$`-keys such as `$schema` are for editors and skipped.
+fn unknown_keys(raw: &Value, known: &Value, path: &str, unknown: &mut Vec<String>) {
+    let (Value::Object(raw), Value::Object(known)) = (raw, known) else {
+        return;
+    };
+    for (key, value) in raw {
+        let full = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", path, key)
+        };
+        match known.get(key) {
+            Some(k) => unknown_keys(value, k, &full, unknown),
+            None if value.is_null() || key.starts_with('$') => {}
+            None => unknown.push(full),
+        }
+    }
+}
+
+fn check_settings(config: &Config, issues: &mut Vec<Issue>) {
+    if config.get_api_key().is_none() && !config.agents.defaults.model.starts_with("bedrock/") {
+        issues.push(Issue::error(
+            "no provider API key set (e.g. providers.openrouter.apiKey)",
+        ));
+    }
+
+    let channels = &config.channels;
+    if channels.telegram.enabled && channels.telegram.token.is_empty() {
+        issues.push(Issue::error("channels.telegram is enabled but has no token"));
+    }
+    if channels.feishu.enabled
+        && (channels.feishu.app_id.is_empty() || channels.feishu.app_secret.is_empty())
+    {
+        issues.push(Issue::error(
+            "channels.feishu is enabled but appId or appSecret is missing",
+        ));
+    }
+
+    if !config.gateway.timezone.is_empty() {
+        if let Err(e) = parse_timezone(&config.gateway.timezone) {
+            issues.push(Issue::error(format!("gateway.timezone: {}", e)));
+        }
+    }
+
+    let mode = config.agents.defaults.mid_turn_messages.as_str();
+    if !["queue", "restart", "cancel", "inject"].contains(&mode) {
+        issues.push(Issue::warning(format!(
+            "agents.defaults.midTurnMessages '{}' is unknown; messages will be queued",
+            mode
+        )));
+    }
+
+    let search = &config.tools.web.search;
+    match search.backend.as_str() {
+        "brave" | "" | "duckduckgo" | "tavily" => {}
+        "searxng" if search.url.is_empty() => issues.push(Issue::error(
+            "tools.web.search.backend is searxng but tools.web.search.url is not set",
+        )),
+        "google" if search.engine_id.is_empty() => issues.push(Issue::error(
+            "tools.web.search.backend is google but tools.web.search.engineId is not set",
+        )),
+        "searxng" | "google" => {}
+        other => issues.push(Issue::warning(format!(
+            "tools.web.search.backend '{}' is unknown; brave will be used",
+            other
+        ))),
+    }
+
+    for entry in &config.tools.web.fetch.allowed_hosts {
+        if !crate::agent::tools::ssrf::is_valid_entry(entry) {
+            issues.push(Issue::error(format!(
+                "tools.web.fetch.allowedHosts: '{}' is not a host, address or network",
+                entry
+            )));
+        }
+    }
+
+    for entry in &config.gateway.api_tokens {
+        if !["read", "send"].contains(&entry.scope.as_str()) {
+            issues.push(Issue::error(format!(
+                "gateway.apiTokens '{}': unknown scope '{}' (read or send)",
+                entry.name, entry.scope
+            )));
+        }
+    }
+
+    let tls = &config.gateway.tls;
+    if tls.is_enabled() {
+        if tls.cert.is_empty() || tls.key.is_empty() {
+            issues.push(Issue::error("gateway.tls needs both cert and key"));
+        }
+        for (name, value, path) in [
+            ("cert", &tls.cert, tls.cert_path()),
+            ("key", &tls.key, tls.key_path()),
+        ] {
+            if !value.is_empty() && !path.is_file() {
+                issues.push(Issue::error(format!(
+                    "gateway.tls.{}: {} does not exist",
+                    name,
+                    path.display()
+                )));
+            }
+        }
+    }
+
+    for hook in &config.webhooks {
+        if !hook.url.starts_with("http://") && !hook.url.starts_with("https://") {
+            issues.push(Issue::error(format!("webhooks: '{}' is not an http(s) URL", hook.url)));
+        }
+    }
+
+    let rotation = &config.observability.logs.rotation;
+    if crate::logs::Rotation::parse(rotation).is_none() {
+        issues.push(Issue::error(format!(
+            "observability.logs.rotation: '{}' is not hour, day or never",
+            rotation
+        )));
+    }
+
+    for pattern in &config.observability.transcripts.redact {
+        if let Err(e) = regex::Regex::new(pattern) {
+            issues.push(Issue::error(format!(
+                "observability.transcripts.redact: '{}' is not a valid pattern: {}",
+                pattern, e
+            )));
+        }
+    }
+    let roles = &config.agents.roles;
+    if !matches!(roles.default_role.as_str(), "guest" | "trusted") {
+        issues.push(Issue::error(format!(
+            "agents.roles.defaultRole: '{}' is not guest or trusted",
+            roles.default_role
+        )));
+    }
+
+    for kind in &config.privacy.kinds {
+        if crate::providers::pii::PiiKind::parse(kind).is_none() {
+            issues.push(Issue::error(format!(
+                "privacy.kinds: '{}' is not email, phone or address",
+                kind
+            )));
+        }
+    }
+    for pattern in &config.observability.redaction.patterns {
+        if let Err(e) = regex::Regex::new(pattern) {
+            issues.push(Issue::error(format!(
+                "observability.redaction.patterns: '{}' is not a valid pattern: {}",
+                pattern, e
+            )));
+        }
+    }
+
+    let otlp = &config.observability.otlp.endpoint;
+    if !otlp.is_empty() && !otlp.starts_with("http://") && !otlp.starts_with("https://") {
+        issues.push(Issue::error(format!(
+            "observability.otlp.endpoint: '{}' is not an http(s) URL",
+            otlp
+        )));
+    }
+
+    if config.bridge.is_enabled() {
+        if let Err(e) = bridge::Target::parse(&config.bridge.url) {
+            issues.push(Issue::error(format!("bridge.url: {}", e)));
+        }
+    }
+
+    for (name, members) in &config.groups {
+        for member in members {
+            match broadcast::parse_target(member) {
+                Some((channel, _)) if channel != broadcast::GROUP_CHANNEL => {}
+                _ => issues.push(Issue::error(format!(
+                    "groups.{}: '{}' is not a channel:chat_id recipient",
+                    name, member
+                ))),
+            }
+        }
+    }
+
+    for rule in &config.guardrails.rules {
+        if let Err(e) = Regex::new(&rule.pattern) {
+            issues.push(Issue::error(format!("guardrails rule '{}': {}", rule.name, e)));
+        }
+    }
+
+    for (channel, steps) in &config.post_processing {
+        for step in steps {
+            if !["signature", "translate", "speech"].contains(&step.kind.as_str()) {
+                issues.push(Issue::warning(format!(
+                    "postProcessing.{}: unknown type '{}'",
+                    channel, step.kind
+                )));
+            }
+        }
+    }
+
+    let agents = &config.agents;
+    for profile in &agents.profiles {
+        if let Some(name) = &profile.workspace {
+            if !agents.workspaces.iter().any(|ws| &ws.name == name) {
+                issues.push(Issue::error(format!(
+                    "profile '{}' uses workspace '{}', which is not in agents.workspaces",
+                    profile.name, name
+                )));
+            }
+        }
+    }
+
+    let bundled = crate::agent::builtin_skills::names();
+    for name in &agents.skills.builtin {
+        if !bundled.contains(name) {
+            issues.push(Issue::warning(format!(
+                "agents.skills.builtin: unknown skill '{}' (bundled: {})",
+                name,
+                bundled.join(", ")
+            )));
+        }
+    }
+
+    if !["deny", "warn", "off"].contains(&agents.skills.tool_scope.as_str()) {
+        issues.push(Issue::warning(format!(
+            "agents.skills.toolScope: unknown mode '{}' (use deny, warn or off); scopes are off",
+            agents.skills.tool_scope
+        )));
+    }
+
+    let hb = &config.heartbeat;
+    if hb.enabled && hb.interval_secs == 0 {
+        issues.push(Issue::error(
+            "heartbeat.intervalSecs must be positive; set heartbeat.enabled to false to turn it off",
+        ));
+    }
+    if hb.enabled
+        && hb.adaptive
+        && !(hb.active_interval_secs..=hb.max_interval_secs).contains(&hb.interval_secs)
+    {
+        issues.push(Issue::warning(
+            "heartbeat.intervalSecs should lie between activeIntervalSecs and maxIntervalSecs",
+        ));
+    }
+    if hb.enabled && hb.maintenance.enabled && hb.maintenance.interval_hours == 0 {
+        issues.push(Issue::error(
+            "heartbeat.maintenance.intervalHours must be positive; set maintenance.enabled to false to turn it off",
+        ));
+    }
+    if hb.enabled && hb.channel.is_empty() != hb.chat_id.is_empty() {
+        issues.push(Issue::warning(
+            "heartbeat needs both channel and chatId to send proactive messages",
+        ));
+    }
+
+    for kind in &config.system_events.react {
+        if !SystemEvent::KINDS.contains(&kind.as_str()) {
+            issues.push(Issue::warning(format!(
+                "systemEvents.react: unknown event type '{}'",
+                kind
+            )));
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Schema
+// ---------------------------------------------------------------------------
+
+/// JSON Schema of the config file, for editor completion and checking.
+/// Unlike the loader, the schema rejects unknown keys in config sections so
+/// editors flag typos as you type.
+pub fn json_schema() -> Value {
+    let mut schema = serde_json::to_value(schemars::schema_for!(Config)).unwrap_or_default();
+    close_objects(&mut schema);
+    if let Some(props) = schema.get_mut("properties").and_then(Value::as_object_mut) {
+        props.insert(
+            "$schema".to_string(),
+            serde_json::json!({"type": "string", "description": "Schema reference for editors."}),
+        );
+    }
+    schema
+}
+
+/// Forbid extra keys on every object schema that lists its properties.
+fn close_objects(node: &mut Value) {
+    match node {
+        Value::Object(map) => {
+            if map.contains_key("properties") && !map.contains_key("additionalProperties") {
+                map.insert("additionalProperties".to_string(), Value::Bool(false));
+            }
+            map.values_mut().for_each(close_objects);
+        }
+        Value::Array(items) => items.iter_mut().for_each(close_objects),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ── get / set ──
+
+    #[test]
+    fn test_get_value_ignores_case_and_underscores() {
+        let config = Config::default();
+        assert_eq!(get_value(&config, "gateway.port").unwrap(), 18790);
+        assert_eq!(
+            get_value(&config, "agents.defaults.max_tool_iterations").unwrap(),
+            20
+        );
+        let err = get_value(&config, "gateway.prot").unwrap_err().to_string();
+        assert!(err.contains("no key 'prot' in gateway") && err.contains("port"), "{}", err);
+    }
+
+    #[test]
+    fn test_set_value_parses_by_type() {
+        let config = Config::default();
+        let config = set_value(&config, "channels.telegram.token", "123:abc").unwrap();
+        let config = set_value(&config, "channels.telegram.enabled", "true").unwrap();
+        let config = set_value(&config, "channels.telegram.allowFrom", r#"["42"]"#).unwrap();
+        let config = set_value(&config, "gateway.port", "9000").unwrap();
+        assert_eq!(config.channels.telegram.token, "123:abc");
+        assert!(config.channels.telegram.enabled);
+        assert_eq!(config.channels.telegram.allow_from, vec!["42"]);
+        assert_eq!(config.gateway.port, 9000);
+    }
+
+    #[test]
+    fn test_set_value_adds_optional_keys_only() {
+        let config = Config::default();
+        let config = set_value(&config, "providers.vllm.apiBase", "http://localhost:8000/v1").unwrap();
+        assert_eq!(
+            config.providers.vllm.api_base.as_deref(),
+            Some("http://localhost:8000/v1")
+        );
+        assert!(set_value(&config, "providers.vllm.apiBsae", "x").is_err());
+        assert!(set_value(&config, "gateway.port", "lots").is_err());
+    }
+
+    // ── validate ──
+
+    #[test]
+    fn test_validate_reports_syntax_and_type_errors() {
+        let issues = validate("{\"gateway\": {\"port\": 1,}}", Path::new("."));
+        assert!(issues[0].error && issues[0].message.starts_with("invalid JSON"));
+        let issues = validate(r#"{"gateway": {"port": "high"}}"#, Path::new("."));
+        assert!(issues[0].error, "{:?}", issues);
+        assert!(
+            issues[0].message.starts_with("invalid value at gateway.port:"),
+            "{}",
+            issues[0].message
+        );
+    }
+
+    #[test]
+    fn test_validate_finds_mistakes() {
+        let issues = validate(
+            r#"{
+                "providers": {"openrouter": {"apiKey": "sk-or-1"}},
+                "channels": {"telegram": {"enabled": true, "tokne": "x"}},
+                "guardrails": {"rules": [{"name": "bad", "pattern": "(oops"}]},
+                "agents": {"costs": {"pricing": {"my-model": {"inputPerMillion": 1.0, "outputPerMillion": 2.0}}}},
+                "systemEvents": {"react": ["cron_fired", "cron_failed"]},
+                "groups": {"alerts": ["telegram:42", "group:ops"]}
+            }"#,
+            Path::new("."),
+        );
+        let messages: Vec<&str> = issues.iter().map(|i| i.message.as_str()).collect();
+        assert_eq!(issues.len(), 5, "{:?}", messages);
+        assert!(messages.contains(&"groups.alerts: 'group:ops' is not a channel:chat_id recipient"));
+        assert!(messages.contains(&"systemEvents.react: unknown event type 'cron_failed'"));
+        assert!(messages.contains(&"unknown key at channels.telegram.tokne (ignored)"));
+        assert!(messages.contains(&"channels.telegram is enabled but has no token"));
+        assert!(messages.iter().any(|m| m.starts_with("guardrails rule 'bad'")));
+    }
+
+    #[test]
+    fn test_parse_config_lists_unknown_keys() {
+        let parsed = parse_config(
+            r#"{"$schema": "./schema.json", "channels": {"telgram": {}}, "gateway": {"port": 9000}}"#,
+            None,
+        )
+        .unwrap();
+        assert_eq!(parsed.config.gateway.port, 9000);
+        assert_eq!(parsed.unknown, vec!["channels.telgram"]);
+    }
+
+    #[test]
+    fn test_validate_clean_config() {
+        let issues = validate(
+            r#"{"providers": {"openrouter": {"apiKey": "sk-or-1"}}}"#,
+            Path::new("."),
+        );
+        assert!(issues.is_empty(), "{:?}", issues);
+    }
+
+    // ── schema ──
+
+    #[test]
+    fn test_json_schema_describes_config() {
+        let schema = json_schema();
+        let props = &schema["properties"];
+        assert!(props["channels"].is_object() && props["gateway"].is_object());
+        assert!(props["$schema"].is_object());
+        assert_eq!(schema["additionalProperties"], false);
+        // Sections are closed too, and use the camelCase names of the file.
+        let defs = schema["$defs"].as_object().unwrap();
+        let telegram = &defs["TelegramConfig"];
+        assert_eq!(telegram["additionalProperties"], false);
+        assert!(telegram["properties"]["allowFrom"].is_object());
+    }
+}
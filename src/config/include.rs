@@ -0,0 +1,173 @@
+//! Config fragments merged with `include`.
+//!
+//! A config file may list other files to merge in:
+//!
+//! ```json
+//! { "include": ["channels.json", "secrets.json"], "gateway": { "port": 18790 } }
+//! ```
+//!
+//! Paths are relative to the including file (`~` is expanded). Fragments
+//! are merged in order, each overriding the ones before, and the including
+//! file overrides them all. Objects merge key by key; other values replace.
+//! Fragments may include further fragments.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+/// Include depth beyond which fragments are ignored.
+const MAX_DEPTH: usize = 8;
+
+/// Merge the fragments included by `raw`, a config read from a file in
+/// `dir`. Fragments that cannot be read are skipped; the problems are
+/// returned for reporting.
+pub fn merge_includes(raw: &mut Value, dir: &Path) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut seen = HashSet::new();
+    _merge(raw, dir, 0, &mut seen, &mut problems);
+    problems
+}
+
+fn _merge(
+    raw: &mut Value,
+    dir: &Path,
+    depth: usize,
+    seen: &mut HashSet<PathBuf>,
+    problems: &mut Vec<String>,
+) {
+    let includes = included_paths(raw, dir);
+    if includes.is_empty() {
+        return;
+    }
+
+    let mut merged = Value::Object(Default::default());
+    for path in includes {
+        if depth >= MAX_DEPTH {
+            problems.push(format!("include {}: nested too deeply", path.display()));
+            continue;
+        }
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+        if !seen.insert(canonical.clone()) {
+            problems.push(format!("include {}: included twice or in a cycle", path.display()));
+            continue;
+        }
+        let fragment = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| serde_json::from_str::<Value>(&text).map_err(|e| e.to_string()));
+        match fragment {
+            Ok(mut fragment) if fragment.is_object() => {
+                let parent = path.parent().unwrap_or(dir).to_path_buf();
+                _merge(&mut fragment, &parent, depth + 1, seen, problems);
+                if let Some(map) = fragment.as_object_mut() {
+                    map.remove("include");
+                }
+                merge_json(&mut merged, fragment);
+            }
+            Ok(_) => problems.push(format!("include {}: not a JSON object", path.display())),
+            Err(e) => problems.push(format!("include {}: {}", path.display(), e)),
+        }
+        seen.remove(&canonical);
+    }
+
+    // The including file wins over its fragments.
+    merge_json(&mut merged, raw.take());
+    *raw = merged;
+}
+
+/// The files listed under `include`.
+fn included_paths(raw: &Value, dir: &Path) -> Vec<PathBuf> {
+    let Some(Value::Array(names)) = raw.get("include") else {
+        return Vec::new();
+    };
+    names
+        .iter()
+        .filter_map(Value::as_str)
+        .filter(|name| !name.trim().is_empty())
+        .map(|name| {
+            let path = match name.strip_prefix("~/") {
+                Some(rest) => dirs::home_dir().unwrap_or_default().join(rest),
+                None => PathBuf::from(name),
+            };
+            dir.join(path)
+        })
+        .collect()
+}
+
+/// Merge `over` into `base`: objects key by key, other values replace.
+pub fn merge_json(base: &mut Value, over: Value) {
+    match (base, over) {
+        (Value::Object(base), Value::Object(over)) => {
+            for (key, value) in over {
+                match base.get_mut(&key) {
+                    Some(slot) => merge_json(slot, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, over) => *base = over,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_json() {
+        let mut base = json!({"a": {"x": 1, "y": [1, 2]}, "b": 1});
+        merge_json(&mut base, json!({"a": {"y": [3], "z": true}, "c": "new"}));
+        assert_eq!(base, json!({"a": {"x": 1, "y": [3], "z": true}, "b": 1, "c": "new"}));
+    }
+
+    #[test]
+    fn test_includes_merge_in_order_under_the_main_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("channels.json"),
+            r#"{"channels": {"telegram": {"enabled": true, "token": "shared"}}, "include": ["more/nested.json"]}"#,
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("more")).unwrap();
+        fs::write(
+            dir.path().join("more/nested.json"),
+            r#"{"gateway": {"host": "127.0.0.1"}}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("secrets.json"),
+            r#"{"channels": {"telegram": {"token": "123:abc"}}}"#,
+        )
+        .unwrap();
+
+        let mut raw = json!({
+            "include": ["channels.json", "secrets.json", "missing.json"],
+            "gateway": {"port": 9000}
+        });
+        let problems = merge_includes(&mut raw, dir.path());
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert!(problems[0].contains("missing.json"));
+        assert_eq!(raw["channels"]["telegram"]["token"], "123:abc");
+        assert_eq!(raw["channels"]["telegram"]["enabled"], true);
+        assert_eq!(raw["gateway"], json!({"host": "127.0.0.1", "port": 9000}));
+        assert_eq!(raw["include"][0], "channels.json");
+    }
+
+    #[test]
+    fn test_include_cycle_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.json"), r#"{"include": ["b.json"], "x": 1}"#).unwrap();
+        fs::write(dir.path().join("b.json"), r#"{"include": ["a.json"], "y": 2}"#).unwrap();
+
+        let mut raw = json!({"include": ["a.json"]});
+        let problems = merge_includes(&mut raw, dir.path());
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert!(problems[0].contains("cycle"));
+        assert_eq!(raw["x"], 1);
+        assert_eq!(raw["y"], 2);
+    }
+}
@@ -1,11 +1,25 @@
 //! Configuration loading and saving utilities.
+//!
+//! Any config key can be overridden with an environment variable named
+//! `NANOCLAW_` followed by the key path in upper case, one `_` between
+//! levels: `NANOCLAW_PROVIDERS_OPENROUTER_APIKEY` sets
+//! `providers.openrouter.apiKey`. Underscores inside a camelCase key are
+//! optional (`NANOCLAW_GATEWAY_PORT`, `NANOCLAW_AGENTS_DEFAULTS_MAX_TOOL_ITERATIONS`).
+//! Values are parsed as the type of the key they replace; lists and objects
+//! take JSON. The `NANOBOT_` prefix of the Python version is accepted too,
+//! with `NANOCLAW_` winning when both are set.
 
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use tracing::warn;
+use serde_json::Value;
+use tracing::{debug, warn};
 
+use crate::config::crypt::PASSPHRASE_VAR;
+use crate::config::edit::parse_config;
+use crate::config::migrate::upgrade_file;
 use crate::config::schema::Config;
+use crate::config::secrets::resolve_secrets;
 use crate::utils::helpers::get_data_path;
 
 /// Get the default configuration file path (`~/.nanoclaw/config.json`).
@@ -14,26 +28,67 @@ pub fn get_config_path() -> PathBuf {
     home.join(".nanoclaw").join("config.json")
 }
 
+/// Prefixes of environment variables that override config keys, lowest
+/// precedence first.
+pub const ENV_PREFIXES: &[&str] = &["NANOBOT_", "NANOCLAW_"];
+
 /// Get the nanoclaw data directory (delegates to `utils::helpers::get_data_path`).
 pub fn get_data_dir() -> PathBuf {
     get_data_path()
 }
 
 /// Load configuration from a file, or return a default [`Config`] if the file
-/// does not exist or cannot be parsed.
+/// does not exist or cannot be parsed, then apply environment overrides and
+/// resolve secret references.
 ///
 /// If `config_path` is `None`, the default path (`~/.nanoclaw/config.json`) is
 /// used.
 pub fn load_config(config_path: Option<&Path>) -> Config {
+    let config = apply_env_overrides(_read_config(config_path, true), std::env::vars());
+    resolve_secrets(config)
+}
+
+/// Migrate an out-of-date config file, returning the text to parse. If the
+/// upgraded file cannot be written, the config is still migrated in memory.
+fn _upgrade(path: &Path, contents: String) -> String {
+    match upgrade_file(path, &contents) {
+        Ok(Some(upgraded)) => upgraded,
+        Ok(None) => contents,
+        Err(e) => {
+            warn!("{:#}", e);
+            contents
+        }
+    }
+}
+
+/// Load configuration from the file only, without included fragments,
+/// environment overrides or keyring secrets (for code that writes the config
+/// back).
+pub fn load_config_file(config_path: Option<&Path>) -> Config {
+    _read_config(config_path, false)
+}
+
+/// Read and parse the config file, merging its `include` fragments when
+/// `includes` is set. Falls back to the defaults if the file is unusable.
+fn _read_config(config_path: Option<&Path>, includes: bool) -> Config {
     let path = match config_path {
         Some(p) => p.to_path_buf(),
         None => get_config_path(),
     };
 
     if path.exists() {
+        let dir = path.parent().filter(|_| includes);
         match fs::read_to_string(&path) {
-            Ok(contents) => match serde_json::from_str::<Config>(&contents) {
-                Ok(cfg) => return cfg,
+            Ok(contents) => match parse_config(&_upgrade(&path, contents), dir) {
+                Ok(parsed) => {
+                    for problem in parsed.include_problems {
+                        warn!("Config {}: {}", path.display(), problem);
+                    }
+                    for key in parsed.unknown {
+                        warn!("Unknown key at {} in {} (ignored)", key, path.display());
+                    }
+                    return parsed.config;
+                }
                 Err(e) => {
                     warn!(
                         "Failed to parse config from {}: {}. Using default configuration.",
@@ -55,6 +110,82 @@ pub fn load_config(config_path: Option<&Path>) -> Config {
     Config::default()
 }
 
+/// Apply `NANOCLAW_*` / `NANOBOT_*` overrides from `vars` to `config`.
+/// Variables that match no key, or values of the wrong type, are logged and
+/// ignored.
+pub fn apply_env_overrides(
+    config: Config,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Config {
+    let mut overrides: Vec<(usize, String, String)> = vars
+        .into_iter()
+        .filter(|(name, _)| name != PASSPHRASE_VAR)
+        .filter_map(|(name, value)| {
+            let (rank, prefix) = ENV_PREFIXES
+                .iter()
+                .enumerate()
+                .find(|(_, p)| name.starts_with(*p))?;
+            Some((rank, name[prefix.len()..].to_string(), value))
+        })
+        .collect();
+    if overrides.is_empty() {
+        return config;
+    }
+    // Apply lower-precedence prefixes first so later ones win.
+    overrides.sort_by_key(|(rank, _, _)| *rank);
+
+    let Ok(mut tree) = serde_json::to_value(&config) else {
+        return config;
+    };
+    for (_, key, value) in &overrides {
+        let segments: Vec<&str> = key.split('_').filter(|s| !s.is_empty()).collect();
+        match _env_target(&mut tree, &segments) {
+            Some(slot) => {
+                *slot = parse_value_like(slot, value);
+                debug!("Config override from environment: {}", key);
+            }
+            None => warn!("Environment override {} matches no config key", key),
+        }
+    }
+
+    match serde_json::from_value(tree) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            warn!("Ignoring environment overrides: {}", e);
+            config
+        }
+    }
+}
+
+/// Find the config value addressed by upper-case `segments`. Each key may
+/// span several segments (`MAX_TOOL_ITERATIONS` for `maxToolIterations`).
+fn _env_target<'a>(node: &'a mut Value, segments: &[&str]) -> Option<&'a mut Value> {
+    if segments.is_empty() {
+        return Some(node);
+    }
+    let map = node.as_object_mut()?;
+    let (key, used) = (1..=segments.len()).find_map(|n| {
+        let wanted = segments[..n].concat().to_lowercase();
+        map.keys()
+            .find(|k| k.replace('_', "").to_lowercase() == wanted)
+            .map(|k| (k.clone(), n))
+    })?;
+    _env_target(map.get_mut(&key)?, &segments[used..])
+}
+
+/// Parse `raw` as the type of the value it replaces.
+pub fn parse_value_like(current: &Value, raw: &str) -> Value {
+    match current {
+        Value::String(_) => Value::String(raw.to_string()),
+        Value::Bool(_) => match raw.to_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Value::Bool(true),
+            "0" | "false" | "no" | "off" | "" => Value::Bool(false),
+            _ => Value::String(raw.to_string()),
+        },
+        _ => serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string())),
+    }
+}
+
 /// Save configuration to a JSON file.
 ///
 /// If `config_path` is `None`, the default path (`~/.nanoclaw/config.json`) is
@@ -93,6 +224,50 @@ mod tests {
         assert_eq!(cfg.gateway.port, 18790);
     }
 
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_env_overrides_nested_keys() {
+        let cfg = apply_env_overrides(
+            Config::default(),
+            vars(&[
+                ("NANOBOT_PROVIDERS_OPENROUTER_APIKEY", "sk-or-123"),
+                ("NANOCLAW_GATEWAY_PORT", "9000"),
+                ("NANOCLAW_AGENTS_DEFAULTS_MAX_TOOL_ITERATIONS", "7"),
+                ("NANOCLAW_CHANNELS_TELEGRAM_ENABLED", "yes"),
+                ("NANOCLAW_CHANNELS_TELEGRAM_ALLOWFROM", r#"["42"]"#),
+                ("HOME", "/root"),
+            ]),
+        );
+        assert_eq!(cfg.providers.openrouter.api_key, "sk-or-123");
+        assert_eq!(cfg.gateway.port, 9000);
+        assert_eq!(cfg.agents.defaults.max_tool_iterations, 7);
+        assert!(cfg.channels.telegram.enabled);
+        assert_eq!(cfg.channels.telegram.allow_from, vec!["42"]);
+    }
+
+    #[test]
+    fn test_env_override_precedence_and_bad_values() {
+        let cfg = apply_env_overrides(
+            Config::default(),
+            vars(&[
+                ("NANOCLAW_GATEWAY_HOST", "127.0.0.1"),
+                ("NANOBOT_GATEWAY_HOST", "10.0.0.1"),
+                ("NANOCLAW_NO_SUCH_KEY", "x"),
+            ]),
+        );
+        assert_eq!(cfg.gateway.host, "127.0.0.1");
+
+        // A value of the wrong type leaves the config untouched.
+        let cfg = apply_env_overrides(Config::default(), vars(&[("NANOCLAW_GATEWAY_PORT", "abc")]));
+        assert_eq!(cfg.gateway.port, 18790);
+    }
+
     #[test]
     fn test_load_and_save_roundtrip() {
         let dir = std::env::temp_dir().join("nanoclaw_test_loader");
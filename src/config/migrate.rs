@@ -0,0 +1,130 @@
+//! Config versioning and migrations.
+//!
+//! Config files carry a `version`. When the schema changes incompatibly (a
+//! key is renamed, a section moves), a migration step is appended to
+//! [`MIGRATIONS`]; older files are then upgraded step by step as raw JSON
+//! before they are parsed, and the loader rewrites them after keeping a
+//! backup of the original.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use tracing::{info, warn};
+
+/// A migration step, editing the config object in place.
+type Migration = fn(&mut Map<String, Value>);
+
+/// Upgrade steps; entry `i` moves a config from version `i` to `i + 1`.
+const MIGRATIONS: &[Migration] = &[v0_unversioned];
+
+/// Version written by this build.
+pub const CONFIG_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Version 0 is every config written before versioning; the keys did not
+/// change, the file only gains its `version`.
+fn v0_unversioned(_config: &mut Map<String, Value>) {}
+
+/// The version of a raw config; files without one are version 0.
+pub fn version_of(raw: &Value) -> u32 {
+    raw.get("version")
+        .and_then(Value::as_u64)
+        .map_or(0, |v| v.min(u32::MAX as u64) as u32)
+}
+
+/// Upgrade a raw config to [`CONFIG_VERSION`]. Returns the version it had
+/// when a migration ran, `None` when it was already current (or newer, which
+/// is left alone with a warning).
+pub fn migrate(raw: &mut Value) -> Option<u32> {
+    let from = version_of(raw);
+    let config = raw.as_object_mut()?;
+    if from > CONFIG_VERSION {
+        warn!(
+            "Config version {} is newer than this nanoclaw supports ({}); some settings may be ignored",
+            from, CONFIG_VERSION
+        );
+        return None;
+    }
+    if from == CONFIG_VERSION {
+        return None;
+    }
+    for step in &MIGRATIONS[from as usize..] {
+        step(config);
+    }
+    config.insert("version".to_string(), Value::from(CONFIG_VERSION));
+    Some(from)
+}
+
+/// Upgrade the config file at `path` whose text is `contents`. When it is
+/// out of date, the original is kept as `<file>.v<N>.bak` and the upgraded
+/// file written in its place. Returns the upgraded text, or `None` when the
+/// file is current or not a JSON object.
+pub fn upgrade_file(path: &Path, contents: &str) -> Result<Option<String>> {
+    let Ok(mut raw) = serde_json::from_str::<Value>(contents) else {
+        return Ok(None);
+    };
+    let Some(from) = migrate(&mut raw) else {
+        return Ok(None);
+    };
+
+    let upgraded = serde_json::to_string_pretty(&raw)?;
+    let backup = backup_path(path, from);
+    if !backup.exists() {
+        fs::write(&backup, contents)
+            .with_context(|| format!("cannot back up config to {}", backup.display()))?;
+    }
+    fs::write(path, &upgraded)
+        .with_context(|| format!("cannot write upgraded config to {}", path.display()))?;
+    info!(
+        "Upgraded config {} from version {} to {} (backup: {})",
+        path.display(),
+        from,
+        CONFIG_VERSION,
+        backup.display()
+    );
+    Ok(Some(upgraded))
+}
+
+/// Where the original of a config at version `from` is kept.
+fn backup_path(path: &Path, from: u32) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".v{}.bak", from));
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_migrate_stamps_version() {
+        let mut raw = json!({"gateway": {"port": 9000}});
+        assert_eq!(migrate(&mut raw), Some(0));
+        assert_eq!(version_of(&raw), CONFIG_VERSION);
+        assert_eq!(raw["gateway"]["port"], 9000);
+        // Current and newer configs are left alone.
+        assert_eq!(migrate(&mut raw), None);
+        let mut newer = json!({"version": CONFIG_VERSION + 1});
+        assert_eq!(migrate(&mut newer), None);
+        assert_eq!(version_of(&newer), CONFIG_VERSION + 1);
+    }
+
+    #[test]
+    fn test_upgrade_file_keeps_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        let original = r#"{"gateway": {"port": 9000}}"#;
+        fs::write(&path, original).unwrap();
+
+        let upgraded = upgrade_file(&path, original).unwrap().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), upgraded);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("config.json.v0.bak")).unwrap(),
+            original
+        );
+        assert_eq!(upgrade_file(&path, &upgraded).unwrap(), None);
+        assert_eq!(upgrade_file(&path, "not json").unwrap(), None);
+    }
+}
@@ -1,2 +1,7 @@
 pub mod schema;
 pub mod loader;
+pub mod migrate;
+pub mod include;
+pub mod edit;
+pub mod secrets;
+pub mod crypt;
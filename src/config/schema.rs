@@ -3,16 +3,20 @@
 //! All structs use `#[serde(rename_all = "camelCase")]` so that the JSON config
 //! file can use camelCase keys while Rust code uses snake_case fields.
 
+use std::collections::HashMap;
 use std::path::PathBuf;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::config::migrate::CONFIG_VERSION;
+
 // ---------------------------------------------------------------------------
 // Channel configs
 // ---------------------------------------------------------------------------
 
 /// WhatsApp channel configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct WhatsAppConfig {
     #[serde(default)]
@@ -38,7 +42,7 @@ impl Default for WhatsAppConfig {
 }
 
 /// Telegram channel configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct TelegramConfig {
     #[serde(default)]
@@ -51,19 +55,8 @@ pub struct TelegramConfig {
     pub proxy: Option<String>,
 }
 
-impl Default for TelegramConfig {
-    fn default() -> Self {
-        Self {
-            enabled: false,
-            token: String::new(),
-            allow_from: Vec::new(),
-            proxy: None,
-        }
-    }
-}
-
 /// Feishu/Lark channel configuration using WebSocket long connection.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct FeishuConfig {
     #[serde(default)]
@@ -80,21 +73,8 @@ pub struct FeishuConfig {
     pub allow_from: Vec<String>,
 }
 
-impl Default for FeishuConfig {
-    fn default() -> Self {
-        Self {
-            enabled: false,
-            app_id: String::new(),
-            app_secret: String::new(),
-            encrypt_key: String::new(),
-            verification_token: String::new(),
-            allow_from: Vec::new(),
-        }
-    }
-}
-
 /// Configuration for chat channels.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ChannelsConfig {
     #[serde(default)]
@@ -103,6 +83,10 @@ pub struct ChannelsConfig {
     pub telegram: TelegramConfig,
     #[serde(default)]
     pub feishu: FeishuConfig,
+    /// Extra system-prompt text by channel name (e.g. `"whatsapp"`, `"cli"`),
+    /// added when building the prompt for a message from that channel.
+    #[serde(default)]
+    pub system_prompts: HashMap<String, String>,
 }
 
 // ---------------------------------------------------------------------------
@@ -110,7 +94,7 @@ pub struct ChannelsConfig {
 // ---------------------------------------------------------------------------
 
 /// Default agent configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct AgentDefaults {
     #[serde(default = "default_workspace")]
@@ -123,6 +107,29 @@ pub struct AgentDefaults {
     pub temperature: f64,
     #[serde(default = "default_max_tool_iterations")]
     pub max_tool_iterations: u32,
+    /// Show replies forming on channels that can edit messages (Telegram).
+    #[serde(default)]
+    pub streaming: bool,
+    /// Wall-clock limit in seconds for a whole agent turn (0 = no limit).
+    #[serde(default = "default_turn_timeout_secs")]
+    pub turn_timeout_secs: u64,
+    /// What to do with a message that arrives in a chat while its previous
+    /// message is still being handled: "queue" (answer it afterwards),
+    /// "restart" (drop the current turn and start over with both messages)
+    /// or "inject" (show it to the model in the current turn).
+    #[serde(default = "default_mid_turn_messages")]
+    pub mid_turn_messages: String,
+    /// Ask in the chat before each tool call that changes something
+    /// (files, commands, messages to other chats) and only run it on a yes.
+    #[serde(default)]
+    pub supervised: bool,
+    /// Seconds to wait for that yes before declining.
+    #[serde(default = "default_confirm_timeout_secs")]
+    pub confirm_timeout_secs: u64,
+    /// Post each tool call and the start of its result in the chat while
+    /// the agent works.
+    #[serde(default)]
+    pub show_tool_calls: bool,
 }
 
 fn default_workspace() -> String {
@@ -145,6 +152,18 @@ fn default_max_tool_iterations() -> u32 {
     20
 }
 
+fn default_turn_timeout_secs() -> u64 {
+    600
+}
+
+fn default_mid_turn_messages() -> String {
+    "queue".to_string()
+}
+
+fn default_confirm_timeout_secs() -> u64 {
+    120
+}
+
 impl Default for AgentDefaults {
     fn default() -> Self {
         Self {
@@ -153,16 +172,294 @@ impl Default for AgentDefaults {
             max_tokens: default_max_tokens(),
             temperature: default_temperature(),
             max_tool_iterations: default_max_tool_iterations(),
+            streaming: false,
+            turn_timeout_secs: default_turn_timeout_secs(),
+            mid_turn_messages: default_mid_turn_messages(),
+            supervised: false,
+            confirm_timeout_secs: default_confirm_timeout_secs(),
+            show_tool_calls: false,
+        }
+    }
+}
+
+/// Limits for background subagents started with the `spawn` tool.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SubagentConfig {
+    /// Maximum number of subagents running at once.
+    #[serde(default = "default_subagent_max_concurrent")]
+    pub max_concurrent: usize,
+    /// LLM/tool iterations each subagent may use.
+    #[serde(default = "default_subagent_max_iterations")]
+    pub max_iterations: u32,
+    /// Tools available to subagents.
+    #[serde(default = "default_subagent_tools")]
+    pub tools: Vec<String>,
+}
+
+fn default_subagent_max_concurrent() -> usize {
+    3
+}
+
+fn default_subagent_max_iterations() -> u32 {
+    15
+}
+
+fn default_subagent_tools() -> Vec<String> {
+    ["read_file", "write_file", "list_dir", "exec", "web_search", "web_fetch"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+impl Default for SubagentConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: default_subagent_max_concurrent(),
+            max_iterations: default_subagent_max_iterations(),
+            tools: default_subagent_tools(),
         }
     }
 }
 
+/// Token budget for the prompt sent to the LLM.
+///
+/// The system prompt, memory and skills each get a share of `max_tokens`;
+/// conversation history gets whatever is left.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextConfig {
+    /// Estimated prompt tokens allowed per LLM call (0 = no limit).
+    #[serde(default = "default_context_max_tokens")]
+    pub max_tokens: usize,
+    /// Percentage of the budget for identity and bootstrap files.
+    #[serde(default = "default_context_system_percent")]
+    pub system_percent: u8,
+    /// Percentage of the budget for memory.
+    #[serde(default = "default_context_memory_percent")]
+    pub memory_percent: u8,
+    /// Percentage of the budget for skills.
+    #[serde(default = "default_context_skills_percent")]
+    pub skills_percent: u8,
+}
+
+fn default_context_max_tokens() -> usize {
+    64_000
+}
+
+fn default_context_system_percent() -> u8 {
+    20
+}
+
+fn default_context_memory_percent() -> u8 {
+    15
+}
+
+fn default_context_skills_percent() -> u8 {
+    15
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            max_tokens: default_context_max_tokens(),
+            system_percent: default_context_system_percent(),
+            memory_percent: default_context_memory_percent(),
+            skills_percent: default_context_skills_percent(),
+        }
+    }
+}
+
+/// Skill settings.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SkillsConfig {
+    /// Bundled skills to enable (default: all of them).
+    #[serde(default = "default_builtin_skills")]
+    pub builtin: Vec<String>,
+    /// What happens when the agent calls a tool a skill it read does not
+    /// list in `allowed-tools`: `"deny"`, `"warn"` or `"off"`.
+    #[serde(default = "default_skill_tool_scope")]
+    pub tool_scope: String,
+    /// JSON index of community skills searched by `nanoclaw skills search`
+    /// (an `http(s)` URL or a local path).
+    #[serde(default)]
+    pub index_url: String,
+}
+
+fn default_builtin_skills() -> Vec<String> {
+    crate::agent::builtin_skills::names()
+}
+
+fn default_skill_tool_scope() -> String {
+    "deny".to_string()
+}
+
+impl Default for SkillsConfig {
+    fn default() -> Self {
+        Self {
+            builtin: default_builtin_skills(),
+            tool_scope: default_skill_tool_scope(),
+            index_url: String::new(),
+        }
+    }
+}
+
+/// Price of a model in USD per million tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPricing {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+/// Cost tracking settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CostConfig {
+    /// Warn once a day's spend passes this many USD (0 = no budget).
+    #[serde(default)]
+    pub daily_budget_usd: f64,
+    /// Prices by model name, overriding the built-in table. Keys match any
+    /// model whose name contains them; the longest match wins.
+    #[serde(default)]
+    pub pricing: HashMap<String, ModelPricing>,
+}
+
+/// A named agent persona hosted by the same gateway, with its own model,
+/// prompt and tools.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentProfileConfig {
+    /// Profile name, also usable as a `@name` message prefix.
+    pub name: String,
+    /// Model override (defaults to `agents.defaults.model`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// File with persona instructions, relative to the workspace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt_file: Option<String>,
+    /// Tools this profile may use (empty = all tools).
+    #[serde(default)]
+    pub tools: Vec<String>,
+    /// Chats routed to this profile: `"channel"` or `"channel:chat_id"`.
+    #[serde(default)]
+    pub routes: Vec<String>,
+    /// Name of the workspace (from `agents.workspaces`) this profile uses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<String>,
+}
+
+/// An extra workspace with its own memory, skills and bootstrap files.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceConfig {
+    /// Name that profiles use to select the workspace.
+    pub name: String,
+    /// Workspace directory; `~` is expanded.
+    pub path: String,
+    /// Chats that use this workspace: `"channel"` or `"channel:chat_id"`.
+    #[serde(default)]
+    pub routes: Vec<String>,
+}
+
+impl WorkspaceConfig {
+    /// The workspace directory with `~` expanded.
+    pub fn expanded_path(&self) -> PathBuf {
+        expand_tilde(&self.path)
+    }
+}
+
 /// Agent configuration.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct AgentsConfig {
     #[serde(default)]
     pub defaults: AgentDefaults,
+    #[serde(default)]
+    pub subagents: SubagentConfig,
+    #[serde(default)]
+    pub context: ContextConfig,
+    /// Additional agent personas and how messages are routed to them.
+    #[serde(default)]
+    pub profiles: Vec<AgentProfileConfig>,
+    /// Workspaces besides `defaults.workspace`, selected by profile or chat.
+    #[serde(default)]
+    pub workspaces: Vec<WorkspaceConfig>,
+    #[serde(default)]
+    pub costs: CostConfig,
+    #[serde(default)]
+    pub skills: SkillsConfig,
+    #[serde(default)]
+    pub roles: RolesConfig,
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+}
+
+/// Thresholds that alert the owner; 0 turns one off.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertsConfig {
+    /// Alert once a day's LLM spend passes this many USD.
+    #[serde(default)]
+    pub daily_cost_usd: f64,
+    /// Alert when this many tool calls are made within an hour.
+    #[serde(default)]
+    pub tool_calls_per_hour: u32,
+    /// Alert when this many turns in a row fail.
+    #[serde(default)]
+    pub consecutive_errors: u32,
+    /// Chat alerts are sent to; defaults to the first of `roles.owners`
+    /// given as `channel:id`.
+    #[serde(default)]
+    pub channel: String,
+    #[serde(default)]
+    pub chat_id: String,
+    /// Stop handling messages after an alert until an owner replies
+    /// `/resume`.
+    #[serde(default)]
+    pub pause: bool,
+}
+
+/// What channel users may do. Users are given as `"channel:senderId"` or a
+/// bare sender ID (a Telegram user ID or username, a phone number) on any
+/// channel. With no owners or trusted users set, everyone is an owner.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RolesConfig {
+    /// Users with every tool and the admin commands (`/reset`, `/resume`).
+    #[serde(default)]
+    pub owners: Vec<String>,
+    /// Users with every tool.
+    #[serde(default)]
+    pub trusted: Vec<String>,
+    /// Role of everyone else: `guest` or `trusted`.
+    #[serde(default = "default_role")]
+    pub default_role: String,
+    /// The only tools guests may use.
+    #[serde(default = "default_guest_tools")]
+    pub guest_tools: Vec<String>,
+}
+
+fn default_role() -> String {
+    "guest".to_string()
+}
+
+fn default_guest_tools() -> Vec<String> {
+    ["web_search", "web_fetch", "crawl", "message"]
+        .map(String::from)
+        .to_vec()
+}
+
+impl Default for RolesConfig {
+    fn default() -> Self {
+        Self {
+            owners: Vec::new(),
+            trusted: Vec::new(),
+            default_role: default_role(),
+            guest_tools: default_guest_tools(),
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -170,7 +467,7 @@ pub struct AgentsConfig {
 // ---------------------------------------------------------------------------
 
 /// LLM provider configuration.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ProviderConfig {
     #[serde(default)]
@@ -180,7 +477,7 @@ pub struct ProviderConfig {
 }
 
 /// Configuration for LLM providers.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ProvidersConfig {
     #[serde(default)]
@@ -206,13 +503,73 @@ pub struct ProvidersConfig {
 // ---------------------------------------------------------------------------
 
 /// Gateway/server configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct GatewayConfig {
     #[serde(default = "default_gateway_host")]
     pub host: String,
     #[serde(default = "default_gateway_port")]
     pub port: u16,
+    /// IANA timezone (e.g. `"Europe/Rome"`) used for cron jobs without their
+    /// own `tz`. Empty means the system's local timezone.
+    #[serde(default)]
+    pub timezone: String,
+    /// Bearer token for the REST API, with full access. Without any token,
+    /// the API only accepts requests from this machine.
+    #[serde(default)]
+    pub api_token: String,
+    /// Further API tokens, each with its own scope.
+    #[serde(default)]
+    pub api_tokens: Vec<ApiTokenConfig>,
+    /// Origins allowed to call the API from a browser (`"*"` for any);
+    /// empty disables CORS.
+    #[serde(default)]
+    pub cors_origins: Vec<String>,
+    /// Serve the APIs over HTTPS with these certificate files.
+    #[serde(default)]
+    pub tls: TlsConfig,
+}
+
+/// Certificate and private key for HTTPS, as PEM files.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsConfig {
+    /// Certificate chain, leaf first; `~` is expanded.
+    #[serde(default)]
+    pub cert: String,
+    /// Private key of the certificate; `~` is expanded.
+    #[serde(default)]
+    pub key: String,
+}
+
+impl TlsConfig {
+    /// Whether a certificate is configured.
+    pub fn is_enabled(&self) -> bool {
+        !self.cert.is_empty() || !self.key.is_empty()
+    }
+
+    pub fn cert_path(&self) -> PathBuf {
+        expand_tilde(&self.cert)
+    }
+
+    pub fn key_path(&self) -> PathBuf {
+        expand_tilde(&self.key)
+    }
+}
+
+/// A named API token.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiTokenConfig {
+    pub name: String,
+    pub token: String,
+    /// `"read"` (status and listings) or `"send"` (also sends messages).
+    #[serde(default = "default_token_scope")]
+    pub scope: String,
+}
+
+fn default_token_scope() -> String {
+    "read".to_string()
 }
 
 fn default_gateway_host() -> String {
@@ -228,6 +585,44 @@ impl Default for GatewayConfig {
         Self {
             host: default_gateway_host(),
             port: default_gateway_port(),
+            timezone: String::new(),
+            api_token: String::new(),
+            api_tokens: Vec::new(),
+            cors_origins: Vec::new(),
+            tls: TlsConfig::default(),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Cron config
+// ---------------------------------------------------------------------------
+
+/// Scheduler configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CronConfig {
+    /// Maximum number of cron-initiated agent turns running at once.
+    #[serde(default = "default_cron_max_concurrent")]
+    pub max_concurrent_runs: usize,
+    /// Minimum seconds between runs of a recurring job.
+    #[serde(default = "default_cron_min_interval")]
+    pub min_interval_secs: u64,
+}
+
+fn default_cron_max_concurrent() -> usize {
+    crate::cron::service::DEFAULT_MAX_CONCURRENT_RUNS
+}
+
+fn default_cron_min_interval() -> u64 {
+    crate::cron::service::DEFAULT_MIN_INTERVAL_SECS
+}
+
+impl Default for CronConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_runs: default_cron_max_concurrent(),
+            min_interval_secs: default_cron_min_interval(),
         }
     }
 }
@@ -237,38 +632,167 @@ impl Default for GatewayConfig {
 // ---------------------------------------------------------------------------
 
 /// Web search tool configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct WebSearchConfig {
     #[serde(default)]
     pub api_key: String,
     #[serde(default = "default_max_results")]
     pub max_results: u32,
+    /// Search backend: `"brave"`, `"duckduckgo"` (no key), `"searxng"`,
+    /// `"google"` or `"tavily"`.
+    #[serde(default = "default_search_backend", alias = "provider")]
+    pub backend: String,
+    /// Base URL of the SearXNG instance (for the `searxng` backend).
+    #[serde(default)]
+    pub url: String,
+    /// Programmable Search Engine ID, `cx` (for the `google` backend).
+    #[serde(default)]
+    pub engine_id: String,
 }
 
 fn default_max_results() -> u32 {
     5
 }
 
+fn default_search_backend() -> String {
+    "brave".to_string()
+}
+
 impl Default for WebSearchConfig {
     fn default() -> Self {
         Self {
             api_key: String::new(),
             max_results: default_max_results(),
+            backend: default_search_backend(),
+            url: String::new(),
+            engine_id: String::new(),
+        }
+    }
+}
+
+/// Web fetch tool configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WebFetchConfig {
+    /// Most characters of page text returned.
+    #[serde(default = "default_fetch_max_chars")]
+    pub max_chars: usize,
+    #[serde(default = "default_fetch_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Private hosts (`nas.local`, `*.lan`), addresses and networks
+    /// (`192.168.1.0/24`) that may be fetched; others are blocked.
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+    /// Skip pages robots.txt disallows for nanoclaw.
+    #[serde(default = "default_true")]
+    pub respect_robots_txt: bool,
+    /// Least time between requests to the same host, in milliseconds;
+    /// 0 turns the throttle off.
+    #[serde(default = "default_fetch_min_interval_ms")]
+    pub min_interval_ms: u64,
+    /// Largest non-text file (PDF, archive, image) saved to the workspace's
+    /// `downloads` directory, in megabytes; 0 refuses them.
+    #[serde(default = "default_fetch_max_download_mb")]
+    pub max_download_mb: u64,
+}
+
+fn default_fetch_max_chars() -> usize {
+    50_000
+}
+
+fn default_fetch_timeout_secs() -> u64 {
+    30
+}
+
+fn default_fetch_min_interval_ms() -> u64 {
+    1000
+}
+
+fn default_fetch_max_download_mb() -> u64 {
+    25
+}
+
+impl Default for WebFetchConfig {
+    fn default() -> Self {
+        Self {
+            max_chars: default_fetch_max_chars(),
+            timeout_secs: default_fetch_timeout_secs(),
+            allowed_hosts: Vec::new(),
+            respect_robots_txt: true,
+            min_interval_ms: default_fetch_min_interval_ms(),
+            max_download_mb: default_fetch_max_download_mb(),
+        }
+    }
+}
+
+/// `crawl` tool configuration: the most the agent may ask for.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WebCrawlConfig {
+    /// Links followed away from the start page.
+    #[serde(default = "default_crawl_max_depth")]
+    pub max_depth: u32,
+    #[serde(default = "default_crawl_max_pages")]
+    pub max_pages: usize,
+    /// Most characters of text returned for a crawl.
+    #[serde(default = "default_fetch_max_chars")]
+    pub max_chars: usize,
+}
+
+fn default_crawl_max_depth() -> u32 {
+    2
+}
+
+fn default_crawl_max_pages() -> usize {
+    20
+}
+
+impl Default for WebCrawlConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: default_crawl_max_depth(),
+            max_pages: default_crawl_max_pages(),
+            max_chars: default_fetch_max_chars(),
         }
     }
 }
 
 /// Web tools configuration.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct WebToolsConfig {
     #[serde(default)]
     pub search: WebSearchConfig,
+    #[serde(default)]
+    pub fetch: WebFetchConfig,
+    #[serde(default)]
+    pub crawl: WebCrawlConfig,
+}
+
+/// File tools (read, write, edit, list) configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FilesystemToolConfig {
+    /// Directories the file tools may access (`~` is expanded); empty
+    /// allows any path.
+    #[serde(default)]
+    pub allowed_paths: Vec<String>,
+}
+
+/// Message tool configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageToolConfig {
+    /// Target used when neither the call nor the current chat gives one.
+    #[serde(default)]
+    pub default_channel: String,
+    #[serde(default)]
+    pub default_chat_id: String,
 }
 
 /// Shell exec tool configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ExecToolConfig {
     #[serde(default = "default_exec_timeout")]
@@ -294,13 +818,521 @@ impl Default for ExecToolConfig {
 ///
 /// Note: the `exec` field from Python is renamed to `exec_` in Rust to avoid
 /// the reserved keyword, but serializes as `"exec"` in JSON.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ToolsConfig {
     #[serde(default)]
     pub web: WebToolsConfig,
     #[serde(default, rename = "exec")]
     pub exec_: ExecToolConfig,
+    #[serde(default)]
+    pub filesystem: FilesystemToolConfig,
+    #[serde(default)]
+    pub message: MessageToolConfig,
+}
+
+// ---------------------------------------------------------------------------
+// Heartbeat config
+// ---------------------------------------------------------------------------
+
+/// Periodic agenda review and proactive messages.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HeartbeatConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Seconds between beats.
+    #[serde(default = "default_heartbeat_interval_secs")]
+    pub interval_secs: u64,
+    /// Most proactive messages the agent may send per day.
+    #[serde(default = "default_max_proactive_per_day")]
+    pub max_proactive_per_day: u32,
+    /// Channel and chat that receive proactive messages; none are sent
+    /// while either is empty.
+    #[serde(default)]
+    pub channel: String,
+    #[serde(default)]
+    pub chat_id: String,
+    /// Instructions opening each beat, replacing the built-in ones. An
+    /// `## Instructions` section in `HEARTBEAT.md` takes precedence.
+    #[serde(default)]
+    pub prompt: String,
+    /// Beat faster while the user is active and has something pending, and
+    /// back off while they are idle and nothing is.
+    #[serde(default = "default_true")]
+    pub adaptive: bool,
+    /// Seconds between beats while the user is active and the agenda is not
+    /// empty.
+    #[serde(default = "default_heartbeat_active_interval_secs")]
+    pub active_interval_secs: u64,
+    /// Longest wait between beats when idle.
+    #[serde(default = "default_heartbeat_max_interval_secs")]
+    pub max_interval_secs: u64,
+    /// Seconds without a message after which the user counts as idle.
+    #[serde(default = "default_heartbeat_idle_after_secs")]
+    pub idle_after_secs: u64,
+    /// Housekeeping done between beats.
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+}
+
+fn default_heartbeat_interval_secs() -> u64 {
+    30 * 60
+}
+
+fn default_max_proactive_per_day() -> u32 {
+    3
+}
+
+fn default_heartbeat_active_interval_secs() -> u64 {
+    10 * 60
+}
+
+fn default_heartbeat_max_interval_secs() -> u64 {
+    4 * 60 * 60
+}
+
+fn default_heartbeat_idle_after_secs() -> u64 {
+    2 * 60 * 60
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: default_heartbeat_interval_secs(),
+            max_proactive_per_day: default_max_proactive_per_day(),
+            channel: String::new(),
+            chat_id: String::new(),
+            prompt: String::new(),
+            adaptive: true,
+            active_interval_secs: default_heartbeat_active_interval_secs(),
+            max_interval_secs: default_heartbeat_max_interval_secs(),
+            idle_after_secs: default_heartbeat_idle_after_secs(),
+            maintenance: MaintenanceConfig::default(),
+        }
+    }
+}
+
+/// Housekeeping the heartbeat does on its own, without the agent. A limit
+/// of 0 turns that task off.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Hours between maintenance runs.
+    #[serde(default = "default_maintenance_interval_hours")]
+    pub interval_hours: u64,
+    /// Fold daily memory notes older than this many days into monthly files.
+    #[serde(default = "default_consolidate_memory_after_days")]
+    pub consolidate_memory_after_days: u64,
+    /// Move sessions untouched for this many days to `sessions/archive/`.
+    #[serde(default = "default_archive_sessions_after_days")]
+    pub archive_sessions_after_days: u64,
+    /// Delete downloaded media older than this many days.
+    #[serde(default = "default_prune_media_after_days")]
+    pub prune_media_after_days: u64,
+    /// Rotate `gateway.log` once it grows past this many megabytes.
+    #[serde(default = "default_max_gateway_log_mb")]
+    pub max_gateway_log_mb: u64,
+}
+
+fn default_maintenance_interval_hours() -> u64 {
+    24
+}
+
+fn default_consolidate_memory_after_days() -> u64 {
+    30
+}
+
+fn default_archive_sessions_after_days() -> u64 {
+    90
+}
+
+fn default_prune_media_after_days() -> u64 {
+    14
+}
+
+fn default_max_gateway_log_mb() -> u64 {
+    10
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_hours: default_maintenance_interval_hours(),
+            consolidate_memory_after_days: default_consolidate_memory_after_days(),
+            archive_sessions_after_days: default_archive_sessions_after_days(),
+            prune_media_after_days: default_prune_media_after_days(),
+            max_gateway_log_mb: default_max_gateway_log_mb(),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Guardrails config
+// ---------------------------------------------------------------------------
+
+/// A custom content filter rule.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GuardrailRule {
+    /// Rule name, recorded in the audit log.
+    pub name: String,
+    /// Regular expression matched against outgoing text.
+    pub pattern: String,
+    /// `"redact"` replaces matches; `"block"` withholds the whole message.
+    #[serde(default = "default_guardrail_action")]
+    pub action: String,
+    /// Text that replaces redacted matches.
+    #[serde(default = "default_guardrail_replacement")]
+    pub replacement: String,
+}
+
+fn default_guardrail_action() -> String {
+    "redact".to_string()
+}
+
+fn default_guardrail_replacement() -> String {
+    "[redacted]".to_string()
+}
+
+/// Filters applied to outgoing messages before they reach channels.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GuardrailsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Redact API keys, tokens and private keys.
+    #[serde(default = "default_true")]
+    pub redact_secrets: bool,
+    /// Mask common profanity.
+    #[serde(default)]
+    pub redact_profanity: bool,
+    /// Additional regex rules.
+    #[serde(default)]
+    pub rules: Vec<GuardrailRule>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for GuardrailsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            redact_secrets: true,
+            redact_profanity: false,
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// Personal data kept from the LLM provider.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PrivacyConfig {
+    /// Replace personal data in LLM requests with placeholders such as
+    /// `[EMAIL_1]`, and the placeholders in replies with the data again.
+    #[serde(default)]
+    pub pseudonymize: bool,
+    /// What to replace: `email`, `phone` and `address`.
+    #[serde(default = "default_pii_kinds")]
+    pub kinds: Vec<String>,
+}
+
+fn default_pii_kinds() -> Vec<String> {
+    vec!["email".to_string(), "phone".to_string(), "address".to_string()]
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            pseudonymize: false,
+            kinds: default_pii_kinds(),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Post-processing config
+// ---------------------------------------------------------------------------
+
+/// One step in a chain of transforms applied to final replies.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PostProcessorConfig {
+    /// `"signature"`, `"translate"` or `"speech"`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Text appended by `signature`.
+    #[serde(default)]
+    pub text: String,
+    /// Target language for `translate`; empty means the language in USER.md.
+    #[serde(default)]
+    pub language: String,
+    /// Model for `translate` (default: the agent model) or `speech`
+    /// (default: `tts-1`).
+    #[serde(default)]
+    pub model: String,
+    /// Voice for `speech` (default: `alloy`).
+    #[serde(default)]
+    pub voice: String,
+}
+
+// ---------------------------------------------------------------------------
+// Webhooks config
+// ---------------------------------------------------------------------------
+
+/// A URL that receives a JSON POST for each event.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Events to send (`agent.replied`, `cron.failed`,
+    /// `channel.disconnected`, `budget.exceeded`, `alert.triggered`); empty means all.
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// Key for the `X-Nanoclaw-Signature` HMAC-SHA256 header; none when empty.
+    #[serde(default)]
+    pub secret: String,
+    /// Delivery attempts after the first one fails.
+    #[serde(default = "default_webhook_retries")]
+    pub max_retries: u32,
+}
+
+fn default_webhook_retries() -> u32 {
+    3
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            events: Vec::new(),
+            secret: String::new(),
+            max_retries: default_webhook_retries(),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// System events config
+// ---------------------------------------------------------------------------
+
+/// Which system events the agent is told about, and where its reaction goes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemEventsConfig {
+    /// Event types passed to the agent (`channel_connected`,
+    /// `channel_disconnected`, `cron_fired`, `subagent_finished`,
+    /// `budget_warning`); none when empty.
+    #[serde(default)]
+    pub react: Vec<String>,
+    /// Channel and chat that receive the agent's reaction; it is only
+    /// logged while either is empty.
+    #[serde(default)]
+    pub channel: String,
+    #[serde(default)]
+    pub chat_id: String,
+}
+
+// ---------------------------------------------------------------------------
+// Bus bridge config
+// ---------------------------------------------------------------------------
+
+/// Mirrors bus traffic to an MQTT broker or Redis streams.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BridgeConfig {
+    /// `mqtt://[user:pass@]host[:port]` or `redis://[:pass@]host[:port][/db]`;
+    /// no bridge when empty.
+    #[serde(default)]
+    pub url: String,
+    /// MQTT topic prefix, or Redis stream key prefix.
+    #[serde(default = "default_bridge_prefix")]
+    pub prefix: String,
+    /// Accept messages injected through the bridge.
+    #[serde(default)]
+    pub inject: bool,
+}
+
+fn default_bridge_prefix() -> String {
+    "nanoclaw".to_string()
+}
+
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            prefix: default_bridge_prefix(),
+            inject: false,
+        }
+    }
+}
+
+impl BridgeConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.url.is_empty()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Observability config
+// ---------------------------------------------------------------------------
+
+/// Export of telemetry to monitoring systems.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ObservabilityConfig {
+    #[serde(default)]
+    pub logs: LogsConfig,
+    #[serde(default)]
+    pub transcripts: TranscriptsConfig,
+    #[serde(default)]
+    pub otlp: OtlpConfig,
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+}
+
+/// Secrets scrubbed from log lines, transcripts and the tool results the
+/// LLM sees.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RedactionConfig {
+    /// Scrub the keys, tokens and passwords in this config and common
+    /// credential formats (`sk-...`, `ghp_...`, private keys).
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// More secret values to scrub, e.g. keys kept only in the environment.
+    #[serde(default)]
+    pub secrets: Vec<String>,
+    /// Regular expressions whose matches are scrubbed too.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            secrets: Vec::new(),
+            patterns: Vec::new(),
+        }
+    }
+}
+
+/// JSON log files of the gateway and `nanoclaw serve`, in `~/.nanoclaw/logs/`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LogsConfig {
+    /// Write log files; the terminal output is unaffected.
+    #[serde(default = "default_true")]
+    pub files: bool,
+    /// Start a new file every `hour`, every `day`, or `never` (size only).
+    #[serde(default = "default_log_rotation")]
+    pub rotation: String,
+    /// Also start a new file once one reaches this size; 0 for no limit.
+    #[serde(default = "default_log_max_file_mb")]
+    pub max_file_mb: u64,
+    /// Files kept; older ones are deleted. 0 keeps all.
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+}
+
+fn default_log_rotation() -> String {
+    "day".to_string()
+}
+
+fn default_log_max_file_mb() -> u64 {
+    50
+}
+
+fn default_log_max_files() -> usize {
+    7
+}
+
+impl Default for LogsConfig {
+    fn default() -> Self {
+        Self {
+            files: true,
+            rotation: default_log_rotation(),
+            max_file_mb: default_log_max_file_mb(),
+            max_files: default_log_max_files(),
+        }
+    }
+}
+
+/// Conversation transcripts in `~/.nanoclaw/transcripts/`, one file a day.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Redact the secrets that `observability.redaction` scrubs.
+    #[serde(default = "default_true")]
+    pub redact_secrets: bool,
+    /// Regular expressions whose matches are replaced by `[redacted]`.
+    #[serde(default)]
+    pub redact: Vec<String>,
+}
+
+impl Default for TranscriptsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            redact_secrets: true,
+            redact: Vec::new(),
+        }
+    }
+}
+
+/// Traces and metrics sent to an OpenTelemetry collector over OTLP/HTTP.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OtlpConfig {
+    /// Collector base URL, e.g. `http://localhost:4318`; no export when empty.
+    #[serde(default)]
+    pub endpoint: String,
+    /// Extra headers sent with each export, e.g. for authentication.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// `service.name` of the exported resource.
+    #[serde(default = "default_otlp_service_name")]
+    pub service_name: String,
+    /// Export turn, LLM call and tool call spans.
+    #[serde(default = "default_true")]
+    pub traces: bool,
+    /// Export counters and duration histograms.
+    #[serde(default = "default_true")]
+    pub metrics: bool,
+    /// Seconds between exports.
+    #[serde(default = "default_otlp_export_interval")]
+    pub export_interval_secs: u64,
+}
+
+fn default_otlp_service_name() -> String {
+    "nanoclaw".to_string()
+}
+
+fn default_otlp_export_interval() -> u64 {
+    10
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            headers: HashMap::new(),
+            service_name: default_otlp_service_name(),
+            traces: true,
+            metrics: true,
+            export_interval_secs: default_otlp_export_interval(),
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -308,9 +1340,15 @@ pub struct ToolsConfig {
 // ---------------------------------------------------------------------------
 
 /// Root configuration for nanoclaw.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Config {
+    /// Schema version of the file; older files are migrated on load.
+    #[serde(default = "default_config_version")]
+    pub version: u32,
+    /// Config fragments merged under this file (see `config::include`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
     #[serde(default)]
     pub agents: AgentsConfig,
     #[serde(default)]
@@ -320,7 +1358,60 @@ pub struct Config {
     #[serde(default)]
     pub gateway: GatewayConfig,
     #[serde(default)]
+    pub cron: CronConfig,
+    #[serde(default)]
     pub tools: ToolsConfig,
+    #[serde(default)]
+    pub heartbeat: HeartbeatConfig,
+    #[serde(default)]
+    pub guardrails: GuardrailsConfig,
+    #[serde(default)]
+    pub privacy: PrivacyConfig,
+    /// Transforms applied to final replies, by channel name; `"*"` applies
+    /// to every channel, before the channel's own chain.
+    #[serde(default)]
+    pub post_processing: HashMap<String, Vec<PostProcessorConfig>>,
+    /// URLs notified of events.
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    #[serde(default)]
+    pub system_events: SystemEventsConfig,
+    #[serde(default)]
+    pub bridge: BridgeConfig,
+    #[serde(default)]
+    pub observability: ObservabilityConfig,
+    /// Named lists of `channel:chat_id` recipients, addressed as channel
+    /// `group` with the name as chat ID.
+    #[serde(default)]
+    pub groups: HashMap<String, Vec<String>>,
+}
+
+fn default_config_version() -> u32 {
+    CONFIG_VERSION
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: default_config_version(),
+            include: Vec::new(),
+            agents: AgentsConfig::default(),
+            channels: ChannelsConfig::default(),
+            providers: ProvidersConfig::default(),
+            gateway: GatewayConfig::default(),
+            cron: CronConfig::default(),
+            tools: ToolsConfig::default(),
+            heartbeat: HeartbeatConfig::default(),
+            guardrails: GuardrailsConfig::default(),
+            privacy: PrivacyConfig::default(),
+            post_processing: HashMap::new(),
+            webhooks: Vec::new(),
+            system_events: SystemEventsConfig::default(),
+            bridge: BridgeConfig::default(),
+            observability: ObservabilityConfig::default(),
+            groups: HashMap::new(),
+        }
+    }
 }
 
 impl Config {
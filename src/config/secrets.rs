@@ -0,0 +1,377 @@
+//! Secrets kept out of the config file.
+//!
+//! Any string value in the config can be a reference of the form
+//! `keyring:<name>` instead of the secret itself. References are resolved
+//! when the config is loaded, from the entry `<name>` of the `nanoclaw`
+//! service in the platform keyring (Keychain on macOS, Credential Manager
+//! on Windows, Secret Service on Linux). Secret fields (API keys, tokens,
+//! ...) can also be `env:<VAR>` or `file:<path>`. Values encrypted in the
+//! file (see [`crate::config::crypt`]) are decrypted at the same point.
+//!
+//! Loading also checks for careless handling of secrets: a config file
+//! others can read, or secrets copied into workspace files.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::config::crypt::{decrypt_secrets, is_secret_key};
+use crate::config::schema::Config;
+
+/// Prefix that marks a config value as a keyring reference.
+pub const KEYRING_PREFIX: &str = "keyring:";
+
+/// Keyring service under which nanoclaw stores its entries.
+const KEYRING_SERVICE: &str = "nanoclaw";
+
+fn entry(name: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, name).map_err(|e| anyhow!("keyring: {}", e))
+}
+
+/// Read the secret `name` from the keyring.
+pub fn get_secret(name: &str) -> Result<String> {
+    entry(name)?
+        .get_password()
+        .map_err(|e| anyhow!("cannot read keyring entry '{}': {}", name, e))
+}
+
+/// Store `value` as the secret `name` in the keyring.
+pub fn set_secret(name: &str, value: &str) -> Result<()> {
+    entry(name)?
+        .set_password(value)
+        .map_err(|e| anyhow!("cannot write keyring entry '{}': {}", name, e))
+}
+
+/// Remove the secret `name` from the keyring.
+pub fn delete_secret(name: &str) -> Result<()> {
+    entry(name)?
+        .delete_credential()
+        .map_err(|e| anyhow!("cannot delete keyring entry '{}': {}", name, e))
+}
+
+/// Prefix of secret fields read from an environment variable.
+const ENV_PREFIX: &str = "env:";
+
+/// Prefix of secret fields read from a file.
+const FILE_PREFIX: &str = "file:";
+
+/// Replace `keyring:` references in `config` with secrets from the OS
+/// keyring and `env:`/`file:` secret fields with their values, after
+/// decrypting values encrypted with `config encrypt`.
+pub fn resolve_secrets(config: Config) -> Config {
+    let config = resolve_with(decrypt_secrets(config), |name| {
+        get_secret(name).map_err(|e| e.to_string())
+    });
+    resolve_indirect(config, |var| std::env::var(var).ok())
+}
+
+/// Replace `keyring:` references using `lookup`. References that cannot be
+/// resolved are logged and become empty, so they are never used as secrets.
+pub fn resolve_with(
+    config: Config,
+    lookup: impl Fn(&str) -> std::result::Result<String, String>,
+) -> Config {
+    rewrite_strings(config, |_, s| {
+        let Some(name) = s.strip_prefix(KEYRING_PREFIX) else {
+            return false;
+        };
+        *s = lookup(name.trim()).unwrap_or_else(|e| {
+            warn!("{}", e);
+            String::new()
+        });
+        true
+    })
+}
+
+/// Replace `env:<VAR>` and `file:<path>` secret fields with the variable
+/// (looked up with `env`) or the file contents, trailing newline removed.
+/// Values that cannot be read are logged and become empty.
+pub fn resolve_indirect(config: Config, env: impl Fn(&str) -> Option<String>) -> Config {
+    rewrite_strings(config, |key, s| {
+        if !is_secret_key(key) {
+            return false;
+        }
+        let value = if let Some(var) = s.strip_prefix(ENV_PREFIX) {
+            env(var.trim()).ok_or_else(|| format!("environment variable {} is not set", var.trim()))
+        } else if let Some(path) = s.strip_prefix(FILE_PREFIX) {
+            let path = expand_home(path.trim());
+            fs::read_to_string(&path)
+                .map(|text| text.trim_end_matches(['\r', '\n']).to_string())
+                .map_err(|e| format!("cannot read secret file {}: {}", path.display(), e))
+        } else {
+            return false;
+        };
+        *s = value.unwrap_or_else(|e| {
+            warn!("{}: {}", key, e);
+            String::new()
+        });
+        true
+    })
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().unwrap_or_default().join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Apply `rewrite` to every string value in `config`, with the key it is
+/// under (for list items, the key of the list); it returns whether it
+/// changed the string. The config is only rebuilt when something changed.
+pub fn rewrite_strings(
+    config: Config,
+    mut rewrite: impl FnMut(&str, &mut String) -> bool,
+) -> Config {
+    let Ok(mut tree) = serde_json::to_value(&config) else {
+        return config;
+    };
+    if !_rewrite(&mut tree, "", &mut rewrite) {
+        return config;
+    }
+    match serde_json::from_value(tree) {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            warn!("Ignoring config secrets: {}", e);
+            config
+        }
+    }
+}
+
+/// Rewrite strings in place. Returns whether any changed.
+fn _rewrite(
+    node: &mut Value,
+    key: &str,
+    rewrite: &mut impl FnMut(&str, &mut String) -> bool,
+) -> bool {
+    match node {
+        Value::String(s) => rewrite(key, s),
+        Value::Array(items) => items
+            .iter_mut()
+            .fold(false, |found, v| _rewrite(v, key, rewrite) | found),
+        Value::Object(map) => map
+            .iter_mut()
+            .fold(false, |found, (k, v)| _rewrite(v, k, rewrite) | found),
+        _ => false,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Hygiene checks
+// ---------------------------------------------------------------------------
+
+/// Files larger than this are not searched for secrets.
+const MAX_SCAN_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Stop searching the workspace after this many files.
+const MAX_SCAN_FILES: usize = 2000;
+
+/// Secrets shorter than this are not searched for (too many false hits).
+const MIN_SECRET_LEN: usize = 8;
+
+/// Warnings about how the secrets of `config`, loaded from `config_path`,
+/// are stored.
+pub fn check_hygiene(config_path: &Path, config: &Config) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let secrets = secret_values(config);
+    if secrets.is_empty() {
+        return warnings;
+    }
+
+    // Secrets from the keyring or the environment are not in the file.
+    let in_file = fs::read_to_string(config_path)
+        .is_ok_and(|text| secrets.iter().any(|s| text.contains(s.as_str())));
+    if in_file && is_readable_by_others(config_path) {
+        warnings.push(format!(
+            "{} holds secrets and is readable by other users; run `chmod 600 {}`",
+            config_path.display(),
+            config_path.display()
+        ));
+    }
+
+    let workspace = config.workspace_path();
+    if in_file && config_path.starts_with(&workspace) {
+        warnings.push(format!(
+            "{} holds secrets and is inside the workspace {}",
+            config_path.display(),
+            workspace.display()
+        ));
+    }
+    for file in files_containing(&workspace, &secrets)
+        .into_iter()
+        .filter(|f| f != config_path)
+    {
+        warnings.push(format!(
+            "{} contains a secret from the config; remove it or rotate the key",
+            file.display()
+        ));
+    }
+    warnings
+}
+
+/// The non-trivial values of secret fields.
+fn secret_values(config: &Config) -> Vec<String> {
+    let mut secrets = Vec::new();
+    if let Ok(tree) = serde_json::to_value(config) {
+        _collect_secrets(&tree, "", &mut secrets);
+    }
+    secrets
+}
+
+fn _collect_secrets(node: &Value, key: &str, secrets: &mut Vec<String>) {
+    match node {
+        Value::String(s) if is_secret_key(key) && s.len() >= MIN_SECRET_LEN => {
+            secrets.push(s.clone())
+        }
+        Value::Array(items) => items.iter().for_each(|v| _collect_secrets(v, key, secrets)),
+        Value::Object(map) => map.iter().for_each(|(k, v)| _collect_secrets(v, k, secrets)),
+        _ => {}
+    }
+}
+
+#[cfg(unix)]
+fn is_readable_by_others(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o004 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_readable_by_others(_path: &Path) -> bool {
+    false
+}
+
+/// Files under `dir` (skipping hidden directories) that contain any of
+/// `secrets`.
+fn files_containing(dir: &Path, secrets: &[String]) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    let mut scanned = 0;
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.is_dir() {
+                if !entry.file_name().to_string_lossy().starts_with('.') {
+                    pending.push(path);
+                }
+                continue;
+            }
+            if !meta.is_file() || meta.len() > MAX_SCAN_FILE_BYTES {
+                continue;
+            }
+            scanned += 1;
+            if scanned > MAX_SCAN_FILES {
+                return found;
+            }
+            let Ok(bytes) = fs::read(&path) else {
+                continue;
+            };
+            let text = String::from_utf8_lossy(&bytes);
+            if secrets.iter().any(|s| text.contains(s.as_str())) {
+                found.push(path);
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> std::result::Result<String, String> {
+        match name {
+            "openrouter" => Ok("sk-or-secret".to_string()),
+            _ => Err(format!("no entry {}", name)),
+        }
+    }
+
+    #[test]
+    fn test_resolves_references() {
+        let mut config = Config::default();
+        config.providers.openrouter.api_key = "keyring:openrouter".to_string();
+        config.channels.telegram.token = "keyring:missing".to_string();
+        config.channels.feishu.app_id = "plain".to_string();
+
+        let config = resolve_with(config, lookup);
+        assert_eq!(config.providers.openrouter.api_key, "sk-or-secret");
+        assert_eq!(config.channels.telegram.token, "");
+        assert_eq!(config.channels.feishu.app_id, "plain");
+    }
+
+    #[test]
+    fn test_resolves_env_and_file_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let token_file = dir.path().join("telegram-token");
+        fs::write(&token_file, "123:abc\n").unwrap();
+
+        let mut config = Config::default();
+        config.providers.openrouter.api_key = "env:OPENROUTER_KEY".to_string();
+        config.providers.openai.api_key = "env:MISSING_KEY".to_string();
+        config.channels.telegram.token = format!("file:{}", token_file.display());
+        config.agents.defaults.model = "env:NOT_A_SECRET".to_string();
+
+        let config = resolve_indirect(config, |var| {
+            (var == "OPENROUTER_KEY").then(|| "sk-or-env".to_string())
+        });
+        assert_eq!(config.providers.openrouter.api_key, "sk-or-env");
+        assert_eq!(config.providers.openai.api_key, "");
+        assert_eq!(config.channels.telegram.token, "123:abc");
+        assert_eq!(config.agents.defaults.model, "env:NOT_A_SECRET");
+    }
+
+    #[test]
+    fn test_hygiene_finds_leaked_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("workspace");
+        fs::create_dir_all(workspace.join("memory")).unwrap();
+        fs::create_dir_all(workspace.join(".git")).unwrap();
+        fs::write(workspace.join("memory/MEMORY.md"), "key is sk-or-leaked-123").unwrap();
+        fs::write(workspace.join(".git/config"), "sk-or-leaked-123").unwrap();
+        fs::write(workspace.join("notes.md"), "nothing here").unwrap();
+        let config_path = workspace.join("config.json");
+        fs::write(&config_path, r#"{"providers": {"openrouter": {"apiKey": "sk-or-leaked-123"}}}"#)
+            .unwrap();
+
+        let mut config = Config::default();
+        config.agents.defaults.workspace = workspace.to_string_lossy().to_string();
+        config.providers.openrouter.api_key = "sk-or-leaked-123".to_string();
+
+        let warnings = check_hygiene(&config_path, &config);
+        assert!(warnings.iter().any(|w| w.contains("is inside the workspace")));
+        let leaks: Vec<&String> = warnings.iter().filter(|w| w.contains("contains a secret")).collect();
+        assert_eq!(leaks.len(), 1, "{:?}", warnings);
+        assert!(leaks[0].contains("MEMORY.md"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&config_path, fs::Permissions::from_mode(0o644)).unwrap();
+            assert!(check_hygiene(&config_path, &config)
+                .iter()
+                .any(|w| w.contains("readable by other users")));
+            fs::set_permissions(&config_path, fs::Permissions::from_mode(0o600)).unwrap();
+            assert!(!check_hygiene(&config_path, &config)
+                .iter()
+                .any(|w| w.contains("readable by other users")));
+        }
+
+        // Nothing to leak, nothing to check.
+        assert!(check_hygiene(&config_path, &Config::default()).is_empty());
+    }
+
+    #[test]
+    fn test_no_references_is_untouched() {
+        let config = resolve_with(Config::default(), |_| panic!("no lookups expected"));
+        let config = resolve_indirect(config, |_| panic!("no lookups expected"));
+        assert_eq!(config.gateway.port, 18790);
+    }
+}
@@ -1,2 +1,4 @@
 pub mod types;
 pub mod service;
+pub mod natural;
+pub mod output;
@@ -0,0 +1,449 @@
+//! Natural-language time parsing for one-shot (`"at"`) jobs.
+//!
+//! Understands phrases such as `"in 20 minutes"`, `"in 1 hour and 30 minutes"`,
+//! `"tomorrow 9am"`, `"friday at 14:30"`, `"tonight"`, `"noon"`, and absolute
+//! timestamps like `"2026-03-01 09:00"` or RFC 3339 strings.  All results are
+//! expressed in local time and must lie in the future.
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Weekday};
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// Time of day used when a phrase names a day but no time (e.g. `"tomorrow"`).
+const DEFAULT_HOUR: u32 = 9;
+
+/// Parse a natural-language time phrase relative to `now`.
+pub fn parse_natural_time(input: &str, now: DateTime<Local>) -> Result<DateTime<Local>> {
+    let raw = input.trim();
+    if raw.is_empty() {
+        return Err(anyhow!("Empty time expression"));
+    }
+
+    // Absolute timestamps first (case-sensitive formats like RFC 3339).
+    if let Some(dt) = parse_absolute(raw) {
+        return ensure_future(dt, now, raw);
+    }
+
+    let text = raw
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let text = text
+        .strip_prefix("at ")
+        .or_else(|| text.strip_prefix("on "))
+        .unwrap_or(&text)
+        .to_string();
+
+    if let Some(rest) = text.strip_prefix("in ") {
+        let delta = parse_duration_phrase(rest)?;
+        return now
+            .checked_add_signed(delta)
+            .ok_or_else(|| anyhow!("Duration '{}' is out of range", rest));
+    }
+
+    let dt = parse_day_and_time(&text, now)
+        .ok_or_else(|| anyhow!("Could not understand time '{}'", raw))?;
+    ensure_future(dt, now, raw)
+}
+
+/// Reject times that are not strictly after `now`.
+fn ensure_future(dt: DateTime<Local>, now: DateTime<Local>, raw: &str) -> Result<DateTime<Local>> {
+    if dt <= now {
+        return Err(anyhow!("Time '{}' is in the past", raw));
+    }
+    Ok(dt)
+}
+
+/// Parse ISO-like absolute timestamps.
+fn parse_absolute(s: &str) -> Option<DateTime<Local>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Some(dt.with_timezone(&Local));
+    }
+    for fmt in ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(ndt) = NaiveDateTime::parse_from_str(s, fmt) {
+            return to_local(ndt);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return to_local(date.and_hms_opt(DEFAULT_HOUR, 0, 0)?);
+    }
+    None
+}
+
+/// Convert a naive local datetime into a `DateTime<Local>`, picking the
+/// earliest instant when the wall-clock time is ambiguous (DST fall-back).
+fn to_local(ndt: NaiveDateTime) -> Option<DateTime<Local>> {
+    Local.from_local_datetime(&ndt).earliest()
+}
+
+/// Parse `"20 minutes"`, `"an hour"`, `"2h 30m"`, `"1 day and 3 hours"`.
+fn parse_duration_phrase(s: &str) -> Result<Duration> {
+    static AMOUNT_AND_UNIT: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"\b(\d+(?:\.\d+)?\s*|(?:half an?|an?|one)\s+)([a-z]+)\b").unwrap()
+    });
+    let unknown = || anyhow!("Could not understand duration '{}'", s);
+    let out_of_range = || anyhow!("Duration '{}' is out of range", s);
+    let mut total = Duration::zero();
+    let mut consumed = 0;
+    let mut matched_any = false;
+
+    for caps in AMOUNT_AND_UNIT.captures_iter(s) {
+        let m = caps.get(0).ok_or_else(unknown)?;
+        // Only separators may appear between duration terms.
+        let gap = s[consumed..m.start()].trim();
+        if !gap.is_empty() && gap != "and" && gap != "," {
+            return Err(unknown());
+        }
+        consumed = m.end();
+
+        let amount: f64 = match caps[1].trim() {
+            "a" | "an" | "one" => 1.0,
+            a if a.starts_with("half") => 0.5,
+            n => n.parse().map_err(|_| unknown())?,
+        };
+        let unit_secs: f64 = match &caps[2] {
+            "s" | "sec" | "secs" | "second" | "seconds" => 1.0,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60.0,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 3600.0,
+            "d" | "day" | "days" => 86_400.0,
+            "w" | "week" | "weeks" => 604_800.0,
+            _ => return Err(unknown()),
+        };
+        let ms = amount * unit_secs * 1000.0;
+        if !ms.is_finite() || ms >= i64::MAX as f64 {
+            return Err(out_of_range());
+        }
+        total = Duration::try_milliseconds(ms as i64)
+            .and_then(|d| total.checked_add(&d))
+            .ok_or_else(out_of_range)?;
+        matched_any = true;
+    }
+
+    if !matched_any || !s[consumed..].trim().is_empty() || total <= Duration::zero() {
+        return Err(unknown());
+    }
+    Ok(total)
+}
+
+/// Which day a phrase refers to.
+enum DaySpec {
+    Today,
+    Tonight,
+    Tomorrow,
+    Weekday { day: Weekday, next: bool },
+}
+
+/// Parse phrases combining an optional day word and an optional time of day,
+/// in either order (`"tomorrow at 9am"`, `"9am tomorrow"`, `"next monday"`).
+fn parse_day_and_time(s: &str, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let mut words: Vec<&str> = s.split(' ').collect();
+    let mut day: Option<DaySpec> = None;
+
+    // Extract the day specification.
+    let mut i = 0;
+    while i < words.len() {
+        let w = words[i];
+        let spec = match w {
+            "today" => Some(DaySpec::Today),
+            "tonight" => Some(DaySpec::Tonight),
+            "tomorrow" | "tmrw" => Some(DaySpec::Tomorrow),
+            _ => parse_weekday(w).map(|d| DaySpec::Weekday { day: d, next: false }),
+        };
+        if let Some(mut spec) = spec {
+            if day.is_some() {
+                return None;
+            }
+            // Absorb a preceding "next"/"this".
+            if i > 0 && (words[i - 1] == "next" || words[i - 1] == "this") {
+                if let DaySpec::Weekday { ref mut next, .. } = spec {
+                    *next = words[i - 1] == "next";
+                }
+                words.remove(i - 1);
+                i -= 1;
+            }
+            words.remove(i);
+            day = Some(spec);
+            continue;
+        }
+        i += 1;
+    }
+
+    let time_text = words
+        .into_iter()
+        .filter(|w| !w.is_empty() && *w != "at" && *w != "on")
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let time = if time_text.is_empty() {
+        None
+    } else {
+        Some(parse_time_of_day(&time_text, matches!(day, Some(DaySpec::Tonight)))?)
+    };
+
+    let today = now.date_naive();
+    let (date, time) = match day {
+        None => {
+            // Bare time: today if still ahead, otherwise tomorrow.
+            let t = time?;
+            let candidate = to_local(today.and_time(t))?;
+            if candidate > now {
+                (today, t)
+            } else {
+                (today + Duration::days(1), t)
+            }
+        }
+        Some(DaySpec::Today) => (today, time?),
+        Some(DaySpec::Tonight) => (today, time.unwrap_or(NaiveTime::from_hms_opt(21, 0, 0)?)),
+        Some(DaySpec::Tomorrow) => (
+            today + Duration::days(1),
+            time.unwrap_or(NaiveTime::from_hms_opt(DEFAULT_HOUR, 0, 0)?),
+        ),
+        Some(DaySpec::Weekday { day, next }) => {
+            let t = time.unwrap_or(NaiveTime::from_hms_opt(DEFAULT_HOUR, 0, 0)?);
+            let mut ahead = (day.num_days_from_monday() as i64
+                - today.weekday().num_days_from_monday() as i64)
+                .rem_euclid(7);
+            if ahead == 0 && (next || to_local(today.and_time(t))? <= now) {
+                ahead = 7;
+            }
+            (today + Duration::days(ahead), t)
+        }
+    };
+
+    to_local(date.and_time(time))
+}
+
+/// Parse a weekday name or common abbreviation.
+fn parse_weekday(w: &str) -> Option<Weekday> {
+    let day = match w {
+        "monday" | "mon" => Weekday::Mon,
+        "tuesday" | "tue" | "tues" => Weekday::Tue,
+        "wednesday" | "wed" => Weekday::Wed,
+        "thursday" | "thu" | "thur" | "thurs" => Weekday::Thu,
+        "friday" | "fri" => Weekday::Fri,
+        "saturday" | "sat" => Weekday::Sat,
+        "sunday" | "sun" => Weekday::Sun,
+        _ => return None,
+    };
+    Some(day)
+}
+
+/// Parse `"9am"`, `"9:30 pm"`, `"21:15"`, `"noon"`, `"morning"`, etc.
+///
+/// When `evening` is set (the phrase said "tonight"), hours 1-11 without an
+/// am/pm marker are interpreted as pm.
+fn parse_time_of_day(s: &str, evening: bool) -> Option<NaiveTime> {
+    let named = match s {
+        "noon" | "midday" => Some((12, 0)),
+        "midnight" => Some((0, 0)),
+        "morning" | "in the morning" => Some((9, 0)),
+        "afternoon" | "in the afternoon" => Some((15, 0)),
+        "evening" | "in the evening" => Some((18, 0)),
+        "night" | "at night" => Some((21, 0)),
+        _ => None,
+    };
+    if let Some((h, m)) = named {
+        return NaiveTime::from_hms_opt(h, m, 0);
+    }
+
+    static CLOCK_TIME: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"^(\d{1,2})(?:[:.](\d{2}))?\s*(am|pm|a\.m\.|p\.m\.)?$").unwrap()
+    });
+    let caps = CLOCK_TIME.captures(s)?;
+    let mut hour: u32 = caps[1].parse().ok()?;
+    let minute: u32 = caps.get(2).map(|m| m.as_str().parse().ok()).unwrap_or(Some(0))?;
+    match caps.get(3).map(|m| m.as_str().starts_with('p')) {
+        Some(is_pm) => {
+            if hour == 0 || hour > 12 {
+                return None;
+            }
+            hour %= 12;
+            if is_pm {
+                hour += 12;
+            }
+        }
+        None => {
+            if evening && (1..12).contains(&hour) {
+                hour += 12;
+            }
+        }
+    }
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fixed reference point: Wednesday 2025-01-15 10:00 local time.
+    fn now() -> DateTime<Local> {
+        to_local(
+            NaiveDate::from_ymd_opt(2025, 1, 15)
+                .unwrap()
+                .and_hms_opt(10, 0, 0)
+                .unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Local> {
+        to_local(
+            NaiveDate::from_ymd_opt(y, mo, d)
+                .unwrap()
+                .and_hms_opt(h, mi, 0)
+                .unwrap(),
+        )
+        .unwrap()
+    }
+
+    // ── Relative durations ────────────────────────────────────────
+
+    #[test]
+    fn test_in_minutes() {
+        let dt = parse_natural_time("in 20 minutes", now()).unwrap();
+        assert_eq!(dt, now() + Duration::minutes(20));
+    }
+
+    #[test]
+    fn test_in_an_hour() {
+        let dt = parse_natural_time("in an hour", now()).unwrap();
+        assert_eq!(dt, now() + Duration::hours(1));
+    }
+
+    #[test]
+    fn test_in_compound_duration() {
+        let dt = parse_natural_time("in 1 hour and 30 minutes", now()).unwrap();
+        assert_eq!(dt, now() + Duration::minutes(90));
+        let dt = parse_natural_time("in 2h 15m", now()).unwrap();
+        assert_eq!(dt, now() + Duration::minutes(135));
+    }
+
+    #[test]
+    fn test_in_half_an_hour() {
+        let dt = parse_natural_time("in half an hour", now()).unwrap();
+        assert_eq!(dt, now() + Duration::minutes(30));
+    }
+
+    #[test]
+    fn test_in_unknown_unit_rejected() {
+        assert!(parse_natural_time("in 3 fortnights", now()).is_err());
+        assert!(parse_natural_time("in a while", now()).is_err());
+    }
+
+    #[test]
+    fn test_in_absurd_duration_out_of_range() {
+        for phrase in [
+            "in 100000000 days",
+            "in 99999999999999999999 weeks",
+            "in 100000000000000 days and 100000000000000 days",
+        ] {
+            let err = parse_natural_time(phrase, now()).unwrap_err();
+            assert!(err.to_string().contains("out of range"), "{}: {}", phrase, err);
+        }
+    }
+
+    // ── Day + time ────────────────────────────────────────────────
+
+    #[test]
+    fn test_tomorrow_9am() {
+        let dt = parse_natural_time("tomorrow 9am", now()).unwrap();
+        assert_eq!(dt, at(2025, 1, 16, 9, 0));
+    }
+
+    #[test]
+    fn test_time_before_day() {
+        let dt = parse_natural_time("9:30pm tomorrow", now()).unwrap();
+        assert_eq!(dt, at(2025, 1, 16, 21, 30));
+    }
+
+    #[test]
+    fn test_tomorrow_without_time_defaults_to_morning() {
+        let dt = parse_natural_time("Tomorrow", now()).unwrap();
+        assert_eq!(dt, at(2025, 1, 16, DEFAULT_HOUR, 0));
+    }
+
+    #[test]
+    fn test_tonight() {
+        assert_eq!(parse_natural_time("tonight", now()).unwrap(), at(2025, 1, 15, 21, 0));
+        assert_eq!(
+            parse_natural_time("tonight at 8", now()).unwrap(),
+            at(2025, 1, 15, 20, 0)
+        );
+    }
+
+    #[test]
+    fn test_bare_time_later_today() {
+        let dt = parse_natural_time("at 3pm", now()).unwrap();
+        assert_eq!(dt, at(2025, 1, 15, 15, 0));
+    }
+
+    #[test]
+    fn test_bare_time_already_passed_rolls_to_tomorrow() {
+        let dt = parse_natural_time("8:00", now()).unwrap();
+        assert_eq!(dt, at(2025, 1, 16, 8, 0));
+    }
+
+    #[test]
+    fn test_noon() {
+        let dt = parse_natural_time("noon", now()).unwrap();
+        assert_eq!(dt, at(2025, 1, 15, 12, 0));
+    }
+
+    #[test]
+    fn test_weekday() {
+        // Reference is a Wednesday.
+        let dt = parse_natural_time("friday at 14:30", now()).unwrap();
+        assert_eq!(dt, at(2025, 1, 17, 14, 30));
+        let dt = parse_natural_time("on monday", now()).unwrap();
+        assert_eq!(dt, at(2025, 1, 20, DEFAULT_HOUR, 0));
+    }
+
+    #[test]
+    fn test_same_weekday_passed_goes_to_next_week() {
+        let dt = parse_natural_time("wednesday 9am", now()).unwrap();
+        assert_eq!(dt, at(2025, 1, 22, 9, 0));
+        let dt = parse_natural_time("wednesday 5pm", now()).unwrap();
+        assert_eq!(dt, at(2025, 1, 15, 17, 0));
+        let dt = parse_natural_time("next wednesday 5pm", now()).unwrap();
+        assert_eq!(dt, at(2025, 1, 22, 17, 0));
+    }
+
+    #[test]
+    fn test_today_in_past_is_rejected() {
+        let err = parse_natural_time("today 8am", now()).unwrap_err();
+        assert!(err.to_string().contains("past"));
+    }
+
+    // ── Absolute timestamps ───────────────────────────────────────
+
+    #[test]
+    fn test_iso_datetime() {
+        let dt = parse_natural_time("2025-02-01 09:15", now()).unwrap();
+        assert_eq!(dt, at(2025, 2, 1, 9, 15));
+        let dt = parse_natural_time("2025-02-01T18:00", now()).unwrap();
+        assert_eq!(dt, at(2025, 2, 1, 18, 0));
+    }
+
+    #[test]
+    fn test_iso_date_only() {
+        let dt = parse_natural_time("2025-02-01", now()).unwrap();
+        assert_eq!(dt, at(2025, 2, 1, DEFAULT_HOUR, 0));
+    }
+
+    #[test]
+    fn test_iso_in_past_rejected() {
+        assert!(parse_natural_time("2020-01-01 00:00", now()).is_err());
+    }
+
+    // ── Garbage ───────────────────────────────────────────────────
+
+    #[test]
+    fn test_garbage_rejected() {
+        assert!(parse_natural_time("", now()).is_err());
+        assert!(parse_natural_time("whenever you like", now()).is_err());
+        assert!(parse_natural_time("25:00", now()).is_err());
+        assert!(parse_natural_time("13pm", now()).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+//! Capture cron job responses to dated files in the workspace.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+
+use crate::cron::types::CronJob;
+use crate::utils::helpers::ensure_dir;
+
+/// Directory holding the captured output of `job_id`:
+/// `<workspace>/cron/<job_id>/`.
+pub fn output_dir(workspace: &Path, job_id: &str) -> PathBuf {
+    workspace.join("cron").join(job_id)
+}
+
+/// Write a job's response to `<workspace>/cron/<job_id>/<timestamp>.md`.
+///
+/// The file starts with a short header naming the job and the prompt so
+/// the trail can be reviewed without cross-referencing `jobs.json`.
+pub fn write_run_output(
+    workspace: &Path,
+    job: &CronJob,
+    response: &str,
+    at: DateTime<Local>,
+) -> anyhow::Result<PathBuf> {
+    let dir = ensure_dir(output_dir(workspace, &job.id));
+    let stem = at.format("%Y-%m-%d_%H%M%S").to_string();
+
+    // Two runs within the same second (e.g. "parallel" overlap) must not
+    // overwrite each other.
+    let mut path = dir.join(format!("{}.md", stem));
+    let mut n = 1;
+    while path.exists() {
+        n += 1;
+        path = dir.join(format!("{}-{}.md", stem, n));
+    }
+
+    let content = format!(
+        "# {}\n\n- Job: {}\n- Time: {}\n- Prompt: {}\n\n---\n\n{}\n",
+        job.name,
+        job.id,
+        at.format("%Y-%m-%d %H:%M:%S %Z"),
+        job.payload.message,
+        response.trim_end()
+    );
+    fs::write(&path, content)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    fn job() -> CronJob {
+        serde_json::from_value(serde_json::json!({
+            "id": "abc123",
+            "name": "daily digest",
+            "payload": { "message": "Summarise my inbox" }
+        }))
+        .unwrap()
+    }
+
+    fn at() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2025, 3, 14, 9, 30, 5).unwrap()
+    }
+
+    // ── write_run_output ──
+
+    #[test]
+    fn test_writes_dated_file_under_job_dir() {
+        let tmp = TempDir::new().unwrap();
+        let path = write_run_output(tmp.path(), &job(), "All quiet.\n", at()).unwrap();
+
+        assert_eq!(path, tmp.path().join("cron/abc123/2025-03-14_093005.md"));
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("# daily digest\n"));
+        assert!(content.contains("- Prompt: Summarise my inbox"));
+        assert!(content.ends_with("All quiet.\n"));
+    }
+
+    #[test]
+    fn test_same_second_runs_do_not_overwrite() {
+        let tmp = TempDir::new().unwrap();
+        let first = write_run_output(tmp.path(), &job(), "one", at()).unwrap();
+        let second = write_run_output(tmp.path(), &job(), "two", at()).unwrap();
+
+        assert_ne!(first, second);
+        assert!(second.ends_with("2025-03-14_093005-2.md"));
+        assert!(fs::read_to_string(first).unwrap().ends_with("one\n"));
+    }
+}
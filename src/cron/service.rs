@@ -1,45 +1,337 @@
 //! Cron service for managing scheduled jobs.
 
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::path::PathBuf;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::SystemTime;
 
-use chrono::Local;
-use tracing::{info, warn};
+use chrono::{DateTime, Local};
+use chrono_tz::Tz;
+use tokio::sync::{Mutex, Semaphore};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
-use crate::cron::types::{CronJob, CronJobState, CronPayload, CronSchedule, CronStore};
+use crate::cron::types::{
+    CronJob, CronJobState, CronPayload, CronRetryPolicy, CronRunRecord, CronSchedule, CronStore,
+};
+
+/// Default cap on concurrently running cron jobs.
+pub const DEFAULT_MAX_CONCURRENT_RUNS: usize = 2;
+
+/// Default minimum time between runs of a recurring job.
+pub const DEFAULT_MIN_INTERVAL_SECS: u64 = 60;
+
+/// Number of past runs kept per job.
+pub const MAX_RUN_HISTORY: usize = 20;
+
+/// Maximum characters of output or error stored per run record.
+const MAX_RECORDED_OUTPUT: usize = 500;
+
+/// Callback invoked when a job fires. Returns the agent's response text.
+pub type JobCallback = Arc<
+    dyn Fn(CronJob) -> Pin<Box<dyn Future<Output = anyhow::Result<String>> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// Callback invoked when a run fails after its retries, with the error.
+pub type FailureCallback = Arc<dyn Fn(&CronJob, &str) + Send + Sync>;
 
 fn now_ms() -> i64 {
     Local::now().timestamp_millis()
 }
 
+/// Truncate run output to [`MAX_RECORDED_OUTPUT`] characters.
+fn truncate_output(s: &str) -> String {
+    if s.chars().count() <= MAX_RECORDED_OUTPUT {
+        return s.to_string();
+    }
+    let mut out: String = s.chars().take(MAX_RECORDED_OUTPUT).collect();
+    out.push_str("...");
+    out
+}
+
+/// Normalise a cron expression for the `cron` crate, which expects a leading
+/// seconds field. Standard 5-field expressions get `0` seconds prepended.
+fn normalize_cron_expr(expr: &str) -> String {
+    if expr.split_whitespace().count() == 5 {
+        format!("0 {}", expr.trim())
+    } else {
+        expr.trim().to_string()
+    }
+}
+
+/// Parse an IANA timezone name such as `"America/New_York"`.
+pub fn parse_timezone(name: &str) -> Result<Tz, String> {
+    name.trim()
+        .parse::<Tz>()
+        .map_err(|_| format!("Unknown timezone '{}'", name))
+}
+
+/// Compute the next run time (ms since epoch) for a schedule, strictly after
+/// `now_ms`. Returns `None` if the schedule will never fire again or is invalid.
+///
+/// Cron expressions are evaluated in the schedule's `tz`, falling back to
+/// `default_tz` and then to the system's local timezone.
+pub fn compute_next_run(
+    schedule: &CronSchedule,
+    now_ms: i64,
+    default_tz: Option<&str>,
+) -> Option<i64> {
+    match schedule.kind.as_str() {
+        "at" => schedule.at_ms.filter(|&at| at > now_ms),
+        "every" => schedule
+            .every_ms
+            .filter(|&ms| ms > 0)
+            .map(|ms| now_ms + ms),
+        "cron" => {
+            let expr = schedule.expr.as_deref()?;
+            let sched = cron::Schedule::from_str(&normalize_cron_expr(expr)).ok()?;
+            let now = DateTime::from_timestamp_millis(now_ms)?;
+            let tz_name = schedule
+                .tz
+                .as_deref()
+                .or(default_tz)
+                .filter(|s| !s.trim().is_empty());
+            match tz_name {
+                Some(name) => {
+                    let tz = parse_timezone(name).ok()?;
+                    sched
+                        .after(&now.with_timezone(&tz))
+                        .next()
+                        .map(|dt| dt.timestamp_millis())
+                }
+                None => sched
+                    .after(&now.with_timezone(&Local))
+                    .next()
+                    .map(|dt| dt.timestamp_millis()),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Plan the next run of a job: [`compute_next_run`], pushed out so recurring
+/// jobs never run more often than `min_interval_ms`, plus random jitter.
+fn plan_next_run(
+    schedule: &CronSchedule,
+    now_ms: i64,
+    default_tz: Option<&str>,
+    min_interval_ms: i64,
+) -> Option<i64> {
+    let mut next = compute_next_run(schedule, now_ms, default_tz)?;
+    if schedule.kind == "at" {
+        return Some(next);
+    }
+
+    let earliest = now_ms + min_interval_ms;
+    if next < earliest {
+        next = if schedule.kind == "cron" {
+            compute_next_run(schedule, earliest - 1, default_tz)?
+        } else {
+            earliest
+        };
+    }
+    if let Some(jitter) = schedule.jitter_ms.filter(|&j| j > 0) {
+        next += fastrand::i64(0..=jitter);
+    }
+    Some(next)
+}
+
+/// Plan the next run of `job`, or `None` if it would fall after the job's
+/// `until` limit.
+fn next_run_for(
+    job: &CronJob,
+    now_ms: i64,
+    default_tz: Option<&str>,
+    min_interval_ms: i64,
+) -> Option<i64> {
+    plan_next_run(&job.schedule, now_ms, default_tz, min_interval_ms)
+        .filter(|&next| job.until_ms.is_none_or(|until| next <= until))
+}
+
+/// Whether a job has reached its `max_runs` or `until` limit.
+fn is_expired(job: &CronJob, now_ms: i64) -> bool {
+    job.max_runs.is_some_and(|max| job.state.run_count >= max)
+        || job.until_ms.is_some_and(|until| {
+            until <= now_ms || job.state.next_run_at_ms.is_none()
+        })
+}
+
+fn new_job_id() -> String {
+    Uuid::new_v4().to_string()[..8].to_string()
+}
+
+/// Parse jobs from YAML (or JSON, which is valid YAML). Accepts either a
+/// whole store (`jobs: [...]`) or a bare list of jobs.
+pub fn parse_jobs_yaml(content: &str) -> anyhow::Result<Vec<CronJob>> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Document {
+        Store(CronStore),
+        List(Vec<CronJob>),
+    }
+    Ok(match serde_yaml::from_str(content)? {
+        Document::Store(store) => store.jobs,
+        Document::List(jobs) => jobs,
+    })
+}
+
+/// Check that a schedule is well-formed, returning a user-facing error if not.
+pub fn validate_schedule(schedule: &CronSchedule) -> Result<(), String> {
+    if let Some(tz) = schedule.tz.as_deref() {
+        parse_timezone(tz)?;
+    }
+    match schedule.kind.as_str() {
+        "at" => match schedule.at_ms {
+            Some(at) if at > now_ms() => Ok(()),
+            Some(_) => Err("'at' time is in the past".to_string()),
+            None => Err("'at' schedule requires a time".to_string()),
+        },
+        "every" => match schedule.every_ms {
+            Some(ms) if ms > 0 => Ok(()),
+            _ => Err("'every' schedule requires a positive interval".to_string()),
+        },
+        "cron" => {
+            let expr = schedule
+                .expr
+                .as_deref()
+                .ok_or_else(|| "'cron' schedule requires an expression".to_string())?;
+            cron::Schedule::from_str(&normalize_cron_expr(expr))
+                .map(|_| ())
+                .map_err(|e| format!("Invalid cron expression '{}': {}", expr, e))
+        }
+        other => Err(format!("Unknown schedule kind '{}'", other)),
+    }
+}
+
+/// Outcome of [`CronService::import_jobs`].
+#[derive(Debug, Default)]
+pub struct CronImportReport {
+    /// IDs of newly added jobs.
+    pub added: Vec<String>,
+    /// IDs of existing jobs that were overwritten.
+    pub replaced: Vec<String>,
+    /// Jobs that were not imported, with the reason.
+    pub skipped: Vec<String>,
+}
+
 /// Service that manages cron jobs with file-based persistence.
 pub struct CronService {
     store_path: PathBuf,
     store: CronStore,
     running: bool,
+    on_job: Option<JobCallback>,
+    on_failure: Option<FailureCallback>,
+    /// Timezone for cron expressions that don't specify one.
+    default_tz: Option<String>,
+    /// Minimum time between runs of a recurring job.
+    min_interval_ms: i64,
+    /// Global cap on concurrently executing runs.
+    run_slots: Arc<Semaphore>,
+    /// Number of in-flight runs per job ID.
+    running_jobs: HashMap<String, u32>,
+    /// Jobs with `"queue"` overlap that triggered while already running.
+    queued_jobs: HashSet<String>,
+    /// Modification time of the store file when we last read or wrote it,
+    /// used to pick up edits made by the CLI while the gateway is running.
+    store_mtime: Option<SystemTime>,
 }
 
 impl CronService {
     /// Create a new `CronService` with the given store file path.
     pub fn new(store_path: PathBuf) -> Self {
-        let store = if store_path.exists() {
-            std::fs::read_to_string(&store_path)
-                .ok()
-                .and_then(|c| serde_json::from_str(&c).ok())
-                .unwrap_or_default()
-        } else {
-            CronStore::default()
-        };
-        Self {
+        let mut svc = Self {
             store_path,
-            store,
+            store: CronStore::default(),
             running: false,
+            on_job: None,
+            on_failure: None,
+            default_tz: None,
+            min_interval_ms: DEFAULT_MIN_INTERVAL_SECS as i64 * 1000,
+            run_slots: Arc::new(Semaphore::new(DEFAULT_MAX_CONCURRENT_RUNS)),
+            running_jobs: HashMap::new(),
+            queued_jobs: HashSet::new(),
+            store_mtime: None,
+        };
+        svc._load();
+        svc
+    }
+
+    /// Set the callback that executes jobs when they fire.
+    pub fn set_on_job(&mut self, callback: JobCallback) {
+        self.on_job = Some(callback);
+    }
+
+    /// Set the callback told about failed runs.
+    pub fn set_on_failure(&mut self, callback: FailureCallback) {
+        self.on_failure = Some(callback);
+    }
+
+    /// Set the timezone used for cron expressions without their own `tz`.
+    /// `None` or an empty string means the system's local timezone.
+    pub fn set_default_timezone(&mut self, tz: Option<String>) {
+        self.default_tz = tz.filter(|s| !s.trim().is_empty());
+    }
+
+    /// Set the minimum time between runs of a recurring job.
+    pub fn set_min_interval(&mut self, secs: u64) {
+        self.min_interval_ms = secs as i64 * 1000;
+    }
+
+    /// Reject recurring schedules that would fire more often than the
+    /// configured minimum interval.
+    pub fn check_min_interval(&self, schedule: &CronSchedule) -> Result<(), String> {
+        let interval = match schedule.kind.as_str() {
+            "every" => schedule.every_ms,
+            "cron" => {
+                // Measure the gap between the next two occurrences.
+                let now = now_ms();
+                let tz = self.default_tz.as_deref();
+                let first = compute_next_run(schedule, now, tz);
+                first.and_then(|f| compute_next_run(schedule, f, tz).map(|second| second - f))
+            }
+            _ => None,
+        };
+        match interval {
+            Some(ms) if ms < self.min_interval_ms => Err(format!(
+                "Schedule runs every {}s; the minimum interval is {}s",
+                ms / 1000,
+                self.min_interval_ms / 1000
+            )),
+            _ => Ok(()),
         }
     }
 
+    /// Set the maximum number of job runs that may execute at once.
+    pub fn set_max_concurrent(&mut self, max: usize) {
+        self.run_slots = Arc::new(Semaphore::new(max.max(1)));
+    }
+
     /// Start the cron service.
+    ///
+    /// Recomputes next run times for recurring jobs. One-shot jobs keep their
+    /// stored time so that reminders missed while offline still fire once.
     pub async fn start(&mut self) {
         self.running = true;
+        self._reload_if_changed();
+        let now = now_ms();
+        let tz = self.default_tz.clone();
+        for job in self.store.jobs.iter_mut().filter(|j| j.enabled) {
+            if job.schedule.kind == "at" {
+                if job.state.next_run_at_ms.is_none() && job.state.last_run_at_ms.is_none() {
+                    job.state.next_run_at_ms = job.schedule.at_ms;
+                }
+            } else {
+                job.state.next_run_at_ms =
+                    next_run_for(job, now, tz.as_deref(), self.min_interval_ms);
+            }
+        }
+        self._expire_finished(now);
+        self.persist();
         info!(
             "Cron service started with {} jobs",
             self.store.jobs.len()
@@ -52,6 +344,7 @@ impl CronService {
     }
 
     /// Add a new cron job and persist the store.
+    #[allow(clippy::too_many_arguments)]
     pub fn add_job(
         &mut self,
         name: &str,
@@ -63,9 +356,10 @@ impl CronService {
         delete_after_run: bool,
     ) -> CronJob {
         let now = now_ms();
-        let id = Uuid::new_v4().to_string();
-        let short_id = id[..8].to_string();
+        let short_id = new_job_id();
 
+        let next_run_at_ms =
+            plan_next_run(&schedule, now, self.default_tz.as_deref(), self.min_interval_ms);
         let job = CronJob {
             id: short_id,
             name: name.to_string(),
@@ -77,11 +371,19 @@ impl CronService {
                 deliver,
                 channel: channel.map(|s| s.to_string()),
                 to: to.map(|s| s.to_string()),
+                save_output: false,
+            },
+            state: CronJobState {
+                next_run_at_ms,
+                ..Default::default()
             },
-            state: CronJobState::default(),
             created_at_ms: now,
             updated_at_ms: now,
             delete_after_run,
+            retry: CronRetryPolicy::default(),
+            overlap: "skip".to_string(),
+            max_runs: None,
+            until_ms: None,
         };
 
         self.store.jobs.push(job.clone());
@@ -104,6 +406,29 @@ impl CronService {
         }
     }
 
+    /// Apply `edit` to a job, recompute its next run, and persist the store.
+    pub fn update_job(&mut self, job_id: &str, edit: impl FnOnce(&mut CronJob)) -> Option<CronJob> {
+        let now = now_ms();
+        let default_tz = self.default_tz.clone();
+        let min_interval_ms = self.min_interval_ms;
+        let job = self.store.jobs.iter_mut().find(|j| j.id == job_id)?;
+        edit(job);
+        job.updated_at_ms = now;
+        job.state.next_run_at_ms = if job.enabled {
+            next_run_for(job, now, default_tz.as_deref(), min_interval_ms)
+        } else {
+            None
+        };
+        let result = job.clone();
+        self.persist();
+        Some(result)
+    }
+
+    /// Look up a single job by ID.
+    pub fn get_job(&self, job_id: &str) -> Option<CronJob> {
+        self.store.jobs.iter().find(|j| j.id == job_id).cloned()
+    }
+
     /// Remove a job by its ID. Returns `true` if a job was removed.
     pub fn remove_job(&mut self, job_id: &str) -> bool {
         let before = self.store.jobs.len();
@@ -118,9 +443,16 @@ impl CronService {
 
     /// Enable or disable a job.
     pub fn enable_job(&mut self, job_id: &str, enabled: bool) -> Option<CronJob> {
+        let now = now_ms();
+        let tz = self.default_tz.clone();
         let job = self.store.jobs.iter_mut().find(|j| j.id == job_id)?;
         job.enabled = enabled;
-        job.updated_at_ms = now_ms();
+        job.updated_at_ms = now;
+        job.state.next_run_at_ms = if enabled {
+            next_run_for(job, now, tz.as_deref(), self.min_interval_ms)
+        } else {
+            None
+        };
         let result = job.clone();
         self.persist();
         Some(result)
@@ -134,8 +466,434 @@ impl CronService {
         })
     }
 
+    // ------------------------------------------------------------------
+    // Import / export
+    // ------------------------------------------------------------------
+
+    /// Serialize job definitions to YAML, leaving out run state and
+    /// timestamps so the output is stable under version control.
+    pub fn export_yaml(&self) -> anyhow::Result<String> {
+        let mut jobs = Vec::with_capacity(self.store.jobs.len());
+        for job in &self.store.jobs {
+            let mut value = serde_yaml::to_value(job)?;
+            if let Some(map) = value.as_mapping_mut() {
+                for key in ["state", "createdAtMs", "updatedAtMs"] {
+                    map.remove(key);
+                }
+            }
+            jobs.push(value);
+        }
+        let mut doc = serde_yaml::Mapping::new();
+        doc.insert("version".into(), self.store.version.into());
+        doc.insert("jobs".into(), jobs.into());
+        Ok(serde_yaml::to_string(&doc)?)
+    }
+
+    /// Add imported jobs to the store.
+    ///
+    /// Imported jobs start with fresh run state. With `regenerate_ids`, every
+    /// job gets a new ID. Otherwise a job whose ID already exists is handled
+    /// per `on_conflict`: `"skip"` keeps the existing job, `"replace"`
+    /// overwrites it, and `"rename"` imports it under a new ID.
+    pub fn import_jobs(
+        &mut self,
+        jobs: Vec<CronJob>,
+        regenerate_ids: bool,
+        on_conflict: &str,
+    ) -> CronImportReport {
+        let now = now_ms();
+        let tz = self.default_tz.clone();
+        let mut report = CronImportReport::default();
+
+        for mut job in jobs {
+            let label = format!("'{}' ({})", job.name, job.id);
+            if let Err(e) = validate_schedule(&job.schedule) {
+                report.skipped.push(format!("{}: {}", label, e));
+                continue;
+            }
+            if let Err(e) = self.check_min_interval(&job.schedule) {
+                report.skipped.push(format!("{}: {}", label, e));
+                continue;
+            }
+
+            let existing = self.store.jobs.iter().position(|j| j.id == job.id);
+            if regenerate_ids || job.id.is_empty() {
+                job.id = new_job_id();
+            } else if let Some(idx) = existing {
+                match on_conflict {
+                    "replace" => {
+                        self.store.jobs.remove(idx);
+                        report.replaced.push(job.id.clone());
+                    }
+                    "rename" => job.id = new_job_id(),
+                    _ => {
+                        report.skipped.push(format!("{}: ID already exists", label));
+                        continue;
+                    }
+                }
+            }
+            while self.store.jobs.iter().any(|j| j.id == job.id) {
+                job.id = new_job_id();
+            }
+
+            job.state = CronJobState::default();
+            job.created_at_ms = now;
+            job.updated_at_ms = now;
+            if job.enabled {
+                job.state.next_run_at_ms =
+                    next_run_for(&job, now, tz.as_deref(), self.min_interval_ms);
+            }
+            if !report.replaced.contains(&job.id) {
+                report.added.push(job.id.clone());
+            }
+            self.store.jobs.push(job);
+        }
+
+        if !report.added.is_empty() || !report.replaced.is_empty() {
+            self.persist();
+        }
+        report
+    }
+
+    // ------------------------------------------------------------------
+    // Scheduling
+    // ------------------------------------------------------------------
+
+    /// Take all jobs that are due at `now`, advancing their schedules so they
+    /// are not picked up again while running.
+    ///
+    /// Jobs whose previous run is still in flight are handled according to
+    /// their overlap policy: skipped (recorded in history), queued to run
+    /// once more after the current run, or returned to run in parallel.
+    /// Returned jobs are counted as in flight until [`Self::finish_run`].
+    pub fn take_due_jobs(&mut self, now: i64) -> Vec<CronJob> {
+        self._reload_if_changed();
+        let tz = self.default_tz.clone();
+        let mut triggered = Vec::new();
+        for job in self.store.jobs.iter_mut().filter(|j| j.enabled) {
+            match job.state.next_run_at_ms {
+                Some(next) if next <= now => {
+                    triggered.push(job.clone());
+                    job.state.next_run_at_ms = if job.schedule.kind == "at" {
+                        None
+                    } else {
+                        next_run_for(job, now, tz.as_deref(), self.min_interval_ms)
+                    };
+                }
+                _ => {}
+            }
+        }
+
+        if triggered.is_empty() {
+            if self._expire_finished(now) {
+                self.persist();
+            }
+            return Vec::new();
+        }
+
+        let mut due = Vec::new();
+        for job in triggered {
+            let busy = self.running_jobs.get(&job.id).copied().unwrap_or(0) > 0;
+            if busy && job.overlap != "parallel" {
+                if job.overlap == "queue" {
+                    info!("Cron: job {} still running, queueing another run", job.id);
+                    self.queued_jobs.insert(job.id.clone());
+                } else {
+                    info!("Cron: job {} still running, skipping this trigger", job.id);
+                    self._record_skip(&job.id, now);
+                }
+                continue;
+            }
+            *self.running_jobs.entry(job.id.clone()).or_default() += 1;
+            due.push(job);
+        }
+
+        self.persist();
+        due
+    }
+
+    /// Mark one run of a job as finished. If another run was queued while it
+    /// was in flight, the job is returned (and counted as in flight again).
+    pub fn finish_run(&mut self, job_id: &str) -> Option<CronJob> {
+        if let Some(count) = self.running_jobs.get_mut(job_id) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                self.running_jobs.remove(job_id);
+            }
+        }
+        if !self.queued_jobs.remove(job_id) {
+            return None;
+        }
+        let job = self.get_job(job_id).filter(|j| j.enabled)?;
+        *self.running_jobs.entry(job.id.clone()).or_default() += 1;
+        Some(job)
+    }
+
+    /// Record a trigger that was skipped because the job was still running.
+    fn _record_skip(&mut self, job_id: &str, now: i64) {
+        let Some(job) = self.store.jobs.iter_mut().find(|j| j.id == job_id) else {
+            return;
+        };
+        job.state.last_status = Some("skipped".to_string());
+        Self::_push_history(
+            job,
+            CronRunRecord {
+                started_at_ms: now,
+                status: "skipped".to_string(),
+                attempts: 0,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Append a run record, keeping at most [`MAX_RUN_HISTORY`] entries.
+    fn _push_history(job: &mut CronJob, record: CronRunRecord) {
+        job.state.history.push(record);
+        let excess = job.state.history.len().saturating_sub(MAX_RUN_HISTORY);
+        job.state.history.drain(..excess);
+    }
+
+    /// Record the outcome of a job run that began at `started_at_ms`.
+    ///
+    /// Appends to the job's run history (capped at [`MAX_RUN_HISTORY`]).
+    /// After a scheduled run, one-shot jobs are deleted (when
+    /// `delete_after_run` is set) or disabled; manual runs leave them intact.
+    pub fn record_run(
+        &mut self,
+        job_id: &str,
+        started_at_ms: i64,
+        manual: bool,
+        attempts: u32,
+        result: &anyhow::Result<String>,
+    ) {
+        self._reload_if_changed();
+        let now = now_ms();
+        let Some(idx) = self.store.jobs.iter().position(|j| j.id == job_id) else {
+            return;
+        };
+        let job = &mut self.store.jobs[idx];
+        if !manual {
+            job.state.run_count += 1;
+        }
+        let mut record = CronRunRecord {
+            started_at_ms,
+            duration_ms: (now - started_at_ms).max(0),
+            attempts,
+            manual,
+            ..Default::default()
+        };
+        job.state.last_run_at_ms = Some(now);
+        job.state.last_attempts = Some(attempts);
+        match result {
+            Ok(output) => {
+                job.state.last_status = Some("ok".to_string());
+                job.state.last_error = None;
+                record.status = "ok".to_string();
+                record.output = Some(truncate_output(output));
+            }
+            Err(e) => {
+                job.state.last_status = Some("error".to_string());
+                job.state.last_error = Some(e.to_string());
+                record.status = "error".to_string();
+                record.error = Some(truncate_output(&e.to_string()));
+                if let Some(on_failure) = &self.on_failure {
+                    on_failure(job, &e.to_string());
+                }
+            }
+        }
+        Self::_push_history(job, record);
+        job.updated_at_ms = now;
+
+        if !manual && (job.schedule.kind == "at" || is_expired(job, now)) {
+            self._retire(idx);
+        }
+        self.persist();
+    }
+
+    /// Disable or (if `delete_after_run` is set) delete the job at `idx`
+    /// once it has run its course.
+    fn _retire(&mut self, idx: usize) {
+        let job = &mut self.store.jobs[idx];
+        if job.delete_after_run {
+            info!("Cron: removing finished job '{}' ({})", job.name, job.id);
+            self.store.jobs.remove(idx);
+        } else {
+            info!("Cron: disabling finished job '{}' ({})", job.name, job.id);
+            job.enabled = false;
+            job.state.next_run_at_ms = None;
+        }
+    }
+
+    /// Retire idle jobs that reached their `until` or `max_runs` limit
+    /// without a run to trigger it (e.g. while the gateway was down).
+    /// Returns `true` if any job was retired.
+    fn _expire_finished(&mut self, now: i64) -> bool {
+        let mut retired = false;
+        let mut idx = 0;
+        while idx < self.store.jobs.len() {
+            let job = &self.store.jobs[idx];
+            let idle = !self.running_jobs.contains_key(&job.id);
+            let limited = job.max_runs.is_some() || job.until_ms.is_some();
+            if job.enabled && idle && limited && job.schedule.kind != "at" && is_expired(job, now) {
+                let before = self.store.jobs.len();
+                self._retire(idx);
+                retired = true;
+                if self.store.jobs.len() < before {
+                    continue;
+                }
+            }
+            idx += 1;
+        }
+        retired
+    }
+
+    /// Spawn the background loop that fires due jobs once per second.
+    ///
+    /// Each due job runs in its own task so slow jobs do not delay others.
+    /// The loop exits when the service is stopped.
+    pub fn spawn_scheduler(service: Arc<Mutex<CronService>>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                let (due, callback) = {
+                    let mut svc = service.lock().await;
+                    if !svc.running {
+                        break;
+                    }
+                    let Some(callback) = svc.on_job.clone() else {
+                        continue;
+                    };
+                    (svc.take_due_jobs(now_ms()), callback)
+                };
+
+                for job in due {
+                    tokio::spawn(Self::_run_chain(service.clone(), callback.clone(), job));
+                }
+            }
+            info!("Cron scheduler stopped");
+        })
+    }
+
+    /// Run a job immediately through the job callback, regardless of its
+    /// schedule. Returns `None` if no job with that ID exists.
+    pub async fn run_job_now(
+        service: &Arc<Mutex<CronService>>,
+        job_id: &str,
+    ) -> Option<anyhow::Result<String>> {
+        let (job, callback) = {
+            let mut svc = service.lock().await;
+            let job = svc.get_job(job_id)?;
+            let Some(callback) = svc.on_job.clone() else {
+                return Some(Err(anyhow::anyhow!("No job handler configured")));
+            };
+            *svc.running_jobs.entry(job.id.clone()).or_default() += 1;
+            (job, callback)
+        };
+
+        info!("Cron: manually running job '{}' ({})", job.name, job.id);
+        let (result, queued) = Self::_run_once(service, &callback, job, true).await;
+        if let Some(next) = queued {
+            tokio::spawn(Self::_run_chain(service.clone(), callback, next));
+        }
+        Some(result)
+    }
+
+    /// Run a scheduled job, then any runs queued behind it.
+    async fn _run_chain(service: Arc<Mutex<CronService>>, callback: JobCallback, job: CronJob) {
+        let mut next = Some(job);
+        while let Some(job) = next.take() {
+            info!("Cron: running job '{}' ({})", job.name, job.id);
+            let job_id = job.id.clone();
+            let (result, queued) = Self::_run_once(&service, &callback, job, false).await;
+            if let Err(e) = result {
+                error!("Cron job {} failed: {}", job_id, e);
+            }
+            next = queued;
+        }
+    }
+
+    /// Execute one run under the global concurrency cap and record it.
+    /// Returns the result and the job again if another run was queued.
+    async fn _run_once(
+        service: &Arc<Mutex<CronService>>,
+        callback: &JobCallback,
+        job: CronJob,
+        manual: bool,
+    ) -> (anyhow::Result<String>, Option<CronJob>) {
+        let slots = service.lock().await.run_slots.clone();
+        // The semaphore is never closed, so acquiring only waits for a slot.
+        let permit = slots.acquire_owned().await.ok();
+
+        let job_id = job.id.clone();
+        let started_at = now_ms();
+        let (result, attempts) = Self::_execute_with_retry(callback, job).await;
+        drop(permit);
+
+        let mut svc = service.lock().await;
+        svc.record_run(&job_id, started_at, manual, attempts, &result);
+        let queued = svc.finish_run(&job_id);
+        (result, queued)
+    }
+
+    /// Run a job through `callback`, retrying failures according to the
+    /// job's retry policy. Returns the final result and the attempts made.
+    async fn _execute_with_retry(
+        callback: &JobCallback,
+        job: CronJob,
+    ) -> (anyhow::Result<String>, u32) {
+        let max_attempts = job.retry.max_attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            let result = callback(job.clone()).await;
+            match result {
+                Err(e) if attempt < max_attempts => {
+                    let delay = job.retry.delay_ms(attempt);
+                    warn!(
+                        "Cron job {} attempt {}/{} failed: {}; retrying in {}ms",
+                        job.id, attempt, max_attempts, e, delay
+                    );
+                    tokio::time::sleep(std::time::Duration::from_millis(delay as u64)).await;
+                    attempt += 1;
+                }
+                result => return (result, attempt),
+            }
+        }
+    }
+
+    // ------------------------------------------------------------------
+    // Persistence
+    // ------------------------------------------------------------------
+
+    /// Load the store from disk, replacing the in-memory copy.
+    fn _load(&mut self) {
+        self.store = if self.store_path.exists() {
+            std::fs::read_to_string(&self.store_path)
+                .ok()
+                .and_then(|c| serde_json::from_str(&c).ok())
+                .unwrap_or_default()
+        } else {
+            CronStore::default()
+        };
+        self.store_mtime = self._file_mtime();
+    }
+
+    /// Reload the store if the file was modified by another process.
+    fn _reload_if_changed(&mut self) {
+        let mtime = self._file_mtime();
+        if mtime.is_some() && mtime != self.store_mtime {
+            info!("Cron: store changed on disk, reloading");
+            self._load();
+        }
+    }
+
+    fn _file_mtime(&self) -> Option<SystemTime> {
+        std::fs::metadata(&self.store_path)
+            .and_then(|m| m.modified())
+            .ok()
+    }
+
     /// Serialize the current store to disk.
-    fn persist(&self) {
+    fn persist(&mut self) {
         if let Some(parent) = self.store_path.parent() {
             std::fs::create_dir_all(parent).ok();
         }
@@ -144,6 +902,7 @@ impl CronService {
                 warn!("Failed to persist cron store: {}", e);
             }
         }
+        self.store_mtime = self._file_mtime();
     }
 }
 
@@ -392,4 +1151,693 @@ mod tests {
         assert!(job.created_at_ms > 0);
         assert_eq!(job.created_at_ms, job.updated_at_ms);
     }
+
+    // ── Scheduling ────────────────────────────────────────────────
+
+    fn at_schedule(at_ms: i64) -> CronSchedule {
+        CronSchedule {
+            kind: "at".to_string(),
+            at_ms: Some(at_ms),
+            ..CronSchedule::default()
+        }
+    }
+
+    #[test]
+    fn test_compute_next_run_every() {
+        assert_eq!(compute_next_run(&every_60s(), 1_000, None), Some(61_000));
+    }
+
+    #[test]
+    fn test_compute_next_run_at_future_and_past() {
+        assert_eq!(compute_next_run(&at_schedule(5_000), 1_000, None), Some(5_000));
+        assert_eq!(compute_next_run(&at_schedule(500), 1_000, None), None);
+    }
+
+    #[test]
+    fn test_compute_next_run_cron_five_fields() {
+        let now = now_ms();
+        let next = compute_next_run(&cron_9am(), now, None).expect("cron should have a next run");
+        assert!(next > now);
+        assert!(next <= now + 24 * 3600 * 1000 + 1000);
+    }
+
+    #[test]
+    fn test_validate_schedule() {
+        assert!(validate_schedule(&every_60s()).is_ok());
+        assert!(validate_schedule(&cron_9am()).is_ok());
+        assert!(validate_schedule(&at_schedule(now_ms() + 60_000)).is_ok());
+        assert!(validate_schedule(&at_schedule(1_000)).is_err());
+        let bad = CronSchedule {
+            kind: "cron".to_string(),
+            expr: Some("not a cron".to_string()),
+            ..CronSchedule::default()
+        };
+        assert!(validate_schedule(&bad).is_err());
+        let bad_tz = CronSchedule {
+            tz: Some("Mars/Olympus".to_string()),
+            ..cron_9am()
+        };
+        assert!(validate_schedule(&bad_tz).is_err());
+    }
+
+    // ── Timezones ─────────────────────────────────────────────────
+
+    fn cron_9am_in(tz: Option<&str>) -> CronSchedule {
+        CronSchedule {
+            tz: tz.map(|s| s.to_string()),
+            ..cron_9am()
+        }
+    }
+
+    /// 2025-01-15 00:00:00 UTC.
+    const MIDNIGHT_UTC_MS: i64 = 1_736_899_200_000;
+
+    #[test]
+    fn test_cron_uses_job_timezone() {
+        let next = compute_next_run(&cron_9am_in(Some("America/New_York")), MIDNIGHT_UTC_MS, None)
+            .unwrap();
+        // 09:00 EST is 14:00 UTC.
+        assert_eq!(next, MIDNIGHT_UTC_MS + 14 * 3_600_000);
+    }
+
+    #[test]
+    fn test_cron_falls_back_to_default_timezone() {
+        let next = compute_next_run(&cron_9am_in(None), MIDNIGHT_UTC_MS, Some("Asia/Tokyo"))
+            .unwrap();
+        // 09:00 JST on Jan 15 is 00:00 UTC, which is not strictly after now,
+        // so the next run is the following day.
+        assert_eq!(next, MIDNIGHT_UTC_MS + 24 * 3_600_000);
+    }
+
+    #[test]
+    fn test_job_timezone_overrides_default() {
+        let next = compute_next_run(&cron_9am_in(Some("UTC")), MIDNIGHT_UTC_MS, Some("Asia/Tokyo"))
+            .unwrap();
+        assert_eq!(next, MIDNIGHT_UTC_MS + 9 * 3_600_000);
+    }
+
+    #[test]
+    fn test_service_default_timezone_applies_to_new_jobs() {
+        let (mut svc, _tmp) = temp_service();
+        svc.set_default_timezone(Some("Pacific/Kiritimati".to_string()));
+        let job = svc.add_job("tz", cron_9am_in(None), "m", false, None, None, false);
+        let expected = compute_next_run(&job.schedule, job.created_at_ms, Some("Pacific/Kiritimati"));
+        assert_eq!(job.state.next_run_at_ms, expected);
+    }
+
+    #[test]
+    fn test_add_job_sets_next_run() {
+        let (mut svc, _tmp) = temp_service();
+        let at = now_ms() + 60_000;
+        let job = svc.add_job("once", at_schedule(at), "m", false, None, None, true);
+        assert_eq!(job.state.next_run_at_ms, Some(at));
+    }
+
+    #[test]
+    fn test_take_due_jobs_only_returns_due() {
+        let (mut svc, _tmp) = temp_service();
+        let now = now_ms();
+        let due = svc.add_job("due", at_schedule(now + 1_000), "m", false, None, None, true);
+        svc.add_job("later", at_schedule(now + 3_600_000), "m", false, None, None, true);
+
+        let jobs = svc.take_due_jobs(now + 2_000);
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].id, due.id);
+        // Not returned twice while in flight.
+        assert!(svc.take_due_jobs(now + 3_000).is_empty());
+    }
+
+    #[test]
+    fn test_take_due_jobs_advances_recurring() {
+        let (mut svc, _tmp) = temp_service();
+        let job = svc.add_job("tick", every_60s(), "m", false, None, None, false);
+        let fire_at = job.state.next_run_at_ms.unwrap();
+        assert_eq!(svc.take_due_jobs(fire_at).len(), 1);
+        let stored = &svc.list_jobs(true)[0];
+        assert_eq!(stored.state.next_run_at_ms, Some(fire_at + 60_000));
+    }
+
+    #[test]
+    fn test_record_run_deletes_one_shot() {
+        let (mut svc, _tmp) = temp_service();
+        let now = now_ms();
+        let job = svc.add_job("once", at_schedule(now + 1_000), "m", false, None, None, true);
+        svc.take_due_jobs(now + 2_000);
+        svc.record_run(&job.id, now, false, 1, &Ok("done".to_string()));
+        assert!(svc.list_jobs(true).is_empty());
+    }
+
+    #[test]
+    fn test_record_run_disables_kept_one_shot() {
+        let (mut svc, _tmp) = temp_service();
+        let now = now_ms();
+        let job = svc.add_job("once", at_schedule(now + 1_000), "m", false, None, None, false);
+        svc.take_due_jobs(now + 2_000);
+        svc.record_run(&job.id, now, false, 1, &Err(anyhow::anyhow!("boom")));
+        let jobs = svc.list_jobs(true);
+        assert_eq!(jobs.len(), 1);
+        assert!(!jobs[0].enabled);
+        assert_eq!(jobs[0].state.last_status.as_deref(), Some("error"));
+        assert_eq!(jobs[0].state.last_error.as_deref(), Some("boom"));
+    }
+
+    #[test]
+    fn test_reload_picks_up_external_changes() {
+        let tmp = NamedTempFile::new().expect("failed to create temp file");
+        let path = tmp.path().to_path_buf();
+        std::fs::remove_file(&path).ok();
+
+        let mut gateway = CronService::new(path.clone());
+        gateway.add_job("a", every_60s(), "m", false, None, None, false);
+
+        let mut cli = CronService::new(path);
+        cli.add_job("b", every_60s(), "m", false, None, None, false);
+        // Force a different mtime even if the clock granularity is coarse.
+        let later = SystemTime::now() + std::time::Duration::from_secs(2);
+        std::fs::File::options()
+            .write(true)
+            .open(&cli.store_path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+
+        gateway.take_due_jobs(0);
+        assert_eq!(gateway.list_jobs(true).len(), 2);
+    }
+
+    // ── Run history ───────────────────────────────────────────────
+
+    #[test]
+    fn test_record_run_appends_history() {
+        let (mut svc, _tmp) = temp_service();
+        let job = svc.add_job("tick", every_60s(), "m", false, None, None, false);
+        let start = now_ms() - 1_500;
+        svc.record_run(&job.id, start, false, 1, &Ok("all good".to_string()));
+        svc.record_run(&job.id, start, false, 1, &Err(anyhow::anyhow!("timeout")));
+
+        let history = svc.get_job(&job.id).unwrap().state.history;
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].status, "ok");
+        assert_eq!(history[0].output.as_deref(), Some("all good"));
+        assert!(history[0].duration_ms >= 1_500);
+        assert_eq!(history[1].status, "error");
+        assert_eq!(history[1].error.as_deref(), Some("timeout"));
+    }
+
+    #[test]
+    fn test_failed_runs_are_reported() {
+        let (mut svc, _tmp) = temp_service();
+        let failures = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = failures.clone();
+        svc.set_on_failure(Arc::new(move |job: &CronJob, error: &str| {
+            seen.lock().unwrap().push(format!("{}: {}", job.name, error));
+        }));
+        let job = svc.add_job("tick", every_60s(), "m", false, None, None, false);
+        svc.record_run(&job.id, now_ms(), false, 1, &Ok("fine".to_string()));
+        svc.record_run(&job.id, now_ms(), false, 3, &Err(anyhow::anyhow!("boom")));
+        assert_eq!(*failures.lock().unwrap(), vec!["tick: boom".to_string()]);
+    }
+
+    #[test]
+    fn test_run_history_is_capped_and_truncated() {
+        let (mut svc, _tmp) = temp_service();
+        let job = svc.add_job("tick", every_60s(), "m", false, None, None, false);
+        for i in 0..(MAX_RUN_HISTORY + 5) {
+            svc.record_run(&job.id, i as i64, false, 1, &Ok("x".repeat(2_000)));
+        }
+        let history = svc.get_job(&job.id).unwrap().state.history;
+        assert_eq!(history.len(), MAX_RUN_HISTORY);
+        // Oldest entries were dropped.
+        assert_eq!(history[0].started_at_ms, 5);
+        assert_eq!(
+            history[0].output.as_ref().unwrap().chars().count(),
+            MAX_RECORDED_OUTPUT + 3
+        );
+    }
+
+    // ── Manual runs ───────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn test_run_job_now_uses_callback_and_keeps_one_shot() {
+        let (mut svc, _tmp) = temp_service();
+        let job = svc.add_job(
+            "once",
+            at_schedule(now_ms() + 3_600_000),
+            "ping",
+            false,
+            None,
+            None,
+            true,
+        );
+        svc.set_on_job(Arc::new(|job: CronJob| {
+            Box::pin(async move { Ok(format!("echo: {}", job.payload.message)) })
+        }));
+        let svc = Arc::new(Mutex::new(svc));
+
+        let result = CronService::run_job_now(&svc, &job.id).await.unwrap();
+        assert_eq!(result.unwrap(), "echo: ping");
+
+        let stored = svc.lock().await.get_job(&job.id).expect("one-shot kept");
+        assert!(stored.enabled);
+        assert_eq!(stored.state.history.len(), 1);
+        assert!(stored.state.history[0].manual);
+    }
+
+    #[tokio::test]
+    async fn test_run_job_now_unknown_job() {
+        let (svc, _tmp) = temp_service();
+        let svc = Arc::new(Mutex::new(svc));
+        assert!(CronService::run_job_now(&svc, "nope").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_run_job_now_without_handler_errors() {
+        let (mut svc, _tmp) = temp_service();
+        let job = svc.add_job("j", every_60s(), "m", false, None, None, false);
+        let svc = Arc::new(Mutex::new(svc));
+        let result = CronService::run_job_now(&svc, &job.id).await.unwrap();
+        assert!(result.is_err());
+    }
+
+    // ── Retries ───────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn test_failed_run_is_retried_until_success() {
+        let (mut svc, _tmp) = temp_service();
+        let job = svc.add_job("flaky", every_60s(), "m", false, None, None, false);
+        svc.update_job(&job.id, |j| {
+            j.retry = CronRetryPolicy {
+                max_attempts: 3,
+                backoff_ms: 0,
+            }
+        });
+
+        let calls = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let counter = calls.clone();
+        svc.set_on_job(Arc::new(move |_job: CronJob| {
+            let n = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            Box::pin(async move {
+                if n < 2 {
+                    Err(anyhow::anyhow!("transient"))
+                } else {
+                    Ok("recovered".to_string())
+                }
+            })
+        }));
+        let svc = Arc::new(Mutex::new(svc));
+
+        let result = CronService::run_job_now(&svc, &job.id).await.unwrap();
+        assert_eq!(result.unwrap(), "recovered");
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        let stored = svc.lock().await.get_job(&job.id).unwrap();
+        assert_eq!(stored.state.last_status.as_deref(), Some("ok"));
+        assert_eq!(stored.state.last_attempts, Some(2));
+        assert_eq!(stored.state.history[0].attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_retries_exhausted_records_error() {
+        let (mut svc, _tmp) = temp_service();
+        let job = svc.add_job("broken", every_60s(), "m", false, None, None, false);
+        svc.update_job(&job.id, |j| {
+            j.retry = CronRetryPolicy {
+                max_attempts: 3,
+                backoff_ms: 0,
+            }
+        });
+        svc.set_on_job(Arc::new(|_job: CronJob| {
+            Box::pin(async { Err(anyhow::anyhow!("always fails")) })
+        }));
+        let svc = Arc::new(Mutex::new(svc));
+
+        let result = CronService::run_job_now(&svc, &job.id).await.unwrap();
+        assert!(result.is_err());
+        let stored = svc.lock().await.get_job(&job.id).unwrap();
+        assert_eq!(stored.state.last_status.as_deref(), Some("error"));
+        assert_eq!(stored.state.last_attempts, Some(3));
+    }
+
+    // ── Overlap handling ──────────────────────────────────────────
+
+    /// Add a recurring job, then force it due at `now` and take it once so
+    /// it is marked as in flight.
+    fn running_job(svc: &mut CronService, overlap: &str) -> (CronJob, i64) {
+        let job = svc.add_job("slow", every_60s(), "m", false, None, None, false);
+        let job = svc
+            .update_job(&job.id, |j| j.overlap = overlap.to_string())
+            .unwrap();
+        let first = job.state.next_run_at_ms.unwrap();
+        assert_eq!(svc.take_due_jobs(first).len(), 1);
+        (job, first + 60_000)
+    }
+
+    #[test]
+    fn test_overlap_skip_records_skipped_run() {
+        let (mut svc, _tmp) = temp_service();
+        let (job, second) = running_job(&mut svc, "skip");
+
+        assert!(svc.take_due_jobs(second).is_empty());
+        let stored = svc.get_job(&job.id).unwrap();
+        assert_eq!(stored.state.last_status.as_deref(), Some("skipped"));
+        assert_eq!(stored.state.history.last().unwrap().status, "skipped");
+        assert!(svc.finish_run(&job.id).is_none());
+    }
+
+    #[test]
+    fn test_overlap_queue_reruns_after_finish() {
+        let (mut svc, _tmp) = temp_service();
+        let (job, second) = running_job(&mut svc, "queue");
+
+        assert!(svc.take_due_jobs(second).is_empty());
+        let rerun = svc.finish_run(&job.id).expect("queued run");
+        assert_eq!(rerun.id, job.id);
+        // Only one run is queued no matter how many triggers were missed.
+        assert!(svc.finish_run(&job.id).is_none());
+    }
+
+    #[test]
+    fn test_overlap_parallel_runs_anyway() {
+        let (mut svc, _tmp) = temp_service();
+        let (_job, second) = running_job(&mut svc, "parallel");
+        assert_eq!(svc.take_due_jobs(second).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_global_concurrency_cap() {
+        let (mut svc, _tmp) = temp_service();
+        svc.set_max_concurrent(1);
+        let a = svc.add_job("a", every_60s(), "m", false, None, None, false);
+        let b = svc.add_job("b", every_60s(), "m", false, None, None, false);
+
+        let active = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let peak = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let (active_cb, peak_cb) = (active.clone(), peak.clone());
+        svc.set_on_job(Arc::new(move |_job: CronJob| {
+            let active = active_cb.clone();
+            let peak = peak_cb.clone();
+            Box::pin(async move {
+                let now = active.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                peak.fetch_max(now, std::sync::atomic::Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                active.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(String::new())
+            })
+        }));
+        let svc = Arc::new(Mutex::new(svc));
+
+        let (ra, rb) = tokio::join!(
+            CronService::run_job_now(&svc, &a.id),
+            CronService::run_job_now(&svc, &b.id)
+        );
+        assert!(ra.unwrap().is_ok());
+        assert!(rb.unwrap().is_ok());
+        assert_eq!(peak.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    // ── Minimum interval & jitter ─────────────────────────────────
+
+    fn every_secs(secs: i64) -> CronSchedule {
+        CronSchedule {
+            kind: "every".to_string(),
+            every_ms: Some(secs * 1000),
+            ..CronSchedule::default()
+        }
+    }
+
+    #[test]
+    fn test_check_min_interval_rejects_fast_schedules() {
+        let (mut svc, _tmp) = temp_service();
+        svc.set_min_interval(60);
+        assert!(svc.check_min_interval(&every_secs(1)).is_err());
+        assert!(svc.check_min_interval(&every_secs(60)).is_ok());
+
+        let every_second = CronSchedule {
+            kind: "cron".to_string(),
+            expr: Some("* * * * * *".to_string()),
+            ..CronSchedule::default()
+        };
+        assert!(svc.check_min_interval(&every_second).is_err());
+        assert!(svc.check_min_interval(&cron_9am()).is_ok());
+    }
+
+    #[test]
+    fn test_plan_next_run_enforces_min_interval() {
+        assert_eq!(plan_next_run(&every_secs(1), 0, None, 60_000), Some(60_000));
+        assert_eq!(plan_next_run(&every_secs(120), 0, None, 60_000), Some(120_000));
+        // One-shot jobs are unaffected.
+        assert_eq!(plan_next_run(&at_schedule(5_000), 0, None, 60_000), Some(5_000));
+    }
+
+    #[test]
+    fn test_plan_next_run_cron_respects_min_interval() {
+        let every_second = CronSchedule {
+            kind: "cron".to_string(),
+            expr: Some("* * * * * *".to_string()),
+            tz: Some("UTC".to_string()),
+            ..CronSchedule::default()
+        };
+        let next = plan_next_run(&every_second, MIDNIGHT_UTC_MS, None, 60_000).unwrap();
+        assert_eq!(next, MIDNIGHT_UTC_MS + 60_000);
+    }
+
+    #[test]
+    fn test_plan_next_run_adds_bounded_jitter() {
+        let schedule = CronSchedule {
+            jitter_ms: Some(10_000),
+            ..every_secs(60)
+        };
+        for _ in 0..50 {
+            let next = plan_next_run(&schedule, 0, None, 0).unwrap();
+            assert!((60_000..=70_000).contains(&next));
+        }
+    }
+
+    // ── Editing ───────────────────────────────────────────────────
+
+    #[test]
+    fn test_edit_tz_only_reuses_expression() {
+        let (mut svc, _tmp) = temp_service();
+        let job = svc.add_job("daily", cron_9am(), "m", false, None, None, false);
+        svc.record_run(&job.id, now_ms(), false, 1, &Ok("done".to_string()));
+
+        let edited = svc
+            .update_job(&job.id, |j| {
+                j.set_schedule(CronSchedule {
+                    kind: "cron".to_string(),
+                    expr: j.schedule.expr.clone(),
+                    tz: Some("Asia/Tokyo".to_string()),
+                    ..CronSchedule::default()
+                })
+            })
+            .unwrap();
+        assert_eq!(edited.id, job.id);
+        assert_eq!(edited.schedule.expr.as_deref(), Some("0 9 * * *"));
+        assert_eq!(edited.schedule.tz.as_deref(), Some("Asia/Tokyo"));
+        assert_eq!(edited.state.history.len(), 1);
+        assert_eq!(edited.state.run_count, 1);
+
+        // 09:00 in Tokyo is 00:00 UTC.
+        let next = chrono::DateTime::from_timestamp_millis(edited.state.next_run_at_ms.unwrap())
+            .unwrap();
+        assert_eq!(next.format("%H:%M").to_string(), "00:00");
+    }
+
+    #[test]
+    fn test_edit_to_at_schedule_is_one_shot_and_enabled() {
+        let (mut svc, _tmp) = temp_service();
+        let job = svc.add_job("tick", every_60s(), "m", false, None, None, false);
+        svc.enable_job(&job.id, false);
+        assert!(svc.get_job(&job.id).unwrap().state.next_run_at_ms.is_none());
+
+        let at_ms = now_ms() + 3_600_000;
+        let edited = svc
+            .update_job(&job.id, |j| {
+                j.set_schedule(CronSchedule {
+                    kind: "at".to_string(),
+                    at_ms: Some(at_ms),
+                    ..CronSchedule::default()
+                })
+            })
+            .unwrap();
+        assert!(edited.enabled);
+        assert!(edited.delete_after_run);
+        assert_eq!(edited.state.next_run_at_ms, Some(at_ms));
+    }
+
+    #[test]
+    fn test_edit_recurring_schedule_keeps_delete_after_run() {
+        let (mut svc, _tmp) = temp_service();
+        let job = svc.add_job("limited", every_60s(), "m", false, None, None, true);
+        svc.update_job(&job.id, |j| j.max_runs = Some(3));
+
+        let edited = svc.update_job(&job.id, |j| j.set_schedule(cron_9am())).unwrap();
+        assert!(edited.delete_after_run);
+        assert_eq!(edited.max_runs, Some(3));
+    }
+
+    #[test]
+    fn test_edit_schedule_keeps_jitter_and_history() {
+        let (mut svc, _tmp) = temp_service();
+        let schedule = CronSchedule {
+            jitter_ms: Some(5_000),
+            ..every_60s()
+        };
+        let job = svc.add_job("tick", schedule, "m", false, None, None, false);
+        svc.record_run(&job.id, now_ms(), false, 1, &Ok("done".to_string()));
+
+        let edited = svc.update_job(&job.id, |j| j.set_schedule(cron_9am())).unwrap();
+        assert_eq!(edited.id, job.id);
+        assert_eq!(edited.schedule.kind, "cron");
+        assert_eq!(edited.schedule.jitter_ms, Some(5_000));
+        assert_eq!(edited.created_at_ms, job.created_at_ms);
+        assert_eq!(edited.state.history.len(), 1);
+
+        let stored = svc.get_job(&job.id).unwrap();
+        assert_eq!(stored.schedule.jitter_ms, Some(5_000));
+        assert_eq!(stored.state.history.len(), 1);
+    }
+
+    // ── Expiry ────────────────────────────────────────────────────
+
+    #[test]
+    fn test_max_runs_disables_job() {
+        let (mut svc, _tmp) = temp_service();
+        let job = svc.add_job("limited", every_60s(), "m", false, None, None, false);
+        svc.update_job(&job.id, |j| j.max_runs = Some(2));
+
+        let now = now_ms();
+        svc.record_run(&job.id, now, false, 1, &Ok(String::new()));
+        assert!(svc.get_job(&job.id).unwrap().enabled);
+        // Manual runs don't count towards the limit.
+        svc.record_run(&job.id, now, true, 1, &Ok(String::new()));
+        assert!(svc.get_job(&job.id).unwrap().enabled);
+        svc.record_run(&job.id, now, false, 1, &Ok(String::new()));
+
+        let stored = svc.get_job(&job.id).unwrap();
+        assert!(!stored.enabled);
+        assert_eq!(stored.state.run_count, 2);
+    }
+
+    #[test]
+    fn test_max_runs_deletes_when_requested() {
+        let (mut svc, _tmp) = temp_service();
+        let job = svc.add_job("limited", every_60s(), "m", false, None, None, true);
+        svc.update_job(&job.id, |j| j.max_runs = Some(1));
+        svc.record_run(&job.id, now_ms(), false, 1, &Ok(String::new()));
+        assert!(svc.get_job(&job.id).is_none());
+    }
+
+    #[test]
+    fn test_until_stops_scheduling_past_limit() {
+        let (mut svc, _tmp) = temp_service();
+        let job = svc.add_job("until", every_60s(), "m", false, None, None, false);
+        let planned = job.state.next_run_at_ms.unwrap();
+        // The run after the next one would land beyond the limit. Editing
+        // re-plans from the current time, which may have moved on slightly.
+        let job = svc
+            .update_job(&job.id, |j| j.until_ms = Some(planned + 30_000))
+            .unwrap();
+        let first = job.state.next_run_at_ms.unwrap();
+        assert!(first >= planned && first <= planned + 30_000);
+
+        assert_eq!(svc.take_due_jobs(first).len(), 1);
+        assert!(svc.get_job(&job.id).unwrap().state.next_run_at_ms.is_none());
+        svc.finish_run(&job.id);
+        svc.record_run(&job.id, first, false, 1, &Ok(String::new()));
+        assert!(!svc.get_job(&job.id).unwrap().enabled);
+    }
+
+    #[test]
+    fn test_until_elapsed_while_idle_expires_job() {
+        let (mut svc, _tmp) = temp_service();
+        let job = svc.add_job("stale", every_60s(), "m", false, None, None, false);
+        let now = now_ms();
+        svc.update_job(&job.id, |j| j.until_ms = Some(now + 120_000));
+        assert!(svc.take_due_jobs(now).is_empty());
+        assert!(svc.get_job(&job.id).unwrap().enabled);
+
+        assert!(svc.take_due_jobs(now + 10 * 60_000).len() <= 1);
+        svc.finish_run(&job.id);
+        assert!(svc.take_due_jobs(now + 20 * 60_000).is_empty());
+        assert!(!svc.get_job(&job.id).unwrap().enabled);
+    }
+
+    // ── Import / export ───────────────────────────────────────────
+
+    #[test]
+    fn test_export_omits_run_state() {
+        let (mut svc, _tmp) = temp_service();
+        let job = svc.add_job("digest", cron_9am(), "summarise", false, None, None, false);
+        svc.record_run(&job.id, now_ms(), false, 1, &Ok("done".to_string()));
+
+        let yaml = svc.export_yaml().unwrap();
+        assert!(yaml.contains("name: digest"));
+        assert!(yaml.contains("0 9 * * *"));
+        assert!(!yaml.contains("state"));
+        assert!(!yaml.contains("createdAtMs"));
+    }
+
+    #[test]
+    fn test_export_import_roundtrip() {
+        let (mut src, _a) = temp_service();
+        src.add_job("one", every_60s(), "m1", false, None, None, false);
+        src.add_job("two", cron_9am(), "m2", true, Some("cli"), Some("direct"), false);
+        let yaml = src.export_yaml().unwrap();
+
+        let (mut dst, _b) = temp_service();
+        let report = dst.import_jobs(parse_jobs_yaml(&yaml).unwrap(), false, "skip");
+        assert_eq!(report.added.len(), 2);
+
+        let jobs = dst.list_jobs(true);
+        let two = jobs.iter().find(|j| j.name == "two").unwrap();
+        assert_eq!(two.payload.to.as_deref(), Some("direct"));
+        assert!(two.state.next_run_at_ms.is_some());
+        assert_eq!(
+            jobs.iter().map(|j| j.id.clone()).collect::<Vec<_>>(),
+            src.list_jobs(true).iter().map(|j| j.id.clone()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_import_conflicts() {
+        let (mut svc, _tmp) = temp_service();
+        let job = svc.add_job("original", every_60s(), "m", false, None, None, false);
+        let mut incoming = job.clone();
+        incoming.name = "incoming".to_string();
+
+        let report = svc.import_jobs(vec![incoming.clone()], false, "skip");
+        assert!(report.added.is_empty());
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(svc.get_job(&job.id).unwrap().name, "original");
+
+        let report = svc.import_jobs(vec![incoming.clone()], false, "rename");
+        assert_eq!(report.added.len(), 1);
+        assert_ne!(report.added[0], job.id);
+        assert_eq!(svc.list_jobs(true).len(), 2);
+
+        let report = svc.import_jobs(vec![incoming], false, "replace");
+        assert_eq!(report.replaced, vec![job.id.clone()]);
+        assert_eq!(svc.get_job(&job.id).unwrap().name, "incoming");
+        assert_eq!(svc.list_jobs(true).len(), 2);
+    }
+
+    #[test]
+    fn test_import_regenerates_ids_and_skips_invalid() {
+        let (mut svc, _tmp) = temp_service();
+        let yaml = r#"
+- id: fixed
+  name: hourly
+  schedule: { kind: every, everyMs: 3600000 }
+  payload: { message: check }
+- id: broken
+  name: bad
+  schedule: { kind: cron, expr: "not a cron" }
+"#;
+        let report = svc.import_jobs(parse_jobs_yaml(yaml).unwrap(), true, "skip");
+        assert_eq!(report.added.len(), 1);
+        assert_ne!(report.added[0], "fixed");
+        assert_eq!(report.skipped.len(), 1);
+        assert!(report.skipped[0].contains("bad"));
+    }
 }
@@ -20,6 +20,10 @@ pub struct CronSchedule {
     /// Timezone for cron expressions.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tz: Option<String>,
+    /// For recurring schedules: random delay of up to this many milliseconds
+    /// added to each run, to spread load.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jitter_ms: Option<i64>,
 }
 
 impl Default for CronSchedule {
@@ -30,6 +34,7 @@ impl Default for CronSchedule {
             every_ms: None,
             expr: None,
             tz: None,
+            jitter_ms: None,
         }
     }
 }
@@ -53,6 +58,9 @@ pub struct CronPayload {
     /// Recipient identifier (e.g. a phone number).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub to: Option<String>,
+    /// Also write each response to `workspace/cron/<job_id>/`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub save_output: bool,
 }
 
 fn default_payload_kind() -> String {
@@ -67,6 +75,7 @@ impl Default for CronPayload {
             deliver: false,
             channel: None,
             to: None,
+            save_output: false,
         }
     }
 }
@@ -87,6 +96,80 @@ pub struct CronJobState {
     /// Error message from the last run, if any.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_error: Option<String>,
+    /// Number of completed scheduled runs.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub run_count: u32,
+    /// Number of attempts made by the last run (including retries).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_attempts: Option<u32>,
+    /// Most recent runs, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<CronRunRecord>,
+}
+
+/// Outcome of a single job run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CronRunRecord {
+    /// Run start time in milliseconds since epoch.
+    pub started_at_ms: i64,
+    /// Wall-clock duration of the run in milliseconds.
+    pub duration_ms: i64,
+    /// `"ok"`, `"error"`, or `"skipped"` (previous run still in flight).
+    pub status: String,
+    /// Truncated agent response, if the run succeeded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// Truncated error message, if the run failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Number of attempts made, including retries.
+    #[serde(default = "default_attempts")]
+    pub attempts: u32,
+    /// Whether the run was triggered by hand rather than by the schedule.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub manual: bool,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+fn default_attempts() -> u32 {
+    1
+}
+
+/// Retry behaviour for failed runs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CronRetryPolicy {
+    /// Total attempts per trigger, including the first (1 = no retries).
+    #[serde(default = "default_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first retry in milliseconds; doubles on each retry.
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: i64,
+}
+
+fn default_backoff_ms() -> i64 {
+    30_000
+}
+
+impl Default for CronRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_attempts(),
+            backoff_ms: default_backoff_ms(),
+        }
+    }
+}
+
+impl CronRetryPolicy {
+    /// Delay before retry number `retry` (1-based), capped at one hour.
+    pub fn delay_ms(&self, retry: u32) -> i64 {
+        let factor = 1i64 << retry.saturating_sub(1).min(20);
+        self.backoff_ms.max(0).saturating_mul(factor).min(3_600_000)
+    }
 }
 
 /// A scheduled job.
@@ -109,6 +192,36 @@ pub struct CronJob {
     pub updated_at_ms: i64,
     #[serde(default)]
     pub delete_after_run: bool,
+    #[serde(default)]
+    pub retry: CronRetryPolicy,
+    /// What to do when the job triggers while a previous run is still in
+    /// flight: `"skip"`, `"queue"` (run once more afterwards), or `"parallel"`.
+    #[serde(default = "default_overlap")]
+    pub overlap: String,
+    /// Expire the job after this many scheduled runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_runs: Option<u32>,
+    /// Expire the job once this time (ms since epoch) has passed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until_ms: Option<i64>,
+}
+
+impl CronJob {
+    /// Replace the schedule, keeping the job's jitter. Switching to a one-shot
+    /// `at` schedule re-enables the job and deletes it after it runs; other
+    /// schedules keep the job's clean-up setting.
+    pub fn set_schedule(&mut self, mut schedule: CronSchedule) {
+        if schedule.kind == "at" {
+            self.delete_after_run = true;
+            self.enabled = true;
+        }
+        schedule.jitter_ms = self.schedule.jitter_ms;
+        self.schedule = schedule;
+    }
+}
+
+fn default_overlap() -> String {
+    "skip".to_string()
 }
 
 fn default_true() -> bool {
@@ -152,6 +265,7 @@ mod tests {
             at_ms: None,
             expr: None,
             tz: None,
+            jitter_ms: None,
         };
 
         let json = serde_json::to_string(&schedule).expect("serialize");
@@ -172,6 +286,7 @@ mod tests {
             tz: Some("America/New_York".to_string()),
             at_ms: None,
             every_ms: None,
+            jitter_ms: None,
         };
 
         let json = serde_json::to_string(&schedule).expect("serialize");
@@ -192,6 +307,7 @@ mod tests {
             every_ms: None,
             expr: None,
             tz: None,
+            jitter_ms: None,
         };
 
         let json = serde_json::to_string(&schedule).expect("serialize");
@@ -219,6 +335,7 @@ mod tests {
             at_ms: None,
             expr: None,
             tz: None,
+            jitter_ms: None,
         };
 
         let json = serde_json::to_string(&schedule).expect("serialize");
@@ -240,6 +357,7 @@ mod tests {
             at_ms: Some(999),
             expr: None,
             tz: None,
+            jitter_ms: None,
         };
         let val: serde_json::Value =
             serde_json::to_value(&schedule).expect("to_value");
@@ -271,6 +389,7 @@ mod tests {
             deliver: true,
             channel: Some("email".to_string()),
             to: Some("user@example.com".to_string()),
+            save_output: true,
         };
 
         let json = serde_json::to_string(&payload).expect("serialize");
@@ -281,6 +400,7 @@ mod tests {
         assert!(parsed.deliver);
         assert_eq!(parsed.channel.as_deref(), Some("email"));
         assert_eq!(parsed.to.as_deref(), Some("user@example.com"));
+        assert!(parsed.save_output);
     }
 
     #[test]
@@ -311,6 +431,9 @@ mod tests {
             last_run_at_ms: Some(1_700_000_000_000),
             last_status: Some("ok".to_string()),
             last_error: None,
+            run_count: 0,
+            last_attempts: None,
+            history: Vec::new(),
         };
 
         let json = serde_json::to_string(&state).expect("serialize");
@@ -344,6 +467,7 @@ mod tests {
                 at_ms: None,
                 expr: None,
                 tz: None,
+                jitter_ms: None,
             },
             payload: CronPayload {
                 kind: "agent_turn".to_string(),
@@ -351,11 +475,16 @@ mod tests {
                 deliver: false,
                 channel: None,
                 to: None,
+                save_output: false,
             },
             state: CronJobState::default(),
             created_at_ms: 1_700_000_000_000,
             updated_at_ms: 1_700_000_000_000,
             delete_after_run: false,
+            retry: CronRetryPolicy::default(),
+            overlap: "queue".to_string(),
+            max_runs: Some(5),
+            until_ms: Some(1_800_000_000_000),
         };
 
         let json = serde_json::to_string_pretty(&job).expect("serialize");
@@ -364,6 +493,9 @@ mod tests {
         assert_eq!(parsed.id, "abc12345");
         assert_eq!(parsed.name, "My Job");
         assert!(parsed.enabled);
+        assert_eq!(parsed.overlap, "queue");
+        assert_eq!(parsed.max_runs, Some(5));
+        assert_eq!(parsed.until_ms, Some(1_800_000_000_000));
         assert_eq!(parsed.schedule.kind, "every");
         assert_eq!(parsed.schedule.every_ms, Some(120_000));
         assert_eq!(parsed.payload.message, "check status");
@@ -411,6 +543,10 @@ mod tests {
                 created_at_ms: 0,
                 updated_at_ms: 0,
                 delete_after_run: false,
+                retry: CronRetryPolicy::default(),
+                overlap: "skip".to_string(),
+                max_runs: None,
+                until_ms: None,
             }],
         };
 
@@ -428,4 +564,32 @@ mod tests {
         assert_eq!(store.version, 1);
         assert!(store.jobs.is_empty());
     }
+
+    // ── CronRetryPolicy ───────────────────────────────────────────
+
+    #[test]
+    fn test_retry_policy_default_is_single_attempt() {
+        let policy = CronRetryPolicy::default();
+        assert_eq!(policy.max_attempts, 1);
+    }
+
+    #[test]
+    fn test_retry_policy_backoff_doubles_and_caps() {
+        let policy = CronRetryPolicy {
+            max_attempts: 5,
+            backoff_ms: 1_000,
+        };
+        assert_eq!(policy.delay_ms(1), 1_000);
+        assert_eq!(policy.delay_ms(2), 2_000);
+        assert_eq!(policy.delay_ms(3), 4_000);
+        assert_eq!(policy.delay_ms(40), 3_600_000);
+    }
+
+    #[test]
+    fn test_job_without_retry_field_uses_default() {
+        let json = r#"{"id": "j", "name": "n"}"#;
+        let job: CronJob = serde_json::from_str(json).expect("deserialize");
+        assert_eq!(job.retry, CronRetryPolicy::default());
+        assert_eq!(job.overlap, "skip");
+    }
 }
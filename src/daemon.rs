@@ -0,0 +1,318 @@
+//! Running the gateway in the background.
+//!
+//! A gateway holds a lock on `gateway.lock` while it runs, so a second one
+//! cannot start against the same data directory.
+//!
+//! `nanoclaw gateway --daemon` starts a detached gateway that logs to
+//! `~/.nanoclaw/gateway.log`. While it runs, the gateway keeps its PID in
+//! `gateway.pid` and answers on the control socket `gateway.sock`, where
+//! `nanoclaw stop` and `nanoclaw restart` ask it to shut down. Commands are
+//! single lines: `pid` answers with the PID, `api` with the URL of the REST
+//! API, and `stop` with `ok <pid>` before shutting down.
+
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::net::UnixListener;
+use tokio::sync::watch;
+use tracing::{debug, warn};
+
+/// How long to wait for a reply on the control socket.
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Files of the background gateway, in the data directory.
+pub struct Daemon {
+    dir: PathBuf,
+}
+
+impl Daemon {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn pid_path(&self) -> PathBuf {
+        self.dir.join("gateway.pid")
+    }
+
+    pub fn socket_path(&self) -> PathBuf {
+        self.dir.join("gateway.sock")
+    }
+
+    pub fn log_path(&self) -> PathBuf {
+        self.dir.join("gateway.log")
+    }
+
+    pub fn lock_path(&self) -> PathBuf {
+        self.dir.join("gateway.lock")
+    }
+
+    /// Take the data directory's instance lock, or fail if another gateway
+    /// holds it. The lock is released when the returned guard is dropped,
+    /// or when the process dies.
+    pub fn lock(&self) -> Result<InstanceLock> {
+        let path = self.lock_path();
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("cannot open {}", path.display()))?;
+        match file.try_lock() {
+            Ok(()) => Ok(InstanceLock { _file: file }),
+            Err(TryLockError::WouldBlock) => {
+                let pid = fs::read_to_string(self.pid_path())
+                    .map(|pid| format!(" (PID {})", pid.trim()))
+                    .unwrap_or_default();
+                bail!(
+                    "another gateway{} is already running with {}; stop it with `nanoclaw stop`",
+                    pid,
+                    self.dir.display()
+                )
+            }
+            Err(TryLockError::Error(e)) => {
+                Err(e).with_context(|| format!("cannot lock {}", path.display()))
+            }
+        }
+    }
+
+    /// PID of the running gateway, if it answers on the control socket.
+    pub fn running(&self) -> Option<u32> {
+        let reply = self._request("pid").ok()?;
+        reply.trim().parse().ok()
+    }
+
+    /// URL of the running gateway's REST API, if it answers on the control
+    /// socket.
+    pub fn api_url(&self) -> Option<String> {
+        let reply = self._request("api").ok()?;
+        let url = reply.trim();
+        url.starts_with("http").then(|| url.to_string())
+    }
+
+    /// Start `nanoclaw gateway <args>` detached from the terminal. Returns
+    /// its PID.
+    pub fn spawn(&self, args: &[String]) -> Result<u32> {
+        if let Some(pid) = self.running() {
+            bail!("the gateway is already running (PID {})", pid);
+        }
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.log_path())
+            .with_context(|| format!("cannot open {}", self.log_path().display()))?;
+        let exe = std::env::current_exe().context("cannot find the nanoclaw executable")?;
+        let child = Command::new(exe)
+            .arg("gateway")
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            // A process group of its own, so the terminal's Ctrl+C and
+            // hangup do not reach it.
+            .process_group(0)
+            .spawn()
+            .context("cannot start the gateway")?;
+        Ok(child.id())
+    }
+
+    /// Ask the running gateway to shut down. Returns its PID.
+    pub fn stop(&self) -> Result<u32> {
+        let reply = self._request("stop").map_err(|e| {
+            if self.pid_path().exists() {
+                anyhow!(
+                    "the gateway does not answer ({}); remove {} if it is not running",
+                    e,
+                    self.pid_path().display()
+                )
+            } else {
+                anyhow!("the gateway is not running")
+            }
+        })?;
+        reply
+            .trim()
+            .strip_prefix("ok ")
+            .and_then(|pid| pid.parse().ok())
+            .ok_or_else(|| anyhow!("unexpected reply from the gateway: {}", reply.trim()))
+    }
+
+    /// Wait until the gateway has removed its PID file, up to `timeout`.
+    pub fn wait_stopped(&self, timeout: Duration) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            if !self.pid_path().exists() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        false
+    }
+
+    /// Send `command` on the control socket and read the reply line.
+    fn _request(&self, command: &str) -> Result<String> {
+        let mut stream = UnixStream::connect(self.socket_path())?;
+        stream.set_read_timeout(Some(CONTROL_TIMEOUT))?;
+        writeln!(stream, "{}", command)?;
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply)?;
+        Ok(reply)
+    }
+
+    /// Write the PID file and answer on the control socket. The returned
+    /// guard removes both when dropped. Call with the instance lock held;
+    /// `api_url` is where local clients reach the REST API.
+    pub fn listen(&self, api_url: &str) -> Result<Control> {
+        let socket = self.socket_path();
+        // Left behind by a gateway that did not shut down cleanly.
+        let _ = fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket)
+            .with_context(|| format!("cannot listen on {}", socket.display()))?;
+        let pid = std::process::id();
+        fs::write(self.pid_path(), format!("{}\n", pid))?;
+
+        let api_url = api_url.to_string();
+        let (stop_tx, stop_rx) = watch::channel(false);
+        let task = tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!("Control socket failed: {}", e);
+                        break;
+                    }
+                };
+                let (read, mut write) = stream.into_split();
+                let mut line = String::new();
+                if tokio::io::BufReader::new(read)
+                    .read_line(&mut line)
+                    .await
+                    .is_err()
+                {
+                    continue;
+                }
+                debug!("Control command: {}", line.trim());
+                let reply = match line.trim() {
+                    "pid" => pid.to_string(),
+                    "api" => api_url.clone(),
+                    "stop" => format!("ok {}", pid),
+                    other => format!("error unknown command '{}'", other),
+                };
+                let _ = write.write_all(format!("{}\n", reply).as_bytes()).await;
+                if line.trim() == "stop" {
+                    let _ = stop_tx.send(true);
+                }
+            }
+        });
+        Ok(Control {
+            pid_path: self.pid_path(),
+            socket_path: socket,
+            stop_rx,
+            task,
+        })
+    }
+}
+
+/// Held while the gateway runs; see [`Daemon::lock`].
+#[derive(Debug)]
+pub struct InstanceLock {
+    _file: File,
+}
+
+/// The gateway's end of the control socket.
+pub struct Control {
+    pid_path: PathBuf,
+    socket_path: PathBuf,
+    stop_rx: watch::Receiver<bool>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Control {
+    /// Resolves when a `stop` command arrives.
+    pub async fn stopped(&mut self) {
+        let _ = self.stop_rx.wait_for(|stop| *stop).await;
+    }
+}
+
+impl Drop for Control {
+    fn drop(&mut self) {
+        self.task.abort();
+        remove_if_exists(&self.socket_path);
+        remove_if_exists(&self.pid_path);
+    }
+}
+
+fn remove_if_exists(path: &Path) {
+    if let Err(e) = fs::remove_file(path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            warn!("Cannot remove {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_control_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let daemon = Daemon::new(dir.path());
+        let check = Daemon::new(dir.path());
+        let none = tokio::task::spawn_blocking(move || check.running())
+            .await
+            .unwrap();
+        assert_eq!(none, None);
+
+        let mut control = daemon.listen("http://127.0.0.1:18790/api").unwrap();
+        assert!(daemon.pid_path().exists());
+        let client = Daemon::new(dir.path());
+        let (pid, url, stopped) = tokio::task::spawn_blocking(move || {
+            let pid = client.running();
+            let url = client.api_url();
+            (pid, url, client.stop().unwrap())
+        })
+        .await
+        .unwrap();
+        assert_eq!(pid, Some(std::process::id()));
+        assert_eq!(url.as_deref(), Some("http://127.0.0.1:18790/api"));
+        assert_eq!(stopped, std::process::id());
+
+        tokio::time::timeout(Duration::from_secs(5), control.stopped())
+            .await
+            .unwrap();
+        drop(control);
+        assert!(!daemon.pid_path().exists());
+        assert!(!daemon.socket_path().exists());
+    }
+
+    #[test]
+    fn test_instance_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let daemon = Daemon::new(dir.path());
+        let lock = daemon.lock().unwrap();
+        fs::write(daemon.pid_path(), "4242\n").unwrap();
+        let err = daemon.lock().unwrap_err().to_string();
+        assert!(err.contains("another gateway (PID 4242)"), "{}", err);
+
+        drop(lock);
+        assert!(daemon.lock().is_ok());
+    }
+
+    #[test]
+    fn test_stop_when_not_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let daemon = Daemon::new(dir.path());
+        let err = daemon.stop().unwrap_err().to_string();
+        assert_eq!(err, "the gateway is not running");
+
+        fs::write(daemon.pid_path(), "12345\n").unwrap();
+        let err = daemon.stop().unwrap_err().to_string();
+        assert!(err.contains("does not answer"), "{}", err);
+    }
+}
@@ -0,0 +1,491 @@
+//! Heartbeat agenda -- what the agent reviews on each beat.
+//!
+//! The agenda collects due tasks from the `HEARTBEAT.md` checklist, enabled
+//! cron jobs due within the next day, and unread items: files dropped into
+//! the workspace `inbox/` folder since the previous beat. The agent works through it and
+//! decides whether anything deserves a proactive message to the user; a
+//! per-day cap, tracked in a small state file, keeps it from getting noisy.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::cron::types::CronJob;
+use crate::heartbeat::checklist::{is_instructions_heading, Checklist};
+
+/// How far ahead cron jobs count as upcoming.
+const UPCOMING_WINDOW_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Characters of an inbox file shown in the agenda.
+const UNREAD_PREVIEW_CHARS: usize = 200;
+
+/// Bytes read from the start of an inbox file to build its preview.
+const UNREAD_PREVIEW_BYTES: u64 = 4096;
+
+/// Opening of the prompt unless the config or `HEARTBEAT.md` give their own.
+pub const DEFAULT_INSTRUCTIONS: &str =
+    "Heartbeat check. Review your agenda and work on any task you can do now.";
+
+/// Prefix of a reply that asks for a proactive message to the user.
+pub const NOTIFY_PREFIX: &str = "NOTIFY:";
+
+/// Items the agent reviews on a beat.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Agenda {
+    /// Open tasks from `HEARTBEAT.md`.
+    pub tasks: Vec<String>,
+    /// Cron jobs due soon, with their local run time.
+    pub upcoming: Vec<String>,
+    /// New files in `inbox/`, with a short preview.
+    pub unread: Vec<String>,
+    /// Instructions opening the prompt, if not the default ones.
+    pub instructions: Option<String>,
+}
+
+impl Agenda {
+    /// Gather the agenda for a beat at `now_ms`. Inbox files modified after
+    /// `unread_since_ms` are unread.
+    pub fn collect(workspace: &Path, jobs: &[CronJob], now_ms: i64, unread_since_ms: i64) -> Self {
+        let file = std::fs::read_to_string(workspace.join("HEARTBEAT.md")).unwrap_or_default();
+        Self {
+            tasks: open_tasks(&file),
+            upcoming: upcoming_jobs(jobs, now_ms),
+            unread: unread_items(&workspace.join("inbox"), unread_since_ms),
+            instructions: instructions(&file),
+        }
+    }
+
+    /// Whether there is nothing to review.
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty() && self.upcoming.is_empty() && self.unread.is_empty()
+    }
+
+    /// Prompt asking the agent to work through the agenda. `remaining` is
+    /// the number of proactive messages still allowed today.
+    pub fn prompt(&self, remaining: u32) -> String {
+        let mut prompt = self
+            .instructions
+            .as_deref()
+            .unwrap_or(DEFAULT_INSTRUCTIONS)
+            .trim()
+            .to_string();
+        prompt.push('\n');
+        for (title, items) in [
+            ("Open tasks (HEARTBEAT.md)", &self.tasks),
+            ("Upcoming scheduled jobs", &self.upcoming),
+            ("Unread items (inbox/)", &self.unread),
+        ] {
+            if !items.is_empty() {
+                prompt.push_str(&format!("\n## {}\n", title));
+                for item in items {
+                    prompt.push_str(&format!("- {}\n", item));
+                }
+            }
+        }
+
+        if !self.tasks.is_empty() {
+            prompt.push_str(
+                "\nUse the `checklist` tool to check off tasks you finish, or to \
+                 reschedule the ones that have to wait.\n",
+            );
+        }
+        prompt.push_str(
+            "\nThen decide whether anything here is worth proactively messaging the user \
+             about right now: something time-sensitive, a finished task they are waiting \
+             for, or an important new item. Routine or unchanged items are not.\n",
+        );
+        if remaining > 0 {
+            prompt.push_str(&format!(
+                "You may send at most {} more proactive message(s) today. If a message is \
+                 warranted, reply with `{}` followed by the message for the user. ",
+                remaining, NOTIFY_PREFIX
+            ));
+        } else {
+            prompt.push_str("You cannot send any more proactive messages today. ");
+        }
+        prompt.push_str("Otherwise reply with just: HEARTBEAT_OK");
+        prompt
+    }
+}
+
+/// Text of the `## Instructions` section of `HEARTBEAT.md`, if any.
+pub fn instructions(content: &str) -> Option<String> {
+    let text = content
+        .lines()
+        .skip_while(|line| !is_instructions_heading(line.trim()))
+        .skip(1)
+        .take_while(|line| !line.trim_start().starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Tasks of `HEARTBEAT.md` due today; see [`Checklist`] for the syntax.
+pub fn open_tasks(content: &str) -> Vec<String> {
+    Checklist::parse(content)
+        .due(Local::now().date_naive())
+        .map(|task| task.text.clone())
+        .collect()
+}
+
+/// Enabled jobs due within [`UPCOMING_WINDOW_MS`], soonest first.
+fn upcoming_jobs(jobs: &[CronJob], now_ms: i64) -> Vec<String> {
+    let mut due: Vec<(i64, &CronJob)> = jobs
+        .iter()
+        .filter(|j| j.enabled)
+        .filter_map(|j| Some((j.state.next_run_at_ms?, j)))
+        .filter(|(at, _)| *at >= now_ms && *at - now_ms <= UPCOMING_WINDOW_MS)
+        .collect();
+    due.sort_by_key(|(at, _)| *at);
+    due.into_iter()
+        .map(|(at, job)| {
+            let when = Local
+                .timestamp_millis_opt(at)
+                .single()
+                .map(|t| t.format("%a %H:%M").to_string())
+                .unwrap_or_default();
+            format!("{} at {}: {}", job.name, when, job.payload.message)
+        })
+        .collect()
+}
+
+/// Files in `dir` modified after `since_ms`, oldest first.
+fn unread_items(dir: &Path, since_ms: i64) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut items: Vec<(i64, String)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            let modified_ms = chrono::DateTime::<Local>::from(modified).timestamp_millis();
+            if modified_ms <= since_ms || !entry.path().is_file() {
+                return None;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            let preview: String = read_prefix(&entry.path())
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .chars()
+                .take(UNREAD_PREVIEW_CHARS)
+                .collect();
+            Some((modified_ms, format!("{}: {}", name, preview)))
+        })
+        .collect();
+    items.sort();
+    items.into_iter().map(|(_, item)| item).collect()
+}
+
+/// The first [`UNREAD_PREVIEW_BYTES`] of `path`, decoded lossily so binary or
+/// non-UTF-8 files still get a preview.
+fn read_prefix(path: &Path) -> String {
+    let mut bytes = Vec::new();
+    let read = File::open(path).and_then(|f| f.take(UNREAD_PREVIEW_BYTES).read_to_end(&mut bytes));
+    if let Err(e) = read {
+        warn!("Heartbeat: cannot read {}: {}", path.display(), e);
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// The proactive message in an agent reply, if it asked for one.
+pub fn parse_notification(response: &str) -> Option<String> {
+    let text = response.trim().strip_prefix(NOTIFY_PREFIX)?.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+// ---------------------------------------------------------------------------
+// State
+// ---------------------------------------------------------------------------
+
+/// Beats kept in the run history.
+const MAX_HISTORY: usize = 20;
+
+/// How a beat ended.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BeatOutcome {
+    /// Nothing on the agenda; the agent was not asked.
+    Empty,
+    /// The agent found nothing that needed doing.
+    Ok,
+    /// The agent worked on the agenda without messaging the user.
+    Worked,
+    /// A proactive message was sent.
+    Notified,
+    /// The agent asked to message the user but the cap or a missing target
+    /// stopped it.
+    Dropped,
+    /// The agent did not answer.
+    NoResponse,
+}
+
+impl BeatOutcome {
+    pub fn label(self) -> &'static str {
+        match self {
+            BeatOutcome::Empty => "nothing to review",
+            BeatOutcome::Ok => "OK",
+            BeatOutcome::Worked => "worked on tasks",
+            BeatOutcome::Notified => "messaged the user",
+            BeatOutcome::Dropped => "message dropped",
+            BeatOutcome::NoResponse => "no response",
+        }
+    }
+}
+
+/// One heartbeat run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BeatRecord {
+    pub at_ms: i64,
+    pub outcome: BeatOutcome,
+    /// Tokens the agent used on the beat.
+    #[serde(default)]
+    pub tokens: u64,
+    /// Estimated cost in USD, when the model has a known price.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+}
+
+impl BeatRecord {
+    /// Whether the beat sent a proactive message.
+    pub fn proactive(&self) -> bool {
+        self.outcome == BeatOutcome::Notified
+    }
+}
+
+/// Heartbeat state kept between beats and restarts.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeartbeatState {
+    /// Day (`YYYY-MM-DD`) that `sent_today` refers to.
+    #[serde(default)]
+    pub date: String,
+    /// Proactive messages sent on `date`.
+    #[serde(default)]
+    pub sent_today: u32,
+    /// Time of the previous beat; inbox files older than this are read.
+    #[serde(default)]
+    pub last_beat_ms: i64,
+    /// Time of the last maintenance run.
+    #[serde(default)]
+    pub last_maintenance_ms: i64,
+    /// When the running gateway plans the next beat.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_beat_ms: Option<i64>,
+    /// Recent beats, oldest first.
+    #[serde(default)]
+    pub history: Vec<BeatRecord>,
+}
+
+impl HeartbeatState {
+    /// Load the state from `path`; a missing or broken file starts fresh.
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|c| serde_json::from_str(&c).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write the state to `path`, logging failures.
+    pub fn save(&self, path: &Path) {
+        let result = serde_json::to_string_pretty(self)
+            .map_err(std::io::Error::other)
+            .and_then(|json| std::fs::write(path, json));
+        if let Err(e) = result {
+            warn!("Failed to save heartbeat state {}: {}", path.display(), e);
+        }
+    }
+
+    /// Proactive messages still allowed on `today` under a cap of `max`.
+    pub fn remaining(&mut self, max: u32, today: &str) -> u32 {
+        if self.date != today {
+            self.date = today.to_string();
+            self.sent_today = 0;
+        }
+        max.saturating_sub(self.sent_today)
+    }
+
+    /// Add `record` to the run history.
+    pub fn record_beat(&mut self, record: BeatRecord) {
+        self.history.push(record);
+        let excess = self.history.len().saturating_sub(MAX_HISTORY);
+        self.history.drain(..excess);
+    }
+
+    /// The last beat and the next one, for `/api/status`.
+    pub fn to_json(&self) -> serde_json::Value {
+        let last = self.history.last().map(|beat| {
+            serde_json::json!({
+                "at_ms": beat.at_ms,
+                "outcome": beat.outcome,
+                "proactive": beat.proactive(),
+                "tokens": beat.tokens,
+                "cost_usd": beat.cost_usd,
+            })
+        });
+        serde_json::json!({
+            "last_run": last,
+            "next_beat_ms": self.next_beat_ms,
+        })
+    }
+
+    /// Count a proactive message sent on `today`.
+    pub fn record_sent(&mut self, today: &str) {
+        if self.date != today {
+            self.date = today.to_string();
+            self.sent_today = 0;
+        }
+        self.sent_today += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn job(name: &str, next_run_at_ms: Option<i64>) -> CronJob {
+        serde_json::from_value(serde_json::json!({
+            "id": name,
+            "name": name,
+            "payload": { "message": format!("run {}", name) },
+            "state": { "nextRunAtMs": next_run_at_ms },
+        }))
+        .unwrap()
+    }
+
+    // ── open tasks ──
+
+    #[test]
+    fn test_open_tasks_skips_non_actionable_lines() {
+        assert!(open_tasks("").is_empty());
+        assert!(open_tasks("  \n\n  ").is_empty());
+        assert!(open_tasks("# Tasks\n## Sub\n").is_empty());
+        assert!(open_tasks("<!-- nothing -->\n").is_empty());
+        assert!(open_tasks("- [ ]\n* [x]\n").is_empty());
+    }
+
+    #[test]
+    fn test_open_tasks_lists_open_items() {
+        assert_eq!(open_tasks("Do the thing\n"), vec!["Do the thing"]);
+        assert_eq!(
+            open_tasks("# Tasks\n- Buy milk\n- [ ] Call Bob\n- [x] Pay rent\n"),
+            vec!["Buy milk", "Call Bob"]
+        );
+    }
+
+    #[test]
+    fn test_instructions_section() {
+        let file =
+            "# Heartbeat\n## Instructions\nBe brief.\nNo emojis.\n\n## Tasks\n- [ ] Call Bob\n";
+        assert_eq!(instructions(file).as_deref(), Some("Be brief.\nNo emojis."));
+        assert_eq!(open_tasks(file), vec!["Call Bob"]);
+        assert_eq!(instructions("- [ ] Call Bob\n"), None);
+
+        let agenda = Agenda {
+            tasks: open_tasks(file),
+            instructions: instructions(file),
+            ..Default::default()
+        };
+        assert!(agenda.prompt(0).starts_with("Be brief.\nNo emojis.\n"));
+    }
+
+    // ── agenda ──
+
+    #[test]
+    fn test_upcoming_jobs_window_and_order() {
+        let now = 1_000_000_000_000;
+        let hour = 3_600_000;
+        let mut disabled = job("disabled", Some(now + hour));
+        disabled.enabled = false;
+        let jobs = vec![
+            job("later", Some(now + 5 * hour)),
+            job("soon", Some(now + hour)),
+            job("next-week", Some(now + 7 * 24 * hour)),
+            job("unscheduled", None),
+            disabled,
+        ];
+        let upcoming = upcoming_jobs(&jobs, now);
+        assert_eq!(upcoming.len(), 2);
+        assert!(upcoming[0].starts_with("soon at ") && upcoming[0].ends_with(": run soon"));
+        assert!(upcoming[1].starts_with("later at "));
+    }
+
+    #[test]
+    fn test_collect_reads_tasks_and_new_inbox_files() {
+        let tmp = TempDir::new().unwrap();
+        std::fs::write(tmp.path().join("HEARTBEAT.md"), "- [ ] Water plants\n").unwrap();
+        std::fs::create_dir(tmp.path().join("inbox")).unwrap();
+        std::fs::write(tmp.path().join("inbox/mail.txt"), "Invoice\n  due Friday").unwrap();
+
+        let agenda = Agenda::collect(tmp.path(), &[], 0, 0);
+        assert_eq!(agenda.tasks, vec!["Water plants"]);
+        assert_eq!(agenda.unread, vec!["mail.txt: Invoice due Friday"]);
+
+        let later = Local::now().timestamp_millis() + 60_000;
+        assert!(Agenda::collect(tmp.path(), &[], 0, later).unread.is_empty());
+        assert!(Agenda::collect(&tmp.path().join("missing"), &[], 0, 0).is_empty());
+    }
+
+    #[test]
+    fn test_inbox_preview_reads_a_bounded_prefix() {
+        let tmp = TempDir::new().unwrap();
+        let inbox = tmp.path().join("inbox");
+        std::fs::create_dir(&inbox).unwrap();
+        let mut big = b"Report \xff\xfe".to_vec();
+        big.resize(10 * 1024 * 1024, b'x');
+        std::fs::write(inbox.join("dump.bin"), &big).unwrap();
+
+        let unread = unread_items(&inbox, 0);
+        assert_eq!(unread.len(), 1);
+        assert!(unread[0].starts_with("dump.bin: Report \u{fffd}\u{fffd}x"));
+        assert!(unread[0].chars().count() <= "dump.bin: ".len() + UNREAD_PREVIEW_CHARS);
+    }
+
+    #[test]
+    fn test_prompt_mentions_cap() {
+        let agenda = Agenda {
+            tasks: vec!["Water plants".to_string()],
+            ..Default::default()
+        };
+        let prompt = agenda.prompt(2);
+        assert!(prompt.contains("- Water plants") && prompt.contains("at most 2 more"));
+        assert!(!prompt.contains("Unread items"));
+        let capped = agenda.prompt(0);
+        assert!(capped.contains("cannot send any more") && !capped.contains(NOTIFY_PREFIX));
+    }
+
+    #[test]
+    fn test_parse_notification() {
+        assert_eq!(
+            parse_notification("NOTIFY: Your flight leaves at 9."),
+            Some("Your flight leaves at 9.".to_string())
+        );
+        assert_eq!(parse_notification("HEARTBEAT_OK"), None);
+        assert_eq!(parse_notification("NOTIFY:   "), None);
+    }
+
+    // ── state ──
+
+    #[test]
+    fn test_state_daily_cap_resets() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("heartbeat.json");
+        let mut state = HeartbeatState::load(&path);
+        assert_eq!(state.remaining(2, "2026-01-01"), 2);
+        state.record_sent("2026-01-01");
+        state.record_sent("2026-01-01");
+        assert_eq!(state.remaining(2, "2026-01-01"), 0);
+        state.save(&path);
+
+        let mut state = HeartbeatState::load(&path);
+        assert_eq!(state.sent_today, 2);
+        assert_eq!(state.remaining(2, "2026-01-02"), 2);
+    }
+}
@@ -0,0 +1,374 @@
+//! `HEARTBEAT.md` as a checklist of routines.
+//!
+//! Every list item outside the `## Instructions` section is a task. The agent
+//! ticks tasks off, or puts them off, with the `checklist` tool. A note in
+//! trailing parentheses makes a task recur or wait:
+//!
+//! ```markdown
+//! - [ ] Check mail (every 1d)
+//! - [x] Water the plants (every 3d, done 2026-03-01)
+//! - [ ] Review open pull requests (after 2026-03-10)
+//! ```
+//!
+//! A recurring task reopens once its interval has passed since it was last
+//! done, and a task with `after` stays off the agenda until that day. Unlike
+//! cron jobs nothing runs at a set time: due tasks are reviewed on the next
+//! heartbeat.
+
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{Days, NaiveDate};
+
+/// Heading of the `HEARTBEAT.md` section holding the beat's instructions.
+const INSTRUCTIONS_HEADING: &str = "instructions";
+
+/// Date format of the notes.
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// One task of the checklist.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Task {
+    /// Index of the task's line in the file.
+    line: usize,
+    pub text: String,
+    pub done: bool,
+    /// Reopen this many days after it was done.
+    pub every_days: Option<u64>,
+    /// When it was last done.
+    pub done_on: Option<NaiveDate>,
+    /// Keep it off the agenda before this day.
+    pub after: Option<NaiveDate>,
+    /// Whether the line needs rewriting.
+    changed: bool,
+}
+
+impl Task {
+    /// Whether the task should be worked on `today`.
+    pub fn is_due(&self, today: NaiveDate) -> bool {
+        !self.done && self.after.is_none_or(|after| after <= today)
+    }
+
+    /// The day a done recurring task comes back.
+    fn reopens_on(&self) -> Option<NaiveDate> {
+        self.done_on?.checked_add_days(Days::new(self.every_days?))
+    }
+
+    /// The task as a list item.
+    fn render(&self) -> String {
+        let mut notes = Vec::new();
+        if let Some(days) = self.every_days {
+            notes.push(format!("every {}d", days));
+        }
+        if let Some(done_on) = self.done_on {
+            notes.push(format!("done {}", done_on.format(DATE_FORMAT)));
+        }
+        if let Some(after) = self.after {
+            notes.push(format!("after {}", after.format(DATE_FORMAT)));
+        }
+        let mut line = format!("- [{}] {}", if self.done { "x" } else { " " }, self.text);
+        if !notes.is_empty() {
+            line.push_str(&format!(" ({})", notes.join(", ")));
+        }
+        line
+    }
+}
+
+/// The tasks of `HEARTBEAT.md`, with the rest of the file kept as is.
+#[derive(Debug, Clone, Default)]
+pub struct Checklist {
+    lines: Vec<String>,
+    tasks: Vec<Task>,
+}
+
+impl Checklist {
+    /// Read the tasks from the text of `HEARTBEAT.md`.
+    ///
+    /// Empty lines, headers (`#`), HTML comments (`<!--`), bare checkboxes and
+    /// the instructions section are not tasks; list markers are removed.
+    pub fn parse(content: &str) -> Self {
+        let lines: Vec<String> = content.lines().map(str::to_string).collect();
+        let mut tasks = Vec::new();
+        let mut in_instructions = false;
+        for (index, line) in lines.iter().enumerate() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                in_instructions = is_instructions_heading(line);
+                continue;
+            }
+            if in_instructions || line.is_empty() || line.starts_with("<!--") {
+                continue;
+            }
+            if matches!(line, "-" | "*") {
+                continue;
+            }
+            let item = line
+                .strip_prefix("- ")
+                .or_else(|| line.strip_prefix("* "))
+                .unwrap_or(line)
+                .trim();
+            let (done, item) = match item
+                .strip_prefix("[x]")
+                .or_else(|| item.strip_prefix("[X]"))
+            {
+                Some(rest) => (true, rest),
+                None => (false, item.strip_prefix("[ ]").unwrap_or(item)),
+            };
+            let item = item.trim();
+            if item.is_empty() {
+                continue;
+            }
+            let (text, task) = _split_notes(item);
+            tasks.push(Task {
+                line: index,
+                text: text.to_string(),
+                done,
+                ..task
+            });
+        }
+        Self { lines, tasks }
+    }
+
+    /// Load `HEARTBEAT.md` from `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("cannot read {}", path.display()))?;
+        Ok(Self::parse(&content))
+    }
+
+    /// Write the checklist back to `path`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.render())
+            .with_context(|| format!("cannot write {}", path.display()))
+    }
+
+    /// Tasks to work on `today`.
+    pub fn due(&self, today: NaiveDate) -> impl Iterator<Item = &Task> {
+        self.tasks.iter().filter(move |t| t.is_due(today))
+    }
+
+    /// Reopen recurring tasks whose interval has passed by `today`. Returns
+    /// whether any task changed.
+    pub fn reopen(&mut self, today: NaiveDate) -> bool {
+        let mut changed = false;
+        for task in &mut self.tasks {
+            if task.done && task.reopens_on().is_some_and(|on| on <= today) {
+                task.done = false;
+                task.changed = true;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Tick off the task matching `query` as done on `today`.
+    pub fn check(&mut self, query: &str, today: NaiveDate) -> Result<String> {
+        let task = self._find(query)?;
+        task.done = true;
+        task.done_on = Some(today);
+        task.after = None;
+        task.changed = true;
+        Ok(match task.reopens_on() {
+            Some(on) => format!(
+                "Checked off '{}'; it comes back on {}.",
+                task.text,
+                on.format(DATE_FORMAT)
+            ),
+            None => format!("Checked off '{}'.", task.text),
+        })
+    }
+
+    /// Put off the task matching `query` until `day`.
+    pub fn reschedule(&mut self, query: &str, day: NaiveDate) -> Result<String> {
+        let task = self._find(query)?;
+        task.done = false;
+        task.after = Some(day);
+        task.changed = true;
+        Ok(format!(
+            "'{}' is put off until {}.",
+            task.text,
+            day.format(DATE_FORMAT)
+        ))
+    }
+
+    /// The file's text with the tasks' current state. Only changed tasks are
+    /// rewritten.
+    pub fn render(&self) -> String {
+        let mut lines = self.lines.clone();
+        for task in self.tasks.iter().filter(|t| t.changed) {
+            let original = &lines[task.line];
+            let indent = &original[..original.len() - original.trim_start().len()];
+            lines[task.line] = format!("{}{}", indent, task.render());
+        }
+        let mut text = lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    /// The one task whose text is `query`, or else contains it.
+    fn _find(&mut self, query: &str) -> Result<&mut Task> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            bail!("no task given");
+        }
+        let exact: Vec<usize> = (0..self.tasks.len())
+            .filter(|&i| self.tasks[i].text.to_lowercase() == query)
+            .collect();
+        let matches = if exact.is_empty() {
+            (0..self.tasks.len())
+                .filter(|&i| self.tasks[i].text.to_lowercase().contains(&query))
+                .collect()
+        } else {
+            exact
+        };
+        match matches.as_slice() {
+            [i] => Ok(&mut self.tasks[*i]),
+            [] => Err(anyhow!("no task in HEARTBEAT.md matches '{}'", query)),
+            several => {
+                let names: Vec<String> = several
+                    .iter()
+                    .map(|&i| format!("'{}'", self.tasks[i].text))
+                    .collect();
+                Err(anyhow!(
+                    "'{}' matches several tasks: {}",
+                    query,
+                    names.join(", ")
+                ))
+            }
+        }
+    }
+}
+
+/// Whether `line` is the heading of the instructions section.
+pub fn is_instructions_heading(line: &str) -> bool {
+    line.starts_with('#')
+        && line
+            .trim_start_matches('#')
+            .trim()
+            .eq_ignore_ascii_case(INSTRUCTIONS_HEADING)
+}
+
+/// Split the notes in trailing parentheses off a task. Parentheses that do
+/// not hold notes are part of the text.
+fn _split_notes(item: &str) -> (&str, Task) {
+    let mut task = Task {
+        line: 0,
+        text: String::new(),
+        done: false,
+        every_days: None,
+        done_on: None,
+        after: None,
+        changed: false,
+    };
+    let Some((text, notes)) = item
+        .strip_suffix(')')
+        .and_then(|rest| rest.rsplit_once(" ("))
+    else {
+        return (item, task);
+    };
+    for note in notes.split(',').map(str::trim) {
+        let parsed = match note.split_once(' ') {
+            Some(("every", interval)) => {
+                _parse_interval(interval).map(|d| task.every_days = Some(d))
+            }
+            Some(("done", day)) => _parse_date(day).map(|d| task.done_on = Some(d)),
+            Some(("after", day)) => _parse_date(day).map(|d| task.after = Some(d)),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return (
+                item,
+                Task {
+                    every_days: None,
+                    done_on: None,
+                    after: None,
+                    ..task
+                },
+            );
+        }
+    }
+    (text.trim_end(), task)
+}
+
+/// Days in an interval such as `3d`, `2w`, `day` or `week`.
+fn _parse_interval(interval: &str) -> Option<u64> {
+    match interval.trim() {
+        "day" => Some(1),
+        "week" => Some(7),
+        other => {
+            let (count, unit) = other.split_at(other.len().checked_sub(1)?);
+            let count: u64 = count.parse().ok().filter(|&n| n > 0)?;
+            match unit {
+                "d" => Some(count),
+                "w" => Some(count * 7),
+                _ => None,
+            }
+        }
+    }
+}
+
+fn _parse_date(day: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(day.trim(), DATE_FORMAT).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, DATE_FORMAT).unwrap()
+    }
+
+    #[test]
+    fn test_parse_notes() {
+        let list = Checklist::parse(
+            "# Routines\n\
+             - [ ] Check mail (every 1d)\n\
+             - [x] Water the plants (every 3d, done 2026-03-01)\n\
+             - [ ] Review PRs (after 2026-03-10)\n\
+             - [ ] Call Bob (re: invoice)\n",
+        );
+        let tasks = &list.tasks;
+        assert_eq!(tasks.len(), 4);
+        assert_eq!(tasks[0].every_days, Some(1));
+        assert_eq!(tasks[1].done_on, Some(day("2026-03-01")));
+        assert_eq!(tasks[2].after, Some(day("2026-03-10")));
+        assert_eq!(tasks[3].text, "Call Bob (re: invoice)");
+
+        let due: Vec<&str> = list
+            .due(day("2026-03-02"))
+            .map(|t| t.text.as_str())
+            .collect();
+        assert_eq!(due, ["Check mail", "Call Bob (re: invoice)"]);
+    }
+
+    #[test]
+    fn test_check_reopen_and_reschedule() {
+        let mut list =
+            Checklist::parse("Intro text\n\n- [ ] Water the plants (every 3d)\n  * [ ] Pay rent\n");
+        let today = day("2026-03-01");
+
+        let reply = list.check("water", today).unwrap();
+        assert_eq!(
+            reply,
+            "Checked off 'Water the plants'; it comes back on 2026-03-04."
+        );
+        assert!(!list.reopen(day("2026-03-03")));
+        assert!(list.reopen(day("2026-03-04")));
+        assert!(list.tasks[1].is_due(day("2026-03-04")));
+
+        list.reschedule("pay rent", day("2026-03-05")).unwrap();
+        assert_eq!(
+            list.render(),
+            "Intro text\n\n\
+             - [ ] Water the plants (every 3d, done 2026-03-01)\n  \
+             - [ ] Pay rent (after 2026-03-05)\n"
+        );
+        assert!(list.check("nothing", today).is_err());
+        assert!(list
+            .check("a", today)
+            .unwrap_err()
+            .to_string()
+            .contains("several tasks"));
+    }
+}
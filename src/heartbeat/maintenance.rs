@@ -0,0 +1,260 @@
+//! Housekeeping done by the heartbeat, separate from its agenda.
+//!
+//! Every `intervalHours` the heartbeat loop tidies up without involving the
+//! agent or the user:
+//!
+//! - daily memory notes past their age are appended to a monthly file in
+//!   `memory/archive/` and removed, so old notes stay searchable but out of
+//!   the prompt;
+//! - sessions nobody wrote to for a while are moved to `sessions/archive/`;
+//! - downloaded media past its age is deleted;
+//! - `gateway.log`, which the background gateway appends to forever, is
+//!   copied to `gateway.log.1` and emptied once it grows too big.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::{DateTime, Local, NaiveDate};
+use tracing::warn;
+
+use crate::agent::memory::MemoryStore;
+use crate::config::schema::MaintenanceConfig;
+
+/// Housekeeping for one workspace and data directory.
+#[derive(Clone)]
+pub struct Maintenance {
+    config: MaintenanceConfig,
+    workspace: PathBuf,
+    data_dir: PathBuf,
+}
+
+/// What a maintenance run did.
+#[derive(Debug, Default, PartialEq)]
+pub struct MaintenanceReport {
+    pub notes_consolidated: usize,
+    pub sessions_archived: usize,
+    pub media_pruned: usize,
+    pub logs_rotated: usize,
+}
+
+impl MaintenanceReport {
+    /// One line for the log, e.g. `2 notes consolidated, 1 session archived`.
+    pub fn summary(&self) -> String {
+        let parts: Vec<String> = [
+            (self.notes_consolidated, "note", "consolidated"),
+            (self.sessions_archived, "session", "archived"),
+            (self.media_pruned, "media file", "pruned"),
+            (self.logs_rotated, "log", "rotated"),
+        ]
+        .iter()
+        .filter(|(n, _, _)| *n > 0)
+        .map(|(n, what, done)| {
+            let plural = if *n == 1 { "" } else { "s" };
+            format!("{} {}{} {}", n, what, plural, done)
+        })
+        .collect();
+        if parts.is_empty() {
+            "nothing to do".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+impl Maintenance {
+    pub fn new(config: MaintenanceConfig, workspace: PathBuf, data_dir: PathBuf) -> Self {
+        Self {
+            config,
+            workspace,
+            data_dir,
+        }
+    }
+
+    /// Milliseconds between runs.
+    pub fn interval_ms(&self) -> i64 {
+        self.config.interval_hours as i64 * 3_600_000
+    }
+
+    /// Do every task whose limit is set.
+    pub fn run(&self, now: DateTime<Local>) -> MaintenanceReport {
+        let days = |n: u64| (n > 0).then(|| now - chrono::Duration::days(n as i64));
+        let mut report = MaintenanceReport::default();
+        if let Some(cutoff) = days(self.config.consolidate_memory_after_days) {
+            report.notes_consolidated = self._consolidate_memory(cutoff.date_naive());
+        }
+        if let Some(cutoff) = days(self.config.archive_sessions_after_days) {
+            report.sessions_archived = _move_older(
+                &self.data_dir.join("sessions"),
+                &self.data_dir.join("sessions").join("archive"),
+                cutoff.into(),
+            );
+        }
+        if let Some(cutoff) = days(self.config.prune_media_after_days) {
+            report.media_pruned = _remove_older(&self.data_dir.join("media"), cutoff.into());
+        }
+        if self.config.max_gateway_log_mb > 0 {
+            let max_bytes = self.config.max_gateway_log_mb * 1024 * 1024;
+            report.logs_rotated =
+                _rotate_log(&self.data_dir.join("gateway.log"), max_bytes) as usize;
+        }
+        report
+    }
+
+    /// Append daily notes dated before `cutoff` to their monthly archive.
+    fn _consolidate_memory(&self, cutoff: NaiveDate) -> usize {
+        let memory = MemoryStore::new(&self.workspace);
+        let archive_dir = memory.memory_dir.join("archive");
+        let mut done = 0;
+        // Oldest first, so each monthly file stays in date order.
+        for path in memory.list_memory_files().into_iter().rev() {
+            let Some(date) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
+            else {
+                continue;
+            };
+            if date >= cutoff {
+                break;
+            }
+            let month = archive_dir.join(format!("{}.md", date.format("%Y-%m")));
+            let result = fs::read_to_string(&path).and_then(|notes| {
+                fs::create_dir_all(&archive_dir)?;
+                let mut file = OpenOptions::new().create(true).append(true).open(&month)?;
+                writeln!(file, "{}\n", notes.trim_end())?;
+                fs::remove_file(&path)
+            });
+            match result {
+                Ok(()) => done += 1,
+                Err(e) => warn!("Maintenance: cannot archive {}: {}", path.display(), e),
+            }
+        }
+        done
+    }
+}
+
+/// Files directly in `dir` last modified before `cutoff`.
+fn _files_older(dir: &Path, cutoff: SystemTime) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| {
+            entry
+                .metadata()
+                .is_ok_and(|m| m.is_file() && m.modified().is_ok_and(|t| t < cutoff))
+        })
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// Move files of `dir` older than `cutoff` into `to`.
+fn _move_older(dir: &Path, to: &Path, cutoff: SystemTime) -> usize {
+    let files = _files_older(dir, cutoff);
+    if files.is_empty() || fs::create_dir_all(to).is_err() {
+        return 0;
+    }
+    files
+        .iter()
+        .filter(|path| {
+            let target = to.join(path.file_name().unwrap_or_default());
+            fs::rename(path, target)
+                .inspect_err(|e| warn!("Maintenance: cannot archive {}: {}", path.display(), e))
+                .is_ok()
+        })
+        .count()
+}
+
+/// Delete files of `dir` older than `cutoff`.
+fn _remove_older(dir: &Path, cutoff: SystemTime) -> usize {
+    _files_older(dir, cutoff)
+        .iter()
+        .filter(|path| fs::remove_file(path).is_ok())
+        .count()
+}
+
+/// Copy `path` to `path.1` and empty it when it is larger than `max_bytes`.
+/// The gateway keeps the file open for appending, so it is truncated in
+/// place rather than renamed.
+fn _rotate_log(path: &Path, max_bytes: u64) -> bool {
+    if fs::metadata(path).map_or(true, |m| m.len() <= max_bytes) {
+        return false;
+    }
+    let mut old = path.as_os_str().to_owned();
+    old.push(".1");
+    let result = fs::copy(path, &old)
+        .and_then(|_| OpenOptions::new().write(true).open(path))
+        .and_then(|file| file.set_len(0));
+    if let Err(e) = &result {
+        warn!("Maintenance: cannot rotate {}: {}", path.display(), e);
+    }
+    result.is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn set_age(path: &Path, days: u64) {
+        let at = SystemTime::now() - Duration::from_secs(days * 86_400);
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(at)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_run() {
+        let tmp = TempDir::new().unwrap();
+        let (workspace, data) = (tmp.path().join("ws"), tmp.path().join("data"));
+        let memory = workspace.join("memory");
+        fs::create_dir_all(&memory).unwrap();
+        let now = Local::now();
+        let today = now.format("%Y-%m-%d").to_string();
+        fs::write(
+            memory.join("2020-01-05.md"),
+            "# 2020-01-05\n\nBought milk\n",
+        )
+        .unwrap();
+        fs::write(memory.join("2020-01-20.md"), "# 2020-01-20\n\nCalled Bob\n").unwrap();
+        fs::write(memory.join(format!("{}.md", today)), "Recent\n").unwrap();
+        for dir in ["sessions", "media"] {
+            fs::create_dir_all(data.join(dir)).unwrap();
+            fs::write(data.join(dir).join("old"), "x").unwrap();
+            fs::write(data.join(dir).join("new"), "x").unwrap();
+            set_age(&data.join(dir).join("old"), 200);
+        }
+        fs::write(data.join("gateway.log"), vec![b'x'; 2 * 1024 * 1024]).unwrap();
+
+        let config = MaintenanceConfig {
+            consolidate_memory_after_days: 10,
+            max_gateway_log_mb: 1,
+            ..Default::default()
+        };
+        let maintenance = Maintenance::new(config, workspace, data.clone());
+        let report = maintenance.run(now);
+        assert_eq!(
+            report.summary(),
+            "2 notes consolidated, 1 session archived, 1 media file pruned, 1 log rotated"
+        );
+
+        assert_eq!(
+            fs::read_to_string(memory.join("archive/2020-01.md")).unwrap(),
+            "# 2020-01-05\n\nBought milk\n\n# 2020-01-20\n\nCalled Bob\n\n"
+        );
+        assert!(memory.join(format!("{}.md", today)).exists());
+        assert!(data.join("sessions/archive/old").exists() && data.join("sessions/new").exists());
+        assert!(!data.join("media/old").exists() && data.join("media/new").exists());
+        assert_eq!(fs::metadata(data.join("gateway.log")).unwrap().len(), 0);
+        assert!(data.join("gateway.log.1").exists());
+
+        assert_eq!(maintenance.run(now).summary(), "nothing to do");
+    }
+}
@@ -1 +1,5 @@
+pub mod agenda;
+pub mod checklist;
+pub mod maintenance;
+pub mod pace;
 pub mod service;
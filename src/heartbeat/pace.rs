@@ -0,0 +1,147 @@
+//! Adaptive heartbeat interval.
+//!
+//! A gateway that is always on spends most of its beats on an idle user. With
+//! an [`AdaptivePace`] the interval follows what is going on:
+//!
+//! - while the user is active and the agenda has something on it, beats come
+//!   every `active_interval_s`;
+//! - once the user has been quiet for `idle_after_s` and the agenda is empty,
+//!   the interval doubles after each beat, up to `max_interval_s`;
+//! - otherwise beats come at the configured interval.
+//!
+//! User activity is any message a channel puts on the bus, as seen by
+//! [`UserActivity`]; subagent results, requests through the API and cron
+//! jobs do not count.
+
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Local;
+
+use crate::bus::tap::{BusSubscriber, BusTraffic};
+
+/// Time of the user's last message, kept up to date from the bus.
+pub struct UserActivity {
+    last_ms: AtomicI64,
+}
+
+impl UserActivity {
+    /// Start counting from now, so a fresh gateway is not idle right away.
+    pub fn new() -> Self {
+        Self {
+            last_ms: AtomicI64::new(Local::now().timestamp_millis()),
+        }
+    }
+
+    /// Record activity at `at_ms`.
+    pub fn touch(&self, at_ms: i64) {
+        self.last_ms.fetch_max(at_ms, Ordering::Relaxed);
+    }
+
+    /// Seconds since the last activity at `now_ms`.
+    pub fn idle_secs(&self, now_ms: i64) -> u64 {
+        let idle_ms = now_ms - self.last_ms.load(Ordering::Relaxed);
+        (idle_ms.max(0) / 1000) as u64
+    }
+}
+
+impl Default for UserActivity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl BusSubscriber for UserActivity {
+    fn name(&self) -> &str {
+        "user activity"
+    }
+
+    async fn on_traffic(&self, traffic: &BusTraffic) {
+        if let BusTraffic::Inbound(msg) = traffic {
+            let is_system = msg
+                .metadata
+                .get("is_system")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            if !is_system {
+                self.touch(msg.timestamp.timestamp_millis());
+            }
+        }
+    }
+}
+
+/// How the heartbeat interval adapts; see the module docs.
+#[derive(Clone)]
+pub struct AdaptivePace {
+    pub active_interval_s: u64,
+    pub max_interval_s: u64,
+    pub idle_after_s: u64,
+    pub activity: Arc<UserActivity>,
+}
+
+impl AdaptivePace {
+    /// Seconds until the next beat, after a beat at `now_ms` that waited
+    /// `current_s`. `pending` tells whether the agenda had anything on it.
+    pub fn next_interval(&self, base_s: u64, current_s: u64, pending: bool, now_ms: i64) -> u64 {
+        let active = self.activity.idle_secs(now_ms) < self.idle_after_s;
+        match (active, pending) {
+            (true, true) => self.active_interval_s.clamp(1, base_s),
+            (false, false) => current_s
+                .saturating_mul(2)
+                .clamp(base_s, self.max_interval_s.max(base_s)),
+            _ => base_s,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::events::InboundMessage;
+
+    fn activity_at(ms: i64) -> UserActivity {
+        UserActivity {
+            last_ms: AtomicI64::new(ms),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_activity_from_bus() {
+        let activity = activity_at(0);
+        assert_eq!(activity.idle_secs(90_000), 90);
+
+        let mut msg = InboundMessage::new("telegram", "u", "42", "hi");
+        let later = msg.timestamp + chrono::Duration::hours(1);
+        msg.timestamp = later;
+        activity.on_traffic(&BusTraffic::Inbound(msg.clone())).await;
+        assert_eq!(activity.idle_secs(later.timestamp_millis() + 60_000), 60);
+
+        msg.metadata
+            .insert("is_system".to_string(), serde_json::json!(true));
+        msg.timestamp = later + chrono::Duration::hours(1);
+        activity.on_traffic(&BusTraffic::Inbound(msg)).await;
+        assert_eq!(activity.idle_secs(later.timestamp_millis() + 60_000), 60);
+    }
+
+    #[test]
+    fn test_next_interval() {
+        let activity = Arc::new(activity_at(0));
+        let pace = AdaptivePace {
+            active_interval_s: 600,
+            max_interval_s: 4 * 3600,
+            idle_after_s: 3600,
+            activity,
+        };
+        let base = 1800;
+        let active = 10 * 60 * 1000;
+        let idle = 2 * 3600 * 1000;
+
+        assert_eq!(pace.next_interval(base, base, true, active), 600);
+        assert_eq!(pace.next_interval(base, 600, false, active), base);
+        assert_eq!(pace.next_interval(base, 600, true, idle), base);
+        assert_eq!(pace.next_interval(base, base, false, idle), 3600);
+        assert_eq!(pace.next_interval(base, 3 * 3600, false, idle), 4 * 3600);
+    }
+}
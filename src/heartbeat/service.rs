@@ -1,7 +1,9 @@
-//! Heartbeat service -- periodic agent wake-up to check for tasks.
+//! Heartbeat service -- periodic agent wake-up to review its agenda.
 //!
-//! The agent reads `HEARTBEAT.md` from the workspace and executes any tasks
-//! listed there. If nothing needs attention it replies `HEARTBEAT_OK`.
+//! On each beat the agent is shown its [`Agenda`] (due tasks in
+//! `HEARTBEAT.md`, upcoming cron jobs, unread inbox items), works on what it
+//! can and decides whether to proactively message the user, within a daily
+//! cap. If nothing needs attention it replies `HEARTBEAT_OK`.
 
 use std::future::Future;
 use std::path::PathBuf;
@@ -9,16 +11,28 @@ use std::pin::Pin;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
+use chrono::{DateTime, Local, NaiveDate};
+use serde_json::json;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::Mutex;
 use tokio::time::Duration;
-use tracing::{debug, error, info};
+use tracing::{debug, info, warn};
+
+use crate::bus::events::OutboundMessage;
+use crate::cron::service::CronService;
+use crate::agent::usage::{read_records, UsageRecord};
+use crate::heartbeat::agenda::{
+    parse_notification, Agenda, BeatOutcome, BeatRecord, HeartbeatState,
+};
+use crate::heartbeat::checklist::Checklist;
+use crate::heartbeat::maintenance::Maintenance;
+use crate::heartbeat::pace::AdaptivePace;
 
 /// Default heartbeat interval: 30 minutes.
 pub const DEFAULT_HEARTBEAT_INTERVAL_S: u64 = 30 * 60;
 
-/// The prompt sent to the agent during a heartbeat.
-pub const HEARTBEAT_PROMPT: &str = "Read HEARTBEAT.md in your workspace (if it exists).\n\
-    Follow any instructions or tasks listed there.\n\
-    If nothing needs attention, reply with just: HEARTBEAT_OK";
+/// Session the heartbeat runs in.
+pub const HEARTBEAT_SESSION: &str = "heartbeat";
 
 /// Token that indicates "nothing to do".
 pub const HEARTBEAT_OK_TOKEN: &str = "HEARTBEAT_OK";
@@ -33,54 +47,211 @@ pub type HeartbeatCallback = Arc<
     dyn Fn(String) -> Pin<Box<dyn Future<Output = Option<String>> + Send>> + Send + Sync,
 >;
 
+/// Where proactive messages go.
+#[derive(Clone)]
+pub struct ProactiveTarget {
+    pub channel: String,
+    pub chat_id: String,
+    pub outbound_tx: UnboundedSender<OutboundMessage>,
+}
+
 // ---------------------------------------------------------------------------
 // Helpers
 // ---------------------------------------------------------------------------
 
-/// Check whether a `HEARTBEAT.md` file has no actionable content.
-///
-/// Lines that are empty, headers (`#`), HTML comments (`<!--`), or checkbox
-/// items (`- [ ]`, `- [x]`, `* [ ]`, `* [x]`) are skipped.  If only such
-/// lines are present the file is considered empty.
-fn is_heartbeat_empty(content: Option<&str>) -> bool {
-    let content = match content {
-        Some(c) => c,
-        None => return true,
-    };
-
-    let skip_exact: &[&str] = &["- [ ]", "* [ ]", "- [x]", "* [x]"];
-
-    for line in content.lines() {
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
-            continue;
+/// Whether `session` is the heartbeat's, possibly under an agent profile.
+fn is_heartbeat_session(session: &str) -> bool {
+    session == HEARTBEAT_SESSION
+        || session
+            .strip_prefix(HEARTBEAT_SESSION)
+            .is_some_and(|rest| rest.starts_with(':'))
+}
+
+/// Whether an agent reply means "nothing to do".
+fn is_heartbeat_ok(response: &str) -> bool {
+    // Normalize both sides for comparison (strip underscores, uppercase).
+    let normalized = response.to_uppercase().replace('_', "");
+    normalized.contains(&HEARTBEAT_OK_TOKEN.replace('_', ""))
+}
+
+/// Everything a beat needs, shared with the background loop.
+#[derive(Clone)]
+struct Beat {
+    workspace: PathBuf,
+    on_heartbeat: Option<HeartbeatCallback>,
+    cron: Option<Arc<Mutex<CronService>>>,
+    target: Option<ProactiveTarget>,
+    max_proactive_per_day: u32,
+    state_path: Option<PathBuf>,
+    /// Instructions from the config, used unless `HEARTBEAT.md` has some.
+    instructions: Option<String>,
+    /// Adapts the interval to user activity; fixed when unset.
+    pace: Option<AdaptivePace>,
+    /// Usage file the beats' token cost is read from.
+    usage_path: Option<PathBuf>,
+    /// Housekeeping done between beats.
+    maintenance: Option<Maintenance>,
+}
+
+impl Beat {
+    /// Review the agenda once. Returns whether the agenda had anything on
+    /// it, and the agent response.
+    async fn run(&self) -> (bool, Option<String>) {
+        let Some(cb) = self.on_heartbeat.as_ref() else {
+            return (false, None);
+        };
+        let now = Local::now();
+        let today = now.format("%Y-%m-%d").to_string();
+        let mut state = self
+            .state_path
+            .as_deref()
+            .map(HeartbeatState::load)
+            .unwrap_or_default();
+
+        self.reopen_tasks(now.date_naive());
+        let jobs = match &self.cron {
+            Some(cron) => cron.lock().await.list_jobs(false),
+            None => Vec::new(),
+        };
+        let mut agenda = Agenda::collect(&self.workspace, &jobs, now.timestamp_millis(), state.last_beat_ms);
+        state.last_beat_ms = now.timestamp_millis();
+        if agenda.is_empty() {
+            debug!("Heartbeat: agenda is empty");
+            state.record_beat(BeatRecord {
+                at_ms: now.timestamp_millis(),
+                outcome: BeatOutcome::Empty,
+                tokens: 0,
+                cost_usd: None,
+            });
+            self.save_state(&state);
+            return (false, None);
         }
-        if trimmed.starts_with('#') {
-            continue;
+
+        info!(
+            "Heartbeat: reviewing {} tasks, {} upcoming jobs, {} unread items",
+            agenda.tasks.len(),
+            agenda.upcoming.len(),
+            agenda.unread.len()
+        );
+        let remaining = match &self.target {
+            Some(_) => state.remaining(self.max_proactive_per_day, &today),
+            None => 0,
+        };
+        if agenda.instructions.is_none() {
+            agenda.instructions = self.instructions.clone();
         }
-        if trimmed.starts_with("<!--") {
-            continue;
+        let response = cb(agenda.prompt(remaining)).await;
+
+        let outcome = match response.as_deref().map(|r| (parse_notification(r), r)) {
+            Some((Some(text), _)) => match &self.target {
+                Some(target) if remaining > 0 => {
+                    let mut msg = OutboundMessage::new(&target.channel, &target.chat_id, text);
+                    msg.metadata.insert("proactive".to_string(), json!(true));
+                    if target.outbound_tx.send(msg).is_ok() {
+                        state.record_sent(&today);
+                        info!("Heartbeat: sent a proactive message");
+                        BeatOutcome::Notified
+                    } else {
+                        BeatOutcome::Dropped
+                    }
+                }
+                _ => {
+                    info!("Heartbeat: proactive message dropped (daily cap or no target)");
+                    BeatOutcome::Dropped
+                }
+            },
+            Some((None, r)) if is_heartbeat_ok(r) => {
+                info!("Heartbeat: OK (no action needed)");
+                BeatOutcome::Ok
+            }
+            Some(_) => {
+                info!("Heartbeat: completed task");
+                BeatOutcome::Worked
+            }
+            None => {
+                info!("Heartbeat: callback returned no response");
+                BeatOutcome::NoResponse
+            }
+        };
+        let (tokens, cost_usd) = self.usage_since(now);
+        state.record_beat(BeatRecord {
+            at_ms: now.timestamp_millis(),
+            outcome,
+            tokens,
+            cost_usd,
+        });
+        self.save_state(&state);
+        (true, response)
+    }
+
+    /// Tokens and cost of the heartbeat session's LLM calls since `start`.
+    fn usage_since(&self, start: DateTime<Local>) -> (u64, Option<f64>) {
+        let Some(path) = &self.usage_path else {
+            return (0, None);
+        };
+        let records: Vec<UsageRecord> = read_records(path)
+            .into_iter()
+            .filter(|r| r.timestamp >= start && is_heartbeat_session(&r.session))
+            .collect();
+        let tokens = records
+            .iter()
+            .map(|r| r.prompt_tokens + r.completion_tokens)
+            .sum();
+        let cost = records.iter().filter_map(|r| r.cost_usd).reduce(|a, b| a + b);
+        (tokens, cost)
+    }
+
+    /// Reopen recurring `HEARTBEAT.md` tasks that are due again.
+    fn reopen_tasks(&self, today: NaiveDate) {
+        let path = self.workspace.join("HEARTBEAT.md");
+        let Ok(mut checklist) = Checklist::load(&path) else {
+            return;
+        };
+        if checklist.reopen(today) {
+            if let Err(e) = checklist.save(&path) {
+                warn!("Heartbeat: failed to reopen tasks: {}", e);
+            }
         }
-        if skip_exact.contains(&trimmed) {
-            continue;
+    }
+
+    /// Run the housekeeping if it is due.
+    fn maintain(&self, now: DateTime<Local>) {
+        let (Some(maintenance), Some(path)) = (&self.maintenance, &self.state_path) else {
+            return;
+        };
+        let mut state = HeartbeatState::load(path);
+        if now.timestamp_millis() - state.last_maintenance_ms < maintenance.interval_ms() {
+            return;
         }
-        // Found an actionable line.
-        return false;
+        let report = maintenance.run(now);
+        info!("Heartbeat maintenance: {}", report.summary());
+        state.last_maintenance_ms = now.timestamp_millis();
+        state.save(path);
     }
 
-    true
+    /// Note when the next beat is due, for `nanoclaw status`.
+    fn plan_next(&self, at_ms: Option<i64>) {
+        if let Some(path) = &self.state_path {
+            let mut state = HeartbeatState::load(path);
+            state.next_beat_ms = at_ms;
+            state.save(path);
+        }
+    }
+
+    fn save_state(&self, state: &HeartbeatState) {
+        if let Some(path) = &self.state_path {
+            state.save(path);
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
 // HeartbeatService
 // ---------------------------------------------------------------------------
 
-/// Periodic heartbeat service that wakes the agent to check for tasks.
+/// Periodic heartbeat service that wakes the agent to review its agenda.
 pub struct HeartbeatService {
-    /// Root workspace directory (contains `HEARTBEAT.md`).
-    pub workspace: PathBuf,
-    /// Callback invoked on each heartbeat tick.
-    on_heartbeat: Option<HeartbeatCallback>,
+    beat: Beat,
     /// Interval between heartbeats in seconds.
     pub interval_s: u64,
     /// Whether the service is enabled at all.
@@ -100,8 +271,18 @@ impl HeartbeatService {
         enabled: bool,
     ) -> Self {
         Self {
-            workspace,
-            on_heartbeat,
+            beat: Beat {
+                workspace,
+                on_heartbeat,
+                cron: None,
+                target: None,
+                max_proactive_per_day: 0,
+                state_path: None,
+                instructions: None,
+                pace: None,
+                usage_path: None,
+                maintenance: None,
+            },
             interval_s,
             enabled,
             running: Arc::new(AtomicBool::new(false)),
@@ -109,19 +290,47 @@ impl HeartbeatService {
         }
     }
 
-    /// Path to `HEARTBEAT.md` inside the workspace.
-    pub fn heartbeat_file(&self) -> PathBuf {
-        self.workspace.join("HEARTBEAT.md")
+    /// Include jobs of `cron` that are due soon in the agenda.
+    pub fn set_cron_service(&mut self, cron: Arc<Mutex<CronService>>) {
+        self.beat.cron = Some(cron);
     }
 
-    /// Read the contents of `HEARTBEAT.md`, if it exists.
-    fn read_heartbeat_file(&self) -> Option<String> {
-        let path = self.heartbeat_file();
-        if path.exists() {
-            std::fs::read_to_string(&path).ok()
-        } else {
-            None
-        }
+    /// Allow up to `max_per_day` proactive messages to `target`.
+    pub fn set_proactive_target(&mut self, target: ProactiveTarget, max_per_day: u32) {
+        self.beat.target = Some(target);
+        self.beat.max_proactive_per_day = max_per_day;
+    }
+
+    /// Open each beat with `prompt` instead of the built-in instructions.
+    pub fn set_prompt(&mut self, prompt: &str) {
+        self.beat.instructions = Some(prompt.to_string()).filter(|p| !p.trim().is_empty());
+    }
+
+    /// Let the interval follow user activity; see [`AdaptivePace`].
+    pub fn set_adaptive(&mut self, pace: AdaptivePace) {
+        self.beat.pace = Some(pace);
+    }
+
+    /// Keep the daily message count, last beat time and run history in
+    /// `path`.
+    pub fn set_state_file(&mut self, path: PathBuf) {
+        self.beat.state_path = Some(path);
+    }
+
+    /// Do `maintenance` between beats, on its own cadence. Needs a state
+    /// file to remember the last run.
+    pub fn set_maintenance(&mut self, maintenance: Maintenance) {
+        self.beat.maintenance = Some(maintenance);
+    }
+
+    /// Read the token cost of each beat from the usage file at `path`.
+    pub fn set_usage_file(&mut self, path: PathBuf) {
+        self.beat.usage_path = Some(path);
+    }
+
+    /// Path to `HEARTBEAT.md` inside the workspace.
+    pub fn heartbeat_file(&self) -> PathBuf {
+        self.beat.workspace.join("HEARTBEAT.md")
     }
 
     /// Start the heartbeat background loop.
@@ -130,17 +339,19 @@ impl HeartbeatService {
             info!("Heartbeat disabled");
             return;
         }
+        if self.beat.target.is_none() {
+            warn!("Heartbeat: no channel/chat configured, proactive messages are off");
+        }
 
         self.running.store(true, Ordering::Relaxed);
         info!("Heartbeat started (every {}s)", self.interval_s);
 
         let running = Arc::clone(&self.running);
         let interval_s = self.interval_s;
-        let workspace = self.workspace.clone();
-        let on_heartbeat = self.on_heartbeat.clone();
+        let beat = self.beat.clone();
 
         let handle = tokio::spawn(async move {
-            Self::run_loop(running, interval_s, workspace, on_heartbeat).await;
+            Self::run_loop(running, interval_s, beat).await;
         });
 
         let mut guard = self.task_handle.lock().await;
@@ -153,6 +364,7 @@ impl HeartbeatService {
         let mut guard = self.task_handle.lock().await;
         if let Some(h) = guard.take() {
             h.abort();
+            self.beat.plan_next(None);
         }
     }
 
@@ -160,63 +372,35 @@ impl HeartbeatService {
     ///
     /// Returns the agent response (if any).
     pub async fn trigger_now(&self) -> Option<String> {
-        if let Some(ref cb) = self.on_heartbeat {
-            let result = cb(HEARTBEAT_PROMPT.to_string()).await;
-            return result;
-        }
-        None
+        self.beat.run().await.1
     }
 
     // -----------------------------------------------------------------------
     // Internal loop
     // -----------------------------------------------------------------------
 
-    async fn run_loop(
-        running: Arc<AtomicBool>,
-        interval_s: u64,
-        workspace: PathBuf,
-        on_heartbeat: Option<HeartbeatCallback>,
-    ) {
+    async fn run_loop(running: Arc<AtomicBool>, interval_s: u64, beat: Beat) {
+        let mut current_s = interval_s;
         loop {
-            tokio::time::sleep(Duration::from_secs(interval_s)).await;
+            beat.plan_next(Some(Local::now().timestamp_millis() + current_s as i64 * 1000));
+            tokio::time::sleep(Duration::from_secs(current_s)).await;
 
             if !running.load(Ordering::Relaxed) {
                 break;
             }
-
-            // Read HEARTBEAT.md
-            let content = {
-                let path = workspace.join("HEARTBEAT.md");
-                if path.exists() {
-                    std::fs::read_to_string(&path).ok()
-                } else {
-                    None
-                }
-            };
-
-            if is_heartbeat_empty(content.as_deref()) {
-                debug!("Heartbeat: no tasks (HEARTBEAT.md empty)");
-                continue;
-            }
-
-            info!("Heartbeat: checking for tasks...");
-
-            if let Some(ref cb) = on_heartbeat {
-                match cb(HEARTBEAT_PROMPT.to_string()).await {
-                    Some(response) => {
-                        // Normalize both sides for comparison (strip underscores, uppercase).
-                        let normalized = response.to_uppercase().replace('_', "");
-                        let token_normalized = HEARTBEAT_OK_TOKEN.replace('_', "");
-                        if normalized.contains(&token_normalized) {
-                            info!("Heartbeat: OK (no action needed)");
-                        } else {
-                            info!("Heartbeat: completed task");
-                        }
-                    }
-                    None => {
-                        info!("Heartbeat: callback returned no response");
-                    }
+            let (pending, _) = beat.run().await;
+            beat.maintain(Local::now());
+            if let Some(pace) = &beat.pace {
+                let next_s = pace.next_interval(
+                    interval_s,
+                    current_s,
+                    pending,
+                    Local::now().timestamp_millis(),
+                );
+                if next_s != current_s {
+                    debug!("Heartbeat: next beat in {}s", next_s);
                 }
+                current_s = next_s;
             }
         }
     }
@@ -229,36 +413,126 @@ impl HeartbeatService {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tempfile::TempDir;
+
+    /// Callback that records prompts and gives a fixed reply.
+    fn callback(reply: &'static str, prompts: Arc<std::sync::Mutex<Vec<String>>>) -> HeartbeatCallback {
+        Arc::new(move |prompt| {
+            prompts.lock().unwrap().push(prompt);
+            Box::pin(async move { Some(reply.to_string()) })
+        })
+    }
 
-    #[test]
-    fn test_is_heartbeat_empty_none() {
-        assert!(is_heartbeat_empty(None));
+    fn service(
+        workspace: &std::path::Path,
+        reply: &'static str,
+        max_per_day: u32,
+    ) -> (
+        HeartbeatService,
+        Arc<std::sync::Mutex<Vec<String>>>,
+        tokio::sync::mpsc::UnboundedReceiver<OutboundMessage>,
+    ) {
+        let prompts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let (outbound_tx, outbound_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut hb = HeartbeatService::new(
+            workspace.to_path_buf(),
+            Some(callback(reply, prompts.clone())),
+            60,
+            true,
+        );
+        hb.set_state_file(workspace.join("heartbeat.json"));
+        hb.set_proactive_target(
+            ProactiveTarget {
+                channel: "telegram".to_string(),
+                chat_id: "42".to_string(),
+                outbound_tx,
+            },
+            max_per_day,
+        );
+        (hb, prompts, outbound_rx)
     }
 
     #[test]
-    fn test_is_heartbeat_empty_blank() {
-        assert!(is_heartbeat_empty(Some("")));
-        assert!(is_heartbeat_empty(Some("  \n\n  ")));
+    fn test_is_heartbeat_ok() {
+        assert!(is_heartbeat_ok("heartbeat_ok."));
+        assert!(is_heartbeat_ok("HEARTBEAT_OK"));
+        assert!(!is_heartbeat_ok("Watered the plants."));
     }
 
-    #[test]
-    fn test_is_heartbeat_empty_headers_only() {
-        assert!(is_heartbeat_empty(Some("# Tasks\n## Sub\n")));
+    #[tokio::test]
+    async fn test_empty_agenda_skips_agent() {
+        let tmp = TempDir::new().unwrap();
+        std::fs::write(tmp.path().join("HEARTBEAT.md"), "# Tasks\n- [x] done\n").unwrap();
+        let (hb, prompts, _rx) = service(tmp.path(), "HEARTBEAT_OK", 1);
+        assert!(hb.trigger_now().await.is_none());
+        assert!(prompts.lock().unwrap().is_empty());
     }
 
-    #[test]
-    fn test_is_heartbeat_empty_comments_only() {
-        assert!(is_heartbeat_empty(Some("<!-- nothing -->\n")));
+    #[tokio::test]
+    async fn test_prompt_from_config_or_file() {
+        let tmp = TempDir::new().unwrap();
+        std::fs::write(tmp.path().join("HEARTBEAT.md"), "- [ ] Check the oven\n").unwrap();
+        let (mut hb, prompts, _rx) = service(tmp.path(), "HEARTBEAT_OK", 1);
+        hb.set_prompt("Only act on kitchen tasks.");
+        hb.trigger_now().await;
+        assert!(prompts.lock().unwrap()[0].starts_with("Only act on kitchen tasks.\n"));
+
+        std::fs::write(
+            tmp.path().join("HEARTBEAT.md"),
+            "## Instructions\nBe quick.\n## Tasks\n- [ ] Check the oven\n",
+        )
+        .unwrap();
+        hb.trigger_now().await;
+        assert!(prompts.lock().unwrap()[1].starts_with("Be quick.\n"));
     }
 
-    #[test]
-    fn test_is_heartbeat_empty_checkboxes_only() {
-        assert!(is_heartbeat_empty(Some("- [ ]\n* [x]\n")));
+    #[tokio::test]
+    async fn test_proactive_messages_are_capped_per_day() {
+        let tmp = TempDir::new().unwrap();
+        std::fs::write(tmp.path().join("HEARTBEAT.md"), "- [ ] Check the oven\n").unwrap();
+        let (hb, prompts, mut rx) = service(tmp.path(), "NOTIFY: The oven is still on!", 1);
+
+        hb.trigger_now().await;
+        let msg = rx.try_recv().unwrap();
+        assert_eq!((msg.channel.as_str(), msg.chat_id.as_str()), ("telegram", "42"));
+        assert_eq!(msg.content, "The oven is still on!");
+        assert!(prompts.lock().unwrap()[0].contains("- Check the oven"));
+
+        hb.trigger_now().await;
+        assert!(rx.try_recv().is_err());
+        assert!(prompts.lock().unwrap()[1].contains("cannot send any more"));
     }
 
-    #[test]
-    fn test_is_heartbeat_not_empty() {
-        assert!(!is_heartbeat_empty(Some("Do the thing\n")));
-        assert!(!is_heartbeat_empty(Some("# Tasks\n- Buy milk\n")));
+    #[tokio::test]
+    async fn test_run_history() {
+        let tmp = TempDir::new().unwrap();
+        let (mut hb, _prompts, _rx) = service(tmp.path(), "HEARTBEAT_OK", 1);
+        let usage = tmp.path().join("usage.jsonl");
+        hb.set_usage_file(usage.clone());
+        let record = |session: &str| {
+            json!({
+                "timestamp": Local::now() + chrono::Duration::minutes(1),
+                "session": session,
+                "channel": "telegram",
+                "model": "m",
+                "promptTokens": 900,
+                "completionTokens": 100,
+                "costUsd": 0.01,
+            })
+            .to_string()
+        };
+        std::fs::write(&usage, format!("{}\n{}\n", record("heartbeat"), record("telegram:42"))).unwrap();
+
+        hb.trigger_now().await;
+        std::fs::write(tmp.path().join("HEARTBEAT.md"), "- [ ] Check the oven\n").unwrap();
+        hb.trigger_now().await;
+
+        let state = HeartbeatState::load(&tmp.path().join("heartbeat.json"));
+        let outcomes: Vec<BeatOutcome> = state.history.iter().map(|b| b.outcome).collect();
+        assert_eq!(outcomes, [BeatOutcome::Empty, BeatOutcome::Ok]);
+        let last = state.history.last().unwrap();
+        assert_eq!((last.tokens, last.cost_usd), (1000, Some(0.01)));
+        assert!(!last.proactive());
+        assert_eq!(state.to_json()["last_run"]["outcome"], "ok");
     }
 }
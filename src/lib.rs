@@ -0,0 +1,29 @@
+//! nanoclaw - A lightweight personal AI assistant framework in Rust.
+//! Based on nanobot by HKUDS (<https://github.com/HKUDS/nanobot>).
+//!
+//! The `nanoclaw` binary is built on this library, which other programs can
+//! use to embed an agent:
+//!
+//! - [`agent::builder::AgentLoopBuilder`] creates an
+//!   [`agent::agent_loop::AgentLoop`], from a [`config::schema::Config`] or
+//!   from a provider and a workspace.
+//! - [`agent::tools`] has the [`Tool`](agent::tools::base::Tool) trait and
+//!   the [`ToolRegistry`](agent::tools::registry::ToolRegistry);
+//!   [`agent::hooks`] the hooks around LLM and tool calls.
+//! - [`providers`] talk to LLMs, [`channels`] to chat apps, and [`bus`]
+//!   carries messages between them and the agent.
+
+pub mod agent;
+pub mod api;
+pub mod audit;
+pub mod bus;
+pub mod channels;
+pub mod config;
+pub mod cron;
+pub mod daemon;
+pub mod heartbeat;
+pub mod logs;
+pub mod observability;
+pub mod providers;
+pub mod session;
+pub mod utils;
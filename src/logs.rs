@@ -0,0 +1,775 @@
+//! Log files and `nanoclaw logs`.
+//!
+//! The gateway and `nanoclaw serve` also log to `~/.nanoclaw/logs/`, one
+//! JSON object per line, ready for Loki or Elasticsearch. A new file is
+//! started every day (`observability.logs.rotation`) and whenever one grows
+//! past `maxFileMb`, keeping the last `maxFiles`. Log lines written during
+//! an agent turn carry the session key and ID of the turn as top-level
+//! `session` and `turn` fields, so `nanoclaw logs --session` can pick out
+//! one conversation and `nanoclaw logs --turn` one turn of it, including the
+//! delivery of its replies.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::fmt::format::{JsonFields, Writer};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter, Layer};
+
+use crate::config::schema::LogsConfig;
+use crate::observability::redact::RedactingWriter;
+use crate::utils::helpers::get_data_path;
+
+/// Log file names are this prefix, the date and a number if a day has
+/// several files.
+const LOG_FILE_PREFIX: &str = "nanoclaw.log";
+
+/// Span fields copied onto each log line.
+const SPAN_FIELDS: [&str; 2] = ["session", "turn"];
+
+/// How often `--follow` checks for new lines.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Directory of the log files.
+pub fn logs_dir() -> PathBuf {
+    get_data_path().join("logs")
+}
+
+fn env_filter() -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"))
+}
+
+/// Where log lines are shown in the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Console {
+    Stdout,
+    /// Keeps stdout for output meant for other programs, such as JSON.
+    Stderr,
+    /// The terminal is taken by a full-screen interface.
+    Off,
+}
+
+/// Log filtered by `RUST_LOG` (default `info`) to the `console`, and with
+/// `files` also to the log files. Secrets are redacted from every line. Keep
+/// the returned guard until exit so buffered lines are written.
+pub fn init(files: Option<&LogsConfig>, console: Console) -> Option<WorkerGuard> {
+    let terminal = match console {
+        Console::Stdout => Some((
+            std::io::IsTerminal::is_terminal(&std::io::stdout()),
+            BoxMakeWriter::new(std::io::stdout),
+        )),
+        Console::Stderr => Some((
+            std::io::IsTerminal::is_terminal(&std::io::stderr()),
+            BoxMakeWriter::new(std::io::stderr),
+        )),
+        Console::Off => None,
+    };
+    let terminal = terminal.map(|(ansi, writer)| {
+        fmt::layer()
+            // No colour codes when redirected, e.g. for the background gateway.
+            .with_ansi(ansi)
+            .with_writer(RedactingWriter(writer))
+            .with_filter(env_filter())
+    });
+
+    let mut guard = None;
+    let appender = files
+        .filter(|c| c.files)
+        .map(|config| RotatingFile::new(&logs_dir(), config));
+    let files = match appender {
+        Some(Ok(appender)) => {
+            let (writer, worker) = tracing_appender::non_blocking(appender);
+            guard = Some(worker);
+            Some(
+                fmt::layer()
+                    .fmt_fields(JsonFields::new())
+                    .event_format(JsonLine)
+                    .with_writer(RedactingWriter(writer))
+                    .with_filter(env_filter()),
+            )
+        }
+        Some(Err(e)) => {
+            eprintln!("Warning: not writing log files: {}", e);
+            None
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(terminal)
+        .with(files)
+        .init();
+    guard
+}
+
+/// The log files in `dir`, oldest first.
+pub fn log_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<(String, u32, PathBuf)> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter_map(|p| {
+                    let (period, number) = _file_key(p.file_name()?.to_str()?)?;
+                    Some((period, number, p))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    // The date sorts chronologically, then the number within a day.
+    files.sort();
+    files.into_iter().map(|(_, _, path)| path).collect()
+}
+
+/// The period and number of a log file name: `nanoclaw.log.2026-03-01.2`
+/// is `("2026-03-01", 2)`.
+fn _file_key(name: &str) -> Option<(String, u32)> {
+    let rest = name.strip_prefix(LOG_FILE_PREFIX)?;
+    if rest.is_empty() {
+        return Some((String::new(), 0));
+    }
+    let rest = rest.strip_prefix('.')?;
+    let (period, number) = match rest.rsplit_once('.') {
+        Some((period, number)) => (period, number),
+        None if rest.bytes().all(|b| b.is_ascii_digit()) => ("", rest),
+        None => (rest, "0"),
+    };
+    Some((period.to_string(), number.parse().ok()?))
+}
+
+// ---------------------------------------------------------------------------
+// Writing
+// ---------------------------------------------------------------------------
+
+/// How often a new log file is started regardless of size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rotation {
+    Hour,
+    Day,
+    Never,
+}
+
+impl Rotation {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "hour" => Some(Self::Hour),
+            "day" => Some(Self::Day),
+            "never" => Some(Self::Never),
+            _ => None,
+        }
+    }
+
+    /// The period `at` falls in, as it appears in file names.
+    fn period(self, at: DateTime<Utc>) -> String {
+        match self {
+            Self::Hour => at.format("%Y-%m-%d-%H").to_string(),
+            Self::Day => at.format("%Y-%m-%d").to_string(),
+            Self::Never => String::new(),
+        }
+    }
+}
+
+/// A log file that moves on to a new one when the period ends or it gets
+/// too big, deleting the oldest files beyond the limit.
+pub struct RotatingFile {
+    dir: PathBuf,
+    rotation: Rotation,
+    max_bytes: u64,
+    max_files: usize,
+    file: Option<File>,
+    period: String,
+    number: u32,
+    size: u64,
+    /// Whether the last write ended a line; files only change between lines.
+    at_line_start: bool,
+}
+
+impl RotatingFile {
+    pub fn new(dir: &Path, config: &LogsConfig) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            rotation: Rotation::parse(&config.rotation).unwrap_or(Rotation::Day),
+            max_bytes: config.max_file_mb * 1024 * 1024,
+            max_files: config.max_files,
+            file: None,
+            period: String::new(),
+            number: 0,
+            size: 0,
+            at_line_start: true,
+        })
+    }
+
+    fn _path(&self) -> PathBuf {
+        let mut name = LOG_FILE_PREFIX.to_string();
+        if !self.period.is_empty() {
+            name.push_str(&format!(".{}", self.period));
+        }
+        if self.number > 0 {
+            name.push_str(&format!(".{}", self.number));
+        }
+        self.dir.join(name)
+    }
+
+    fn _full(&self) -> bool {
+        self.max_bytes > 0 && self.size >= self.max_bytes
+    }
+
+    /// Make sure the open file is the right one for a line written `now`.
+    fn _roll(&mut self, now: DateTime<Utc>) -> std::io::Result<()> {
+        let period = self.rotation.period(now);
+        if self.file.is_some() && period == self.period && !self._full() {
+            return Ok(());
+        }
+        if self.file.is_none() || period != self.period {
+            // Carry on with the newest file of the period, e.g. after a restart.
+            self.number = log_files(&self.dir)
+                .iter()
+                .filter_map(|p| _file_key(p.file_name()?.to_str()?))
+                .filter(|(p, _)| *p == period)
+                .map(|(_, number)| number)
+                .max()
+                .unwrap_or(0);
+            self.period = period;
+        } else {
+            self.number += 1;
+        }
+        loop {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self._path())?;
+            self.size = file.metadata()?.len();
+            self.file = Some(file);
+            if !self._full() {
+                break;
+            }
+            self.number += 1;
+        }
+        self._prune();
+        Ok(())
+    }
+
+    /// Write `buf`, moving to the file for `now` at the start of a line.
+    fn _write_at(&mut self, buf: &[u8], now: DateTime<Utc>) -> std::io::Result<usize> {
+        if self.at_line_start {
+            self._roll(now)?;
+        }
+        let Some(file) = self.file.as_mut() else {
+            return Err(std::io::Error::other("log file is not open"));
+        };
+        let written = file.write(buf)?;
+        self.size += written as u64;
+        self.at_line_start = buf[..written].ends_with(b"\n");
+        Ok(written)
+    }
+
+    /// Delete the oldest files beyond `max_files`.
+    fn _prune(&self) {
+        if self.max_files == 0 {
+            return;
+        }
+        let files = log_files(&self.dir);
+        let current = self._path();
+        let excess = files.len().saturating_sub(self.max_files);
+        for path in files.iter().take(excess).filter(|p| **p != current) {
+            if let Err(e) = std::fs::remove_file(path) {
+                eprintln!("Warning: cannot delete {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self._write_at(buf, Utc::now())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Formats an event as one flat JSON object: time, level, target, the
+/// session and turn of the spans it is in, the message and its fields.
+struct JsonLine;
+
+impl<S, N> FormatEvent<S, N> for JsonLine
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        let meta = event.metadata();
+        let mut line = Map::new();
+        line.insert(
+            "timestamp".to_string(),
+            Value::from(Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Micros, true)),
+        );
+        line.insert("level".to_string(), Value::from(meta.level().as_str()));
+        line.insert("target".to_string(), Value::from(meta.target()));
+        // Innermost spans first, so a nested turn wins.
+        for span in ctx.event_scope().into_iter().flatten() {
+            let extensions = span.extensions();
+            let Some(fields) = extensions.get::<FormattedFields<N>>() else {
+                continue;
+            };
+            let Ok(Value::Object(fields)) = serde_json::from_str::<Value>(fields) else {
+                continue;
+            };
+            for key in SPAN_FIELDS {
+                if let (false, Some(value)) = (line.contains_key(key), fields.get(key)) {
+                    line.insert(key.to_string(), value.clone());
+                }
+            }
+        }
+        event.record(&mut _FieldVisitor(&mut line));
+        writeln!(writer, "{}", Value::Object(line))
+    }
+}
+
+/// Collects event fields into a JSON object.
+struct _FieldVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for _FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(
+            field.name().to_string(),
+            Value::from(format!("{:?}", value)),
+        );
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Reading
+// ---------------------------------------------------------------------------
+
+/// Which log lines to show.
+#[derive(Debug, Default)]
+pub struct LogFilter {
+    /// Least severe level to show.
+    pub level: Option<Level>,
+    /// Session key, matching its profile sessions (`key:profile`) too.
+    pub session: Option<String>,
+    /// Turn ID, or its first characters.
+    pub turn: Option<String>,
+}
+
+impl LogFilter {
+    pub fn new(level: Option<&str>, session: Option<String>) -> Result<Self> {
+        let level = level
+            .map(|l| {
+                Level::from_str(l).map_err(|_| {
+                    anyhow::anyhow!("unknown level '{}' (error, warn, info, debug, trace)", l)
+                })
+            })
+            .transpose()?;
+        Ok(Self {
+            level,
+            session,
+            turn: None,
+        })
+    }
+
+    fn matches(&self, entry: &LogEntry) -> bool {
+        if let Some(level) = self.level {
+            // More verbose levels compare greater.
+            if entry.level > level {
+                return false;
+            }
+        }
+        if let Some(turn) = &self.turn {
+            if !entry
+                .turn
+                .as_ref()
+                .is_some_and(|t| t.starts_with(turn.as_str()))
+            {
+                return false;
+            }
+        }
+        if let Some(session) = &self.session {
+            let Some(key) = &entry.session else {
+                return false;
+            };
+            let profile_session = key
+                .strip_prefix(session.as_str())
+                .is_some_and(|rest| rest.starts_with(':'));
+            if key != session && !profile_session {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// One line of a log file.
+#[derive(Debug)]
+pub struct LogEntry {
+    pub timestamp: String,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+    pub session: Option<String>,
+    pub turn: Option<String>,
+}
+
+impl LogEntry {
+    /// Parse a JSON log line; `None` for anything else.
+    pub fn parse(line: &str) -> Option<Self> {
+        let value: Value = serde_json::from_str(line).ok()?;
+        let level = Level::from_str(value["level"].as_str()?).ok()?;
+        // Fields are top-level, or under `fields` in older files.
+        let nested = value["fields"].as_object();
+        let fields = nested.or(value.as_object());
+        let mut message = fields
+            .and_then(|f| f.get("message"))
+            .and_then(|m| m.as_str())
+            .unwrap_or_default()
+            .to_string();
+        // Set on log lines about a turn's replies, outside the turn itself.
+        let mut turn = fields
+            .and_then(|f| f.get("turn"))
+            .and_then(|t| t.as_str())
+            .map(str::to_string);
+        let known: &[&str] = match nested {
+            Some(_) => &["message", "turn"],
+            None => &["message", "turn", "session", "timestamp", "level", "target"],
+        };
+        for (key, field) in fields.into_iter().flatten() {
+            if !known.contains(&key.as_str()) {
+                let field = field
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or(field.to_string());
+                message.push_str(&format!(" {}={}", key, field));
+            }
+        }
+        let spans = || {
+            value["spans"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .chain(std::iter::once(&value["span"]))
+        };
+        let session = value["session"]
+            .as_str()
+            .or_else(|| spans().find_map(|span| span["session"].as_str()))
+            .map(str::to_string);
+        if turn.is_none() {
+            turn = spans()
+                .find_map(|span| span["turn"].as_str())
+                .map(str::to_string);
+        }
+        Some(Self {
+            timestamp: value["timestamp"].as_str().unwrap_or_default().to_string(),
+            level,
+            target: value["target"].as_str().unwrap_or_default().to_string(),
+            message,
+            session,
+            turn,
+        })
+    }
+
+    pub fn format(&self) -> String {
+        let timestamp = chrono::DateTime::parse_from_rfc3339(&self.timestamp)
+            .map(|t| {
+                t.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            })
+            .unwrap_or_else(|_| self.timestamp.clone());
+        let turn = self.turn.as_deref().map(|t| &t[..t.len().min(8)]);
+        let session = match (self.session.as_deref(), turn) {
+            (Some(s), Some(t)) => format!(" [{} {}]", s, t),
+            (Some(s), None) => format!(" [{}]", s),
+            (None, Some(t)) => format!(" [{}]", t),
+            (None, None) => String::new(),
+        };
+        format!(
+            "{} {:>5} {}{}: {}",
+            timestamp, self.level, self.target, session, self.message
+        )
+    }
+}
+
+/// The last `count` entries in `dir` matching `filter`, oldest first.
+pub fn tail(dir: &Path, filter: &LogFilter, count: usize) -> Vec<LogEntry> {
+    let mut entries = Vec::new();
+    // Read the newest files until there are enough entries.
+    for path in log_files(dir).iter().rev() {
+        let Ok(file) = File::open(path) else {
+            continue;
+        };
+        let mut found: Vec<LogEntry> = BufReader::new(file)
+            .lines()
+            .map_while(|l| l.ok())
+            .filter_map(|l| LogEntry::parse(&l))
+            .filter(|e| filter.matches(e))
+            .collect();
+        found.append(&mut entries);
+        entries = found;
+        if entries.len() >= count {
+            break;
+        }
+    }
+    let skip = entries.len().saturating_sub(count);
+    entries.split_off(skip)
+}
+
+/// Print entries matching `filter` as they are written, moving on to the
+/// next file when the log rotates. Runs until the process is stopped.
+pub fn follow(dir: &Path, filter: &LogFilter) -> Result<()> {
+    // Start at the end of the newest file.
+    let mut current = log_files(dir).pop();
+    let mut offset = match &current {
+        Some(path) => std::fs::metadata(path)?.len(),
+        None => 0,
+    };
+    let mut pending = String::new();
+    loop {
+        if let Some(path) = &current {
+            let mut file = File::open(path)?;
+            file.seek(SeekFrom::Start(offset))?;
+            let mut reader = BufReader::new(file);
+            let mut line = String::new();
+            while reader.read_line(&mut line)? > 0 {
+                offset += line.len() as u64;
+                pending.push_str(&line);
+                line.clear();
+                // Wait for the rest of a partly written line.
+                if !pending.ends_with('\n') {
+                    break;
+                }
+                if let Some(entry) = LogEntry::parse(pending.trim_end()) {
+                    if filter.matches(&entry) {
+                        println!("{}", entry.format());
+                    }
+                }
+                pending.clear();
+            }
+        }
+        let newest = log_files(dir).pop();
+        if newest.is_some() && newest != current {
+            current = newest;
+            offset = 0;
+            pending.clear();
+            continue;
+        }
+        std::thread::sleep(FOLLOW_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(level: &str, message: &str, session: Option<&str>) -> String {
+        let mut value = serde_json::json!({
+            "timestamp": "2026-03-01T09:00:00.000000Z",
+            "level": level,
+            "fields": {"message": message},
+            "target": "nanoclaw::agent::agent_loop",
+        });
+        if let Some(session) = session {
+            value["span"] = serde_json::json!({"name": "turn", "session": session});
+            value["spans"] = serde_json::json!([{"name": "turn", "session": session}]);
+        }
+        value.to_string()
+    }
+
+    #[test]
+    fn test_parse_and_filter() {
+        let entry = LogEntry::parse(&line("WARN", "slow", Some("telegram:42:coder"))).unwrap();
+        assert_eq!(entry.level, Level::WARN);
+        assert_eq!(entry.session.as_deref(), Some("telegram:42:coder"));
+        assert!(entry.format().ends_with("[telegram:42:coder]: slow"));
+        assert!(LogEntry::parse("not json").is_none());
+
+        let filter = LogFilter::new(Some("info"), Some("telegram:42".to_string())).unwrap();
+        assert!(filter.matches(&entry));
+        let other = LogEntry::parse(&line("ERROR", "x", Some("telegram:420"))).unwrap();
+        assert!(!filter.matches(&other));
+        let debug = LogEntry::parse(&line("DEBUG", "x", Some("telegram:42"))).unwrap();
+        assert!(!filter.matches(&debug));
+        assert!(LogFilter::new(Some("loud"), None).is_err());
+    }
+
+    #[test]
+    fn test_filter_by_turn() {
+        let mut in_turn: Value =
+            serde_json::from_str(&line("INFO", "tool", Some("cli:x"))).unwrap();
+        in_turn["spans"][0]["turn"] = serde_json::json!("4f1c2a9e-0000");
+        let entry = LogEntry::parse(&in_turn.to_string()).unwrap();
+        assert_eq!(entry.turn.as_deref(), Some("4f1c2a9e-0000"));
+        assert!(entry.format().ends_with("[cli:x 4f1c2a9e]: tool"));
+
+        let mut delivery: Value = serde_json::from_str(&line("WARN", "retrying", None)).unwrap();
+        delivery["fields"]["turn"] = serde_json::json!("4f1c2a9e-0000");
+        let delivery = LogEntry::parse(&delivery.to_string()).unwrap();
+        assert_eq!(delivery.message, "retrying");
+
+        let filter = LogFilter {
+            turn: Some("4f1c".to_string()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&entry));
+        assert!(filter.matches(&delivery));
+        assert!(!filter.matches(&LogEntry::parse(&line("INFO", "x", Some("cli:x"))).unwrap()));
+    }
+
+    #[test]
+    fn test_json_line_carries_session_and_turn() {
+        #[derive(Clone, Default)]
+        struct Buffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+        impl Write for Buffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            fmt::layer()
+                .fmt_fields(JsonFields::new())
+                .event_format(JsonLine)
+                .with_writer(move || writer.clone()),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!(
+                "turn",
+                session = tracing::field::Empty,
+                turn = tracing::field::Empty
+            );
+            let _entered = span.enter();
+            span.record("turn", "4f1c2a9e-0000");
+            span.record("session", "cli:x");
+            tracing::warn!(tool = "exec", "slow {}", 1);
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let value: Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(value["level"], "WARN");
+        assert_eq!(value["message"], "slow 1");
+        assert_eq!(value["session"], "cli:x");
+        assert_eq!(value["turn"], "4f1c2a9e-0000");
+        let entry = LogEntry::parse(output.trim()).unwrap();
+        assert_eq!(entry.session.as_deref(), Some("cli:x"));
+        assert_eq!(entry.turn.as_deref(), Some("4f1c2a9e-0000"));
+        assert_eq!(entry.message, "slow 1 tool=exec");
+    }
+
+    #[test]
+    fn test_rotating_file_by_size_and_period() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogsConfig {
+            max_file_mb: 0,
+            max_files: 3,
+            ..LogsConfig::default()
+        };
+        let mut file = RotatingFile::new(dir.path(), &config).unwrap();
+        // A tiny limit so every line starts a new file.
+        file.max_bytes = 10;
+        let day = |d: u32| {
+            DateTime::parse_from_rfc3339(&format!("2026-03-{:02}T09:00:00Z", d))
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        for _ in 0..2 {
+            file._write_at(b"first day line\n", day(1)).unwrap();
+        }
+        file._write_at(b"part", day(2)).unwrap();
+        file._write_at(b" of a line\n", day(2)).unwrap();
+
+        let names: Vec<String> = log_files(dir.path())
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "nanoclaw.log.2026-03-01",
+                "nanoclaw.log.2026-03-01.1",
+                "nanoclaw.log.2026-03-02"
+            ]
+        );
+
+        // A restart carries on after the newest file of the day.
+        let mut file = RotatingFile::new(dir.path(), &config).unwrap();
+        file.max_bytes = 10;
+        file._write_at(b"more\n", day(1)).unwrap();
+        assert_eq!(file.number, 2);
+        assert_eq!(log_files(dir.path()).len(), 3);
+        assert_eq!(
+            _file_key("nanoclaw.log.2026-03-01.10"),
+            Some(("2026-03-01".to_string(), 10))
+        );
+        assert_eq!(_file_key("nanoclaw.log.3"), Some((String::new(), 3)));
+        assert_eq!(_file_key("other.txt"), None);
+    }
+
+    #[test]
+    fn test_tail_across_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = (1..=3)
+            .map(|i| line("INFO", &format!("old {}", i), None))
+            .collect::<Vec<_>>()
+            .join("\n");
+        std::fs::write(dir.path().join("nanoclaw.log.2026-03-01"), old).unwrap();
+        std::fs::write(
+            dir.path().join("nanoclaw.log.2026-03-02"),
+            line("INFO", "new", None) + "\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("other.txt"), "ignored").unwrap();
+
+        let messages: Vec<String> = tail(dir.path(), &LogFilter::default(), 2)
+            .into_iter()
+            .map(|e| e.message)
+            .collect();
+        assert_eq!(messages, ["old 3", "new"]);
+    }
+}
@@ -1,32 +1,75 @@
 //! nanoclaw - A lightweight personal AI assistant framework in Rust.
 //! Based on nanobot by HKUDS (https://github.com/HKUDS/nanobot).
 
-mod agent;
-mod bus;
-mod channels;
-mod config;
-mod cron;
-mod heartbeat;
-mod providers;
-mod session;
-mod utils;
-
-use std::io::{self, Write as _};
+mod markdown;
+mod repl;
+mod service;
+mod tui;
+
+use nanoclaw::{
+    agent, api, audit, bus, channels, config, cron, daemon, heartbeat, logs, observability,
+    providers, session, utils,
+};
+
+use std::io::{self, IsTerminal, Read as _, Write as _};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use clap::{Parser, Subcommand};
-use tokio::sync::mpsc;
-use tracing::info;
-
-use crate::bus::events::{InboundMessage, OutboundMessage};
-use crate::config::loader::{get_config_path, get_data_dir, load_config, save_config};
-use crate::config::schema::Config;
-use crate::agent::agent_loop::AgentLoop;
+use clap::{Args, Parser, Subcommand};
+use tokio::sync::{broadcast, mpsc, oneshot, Mutex};
+use tracing::{info, warn};
+
+use crate::bus::bridge::BridgeBus;
+use crate::bus::events::{
+    AgentEvent, Attachment, InboundMessage, OutboundMessage, Priority, SystemEvent,
+};
+use crate::bus::receipts::DeliveryReceipts;
+use crate::bus::tap::{BusTap, TrafficCounter};
+use crate::config::edit as config_edit;
+use crate::config::crypt;
+use crate::config::secrets;
+use crate::config::loader::{
+    get_config_path, get_data_dir, load_config, load_config_file, save_config,
+};
+use crate::config::schema::{ApiTokenConfig, Config};
+use crate::agent::agent_loop::{AgentLoop, DirectRequest};
+use crate::agent::builder::AgentLoopBuilder;
+use crate::api::auth::{generate_token, ApiAccess, Scope};
+use crate::api::client::{local_api_url, GatewayClient};
+use crate::api::openai::OpenAiState;
+use crate::api::server::ApiState;
+use crate::api::webhooks::Webhooks;
+use crate::observability::otlp::OtlpExporter;
+use crate::observability::redact::{self, Redactor};
+use crate::agent::builtin_skills;
+use crate::agent::skills::{command_exists, SkillMeta, SkillsLoader};
+use crate::agent::skills_install;
+use crate::agent::skill_test;
+use crate::agent::skills_index;
+use crate::agent::subagent::SubagentStatus;
+use crate::audit::{read_entries, AuditFilter, AuditKind, AuditLog};
+use crate::agent::memory::MemoryStore;
+use crate::agent::progress::TerminalProgressHook;
+use crate::agent::usage::{parse_since, read_records, usage_by, UsageGrouping, UsageRow};
+use crate::channels::dead_letters::DeadLetterStore;
 use crate::channels::manager::ChannelManager;
-use crate::cron::service::CronService;
-use crate::cron::types::CronSchedule;
-use crate::providers::base::LLMProvider;
-use crate::providers::openai_compat::OpenAICompatProvider;
+use crate::channels::postprocess::PostProcessPipeline;
+use crate::cron::natural::parse_natural_time;
+use crate::cron::output::write_run_output;
+use crate::cron::service::{parse_jobs_yaml, validate_schedule, CronService, JobCallback};
+use crate::cron::types::{CronJob, CronRetryPolicy, CronSchedule};
+use crate::daemon::Daemon;
+use crate::heartbeat::pace::{AdaptivePace, UserActivity};
+use crate::heartbeat::agenda::HeartbeatState;
+use crate::heartbeat::maintenance::Maintenance;
+use crate::heartbeat::service::{
+    HeartbeatCallback, HeartbeatService, ProactiveTarget, HEARTBEAT_SESSION,
+};
+use crate::providers::create_provider;
+use crate::logs::Console;
+use crate::markdown::Renderer;
+use crate::repl::Command;
+use crate::session::manager::SessionManager;
 use crate::utils::helpers::get_workspace_path;
 
 const VERSION: &str = "0.1.0";
@@ -51,18 +94,111 @@ enum Commands {
         /// Session ID.
         #[arg(short, long, default_value = "cli:default")]
         session: String,
+        /// Print replies as raw Markdown instead of styled text.
+        #[arg(long)]
+        plain: bool,
+        /// Attach a file or image to the message (repeatable).
+        #[arg(short, long = "file", value_name = "PATH", requires = "message")]
+        files: Vec<PathBuf>,
+        /// Print the reply, tool calls and token usage as JSON.
+        #[arg(long, requires = "message")]
+        json: bool,
+        /// Show each tool call and the start of its result as it happens.
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Talk to the agent in a full-screen terminal interface.
+    Tui {
+        /// Session to open first.
+        #[arg(short, long, default_value = "cli:default")]
+        session: String,
     },
     /// Start the nanoclaw gateway (channels + agent loop).
     Gateway {
-        /// Gateway port.
-        #[arg(short, long, default_value_t = 18790)]
-        port: u16,
+        /// Gateway port (default: gateway.port from the config).
+        #[arg(short, long)]
+        port: Option<u16>,
         /// Verbose logging.
         #[arg(short, long)]
         verbose: bool,
+        /// Run in the background; stop it with `nanoclaw stop`.
+        #[arg(short, long)]
+        daemon: bool,
+    },
+    /// Stop the background gateway.
+    Stop,
+    /// Restart the background gateway, or start it if it is not running.
+    Restart {
+        /// Gateway port (default: gateway.port from the config).
+        #[arg(short, long)]
+        port: Option<u16>,
+    },
+    /// Show the gateway's log files.
+    Logs {
+        /// Keep printing new lines as they are written.
+        #[arg(short, long)]
+        follow: bool,
+        /// Least severe level to show (error, warn, info, debug, trace).
+        #[arg(short, long)]
+        level: Option<String>,
+        /// Only lines from turns of this session (e.g. `telegram:12345`).
+        #[arg(short, long)]
+        session: Option<String>,
+        /// Only lines from this turn, given by its ID or a prefix of it.
+        #[arg(short, long)]
+        turn: Option<String>,
+        /// Number of lines to show.
+        #[arg(short = 'n', long, default_value_t = 50)]
+        lines: usize,
+    },
+    /// Serve the agent over an API.
+    Serve {
+        /// Expose an OpenAI-compatible `/v1/chat/completions` endpoint.
+        #[arg(long)]
+        openai: bool,
+        /// Address to listen on.
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        /// Port to listen on.
+        #[arg(short, long, default_value_t = 18791)]
+        port: u16,
     },
     /// Show nanoclaw status.
-    Status,
+    Status {
+        /// Show running and recent subagents of the gateway.
+        #[arg(long)]
+        agents: bool,
+        /// Print as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show token usage and estimated cost of LLM calls.
+    Usage {
+        /// Only calls since a time ago (`24h`, `7d`, `2w`) or a date
+        /// (`2026-03-01`); default: all recorded calls.
+        #[arg(short, long)]
+        since: Option<String>,
+        /// Group by `model`, `session` or `channel`.
+        #[arg(short, long, default_value = "model")]
+        by: String,
+    },
+    /// Show the audit log of files written, commands run, messages sent
+    /// and webhook POSTs.
+    Audit {
+        /// Only entries since a time ago (`24h`, `7d`) or a date
+        /// (`2026-03-01`).
+        #[arg(long)]
+        since: Option<String>,
+        /// Only entries of this session (e.g. `telegram:12345`).
+        #[arg(short, long)]
+        session: Option<String>,
+        /// Only `file`, `command`, `message` or `http` entries.
+        #[arg(short, long)]
+        kind: Option<String>,
+        /// Number of entries to show.
+        #[arg(short = 'n', long, default_value_t = 50)]
+        lines: usize,
+    },
     /// Manage channels.
     Channels {
         #[command(subcommand)]
@@ -73,12 +209,218 @@ enum Commands {
         #[command(subcommand)]
         action: CronAction,
     },
+    /// Show conversation sessions.
+    Sessions {
+        #[command(subcommand)]
+        action: SessionsAction,
+    },
+    /// Read, change or check the configuration file.
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Store secrets in the OS keyring instead of the config file.
+    Secrets {
+        #[command(subcommand)]
+        action: SecretsAction,
+    },
+    /// Manage tokens for the gateway API.
+    ApiToken {
+        #[command(subcommand)]
+        action: ApiTokenAction,
+    },
+    /// Run the gateway as a systemd (Linux) or launchd (macOS) user service.
+    Service {
+        #[command(subcommand)]
+        action: ServiceAction,
+    },
+    /// List, install and remove skills.
+    Skills {
+        #[command(subcommand)]
+        action: SkillsAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum SkillsAction {
+    /// List workspace and built-in skills and whether they are available.
+    List,
+    /// Install a skill from a git URL, a local directory or, by name, the
+    /// skills index into workspace/skills.
+    Install {
+        /// Git URL, path of the skill's directory or name in the index.
+        source: String,
+        /// Install under this name instead of the skill's own.
+        #[arg(long)]
+        name: Option<String>,
+        /// Replace a skill of the same name.
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Remove a workspace skill.
+    Remove {
+        /// Skill name.
+        name: String,
+    },
+    /// Fetch skills again from where they came from and, after showing
+    /// the changes to SKILL.md, apply them.
+    Update {
+        /// Skill name (default: every skill installed from a source).
+        name: Option<String>,
+        /// Only show which skills changed.
+        #[arg(long)]
+        check: bool,
+        /// Apply updates without asking.
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Check skills for invalid files and unmet requirements, and suggest
+    /// how to install what is missing.
+    Doctor,
+    /// Search the skills index (agents.skills.indexUrl) for community
+    /// skills.
+    Search {
+        /// Words to look for in names, descriptions and tags.
+        #[arg(required = true)]
+        query: Vec<String>,
+    },
+    /// Run the scenarios in a skill's tests.yaml against a scripted model.
+    Test {
+        /// Skill name.
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ServiceAction {
+    /// Write the service file for this binary and home directory.
+    Install,
+    /// Stop the service and remove its file.
+    Uninstall,
+    /// Start the service now and at login.
+    Enable,
+    /// Stop the service and no longer start it at login.
+    Disable,
+    /// Show the service manager's status of the service.
+    Status,
+}
+
+#[derive(Subcommand)]
+enum ApiTokenAction {
+    /// Create a token and print it.
+    Add {
+        /// Token name (e.g. `phone-shortcuts`).
+        name: String,
+        /// `read` (status and listings) or `send` (also sends messages).
+        #[arg(long, default_value = "read")]
+        scope: String,
+    },
+    /// List tokens without their values.
+    List,
+    /// Replace a token's value and print the new one.
+    Rotate {
+        /// Token name.
+        name: String,
+    },
+    /// Delete a token.
+    Remove {
+        /// Token name.
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SecretsAction {
+    /// Store a secret; config values `keyring:<name>` then resolve to it.
+    Set {
+        /// Entry name (e.g. `openrouter`).
+        name: String,
+        /// Secret value; read from stdin when omitted.
+        #[arg(long)]
+        value: Option<String>,
+        /// Also point this config key at the secret (e.g.
+        /// `providers.openrouter.apiKey`).
+        #[arg(long)]
+        config_key: Option<String>,
+    },
+    /// Remove a secret from the keyring.
+    Delete {
+        /// Entry name.
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print the value at a dotted key path (e.g. `channels.telegram.token`).
+    Get {
+        /// Key path.
+        path: String,
+    },
+    /// Set the value at a key path; it is parsed as the key's type (JSON for
+    /// lists and objects).
+    Set {
+        /// Key path.
+        path: String,
+        /// New value.
+        value: String,
+    },
+    /// Check the config file for errors and likely mistakes.
+    Validate {
+        /// Treat warnings, such as unknown keys, as errors.
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Encrypt API keys and tokens in the config file; they are decrypted
+    /// when the config is loaded.
+    Encrypt {
+        /// Use a random key kept in this machine's OS keyring instead of a
+        /// passphrase (the file then only decrypts on this machine).
+        #[arg(long)]
+        machine: bool,
+    },
+    /// Decrypt the secrets in the config file back to plain text.
+    Decrypt,
+    /// Print the JSON Schema of the config file.
+    Schema {
+        /// Write the schema to this file instead of stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 }
 
 #[derive(Subcommand)]
 enum ChannelsAction {
     /// Show channel status.
     Status,
+    /// Send a message to a chat through the running gateway, without the
+    /// agent.
+    Send {
+        /// Channel to send through (e.g. `telegram`).
+        #[arg(long)]
+        channel: String,
+        /// Chat ID to send to.
+        #[arg(long)]
+        to: String,
+        /// Message text.
+        text: String,
+    },
+    /// Inspect or resend messages the channels failed to deliver.
+    Dlq {
+        #[command(subcommand)]
+        action: DlqAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum DlqAction {
+    /// List undelivered messages.
+    List,
+    /// Resend undelivered messages through the running gateway.
+    Retry {
+        /// Dead letter ID, or `all`.
+        id: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -88,36 +430,29 @@ enum CronAction {
         /// Include disabled jobs.
         #[arg(short, long)]
         all: bool,
-    },
-    /// Add a scheduled job.
-    Add {
-        /// Job name.
-        #[arg(short, long)]
-        name: String,
-        /// Message for agent.
-        #[arg(short, long)]
-        message: String,
-        /// Run every N seconds.
-        #[arg(short, long)]
-        every: Option<u64>,
-        /// Cron expression.
-        #[arg(short, long)]
-        cron: Option<String>,
-        /// Deliver response to channel.
-        #[arg(short, long)]
-        deliver: bool,
-        /// Recipient for delivery.
+        /// Print as JSON.
         #[arg(long)]
-        to: Option<String>,
-        /// Channel for delivery.
-        #[arg(long)]
-        channel: Option<String>,
+        json: bool,
     },
+    /// Add a scheduled job.
+    Add(Box<CronAddArgs>),
+    /// Change a job's schedule, message, delivery, or name in place.
+    Edit(Box<CronEditArgs>),
     /// Remove a scheduled job.
     Remove {
         /// Job ID to remove.
         job_id: String,
     },
+    /// Run a job immediately.
+    Run {
+        /// Job ID.
+        job_id: String,
+    },
+    /// Show recent runs of a job.
+    History {
+        /// Job ID.
+        job_id: String,
+    },
     /// Enable or disable a job.
     Enable {
         /// Job ID.
@@ -126,33 +461,283 @@ enum CronAction {
         #[arg(long)]
         disable: bool,
     },
+    /// Export job definitions as YAML.
+    Export {
+        /// Write to a file instead of stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Import jobs from a YAML (or JSON) export.
+    Import {
+        /// File to import.
+        file: PathBuf,
+        /// Give every imported job a new ID.
+        #[arg(long)]
+        new_ids: bool,
+        /// What to do with jobs whose ID already exists.
+        #[arg(long, default_value = "skip", value_parser = ["skip", "replace", "rename"])]
+        on_conflict: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SessionsAction {
+    /// List sessions, most recently updated first.
+    List {
+        /// Print as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Args)]
+struct CronAddArgs {
+    /// Job name.
+    #[arg(short, long)]
+    name: String,
+    /// Message for agent.
+    #[arg(short, long)]
+    message: String,
+    /// Run every N seconds.
+    #[arg(short, long)]
+    every: Option<u64>,
+    /// Cron expression.
+    #[arg(short, long)]
+    cron: Option<String>,
+    /// Run once at a time like "tomorrow 9am" or "in 20 minutes".
+    #[arg(long)]
+    at: Option<String>,
+    /// Timezone for the cron expression (e.g. "Europe/Rome").
+    #[arg(long)]
+    tz: Option<String>,
+    /// Random delay of up to N seconds added to each run.
+    #[arg(long)]
+    jitter: Option<u64>,
+    /// Deliver response to channel.
+    #[arg(short, long)]
+    deliver: bool,
+    /// Recipient for delivery.
+    #[arg(long)]
+    to: Option<String>,
+    /// Channel for delivery.
+    #[arg(long)]
+    channel: Option<String>,
+    /// Save each response under workspace/cron/<job_id>/.
+    #[arg(long)]
+    save_output: bool,
+    /// Total attempts per run when the agent turn or delivery fails.
+    #[arg(long, default_value_t = 1)]
+    max_attempts: u32,
+    /// Seconds to wait before the first retry (doubles on each retry).
+    #[arg(long, default_value_t = 30)]
+    backoff: u64,
+    /// What to do if the previous run is still in progress.
+    #[arg(long, default_value = "skip", value_parser = ["skip", "queue", "parallel"])]
+    overlap: String,
+    /// Disable the job (or delete it, with --delete-when-done) after N runs.
+    #[arg(long)]
+    max_runs: Option<u32>,
+    /// Stop running the job after a time like "friday 6pm".
+    #[arg(long)]
+    until: Option<String>,
+    /// Delete the job instead of disabling it once it expires.
+    #[arg(long)]
+    delete_when_done: bool,
+}
+
+#[derive(Args)]
+struct CronEditArgs {
+    /// Job ID.
+    job_id: String,
+    /// New job name.
+    #[arg(short, long)]
+    name: Option<String>,
+    /// New message for agent.
+    #[arg(short, long)]
+    message: Option<String>,
+    /// Run every N seconds.
+    #[arg(short, long, conflicts_with_all = ["cron", "at"])]
+    every: Option<u64>,
+    /// Cron expression.
+    #[arg(short, long, conflicts_with = "at")]
+    cron: Option<String>,
+    /// Run once at a time like "tomorrow 9am" or "in 20 minutes".
+    #[arg(long)]
+    at: Option<String>,
+    /// Timezone for the cron expression (e.g. "Europe/Rome").
+    #[arg(long)]
+    tz: Option<String>,
+    /// Random delay of up to N seconds added to each run (0 to disable).
+    #[arg(long)]
+    jitter: Option<u64>,
+    /// Deliver response to channel.
+    #[arg(short, long, conflicts_with = "no_deliver")]
+    deliver: bool,
+    /// Stop delivering responses to a channel.
+    #[arg(long)]
+    no_deliver: bool,
+    /// Recipient for delivery.
+    #[arg(long)]
+    to: Option<String>,
+    /// Channel for delivery.
+    #[arg(long)]
+    channel: Option<String>,
+    /// Save each response under workspace/cron/<job_id>/.
+    #[arg(long, conflicts_with = "no_save_output")]
+    save_output: bool,
+    /// Stop saving responses to the workspace.
+    #[arg(long)]
+    no_save_output: bool,
+    /// Total attempts per run when the agent turn or delivery fails.
+    #[arg(long)]
+    max_attempts: Option<u32>,
+    /// Seconds to wait before the first retry (doubles on each retry).
+    #[arg(long)]
+    backoff: Option<u64>,
+    /// What to do if the previous run is still in progress.
+    #[arg(long, value_parser = ["skip", "queue", "parallel"])]
+    overlap: Option<String>,
+    /// Expire the job after N runs in total (0 to remove the limit).
+    #[arg(long)]
+    max_runs: Option<u32>,
+    /// Stop running the job after a time like "friday 6pm".
+    #[arg(long, conflicts_with = "no_until")]
+    until: Option<String>,
+    /// Remove the --until limit.
+    #[arg(long)]
+    no_until: bool,
 }
 
 fn main() {
     let cli = Cli::parse();
 
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
-        )
-        .init();
+    // Long-running commands also log to files, for `nanoclaw logs`.
+    let long_running = matches!(
+        cli.command,
+        Commands::Gateway { daemon: false, .. } | Commands::Serve { .. } | Commands::Tui { .. }
+    );
+    let config = long_running.then(|| load_config(None));
+    if let Some(config) = &config {
+        redact::install(Redactor::new(config));
+    }
+    let console = match &cli.command {
+        // The TUI has the terminal to itself.
+        Commands::Tui { .. } => Console::Off,
+        Commands::Agent { json: true, .. }
+        | Commands::Status { json: true, .. }
+        | Commands::Cron {
+            action: CronAction::List { json: true, .. },
+        }
+        | Commands::Sessions {
+            action: SessionsAction::List { json: true },
+        } => Console::Stderr,
+        _ => Console::Stdout,
+    };
+    let _log_guard = logs::init(config.as_ref().map(|c| &c.observability.logs), console);
 
     match cli.command {
         Commands::Onboard => cmd_onboard(),
-        Commands::Agent { message, session } => cmd_agent(message, session),
-        Commands::Gateway { port, verbose } => cmd_gateway(port, verbose),
-        Commands::Status => cmd_status(),
+        Commands::Agent {
+            message,
+            session,
+            plain,
+            files,
+            json,
+            verbose,
+        } => cmd_agent(message, session, plain, &files, json, verbose),
+        Commands::Tui { session } => cmd_tui(&session),
+        Commands::Gateway {
+            port,
+            verbose,
+            daemon,
+        } => {
+            if daemon {
+                cmd_gateway_daemon(port, verbose)
+            } else {
+                cmd_gateway(port, verbose)
+            }
+        }
+        Commands::Stop => cmd_stop(),
+        Commands::Logs {
+            follow,
+            level,
+            session,
+            turn,
+            lines,
+        } => cmd_logs(follow, level.as_deref(), session, turn, lines),
+        Commands::Restart { port } => cmd_restart(port),
+        Commands::Serve { openai, host, port } => cmd_serve(openai, &host, port),
+        Commands::Status { agents, json } => cmd_status(agents, json),
+        Commands::Usage { since, by } => cmd_usage(since.as_deref(), &by),
+        Commands::Audit {
+            since,
+            session,
+            kind,
+            lines,
+        } => cmd_audit(since.as_deref(), session, kind.as_deref(), lines),
         Commands::Channels { action } => match action {
             ChannelsAction::Status => cmd_channels_status(),
+            ChannelsAction::Send { channel, to, text } => cmd_channels_send(&channel, &to, &text),
+            ChannelsAction::Dlq { action } => match action {
+                DlqAction::List => cmd_dlq_list(),
+                DlqAction::Retry { id } => cmd_dlq_retry(&id),
+            },
+        },
+        Commands::Config { action } => match action {
+            ConfigAction::Get { path } => cmd_config_get(&path),
+            ConfigAction::Set { path, value } => cmd_config_set(&path, &value),
+            ConfigAction::Validate { strict } => cmd_config_validate(strict),
+            ConfigAction::Encrypt { machine } => cmd_config_encrypt(machine),
+            ConfigAction::Decrypt => cmd_config_decrypt(),
+            ConfigAction::Schema { output } => cmd_config_schema(output.as_deref()),
+        },
+        Commands::Secrets { action } => match action {
+            SecretsAction::Set {
+                name,
+                value,
+                config_key,
+            } => cmd_secrets_set(&name, value, config_key),
+            SecretsAction::Delete { name } => cmd_secrets_delete(&name),
+        },
+        Commands::ApiToken { action } => match action {
+            ApiTokenAction::Add { name, scope } => cmd_api_token_add(&name, &scope),
+            ApiTokenAction::List => cmd_api_token_list(),
+            ApiTokenAction::Rotate { name } => cmd_api_token_rotate(&name),
+            ApiTokenAction::Remove { name } => cmd_api_token_remove(&name),
+        },
+        Commands::Service { action } => cmd_service(action),
+        Commands::Skills { action } => match action {
+            SkillsAction::List => cmd_skills_list(),
+            SkillsAction::Install {
+                source,
+                name,
+                force,
+            } => cmd_skills_install(&source, name.as_deref(), force),
+            SkillsAction::Remove { name } => cmd_skills_remove(&name),
+            SkillsAction::Update { name, check, yes } => {
+                cmd_skills_update(name.as_deref(), check, yes)
+            }
+            SkillsAction::Doctor => cmd_skills_doctor(),
+            SkillsAction::Search { query } => cmd_skills_search(&query.join(" ")),
+            SkillsAction::Test { name } => cmd_skills_test(&name),
         },
         Commands::Cron { action } => match action {
-            CronAction::List { all } => cmd_cron_list(all),
-            CronAction::Add {
-                name, message, every, cron, deliver, to, channel,
-            } => cmd_cron_add(name, message, every, cron, deliver, to, channel),
+            CronAction::List { all, json } => cmd_cron_list(all, json),
+            CronAction::Add(args) => cmd_cron_add(args),
+            CronAction::Edit(args) => cmd_cron_edit(args),
             CronAction::Remove { job_id } => cmd_cron_remove(job_id),
+            CronAction::Run { job_id } => cmd_cron_run(job_id),
+            CronAction::History { job_id } => cmd_cron_history(job_id),
             CronAction::Enable { job_id, disable } => cmd_cron_enable(job_id, disable),
+            CronAction::Export { output } => cmd_cron_export(output),
+            CronAction::Import {
+                file,
+                new_ids,
+                on_conflict,
+            } => cmd_cron_import(file, new_ids, on_conflict),
+        },
+        Commands::Sessions { action } => match action {
+            SessionsAction::List { json } => cmd_sessions_list(json),
         },
     }
 }
@@ -184,6 +769,14 @@ fn cmd_onboard() {
 
     create_workspace_templates(&workspace);
 
+    match builtin_skills::copy_to(&workspace.join("skills")) {
+        Ok(copied) if !copied.is_empty() => {
+            println!("  Copied skills to customize: {}", copied.join(", "))
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("  Could not copy skills: {:#}", e),
+    }
+
     println!("\n{} nanoclaw is ready!", LOGO);
     println!("\nNext steps:");
     println!("  1. Add your API key to ~/.nanoclaw/config.json");
@@ -223,8 +816,36 @@ fn create_workspace_templates(workspace: &std::path::Path) {
 // Agent
 // ============================================================================
 
-fn cmd_agent(message: Option<String>, session_id: String) {
+fn cmd_agent(
+    message: Option<String>,
+    session_id: String,
+    plain: bool,
+    files: &[PathBuf],
+    json: bool,
+    verbose: bool,
+) {
     let config = load_config(None);
+    let message = message.map(_with_stdin);
+    let attachments: Vec<String> = files
+        .iter()
+        .map(|file| match std::fs::canonicalize(file) {
+            Ok(path) => path.to_string_lossy().to_string(),
+            Err(e) => {
+                eprintln!("Error: cannot attach {}: {}", file.display(), e);
+                std::process::exit(1);
+            }
+        })
+        .collect();
+
+    // Talk to a running gateway rather than racing it over the same files.
+    if let Some(client) = GatewayClient::connect(&Daemon::new(get_data_dir()), &config.gateway) {
+        if verbose {
+            eprintln!("Tool calls are not shown through a running gateway.");
+        }
+        cmd_agent_via_gateway(&client, message, &attachments, &session_id, plain, json);
+        return;
+    }
+
     let api_key = config.get_api_key();
     let model = config.agents.defaults.model.clone();
 
@@ -234,77 +855,325 @@ fn cmd_agent(message: Option<String>, session_id: String) {
         std::process::exit(1);
     }
 
+    let renderer = Renderer::new(plain);
     let runtime = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
 
     runtime.block_on(async {
         let (inbound_tx, inbound_rx) = mpsc::unbounded_channel::<InboundMessage>();
         let (outbound_tx, _outbound_rx) = mpsc::unbounded_channel::<OutboundMessage>();
 
-        let provider = create_provider(&config);
-        let brave_key = if config.tools.web.search.api_key.is_empty() {
-            None
-        } else {
-            Some(config.tools.web.search.api_key.clone())
-        };
-
-        let cron_store_path = get_data_dir().join("cron").join("jobs.json");
-        let cron_service = Arc::new(CronService::new(cron_store_path));
+        let cron_service = Arc::new(Mutex::new(open_cron_service(&config)));
 
-        let mut agent_loop = AgentLoop::new(
-            inbound_rx,
-            outbound_tx,
-            inbound_tx,
-            provider,
-            config.workspace_path(),
-            model,
-            config.agents.defaults.max_tool_iterations,
-            brave_key,
-            config.tools.exec_.timeout,
-            config.tools.exec_.restrict_to_workspace,
-            Some(cron_service),
-        );
+        let mut agent_loop =
+            create_agent_loop(&config, inbound_rx, outbound_tx, inbound_tx, cron_service);
+        if verbose {
+            agent_loop.add_hook(Arc::new(TerminalProgressHook::new()));
+        }
 
         if let Some(msg) = message {
+            let attachments = attachments.iter().map(Attachment::new).collect();
+            let mut events = agent_loop.subscribe_events();
             let response = agent_loop
-                .process_direct(&msg, &session_id, "cli", "direct")
+                .process_direct_with_attachments(&msg, attachments, &session_id, "cli", "direct")
                 .await;
-            println!("\n{} {}", LOGO, response);
-        } else {
-            println!("{} Interactive mode (Ctrl+C to exit)\n", LOGO);
-            loop {
-                print!("You: ");
-                io::stdout().flush().ok();
-                let mut input = String::new();
-                match io::stdin().read_line(&mut input) {
-                    Ok(0) | Err(_) => break,
-                    _ => {}
+            if json {
+                let (report, ok) = _turn_report(&mut events, &session_id, &response);
+                println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+                if !ok {
+                    std::process::exit(1);
                 }
-                let input = input.trim();
-                if input.is_empty() {
+            } else {
+                println!("\n{} {}", LOGO, renderer.render(&response));
+            }
+        } else {
+            let mut repl = _open_repl();
+            let mut session_id = session_id;
+            println!(
+                "{} Interactive mode (/help for commands, Ctrl+D to exit, Ctrl+C stops a reply)\n",
+                LOGO
+            );
+            while let Some(input) = repl.read() {
+                if let Some(command) = Command::parse(&input) {
+                    let output = match command {
+                        Command::Model(Some(model)) => {
+                            agent_loop.set_model(&model);
+                            format!("Using {} from now on.", model)
+                        }
+                        Command::Model(None) => format!("Model: {}", agent_loop.model()),
+                        Command::Tools => agent_loop.tool_names().join(", "),
+                        Command::History(n) => _format_history(&agent_loop.history(&session_id, n)),
+                        command => _repl_command(command, &mut session_id, &config),
+                    };
+                    println!("{}\n", output);
                     continue;
                 }
-                let response = agent_loop
-                    .process_direct(input, &session_id, "cli", "direct")
-                    .await;
-                println!("\n{} {}\n", LOGO, response);
+                let turn = agent_loop.process_direct(&input, &session_id, "cli", "direct");
+                if let Some(response) = repl::interruptible(turn).await {
+                    println!("\n{} {}\n", LOGO, renderer.render(&response));
+                }
             }
             println!("Goodbye!");
         }
     });
 }
 
+fn _open_repl() -> repl::Repl {
+    match repl::Repl::new() {
+        Ok(repl) => repl,
+        Err(e) => {
+            eprintln!("Error: cannot read from the terminal: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn cmd_tui(session_id: &str) {
+    let config = load_config(None);
+    if GatewayClient::connect(&Daemon::new(get_data_dir()), &config.gateway).is_some() {
+        eprintln!("Error: the gateway is running; stop it or use `nanoclaw agent` instead.");
+        std::process::exit(1);
+    }
+    if config.get_api_key().is_none() && !config.agents.defaults.model.starts_with("bedrock/") {
+        eprintln!("Error: No API key configured.");
+        std::process::exit(1);
+    }
+
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+    runtime.block_on(async {
+        let (inbound_tx, inbound_rx) = mpsc::unbounded_channel::<InboundMessage>();
+        let (outbound_tx, outbound_rx) = mpsc::unbounded_channel::<OutboundMessage>();
+        let cron_service = Arc::new(Mutex::new(open_cron_service(&config)));
+        let agent_loop =
+            create_agent_loop(&config, inbound_rx, outbound_tx, inbound_tx, cron_service);
+        if let Err(e) =
+            tui::run(agent_loop, outbound_rx, &config.workspace_path(), session_id).await
+        {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+    });
+}
+
+/// What `agent --json` prints for a turn whose events are waiting in
+/// `events`, and whether the turn succeeded.
+fn _turn_report(
+    events: &mut broadcast::Receiver<AgentEvent>,
+    session_id: &str,
+    reply: &str,
+) -> (serde_json::Value, bool) {
+    let mut tool_calls = Vec::new();
+    let (mut llm_calls, mut prompt_tokens, mut completion_tokens) = (0, 0, 0);
+    let mut failed = false;
+    loop {
+        match events.try_recv() {
+            Ok(AgentEvent::ToolCallFinished {
+                tool,
+                result_bytes,
+                duration_ms,
+                ..
+            }) => tool_calls.push(serde_json::json!({
+                "tool": tool,
+                "resultBytes": result_bytes,
+                "durationMs": duration_ms,
+            })),
+            Ok(AgentEvent::LlmTokens {
+                prompt_tokens: prompt,
+                completion_tokens: completion,
+                ..
+            }) => {
+                llm_calls += 1;
+                prompt_tokens += prompt;
+                completion_tokens += completion;
+            }
+            Ok(AgentEvent::TurnCompleted { failed: f, .. }) => failed = f,
+            Ok(_) | Err(broadcast::error::TryRecvError::Lagged(_)) => {}
+            Err(_) => break,
+        }
+    }
+    let mut report = serde_json::json!({
+        "session": session_id,
+        "ok": !failed,
+        "reply": reply,
+        "toolCalls": tool_calls,
+        "usage": {
+            "llmCalls": llm_calls,
+            "promptTokens": prompt_tokens,
+            "completionTokens": completion_tokens,
+        },
+    });
+    if failed {
+        report["error"] = serde_json::json!(reply);
+    }
+    (report, !failed)
+}
+
+/// Characters of piped input added to a message; the rest is cut.
+const MAX_STDIN_CHARS: usize = 100_000;
+
+/// `message` followed by what was piped to stdin, if anything.
+fn _with_stdin(message: String) -> String {
+    if io::stdin().is_terminal() {
+        return message;
+    }
+    let mut input = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut input) {
+        eprintln!("Warning: not reading stdin: {}", e);
+        return message;
+    }
+    let input = input.trim_end();
+    if input.is_empty() {
+        return message;
+    }
+    let mut piped: String = input.chars().take(MAX_STDIN_CHARS).collect();
+    if piped.len() < input.len() {
+        piped.push_str("\n[... cut: stdin is longer]");
+    }
+    format!("{}\n\n<stdin>\n{}\n</stdin>", message, piped)
+}
+
+/// The output of a prompt command that needs no agent loop.
+fn _repl_command(command: Command, session_id: &mut String, config: &Config) -> String {
+    match command {
+        Command::New => {
+            let previous = std::mem::replace(session_id, repl::new_session_key());
+            format!(
+                "Started {}. Continue the last conversation with `nanoclaw agent -s {}`.",
+                session_id, previous
+            )
+        }
+        Command::Memory => {
+            let memory = MemoryStore::new(&config.workspace_path());
+            let or_empty = |text: String| {
+                if text.trim().is_empty() {
+                    "(empty)".to_string()
+                } else {
+                    text.trim().to_string()
+                }
+            };
+            format!(
+                "Long-term memory:\n{}\n\nToday's notes:\n{}",
+                or_empty(memory.read_long_term()),
+                or_empty(memory.read_today())
+            )
+        }
+        _ => repl::HELP.to_string(),
+    }
+}
+
+/// Messages of a session as `/history` shows them.
+fn _format_history(messages: &[serde_json::Value]) -> String {
+    if messages.is_empty() {
+        return "No messages yet.".to_string();
+    }
+    messages
+        .iter()
+        .map(|m| {
+            let content = m["content"].as_str().unwrap_or_default();
+            let mut text: String = content.chars().take(500).collect();
+            if text.len() < content.len() {
+                text.push('…');
+            }
+            match m["role"].as_str().unwrap_or_default() {
+                "user" => format!("You: {}", text),
+                "assistant" => format!("{} {}", LOGO, text),
+                role => format!("({}) {}", role, text),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `cmd_agent` through the running gateway's API.
+fn cmd_agent_via_gateway(
+    client: &GatewayClient,
+    message: Option<String>,
+    attachments: &[String],
+    session_id: &str,
+    plain: bool,
+    json: bool,
+) {
+    let renderer = Renderer::new(plain);
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+    runtime.block_on(async {
+        if let Some(msg) = message {
+            let reply = client.send(session_id, &msg, attachments).await;
+            if json {
+                // The gateway does not report the turn's tool calls and usage.
+                let report = match &reply {
+                    Ok(response) => {
+                        serde_json::json!({"session": session_id, "ok": true, "reply": response})
+                    }
+                    Err(e) => serde_json::json!({
+                        "session": session_id,
+                        "ok": false,
+                        "error": e.to_string(),
+                    }),
+                };
+                println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+                if reply.is_err() {
+                    std::process::exit(1);
+                }
+                return;
+            }
+            match reply {
+                Ok(response) => println!("\n{} {}", LOGO, renderer.render(&response)),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        let config = load_config(None);
+        let mut repl = _open_repl();
+        let mut session_id = session_id.to_string();
+        println!(
+            "{} Interactive mode via the running gateway (/help for commands, Ctrl+D to exit, \
+             Ctrl+C stops a reply)\n",
+            LOGO
+        );
+        while let Some(input) = repl.read() {
+            if let Some(command) = Command::parse(&input) {
+                let output = match command {
+                    Command::Model(_) | Command::Tools => {
+                        "Not available through the gateway; stop it to use /model and /tools."
+                            .to_string()
+                    }
+                    Command::History(n) => {
+                        let mut sessions = SessionManager::new(&config.workspace_path());
+                        _format_history(&sessions.get_or_create(&session_id).get_history(n))
+                    }
+                    command => _repl_command(command, &mut session_id, &config),
+                };
+                println!("{}\n", output);
+                continue;
+            }
+            match repl::interruptible(client.send(&session_id, &input, &[])).await {
+                Some(Ok(response)) => println!("\n{} {}\n", LOGO, renderer.render(&response)),
+                Some(Err(e)) => eprintln!("\nError: {}\n", e),
+                None => {}
+            }
+        }
+        println!("Goodbye!");
+    });
+}
+
 // ============================================================================
 // Gateway
 // ============================================================================
 
-fn cmd_gateway(port: u16, verbose: bool) {
+fn cmd_gateway(port: Option<u16>, verbose: bool) {
     if verbose {
         eprintln!("Verbose mode enabled");
     }
 
+    let config = load_config(None);
+    let port = port.unwrap_or(config.gateway.port);
     println!("{} Starting nanoclaw gateway on port {}...", LOGO, port);
+    for warning in exposed_secret_warnings(&config) {
+        warn!("{}", warning);
+    }
 
-    let config = load_config(None);
     let api_key = config.get_api_key();
     let model = config.agents.defaults.model.clone();
 
@@ -313,41 +1182,94 @@ fn cmd_gateway(port: u16, verbose: bool) {
         std::process::exit(1);
     }
 
+    let daemon = Daemon::new(get_data_dir());
+    // Held until the gateway exits.
+    let _instance = match daemon.lock() {
+        Ok(lock) => lock,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+
     let runtime = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
 
     runtime.block_on(async {
-        let (inbound_tx, inbound_rx) = mpsc::unbounded_channel::<InboundMessage>();
-        let (outbound_tx, outbound_rx) = mpsc::unbounded_channel::<OutboundMessage>();
-
-        let provider = create_provider(&config);
-        let brave_key = if config.tools.web.search.api_key.is_empty() {
-            None
-        } else {
-            Some(config.tools.web.search.api_key.clone())
+        let mut control = match daemon.listen(&local_api_url(&config.gateway, port)) {
+            Ok(control) => control,
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                std::process::exit(1);
+            }
         };
 
-        let cron_store_path = get_data_dir().join("cron").join("jobs.json");
-        let mut cron_service = CronService::new(cron_store_path);
+        let (inbound_tx, inbound_rx) = mpsc::unbounded_channel::<InboundMessage>();
+        let (outbound_tx, outbound_rx) = mpsc::unbounded_channel::<OutboundMessage>();
+        // Everything on the bus is also copied to the tap's subscribers.
+        let tap = BusTap::new();
+        let inbound_tx = tap.inbound(inbound_tx);
+        let outbound_tx = tap.outbound(outbound_tx);
+        let traffic = Arc::new(TrafficCounter::default());
+        let traffic_counter = tap.add_subscriber(traffic.clone());
+
+        let mut cron_service = open_cron_service(&config);
         cron_service.start().await;
         let cron_status = cron_service.status();
-        let cron_arc = Arc::new(cron_service);
+        let cron_arc = Arc::new(Mutex::new(cron_service));
 
-        let mut agent_loop = AgentLoop::new(
+        let mut agent_loop = create_agent_loop(
+            &config,
             inbound_rx,
-            outbound_tx,
+            outbound_tx.clone(),
             inbound_tx.clone(),
-            provider,
-            config.workspace_path(),
-            model,
-            config.agents.defaults.max_tool_iterations,
-            brave_key,
-            config.tools.exec_.timeout,
-            config.tools.exec_.restrict_to_workspace,
-            Some(cron_arc),
+            cron_arc.clone(),
         );
-
-        let channel_manager = ChannelManager::new(&config, inbound_tx, outbound_rx);
-
+        let system_events = SystemEvent::channel();
+        agent_loop.set_system_events(system_events.clone());
+        let system_tap = tap.system(&system_events);
+
+        let receipts = Arc::new(DeliveryReceipts::default());
+        cron_arc
+            .lock()
+            .await
+            .set_on_job(cron_job_callback(
+                agent_loop.direct_sender(),
+                outbound_tx.clone(),
+                config.workspace_path(),
+                Some(system_events.clone()),
+                Some(receipts.clone()),
+            ));
+        let cron_scheduler = CronService::spawn_scheduler(cron_arc.clone());
+
+        let user_activity = Arc::new(UserActivity::new());
+        let activity_tracker = tap.add_subscriber(user_activity.clone());
+        let heartbeat = create_heartbeat(
+            &config,
+            agent_loop.direct_sender(),
+            outbound_tx.clone(),
+            cron_arc.clone(),
+            user_activity,
+        );
+        heartbeat.start().await;
+
+        let subagents = agent_loop.subagents();
+        subagents.set_status_file(subagent_status_path()).await;
+        subagents.set_system_events(system_events.clone()).await;
+
+        let mut channel_manager = ChannelManager::new(&config, inbound_tx.clone(), outbound_rx);
+        channel_manager.set_audit_log(Arc::new(AuditLog::open()));
+        channel_manager.set_delivery_receipts(receipts);
+        channel_manager.set_post_processors(PostProcessPipeline::from_config(
+            &config,
+            create_provider(&config),
+            &config.workspace_path(),
+            get_data_dir().join("media"),
+        ));
+
+        let channel_manager = Arc::new(channel_manager);
+        let channel_watch = channel_manager
+            .clone()
+            .watch(std::time::Duration::from_secs(30), system_events.clone());
         let enabled = channel_manager.enabled_channels();
         if !enabled.is_empty() {
             println!("  Channels enabled: {}", enabled.join(", "));
@@ -362,33 +1284,659 @@ fn cmd_gateway(port: u16, verbose: bool) {
             }
         }
 
-        println!("  Heartbeat: every 30m");
+        if config.heartbeat.enabled {
+            let hb = &config.heartbeat;
+            let secs = |s: u64| format_duration(chrono::Duration::seconds(s as i64));
+            if hb.adaptive {
+                println!(
+                    "  Heartbeat: every {} (adaptive, {} to {})",
+                    secs(hb.interval_secs),
+                    secs(hb.active_interval_secs.min(hb.interval_secs)),
+                    secs(hb.max_interval_secs.max(hb.interval_secs))
+                );
+            } else {
+                println!("  Heartbeat: every {}", secs(hb.interval_secs));
+            }
+        } else {
+            println!("  Heartbeat: off");
+        }
+
+        let api_state = Arc::new(ApiState::new(
+            agent_loop.direct_sender(),
+            cron_arc.clone(),
+            config.workspace_path(),
+            config.agents.defaults.model.clone(),
+            enabled.clone(),
+            ApiAccess::from_config(&config.gateway),
+        )
+        .with_readiness(channel_manager.clone(), create_provider(&config))
+        .with_subagents(subagents.clone())
+        .with_outbound(outbound_tx.clone())
+        .with_dead_letters(channel_manager.dead_letters())
+        .with_system_events(system_events.clone())
+        .with_traffic(traffic)
+        .with_heartbeat(heartbeat_state_path()));
+        let tls = match api::tls::load(&config.gateway.tls).await {
+            Ok(tls) => tls,
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                std::process::exit(1);
+            }
+        };
+        let host = config.gateway.host.clone();
+        let api_server = tokio::spawn(async move {
+            if let Err(e) = api::server::serve(api_state, &host, port, tls).await {
+                tracing::error!("REST API stopped: {}", e);
+            }
+        });
+        println!(
+            "  API: {}://{}:{}/api",
+            api::tls::scheme(&config.gateway.tls),
+            config.gateway.host,
+            port
+        );
+
+        let webhooks =
+            Arc::new(Webhooks::new(config.webhooks.clone()).with_audit_log(AuditLog::open()));
+        let mut webhook_tasks = Vec::new();
+        if !webhooks.is_empty() {
+            cron_arc.lock().await.set_on_failure(webhooks.cron_failure_callback());
+            webhook_tasks.push(webhooks.clone().forward_agent_events(agent_loop.subscribe_events()));
+            webhook_tasks.push(webhooks.clone().forward_system_events(system_events.subscribe()));
+            println!("  Webhooks: {}", config.webhooks.len());
+        }
+
+        let otlp_exporter = OtlpExporter::new(&config.observability.otlp).map(|exporter| {
+            println!("  OTLP: {}", config.observability.otlp.endpoint);
+            exporter.forward_agent_events(agent_loop.subscribe_events())
+        });
+
+        let bridge = if config.bridge.is_enabled() {
+            let bus = BridgeBus {
+                tap: tap.clone(),
+                inbound_tx: inbound_tx.clone(),
+                outbound_tx: outbound_tx.clone(),
+            };
+            match bus::bridge::spawn(&config.bridge, bus) {
+                Ok(task) => {
+                    println!("  Bridge: {}", bus::bridge::redacted_url(&config.bridge.url));
+                    Some(task)
+                }
+                Err(e) => {
+                    eprintln!("Error: bridge: {:#}", e);
+                    std::process::exit(1);
+                }
+            }
+        } else {
+            None
+        };
+
+        let system_event_reactor = (!config.system_events.react.is_empty()).then(|| {
+            react_to_system_events(
+                &config,
+                system_events.subscribe(),
+                agent_loop.direct_sender(),
+                outbound_tx.clone(),
+            )
+        });
 
         tokio::select! {
             _ = agent_loop.run() => {
                 info!("Agent loop ended");
             }
-            _ = channel_manager.start_all() => {
+            // Without channels the gateway still serves the API.
+            _ = channel_manager.start_all(), if !enabled.is_empty() => {
                 info!("Channel manager ended");
             }
             _ = tokio::signal::ctrl_c() => {
                 println!("\nShutting down...");
             }
+            _ = control.stopped() => {
+                println!("Stop requested, shutting down...");
+            }
         }
 
         agent_loop.stop();
+        api_server.abort();
+        webhook_tasks.iter().for_each(|task| task.abort());
+        if let Some(exporter) = otlp_exporter {
+            exporter.abort();
+        }
+        channel_watch.abort();
+        system_tap.abort();
+        traffic_counter.abort();
+        activity_tracker.abort();
+        if let Some(reactor) = system_event_reactor {
+            reactor.abort();
+        }
+        if let Some(bridge) = bridge {
+            bridge.abort();
+        }
+        heartbeat.stop().await;
+        subagents.shutdown().await;
+        cron_arc.lock().await.stop();
+        cron_scheduler.abort();
         channel_manager.stop_all().await;
     });
 }
 
+fn cmd_gateway_daemon(port: Option<u16>, verbose: bool) {
+    let daemon = Daemon::new(get_data_dir());
+    let mut args = Vec::new();
+    if let Some(port) = port {
+        args.extend(["--port".to_string(), port.to_string()]);
+    }
+    if verbose {
+        args.push("--verbose".to_string());
+    }
+    match daemon.spawn(&args) {
+        Ok(pid) => {
+            println!("{} Gateway started in the background (PID {})", LOGO, pid);
+            println!("  Logs: {}", daemon.log_path().display());
+            println!("  Stop it with: nanoclaw stop");
+        }
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// How long `stop` and `restart` wait for the gateway to exit.
+const STOP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+fn cmd_stop() {
+    let daemon = Daemon::new(get_data_dir());
+    let pid = match daemon.stop() {
+        Ok(pid) => pid,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    if daemon.wait_stopped(STOP_TIMEOUT) {
+        println!("Gateway stopped (PID {}).", pid);
+    } else {
+        eprintln!("Error: the gateway (PID {}) is still shutting down.", pid);
+        std::process::exit(1);
+    }
+}
+
+fn cmd_logs(
+    follow: bool,
+    level: Option<&str>,
+    session: Option<String>,
+    turn: Option<String>,
+    lines: usize,
+) {
+    let mut filter = match logs::LogFilter::new(level, session) {
+        Ok(filter) => filter,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    filter.turn = turn;
+    let dir = logs::logs_dir();
+    if logs::log_files(&dir).is_empty() && !follow {
+        println!("No log files in {} yet.", dir.display());
+        return;
+    }
+    for entry in logs::tail(&dir, &filter, lines) {
+        println!("{}", entry.format());
+    }
+    if follow {
+        if let Err(e) = logs::follow(&dir, &filter) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn cmd_restart(port: Option<u16>) {
+    let daemon = Daemon::new(get_data_dir());
+    if daemon.running().is_some() {
+        cmd_stop();
+    }
+    cmd_gateway_daemon(port, false);
+}
+
+fn cmd_service(action: ServiceAction) {
+    let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    let result = service::ServiceManager::detect().and_then(|manager| match action {
+        ServiceAction::Install => service::install(manager, &home).map(|path| {
+            println!("Installed {}", path.display());
+            println!("Start it with: nanoclaw service enable");
+        }),
+        ServiceAction::Uninstall => service::uninstall(manager, &home)
+            .map(|path| println!("Removed {}", path.display())),
+        ServiceAction::Enable => service::passthrough(manager, &home, service::Action::Enable),
+        ServiceAction::Disable => service::passthrough(manager, &home, service::Action::Disable),
+        ServiceAction::Status => service::passthrough(manager, &home, service::Action::Status),
+    });
+    if let Err(e) = result {
+        eprintln!("Error: {:#}", e);
+        std::process::exit(1);
+    }
+}
+
+// ============================================================================
+// Serve
+// ============================================================================
+
+fn cmd_serve(openai: bool, host: &str, port: u16) {
+    if !openai {
+        eprintln!("Error: nothing to serve; pass --openai for the OpenAI-compatible API.");
+        std::process::exit(1);
+    }
+
+    let config = load_config(None);
+    if config.get_api_key().is_none() && !config.agents.defaults.model.starts_with("bedrock/") {
+        eprintln!("Error: No API key configured.");
+        std::process::exit(1);
+    }
+
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+    runtime.block_on(async {
+        let (inbound_tx, inbound_rx) = mpsc::unbounded_channel::<InboundMessage>();
+        let (outbound_tx, mut outbound_rx) = mpsc::unbounded_channel::<OutboundMessage>();
+        // No channels run here; messages the agent sends to chats are dropped.
+        tokio::spawn(async move {
+            while let Some(msg) = outbound_rx.recv().await {
+                info!("Dropping message to {}:{} (no channels in serve mode)", msg.channel, msg.chat_id);
+            }
+        });
+
+        let cron_service = Arc::new(Mutex::new(open_cron_service(&config)));
+        let mut agent_loop =
+            create_agent_loop(&config, inbound_rx, outbound_tx, inbound_tx, cron_service);
+        let state = Arc::new(OpenAiState::new(
+            agent_loop.direct_sender(),
+            ApiAccess::from_config(&config.gateway),
+        ));
+
+        let tls = match api::tls::load(&config.gateway.tls).await {
+            Ok(tls) => tls,
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                std::process::exit(1);
+            }
+        };
+        println!(
+            "{} Serving the agent at {}://{}:{}/v1 (model \"nanoclaw\")",
+            LOGO,
+            api::tls::scheme(&config.gateway.tls),
+            host,
+            port
+        );
+        tokio::select! {
+            _ = agent_loop.run() => {
+                info!("Agent loop ended");
+            }
+            result = api::openai::serve(state, host, port, tls) => {
+                if let Err(e) = result {
+                    eprintln!("Error: {}", e);
+                }
+            }
+            _ = tokio::signal::ctrl_c() => {
+                println!("\nShutting down...");
+            }
+        }
+        agent_loop.stop();
+    });
+}
+
+// ============================================================================
+// Config
+// ============================================================================
+
+fn cmd_config_get(path: &str) {
+    match config_edit::get_value(&load_config(None), path) {
+        Ok(serde_json::Value::String(s)) => println!("{}", s),
+        Ok(value) => println!(
+            "{}",
+            serde_json::to_string_pretty(&value).unwrap_or_default()
+        ),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn cmd_config_set(path: &str, value: &str) {
+    // Edit the file as written, without environment overrides.
+    let config = load_config_file(None);
+    match config_edit::set_value(&config, path, value) {
+        Ok(updated) => {
+            save_config(&updated, None);
+            println!("Set {} in {}", path, get_config_path().display());
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn cmd_config_validate(strict: bool) {
+    let path = get_config_path();
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(_) => {
+            println!("No config file at {}; defaults are used.", path.display());
+            println!("Run `nanoclaw onboard` to create one.");
+            return;
+        }
+    };
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let issues = config_edit::validate(&contents, dir);
+    let exposed = exposed_secret_warnings(&load_config(None));
+    if issues.is_empty() && exposed.is_empty() {
+        println!("{} {} is valid", LOGO, path.display());
+        return;
+    }
+    for issue in &issues {
+        let level = if issue.error { "error" } else { "warning" };
+        println!("  {}: {}", level, issue.message);
+    }
+    for warning in &exposed {
+        println!("  warning: {}", warning);
+    }
+    if strict || issues.iter().any(|i| i.error) {
+        std::process::exit(1);
+    }
+}
+
+/// Warnings about how the config file's secrets are stored. Searches the
+/// workspace, so it runs only on `config validate` and gateway startup.
+fn exposed_secret_warnings(config: &Config) -> Vec<String> {
+    let path = get_config_path();
+    if !path.exists() {
+        return Vec::new();
+    }
+    secrets::check_hygiene(&path, config)
+}
+
+/// Read the config file as raw JSON, exiting on errors.
+fn read_raw_config(path: &Path) -> serde_json::Value {
+    let parsed = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|c| serde_json::from_str(&c).map_err(|e| e.to_string()));
+    match parsed {
+        Ok(raw) => raw,
+        Err(e) => {
+            eprintln!("Error: cannot read {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn write_raw_config(path: &Path, raw: &serde_json::Value) {
+    let json = serde_json::to_string_pretty(raw).expect("config serializes");
+    if let Err(e) = std::fs::write(path, json) {
+        eprintln!("Error: cannot write {}: {}", path.display(), e);
+        std::process::exit(1);
+    }
+}
+
+/// The config passphrase from the environment, or asked for on stdin
+/// (without echo when stdin is a terminal).
+fn read_passphrase(confirm: bool) -> String {
+    if let Ok(passphrase) = std::env::var(crypt::PASSPHRASE_VAR) {
+        return passphrase;
+    }
+    let ask = |prompt: &str| {
+        print!("{}", prompt);
+        io::stdout().flush().ok();
+        let mut line = String::new();
+        if io::stdin().is_terminal() {
+            let _echo = EchoOff::new();
+            io::stdin().read_line(&mut line).ok();
+            println!();
+        } else {
+            io::stdin().read_line(&mut line).ok();
+        }
+        line.trim_end_matches(['\r', '\n']).to_string()
+    };
+    let passphrase = ask("Config passphrase: ");
+    if passphrase.is_empty() {
+        eprintln!("Error: empty passphrase");
+        std::process::exit(1);
+    }
+    if confirm && ask("Repeat passphrase: ") != passphrase {
+        eprintln!("Error: passphrases do not match");
+        std::process::exit(1);
+    }
+    passphrase
+}
+
+/// Turns terminal echo off on stdin until dropped.
+struct EchoOff {
+    #[cfg(unix)]
+    saved: Option<libc::termios>,
+}
+
+impl EchoOff {
+    #[cfg(unix)]
+    fn new() -> Self {
+        // SAFETY: `termios` is plain data, and tcgetattr/tcsetattr only read
+        // or write the struct we pass for the stdin descriptor.
+        let saved = unsafe {
+            let mut term: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut term) != 0 {
+                return Self { saved: None };
+            }
+            let saved = term;
+            term.c_lflag &= !libc::ECHO;
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &term);
+            saved
+        };
+        Self { saved: Some(saved) }
+    }
+
+    #[cfg(not(unix))]
+    fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Drop for EchoOff {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(saved) = &self.saved {
+            // SAFETY: restores the settings read in `new`.
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, saved);
+            }
+        }
+    }
+}
+
+fn cmd_config_encrypt(machine: bool) {
+    let path = get_config_path();
+    let mut raw = read_raw_config(&path);
+    let source = if machine {
+        crypt::KeySource::Machine
+    } else {
+        crypt::KeySource::Passphrase(read_passphrase(true))
+    };
+    match crypt::encrypt_config(&mut raw, &source) {
+        Ok(0) => println!("No plain-text secrets in {}", path.display()),
+        Ok(n) => {
+            write_raw_config(&path, &raw);
+            println!("{} Encrypted {} secret(s) in {}", LOGO, n, path.display());
+            if !machine {
+                println!(
+                    "Set {} for nanoclaw to decrypt them when it starts.",
+                    crypt::PASSPHRASE_VAR
+                );
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn cmd_config_decrypt() {
+    let path = get_config_path();
+    let mut raw = read_raw_config(&path);
+    let text = raw.to_string();
+    let passphrase = text.contains("\"enc:pass:").then(|| read_passphrase(false));
+    match crypt::decrypt_config(&mut raw, passphrase) {
+        Ok(0) => println!("No encrypted secrets in {}", path.display()),
+        Ok(n) => {
+            write_raw_config(&path, &raw);
+            println!("{} Decrypted {} secret(s) in {}", LOGO, n, path.display());
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn cmd_config_schema(output: Option<&Path>) {
+    let schema = serde_json::to_string_pretty(&config_edit::json_schema())
+        .expect("schema serializes");
+    match output {
+        Some(path) => match std::fs::write(path, schema + "\n") {
+            Ok(()) => println!("{} Wrote config schema to {}", LOGO, path.display()),
+            Err(e) => {
+                eprintln!("Error: cannot write {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+        None => println!("{}", schema),
+    }
+}
+
+// ============================================================================
+// Secrets
+// ============================================================================
+
+fn cmd_secrets_set(name: &str, value: Option<String>, config_key: Option<String>) {
+    let value = match value {
+        Some(v) => v,
+        None => {
+            print!("Value for {}: ", name);
+            io::stdout().flush().ok();
+            let mut line = String::new();
+            io::stdin().read_line(&mut line).ok();
+            line.trim().to_string()
+        }
+    };
+    if value.is_empty() {
+        eprintln!("Error: empty secret");
+        std::process::exit(1);
+    }
+    if let Err(e) = secrets::set_secret(name, &value) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    println!("Stored '{}' in the OS keyring", name);
+
+    if let Some(path) = config_key {
+        let reference = format!("{}{}", secrets::KEYRING_PREFIX, name);
+        cmd_config_set(&path, &reference);
+    }
+}
+
+fn cmd_secrets_delete(name: &str) {
+    match secrets::delete_secret(name) {
+        Ok(()) => println!("Deleted '{}' from the OS keyring", name),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// ============================================================================
+// API tokens
+// ============================================================================
+
+fn cmd_api_token_add(name: &str, scope: &str) {
+    if Scope::parse(scope).is_none() {
+        eprintln!("Error: unknown scope '{}' (use read or send)", scope);
+        std::process::exit(1);
+    }
+    let mut config = load_config_file(None);
+    if config.gateway.api_tokens.iter().any(|t| t.name == name) {
+        eprintln!("Error: token '{}' exists; use `nanoclaw api-token rotate {}`", name, name);
+        std::process::exit(1);
+    }
+    let token = generate_token();
+    config.gateway.api_tokens.push(ApiTokenConfig {
+        name: name.to_string(),
+        token: token.clone(),
+        scope: scope.to_lowercase(),
+    });
+    save_config(&config, None);
+    println!("Created {} token '{}':\n{}", scope.to_lowercase(), name, token);
+    println!("Restart the gateway to use it.");
+}
+
+fn cmd_api_token_list() {
+    let config = load_config(None);
+    if config.gateway.api_tokens.is_empty() {
+        println!("No API tokens. Create one with `nanoclaw api-token add <name>`.");
+    }
+    for entry in &config.gateway.api_tokens {
+        let shown: String = entry.token.chars().take(7).collect();
+        println!("  {:<20} {:<5} {}...", entry.name, entry.scope, shown);
+    }
+    if !config.gateway.api_token.is_empty() {
+        println!("  {:<20} {:<5} (gateway.apiToken)", "(default)", "send");
+    }
+}
+
+fn cmd_api_token_rotate(name: &str) {
+    let mut config = load_config_file(None);
+    let Some(entry) = config.gateway.api_tokens.iter_mut().find(|t| t.name == name) else {
+        eprintln!("Error: no token named '{}'", name);
+        std::process::exit(1);
+    };
+    entry.token = generate_token();
+    let token = entry.token.clone();
+    save_config(&config, None);
+    println!("New value of '{}':\n{}", name, token);
+    println!("The old value stops working when the gateway restarts.");
+}
+
+fn cmd_api_token_remove(name: &str) {
+    let mut config = load_config_file(None);
+    let before = config.gateway.api_tokens.len();
+    config.gateway.api_tokens.retain(|t| t.name != name);
+    if config.gateway.api_tokens.len() == before {
+        eprintln!("Error: no token named '{}'", name);
+        std::process::exit(1);
+    }
+    save_config(&config, None);
+    println!("Removed token '{}'", name);
+}
+
 // ============================================================================
 // Status
 // ============================================================================
 
-fn cmd_status() {
+fn cmd_status(agents: bool, json: bool) {
+    if agents {
+        cmd_status_agents(json);
+        return;
+    }
+
     let config_path = get_config_path();
     let config = load_config(None);
     let workspace = config.workspace_path();
+    if json {
+        let report = _status_report(&config, &config_path, &workspace);
+        println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+        return;
+    }
 
     println!("{} nanoclaw Status\n", LOGO);
     println!(
@@ -417,55 +1965,710 @@ fn cmd_status() {
             if config.providers.openai.api_key.is_empty() { "not set" } else { "configured" }
         );
         println!(
-            "Gemini API: {}",
-            if config.providers.gemini.api_key.is_empty() { "not set" } else { "configured" }
+            "Gemini API: {}",
+            if config.providers.gemini.api_key.is_empty() { "not set" } else { "configured" }
+        );
+        let vllm_status = if let Some(ref base) = config.providers.vllm.api_base {
+            format!("configured ({})", base)
+        } else {
+            "not set".to_string()
+        };
+        println!("vLLM/Local: {}", vllm_status);
+    }
+
+    if !config.heartbeat.enabled {
+        println!("Heartbeat: off");
+        return;
+    }
+    use chrono::TimeZone;
+    let state = HeartbeatState::load(&heartbeat_state_path());
+    let at = |ms: i64| {
+        chrono::Local
+            .timestamp_millis_opt(ms)
+            .single()
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default()
+    };
+    match state.history.last() {
+        Some(beat) => {
+            let mut cost = format!("{} tokens", beat.tokens);
+            if let Some(usd) = beat.cost_usd {
+                cost.push_str(&format!(", ${:.4}", usd));
+            }
+            println!(
+                "Heartbeat: last run {} ({}, {})",
+                at(beat.at_ms),
+                beat.outcome.label(),
+                cost
+            );
+        }
+        None => println!("Heartbeat: no runs yet"),
+    }
+    match state.next_beat_ms {
+        Some(ms) if ms > chrono::Local::now().timestamp_millis() => {
+            println!("Next beat: {}", at(ms))
+        }
+        _ => println!("Next beat: not scheduled (is the gateway running?)"),
+    }
+}
+
+/// What `status --json` prints.
+fn _status_report(config: &Config, config_path: &Path, workspace: &Path) -> serde_json::Value {
+    let providers = &config.providers;
+    let mut report = serde_json::json!({
+        "config": {"path": config_path, "exists": config_path.exists()},
+        "workspace": {"path": workspace, "exists": workspace.exists()},
+        "model": config.agents.defaults.model,
+        "providers": {
+            "openrouter": !providers.openrouter.api_key.is_empty(),
+            "anthropic": !providers.anthropic.api_key.is_empty(),
+            "openai": !providers.openai.api_key.is_empty(),
+            "gemini": !providers.gemini.api_key.is_empty(),
+            "vllm": providers.vllm.api_base,
+        },
+        "heartbeat": {"enabled": config.heartbeat.enabled},
+    });
+    if config.heartbeat.enabled {
+        let state = HeartbeatState::load(&heartbeat_state_path());
+        let at = |ms: i64| {
+            chrono::DateTime::from_timestamp_millis(ms).map(|t| t.with_timezone(&chrono::Local))
+        };
+        report["heartbeat"]["lastRun"] = match state.history.last() {
+            Some(beat) => serde_json::json!({
+                "at": at(beat.at_ms),
+                "outcome": beat.outcome.label(),
+                "tokens": beat.tokens,
+                "costUsd": beat.cost_usd,
+            }),
+            None => serde_json::Value::Null,
+        };
+        let next = state
+            .next_beat_ms
+            .filter(|ms| *ms > chrono::Local::now().timestamp_millis());
+        report["heartbeat"]["nextBeat"] = serde_json::json!(next.and_then(at));
+    }
+    report
+}
+
+/// Where the heartbeat keeps its state and run history.
+fn heartbeat_state_path() -> PathBuf {
+    get_data_dir().join("heartbeat.json")
+}
+
+/// Where the gateway publishes its subagent activity.
+fn subagent_status_path() -> PathBuf {
+    get_data_dir().join("subagents.json")
+}
+
+fn cmd_status_agents(json: bool) {
+    let status = SubagentStatus::load(&subagent_status_path());
+    if json {
+        // Null when the gateway has recorded nothing.
+        println!("{}", serde_json::to_string_pretty(&status).unwrap_or_default());
+        return;
+    }
+    let Some(status) = status else {
+        println!("No subagent activity recorded (is the gateway running?).");
+        return;
+    };
+    let now = chrono::Local::now();
+    if let Some(updated) = status.updated_at {
+        println!("Subagents (as of {})\n", updated.format("%Y-%m-%d %H:%M:%S"));
+    }
+
+    println!("Running:");
+    if status.running.is_empty() {
+        println!("  (none)");
+    }
+    for info in &status.running {
+        println!(
+            "  {:<10} {:<30} {:>8}  {}:{}",
+            info.id,
+            info.label,
+            format_duration(now - info.started_at),
+            info.origin_channel,
+            info.origin_chat_id
+        );
+    }
+
+    println!("\nRecent:");
+    if status.recent.is_empty() {
+        println!("  (none)");
+    }
+    for run in &status.recent {
+        println!(
+            "  {:<10} {:<30} {:>8}  {:<12} {}",
+            run.info.id,
+            run.info.label,
+            format_duration(run.duration()),
+            run.status,
+            run.finished_at.format("%Y-%m-%d %H:%M")
+        );
+    }
+}
+
+fn cmd_audit(since: Option<&str>, session: Option<String>, kind: Option<&str>, lines: usize) {
+    let kind = kind.map(|k| match AuditKind::parse(k) {
+        Some(kind) => kind,
+        None => {
+            eprintln!("Error: unknown kind '{}' (file, command, message, http)", k);
+            std::process::exit(1);
+        }
+    });
+    let since = since.map(|s| match parse_since(s, chrono::Local::now()) {
+        Some(start) => start,
+        None => {
+            eprintln!("Error: --since takes a time ago like 7d or 24h, or a date like 2026-03-01");
+            std::process::exit(1);
+        }
+    });
+    let filter = AuditFilter {
+        since,
+        session,
+        kind,
+    };
+    let entries = read_entries(&audit::audit_path(), &filter, lines);
+    if entries.is_empty() {
+        println!("No matching entries in {}.", audit::audit_path().display());
+        return;
+    }
+    for entry in &entries {
+        println!(
+            "{} {} {:<20} {:<8} {:<16} {}",
+            entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            if entry.ok { " " } else { "✗" },
+            entry.session,
+            entry.kind.name(),
+            entry.action,
+            entry.target
+        );
+    }
+    if entries.iter().any(|e| !e.ok) {
+        println!("\n✗ failed");
+    }
+}
+
+fn cmd_usage(since: Option<&str>, by: &str) {
+    let Some(grouping) = UsageGrouping::parse(by) else {
+        eprintln!("Error: unknown grouping '{}' (model, session, channel)", by);
+        std::process::exit(1);
+    };
+    let start = since.map(|s| match parse_since(s, chrono::Local::now()) {
+        Some(start) => start,
+        None => {
+            eprintln!("Error: --since takes a time ago like 7d or 24h, or a date like 2026-03-01");
+            std::process::exit(1);
+        }
+    });
+    let records = read_records(&get_data_dir().join("usage.jsonl"));
+    let rows = usage_by(&records, start, grouping);
+    if rows.is_empty() {
+        let period = since.map(|s| format!(" since {}", s)).unwrap_or_default();
+        println!("No LLM calls recorded{}.", period);
+        return;
+    }
+
+    match start {
+        Some(start) => println!("Usage since {}\n", start.format("%Y-%m-%d %H:%M")),
+        None => println!("Usage of all recorded calls\n"),
+    }
+    let width = rows
+        .iter()
+        .map(|r| r.key.chars().count())
+        .max()
+        .unwrap_or(0)
+        .clamp(8, 40);
+    let header = grouping.name()[..1].to_uppercase() + &grouping.name()[1..];
+    println!(
+        "{:<width$} {:>7} {:>12} {:>12} {:>10}",
+        header, "Calls", "Input", "Output", "Cost"
+    );
+    println!("{}", "-".repeat(width + 45));
+    let mut total = UsageRow::default();
+    for row in &rows {
+        let key: String = row.key.chars().take(width).collect();
+        let cost = format!("${:.4}{}", row.cost_usd, if row.unpriced > 0 { "*" } else { "" });
+        println!(
+            "{:<width$} {:>7} {:>12} {:>12} {:>10}",
+            key, row.calls, row.prompt_tokens, row.completion_tokens, cost
+        );
+        total.calls += row.calls;
+        total.prompt_tokens += row.prompt_tokens;
+        total.completion_tokens += row.completion_tokens;
+        total.cost_usd += row.cost_usd;
+        total.unpriced += row.unpriced;
+    }
+    println!("{}", "-".repeat(width + 45));
+    println!(
+        "{:<width$} {:>7} {:>12} {:>12} {:>10}",
+        "Total",
+        total.calls,
+        total.prompt_tokens,
+        total.completion_tokens,
+        format!("${:.4}", total.cost_usd)
+    );
+    if total.unpriced > 0 {
+        println!(
+            "\n* {} calls used models without a known price and are not in the cost; \
+             set agents.costs.pricing to include them.",
+            total.unpriced
+        );
+    }
+}
+
+/// Render a duration compactly, e.g. "2m 05s".
+fn format_duration(d: chrono::Duration) -> String {
+    let secs = d.num_seconds().max(0);
+    match secs {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m {:02}s", s / 60, s % 60),
+        s => format!("{}h {:02}m", s / 3600, s % 3600 / 60),
+    }
+}
+
+// ============================================================================
+// Channels
+// ============================================================================
+
+fn cmd_channels_send(channel: &str, to: &str, text: &str) {
+    let config = load_config(None);
+    let Some(client) = GatewayClient::connect(&Daemon::new(get_data_dir()), &config.gateway) else {
+        eprintln!("Error: the gateway is not running; start it with `nanoclaw gateway`.");
+        std::process::exit(1);
+    };
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+    match runtime.block_on(client.send_to_channel(channel, to, text)) {
+        Ok(()) => println!("Queued for {}:{}", channel, to),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn cmd_dlq_list() {
+    let letters = DeadLetterStore::new(get_data_dir().join("dead_letters.json")).list();
+    if letters.is_empty() {
+        println!("No undelivered messages.");
+        return;
+    }
+
+    println!("Undelivered Messages\n");
+    println!(
+        "{:<10} {:<17} {:<25} {:<9} Error",
+        "ID", "Failed", "Chat", "Attempts"
+    );
+    println!("{}", "-".repeat(80));
+    for letter in &letters {
+        let chat = format!("{}:{}", letter.message.channel, letter.message.chat_id);
+        println!(
+            "{:<10} {:<17} {:<25} {:<9} {}",
+            letter.id,
+            letter.failed_at.format("%Y-%m-%d %H:%M"),
+            chat,
+            letter.attempts,
+            letter.error
+        );
+        let preview: String = letter.message.content.chars().take(70).collect();
+        println!("           {}", preview.replace('\n', " "));
+    }
+}
+
+fn cmd_dlq_retry(id: &str) {
+    let config = load_config(None);
+    let Some(client) = GatewayClient::connect(&Daemon::new(get_data_dir()), &config.gateway) else {
+        eprintln!("Error: the gateway is not running; start it with `nanoclaw gateway`.");
+        std::process::exit(1);
+    };
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+    match runtime.block_on(client.retry_dead_letter(id)) {
+        Ok(ids) if ids.is_empty() => println!("No undelivered messages."),
+        Ok(ids) => println!("Resending {}", ids.join(", ")),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn cmd_channels_status() {
+    let config = load_config(None);
+    println!("Channel Status\n");
+    println!(
+        "  WhatsApp: {} ({})",
+        if config.channels.whatsapp.enabled { "enabled" } else { "disabled" },
+        config.channels.whatsapp.bridge_url
+    );
+    let tg_info = if config.channels.telegram.token.is_empty() {
+        "not configured".to_string()
+    } else {
+        let t = &config.channels.telegram.token;
+        format!("token: {}...", &t[..t.len().min(10)])
+    };
+    println!(
+        "  Telegram: {} ({})",
+        if config.channels.telegram.enabled { "enabled" } else { "disabled" },
+        tg_info
+    );
+    println!(
+        "  Feishu: {}",
+        if config.channels.feishu.enabled { "enabled" } else { "disabled" }
+    );
+}
+
+// ============================================================================
+// Skills
+// ============================================================================
+
+/// Skills loader for the configured workspace, checking config requirements.
+fn skills_loader(config: &Config) -> SkillsLoader {
+    let mut loader = SkillsLoader::new(&config.workspace_path(), None);
+    loader.set_config(Arc::new(config.clone()));
+    loader
+}
+
+fn cmd_skills_list() {
+    let config = load_config(None);
+    let workspace = config.workspace_path();
+    let skills = skills_loader(&config).list_skills(false);
+    if skills.is_empty() {
+        println!("No skills. Install one with `nanoclaw skills install <git-url|path>`.");
+        return;
+    }
+
+    println!("Skills\n");
+    println!(
+        "{:<20} {:<10} {:<10} Status",
+        "Name", "Version", "Source"
+    );
+    println!("{}", "-".repeat(70));
+    let skills_dir = workspace.join("skills");
+    for skill in &skills {
+        let status = if skill.missing.is_empty() {
+            "available".to_string()
+        } else {
+            format!("missing {}", skill.missing_text())
+        };
+        println!(
+            "{:<20} {:<10} {:<10} {}",
+            skill.display_name(),
+            skill.meta.version.as_deref().unwrap_or("-"),
+            skill.source,
+            status
+        );
+        if skill.source == "workspace" {
+            if let Some(origin) = skills_install::origin_of(&skills_dir, &skill.name) {
+                let installed = origin
+                    .installed_at
+                    .map(|at| {
+                        let day = at.with_timezone(&chrono::Local).format("%Y-%m-%d");
+                        format!(", installed {}", day)
+                    })
+                    .unwrap_or_default();
+                println!("{:<20} from {}{}", "", origin.source, installed);
+            }
+        }
+    }
+}
+
+fn cmd_skills_install(source: &str, name: Option<&str>, force: bool) {
+    let config = load_config(None);
+    let skills_dir = config.workspace_path().join("skills");
+    let mut source = source.to_string();
+    let mut name = name.map(str::to_string);
+    if !skills_install::is_git_url(&source) && !Path::new(&source).exists() {
+        let index_url = &config.agents.skills.index_url;
+        let runtime = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+        let entry = runtime
+            .block_on(skills_index::fetch(index_url))
+            .and_then(|entries| {
+                skills_index::find(&entries, &source)
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("no skill named '{}' in the index", source))
+            });
+        match entry {
+            Ok(entry) => {
+                println!("Installing '{}' from {}", entry.name, entry.source);
+                name = name.or(Some(entry.name));
+                source = entry.source;
+            }
+            Err(e) => {
+                eprintln!("Error: {} is not a directory or git URL ({:#})", source, e);
+                std::process::exit(1);
+            }
+        }
+    }
+    match skills_install::install(&skills_dir, &source, name.as_deref(), force) {
+        Ok(installed) => {
+            let version = installed
+                .meta
+                .version
+                .map(|v| format!(" {}", v))
+                .unwrap_or_default();
+            println!(
+                "Installed skill '{}'{} to {}",
+                installed.name,
+                version,
+                installed.path.display()
+            );
+            let missing = installed.meta.requires.missing(Some(&config));
+            if !missing.is_empty() {
+                let missing: Vec<String> = missing.iter().map(|m| m.to_string()).collect();
+                println!(
+                    "  Unavailable until provided: {} (see `nanoclaw skills doctor`)",
+                    missing.join(", ")
+                );
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn cmd_skills_remove(name: &str) {
+    let skills_dir = load_config(None).workspace_path().join("skills");
+    match skills_install::remove(&skills_dir, name) {
+        Ok(()) => println!("Removed skill '{}'", name),
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn cmd_skills_update(name: Option<&str>, check: bool, yes: bool) {
+    let skills_dir = load_config(None).workspace_path().join("skills");
+    let names: Vec<String> = match name {
+        Some(name) => vec![name.to_string()],
+        None => {
+            let mut names: Vec<String> = std::fs::read_dir(&skills_dir)
+                .map(|entries| {
+                    entries
+                        .flatten()
+                        .map(|e| e.file_name().to_string_lossy().to_string())
+                        .filter(|n| skills_install::origin_of(&skills_dir, n).is_some())
+                        .collect()
+                })
+                .unwrap_or_default();
+            names.sort();
+            names
+        }
+    };
+    if names.is_empty() {
+        println!("No skills were installed from a source.");
+        return;
+    }
+
+    let mut failed = false;
+    for name in &names {
+        let update = match skills_install::check_update(&skills_dir, name) {
+            Ok(update) => update,
+            Err(e) => {
+                eprintln!("Error: {}: {:#}", name, e);
+                failed = true;
+                continue;
+            }
+        };
+        let version = |v: &Option<String>| v.clone().unwrap_or_else(|| "-".to_string());
+        if !update.changed {
+            println!("{}: up to date ({})", name, version(&update.installed_version));
+            continue;
+        }
+        println!(
+            "{}: {} -> {}",
+            name,
+            version(&update.installed_version),
+            version(&update.staged.meta.version)
+        );
+        let diff = update.diff();
+        if diff.is_empty() {
+            println!("  (SKILL.md is unchanged; other files changed)");
+        } else {
+            println!("{}", diff);
+        }
+        if check {
+            continue;
+        }
+        if !yes {
+            print!("Apply the update to {}? [y/N] ", name);
+            io::stdout().flush().ok();
+            let mut input = String::new();
+            io::stdin().read_line(&mut input).ok();
+            if !input.trim().eq_ignore_ascii_case("y") {
+                println!("Skipped {}", name);
+                continue;
+            }
+        }
+        match update.apply(&skills_dir) {
+            Ok(installed) => println!("Updated skill '{}'", installed.name),
+            Err(e) => {
+                eprintln!("Error: {}: {:#}", name, e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+fn cmd_skills_doctor() {
+    let config = load_config(None);
+    let skills = skills_loader(&config).list_skills(false);
+    if skills.is_empty() {
+        println!("No skills.");
+        return;
+    }
+
+    let mut available = 0;
+    for skill in &skills {
+        let content = std::fs::read_to_string(&skill.path).unwrap_or_default();
+        if let Err(e) = SkillMeta::try_parse(&content) {
+            println!("{} ({}): invalid SKILL.md: {:#}", skill.name, skill.source, e);
+            continue;
+        }
+        if skill.missing.is_empty() {
+            available += 1;
+            println!("{} ({}): ok", skill.display_name(), skill.source);
+            continue;
+        }
+        println!(
+            "{} ({}): missing {}",
+            skill.display_name(),
+            skill.source,
+            skill.missing_text()
         );
-        let vllm_status = if let Some(ref base) = config.providers.vllm.api_base {
-            format!("configured ({})", base)
+        for missing in &skill.missing {
+            for fix in skills_install::suggest_fix(&skill.meta, missing, command_exists) {
+                println!("    {}", fix);
+            }
+        }
+    }
+    println!("\n{} of {} skills available.", available, skills.len());
+}
+
+fn cmd_skills_search(query: &str) {
+    let config = load_config(None);
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+    let entries = match runtime.block_on(skills_index::fetch(&config.agents.skills.index_url)) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    let found = skills_index::search(&entries, query);
+    if found.is_empty() {
+        println!("No skills match '{}'.", query);
+        return;
+    }
+
+    let skills_dir = config.workspace_path().join("skills");
+    println!("{:<20} {:<10} Description", "Name", "Version");
+    println!("{}", "-".repeat(70));
+    for entry in found {
+        let installed = if skills_dir.join(&entry.name).join("SKILL.md").is_file() {
+            " (installed)"
         } else {
-            "not set".to_string()
+            ""
         };
-        println!("vLLM/Local: {}", vllm_status);
+        println!(
+            "{:<20} {:<10} {}{}",
+            entry.name,
+            entry.version.as_deref().unwrap_or("-"),
+            entry.description,
+            installed
+        );
+    }
+    println!("\nInstall one with `nanoclaw skills install <name>`.");
+}
+
+fn cmd_skills_test(name: &str) {
+    let config = load_config(None);
+    let skills = skills_loader(&config).list_skills(false);
+    let Some(skill) = skills.iter().find(|s| s.name == name) else {
+        eprintln!("Error: no skill named '{}'", name);
+        std::process::exit(1);
+    };
+    let skill_dir = Path::new(&skill.path).parent().unwrap_or(Path::new("."));
+
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+    let outcomes = match runtime.block_on(skill_test::run(name, skill_dir, &config)) {
+        Ok(outcomes) => outcomes,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    let passed = outcomes.iter().filter(|o| o.passed()).count();
+    for outcome in &outcomes {
+        if outcome.passed() {
+            println!("{}: ok", outcome.name);
+            continue;
+        }
+        println!("{}: FAILED", outcome.name);
+        for failure in &outcome.failures {
+            println!("    {}", failure);
+        }
+    }
+    println!("\n{} of {} scenarios passed.", passed, outcomes.len());
+    if passed < outcomes.len() {
+        std::process::exit(1);
     }
 }
 
 // ============================================================================
-// Channels
+// Sessions
 // ============================================================================
 
-fn cmd_channels_status() {
+fn cmd_sessions_list(json: bool) {
     let config = load_config(None);
-    println!("Channel Status\n");
-    println!(
-        "  WhatsApp: {} ({})",
-        if config.channels.whatsapp.enabled { "enabled" } else { "disabled" },
-        config.channels.whatsapp.bridge_url
-    );
-    let tg_info = if config.channels.telegram.token.is_empty() {
-        "not configured".to_string()
-    } else {
-        let t = &config.channels.telegram.token;
-        format!("token: {}...", &t[..t.len().min(10)])
-    };
-    println!(
-        "  Telegram: {} ({})",
-        if config.channels.telegram.enabled { "enabled" } else { "disabled" },
-        tg_info
-    );
-    println!(
-        "  Feishu: {}",
-        if config.channels.feishu.enabled { "enabled" } else { "disabled" }
-    );
+    let mut sessions = SessionManager::new(&config.workspace_path()).list_sessions();
+    sessions.sort_by(|a, b| b["updated_at"].as_str().cmp(&a["updated_at"].as_str()));
+    if json {
+        let sessions: Vec<serde_json::Value> = sessions
+            .iter()
+            .map(|s| {
+                serde_json::json!({
+                    "key": s["key"],
+                    "createdAt": s["created_at"],
+                    "updatedAt": s["updated_at"],
+                    "path": s["path"],
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&sessions).unwrap_or_default());
+        return;
+    }
+    if sessions.is_empty() {
+        println!("No sessions.");
+        return;
+    }
+    println!("{:<40} Updated", "Session");
+    println!("{}", "-".repeat(60));
+    for session in &sessions {
+        let updated = session["updated_at"]
+            .as_str()
+            .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+            .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        println!("{:<40} {}", session["key"].as_str().unwrap_or_default(), updated);
+    }
 }
 
 // ============================================================================
 // Cron
 // ============================================================================
 
-fn cmd_cron_list(include_all: bool) {
-    let store_path = get_data_dir().join("cron").join("jobs.json");
-    let service = CronService::new(store_path);
+fn cmd_cron_list(include_all: bool, json: bool) {
+    let service = open_cron_service(&load_config(None));
     let jobs = service.list_jobs(include_all);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&jobs).unwrap_or_default());
+        return;
+    }
 
     if jobs.is_empty() {
         println!("No scheduled jobs.");
@@ -474,15 +2677,18 @@ fn cmd_cron_list(include_all: bool) {
 
     println!("Scheduled Jobs\n");
     println!(
-        "{:<10} {:<20} {:<15} {:<10} {}",
-        "ID", "Name", "Schedule", "Status", "Next Run"
+        "{:<10} {:<20} {:<15} {:<10} Next Run",
+        "ID", "Name", "Schedule", "Status"
     );
     println!("{}", "-".repeat(70));
 
     for job in &jobs {
         let sched = match job.schedule.kind.as_str() {
             "every" => format!("every {}s", job.schedule.every_ms.unwrap_or(0) / 1000),
-            "cron" => job.schedule.expr.clone().unwrap_or_default(),
+            "cron" => match job.schedule.tz.as_deref() {
+                Some(tz) => format!("{} ({})", job.schedule.expr.as_deref().unwrap_or(""), tz),
+                None => job.schedule.expr.clone().unwrap_or_default(),
+            },
             _ => "one-time".to_string(),
         };
         let status = if job.enabled { "enabled" } else { "disabled" };
@@ -490,8 +2696,12 @@ fn cmd_cron_list(include_all: bool) {
             .state
             .next_run_at_ms
             .map(|ms| {
-                chrono::DateTime::from_timestamp(ms / 1000, 0)
-                    .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+                chrono::DateTime::from_timestamp_millis(ms)
+                    .map(|dt| {
+                        dt.with_timezone(&chrono::Local)
+                            .format("%Y-%m-%d %H:%M")
+                            .to_string()
+                    })
                     .unwrap_or_default()
             })
             .unwrap_or_default();
@@ -502,49 +2712,236 @@ fn cmd_cron_list(include_all: bool) {
     }
 }
 
-fn cmd_cron_add(
-    name: String,
-    message: String,
+/// Build a schedule from the mutually exclusive `--every` / `--cron` / `--at`
+/// flags. Returns `Ok(None)` when none of them was given.
+fn schedule_from_flags(
     every: Option<u64>,
     cron_expr: Option<String>,
-    deliver: bool,
-    to: Option<String>,
-    channel: Option<String>,
-) {
+    at: Option<String>,
+    tz: Option<String>,
+) -> Result<Option<CronSchedule>, String> {
     let schedule = if let Some(secs) = every {
         CronSchedule {
             kind: "every".to_string(),
-            every_ms: Some((secs * 1000) as i64),
+            every_ms: Some(_secs_to_ms(secs, "every")?),
             ..Default::default()
         }
     } else if let Some(expr) = cron_expr {
         CronSchedule {
             kind: "cron".to_string(),
             expr: Some(expr),
+            tz,
+            ..Default::default()
+        }
+    } else if let Some(when) = at {
+        let dt = parse_natural_time(&when, chrono::Local::now()).map_err(|e| e.to_string())?;
+        CronSchedule {
+            kind: "at".to_string(),
+            at_ms: Some(dt.timestamp_millis()),
             ..Default::default()
         }
     } else {
-        eprintln!("Error: Must specify --every or --cron");
+        return Ok(None);
+    };
+    validate_schedule(&schedule)?;
+    Ok(Some(schedule))
+}
+
+/// Convert a seconds flag to milliseconds, rejecting values that overflow.
+fn _secs_to_ms(secs: u64, flag: &str) -> Result<i64, String> {
+    i64::try_from(secs.saturating_mul(1000)).map_err(|_| format!("--{} is too large", flag))
+}
+
+/// Unwrap a flag conversion, exiting with its error.
+fn _exit_on_err<T>(result: Result<T, String>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
         std::process::exit(1);
+    })
+}
+
+/// Parse an `--until` value into ms since epoch, exiting on error.
+fn _parse_until(until: Option<&str>) -> Option<i64> {
+    let until = until?;
+    match parse_natural_time(until, chrono::Local::now()) {
+        Ok(dt) => Some(dt.timestamp_millis()),
+        Err(e) => {
+            eprintln!("Error: invalid --until: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn cmd_cron_add(args: Box<CronAddArgs>) {
+    let args = *args;
+    let until_ms = _parse_until(args.until.as_deref());
+    let mut schedule = match schedule_from_flags(args.every, args.cron, args.at, args.tz) {
+        Ok(Some(schedule)) => schedule,
+        Ok(None) => {
+            eprintln!("Error: Must specify --every, --cron or --at");
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
     };
 
-    let store_path = get_data_dir().join("cron").join("jobs.json");
-    let mut service = CronService::new(store_path);
+    schedule.jitter_ms = args
+        .jitter
+        .filter(|&j| j > 0)
+        .map(|j| _exit_on_err(_secs_to_ms(j, "jitter")));
+
+    // One-shot jobs clean themselves up after running.
+    let one_shot = schedule.kind == "at";
+
+    let mut service = open_cron_service(&load_config(None));
+    if let Err(e) = service.check_min_interval(&schedule) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
     let job = service.add_job(
-        &name,
+        &args.name,
         schedule,
-        &message,
-        deliver,
-        channel.as_deref(),
-        to.as_deref(),
-        false,
+        &args.message,
+        args.deliver,
+        args.channel.as_deref(),
+        args.to.as_deref(),
+        one_shot || args.delete_when_done,
     );
+    let retry = CronRetryPolicy {
+        max_attempts: args.max_attempts.max(1),
+        backoff_ms: _exit_on_err(_secs_to_ms(args.backoff, "backoff")),
+    };
+    let job = service
+        .update_job(&job.id, |j| {
+            j.retry = retry;
+            j.overlap = args.overlap;
+            j.payload.save_output = args.save_output;
+            j.max_runs = args.max_runs.filter(|&n| n > 0);
+            j.until_ms = until_ms;
+        })
+        .unwrap_or(job);
     println!("  Added job '{}' ({})", job.name, job.id);
+    print_next_run(&job);
+}
+
+fn print_next_run(job: &CronJob) {
+    if let Some(next) = job
+        .state
+        .next_run_at_ms
+        .and_then(chrono::DateTime::from_timestamp_millis)
+    {
+        println!(
+            "  Next run: {}",
+            next.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")
+        );
+    }
+    if let Some(max) = job.max_runs {
+        println!("  Runs: {}/{}", job.state.run_count, max);
+    }
+    if let Some(until) = job.until_ms.and_then(chrono::DateTime::from_timestamp_millis) {
+        println!(
+            "  Until: {}",
+            until.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")
+        );
+    }
+}
+
+fn cmd_cron_edit(args: Box<CronEditArgs>) {
+    let args = *args;
+    let until_ms = _parse_until(args.until.as_deref());
+    let mut service = open_cron_service(&load_config(None));
+    let Some(existing) = service.get_job(&args.job_id) else {
+        eprintln!("Job {} not found", args.job_id);
+        std::process::exit(1);
+    };
+
+    // A bare --tz re-targets the existing cron expression.
+    let (cron_expr, tz) = match (args.cron, args.tz) {
+        (None, Some(tz)) if existing.schedule.kind == "cron" => {
+            (existing.schedule.expr.clone(), Some(tz))
+        }
+        (None, Some(_)) => {
+            eprintln!("Error: --tz only applies to cron-expression schedules");
+            std::process::exit(1);
+        }
+        (cron, tz) => (cron, tz),
+    };
+    let schedule = match schedule_from_flags(args.every, cron_expr, args.at, tz) {
+        Ok(schedule) => schedule,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(ref schedule) = schedule {
+        if let Err(e) = service.check_min_interval(schedule) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+    let jitter_ms = args.jitter.map(|j| _exit_on_err(_secs_to_ms(j, "jitter")));
+    let backoff_ms = args.backoff.map(|b| _exit_on_err(_secs_to_ms(b, "backoff")));
+
+    let job = service.update_job(&args.job_id, |job| {
+        if let Some(name) = args.name {
+            job.name = name;
+        }
+        if let Some(message) = args.message {
+            job.payload.message = message;
+        }
+        if let Some(schedule) = schedule {
+            job.set_schedule(schedule);
+        }
+        if let Some(jitter) = jitter_ms {
+            job.schedule.jitter_ms = (jitter > 0).then_some(jitter);
+        }
+        if args.deliver {
+            job.payload.deliver = true;
+        }
+        if args.no_deliver {
+            job.payload.deliver = false;
+        }
+        if let Some(to) = args.to {
+            job.payload.to = Some(to);
+        }
+        if let Some(channel) = args.channel {
+            job.payload.channel = Some(channel);
+        }
+        if args.save_output {
+            job.payload.save_output = true;
+        }
+        if args.no_save_output {
+            job.payload.save_output = false;
+        }
+        if let Some(max_attempts) = args.max_attempts {
+            job.retry.max_attempts = max_attempts.max(1);
+        }
+        if let Some(backoff_ms) = backoff_ms {
+            job.retry.backoff_ms = backoff_ms;
+        }
+        if let Some(overlap) = args.overlap {
+            job.overlap = overlap;
+        }
+        if let Some(max_runs) = args.max_runs {
+            job.max_runs = (max_runs > 0).then_some(max_runs);
+        }
+        if until_ms.is_some() || args.no_until {
+            job.until_ms = until_ms;
+        }
+    });
+
+    let Some(job) = job else {
+        eprintln!("Job {} not found", args.job_id);
+        std::process::exit(1);
+    };
+    println!("  Updated job '{}' ({})", job.name, job.id);
+    print_next_run(&job);
 }
 
 fn cmd_cron_remove(job_id: String) {
-    let store_path = get_data_dir().join("cron").join("jobs.json");
-    let mut service = CronService::new(store_path);
+    let mut service = open_cron_service(&load_config(None));
     if service.remove_job(&job_id) {
         println!("  Removed job {}", job_id);
     } else {
@@ -552,9 +2949,134 @@ fn cmd_cron_remove(job_id: String) {
     }
 }
 
+fn cmd_cron_run(job_id: String) {
+    let config = load_config(None);
+
+    // A running gateway runs the job itself, delivering its output.
+    if let Some(client) = GatewayClient::connect(&Daemon::new(get_data_dir()), &config.gateway) {
+        let runtime = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+        println!("{} Running job {} in the gateway...", LOGO, job_id);
+        match runtime.block_on(client.run_job(&job_id)) {
+            Ok(response) => println!("\n{} {}", LOGO, response),
+            Err(e) => {
+                eprintln!("Error: job failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let model = config.agents.defaults.model.clone();
+    if config.get_api_key().is_none() && !model.starts_with("bedrock/") {
+        eprintln!("Error: No API key configured.");
+        std::process::exit(1);
+    }
+
+    let service = open_cron_service(&config);
+    let Some(job) = service.get_job(&job_id) else {
+        eprintln!("Job {} not found", job_id);
+        std::process::exit(1);
+    };
+    if job.payload.deliver {
+        println!("  Note: delivery to channels requires a running gateway; printing the response instead.");
+    }
+
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+
+    runtime.block_on(async {
+        let (inbound_tx, inbound_rx) = mpsc::unbounded_channel::<InboundMessage>();
+        let (outbound_tx, _outbound_rx) = mpsc::unbounded_channel::<OutboundMessage>();
+
+        let cron_arc = Arc::new(Mutex::new(service));
+        let mut agent_loop = create_agent_loop(
+            &config,
+            inbound_rx,
+            outbound_tx.clone(),
+            inbound_tx,
+            cron_arc.clone(),
+        );
+        cron_arc
+            .lock()
+            .await
+            .set_on_job(cron_job_callback(
+                agent_loop.direct_sender(),
+                outbound_tx,
+                config.workspace_path(),
+                None,
+                None,
+            ));
+
+        println!("{} Running job '{}' ({})...", LOGO, job.name, job.id);
+        let result = tokio::select! {
+            result = CronService::run_job_now(&cron_arc, &job_id) => result,
+            _ = agent_loop.run() => None,
+        };
+
+        match result {
+            Some(Ok(response)) => println!("\n{} {}", LOGO, response),
+            Some(Err(e)) => {
+                eprintln!("Error: job failed: {}", e);
+                std::process::exit(1);
+            }
+            None => {
+                eprintln!("Error: job did not complete");
+                std::process::exit(1);
+            }
+        }
+    });
+}
+
+fn cmd_cron_history(job_id: String) {
+    let service = open_cron_service(&load_config(None));
+    let Some(job) = service.get_job(&job_id) else {
+        eprintln!("Job {} not found", job_id);
+        std::process::exit(1);
+    };
+
+    if job.state.history.is_empty() {
+        println!("No runs recorded for '{}' ({}).", job.name, job.id);
+        return;
+    }
+
+    println!("Run history for '{}' ({})\n", job.name, job.id);
+    println!("{:<20} {:<10} {:<10} Output", "Started", "Duration", "Status");
+    println!("{}", "-".repeat(70));
+
+    for run in job.state.history.iter().rev() {
+        let started = chrono::DateTime::from_timestamp_millis(run.started_at_ms)
+            .map(|dt| {
+                dt.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        let duration = format!("{:.1}s", run.duration_ms as f64 / 1000.0);
+        let mut status = run.status.clone();
+        if run.attempts > 1 {
+            status.push_str(&format!(" x{}", run.attempts));
+        }
+        if run.manual {
+            status.push('*');
+        }
+        let detail = run
+            .error
+            .as_deref()
+            .or(run.output.as_deref())
+            .unwrap_or("")
+            .replace('\n', " ");
+        let detail: String = detail.chars().take(60).collect();
+        println!("{:<20} {:<10} {:<10} {}", started, duration, status, detail);
+    }
+    if job.state.history.iter().any(|r| r.attempts > 1) {
+        println!("\nxN = number of attempts");
+    }
+    if job.state.history.iter().any(|r| r.manual) {
+        println!("* manual run");
+    }
+}
+
 fn cmd_cron_enable(job_id: String, disable: bool) {
-    let store_path = get_data_dir().join("cron").join("jobs.json");
-    let mut service = CronService::new(store_path);
+    let mut service = open_cron_service(&load_config(None));
     if let Some(job) = service.enable_job(&job_id, !disable) {
         let status = if disable { "disabled" } else { "enabled" };
         println!("  Job '{}' {}", job.name, status);
@@ -563,17 +3085,293 @@ fn cmd_cron_enable(job_id: String, disable: bool) {
     }
 }
 
+fn cmd_cron_export(output: Option<PathBuf>) {
+    let service = open_cron_service(&load_config(None));
+    let yaml = match service.export_yaml() {
+        Ok(yaml) => yaml,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    match output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, yaml) {
+                eprintln!("Error: failed to write {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+            let count = service.list_jobs(true).len();
+            println!("  Exported {} job(s) to {}", count, path.display());
+        }
+        None => print!("{}", yaml),
+    }
+}
+
+fn cmd_cron_import(file: PathBuf, new_ids: bool, on_conflict: String) {
+    let jobs = match std::fs::read_to_string(&file)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| parse_jobs_yaml(&content))
+    {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            eprintln!("Error: failed to read {}: {}", file.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut service = open_cron_service(&load_config(None));
+    let report = service.import_jobs(jobs, new_ids, &on_conflict);
+    println!(
+        "  Imported {} job(s), replaced {}, skipped {}",
+        report.added.len(),
+        report.replaced.len(),
+        report.skipped.len()
+    );
+    for reason in &report.skipped {
+        println!("    skipped {}", reason);
+    }
+}
+
 // ============================================================================
 // Helpers
 // ============================================================================
 
-fn create_provider(config: &Config) -> Arc<dyn LLMProvider> {
-    let api_key = config.get_api_key().unwrap_or_default();
-    let api_base = config.get_api_base();
-    let model = &config.agents.defaults.model;
-    Arc::new(OpenAICompatProvider::new(
-        &api_key,
-        api_base.as_deref(),
-        Some(model.as_str()),
-    ))
+/// Open the cron store, applying the configured default timezone.
+fn open_cron_service(config: &Config) -> CronService {
+    let mut service = CronService::new(get_data_dir().join("cron").join("jobs.json"));
+    service.set_default_timezone(Some(config.gateway.timezone.clone()));
+    service.set_max_concurrent(config.cron.max_concurrent_runs);
+    service.set_min_interval(config.cron.min_interval_secs);
+    service
+}
+
+/// Build the agent loop from config, wired to the given bus channels.
+/// Write the enabled bundled skills to every workspace.
+fn sync_builtin_skills(config: &Config) {
+    let workspaces = std::iter::once(config.workspace_path())
+        .chain(config.agents.workspaces.iter().map(|ws| ws.expanded_path()));
+    for workspace in workspaces {
+        let dir = workspace.join("builtin_skills");
+        if let Err(e) = builtin_skills::sync(&dir, &config.agents.skills.builtin) {
+            tracing::warn!("Cannot write bundled skills: {:#}", e);
+        }
+    }
+}
+
+fn create_agent_loop(
+    config: &Config,
+    inbound_rx: mpsc::UnboundedReceiver<InboundMessage>,
+    outbound_tx: mpsc::UnboundedSender<OutboundMessage>,
+    inbound_tx: mpsc::UnboundedSender<InboundMessage>,
+    cron_service: Arc<Mutex<CronService>>,
+) -> AgentLoop {
+    // Keep the config's secrets out of tool results, logs and transcripts.
+    redact::install(Redactor::new(config));
+    sync_builtin_skills(config);
+    AgentLoopBuilder::from_config(config)
+        .bus(inbound_rx, outbound_tx, inbound_tx)
+        .cron_service(cron_service)
+        .build()
+}
+
+/// Build the callback that runs cron jobs through the agent loop and, when
+/// requested, delivers the response to the job's channel. Each run is
+/// published on `system_events`, if given; with `receipts`, a run waits for
+/// the channel to confirm delivery and fails if it could not.
+fn cron_job_callback(
+    direct_tx: mpsc::UnboundedSender<DirectRequest>,
+    outbound_tx: mpsc::UnboundedSender<OutboundMessage>,
+    workspace: PathBuf,
+    system_events: Option<broadcast::Sender<SystemEvent>>,
+    receipts: Option<Arc<DeliveryReceipts>>,
+) -> JobCallback {
+    Arc::new(move |job: CronJob| {
+        let direct_tx = direct_tx.clone();
+        let outbound_tx = outbound_tx.clone();
+        let receipts = receipts.clone();
+        let workspace = workspace.clone();
+        if let Some(events) = &system_events {
+            let _ = events.send(SystemEvent::CronFired {
+                job_id: job.id.clone(),
+                name: job.name.clone(),
+            });
+        }
+        Box::pin(async move {
+            let channel = job.payload.channel.clone().unwrap_or_else(|| "cli".to_string());
+            let chat_id = job.payload.to.clone().unwrap_or_else(|| "direct".to_string());
+
+            let mut msg = InboundMessage::new(&channel, "cron", &chat_id, &job.payload.message);
+            msg.priority = Priority::Low;
+            msg.metadata.insert(
+                "session_key".to_string(),
+                serde_json::json!(format!("cron:{}", job.id)),
+            );
+
+            let (reply_tx, reply_rx) = oneshot::channel();
+            direct_tx
+                .send(DirectRequest { msg, reply_tx, deltas: None })
+                .map_err(|_| anyhow::anyhow!("Agent loop is not running"))?;
+            let response = reply_rx
+                .await
+                .map_err(|_| anyhow::anyhow!("Agent loop dropped the request"))??;
+
+            if job.payload.save_output {
+                let path = write_run_output(&workspace, &job, &response, chrono::Local::now())
+                    .map_err(|e| anyhow::anyhow!("Failed to save cron output: {}", e))?;
+                info!("Cron: saved output of '{}' to {}", job.name, path.display());
+            }
+            if job.payload.deliver && job.payload.to.is_some() && !response.is_empty() {
+                let mut out = OutboundMessage::new(&channel, &chat_id, &response);
+                let receipt = receipts.as_ref().map(|r| r.request(&mut out));
+                outbound_tx
+                    .send(out)
+                    .map_err(|e| anyhow::anyhow!("Failed to deliver cron output: {}", e))?;
+                // A failed send fails the run, so the job's retry policy
+                // covers delivery too.
+                if let Some(receipt) = receipt {
+                    receipt
+                        .await
+                        .map_err(|_| anyhow::anyhow!("Delivery of cron output was not confirmed"))?
+                        .map_err(|e| anyhow::anyhow!("Failed to deliver cron output: {}", e))?;
+                }
+            }
+            Ok(response)
+        })
+    })
+}
+
+/// What the agent answers when a system event needs no message.
+const NOTHING_TO_SAY: &str = "NOTHING_TO_SAY";
+
+/// Tell the agent about the system events listed in `systemEvents.react`,
+/// at low priority in the `system` session, and send its reaction to the
+/// configured chat.
+fn react_to_system_events(
+    config: &Config,
+    mut events: broadcast::Receiver<SystemEvent>,
+    direct_tx: mpsc::UnboundedSender<DirectRequest>,
+    outbound_tx: mpsc::UnboundedSender<OutboundMessage>,
+) -> tokio::task::JoinHandle<()> {
+    let settings = config.system_events.clone();
+    let deliver = !settings.channel.is_empty() && !settings.chat_id.is_empty();
+    let (channel, chat_id) = if deliver {
+        (settings.channel.clone(), settings.chat_id.clone())
+    } else {
+        ("cli".to_string(), "system".to_string())
+    };
+    tokio::spawn(async move {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    tracing::warn!("The agent missed {} system events", n);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            if !settings.react.iter().any(|kind| kind == event.kind()) {
+                continue;
+            }
+            let prompt = format!(
+                "[System event] {}\n\nIf the user should know about this, write them a short \
+                 message. Otherwise reply with just: {}",
+                event.describe(),
+                NOTHING_TO_SAY
+            );
+            let mut msg = InboundMessage::new(&channel, "system", &chat_id, prompt);
+            msg.priority = Priority::Low;
+            msg.metadata
+                .insert("session_key".to_string(), serde_json::json!("system"));
+            msg.metadata
+                .insert("is_system".to_string(), serde_json::json!(true));
+            let (reply_tx, reply_rx) = oneshot::channel();
+            if direct_tx.send(DirectRequest { msg, reply_tx, deltas: None }).is_err() {
+                break;
+            }
+            // Wait for the reply without holding up the next event.
+            let outbound_tx = outbound_tx.clone();
+            let (channel, chat_id) = (channel.clone(), chat_id.clone());
+            tokio::spawn(async move {
+                match reply_rx.await {
+                    Ok(Ok(reply)) if reply.contains(NOTHING_TO_SAY) || reply.trim().is_empty() => {}
+                    Ok(Ok(reply)) if deliver => {
+                        let _ = outbound_tx.send(OutboundMessage::new(&channel, &chat_id, &reply));
+                    }
+                    Ok(Ok(reply)) => info!("Reaction to {}: {}", event.kind(), reply),
+                    Ok(Err(e)) => tracing::warn!("The agent failed to react to {}: {}", event.kind(), e),
+                    Err(_) => {}
+                }
+            });
+        }
+    })
+}
+
+/// Heartbeat that runs its agenda through the agent loop and may message
+/// the configured chat.
+fn create_heartbeat(
+    config: &Config,
+    direct_tx: mpsc::UnboundedSender<DirectRequest>,
+    outbound_tx: mpsc::UnboundedSender<OutboundMessage>,
+    cron_service: Arc<Mutex<CronService>>,
+    activity: Arc<UserActivity>,
+) -> HeartbeatService {
+    let hb = &config.heartbeat;
+    let (channel, chat_id) = if hb.channel.is_empty() || hb.chat_id.is_empty() {
+        ("cli".to_string(), "heartbeat".to_string())
+    } else {
+        (hb.channel.clone(), hb.chat_id.clone())
+    };
+
+    let callback: HeartbeatCallback = {
+        let (channel, chat_id) = (channel.clone(), chat_id.clone());
+        Arc::new(move |prompt: String| {
+            let direct_tx = direct_tx.clone();
+            let mut msg = InboundMessage::new(&channel, "heartbeat", &chat_id, prompt);
+            msg.priority = Priority::Low;
+            msg.metadata
+                .insert("session_key".to_string(), serde_json::json!(HEARTBEAT_SESSION));
+            Box::pin(async move {
+                let (reply_tx, reply_rx) = oneshot::channel();
+                direct_tx.send(DirectRequest { msg, reply_tx, deltas: None }).ok()?;
+                reply_rx.await.ok()?.ok()
+            })
+        })
+    };
+
+    let mut heartbeat = HeartbeatService::new(
+        config.workspace_path(),
+        Some(callback),
+        hb.interval_secs,
+        hb.enabled,
+    );
+    heartbeat.set_cron_service(cron_service);
+    heartbeat.set_state_file(heartbeat_state_path());
+    heartbeat.set_usage_file(get_data_dir().join("usage.jsonl"));
+    if hb.maintenance.enabled {
+        heartbeat.set_maintenance(Maintenance::new(
+            hb.maintenance.clone(),
+            config.workspace_path(),
+            get_data_dir(),
+        ));
+    }
+    heartbeat.set_prompt(&hb.prompt);
+    if hb.adaptive {
+        heartbeat.set_adaptive(AdaptivePace {
+            active_interval_s: hb.active_interval_secs,
+            max_interval_s: hb.max_interval_secs,
+            idle_after_s: hb.idle_after_secs,
+            activity,
+        });
+    }
+    if channel != "cli" {
+        heartbeat.set_proactive_target(
+            ProactiveTarget {
+                channel,
+                chat_id,
+                outbound_tx,
+            },
+            hb.max_proactive_per_day,
+        );
+    }
+    heartbeat
 }
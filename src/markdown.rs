@@ -0,0 +1,157 @@
+//! Markdown replies rendered for the terminal.
+//!
+//! `nanoclaw agent` prints replies with headings, emphasis, lists, tables
+//! and quotes styled by termimad, and fenced code blocks highlighted by
+//! syntect for the language on the fence. With `--plain`, `NO_COLOR` set or
+//! output that is not a terminal, the Markdown is printed as it is.
+
+use std::io::IsTerminal;
+
+use once_cell::sync::Lazy;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
+use termimad::MadSkin;
+
+/// Replies are wrapped to the terminal, but no wider than this.
+const MAX_WIDTH: usize = 100;
+
+/// Columns taken by the logo in front of a reply.
+const INDENT: usize = 3;
+
+const THEME: &str = "base16-ocean.dark";
+
+const RESET: &str = "\x1b[0m";
+
+static SYNTAXES: Lazy<SyntaxSet> = Lazy::new(SyntaxSet::load_defaults_newlines);
+
+static THEMES: Lazy<ThemeSet> = Lazy::new(ThemeSet::load_defaults);
+
+/// Turns replies into what is printed.
+pub struct Renderer {
+    skin: Option<MadSkin>,
+}
+
+impl Renderer {
+    /// Styled output unless `plain`, `NO_COLOR` is set or stdout is not a
+    /// terminal.
+    pub fn new(plain: bool) -> Self {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        Self::with_style(!plain && !no_color && std::io::stdout().is_terminal())
+    }
+
+    fn with_style(styled: bool) -> Self {
+        Self {
+            skin: styled.then(MadSkin::default_dark),
+        }
+    }
+
+    /// `markdown` ready to print.
+    pub fn render(&self, markdown: &str) -> String {
+        let Some(skin) = &self.skin else {
+            return markdown.to_string();
+        };
+        let width = (termimad::terminal_size().0 as usize)
+            .saturating_sub(INDENT)
+            .clamp(20, MAX_WIDTH);
+        _blocks(markdown)
+            .iter()
+            .map(|block| match block {
+                Block::Prose(text) => skin.text(text, Some(width)).to_string(),
+                Block::Code { language, code } => _highlight(language, code),
+            })
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    }
+}
+
+/// A stretch of a reply: Markdown prose or a fenced code block.
+#[derive(Debug, PartialEq)]
+enum Block<'a> {
+    Prose(String),
+    Code { language: &'a str, code: String },
+}
+
+/// Split `markdown` at its code fences. An unclosed fence runs to the end.
+fn _blocks(markdown: &str) -> Vec<Block<'_>> {
+    let mut blocks = Vec::new();
+    let mut prose = String::new();
+    let mut code: Option<(&str, String)> = None;
+    for line in LinesWithEndings::from(markdown) {
+        let fence = line.trim_start().strip_prefix("```");
+        match (&mut code, fence) {
+            (None, Some(language)) => {
+                if !prose.is_empty() {
+                    blocks.push(Block::Prose(std::mem::take(&mut prose)));
+                }
+                code = Some((language.trim(), String::new()));
+            }
+            (Some(_), Some(_)) => {
+                let (language, code) = code.take().expect("inside a code block");
+                blocks.push(Block::Code { language, code });
+            }
+            (Some((_, code)), None) => code.push_str(line),
+            (None, None) => prose.push_str(line),
+        }
+    }
+    if let Some((language, code)) = code {
+        blocks.push(Block::Code { language, code });
+    }
+    if !prose.is_empty() {
+        blocks.push(Block::Prose(prose));
+    }
+    blocks
+}
+
+/// `code` coloured as `language`, or as plain text if it is not known.
+fn _highlight(language: &str, code: &str) -> String {
+    let syntax = SYNTAXES
+        .find_syntax_by_token(language)
+        .unwrap_or_else(|| SYNTAXES.find_syntax_plain_text());
+    let theme: &Theme = &THEMES.themes[THEME];
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let mut out = String::new();
+    for line in LinesWithEndings::from(code) {
+        match highlighter.highlight_line(line, &SYNTAXES) {
+            Ok(ranges) => out.push_str(&as_24_bit_terminal_escaped(&ranges, false)),
+            Err(_) => out.push_str(line),
+        }
+    }
+    out.push_str(RESET);
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPLY: &str = "# Fix\n\nUse **this**:\n\n```rust\nfn main() {}\n```\nDone.\n";
+
+    #[test]
+    fn test_blocks() {
+        let blocks = _blocks(REPLY);
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(
+            blocks[1],
+            Block::Code {
+                language: "rust",
+                code: "fn main() {}\n".to_string()
+            }
+        );
+        assert_eq!(blocks[2], Block::Prose("Done.\n".to_string()));
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(Renderer::with_style(false).render(REPLY), REPLY);
+        let styled = Renderer::with_style(true).render(REPLY);
+        assert!(styled.contains("\x1b["));
+        assert!(!styled.contains("**") && !styled.contains("```"));
+        assert!(styled.contains("this") && styled.contains("main"));
+    }
+}
@@ -0,0 +1,5 @@
+//! Telemetry sent to monitoring systems, and records kept for audit.
+
+pub mod otlp;
+pub mod redact;
+pub mod transcripts;
@@ -0,0 +1,584 @@
+//! Export of traces and metrics to an OpenTelemetry collector.
+//!
+//! With `observability.otlp.endpoint` set (e.g. `http://localhost:4318`),
+//! the gateway turns the agent loop's [`AgentEvent`]s into spans and
+//! metrics and POSTs them as OTLP/HTTP JSON to `/v1/traces` and
+//! `/v1/metrics`, which Jaeger, Tempo and the OpenTelemetry Collector
+//! accept. Each turn is a trace: an `agent.turn` span with a `chat` span
+//! per LLM call (model and token counts) and an `execute_tool` span per
+//! tool call under it. Metrics are cumulative counters and histograms of
+//! turns, LLM calls, tokens and tool calls.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::Client;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+use crate::bus::events::AgentEvent;
+use crate::config::schema::OtlpConfig;
+use crate::utils::http::shared_client;
+
+/// Spans buffered before an export is forced.
+const MAX_BUFFERED_SPANS: usize = 512;
+
+/// Timeout of one export request.
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Histogram bucket bounds for durations, in milliseconds.
+const DURATION_BOUNDS_MS: &[f64] = &[
+    10.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0, 30000.0, 60000.0,
+];
+
+/// OTLP span kinds.
+const SPAN_KIND_INTERNAL: u8 = 1;
+const SPAN_KIND_CLIENT: u8 = 3;
+
+/// OTLP status code of a failed span.
+const STATUS_ERROR: u8 = 2;
+
+/// A finished span.
+#[derive(Debug, Clone)]
+struct Span {
+    trace_id: String,
+    span_id: String,
+    parent_id: Option<String>,
+    name: String,
+    kind: u8,
+    start: u128,
+    end: u128,
+    attributes: Vec<(String, Value)>,
+    failed: bool,
+}
+
+/// A turn in progress.
+struct Turn {
+    trace_id: String,
+    span_id: String,
+    start: u128,
+    attributes: Vec<(String, Value)>,
+}
+
+/// A cumulative histogram.
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    count: u64,
+    sum: f64,
+    buckets: Vec<u64>,
+}
+
+impl Histogram {
+    fn record(&mut self, value: f64) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; DURATION_BOUNDS_MS.len() + 1];
+        }
+        let bucket = DURATION_BOUNDS_MS
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(DURATION_BOUNDS_MS.len());
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.sum += value;
+    }
+}
+
+/// Attribute sets of a data point, in a stable order.
+type Labels = Vec<(String, String)>;
+
+/// Cumulative metrics since the exporter started.
+#[derive(Default)]
+struct Metrics {
+    /// Counters by metric name and labels.
+    counters: BTreeMap<(&'static str, Labels), u64>,
+    /// Duration histograms by metric name and labels.
+    histograms: BTreeMap<(&'static str, Labels), Histogram>,
+}
+
+impl Metrics {
+    fn add(&mut self, name: &'static str, labels: Labels, value: u64) {
+        *self.counters.entry((name, labels)).or_default() += value;
+    }
+
+    fn record(&mut self, name: &'static str, labels: Labels, duration_ms: u64) {
+        self.histograms
+            .entry((name, labels))
+            .or_default()
+            .record(duration_ms as f64);
+    }
+}
+
+/// Turns agent events into spans and metrics and exports them.
+pub struct OtlpExporter {
+    config: OtlpConfig,
+    client: Client,
+    started_at: u128,
+    turns: HashMap<String, Turn>,
+    /// Start time of running tool calls, by call ID.
+    tools: HashMap<String, u128>,
+    spans: Vec<Span>,
+    metrics: Metrics,
+}
+
+impl OtlpExporter {
+    /// An exporter for `config`, or `None` when no endpoint is set.
+    pub fn new(config: &OtlpConfig) -> Option<Self> {
+        if config.endpoint.trim().is_empty() {
+            return None;
+        }
+        Some(Self {
+            config: config.clone(),
+            client: shared_client(),
+            started_at: _now(),
+            turns: HashMap::new(),
+            tools: HashMap::new(),
+            spans: Vec::new(),
+            metrics: Metrics::default(),
+        })
+    }
+
+    /// Consume `events` in the background, exporting every
+    /// `exportIntervalSecs` and once more when the channel closes.
+    pub fn forward_agent_events(
+        mut self,
+        mut events: broadcast::Receiver<AgentEvent>,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let period = Duration::from_secs(self.config.export_interval_secs.max(1));
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    event = events.recv() => match event {
+                        Ok(event) => {
+                            self.observe(&event);
+                            if self.spans.len() >= MAX_BUFFERED_SPANS {
+                                self.export().await;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(n)) => {
+                            warn!("OTLP export missed {} agent events", n);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                    _ = interval.tick() => self.export().await,
+                }
+            }
+            self.export().await;
+        })
+    }
+
+    /// Update spans and metrics with `event`.
+    fn observe(&mut self, event: &AgentEvent) {
+        let now = _now();
+        match event {
+            AgentEvent::TurnStarted {
+                turn_id,
+                session_key,
+                channel,
+                chat_id,
+            } => {
+                self.turns.insert(
+                    turn_id.clone(),
+                    Turn {
+                        trace_id: _trace_id(turn_id),
+                        span_id: _span_id(),
+                        start: now,
+                        attributes: vec![
+                            ("nanoclaw.turn_id".to_string(), json!(turn_id)),
+                            ("nanoclaw.session_key".to_string(), json!(session_key)),
+                            ("nanoclaw.channel".to_string(), json!(channel)),
+                            ("nanoclaw.chat_id".to_string(), json!(chat_id)),
+                        ],
+                    },
+                );
+            }
+            AgentEvent::TurnCompleted {
+                turn_id,
+                iterations,
+                duration_ms,
+                failed,
+                ..
+            } => {
+                let labels = vec![("failed".to_string(), failed.to_string())];
+                self.metrics.add("nanoclaw.turns", labels.clone(), 1);
+                self.metrics
+                    .record("nanoclaw.turn.duration", labels, *duration_ms);
+                if let Some(turn) = self.turns.remove(turn_id) {
+                    let mut attributes = turn.attributes;
+                    attributes.push(("nanoclaw.iterations".to_string(), json!(iterations)));
+                    self._push_span(Span {
+                        trace_id: turn.trace_id,
+                        span_id: turn.span_id,
+                        parent_id: None,
+                        name: "agent.turn".to_string(),
+                        kind: SPAN_KIND_INTERNAL,
+                        start: turn.start,
+                        end: now,
+                        attributes,
+                        failed: *failed,
+                    });
+                }
+            }
+            AgentEvent::LlmTokens {
+                turn_id,
+                model,
+                prompt_tokens,
+                completion_tokens,
+                duration_ms,
+            } => {
+                let labels = vec![("model".to_string(), model.clone())];
+                self.metrics.add("nanoclaw.llm.calls", labels.clone(), 1);
+                self.metrics
+                    .record("nanoclaw.llm.duration", labels, *duration_ms);
+                for (kind, tokens) in [("input", prompt_tokens), ("output", completion_tokens)] {
+                    let labels = vec![
+                        ("model".to_string(), model.clone()),
+                        ("type".to_string(), kind.to_string()),
+                    ];
+                    self.metrics.add("nanoclaw.llm.tokens", labels, *tokens);
+                }
+                let (trace_id, parent_id) = self._parent(turn_id);
+                self._push_span(Span {
+                    trace_id,
+                    span_id: _span_id(),
+                    parent_id,
+                    name: format!("chat {}", model),
+                    kind: SPAN_KIND_CLIENT,
+                    start: now.saturating_sub(*duration_ms as u128 * 1_000_000),
+                    end: now,
+                    attributes: vec![
+                        ("gen_ai.operation.name".to_string(), json!("chat")),
+                        ("gen_ai.request.model".to_string(), json!(model)),
+                        (
+                            "gen_ai.usage.input_tokens".to_string(),
+                            json!(prompt_tokens),
+                        ),
+                        (
+                            "gen_ai.usage.output_tokens".to_string(),
+                            json!(completion_tokens),
+                        ),
+                    ],
+                    failed: false,
+                });
+            }
+            AgentEvent::ToolCallStarted { call_id, .. } => {
+                self.tools.insert(call_id.clone(), now);
+            }
+            AgentEvent::ToolCallFinished {
+                turn_id,
+                call_id,
+                tool,
+                result_bytes,
+                duration_ms,
+            } => {
+                let labels = vec![("tool".to_string(), tool.clone())];
+                self.metrics.add("nanoclaw.tool.calls", labels.clone(), 1);
+                self.metrics
+                    .record("nanoclaw.tool.duration", labels, *duration_ms);
+                let start = self
+                    .tools
+                    .remove(call_id)
+                    .unwrap_or_else(|| now.saturating_sub(*duration_ms as u128 * 1_000_000));
+                let (trace_id, parent_id) = self._parent(turn_id);
+                self._push_span(Span {
+                    trace_id,
+                    span_id: _span_id(),
+                    parent_id,
+                    name: format!("execute_tool {}", tool),
+                    kind: SPAN_KIND_INTERNAL,
+                    start,
+                    end: now,
+                    attributes: vec![
+                        ("gen_ai.tool.name".to_string(), json!(tool)),
+                        ("gen_ai.tool.call.id".to_string(), json!(call_id)),
+                        ("nanoclaw.result_bytes".to_string(), json!(result_bytes)),
+                    ],
+                    failed: false,
+                });
+            }
+            AgentEvent::BudgetExceeded { .. } => {}
+        }
+    }
+
+    /// The trace of `turn_id` and the span to put children under.
+    fn _parent(&self, turn_id: &str) -> (String, Option<String>) {
+        match self.turns.get(turn_id) {
+            Some(turn) => (turn.trace_id.clone(), Some(turn.span_id.clone())),
+            None => (_trace_id(turn_id), None),
+        }
+    }
+
+    fn _push_span(&mut self, span: Span) {
+        if self.config.traces {
+            self.spans.push(span);
+        }
+    }
+
+    /// Send buffered spans and the current metrics.
+    async fn export(&mut self) {
+        if !self.spans.is_empty() {
+            let spans = std::mem::take(&mut self.spans);
+            let count = spans.len();
+            if self._post("/v1/traces", self._traces_body(&spans)).await {
+                debug!("Exported {} spans", count);
+            }
+        }
+        if self.config.metrics && !self.metrics.counters.is_empty() {
+            self._post("/v1/metrics", self._metrics_body(_now())).await;
+        }
+    }
+
+    /// POST `body` to `path` under the endpoint. Returns whether it was
+    /// accepted; failures are logged and the data dropped.
+    async fn _post(&self, path: &str, body: Value) -> bool {
+        let url = format!("{}{}", self.config.endpoint.trim_end_matches('/'), path);
+        let mut request = self.client.post(&url).timeout(EXPORT_TIMEOUT).json(&body);
+        for (name, value) in &self.config.headers {
+            request = request.header(name, value);
+        }
+        match request.send().await {
+            Ok(response) if response.status().is_success() => true,
+            Ok(response) => {
+                warn!("OTLP export to {} answered {}", url, response.status());
+                false
+            }
+            Err(e) => {
+                warn!("OTLP export to {} failed: {}", url, e);
+                false
+            }
+        }
+    }
+
+    fn _resource(&self) -> Value {
+        json!({"attributes": _attributes(&[
+            ("service.name".to_string(), json!(self.config.service_name)),
+            ("service.version".to_string(), json!(env!("CARGO_PKG_VERSION"))),
+        ])})
+    }
+
+    fn _traces_body(&self, spans: &[Span]) -> Value {
+        let spans: Vec<Value> = spans
+            .iter()
+            .map(|span| {
+                let mut value = json!({
+                    "traceId": span.trace_id,
+                    "spanId": span.span_id,
+                    "name": span.name,
+                    "kind": span.kind,
+                    "startTimeUnixNano": span.start.to_string(),
+                    "endTimeUnixNano": span.end.to_string(),
+                    "attributes": _attributes(&span.attributes),
+                });
+                if let Some(parent) = &span.parent_id {
+                    value["parentSpanId"] = json!(parent);
+                }
+                if span.failed {
+                    value["status"] = json!({"code": STATUS_ERROR});
+                }
+                value
+            })
+            .collect();
+        json!({"resourceSpans": [{
+            "resource": self._resource(),
+            "scopeSpans": [{"scope": _scope(), "spans": spans}],
+        }]})
+    }
+
+    fn _metrics_body(&self, now: u128) -> Value {
+        let start = self.started_at.to_string();
+        let now = now.to_string();
+        let labels = |labels: &Labels| {
+            let attributes: Vec<(String, Value)> =
+                labels.iter().map(|(k, v)| (k.clone(), json!(v))).collect();
+            _attributes(&attributes)
+        };
+        let mut metrics: BTreeMap<&str, (&str, Vec<Value>)> = BTreeMap::new();
+        for ((name, labels_), value) in &self.metrics.counters {
+            metrics
+                .entry(name)
+                .or_insert(("sum", Vec::new()))
+                .1
+                .push(json!({
+                    "attributes": labels(labels_),
+                    "startTimeUnixNano": start,
+                    "timeUnixNano": now,
+                    "asInt": value.to_string(),
+                }));
+        }
+        for ((name, labels_), histogram) in &self.metrics.histograms {
+            metrics.entry(name).or_insert(("histogram", Vec::new())).1.push(json!({
+                "attributes": labels(labels_),
+                "startTimeUnixNano": start,
+                "timeUnixNano": now,
+                "count": histogram.count.to_string(),
+                "sum": histogram.sum,
+                "bucketCounts": histogram.buckets.iter().map(u64::to_string).collect::<Vec<_>>(),
+                "explicitBounds": DURATION_BOUNDS_MS,
+            }));
+        }
+        let metrics: Vec<Value> = metrics
+            .into_iter()
+            .map(|(name, (kind, points))| {
+                let unit = if kind == "histogram" { "ms" } else { "1" };
+                let mut data = json!({"aggregationTemporality": 2, "dataPoints": points});
+                if kind == "sum" {
+                    data["isMonotonic"] = json!(true);
+                }
+                json!({"name": name, "unit": unit, kind: data})
+            })
+            .collect();
+        json!({"resourceMetrics": [{
+            "resource": self._resource(),
+            "scopeMetrics": [{"scope": _scope(), "metrics": metrics}],
+        }]})
+    }
+}
+
+fn _scope() -> Value {
+    json!({"name": "nanoclaw", "version": env!("CARGO_PKG_VERSION")})
+}
+
+/// OTLP JSON attributes: strings, booleans and integers (as strings).
+fn _attributes(attributes: &[(String, Value)]) -> Vec<Value> {
+    attributes
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::Bool(b) => json!({"boolValue": b}),
+                Value::Number(n) if n.is_i64() || n.is_u64() => json!({"intValue": n.to_string()}),
+                Value::Number(n) => json!({"doubleValue": n}),
+                Value::String(s) => json!({"stringValue": s}),
+                other => json!({"stringValue": other.to_string()}),
+            };
+            json!({"key": key, "value": value})
+        })
+        .collect()
+}
+
+/// Nanoseconds since the Unix epoch.
+fn _now() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos())
+}
+
+/// A trace ID for a turn: the turn's UUID, or a hash of its ID.
+fn _trace_id(turn_id: &str) -> String {
+    match uuid::Uuid::parse_str(turn_id) {
+        Ok(uuid) => uuid.simple().to_string(),
+        Err(_) => hex::encode(&Sha256::digest(turn_id.as_bytes())[..16]),
+    }
+}
+
+/// A random span ID.
+fn _span_id() -> String {
+    format!("{:016x}", fastrand::u64(1..))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exporter() -> OtlpExporter {
+        OtlpExporter::new(&OtlpConfig {
+            endpoint: "http://collector:4318".to_string(),
+            ..OtlpConfig::default()
+        })
+        .unwrap()
+    }
+
+    fn turn(exporter: &mut OtlpExporter) {
+        let turn_id = "7d444840-9dc0-11d1-b245-5ffdce74fad2".to_string();
+        exporter.observe(&AgentEvent::TurnStarted {
+            turn_id: turn_id.clone(),
+            session_key: "cli:direct".to_string(),
+            channel: "cli".to_string(),
+            chat_id: "direct".to_string(),
+        });
+        exporter.observe(&AgentEvent::LlmTokens {
+            turn_id: turn_id.clone(),
+            model: "m".to_string(),
+            prompt_tokens: 100,
+            completion_tokens: 20,
+            duration_ms: 800,
+        });
+        exporter.observe(&AgentEvent::ToolCallStarted {
+            turn_id: turn_id.clone(),
+            call_id: "c1".to_string(),
+            tool: "exec".to_string(),
+        });
+        exporter.observe(&AgentEvent::ToolCallFinished {
+            turn_id: turn_id.clone(),
+            call_id: "c1".to_string(),
+            tool: "exec".to_string(),
+            result_bytes: 5,
+            duration_ms: 30,
+        });
+        exporter.observe(&AgentEvent::TurnCompleted {
+            turn_id,
+            session_key: "cli:direct".to_string(),
+            iterations: 2,
+            duration_ms: 900,
+            failed: true,
+        });
+    }
+
+    #[test]
+    fn test_turn_becomes_a_trace() {
+        assert!(OtlpExporter::new(&OtlpConfig::default()).is_none());
+        let mut exporter = exporter();
+        turn(&mut exporter);
+
+        let body = exporter._traces_body(&exporter.spans);
+        let spans = body["resourceSpans"][0]["scopeSpans"][0]["spans"]
+            .as_array()
+            .unwrap();
+        let names: Vec<&str> = spans.iter().map(|s| s["name"].as_str().unwrap()).collect();
+        assert_eq!(names, vec!["chat m", "execute_tool exec", "agent.turn"]);
+        let root = &spans[2];
+        assert_eq!(root["traceId"], "7d4448409dc011d1b2455ffdce74fad2");
+        assert_eq!(root["status"]["code"], 2);
+        assert!(root.get("parentSpanId").is_none());
+        for child in &spans[..2] {
+            assert_eq!(child["traceId"], root["traceId"]);
+            assert_eq!(child["parentSpanId"], root["spanId"]);
+        }
+        assert!(spans[0]["attributes"]
+            .as_array()
+            .unwrap()
+            .contains(&json!({"key": "gen_ai.usage.input_tokens", "value": {"intValue": "100"}})));
+        assert_eq!(
+            body["resourceSpans"][0]["resource"]["attributes"][0],
+            json!({"key": "service.name", "value": {"stringValue": "nanoclaw"}})
+        );
+    }
+
+    #[test]
+    fn test_metrics_are_cumulative() {
+        let mut exporter = exporter();
+        turn(&mut exporter);
+        turn(&mut exporter);
+
+        let body = exporter._metrics_body(_now());
+        let metrics = body["resourceMetrics"][0]["scopeMetrics"][0]["metrics"]
+            .as_array()
+            .unwrap();
+        let metric = |name: &str| metrics.iter().find(|m| m["name"] == name).unwrap().clone();
+
+        let tokens = metric("nanoclaw.llm.tokens");
+        let points = tokens["sum"]["dataPoints"].as_array().unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0]["asInt"], "200");
+        assert_eq!(tokens["sum"]["isMonotonic"], true);
+
+        let duration = metric("nanoclaw.tool.duration");
+        let point = &duration["histogram"]["dataPoints"][0];
+        assert_eq!(point["count"], "2");
+        assert_eq!(point["sum"], 60.0);
+        assert_eq!(point["bucketCounts"][1], "2");
+        assert_eq!(duration["unit"], "ms");
+    }
+}
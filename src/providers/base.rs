@@ -5,6 +5,7 @@ use std::collections::HashMap;
 use anyhow::Result;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
 
 /// A tool call request from the LLM.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,6 +54,37 @@ pub trait LLMProvider: Send + Sync {
         temperature: f64,
     ) -> Result<LLMResponse>;
 
+    /// Send a chat completion request, forwarding content deltas to `deltas`
+    /// as they arrive. Returns the same complete response as [`chat`](Self::chat).
+    ///
+    /// The default implementation does not stream: it calls `chat` and sends
+    /// the whole content as a single delta.
+    async fn chat_stream(
+        &self,
+        messages: &[serde_json::Value],
+        tools: Option<&[serde_json::Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f64,
+        deltas: UnboundedSender<String>,
+    ) -> Result<LLMResponse> {
+        let response = self
+            .chat(messages, tools, model, max_tokens, temperature)
+            .await?;
+        if response.finish_reason != "error" {
+            if let Some(content) = response.content.as_ref().filter(|c| !c.is_empty()) {
+                let _ = deltas.send(content.clone());
+            }
+        }
+        Ok(response)
+    }
+
+    /// Check that the provider's API can be reached, for readiness checks.
+    /// The default assumes it can.
+    async fn check_reachable(&self) -> Result<()> {
+        Ok(())
+    }
+
     /// Get the default model for this provider.
     fn get_default_model(&self) -> &str;
 }
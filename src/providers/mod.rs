@@ -1,3 +1,28 @@
 pub mod base;
 pub mod openai_compat;
+pub mod pii;
 pub mod transcription;
+
+use std::sync::Arc;
+
+use crate::config::schema::Config;
+use crate::providers::base::LLMProvider;
+use crate::providers::openai_compat::OpenAICompatProvider;
+use crate::providers::pii::PseudonymizingProvider;
+
+/// The LLM provider `config` selects, pseudonymizing personal data when
+/// `privacy.pseudonymize` is set.
+pub fn create_provider(config: &Config) -> Arc<dyn LLMProvider> {
+    let api_key = config.get_api_key().unwrap_or_default();
+    let api_base = config.get_api_base();
+    let model = &config.agents.defaults.model;
+    let provider: Arc<dyn LLMProvider> = Arc::new(OpenAICompatProvider::new(
+        &api_key,
+        api_base.as_deref(),
+        Some(model.as_str()),
+    ));
+    if config.privacy.pseudonymize {
+        return Arc::new(PseudonymizingProvider::new(provider, &config.privacy.kinds));
+    }
+    provider
+}
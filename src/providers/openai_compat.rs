@@ -10,10 +10,13 @@ use std::collections::HashMap;
 use anyhow::Result;
 use async_trait::async_trait;
 use reqwest::Client;
+use tokio::sync::mpsc::UnboundedSender;
 use tracing::warn;
 
 use super::base::{LLMProvider, LLMResponse, ToolCallRequest};
 
+use crate::utils::http::shared_client;
+
 /// An LLM provider that talks to any OpenAI-compatible chat completions endpoint.
 pub struct OpenAICompatProvider {
     api_key: String,
@@ -62,26 +65,30 @@ impl OpenAICompatProvider {
             api_key: api_key.to_string(),
             api_base: resolved_base,
             default_model,
-            client: Client::new(),
+            client: shared_client(),
         }
     }
-}
 
-#[async_trait]
-impl LLMProvider for OpenAICompatProvider {
-    async fn chat(
+    /// Send requests with `client` instead of the shared one.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Build the chat completions URL and request body.
+    fn _request(
         &self,
         messages: &[serde_json::Value],
         tools: Option<&[serde_json::Value]>,
         model: Option<&str>,
         max_tokens: u32,
         temperature: f64,
-    ) -> Result<LLMResponse> {
+    ) -> (String, serde_json::Value) {
         let raw_model = model.unwrap_or(&self.default_model);
         // Strip "provider/" prefix for non-OpenRouter APIs (e.g. "anthropic/claude-opus-4-5"
         // becomes "claude-opus-4-5" when hitting api.anthropic.com directly).
         let model = if !self.api_base.contains("openrouter") {
-            raw_model.split('/').last().unwrap_or(raw_model)
+            raw_model.rsplit('/').next().unwrap_or(raw_model)
         } else {
             raw_model
         };
@@ -101,74 +108,267 @@ impl LLMProvider for OpenAICompatProvider {
                 body["tool_choice"] = serde_json::json!("auto");
             }
         }
+        (url, body)
+    }
 
+    /// POST `body` to `url`, returning an error response for transport
+    /// failures and non-2xx statuses.
+    async fn _send(
+        &self,
+        url: &str,
+        body: &serde_json::Value,
+    ) -> std::result::Result<reqwest::Response, LLMResponse> {
         let response = match self
             .client
-            .post(&url)
+            .post(url)
             .header("Authorization", format!("Bearer {}", self.api_key))
             .header("Content-Type", "application/json")
-            .json(&body)
+            .json(body)
             .send()
             .await
         {
             Ok(r) => r,
             Err(e) => {
                 warn!("HTTP request to LLM failed: {}", e);
-                return Ok(LLMResponse {
-                    content: Some(format!("Error calling LLM: {}", e)),
-                    tool_calls: Vec::new(),
-                    finish_reason: "error".to_string(),
-                    usage: HashMap::new(),
-                });
+                return Err(error_response(format!("Error calling LLM: {}", e)));
             }
         };
 
         let status = response.status();
-        let response_text = match response.text().await {
-            Ok(t) => t,
-            Err(e) => {
-                return Ok(LLMResponse {
-                    content: Some(format!("Error reading LLM response: {}", e)),
-                    tool_calls: Vec::new(),
-                    finish_reason: "error".to_string(),
-                    usage: HashMap::new(),
-                });
-            }
-        };
-
         if !status.is_success() {
+            let response_text = response.text().await.unwrap_or_default();
             warn!("LLM API returned status {}: {}", status, response_text);
-            return Ok(LLMResponse {
-                content: Some(format!(
-                    "Error calling LLM (HTTP {}): {}",
-                    status, response_text
-                )),
-                tool_calls: Vec::new(),
-                finish_reason: "error".to_string(),
-                usage: HashMap::new(),
-            });
+            return Err(error_response(format!(
+                "Error calling LLM (HTTP {}): {}",
+                status, response_text
+            )));
         }
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl LLMProvider for OpenAICompatProvider {
+    async fn chat(
+        &self,
+        messages: &[serde_json::Value],
+        tools: Option<&[serde_json::Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f64,
+    ) -> Result<LLMResponse> {
+        let (url, body) = self._request(messages, tools, model, max_tokens, temperature);
+        let response = match self._send(&url, &body).await {
+            Ok(r) => r,
+            Err(error) => return Ok(error),
+        };
+
+        let response_text = match response.text().await {
+            Ok(t) => t,
+            Err(e) => return Ok(error_response(format!("Error reading LLM response: {}", e))),
+        };
 
         let data: serde_json::Value = match serde_json::from_str(&response_text) {
             Ok(v) => v,
             Err(e) => {
-                return Ok(LLMResponse {
-                    content: Some(format!("Error parsing LLM response JSON: {}", e)),
-                    tool_calls: Vec::new(),
-                    finish_reason: "error".to_string(),
-                    usage: HashMap::new(),
-                });
+                return Ok(error_response(format!("Error parsing LLM response JSON: {}", e)));
             }
         };
 
         parse_response(&data)
     }
 
+    async fn chat_stream(
+        &self,
+        messages: &[serde_json::Value],
+        tools: Option<&[serde_json::Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f64,
+        deltas: UnboundedSender<String>,
+    ) -> Result<LLMResponse> {
+        let (url, mut body) = self._request(messages, tools, model, max_tokens, temperature);
+        body["stream"] = serde_json::json!(true);
+        // Ask for token usage in the final chunk, for cost tracking.
+        body["stream_options"] = serde_json::json!({"include_usage": true});
+        let mut response = match self._send(&url, &body).await {
+            Ok(r) => r,
+            Err(error) => return Ok(error),
+        };
+
+        // Server-sent events: one `data: {...}` line per chunk. Lines (and
+        // multi-byte characters) may be split across network reads, so
+        // buffer raw bytes and only decode complete lines.
+        let mut stream = StreamAccumulator::default();
+        let mut buffer: Vec<u8> = Vec::new();
+        loop {
+            let chunk = match response.chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(e) => {
+                    return Ok(error_response(format!("Error reading LLM stream: {}", e)));
+                }
+            };
+            buffer.extend_from_slice(&chunk);
+            while let Some(newline) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=newline).collect();
+                if let Some(delta) = stream.push_line(String::from_utf8_lossy(&line).trim()) {
+                    let _ = deltas.send(delta);
+                }
+            }
+        }
+        if let Some(delta) = stream.push_line(String::from_utf8_lossy(&buffer).trim()) {
+            let _ = deltas.send(delta);
+        }
+
+        Ok(stream.finish())
+    }
+
+    /// List the models: any answer but a server error or a rejected key
+    /// counts (some APIs have no `/models`).
+    async fn check_reachable(&self) -> Result<()> {
+        let status = self
+            .client
+            .get(format!("{}/models", self.api_base))
+            .bearer_auth(&self.api_key)
+            .timeout(std::time::Duration::from_secs(10))
+            .send()
+            .await?
+            .status();
+        if status.is_server_error() {
+            anyhow::bail!("{} answered {}", self.api_base, status);
+        }
+        if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+            anyhow::bail!("{} rejected the API key", self.api_base);
+        }
+        Ok(())
+    }
+
     fn get_default_model(&self) -> &str {
         &self.default_model
     }
 }
 
+/// Build an `LLMResponse` describing a failed call.
+fn error_response(message: String) -> LLMResponse {
+    LLMResponse {
+        content: Some(message),
+        tool_calls: Vec::new(),
+        finish_reason: "error".to_string(),
+        usage: HashMap::new(),
+    }
+}
+
+/// A tool call being assembled from streamed fragments.
+#[derive(Default)]
+struct PartialToolCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+/// Assembles a streamed chat completion from its `data:` lines.
+#[derive(Default)]
+struct StreamAccumulator {
+    content: String,
+    tool_calls: Vec<PartialToolCall>,
+    finish_reason: Option<String>,
+    usage: HashMap<String, i64>,
+    error: Option<String>,
+}
+
+impl StreamAccumulator {
+    /// Consume one SSE line, returning any new content text.
+    fn push_line(&mut self, line: &str) -> Option<String> {
+        let data = line.strip_prefix("data:")?.trim();
+        if data.is_empty() || data == "[DONE]" {
+            return None;
+        }
+        let event: serde_json::Value = serde_json::from_str(data).ok()?;
+
+        if let Some(err) = event.get("error") {
+            let message = err.get("message").and_then(|v| v.as_str()).unwrap_or("unknown error");
+            self.error = Some(format!("Error calling LLM: {}", message));
+            return None;
+        }
+        if let Some(usage) = event.get("usage").and_then(|v| v.as_object()) {
+            for (key, value) in usage {
+                if let Some(n) = value.as_i64() {
+                    self.usage.insert(key.clone(), n);
+                }
+            }
+        }
+
+        let choice = event.get("choices")?.get(0)?;
+        if let Some(reason) = choice.get("finish_reason").and_then(|v| v.as_str()) {
+            self.finish_reason = Some(reason.to_string());
+        }
+        let delta = choice.get("delta")?;
+
+        if let Some(calls) = delta.get("tool_calls").and_then(|v| v.as_array()) {
+            for call in calls {
+                let index = call.get("index").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+                if self.tool_calls.len() <= index {
+                    self.tool_calls.resize_with(index + 1, PartialToolCall::default);
+                }
+                let partial = &mut self.tool_calls[index];
+                if let Some(id) = call.get("id").and_then(|v| v.as_str()) {
+                    partial.id = id.to_string();
+                }
+                if let Some(function) = call.get("function") {
+                    if let Some(name) = function.get("name").and_then(|v| v.as_str()) {
+                        partial.name.push_str(name);
+                    }
+                    if let Some(args) = function.get("arguments").and_then(|v| v.as_str()) {
+                        partial.arguments.push_str(args);
+                    }
+                }
+            }
+        }
+
+        let text = delta.get("content").and_then(|v| v.as_str())?;
+        if text.is_empty() {
+            return None;
+        }
+        self.content.push_str(text);
+        Some(text.to_string())
+    }
+
+    /// Build the final response, reusing [`parse_response`] for tool calls.
+    fn finish(self) -> LLMResponse {
+        if let Some(error) = self.error {
+            return error_response(error);
+        }
+        let tool_calls: Vec<serde_json::Value> = self
+            .tool_calls
+            .into_iter()
+            .filter(|tc| !tc.name.is_empty())
+            .map(|tc| {
+                serde_json::json!({
+                    "id": tc.id,
+                    "type": "function",
+                    "function": { "name": tc.name, "arguments": tc.arguments },
+                })
+            })
+            .collect();
+        let mut message = serde_json::json!({});
+        if !self.content.is_empty() {
+            message["content"] = serde_json::json!(self.content);
+        }
+        if !tool_calls.is_empty() {
+            message["tool_calls"] = serde_json::json!(tool_calls);
+        }
+        let data = serde_json::json!({
+            "choices": [{
+                "message": message,
+                "finish_reason": self.finish_reason.unwrap_or_else(|| "stop".to_string()),
+            }],
+            "usage": self.usage,
+        });
+        parse_response(&data).unwrap_or_else(|e| error_response(e.to_string()))
+    }
+}
+
 /// Parse the OpenAI-compatible JSON response into an `LLMResponse`.
 fn parse_response(data: &serde_json::Value) -> Result<LLMResponse> {
     let choices = data
@@ -448,6 +648,62 @@ mod tests {
         );
     }
 
+    // ── StreamAccumulator tests ───────────────────────────────────
+
+    #[test]
+    fn test_stream_accumulates_content_deltas() {
+        let mut acc = StreamAccumulator::default();
+        let lines = [
+            r#"data: {"choices":[{"delta":{"role":"assistant"}}]}"#,
+            r#"data: {"choices":[{"delta":{"content":"Hel"}}]}"#,
+            "",
+            r#"data: {"choices":[{"delta":{"content":"lo!"},"finish_reason":"stop"}]}"#,
+            r#"data: {"choices":[],"usage":{"total_tokens":12}}"#,
+            "data: [DONE]",
+        ];
+        let deltas: Vec<String> = lines.iter().filter_map(|l| acc.push_line(l)).collect();
+        assert_eq!(deltas, vec!["Hel", "lo!"]);
+
+        let resp = acc.finish();
+        assert_eq!(resp.content.as_deref(), Some("Hello!"));
+        assert_eq!(resp.finish_reason, "stop");
+        assert_eq!(resp.usage.get("total_tokens"), Some(&12));
+        assert!(!resp.has_tool_calls());
+    }
+
+    #[test]
+    fn test_stream_assembles_tool_call_fragments() {
+        let mut acc = StreamAccumulator::default();
+        let lines = [
+            r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_1","function":{"name":"search","arguments":""}}]}}]}"#,
+            r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"query\": "}}]}}]}"#,
+            r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"rust\"}"}}]}}]}"#,
+            r#"data: {"choices":[{"delta":{},"finish_reason":"tool_calls"}]}"#,
+        ];
+        for line in lines {
+            assert!(acc.push_line(line).is_none());
+        }
+
+        let resp = acc.finish();
+        assert!(resp.content.is_none());
+        assert_eq!(resp.finish_reason, "tool_calls");
+        assert_eq!(resp.tool_calls.len(), 1);
+        assert_eq!(resp.tool_calls[0].id, "call_1");
+        assert_eq!(
+            resp.tool_calls[0].arguments.get("query").and_then(|v| v.as_str()),
+            Some("rust")
+        );
+    }
+
+    #[test]
+    fn test_stream_error_event() {
+        let mut acc = StreamAccumulator::default();
+        acc.push_line(r#"data: {"error":{"message":"overloaded"}}"#);
+        let resp = acc.finish();
+        assert_eq!(resp.finish_reason, "error");
+        assert!(resp.content.unwrap().contains("overloaded"));
+    }
+
     // ── Provider creation / detection tests ───────────────────────
 
     #[test]
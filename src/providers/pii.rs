@@ -0,0 +1,460 @@
+//! Pseudonymization of personal data sent to the LLM provider.
+//!
+//! With `privacy.pseudonymize`, [`PseudonymizingProvider`] wraps the real
+//! provider: e-mail addresses, phone numbers and street addresses in the
+//! messages of a request are replaced with placeholders such as `[EMAIL_1]`
+//! before it leaves, and the placeholders in the reply, streamed or not,
+//! and in its tool calls are turned back into the data. Placeholders are
+//! numbered afresh for each request, in the order the values appear in its
+//! messages: a conversation resends its history, so its placeholders stay
+//! the same across calls, and a reply is only ever restored from the data
+//! of its own request, never from another session's.
+//!
+//! Detection is by pattern and errs on the side of missing things: it is a
+//! way to send less, not a guarantee that nothing personal is sent.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::Value;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tracing::warn;
+
+use super::base::{LLMProvider, LLMResponse};
+
+/// Kinds of personal data, in the order they are looked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PiiKind {
+    Email,
+    Phone,
+    Address,
+}
+
+impl PiiKind {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "email" => Some(Self::Email),
+            "phone" => Some(Self::Phone),
+            "address" => Some(Self::Address),
+            _ => None,
+        }
+    }
+
+    /// Name used in placeholders.
+    fn label(self) -> &'static str {
+        match self {
+            Self::Email => "EMAIL",
+            Self::Phone => "PHONE",
+            Self::Address => "ADDRESS",
+        }
+    }
+
+    fn regex(self) -> &'static Regex {
+        match self {
+            Self::Email => &EMAIL,
+            Self::Phone => &PHONE,
+            Self::Address => &ADDRESS,
+        }
+    }
+}
+
+static EMAIL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\b[A-Za-z0-9._%+\-]+@[A-Za-z0-9\-]+(?:\.[A-Za-z0-9\-]+)*\.[A-Za-z]{2,}\b").unwrap()
+});
+
+/// Digits with optional country code, area code in parentheses and
+/// separators; candidates are checked by [`_is_phone`].
+static PHONE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?:\+|\(\d{1,4}\)\s?|\b)\d[\d\s.\-()]{6,}\d\b").unwrap());
+
+/// A house number and street (`221B Baker Street`), or a street and house
+/// number (`Via Roma 12`, `Rue de Rivoli, 99`).
+static ADDRESS: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?x)
+        \b\d{1,5}[A-Za-z]?\s+(?:[A-Z][\w'\-]*\s+){1,4}
+          (?:Street|St|Avenue|Ave|Road|Rd|Boulevard|Blvd|Lane|Ln|Drive|Dr|Court|Ct|Way
+            |Place|Pl|Terrace|Square|Sq|Parkway|Pkwy|Highway|Hwy)\b
+        | \b(?:Via|Viale|Piazza|Piazzale|Corso|Vicolo|Rue|Calle|Avenida|Rua)
+          \s+(?:(?:d[aeiu]|de|del|della|dei|degli|la|le|los|las|do|da)\s+|[A-Z][\w'\-]*\s+){1,4}
+          ,?\s*\d{1,5}[A-Za-z]?\b
+        | \b[A-Z][\w\-]*(?:straße|strasse|weg|gasse|platz|allee)\s+\d{1,5}[A-Za-z]?\b",
+    )
+    .unwrap()
+});
+
+/// `[EMAIL_3]` and the like.
+static PLACEHOLDER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\[(?:EMAIL|PHONE|ADDRESS)_\d+\]").unwrap());
+
+/// Longest placeholder held back from a stream while it may be incomplete.
+const MAX_PLACEHOLDER_CHARS: usize = 20;
+
+/// Whether a phone pattern match is a phone number rather than a date, a
+/// time or an amount.
+fn _is_phone(candidate: &str) -> bool {
+    let digits = candidate.chars().filter(|c| c.is_ascii_digit()).count();
+    let separators = candidate.chars().filter(|c| !c.is_ascii_digit()).count();
+    static DATE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"\d{4}[\-./]\d{1,2}[\-./]\d{1,2}|\d{1,2}[\-./]\d{1,2}[\-./]\d{4}").unwrap()
+    });
+    (9..=15).contains(&digits)
+        && (candidate.starts_with('+') || separators > 0 || digits >= 10)
+        && !DATE.is_match(candidate)
+}
+
+/// The two-way mapping of values and placeholders of one request.
+#[derive(Debug, Default)]
+struct Pseudonyms {
+    by_value: HashMap<String, String>,
+    by_placeholder: HashMap<String, String>,
+    counts: HashMap<PiiKind, usize>,
+}
+
+impl Pseudonyms {
+    fn placeholder(&mut self, kind: PiiKind, value: &str) -> String {
+        if let Some(placeholder) = self.by_value.get(value) {
+            return placeholder.clone();
+        }
+        let count = self.counts.entry(kind).or_default();
+        *count += 1;
+        let placeholder = format!("[{}_{}]", kind.label(), count);
+        self.by_value.insert(value.to_string(), placeholder.clone());
+        self.by_placeholder
+            .insert(placeholder.clone(), value.to_string());
+        placeholder
+    }
+
+    /// `text` with the personal data of the given `kinds` replaced by
+    /// placeholders.
+    fn pseudonymize(&mut self, kinds: &[PiiKind], text: &str) -> String {
+        let mut text = text.to_string();
+        // E-mail addresses first, so their digits are not taken for phones.
+        for &kind in kinds {
+            let regex = kind.regex();
+            if !regex.is_match(&text) {
+                continue;
+            }
+            text = regex
+                .replace_all(&text, |caps: &regex::Captures| {
+                    let value = &caps[0];
+                    if kind == PiiKind::Phone && !_is_phone(value) {
+                        return value.to_string();
+                    }
+                    self.placeholder(kind, value)
+                })
+                .into_owned();
+        }
+        text
+    }
+
+    /// `text` with the placeholders handed out here turned back into their
+    /// values.
+    fn restore(&self, text: &str) -> String {
+        if !PLACEHOLDER.is_match(text) {
+            return text.to_string();
+        }
+        PLACEHOLDER
+            .replace_all(text, |caps: &regex::Captures| {
+                self.by_placeholder
+                    .get(&caps[0])
+                    .cloned()
+                    .unwrap_or_else(|| caps[0].to_string())
+            })
+            .into_owned()
+    }
+
+    fn restore_value(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => *text = self.restore(text),
+            Value::Array(items) => items.iter_mut().for_each(|v| self.restore_value(v)),
+            Value::Object(fields) => fields.values_mut().for_each(|v| self.restore_value(v)),
+            _ => {}
+        }
+    }
+
+    fn restore_response(&self, mut response: LLMResponse) -> LLMResponse {
+        response.content = response.content.map(|c| self.restore(&c));
+        for call in &mut response.tool_calls {
+            for value in call.arguments.values_mut() {
+                self.restore_value(value);
+            }
+        }
+        response
+    }
+}
+
+/// An [`LLMProvider`] that keeps personal data from the provider it wraps.
+pub struct PseudonymizingProvider {
+    inner: Arc<dyn LLMProvider>,
+    kinds: Vec<PiiKind>,
+}
+
+impl PseudonymizingProvider {
+    /// Wrap `inner`, replacing the named `kinds`. Unknown kinds are logged
+    /// and skipped.
+    pub fn new(inner: Arc<dyn LLMProvider>, kinds: &[String]) -> Self {
+        let mut kinds: Vec<PiiKind> = kinds
+            .iter()
+            .filter_map(|name| {
+                let kind = PiiKind::parse(name);
+                if kind.is_none() {
+                    warn!("Ignoring unknown privacy.kinds entry '{}'", name);
+                }
+                kind
+            })
+            .collect();
+        kinds.sort();
+        kinds.dedup();
+        Self { inner, kinds }
+    }
+
+    /// The messages with the text of their contents and tool call
+    /// arguments pseudonymized, and the placeholders handed out for them.
+    fn _messages(&self, messages: &[Value]) -> (Vec<Value>, Pseudonyms) {
+        let mut pseudonyms = Pseudonyms::default();
+        let messages = messages
+            .iter()
+            .map(|message| {
+                let mut message = message.clone();
+                match message.get_mut("content") {
+                    Some(Value::String(text)) => *text = pseudonyms.pseudonymize(&self.kinds, text),
+                    Some(Value::Array(parts)) => {
+                        for part in parts {
+                            if let Some(Value::String(text)) = part.get_mut("text") {
+                                *text = pseudonyms.pseudonymize(&self.kinds, text);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                if let Some(Value::Array(calls)) = message.get_mut("tool_calls") {
+                    for call in calls {
+                        if let Some(Value::String(arguments)) =
+                            call.pointer_mut("/function/arguments")
+                        {
+                            *arguments = pseudonyms.pseudonymize(&self.kinds, arguments);
+                        }
+                    }
+                }
+                message
+            })
+            .collect();
+        (messages, pseudonyms)
+    }
+}
+
+#[async_trait]
+impl LLMProvider for PseudonymizingProvider {
+    async fn chat(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f64,
+    ) -> Result<LLMResponse> {
+        let (messages, pseudonyms) = self._messages(messages);
+        let response = self
+            .inner
+            .chat(&messages, tools, model, max_tokens, temperature)
+            .await?;
+        Ok(pseudonyms.restore_response(response))
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f64,
+        deltas: UnboundedSender<String>,
+    ) -> Result<LLMResponse> {
+        let (messages, pseudonyms) = self._messages(messages);
+        let (inner_tx, mut inner_rx) = mpsc::unbounded_channel::<String>();
+        let call =
+            self.inner
+                .chat_stream(&messages, tools, model, max_tokens, temperature, inner_tx);
+        // Pass deltas on restored, holding back what may be the start of a
+        // placeholder until the rest of it arrives.
+        let relay = async {
+            let mut pending = String::new();
+            while let Some(delta) = inner_rx.recv().await {
+                pending.push_str(&delta);
+                let hold = _incomplete_placeholder(&pending);
+                let ready: String = pending.drain(..hold).collect();
+                if !ready.is_empty() {
+                    let _ = deltas.send(pseudonyms.restore(&ready));
+                }
+            }
+            if !pending.is_empty() {
+                let _ = deltas.send(pseudonyms.restore(&pending));
+            }
+        };
+        let (response, ()) = tokio::join!(call, relay);
+        Ok(pseudonyms.restore_response(response?))
+    }
+
+    async fn check_reachable(&self) -> Result<()> {
+        self.inner.check_reachable().await
+    }
+
+    fn get_default_model(&self) -> &str {
+        self.inner.get_default_model()
+    }
+}
+
+/// Where a possibly unfinished placeholder starts at the end of `text`, or
+/// its length if there is none.
+fn _incomplete_placeholder(text: &str) -> usize {
+    match text.rfind('[') {
+        Some(at)
+            if !text[at..].contains(']')
+                && text.len() - at < MAX_PLACEHOLDER_CHARS
+                && text[at + 1..]
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_') =>
+        {
+            at
+        }
+        _ => text.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    use crate::providers::base::ToolCallRequest;
+
+    /// Echoes the last message back and calls `send_email` with it.
+    struct Echo {
+        seen: Mutex<Vec<Value>>,
+    }
+
+    #[async_trait]
+    impl LLMProvider for Echo {
+        async fn chat(
+            &self,
+            messages: &[Value],
+            _tools: Option<&[Value]>,
+            _model: Option<&str>,
+            _max_tokens: u32,
+            _temperature: f64,
+        ) -> Result<LLMResponse> {
+            *self.seen.lock().unwrap() = messages.to_vec();
+            let last = messages.last().unwrap()["content"]
+                .as_str()
+                .unwrap()
+                .to_string();
+            Ok(LLMResponse {
+                content: Some(format!("You said: {}", last)),
+                tool_calls: vec![ToolCallRequest {
+                    id: "c1".to_string(),
+                    name: "send_email".to_string(),
+                    arguments: HashMap::from([("to".to_string(), Value::String(last))]),
+                }],
+                finish_reason: "tool_calls".to_string(),
+                usage: HashMap::new(),
+            })
+        }
+
+        fn get_default_model(&self) -> &str {
+            "echo"
+        }
+    }
+
+    fn provider() -> (Arc<Echo>, PseudonymizingProvider) {
+        let echo = Arc::new(Echo {
+            seen: Mutex::new(Vec::new()),
+        });
+        let kinds = ["email", "phone", "address", "ssn"].map(String::from);
+        (echo.clone(), PseudonymizingProvider::new(echo, &kinds))
+    }
+
+    #[test]
+    fn test_pseudonymize_detects_pii() {
+        let (_, pii) = provider();
+        let mut pseudonyms = Pseudonyms::default();
+        let text = "Mail anna.rossi@example.it or call +39 333 123 4567 / (555) 123-4567. \
+                    I live at Via della Spiga 12, near 221B Baker Street. \
+                    Meeting on 2026-03-01 10:30, budget 1500000, order 12345.";
+        assert_eq!(
+            pseudonyms.pseudonymize(&pii.kinds, text),
+            "Mail [EMAIL_1] or call [PHONE_1] / [PHONE_2]. \
+             I live at [ADDRESS_1], near [ADDRESS_2]. \
+             Meeting on 2026-03-01 10:30, budget 1500000, order 12345."
+        );
+        assert_eq!(
+            pseudonyms.pseudonymize(&pii.kinds, "again anna.rossi@example.it"),
+            "again [EMAIL_1]"
+        );
+        assert_eq!(
+            pseudonyms.restore("[EMAIL_1] and [EMAIL_9]"),
+            "anna.rossi@example.it and [EMAIL_9]"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chat_hides_and_restores() {
+        let (echo, pii) = provider();
+        let messages = vec![serde_json::json!({"role": "user", "content": "bob@example.com"})];
+        let response = pii.chat(&messages, None, None, 100, 0.0).await.unwrap();
+        assert_eq!(echo.seen.lock().unwrap()[0]["content"], "[EMAIL_1]");
+        assert_eq!(
+            response.content.as_deref(),
+            Some("You said: bob@example.com")
+        );
+        assert_eq!(response.tool_calls[0].arguments["to"], "bob@example.com");
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        pii.chat_stream(&messages, None, None, 100, 0.0, tx)
+            .await
+            .unwrap();
+        let mut streamed = String::new();
+        while let Some(delta) = rx.recv().await {
+            streamed.push_str(&delta);
+        }
+        assert_eq!(streamed, "You said: bob@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_sessions_do_not_share_placeholders() {
+        let (echo, pii) = provider();
+        let alice = vec![serde_json::json!({"role": "user", "content": "alice@example.com"})];
+        let response = pii.chat(&alice, None, None, 100, 0.0).await.unwrap();
+        assert_eq!(echo.seen.lock().unwrap()[0]["content"], "[EMAIL_1]");
+        assert_eq!(
+            response.content.as_deref(),
+            Some("You said: alice@example.com")
+        );
+
+        // Another session typing the placeholder gets it back as typed.
+        let bob = vec![serde_json::json!({"role": "user", "content": "[EMAIL_1]"})];
+        let response = pii.chat(&bob, None, None, 100, 0.0).await.unwrap();
+        assert_eq!(response.content.as_deref(), Some("You said: [EMAIL_1]"));
+        assert_eq!(response.tool_calls[0].arguments["to"], "[EMAIL_1]");
+
+        // And its own [EMAIL_1] is its own address.
+        let carol = vec![serde_json::json!({"role": "user", "content": "carol@example.com"})];
+        let response = pii.chat(&carol, None, None, 100, 0.0).await.unwrap();
+        assert_eq!(echo.seen.lock().unwrap()[0]["content"], "[EMAIL_1]");
+        assert_eq!(
+            response.content.as_deref(),
+            Some("You said: carol@example.com")
+        );
+    }
+
+    #[test]
+    fn test_incomplete_placeholder() {
+        assert_eq!(_incomplete_placeholder("call [PHO"), 5);
+        assert_eq!(_incomplete_placeholder("call [PHONE_1] now"), 18);
+        assert_eq!(_incomplete_placeholder("a [link"), 7);
+    }
+}
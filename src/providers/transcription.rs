@@ -5,6 +5,8 @@ use std::path::Path;
 use reqwest::Client;
 use tracing::{error, warn};
 
+use crate::utils::http::shared_client;
+
 /// Voice transcription provider using Groq's Whisper API.
 ///
 /// Groq offers extremely fast transcription with a generous free tier.
@@ -26,7 +28,7 @@ impl GroqTranscriptionProvider {
         Self {
             api_key: resolved_key,
             api_url: "https://api.groq.com/openai/v1/audio/transcriptions".to_string(),
-            client: Client::new(),
+            client: shared_client(),
         }
     }
 
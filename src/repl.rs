@@ -0,0 +1,228 @@
+//! The interactive prompt of `nanoclaw agent`.
+//!
+//! Lines are read with rustyline, so the usual Emacs keys edit them and Up,
+//! Down and Ctrl-R go through the history, which is kept in
+//! `~/.nanoclaw/history` across runs. A line ending in `\`, or one that
+//! leaves a ```` ``` ```` fence open, is continued on the next under a `...`
+//! prompt. Ctrl-C drops what is being typed (and exits on an empty prompt),
+//! Ctrl-D exits, and [`interruptible`] makes Ctrl-C stop a reply in progress
+//! rather than the whole program.
+//!
+//! A few slash [`Command`]s are handled at the prompt itself; see [`HELP`].
+
+use std::future::Future;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use rustyline::error::ReadlineError;
+use rustyline::{Config, DefaultEditor};
+use tracing::warn;
+
+use crate::utils::helpers::get_data_path;
+
+const PROMPT: &str = "You: ";
+
+/// Prompt for the lines of a message after the first.
+const CONTINUATION_PROMPT: &str = "...  ";
+
+const HISTORY_FILE: &str = "history";
+
+/// Messages kept in the history file.
+const MAX_HISTORY: usize = 1000;
+
+/// Messages `/history` shows unless told otherwise.
+const DEFAULT_HISTORY_MESSAGES: usize = 10;
+
+pub const HELP: &str = "\
+/help            this list
+/new             start a new conversation (the current one is kept)
+/model [name]    show the model, or use another for the rest of the run
+/tools           list the agent's tools
+/history [n]     show the last n messages of the conversation
+/cost            token usage and cost of the conversation
+/memory          show long-term memory and today's notes
+/whoami, /reset  as in any chat";
+
+/// Reads messages from the terminal.
+pub struct Repl {
+    editor: DefaultEditor,
+    history: PathBuf,
+}
+
+impl Repl {
+    /// An editor with the history of earlier runs.
+    pub fn new() -> Result<Self> {
+        let config = Config::builder()
+            .max_history_size(MAX_HISTORY)?
+            .history_ignore_dups(true)?
+            .auto_add_history(false)
+            .build();
+        let mut editor = DefaultEditor::with_config(config)?;
+        let history = get_data_path().join(HISTORY_FILE);
+        if history.exists() {
+            if let Err(e) = editor.load_history(&history) {
+                warn!("Could not load {}: {}", history.display(), e);
+            }
+        }
+        Ok(Self { editor, history })
+    }
+
+    /// The next message, or `None` once the user exits.
+    pub fn read(&mut self) -> Option<String> {
+        let mut buffer = String::new();
+        loop {
+            let prompt = if buffer.is_empty() {
+                PROMPT
+            } else {
+                CONTINUATION_PROMPT
+            };
+            match self.editor.readline(prompt) {
+                Ok(line) => {
+                    if _continues(&mut buffer, &line) {
+                        continue;
+                    }
+                    let input = buffer.trim().to_string();
+                    buffer.clear();
+                    if input.is_empty() {
+                        continue;
+                    }
+                    self._remember(&input);
+                    return Some(input);
+                }
+                Err(ReadlineError::Interrupted) if !buffer.is_empty() => buffer.clear(),
+                Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => return None,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Add `input` to the history and the history file, right away so that
+    /// it survives a crash.
+    fn _remember(&mut self, input: &str) {
+        if !matches!(self.editor.add_history_entry(input), Ok(true)) {
+            return;
+        }
+        if let Err(e) = self.editor.append_history(&self.history) {
+            warn!("Could not save {}: {}", self.history.display(), e);
+        }
+    }
+}
+
+/// Add `line` to the message in `buffer`; whether the message goes on.
+fn _continues(buffer: &mut String, line: &str) -> bool {
+    if let Some(line) = line.strip_suffix('\\') {
+        buffer.push_str(line);
+        buffer.push('\n');
+        return true;
+    }
+    buffer.push_str(line);
+    let open_fence = buffer
+        .lines()
+        .filter(|l| l.trim_start().starts_with("```"))
+        .count()
+        % 2
+        == 1;
+    if open_fence {
+        buffer.push('\n');
+    }
+    open_fence
+}
+
+/// A command handled by the prompt rather than sent to the agent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Help,
+    New,
+    Model(Option<String>),
+    Tools,
+    History(usize),
+    Memory,
+}
+
+impl Command {
+    /// The command `input` is, if any. Other slash commands, such as
+    /// `/cost`, go to the agent, which answers them without the LLM.
+    pub fn parse(input: &str) -> Option<Self> {
+        let mut words = input.split_whitespace();
+        let command = match words.next()? {
+            "/help" | "/?" => Self::Help,
+            "/new" => Self::New,
+            "/model" => Self::Model(words.next().map(str::to_string)),
+            "/tools" => Self::Tools,
+            "/history" => Self::History(
+                words
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or(DEFAULT_HISTORY_MESSAGES),
+            ),
+            "/memory" => Self::Memory,
+            _ => return None,
+        };
+        Some(command)
+    }
+}
+
+/// Key of a new CLI session, named after the time it was started.
+pub fn new_session_key() -> String {
+    format!("cli:{}", chrono::Local::now().format("%Y%m%d-%H%M%S"))
+}
+
+/// Run `fut` unless Ctrl-C is pressed first, in which case it is dropped
+/// and `None` returned.
+pub async fn interruptible<F: Future>(fut: F) -> Option<F::Output> {
+    tokio::select! {
+        output = fut => Some(output),
+        _ = tokio::signal::ctrl_c() => {
+            println!("\nInterrupted.\n");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn join(lines: &[&str]) -> (String, bool) {
+        let mut buffer = String::new();
+        let mut more = false;
+        for line in lines {
+            more = _continues(&mut buffer, line);
+        }
+        (buffer, more)
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(Command::parse("/help"), Some(Command::Help));
+        assert_eq!(
+            Command::parse("/model  gpt-4o "),
+            Some(Command::Model(Some("gpt-4o".to_string())))
+        );
+        assert_eq!(Command::parse("/model"), Some(Command::Model(None)));
+        assert_eq!(Command::parse("/history 3"), Some(Command::History(3)));
+        assert_eq!(
+            Command::parse("/history"),
+            Some(Command::History(DEFAULT_HISTORY_MESSAGES))
+        );
+        assert_eq!(Command::parse("/cost"), None);
+        assert_eq!(Command::parse("hello /new"), None);
+    }
+
+    #[test]
+    fn test_continued_lines() {
+        assert_eq!(join(&["hello"]), ("hello".to_string(), false));
+        assert_eq!(
+            join(&["first \\", "second"]),
+            ("first \nsecond".to_string(), false)
+        );
+        assert_eq!(
+            join(&["fix this: \\", "```", "let x = 1;"]),
+            ("fix this: \n```\nlet x = 1;\n".to_string(), true)
+        );
+        assert!(!join(&["```rust", "let x = 1;", "```"]).1);
+    }
+}
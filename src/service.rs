@@ -0,0 +1,264 @@
+//! Installing the gateway as a user service.
+//!
+//! `nanoclaw service install` writes a systemd user unit on Linux or a
+//! launchd agent on macOS that runs `nanoclaw gateway` from the current
+//! binary with the current home directory, so the same config is used.
+//! `enable`, `disable` and `status` pass through to `systemctl --user` or
+//! `launchctl`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+/// systemd unit name.
+const UNIT_NAME: &str = "nanoclaw.service";
+
+/// launchd label.
+const LAUNCHD_LABEL: &str = "com.nanoclaw.gateway";
+
+/// What `passthrough` asks the service manager to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Start now and at login.
+    Enable,
+    /// Stop and no longer start at login.
+    Disable,
+    Status,
+}
+
+/// The service managers nanoclaw can install into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceManager {
+    Systemd,
+    Launchd,
+}
+
+impl ServiceManager {
+    /// The service manager of this platform.
+    pub fn detect() -> Result<Self> {
+        if cfg!(target_os = "macos") {
+            Ok(Self::Launchd)
+        } else if cfg!(target_os = "linux") {
+            Ok(Self::Systemd)
+        } else {
+            bail!("services are only supported on Linux (systemd) and macOS (launchd)")
+        }
+    }
+
+    /// Where the service file goes, under `home`.
+    pub fn file_path(self, home: &Path) -> PathBuf {
+        match self {
+            Self::Systemd => home.join(".config/systemd/user").join(UNIT_NAME),
+            Self::Launchd => home
+                .join("Library/LaunchAgents")
+                .join(format!("{}.plist", LAUNCHD_LABEL)),
+        }
+    }
+
+    /// The service file running `exe gateway` with `home` as its home.
+    pub fn render(self, exe: &Path, home: &Path) -> String {
+        match self {
+            Self::Systemd => systemd_unit(exe, home),
+            Self::Launchd => launchd_plist(exe, home),
+        }
+    }
+
+    /// The command that does `action` for the service file at `path`.
+    fn command(self, action: Action, path: &Path) -> Command {
+        let mut command;
+        match self {
+            Self::Systemd => {
+                command = Command::new("systemctl");
+                command.arg("--user");
+                match action {
+                    Action::Enable => command.args(["enable", "--now", UNIT_NAME]),
+                    Action::Disable => command.args(["disable", "--now", UNIT_NAME]),
+                    Action::Status => command.args(["status", UNIT_NAME]),
+                };
+            }
+            Self::Launchd => {
+                command = Command::new("launchctl");
+                match action {
+                    Action::Enable => command.arg("load").arg("-w").arg(path),
+                    Action::Disable => command.arg("unload").arg("-w").arg(path),
+                    Action::Status => command.args(["list", LAUNCHD_LABEL]),
+                };
+            }
+        }
+        command
+    }
+}
+
+fn systemd_unit(exe: &Path, home: &Path) -> String {
+    format!(
+        "[Unit]\n\
+         Description=nanoclaw gateway\n\
+         After=network-online.target\n\
+         Wants=network-online.target\n\
+         \n\
+         [Service]\n\
+         ExecStart=\"{exe}\" gateway\n\
+         Environment=\"HOME={home}\"\n\
+         Restart=on-failure\n\
+         RestartSec=5\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n",
+        exe = exe.display(),
+        home = home.display(),
+    )
+}
+
+fn launchd_plist(exe: &Path, home: &Path) -> String {
+    let log = home.join(".nanoclaw/gateway.log");
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{exe}</string>
+        <string>gateway</string>
+    </array>
+    <key>EnvironmentVariables</key>
+    <dict>
+        <key>HOME</key>
+        <string>{home}</string>
+    </dict>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+    <key>StandardOutPath</key>
+    <string>{log}</string>
+    <key>StandardErrorPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#,
+        label = LAUNCHD_LABEL,
+        exe = xml_escape(&exe.display().to_string()),
+        home = xml_escape(&home.display().to_string()),
+        log = xml_escape(&log.display().to_string()),
+    )
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Write the service file for the current binary. Returns its path.
+pub fn install(manager: ServiceManager, home: &Path) -> Result<PathBuf> {
+    let exe = std::env::current_exe().context("cannot find the nanoclaw executable")?;
+    let path = manager.file_path(home);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, manager.render(&exe, home))
+        .with_context(|| format!("cannot write {}", path.display()))?;
+    if manager == ServiceManager::Systemd {
+        run(Command::new("systemctl").args(["--user", "daemon-reload"]))?;
+    }
+    Ok(path)
+}
+
+/// Stop the service and remove its file. Returns the removed path.
+pub fn uninstall(manager: ServiceManager, home: &Path) -> Result<PathBuf> {
+    let path = manager.file_path(home);
+    if !path.exists() {
+        bail!(
+            "the service is not installed ({} not found)",
+            path.display()
+        );
+    }
+    // Fails when the service is not enabled, which is fine here.
+    let _ = manager.command(Action::Disable, &path).status();
+    fs::remove_file(&path)?;
+    if manager == ServiceManager::Systemd {
+        run(Command::new("systemctl").args(["--user", "daemon-reload"]))?;
+    }
+    Ok(path)
+}
+
+/// Run `action` through the service manager, with its output going to the
+/// terminal.
+pub fn passthrough(manager: ServiceManager, home: &Path, action: Action) -> Result<()> {
+    let path = manager.file_path(home);
+    if !path.exists() {
+        bail!("the service is not installed; run `nanoclaw service install` first");
+    }
+    run(&mut manager.command(action, &path))
+}
+
+fn run(command: &mut Command) -> Result<()> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command
+        .status()
+        .with_context(|| format!("cannot run {}", program))?;
+    if !status.success() {
+        bail!("{} failed ({})", program, status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_systemd_unit() {
+        let unit = ServiceManager::Systemd
+            .render(Path::new("/opt/my tools/nanoclaw"), Path::new("/home/ada"));
+        assert!(unit.contains("ExecStart=\"/opt/my tools/nanoclaw\" gateway\n"));
+        assert!(unit.contains("Environment=\"HOME=/home/ada\"\n"));
+        assert!(unit.contains("WantedBy=default.target"));
+        assert_eq!(
+            ServiceManager::Systemd.file_path(Path::new("/home/ada")),
+            Path::new("/home/ada/.config/systemd/user/nanoclaw.service")
+        );
+    }
+
+    #[test]
+    fn test_launchd_plist() {
+        let plist = ServiceManager::Launchd.render(
+            Path::new("/Users/a&b/bin/nanoclaw"),
+            Path::new("/Users/a&b"),
+        );
+        assert!(plist.contains("<string>/Users/a&amp;b/bin/nanoclaw</string>"));
+        assert!(plist.contains("<string>/Users/a&amp;b/.nanoclaw/gateway.log</string>"));
+        assert!(plist.contains(&format!("<string>{}</string>", LAUNCHD_LABEL)));
+    }
+
+    #[test]
+    fn test_commands() {
+        let path = Path::new("/x/agent.plist");
+        let args = |manager: ServiceManager, action: Action| {
+            let command = manager.command(action, path);
+            let mut parts = vec![command.get_program().to_string_lossy().into_owned()];
+            parts.extend(command.get_args().map(|a| a.to_string_lossy().into_owned()));
+            parts.join(" ")
+        };
+        assert_eq!(
+            args(ServiceManager::Systemd, Action::Enable),
+            "systemctl --user enable --now nanoclaw.service"
+        );
+        assert_eq!(
+            args(ServiceManager::Launchd, Action::Disable),
+            "launchctl unload -w /x/agent.plist"
+        );
+        assert_eq!(
+            args(ServiceManager::Launchd, Action::Status),
+            "launchctl list com.nanoclaw.gateway"
+        );
+    }
+}
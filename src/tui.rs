@@ -0,0 +1,530 @@
+//! `nanoclaw tui`: the agent in a full-screen terminal interface.
+//!
+//! The screen shows the sessions on the left, the conversation in the
+//! middle and, on the right, what the agent is doing: the LLM and tool calls
+//! of each turn as they start and finish, and the subagents running. The
+//! agent loop runs in the background as in the gateway, so subagent results
+//! and messages the agent sends show up while you type.
+//!
+//! Enter sends, Tab moves between the input and the session list (where
+//! Enter opens a session and `n` starts a new one), PageUp and PageDown
+//! scroll the conversation and Ctrl-C quits.
+
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Position, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::Frame;
+use serde_json::json;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::agent::agent_loop::{AgentLoop, DirectRequest};
+use crate::agent::subagent::SubagentInfo;
+use crate::bus::events::{AgentEvent, InboundMessage, OutboundMessage};
+use crate::repl::new_session_key;
+use crate::session::manager::SessionManager;
+
+const CHANNEL: &str = "cli";
+const CHAT_ID: &str = "tui";
+
+/// Messages of a session loaded when it is opened.
+const MAX_LOADED_MESSAGES: usize = 200;
+
+/// Lines kept in the activity pane.
+const MAX_ACTIVITY: usize = 200;
+
+/// Lines PageUp and PageDown scroll by.
+const PAGE: u16 = 10;
+
+/// How often the subagent pane is refreshed.
+const REFRESH: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Input,
+    Sessions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Speaker {
+    User,
+    Agent,
+    /// Errors and notices from the interface itself.
+    Note,
+}
+
+/// What a key press asks for.
+#[derive(Debug, PartialEq)]
+enum Action {
+    None,
+    Send(String),
+    Open(String),
+    Quit,
+}
+
+/// The state shown on screen.
+struct App {
+    session_key: String,
+    sessions: Vec<String>,
+    selected: ListState,
+    focus: Focus,
+    conversation: Vec<(Speaker, String)>,
+    activity: VecDeque<String>,
+    subagents: Vec<SubagentInfo>,
+    input: String,
+    /// Lines scrolled up from the end of the conversation.
+    scroll: u16,
+    /// Waiting for the agent's reply.
+    busy: bool,
+}
+
+impl App {
+    fn new(session_key: &str) -> Self {
+        Self {
+            session_key: session_key.to_string(),
+            sessions: vec![session_key.to_string()],
+            selected: ListState::default().with_selected(Some(0)),
+            focus: Focus::Input,
+            conversation: Vec::new(),
+            activity: VecDeque::new(),
+            subagents: Vec::new(),
+            input: String::new(),
+            scroll: 0,
+            busy: false,
+        }
+    }
+
+    /// Show `session_key` with its saved messages, and refresh the list of
+    /// sessions.
+    fn open(&mut self, sessions: &mut SessionManager, session_key: &str) {
+        self.session_key = session_key.to_string();
+        self.conversation = sessions
+            .get_or_create(session_key)
+            .get_history(MAX_LOADED_MESSAGES)
+            .iter()
+            .filter_map(|m| {
+                let speaker = match m["role"].as_str()? {
+                    "user" => Speaker::User,
+                    "assistant" => Speaker::Agent,
+                    _ => return None,
+                };
+                let content = m["content"].as_str()?;
+                (!content.is_empty()).then(|| (speaker, content.to_string()))
+            })
+            .collect();
+        self.scroll = 0;
+        self.refresh_sessions(sessions);
+    }
+
+    /// Most recently updated first, with the open session always listed.
+    fn refresh_sessions(&mut self, sessions: &SessionManager) {
+        let mut listed = sessions.list_sessions();
+        listed.sort_by(|a, b| b["updated_at"].as_str().cmp(&a["updated_at"].as_str()));
+        self.sessions = listed
+            .iter()
+            .filter_map(|s| s["key"].as_str().map(str::to_string))
+            .collect();
+        if !self.sessions.contains(&self.session_key) {
+            self.sessions.insert(0, self.session_key.clone());
+        }
+        let open = self.sessions.iter().position(|k| *k == self.session_key);
+        self.selected.select(open);
+    }
+
+    fn say(&mut self, speaker: Speaker, text: impl Into<String>) {
+        self.conversation.push((speaker, text.into()));
+        self.scroll = 0;
+    }
+
+    fn on_key(&mut self, key: KeyEvent) -> Action {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('c') | KeyCode::Char('d') if ctrl => return Action::Quit,
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.focus = match self.focus {
+                    Focus::Input => Focus::Sessions,
+                    Focus::Sessions => Focus::Input,
+                };
+                return Action::None;
+            }
+            KeyCode::PageUp => {
+                self.scroll = self.scroll.saturating_add(PAGE);
+                return Action::None;
+            }
+            KeyCode::PageDown => {
+                self.scroll = self.scroll.saturating_sub(PAGE);
+                return Action::None;
+            }
+            _ => {}
+        }
+        match self.focus {
+            Focus::Input => match key.code {
+                KeyCode::Enter if !self.busy && !self.input.trim().is_empty() => {
+                    let text = std::mem::take(&mut self.input).trim().to_string();
+                    Action::Send(text)
+                }
+                KeyCode::Char(c) if !ctrl => {
+                    self.input.push(c);
+                    Action::None
+                }
+                KeyCode::Backspace => {
+                    self.input.pop();
+                    Action::None
+                }
+                KeyCode::Esc => {
+                    self.input.clear();
+                    Action::None
+                }
+                _ => Action::None,
+            },
+            Focus::Sessions => {
+                let last = self.sessions.len().saturating_sub(1);
+                let selected = self.selected.selected().unwrap_or(0);
+                match key.code {
+                    KeyCode::Up => self.selected.select(Some(selected.saturating_sub(1))),
+                    KeyCode::Down => self.selected.select(Some((selected + 1).min(last))),
+                    KeyCode::Enter if !self.busy => {
+                        if let Some(key) = self.sessions.get(selected) {
+                            self.focus = Focus::Input;
+                            return Action::Open(key.clone());
+                        }
+                    }
+                    KeyCode::Char('n') if !self.busy => {
+                        self.focus = Focus::Input;
+                        return Action::Open(new_session_key());
+                    }
+                    _ => {}
+                }
+                Action::None
+            }
+        }
+    }
+
+    fn on_agent_event(&mut self, event: &AgentEvent) {
+        let line = match event {
+            AgentEvent::TurnStarted { session_key, .. } => format!("● {}", session_key),
+            AgentEvent::LlmTokens {
+                model,
+                prompt_tokens,
+                completion_tokens,
+                duration_ms,
+                ..
+            } => format!(
+                "  {} {}+{} tokens, {:.1}s",
+                model,
+                prompt_tokens,
+                completion_tokens,
+                *duration_ms as f64 / 1000.0
+            ),
+            AgentEvent::ToolCallStarted { tool, .. } => format!("  ▶ {}", tool),
+            AgentEvent::ToolCallFinished {
+                tool,
+                result_bytes,
+                duration_ms,
+                ..
+            } => format!("  ✓ {} {} B, {} ms", tool, result_bytes, duration_ms),
+            AgentEvent::TurnCompleted {
+                failed,
+                iterations,
+                duration_ms,
+                ..
+            } => format!(
+                "{} {} iterations, {:.1}s",
+                if *failed { "✗" } else { "■" },
+                iterations,
+                *duration_ms as f64 / 1000.0
+            ),
+            AgentEvent::BudgetExceeded { message, .. } => format!("! {}", message),
+        };
+        self.activity.push_back(line);
+        while self.activity.len() > MAX_ACTIVITY {
+            self.activity.pop_front();
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Drawing
+// ---------------------------------------------------------------------------
+
+fn _draw(frame: &mut Frame, app: &mut App) {
+    let [body, input] =
+        Layout::vertical([Constraint::Min(5), Constraint::Length(3)]).areas(frame.area());
+    let [sessions, conversation, side] = Layout::horizontal([
+        Constraint::Length(28),
+        Constraint::Min(30),
+        Constraint::Length(40),
+    ])
+    .areas(body);
+    let [activity, subagents] =
+        Layout::vertical([Constraint::Min(5), Constraint::Length(8)]).areas(side);
+
+    _draw_sessions(frame, app, sessions);
+    _draw_conversation(frame, app, conversation);
+
+    let start = app
+        .activity
+        .len()
+        .saturating_sub(activity.height.saturating_sub(2) as usize);
+    let lines: Vec<Line> = app
+        .activity
+        .range(start..)
+        .map(|l| Line::raw(l.as_str()))
+        .collect();
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" Activity ")),
+        activity,
+    );
+
+    let items: Vec<ListItem> = app
+        .subagents
+        .iter()
+        .map(|s| ListItem::new(format!("{} {}", s.label, s.started_at.format("%H:%M:%S"))))
+        .collect();
+    let title = format!(" Subagents ({}) ", app.subagents.len());
+    frame.render_widget(
+        List::new(items).block(Block::bordered().title(title)),
+        subagents,
+    );
+
+    let title = if app.busy {
+        " Waiting for the agent… "
+    } else {
+        " Message (Enter sends, Tab: sessions, PgUp/PgDn scroll, Ctrl-C quits) "
+    };
+    let block = Block::bordered()
+        .title(title)
+        .border_style(_focused(app.focus == Focus::Input));
+    // Keep the end of a long input in view.
+    let width = input.width.saturating_sub(3) as usize;
+    let skip = app.input.chars().count().saturating_sub(width);
+    let visible: String = app.input.chars().skip(skip).collect();
+    let cursor_x = input.x + 1 + visible.chars().count() as u16;
+    frame.render_widget(Paragraph::new(visible).block(block), input);
+    if app.focus == Focus::Input {
+        frame.set_cursor_position(Position::new(cursor_x, input.y + 1));
+    }
+}
+
+fn _draw_sessions(frame: &mut Frame, app: &mut App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .sessions
+        .iter()
+        .map(|key| {
+            let style = if *key == app.session_key {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Span::styled(key.as_str(), style))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::bordered()
+                .title(" Sessions (n: new) ")
+                .border_style(_focused(app.focus == Focus::Sessions)),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, area, &mut app.selected);
+}
+
+fn _draw_conversation(frame: &mut Frame, app: &mut App, area: Rect) {
+    let mut text = Text::default();
+    for (speaker, content) in &app.conversation {
+        let (name, color) = match speaker {
+            Speaker::User => ("You", Color::Cyan),
+            Speaker::Agent => ("🐈 nanoclaw", Color::Green),
+            Speaker::Note => ("!", Color::Yellow),
+        };
+        text.push_line(Line::styled(
+            name,
+            Style::default().fg(color).add_modifier(Modifier::BOLD),
+        ));
+        for line in content.lines() {
+            text.push_line(Line::raw(line));
+        }
+        text.push_line(Line::default());
+    }
+
+    // Follow the end of the conversation unless scrolled up.
+    let width = area.width.saturating_sub(2).max(1) as usize;
+    let height = area.height.saturating_sub(2);
+    let total: usize = text
+        .lines
+        .iter()
+        .map(|line| line.width().div_ceil(width).max(1))
+        .sum();
+    let bottom = (total as u16).saturating_sub(height);
+    app.scroll = app.scroll.min(bottom);
+    let title = format!(" {} ", app.session_key);
+    frame.render_widget(
+        Paragraph::new(text)
+            .wrap(Wrap { trim: false })
+            .scroll((bottom - app.scroll, 0))
+            .block(Block::bordered().title(title)),
+        area,
+    );
+}
+
+fn _focused(focused: bool) -> Style {
+    if focused {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Running
+// ---------------------------------------------------------------------------
+
+/// Run the interface on `session_key` until the user quits. `agent_loop`
+/// is run in the background; `outbound_rx` receives what it sends on the
+/// bus.
+pub async fn run(
+    mut agent_loop: AgentLoop,
+    mut outbound_rx: UnboundedReceiver<OutboundMessage>,
+    workspace: &Path,
+    session_key: &str,
+) -> Result<()> {
+    let direct_tx = agent_loop.direct_sender();
+    let mut events = agent_loop.subscribe_events();
+    let subagents = agent_loop.subagents();
+    let agent = tokio::spawn(async move { agent_loop.run().await });
+
+    let mut sessions = SessionManager::new(workspace);
+    let mut app = App::new(session_key);
+    app.open(&mut sessions, session_key);
+
+    // Terminal input is read on its own thread, as crossterm blocks.
+    let (input_tx, mut input_rx) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if input_tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut terminal = ratatui::try_init()?;
+    let mut reply: Option<oneshot::Receiver<Result<String>>> = None;
+    let mut refresh = tokio::time::interval(REFRESH);
+    let result: Result<()> = async {
+        loop {
+            terminal.draw(|frame| _draw(frame, &mut app))?;
+            tokio::select! {
+                Some(input) = input_rx.recv() => {
+                    let Event::Key(key) = input else { continue };
+                    if key.kind != KeyEventKind::Press {
+                        continue;
+                    }
+                    match app.on_key(key) {
+                        Action::None => {}
+                        Action::Quit => return Ok(()),
+                        Action::Open(key) => app.open(&mut sessions, &key),
+                        Action::Send(text) => {
+                            let mut msg = InboundMessage::new(CHANNEL, "user", CHAT_ID, &text);
+                            msg.metadata
+                                .insert("session_key".to_string(), json!(app.session_key));
+                            let (reply_tx, reply_rx) = oneshot::channel();
+                            app.say(Speaker::User, text);
+                            if direct_tx.send(DirectRequest { msg, reply_tx, deltas: None }).is_err() {
+                                app.say(Speaker::Note, "The agent loop has stopped.");
+                                continue;
+                            }
+                            reply = Some(reply_rx);
+                            app.busy = true;
+                        }
+                    }
+                }
+                answer = async { reply.as_mut().expect("reply is pending").await },
+                    if reply.is_some() =>
+                {
+                    reply = None;
+                    app.busy = false;
+                    match answer {
+                        Ok(Ok(text)) if text.is_empty() => {}
+                        Ok(Ok(text)) => app.say(Speaker::Agent, text),
+                        Ok(Err(e)) => app.say(Speaker::Note, format!("Error: {}", e)),
+                        Err(_) => app.say(Speaker::Note, "The agent loop has stopped."),
+                    }
+                    app.refresh_sessions(&sessions);
+                }
+                Ok(event) = events.recv() => app.on_agent_event(&event),
+                Some(msg) = outbound_rx.recv() => {
+                    if msg.channel == CHANNEL && !msg.content.is_empty() {
+                        app.say(Speaker::Agent, msg.content);
+                    }
+                }
+                _ = refresh.tick() => app.subagents = subagents.list_running().await,
+            }
+        }
+    }
+    .await;
+
+    ratatui::restore();
+    agent.abort();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(app: &mut App, code: KeyCode) -> Action {
+        app.on_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut app = App::new("cli:default");
+        app.sessions.push("telegram:42".to_string());
+        for c in "hi ".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        assert_eq!(
+            press(&mut app, KeyCode::Enter),
+            Action::Send("hi".to_string())
+        );
+        assert!(app.input.is_empty());
+
+        app.busy = true;
+        press(&mut app, KeyCode::Char('x'));
+        assert_eq!(press(&mut app, KeyCode::Enter), Action::None);
+        assert_eq!(app.input, "x");
+        app.busy = false;
+
+        press(&mut app, KeyCode::Tab);
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Down);
+        assert_eq!(
+            press(&mut app, KeyCode::Enter),
+            Action::Open("telegram:42".to_string())
+        );
+        assert_eq!(app.focus, Focus::Input);
+
+        let quit = app.on_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL));
+        assert_eq!(quit, Action::Quit);
+    }
+
+    #[test]
+    fn test_activity() {
+        let mut app = App::new("cli:default");
+        app.on_agent_event(&AgentEvent::ToolCallFinished {
+            turn_id: "t1".to_string(),
+            call_id: "c1".to_string(),
+            tool: "exec".to_string(),
+            result_bytes: 120,
+            duration_ms: 35,
+        });
+        assert_eq!(app.activity.back().unwrap(), "  ✓ exec 120 B, 35 ms");
+    }
+}
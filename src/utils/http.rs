@@ -0,0 +1,31 @@
+//! The HTTP client shared across nanoclaw.
+//!
+//! A reqwest [`Client`] owns a connection pool, so using one for the LLM
+//! providers, tools, channels and exporters keeps connections (and TLS
+//! sessions) to the same hosts alive between calls instead of opening new
+//! ones every turn. Clones are cheap handles to the same pool. Types that
+//! make requests use [`shared_client`] unless given another with
+//! `with_client`.
+
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use reqwest::Client;
+
+/// How long an idle pooled connection is kept.
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+
+static CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+        .tcp_keepalive(TCP_KEEPALIVE)
+        .build()
+        .unwrap_or_default()
+});
+
+/// A handle to the process-wide HTTP client.
+pub fn shared_client() -> Client {
+    CLIENT.clone()
+}